@@ -41,7 +41,7 @@ use sc_consensus::{
 	BlockImport,
 };
 use sc_network::{config::SyncMode, NetworkService};
-use sc_network_sync::SyncingService;
+use sc_network_sync::{aux_schema::SyncProgressStore, SyncingService};
 use sc_network_transactions::TransactionsHandlerController;
 use sc_service::{Configuration, NetworkStarter, SpawnTaskHandle, TaskManager, WarpSyncParams};
 use sc_telemetry::{log, TelemetryWorkerHandle};
@@ -411,6 +411,7 @@ pub struct BuildNetworkParams<
 	pub spawn_handle: SpawnTaskHandle,
 	pub import_queue: IQ,
 	pub sybil_resistance_level: CollatorSybilResistance,
+	pub sync_progress_store: Option<Arc<dyn SyncProgressStore>>,
 }
 
 /// Build the network service, the network status sinks and an RPC sender.
@@ -425,6 +426,7 @@ pub async fn build_network<'a, Block, Client, RCInterface, IQ>(
 		relay_chain_interface,
 		import_queue,
 		sybil_resistance_level,
+		sync_progress_store,
 	}: BuildNetworkParams<'a, Block, Client, RCInterface, IQ>,
 ) -> sc_service::error::Result<(
 	Arc<NetworkService<Block, Block::Hash>>,
@@ -487,6 +489,7 @@ where
 		block_announce_validator_builder: Some(Box::new(move |_| block_announce_validator)),
 		warp_sync_params,
 		block_relay: None,
+		sync_progress_store,
 	})
 }
 
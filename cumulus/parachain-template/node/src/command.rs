@@ -377,6 +377,13 @@ impl CliConfiguration<Self> for RelayChainCli {
 		self.base.base.max_runtime_instances()
 	}
 
+	fn runtime_artifact_cache(
+		&self,
+		config_dir: &std::path::PathBuf,
+	) -> Result<Option<sc_service::config::RuntimeArtifactCacheConfig>> {
+		self.base.base.runtime_artifact_cache(config_dir)
+	}
+
 	fn announce_block(&self) -> Result<bool> {
 		self.base.base.announce_block()
 	}
@@ -97,6 +97,7 @@ pub fn new_partial(
 		.with_offchain_heap_alloc_strategy(heap_pages)
 		.with_max_runtime_instances(config.max_runtime_instances)
 		.with_runtime_cache_size(config.runtime_cache_size)
+		.with_artifact_cache(config.runtime_artifact_cache.clone())
 		.build();
 
 	let executor = ParachainExecutor::new_with_wasm_executor(wasm);
@@ -194,6 +195,7 @@ async fn start_node_impl(
 			relay_chain_interface: relay_chain_interface.clone(),
 			import_queue: params.import_queue,
 			sybil_resistance_level: CollatorSybilResistance::Resistant, // because of Aura
+			sync_progress_store: Some(client.clone()),
 		})
 		.await?;
 
@@ -213,6 +215,10 @@ async fn start_node_impl(
 				network_provider: network.clone(),
 				is_validator: parachain_config.role.is_authority(),
 				enable_http_requests: false,
+				http_mode: sc_offchain::HttpMode::new(
+					parachain_config.offchain_worker.http_record.as_deref(),
+					parachain_config.offchain_worker.http_replay.as_deref(),
+				)?,
 				custom_extensions: move |_| vec![],
 			})
 			.run(client.clone(), task_manager.spawn_handle())
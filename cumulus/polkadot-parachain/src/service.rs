@@ -224,6 +224,7 @@ where
 		.with_execution_method(config.wasm_method)
 		.with_max_runtime_instances(config.max_runtime_instances)
 		.with_runtime_cache_size(config.runtime_cache_size)
+		.with_artifact_cache(config.runtime_artifact_cache.clone())
 		.with_onchain_heap_alloc_strategy(heap_pages)
 		.with_offchain_heap_alloc_strategy(heap_pages)
 		.build();
@@ -362,6 +363,7 @@ where
 			relay_chain_interface: relay_chain_interface.clone(),
 			import_queue: params.import_queue,
 			sybil_resistance_level,
+			sync_progress_store: Some(client.clone()),
 		})
 		.await?;
 
@@ -540,6 +542,7 @@ where
 			relay_chain_interface: relay_chain_interface.clone(),
 			import_queue: params.import_queue,
 			sybil_resistance_level,
+			sync_progress_store: Some(client.clone()),
 		})
 		.await?;
 
@@ -735,6 +738,7 @@ where
 			relay_chain_interface: relay_chain_interface.clone(),
 			import_queue: params.import_queue,
 			sybil_resistance_level,
+			sync_progress_store: Some(client.clone()),
 		})
 		.await?;
 
@@ -1669,6 +1673,7 @@ where
 			relay_chain_interface: relay_chain_interface.clone(),
 			import_queue: params.import_queue,
 			sybil_resistance_level,
+			sync_progress_store: Some(client.clone()),
 		})
 		.await?;
 
@@ -209,6 +209,7 @@ pub fn new_partial(
 		.with_offchain_heap_alloc_strategy(heap_pages)
 		.with_max_runtime_instances(config.max_runtime_instances)
 		.with_runtime_cache_size(config.runtime_cache_size)
+		.with_artifact_cache(config.runtime_artifact_cache.clone())
 		.build();
 
 	let executor =
@@ -362,6 +363,7 @@ where
 			relay_chain_interface: relay_chain_interface.clone(),
 			import_queue: params.import_queue,
 			sybil_resistance_level: CollatorSybilResistance::Unresistant, // no consensus
+			sync_progress_store: Some(client.clone()),
 		})
 		.await?;
 
@@ -804,7 +806,12 @@ pub fn node_config(
 		prometheus_config: None,
 		telemetry_endpoints: None,
 		default_heap_pages: None,
-		offchain_worker: OffchainWorkerConfig { enabled: true, indexing_enabled: false },
+		offchain_worker: OffchainWorkerConfig {
+			enabled: true,
+			indexing_enabled: false,
+			http_record: None,
+			http_replay: None,
+		},
 		force_authoring: false,
 		disable_grandpa: false,
 		dev_key_seed: Some(key_seed),
@@ -817,6 +824,7 @@ pub fn node_config(
 		informant_output_format: Default::default(),
 		wasm_runtime_overrides: None,
 		runtime_cache_size: 2,
+		runtime_artifact_cache: None,
 	})
 }
 
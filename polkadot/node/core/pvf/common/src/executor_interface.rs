@@ -76,6 +76,8 @@ pub const DEFAULT_CONFIG: Config = Config {
 			logical_max: DEFAULT_LOGICAL_STACK_MAX,
 			native_stack_max: DEFAULT_NATIVE_STACK_MAX,
 		}),
+		// Execution time of PVFs is bounded by timeouts instead.
+		fuel_limit: None,
 		canonicalize_nans: true,
 		// Rationale for turning the multi-threaded compilation off is to make the preparation time
 		// easily reproducible and as deterministic as possible.
@@ -19,7 +19,7 @@
 use derive_more::Display;
 use polkadot_primitives::Hash;
 use sc_network::{
-	config::{NonDefaultSetConfig, SetConfig},
+	config::{BandwidthPriority, NonDefaultSetConfig, ProtocolBandwidth, SetConfig},
 	types::ProtocolName,
 	NotificationService,
 };
@@ -78,7 +78,7 @@ impl PeerSet {
 
 		match self {
 			PeerSet::Validation => {
-				let (config, notification_service) = NonDefaultSetConfig::new(
+				let (mut config, notification_service) = NonDefaultSetConfig::new(
 					protocol,
 					fallback_names,
 					max_notification_size,
@@ -95,6 +95,10 @@ impl PeerSet {
 					},
 				);
 
+				// Approvals and statements must not be delayed by block sync when the bandwidth
+				// is limited.
+				config.set_bandwidth(ProtocolBandwidth::with_priority(BandwidthPriority::High));
+
 				(config, (PeerSet::Validation, notification_service))
 			},
 			PeerSet::Collation => {
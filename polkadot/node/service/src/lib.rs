@@ -54,7 +54,7 @@ use {
 		peer_set::{PeerSet, PeerSetProtocolNames},
 		request_response::ReqProtocolNames,
 	},
-	sc_client_api::BlockBackend,
+	sc_client_api::{BlockBackend, BlockchainEvents},
 	sc_transaction_pool_api::OffchainTransactionPoolFactory,
 	sp_core::traits::SpawnNamed,
 };
@@ -435,6 +435,7 @@ fn new_partial_basics(
 		.with_offchain_heap_alloc_strategy(heap_pages)
 		.with_max_runtime_instances(config.max_runtime_instances)
 		.with_runtime_cache_size(config.runtime_cache_size)
+		.with_artifact_cache(config.runtime_artifact_cache.clone())
 		.build();
 
 	let (client, backend, keystore_container, task_manager) =
@@ -565,6 +566,12 @@ where
 		backend.clone(),
 		Some(shared_authority_set.clone()),
 	);
+	// hard forks are only needed to verify warp sync proofs, not to serve them
+	let warp_sync_provider = Arc::new(grandpa::warp_proof::NetworkProvider::new(
+		backend.clone(),
+		shared_authority_set.clone(),
+		Vec::new(),
+	));
 
 	let import_setup = (block_import, grandpa_link, babe_link, beefy_voter_links);
 	let rpc_setup = shared_voter_state.clone();
@@ -596,10 +603,12 @@ where
 					justification_stream: justification_stream.clone(),
 					subscription_executor: subscription_executor.clone(),
 					finality_provider: finality_proof_provider.clone(),
+					warp_sync_provider: warp_sync_provider.clone(),
 				},
 				beefy: polkadot_rpc::BeefyDeps {
 					beefy_finality_proof_stream: beefy_rpc_links.from_voter_justif_stream.clone(),
 					beefy_best_block_stream: beefy_rpc_links.from_voter_best_beefy_stream.clone(),
+					beefy_justif_requester: beefy_rpc_links.to_voter_justif_requester.clone(),
 					subscription_executor,
 				},
 				backend: backend.clone(),
@@ -820,8 +829,8 @@ pub fn new_full<OverseerGenerator: OverseerGen>(
 	let beefy_gossip_proto_name =
 		beefy::gossip_protocol_name(&genesis_hash, config.chain_spec.fork_id());
 	// `beefy_on_demand_justifications_handler` is given to `beefy-gadget` task to be run,
-	// while `beefy_req_resp_cfg` is added to `config.network.request_response_protocols`.
-	let (beefy_on_demand_justifications_handler, beefy_req_resp_cfg) =
+	// while `beefy_req_resp_cfgs` are added to `config.network.request_response_protocols`.
+	let (beefy_on_demand_justifications_handler, beefy_req_resp_cfgs) =
 		beefy::communication::request_response::BeefyJustifsRequestHandler::new(
 			&genesis_hash,
 			config.chain_spec.fork_id(),
@@ -835,7 +844,9 @@ pub fn new_full<OverseerGenerator: OverseerGen>(
 				beefy::communication::beefy_peers_set_config(beefy_gossip_proto_name.clone());
 
 			net_config.add_notification_protocol(beefy_notification_config);
-			net_config.add_request_response_protocol(beefy_req_resp_cfg);
+			for cfg in beefy_req_resp_cfgs {
+				net_config.add_request_response_protocol(cfg);
+			}
 			Some(beefy_notification_service)
 		},
 	};
@@ -899,6 +910,11 @@ pub fn new_full<OverseerGenerator: OverseerGen>(
 		import_setup.1.shared_authority_set().clone(),
 		grandpa_hard_forks,
 	));
+	task_manager.spawn_handle().spawn_blocking(
+		"grandpa-warp-sync-precompute",
+		Some("grandpa"),
+		warp_sync.clone().precompute_fragments(client.finality_notification_stream()),
+	);
 
 	let (network, system_rpc_tx, tx_handler_controller, network_starter, sync_service) =
 		service::build_network(service::BuildNetworkParams {
@@ -911,6 +927,7 @@ pub fn new_full<OverseerGenerator: OverseerGen>(
 			block_announce_validator_builder: None,
 			warp_sync_params: Some(WarpSyncParams::WithProvider(warp_sync)),
 			block_relay: None,
+			sync_progress_store: Some(client.clone()),
 		})?;
 
 	if config.offchain_worker.enabled {
@@ -929,6 +946,10 @@ pub fn new_full<OverseerGenerator: OverseerGen>(
 				network_provider: network.clone(),
 				is_validator: role.is_authority(),
 				enable_http_requests: false,
+				http_mode: sc_offchain::HttpMode::new(
+					config.offchain_worker.http_record.as_deref(),
+					config.offchain_worker.http_replay.as_deref(),
+				)?,
 				custom_extensions: move |_| vec![],
 			})
 			.run(client.clone(), task_manager.spawn_handle())
@@ -1201,15 +1222,22 @@ pub fn new_full<OverseerGenerator: OverseerGen>(
 	// beefy is enabled if its notification service exists
 	if let Some(notification_service) = beefy_notification_service {
 		let justifications_protocol_name = beefy_on_demand_justifications_handler.protocol_name();
+		let covering_justifications_protocol_name =
+			beefy_on_demand_justifications_handler.covering_protocol_name();
 		let network_params = beefy::BeefyNetworkParams {
 			network: network.clone(),
 			sync: sync_service.clone(),
 			gossip_protocol_name: beefy_gossip_proto_name,
 			justifications_protocol_name,
+			covering_justifications_protocol_name,
 			notification_service,
 			_phantom: core::marker::PhantomData::<Block>,
 		};
-		let payload_provider = beefy_primitives::mmr::MmrRootProvider::new(client.clone());
+		let mut payload_provider = beefy_primitives::mmr::MmrRootProvider::new(client.clone());
+		// offchain MMR nodes are needed to prove old blocks canonical in fork voting reports
+		if let Some(offchain_storage) = backend.offchain_storage() {
+			payload_provider = payload_provider.with_offchain_storage(offchain_storage);
+		}
 		let beefy_params = beefy::BeefyParams {
 			client: client.clone(),
 			backend: backend.clone(),
@@ -193,6 +193,7 @@ pub fn node_config(
 		tracing_receiver: Default::default(),
 		max_runtime_instances: 8,
 		runtime_cache_size: 2,
+		runtime_artifact_cache: None,
 		announce_block: true,
 		data_path: root,
 		base_path,
@@ -22,11 +22,14 @@ use std::sync::Arc;
 
 use jsonrpsee::RpcModule;
 use polkadot_primitives::{AccountId, Balance, Block, BlockNumber, Hash, Nonce};
-use sc_client_api::AuxStore;
-use sc_consensus_beefy::communication::notification::{
-	BeefyBestBlockStream, BeefyVersionedFinalityProofStream,
+use sc_client_api::{AuxStore, BlockBackend};
+use sc_consensus_beefy::communication::{
+	notification::{BeefyBestBlockStream, BeefyVersionedFinalityProofStream},
+	request_response::BeefyJustifsRequester,
+};
+use sc_consensus_grandpa::{
+	warp_proof::NetworkProvider as WarpSyncProvider, FinalityProofProvider,
 };
-use sc_consensus_grandpa::FinalityProofProvider;
 pub use sc_rpc::{DenyUnsafe, SubscriptionTaskExecutor};
 use sp_api::ProvideRuntimeApi;
 use sp_block_builder::BlockBuilder;
@@ -59,6 +62,8 @@ pub struct GrandpaDeps<B> {
 	pub subscription_executor: sc_rpc::SubscriptionTaskExecutor,
 	/// Finality proof provider.
 	pub finality_provider: Arc<FinalityProofProvider<B, Block>>,
+	/// Warp sync proof provider.
+	pub warp_sync_provider: Arc<WarpSyncProvider<Block, B>>,
 }
 
 /// Dependencies for BEEFY
@@ -67,6 +72,8 @@ pub struct BeefyDeps {
 	pub beefy_finality_proof_stream: BeefyVersionedFinalityProofStream<Block>,
 	/// Receives notifications about best block events from BEEFY.
 	pub beefy_best_block_stream: BeefyBestBlockStream<Block>,
+	/// Fetches BEEFY justifications from the network through the BEEFY voter.
+	pub beefy_justif_requester: BeefyJustifsRequester<Block>,
 	/// Executor to drive the subscription manager in the BEEFY RPC handler.
 	pub subscription_executor: sc_rpc::SubscriptionTaskExecutor,
 }
@@ -100,6 +107,7 @@ pub fn create_full<C, P, SC, B>(
 where
 	C: ProvideRuntimeApi<Block>
 		+ HeaderBackend<Block>
+		+ BlockBackend<Block>
 		+ AuxStore
 		+ HeaderMetadata<Block, Error = BlockChainError>
 		+ Send
@@ -119,8 +127,10 @@ where
 	use mmr_rpc::{Mmr, MmrApiServer};
 	use pallet_transaction_payment_rpc::{TransactionPayment, TransactionPaymentApiServer};
 	use sc_consensus_babe_rpc::{Babe, BabeApiServer};
-	use sc_consensus_beefy_rpc::{Beefy, BeefyApiServer};
-	use sc_consensus_grandpa_rpc::{Grandpa, GrandpaApiServer};
+	use sc_consensus_beefy_rpc::{Beefy, BeefyApiServer, BeefyProof, BeefyProofApiServer};
+	use sc_consensus_grandpa_rpc::{
+		Grandpa, GrandpaApiServer, GrandpaWarpSync, GrandpaWarpSyncApiServer,
+	};
 	use sc_sync_state_rpc::{SyncState, SyncStateApiServer};
 	use substrate_state_trie_migration_rpc::{StateMigration, StateMigrationApiServer};
 
@@ -132,6 +142,7 @@ where
 		justification_stream,
 		subscription_executor,
 		finality_provider,
+		warp_sync_provider,
 	} = grandpa;
 
 	io.merge(StateMigration::new(client.clone(), backend.clone(), deny_unsafe).into_rpc())?;
@@ -160,6 +171,18 @@ where
 		)
 		.into_rpc(),
 	)?;
+	io.merge(GrandpaWarpSync::<Block, _>::new(warp_sync_provider).into_rpc())?;
+	io.merge(
+		BeefyProof::<_, Block, _>::new(
+			client.clone(),
+			backend
+				.offchain_storage()
+				.ok_or("Backend doesn't provide the required offchain storage")?,
+			beefy.beefy_justif_requester,
+			deny_unsafe,
+		)
+		.into_rpc(),
+	)?;
 	io.merge(
 		SyncState::new(chain_spec, client, shared_authority_set, babe_worker_handle)?.into_rpc(),
 	)?;
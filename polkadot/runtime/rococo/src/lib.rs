@@ -1197,6 +1197,7 @@ impl pallet_beefy::Config for Runtime {
 	type KeyOwnerProof = <Historical as KeyOwnerProofSystem<(KeyTypeId, BeefyId)>>::Proof;
 	type EquivocationReportSystem =
 		pallet_beefy::EquivocationReportSystem<Self, Offences, Historical, ReportLongevity>;
+	type AncestryHelper = MmrLeaf;
 }
 
 /// MMR helper types.
@@ -1970,6 +1971,39 @@ sp_api::impl_runtime_apis! {
 			)
 		}
 
+		fn submit_report_fork_voting_unsigned_extrinsic(
+			fork_voting_proof: beefy_primitives::ForkVotingProof<
+				BlockNumber,
+				BeefyId,
+				BeefySignature,
+				Header,
+			>,
+			key_owner_proof: beefy_primitives::OpaqueKeyOwnershipProof,
+		) -> Option<()> {
+			let key_owner_proof = key_owner_proof.decode()?;
+
+			Beefy::submit_unsigned_fork_voting_report(
+				fork_voting_proof,
+				key_owner_proof,
+			)
+		}
+
+		fn submit_report_future_block_voting_unsigned_extrinsic(
+			future_block_voting_proof: beefy_primitives::FutureBlockVotingProof<
+				BlockNumber,
+				BeefyId,
+				BeefySignature,
+			>,
+			key_owner_proof: beefy_primitives::OpaqueKeyOwnershipProof,
+		) -> Option<()> {
+			let key_owner_proof = key_owner_proof.decode()?;
+
+			Beefy::submit_unsigned_future_block_voting_report(
+				future_block_voting_proof,
+				key_owner_proof,
+			)
+		}
+
 		fn generate_key_ownership_proof(
 			_set_id: beefy_primitives::ValidatorSetId,
 			authority_id: BeefyId,
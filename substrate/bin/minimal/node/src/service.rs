@@ -131,6 +131,7 @@ pub fn new_full(config: Configuration, consensus: Consensus) -> Result<TaskManag
 			block_announce_validator_builder: None,
 			warp_sync_params: None,
 			block_relay: None,
+			sync_progress_store: Some(client.clone()),
 		})?;
 
 	if config.offchain_worker.enabled {
@@ -147,6 +148,10 @@ pub fn new_full(config: Configuration, consensus: Consensus) -> Result<TaskManag
 				)),
 				network_provider: network.clone(),
 				enable_http_requests: true,
+				http_mode: sc_offchain::HttpMode::new(
+					config.offchain_worker.http_record.as_deref(),
+					config.offchain_worker.http_replay.as_deref(),
+				)?,
 				custom_extensions: |_| vec![],
 			})
 			.run(client.clone(), task_manager.spawn_handle())
@@ -166,6 +166,7 @@ pub fn new_full(config: Configuration) -> Result<TaskManager, ServiceError> {
 			block_announce_validator_builder: None,
 			warp_sync_params: Some(WarpSyncParams::WithProvider(warp_sync)),
 			block_relay: None,
+			sync_progress_store: Some(client.clone()),
 		})?;
 
 	if config.offchain_worker.enabled {
@@ -182,6 +183,10 @@ pub fn new_full(config: Configuration) -> Result<TaskManager, ServiceError> {
 				)),
 				network_provider: network.clone(),
 				enable_http_requests: true,
+				http_mode: sc_offchain::HttpMode::new(
+					config.offchain_worker.http_record.as_deref(),
+					config.offchain_worker.http_replay.as_deref(),
+				)?,
 				custom_extensions: |_| vec![],
 			})
 			.run(client.clone(), task_manager.spawn_handle())
@@ -394,12 +394,34 @@ pub fn testnet_genesis(
 	})
 }
 
+/// Adds the Sassafras authorities generated from `seeds` to the genesis patch.
+#[cfg(feature = "sassafras")]
+fn with_sassafras_authorities(mut genesis: serde_json::Value, seeds: &[&str]) -> serde_json::Value {
+	genesis["sassafras"] = serde_json::json!({
+		"authorities": seeds
+			.iter()
+			.map(|seed| get_from_seed::<sp_consensus_sassafras::AuthorityId>(seed))
+			.collect::<Vec<_>>(),
+		"epochConfig": kitchensink_runtime::SASSAFRAS_GENESIS_EPOCH_CONFIG,
+	});
+	genesis
+}
+
+/// Sassafras isn't enabled, the genesis patch is left untouched.
+#[cfg(not(feature = "sassafras"))]
+fn with_sassafras_authorities(genesis: serde_json::Value, _seeds: &[&str]) -> serde_json::Value {
+	genesis
+}
+
 fn development_config_genesis_json() -> serde_json::Value {
-	testnet_genesis(
-		vec![authority_keys_from_seed("Alice")],
-		vec![],
-		get_account_id_from_seed::<sr25519::Public>("Alice"),
-		None,
+	with_sassafras_authorities(
+		testnet_genesis(
+			vec![authority_keys_from_seed("Alice")],
+			vec![],
+			get_account_id_from_seed::<sr25519::Public>("Alice"),
+			None,
+		),
+		&["Alice"],
 	)
 }
 
@@ -414,11 +436,14 @@ pub fn development_config() -> ChainSpec {
 }
 
 fn local_testnet_genesis() -> serde_json::Value {
-	testnet_genesis(
-		vec![authority_keys_from_seed("Alice"), authority_keys_from_seed("Bob")],
-		vec![],
-		get_account_id_from_seed::<sr25519::Public>("Alice"),
-		None,
+	with_sassafras_authorities(
+		testnet_genesis(
+			vec![authority_keys_from_seed("Alice"), authority_keys_from_seed("Bob")],
+			vec![],
+			get_account_id_from_seed::<sr25519::Public>("Alice"),
+			None,
+		),
+		&["Alice", "Bob"],
 	)
 }
 
@@ -445,12 +470,7 @@ pub(crate) mod tests {
 			.with_name("Integration Test")
 			.with_id("test")
 			.with_chain_type(ChainType::Development)
-			.with_genesis_config_patch(testnet_genesis(
-				vec![authority_keys_from_seed("Alice")],
-				vec![],
-				get_account_id_from_seed::<sr25519::Public>("Alice"),
-				None,
-			))
+			.with_genesis_config_patch(development_config_genesis_json())
 			.build()
 	}
 
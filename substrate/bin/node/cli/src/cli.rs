@@ -94,9 +94,18 @@ pub enum Subcommand {
 	/// Import blocks.
 	ImportBlocks(sc_cli::ImportBlocksCmd),
 
+	/// Import a state snapshot into a fresh database.
+	ImportStateSnapshot(sc_cli::ImportStateSnapshotCmd),
+
+	/// Check the database for missing or corrupt entries and optionally repair it.
+	CheckDatabase(sc_cli::CheckDatabaseCmd),
+
 	/// Remove the whole chain.
 	PurgeChain(sc_cli::PurgeChainCmd),
 
+	/// Migrate the database to ParityDB.
+	MigrateDb(sc_cli::MigrateDbCmd),
+
 	/// Revert the chain to a previous state.
 	Revert(sc_cli::RevertCmd),
 
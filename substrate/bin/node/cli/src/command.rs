@@ -28,6 +28,7 @@ use node_primitives::Block;
 use sc_cli::{Result, SubstrateCli};
 use sc_service::PartialComponents;
 use sp_keyring::Sr25519Keyring;
+use sp_runtime::BuildStorage;
 
 use std::sync::Arc;
 
@@ -191,8 +192,23 @@ pub fn run() -> Result<()> {
 		Some(Subcommand::ExportState(cmd)) => {
 			let runner = cli.create_runner(cmd)?;
 			runner.async_run(|config| {
-				let PartialComponents { client, task_manager, .. } = new_partial(&config, None)?;
-				Ok((cmd.run(client, config.chain_spec), task_manager))
+				let PartialComponents { client, task_manager, backend, other, .. } =
+					new_partial(&config, None)?;
+				let (_, import_setup, _, _, _, _) = other;
+				let warp_sync = Arc::new(grandpa::warp_proof::NetworkProvider::new(
+					backend,
+					import_setup.1.shared_authority_set().clone(),
+					Vec::default(),
+				));
+				let chain_spec = config.chain_spec;
+				let task = async move {
+					if cmd.snapshot.is_some() {
+						cmd.run_snapshot(client, warp_sync).await
+					} else {
+						cmd.run(client, chain_spec).await
+					}
+				};
+				Ok((task, task_manager))
 			})
 		},
 		Some(Subcommand::ImportBlocks(cmd)) => {
@@ -203,23 +219,72 @@ pub fn run() -> Result<()> {
 				Ok((cmd.run(client, import_queue), task_manager))
 			})
 		},
+		Some(Subcommand::ImportStateSnapshot(cmd)) => {
+			let runner = cli.create_runner(cmd)?;
+			runner.async_run(|config| {
+				let PartialComponents {
+					client, task_manager, backend, import_queue, other, ..
+				} = new_partial(&config, None)?;
+				let (_, import_setup, _, _, _, _) = other;
+				let warp_sync = Arc::new(grandpa::warp_proof::NetworkProvider::new(
+					backend,
+					import_setup.1.shared_authority_set().clone(),
+					Vec::default(),
+				));
+				Ok((cmd.run(client, import_queue, warp_sync), task_manager))
+			})
+		},
 		Some(Subcommand::PurgeChain(cmd)) => {
 			let runner = cli.create_runner(cmd)?;
 			runner.sync_run(|config| cmd.run(config.database))
 		},
+		Some(Subcommand::MigrateDb(cmd)) => {
+			let runner = cli.create_runner(cmd)?;
+			runner.sync_run(|config| cmd.run::<Block>(&config.database))
+		},
 		Some(Subcommand::Revert(cmd)) => {
 			let runner = cli.create_runner(cmd)?;
 			runner.async_run(|config| {
 				let PartialComponents { client, task_manager, backend, .. } =
 					new_partial(&config, None)?;
 				let aux_revert = Box::new(|client: Arc<FullClient>, backend, blocks| {
+					#[cfg(not(feature = "sassafras"))]
 					sc_consensus_babe::revert(client.clone(), backend, blocks)?;
+					#[cfg(feature = "sassafras")]
+					sc_consensus_sassafras::revert(client.clone(), backend, blocks)?;
 					grandpa::revert(client, blocks)?;
 					Ok(())
 				});
 				Ok((cmd.run(client, backend, Some(aux_revert)), task_manager))
 			})
 		},
+		Some(Subcommand::CheckDatabase(cmd)) => {
+			let runner = cli.create_runner(cmd)?;
+			runner.sync_run(|config| {
+				let storage = config.chain_spec.as_storage_builder().build_storage()?;
+				let genesis_authorities = sp_io::TestExternalities::new(storage)
+					.execute_with(kitchensink_runtime::Grandpa::grandpa_authorities);
+				let mut verifier =
+					grandpa::JustificationVerifier::<Block>::new(genesis_authorities)?;
+				let verifier = Box::new(move |header: &_, justifications: Option<&_>| {
+					verifier.verify(header, justifications).map_err(|e| e.to_string())
+				});
+
+				let database = config.database.clone();
+				let blocks_pruning = config.blocks_pruning;
+				// The backend is only opened if blocks above the last finalized block are reverted.
+				let aux_revert = Box::new(move |blocks| {
+					let PartialComponents { client, backend, .. } = new_partial(&config, None)?;
+					#[cfg(not(feature = "sassafras"))]
+					sc_consensus_babe::revert(client.clone(), backend, blocks)?;
+					#[cfg(feature = "sassafras")]
+					sc_consensus_sassafras::revert(client.clone(), backend, blocks)?;
+					grandpa::revert(client, blocks)?;
+					Ok(())
+				});
+				cmd.run::<Block>(&database, blocks_pruning, Some(verifier), Some(aux_revert))
+			})
+		},
 		#[cfg(feature = "try-runtime")]
 		Some(Subcommand::TryRuntime) => Err(try_runtime_cli::DEPRECATION_NOTICE.into()),
 		#[cfg(not(feature = "try-runtime"))]
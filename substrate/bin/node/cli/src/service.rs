@@ -27,7 +27,7 @@ use frame_system_rpc_runtime_api::AccountNonceApi;
 use futures::prelude::*;
 use kitchensink_runtime::RuntimeApi;
 use node_primitives::Block;
-use sc_client_api::{Backend, BlockBackend};
+use sc_client_api::{Backend, BlockBackend, BlockchainEvents};
 use sc_consensus_babe::{self, SlotProportion};
 use sc_network::{event::Event, NetworkEventStream, NetworkService};
 use sc_network_sync::{warp::WarpSyncParams, SyncingService};
@@ -64,6 +64,22 @@ type FullSelectChain = sc_consensus::LongestChain<FullBackend, Block>;
 type FullGrandpaBlockImport =
 	grandpa::GrandpaBlockImport<FullBackend, Block, FullClient, FullSelectChain>;
 
+/// The block import of the block production protocol.
+#[cfg(not(feature = "sassafras"))]
+pub type FullBlockImport =
+	sc_consensus_babe::BabeBlockImport<Block, FullClient, FullGrandpaBlockImport>;
+/// The link between the block import and the authoring worker of the block production protocol.
+#[cfg(not(feature = "sassafras"))]
+pub type FullConsensusLink = sc_consensus_babe::BabeLink<Block>;
+
+/// The block import of the block production protocol.
+#[cfg(feature = "sassafras")]
+pub type FullBlockImport =
+	sc_consensus_sassafras::SassafrasBlockImport<Block, FullClient, FullGrandpaBlockImport>;
+/// The link between the block import and the authoring worker of the block production protocol.
+#[cfg(feature = "sassafras")]
+pub type FullConsensusLink = sc_consensus_sassafras::SassafrasLink<Block>;
+
 /// The transaction pool type definition.
 pub type TransactionPool = sc_transaction_pool::FullPool<Block, FullClient>;
 
@@ -165,9 +181,9 @@ pub fn new_partial(
 				sc_rpc::SubscriptionTaskExecutor,
 			) -> Result<jsonrpsee::RpcModule<()>, sc_service::Error>,
 			(
-				sc_consensus_babe::BabeBlockImport<Block, FullClient, FullGrandpaBlockImport>,
+				FullBlockImport,
 				grandpa::LinkHalf<Block, FullClient, FullSelectChain>,
-				sc_consensus_babe::BabeLink<Block>,
+				FullConsensusLink,
 			),
 			grandpa::SharedVoterState,
 			Option<Telemetry>,
@@ -222,38 +238,93 @@ pub fn new_partial(
 	)?;
 	let justification_import = grandpa_block_import.clone();
 
-	let (block_import, babe_link) = sc_consensus_babe::block_import(
-		sc_consensus_babe::configuration(&*client)?,
-		grandpa_block_import,
-		client.clone(),
-	)?;
+	#[cfg(not(feature = "sassafras"))]
+	let (block_import, consensus_link, import_queue, babe_worker_handle) = {
+		let (block_import, babe_link) = sc_consensus_babe::block_import(
+			sc_consensus_babe::configuration(&*client)?,
+			grandpa_block_import,
+			client.clone(),
+		)?;
 
-	let slot_duration = babe_link.config().slot_duration();
-	let (import_queue, babe_worker_handle) =
-		sc_consensus_babe::import_queue(sc_consensus_babe::ImportQueueParams {
-			link: babe_link.clone(),
-			block_import: block_import.clone(),
-			justification_import: Some(Box::new(justification_import)),
-			client: client.clone(),
-			select_chain: select_chain.clone(),
-			create_inherent_data_providers: move |_, ()| async move {
-				let timestamp = sp_timestamp::InherentDataProvider::from_system_time();
+		let slot_duration = babe_link.config().slot_duration();
+		let (import_queue, babe_worker_handle) =
+			sc_consensus_babe::import_queue(sc_consensus_babe::ImportQueueParams {
+				link: babe_link.clone(),
+				block_import: block_import.clone(),
+				justification_import: Some(Box::new(justification_import)),
+				client: client.clone(),
+				select_chain: select_chain.clone(),
+				create_inherent_data_providers: move |_, ()| async move {
+					let timestamp = sp_timestamp::InherentDataProvider::from_system_time();
 
-				let slot =
-				sp_consensus_babe::inherents::InherentDataProvider::from_timestamp_and_slot_duration(
-					*timestamp,
-					slot_duration,
-				);
+					let slot =
+					sp_consensus_babe::inherents::InherentDataProvider::from_timestamp_and_slot_duration(
+						*timestamp,
+						slot_duration,
+					);
 
-				Ok((slot, timestamp))
-			},
-			spawner: &task_manager.spawn_essential_handle(),
-			registry: config.prometheus_registry(),
-			telemetry: telemetry.as_ref().map(|x| x.handle()),
-			offchain_tx_pool_factory: OffchainTransactionPoolFactory::new(transaction_pool.clone()),
-		})?;
+					Ok((slot, timestamp))
+				},
+				spawner: &task_manager.spawn_essential_handle(),
+				registry: config.prometheus_registry(),
+				telemetry: telemetry.as_ref().map(|x| x.handle()),
+				offchain_tx_pool_factory: OffchainTransactionPoolFactory::new(
+					transaction_pool.clone(),
+				),
+			})?;
+
+		(block_import, babe_link, import_queue, Some(babe_worker_handle))
+	};
+
+	#[cfg(feature = "sassafras")]
+	let (block_import, consensus_link, import_queue, babe_worker_handle) = {
+		let (block_import, sassafras_link) = sc_consensus_sassafras::block_import(
+			sc_consensus_sassafras::configuration(
+				&*client,
+				sc_consensus_sassafras::SlotDuration::from_millis(
+					kitchensink_runtime::constants::time::SLOT_DURATION,
+				),
+			)?,
+			grandpa_block_import,
+			client.clone(),
+		)?;
+
+		let slot_duration = sassafras_link.config().slot_duration;
+		let import_queue =
+			sc_consensus_sassafras::import_queue(sc_consensus_sassafras::ImportQueueParams {
+				link: sassafras_link.clone(),
+				block_import: block_import.clone(),
+				justification_import: Some(Box::new(justification_import)),
+				client: client.clone(),
+				select_chain: select_chain.clone(),
+				create_inherent_data_providers: move |_, ()| async move {
+					let timestamp = sp_timestamp::InherentDataProvider::from_system_time();
+
+					let slot =
+					sp_consensus_babe::inherents::InherentDataProvider::from_timestamp_and_slot_duration(
+						*timestamp,
+						slot_duration,
+					);
+
+					Ok((slot, timestamp))
+				},
+				spawner: &task_manager.spawn_essential_handle(),
+				registry: config.prometheus_registry(),
+				telemetry: telemetry.as_ref().map(|x| x.handle()),
+				offchain_tx_pool_factory: OffchainTransactionPoolFactory::new(
+					transaction_pool.clone(),
+				),
+			})?;
+
+		(
+			block_import,
+			sassafras_link,
+			import_queue,
+			None::<sc_consensus_babe::BabeWorkerHandle<Block>>,
+		)
+	};
 
-	let import_setup = (block_import, grandpa_link, babe_link);
+	let import_setup = (block_import, grandpa_link, consensus_link);
 
 	let statement_store = sc_statement_store::Store::new_shared(
 		&config.data_path,
@@ -279,6 +350,11 @@ pub fn new_partial(
 			backend.clone(),
 			Some(shared_authority_set.clone()),
 		);
+		let warp_sync_provider = Arc::new(grandpa::warp_proof::NetworkProvider::new(
+			backend.clone(),
+			shared_authority_set.clone(),
+			Vec::default(),
+		));
 
 		let client = client.clone();
 		let pool = transaction_pool.clone();
@@ -295,16 +371,17 @@ pub fn new_partial(
 				select_chain: select_chain.clone(),
 				chain_spec: chain_spec.cloned_box(),
 				deny_unsafe,
-				babe: node_rpc::BabeDeps {
+				babe: babe_worker_handle.clone().map(|babe_worker_handle| node_rpc::BabeDeps {
 					keystore: keystore.clone(),
-					babe_worker_handle: babe_worker_handle.clone(),
-				},
+					babe_worker_handle,
+				}),
 				grandpa: node_rpc::GrandpaDeps {
 					shared_voter_state: shared_voter_state.clone(),
 					shared_authority_set: shared_authority_set.clone(),
 					justification_stream: justification_stream.clone(),
 					subscription_executor,
 					finality_provider: finality_proof_provider.clone(),
+					warp_sync_provider: warp_sync_provider.clone(),
 				},
 				statement_store: rpc_statement_store.clone(),
 				backend: rpc_backend.clone(),
@@ -357,10 +434,7 @@ pub fn new_full_base(
 	config: Configuration,
 	mixnet_config: Option<sc_mixnet::Config>,
 	disable_hardware_benchmarks: bool,
-	with_startup_data: impl FnOnce(
-		&sc_consensus_babe::BabeBlockImport<Block, FullClient, FullGrandpaBlockImport>,
-		&sc_consensus_babe::BabeLink<Block>,
-	),
+	with_startup_data: impl FnOnce(&FullBlockImport, &FullConsensusLink),
 ) -> Result<NewFullBase, ServiceError> {
 	let hwbench = (!disable_hardware_benchmarks)
 		.then_some(config.database.path().map(|database_path| {
@@ -412,6 +486,11 @@ pub fn new_full_base(
 		import_setup.1.shared_authority_set().clone(),
 		Vec::default(),
 	));
+	task_manager.spawn_handle().spawn_blocking(
+		"grandpa-warp-sync-precompute",
+		Some("grandpa"),
+		warp_sync.clone().precompute_fragments(client.finality_notification_stream()),
+	);
 
 	let (network, system_rpc_tx, tx_handler_controller, network_starter, sync_service) =
 		sc_service::build_network(sc_service::BuildNetworkParams {
@@ -424,6 +503,7 @@ pub fn new_full_base(
 			block_announce_validator_builder: None,
 			warp_sync_params: Some(WarpSyncParams::WithProvider(warp_sync)),
 			block_relay: None,
+			sync_progress_store: Some(client.clone()),
 		})?;
 
 	if let Some(mixnet_config) = mixnet_config {
@@ -438,6 +518,16 @@ pub fn new_full_base(
 			Some(keystore_container.keystore()),
 			mixnet_notification_service
 				.expect("`NotificationService` exists since mixnet was enabled; qed"),
+			sc_mixnet::RequestHandlers::new()
+				.with_handler(
+					sc_mixnet::READ_PROOF_PROTOCOL,
+					sc_mixnet::ReadProofHandler::<Block, _>::new(client.clone()),
+				)
+				.with_handler(
+					sc_mixnet::STATEMENT_BROADCASTS_PROTOCOL,
+					sc_mixnet::StatementBroadcastsHandler::new(statement_store.clone()),
+				),
+			config.prometheus_registry().cloned(),
 		);
 		task_manager.spawn_handle().spawn("mixnet", None, mixnet);
 	}
@@ -450,6 +540,20 @@ pub fn new_full_base(
 	let enable_grandpa = !config.disable_grandpa;
 	let prometheus_registry = config.prometheus_registry().cloned();
 	let enable_offchain_worker = config.offchain_worker.enabled;
+	let offchain_http_mode = sc_offchain::HttpMode::new(
+		config.offchain_worker.http_record.as_deref(),
+		config.offchain_worker.http_replay.as_deref(),
+	)?;
+
+	// Sassafras authorities aren't session keys, so the development key is inserted here.
+	#[cfg(feature = "sassafras")]
+	if let Some(seed) = &config.dev_key_seed {
+		use sp_keystore::Keystore;
+		keystore_container
+			.keystore()
+			.bandersnatch_generate_new(sp_consensus_sassafras::KEY_TYPE, Some(seed))
+			.map_err(|e| ServiceError::Other(format!("Sassafras keystore error: {:?}", e)))?;
+	}
 
 	let rpc_handlers = sc_service::spawn_tasks(sc_service::SpawnTasksParams {
 		config,
@@ -488,9 +592,9 @@ pub fn new_full_base(
 		}
 	}
 
-	let (block_import, grandpa_link, babe_link) = import_setup;
+	let (block_import, grandpa_link, consensus_link) = import_setup;
 
-	(with_startup_data)(&block_import, &babe_link);
+	(with_startup_data)(&block_import, &consensus_link);
 
 	if let sc_service::config::Role::Authority { .. } = &role {
 		let proposer = sc_basic_authorship::ProposerFactory::new(
@@ -502,49 +606,86 @@ pub fn new_full_base(
 		);
 
 		let client_clone = client.clone();
-		let slot_duration = babe_link.config().slot_duration();
-		let babe_config = sc_consensus_babe::BabeParams {
-			keystore: keystore_container.keystore(),
-			client: client.clone(),
-			select_chain,
-			env: proposer,
-			block_import,
-			sync_oracle: sync_service.clone(),
-			justification_sync_link: sync_service.clone(),
-			create_inherent_data_providers: move |parent, ()| {
-				let client_clone = client_clone.clone();
-				async move {
-					let timestamp = sp_timestamp::InherentDataProvider::from_system_time();
+		#[cfg(not(feature = "sassafras"))]
+		let slot_duration = consensus_link.config().slot_duration();
+		#[cfg(feature = "sassafras")]
+		let slot_duration = consensus_link.config().slot_duration;
+		let create_inherent_data_providers = move |parent, ()| {
+			let client_clone = client_clone.clone();
+			async move {
+				let timestamp = sp_timestamp::InherentDataProvider::from_system_time();
 
-					let slot =
-						sp_consensus_babe::inherents::InherentDataProvider::from_timestamp_and_slot_duration(
-							*timestamp,
-							slot_duration,
-						);
-
-					let storage_proof =
-						sp_transaction_storage_proof::registration::new_data_provider(
-							&*client_clone,
-							&parent,
-						)?;
-
-					Ok((slot, timestamp, storage_proof))
-				}
-			},
-			force_authoring,
-			backoff_authoring_blocks,
-			babe_link,
-			block_proposal_slot_portion: SlotProportion::new(0.5),
-			max_block_proposal_slot_portion: None,
-			telemetry: telemetry.as_ref().map(|x| x.handle()),
+				let slot =
+					sp_consensus_babe::inherents::InherentDataProvider::from_timestamp_and_slot_duration(
+						*timestamp,
+						slot_duration,
+					);
+
+				let storage_proof = sp_transaction_storage_proof::registration::new_data_provider(
+					&*client_clone,
+					&parent,
+				)?;
+
+				Ok((slot, timestamp, storage_proof))
+			}
 		};
 
-		let babe = sc_consensus_babe::start_babe(babe_config)?;
-		task_manager.spawn_essential_handle().spawn_blocking(
-			"babe-proposer",
-			Some("block-authoring"),
-			babe,
-		);
+		#[cfg(not(feature = "sassafras"))]
+		{
+			let babe_config = sc_consensus_babe::BabeParams {
+				keystore: keystore_container.keystore(),
+				client: client.clone(),
+				select_chain,
+				env: proposer,
+				block_import,
+				sync_oracle: sync_service.clone(),
+				justification_sync_link: sync_service.clone(),
+				create_inherent_data_providers,
+				force_authoring,
+				backoff_authoring_blocks,
+				babe_link: consensus_link,
+				block_proposal_slot_portion: SlotProportion::new(0.5),
+				max_block_proposal_slot_portion: None,
+				telemetry: telemetry.as_ref().map(|x| x.handle()),
+			};
+
+			let babe = sc_consensus_babe::start_babe(babe_config)?;
+			task_manager.spawn_essential_handle().spawn_blocking(
+				"babe-proposer",
+				Some("block-authoring"),
+				babe,
+			);
+		}
+
+		#[cfg(feature = "sassafras")]
+		{
+			let sassafras_params = sc_consensus_sassafras::SassafrasParams {
+				keystore: keystore_container.keystore(),
+				client: client.clone(),
+				select_chain,
+				env: proposer,
+				block_import,
+				sync_oracle: sync_service.clone(),
+				justification_sync_link: sync_service.clone(),
+				create_inherent_data_providers,
+				force_authoring,
+				backoff_authoring_blocks,
+				sassafras_link: consensus_link,
+				block_proposal_slot_portion: SlotProportion::new(0.5),
+				max_block_proposal_slot_portion: None,
+				telemetry: telemetry.as_ref().map(|x| x.handle()),
+				offchain_tx_pool_factory: OffchainTransactionPoolFactory::new(
+					transaction_pool.clone(),
+				),
+			};
+
+			let sassafras = sc_consensus_sassafras::start_sassafras(sassafras_params)?;
+			task_manager.spawn_essential_handle().spawn_blocking(
+				"sassafras-proposer",
+				Some("block-authoring"),
+				sassafras,
+			);
+		}
 	}
 
 	// Spawn authority discovery module.
@@ -657,6 +798,7 @@ pub fn new_full_base(
 				network_provider: network.clone(),
 				is_validator: role.is_authority(),
 				enable_http_requests: true,
+				http_mode: offchain_http_mode,
 				custom_extensions: move |_| {
 					vec![Box::new(statement_store.clone().as_statement_store_ext()) as Box<_>]
 				},
@@ -696,6 +838,9 @@ pub fn new_full(config: Configuration, cli: Cli) -> Result<TaskManager, ServiceE
 
 #[cfg(test)]
 mod tests {
+	// `test_sync` authors BABE blocks by hand and is skipped when Sassafras produces the blocks.
+	#![cfg_attr(feature = "sassafras", allow(dead_code, unused_imports))]
+
 	use crate::service::{new_full_base, NewFullBase};
 	use codec::Encode;
 	use kitchensink_runtime::{
@@ -730,6 +875,7 @@ mod tests {
 	// It is "ignored", but the node-cli ignored tests are running on the CI.
 	// This can be run locally with `cargo test --release -p node-cli test_sync -- --ignored`.
 	#[ignore]
+	#[cfg(not(feature = "sassafras"))]
 	fn test_sync() {
 		sp_tracing::try_init_simple();
 
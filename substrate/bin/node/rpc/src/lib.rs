@@ -38,7 +38,8 @@ use node_primitives::{AccountId, Balance, Block, BlockNumber, Hash, Nonce};
 use sc_client_api::AuxStore;
 use sc_consensus_babe::BabeWorkerHandle;
 use sc_consensus_grandpa::{
-	FinalityProofProvider, GrandpaJustificationStream, SharedAuthoritySet, SharedVoterState,
+	warp_proof::NetworkProvider as WarpSyncProvider, FinalityProofProvider,
+	GrandpaJustificationStream, SharedAuthoritySet, SharedVoterState,
 };
 use sc_rpc::SubscriptionTaskExecutor;
 pub use sc_rpc_api::DenyUnsafe;
@@ -70,6 +71,8 @@ pub struct GrandpaDeps<B> {
 	pub subscription_executor: SubscriptionTaskExecutor,
 	/// Finality proof provider.
 	pub finality_provider: Arc<FinalityProofProvider<B, Block>>,
+	/// Warp sync proof provider.
+	pub warp_sync_provider: Arc<WarpSyncProvider<Block, B>>,
 }
 
 /// Full client dependencies.
@@ -84,8 +87,8 @@ pub struct FullDeps<C, P, SC, B> {
 	pub chain_spec: Box<dyn sc_chain_spec::ChainSpec>,
 	/// Whether to deny unsafe calls
 	pub deny_unsafe: DenyUnsafe,
-	/// BABE specific dependencies.
-	pub babe: BabeDeps,
+	/// BABE specific dependencies, `None` if the node doesn't produce blocks with BABE.
+	pub babe: Option<BabeDeps>,
 	/// GRANDPA specific dependencies.
 	pub grandpa: GrandpaDeps<B>,
 	/// Shared statement store reference.
@@ -133,7 +136,9 @@ where
 	use mmr_rpc::{Mmr, MmrApiServer};
 	use pallet_transaction_payment_rpc::{TransactionPayment, TransactionPaymentApiServer};
 	use sc_consensus_babe_rpc::{Babe, BabeApiServer};
-	use sc_consensus_grandpa_rpc::{Grandpa, GrandpaApiServer};
+	use sc_consensus_grandpa_rpc::{
+		Grandpa, GrandpaApiServer, GrandpaWarpSync, GrandpaWarpSyncApiServer,
+	};
 	use sc_rpc::{
 		dev::{Dev, DevApiServer},
 		mixnet::MixnetApiServer,
@@ -146,13 +151,13 @@ where
 
 	let mut io = RpcModule::new(());
 
-	let BabeDeps { keystore, babe_worker_handle } = babe;
 	let GrandpaDeps {
 		shared_voter_state,
 		shared_authority_set,
 		justification_stream,
 		subscription_executor,
 		finality_provider,
+		warp_sync_provider,
 	} = grandpa;
 
 	let chain_name = chain_spec.name().to_string();
@@ -174,13 +179,9 @@ where
 		.into_rpc(),
 	)?;
 	io.merge(TransactionPayment::new(client.clone()).into_rpc())?;
-	io.merge(
-		Babe::new(client.clone(), babe_worker_handle.clone(), keystore, select_chain, deny_unsafe)
-			.into_rpc(),
-	)?;
 	io.merge(
 		Grandpa::new(
-			subscription_executor,
+			subscription_executor.clone(),
 			shared_authority_set.clone(),
 			shared_voter_state,
 			justification_stream,
@@ -188,20 +189,34 @@ where
 		)
 		.into_rpc(),
 	)?;
-
-	io.merge(
-		SyncState::new(chain_spec, client.clone(), shared_authority_set, babe_worker_handle)?
+	io.merge(GrandpaWarpSync::<Block, _>::new(warp_sync_provider).into_rpc())?;
+
+	if let Some(BabeDeps { keystore, babe_worker_handle }) = babe {
+		io.merge(
+			Babe::new(
+				client.clone(),
+				babe_worker_handle.clone(),
+				keystore,
+				select_chain,
+				deny_unsafe,
+			)
 			.into_rpc(),
-	)?;
+		)?;
+		io.merge(
+			SyncState::new(chain_spec, client.clone(), shared_authority_set, babe_worker_handle)?
+				.into_rpc(),
+		)?;
+	}
 
 	io.merge(StateMigration::new(client.clone(), backend, deny_unsafe).into_rpc())?;
 	io.merge(Dev::new(client, deny_unsafe).into_rpc())?;
 	let statement_store =
-		sc_rpc::statement::StatementStore::new(statement_store, deny_unsafe).into_rpc();
+		sc_rpc::statement::StatementStore::new(statement_store, deny_unsafe, subscription_executor)
+			.into_rpc();
 	io.merge(statement_store)?;
 
 	if let Some(mixnet_api) = mixnet_api {
-		let mixnet = sc_rpc::mixnet::Mixnet::new(mixnet_api).into_rpc();
+		let mixnet = MixnetApiServer::<Hash>::into_rpc(sc_rpc::mixnet::Mixnet::new(mixnet_api));
 		io.merge(mixnet)?;
 	}
 
@@ -162,6 +162,11 @@ pub const BABE_GENESIS_EPOCH_CONFIG: sp_consensus_babe::BabeEpochConfiguration =
 		allowed_slots: sp_consensus_babe::AllowedSlots::PrimaryAndSecondaryPlainSlots,
 	};
 
+/// The Sassafras epoch configuration at genesis.
+#[cfg(feature = "sassafras")]
+pub const SASSAFRAS_GENESIS_EPOCH_CONFIG: sp_consensus_sassafras::EpochConfiguration =
+	sp_consensus_sassafras::EpochConfiguration { redundancy_factor: 1, attempts_number: 32 };
+
 /// Native version.
 #[cfg(any(feature = "std", test))]
 pub fn native_version() -> NativeVersion {
@@ -490,6 +495,19 @@ impl pallet_babe::Config for Runtime {
 		pallet_babe::EquivocationReportSystem<Self, Offences, Historical, ReportLongevity>;
 }
 
+#[cfg(feature = "sassafras")]
+parameter_types! {
+	pub const SassafrasEpochLength: u32 = EPOCH_DURATION_IN_SLOTS as u32;
+}
+
+#[cfg(feature = "sassafras")]
+impl pallet_sassafras::Config for Runtime {
+	type EpochLength = SassafrasEpochLength;
+	type MaxAuthorities = MaxAuthorities;
+	type EpochChangeTrigger = pallet_sassafras::EpochChangeInternalTrigger;
+	type WeightInfo = ();
+}
+
 parameter_types! {
 	pub const IndexDeposit: Balance = 1 * DOLLARS;
 }
@@ -577,7 +595,11 @@ parameter_types! {
 
 impl pallet_timestamp::Config for Runtime {
 	type Moment = Moment;
+	#[cfg(not(feature = "sassafras"))]
 	type OnTimestampSet = Babe;
+	// BABE doesn't track the slot of blocks produced by Sassafras.
+	#[cfg(feature = "sassafras")]
+	type OnTimestampSet = ();
 	type MinimumPeriod = MinimumPeriod;
 	type WeightInfo = pallet_timestamp::weights::SubstrateWeight<Runtime>;
 }
@@ -2070,6 +2092,8 @@ construct_runtime!(
 		System: frame_system,
 		Utility: pallet_utility,
 		Babe: pallet_babe,
+		#[cfg(feature = "sassafras")]
+		Sassafras: pallet_sassafras,
 		Timestamp: pallet_timestamp,
 		// Authorship must be before session in order to note author in the correct session and era
 		// for im-online and staking.
@@ -2464,6 +2488,53 @@ impl_runtime_apis! {
 		}
 	}
 
+	#[cfg(feature = "sassafras")]
+	impl sp_consensus_sassafras::SassafrasApi<Block> for Runtime {
+		fn ring_context() -> Option<sp_consensus_sassafras::vrf::RingContext> {
+			Sassafras::ring_context()
+		}
+
+		fn submit_tickets_unsigned_extrinsic(
+			tickets: Vec<sp_consensus_sassafras::TicketEnvelope>,
+		) -> bool {
+			Sassafras::submit_tickets_unsigned_extrinsic(tickets)
+		}
+
+		fn slot_ticket_id(
+			slot: sp_consensus_sassafras::Slot,
+		) -> Option<sp_consensus_sassafras::TicketId> {
+			Sassafras::slot_ticket_id(slot)
+		}
+
+		fn slot_ticket(
+			slot: sp_consensus_sassafras::Slot,
+		) -> Option<(sp_consensus_sassafras::TicketId, sp_consensus_sassafras::TicketBody)> {
+			Sassafras::slot_ticket(slot)
+		}
+
+		fn current_epoch() -> sp_consensus_sassafras::Epoch {
+			Sassafras::current_epoch()
+		}
+
+		fn next_epoch() -> sp_consensus_sassafras::Epoch {
+			Sassafras::next_epoch()
+		}
+
+		fn generate_key_ownership_proof(
+			_authority_id: sp_consensus_sassafras::AuthorityId,
+		) -> Option<sp_consensus_sassafras::OpaqueKeyOwnershipProof> {
+			// Sassafras authorities are not session keys, equivocations are not reported yet.
+			None
+		}
+
+		fn submit_report_equivocation_unsigned_extrinsic(
+			_equivocation_proof: sp_consensus_sassafras::EquivocationProof<<Block as BlockT>::Header>,
+			_key_owner_proof: sp_consensus_sassafras::OpaqueKeyOwnershipProof,
+		) -> bool {
+			false
+		}
+	}
+
 	impl sp_authority_discovery::AuthorityDiscoveryApi<Block> for Runtime {
 		fn authorities() -> Vec<AuthorityDiscoveryId> {
 			AuthorityDiscovery::authorities()
@@ -66,6 +66,24 @@ pub trait ProofProvider<Block: BlockT> {
 		size_limit: usize,
 	) -> sp_blockchain::Result<(CompactProof, u32)>;
 
+	/// Given a `Hash` iterate over the storage values of the top trie, or of the child trie
+	/// `child_info`, whose keys start with `prefix`, starting at `start_key` inclusively.
+	/// Proof is build until size limit is reached and always include at least one key if any is
+	/// left.
+	/// Returns compact proof and the numbers of collected keys.
+	///
+	/// Fails by default, for providers that don't support range proofs.
+	fn read_range_proof(
+		&self,
+		_hash: Block::Hash,
+		_child_info: Option<&ChildInfo>,
+		_prefix: Option<&[u8]>,
+		_start_key: Option<&[u8]>,
+		_size_limit: usize,
+	) -> sp_blockchain::Result<(CompactProof, u32)> {
+		Err(sp_blockchain::Error::Backend("Range read proofs are not supported.".into()))
+	}
+
 	/// Given a `Hash` iterate over all storage values starting at `start_key`.
 	/// Returns collected keys and values.
 	/// Returns the collected keys values content of the top trie followed by the
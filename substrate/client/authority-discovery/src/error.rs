@@ -75,4 +75,7 @@ pub enum Error {
 
 	#[error("Unable to fetch best block.")]
 	BestBlockFetchingError,
+
+	#[error("Authority metadata of {0} bytes exceeds the maximum size.")]
+	MetadataTooLarge(usize),
 }
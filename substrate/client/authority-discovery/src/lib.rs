@@ -30,7 +30,9 @@
 pub use crate::{
 	error::Error,
 	service::Service,
-	worker::{AuthorityDiscovery, NetworkProvider, Role, Worker},
+	worker::{
+		AuthorityDiscovery, AuthorityMetadata, NetworkProvider, Role, Worker, MAX_METADATA_SIZE,
+	},
 };
 
 use std::{collections::HashSet, sync::Arc, time::Duration};
@@ -84,6 +86,19 @@ pub struct WorkerConfig {
 	///
 	/// Defaults to `false` to provide compatibility with old versions
 	pub strict_record_validation: bool,
+
+	/// Metadata to publish on the DHT along with the node's addresses. Can be changed later via
+	/// [`Service::set_local_metadata`]. Metadata larger than [`MAX_METADATA_SIZE`] is not
+	/// published.
+	///
+	/// Defaults to `None`.
+	pub metadata: Option<AuthorityMetadata>,
+
+	/// Minimum interval between re-publications triggered by changes of the local metadata.
+	/// Changes made more frequently are published with a delay.
+	///
+	/// By default this is set to 10 minutes.
+	pub min_metadata_republish_interval: Duration,
 }
 
 impl Default for WorkerConfig {
@@ -105,6 +120,8 @@ impl Default for WorkerConfig {
 			max_query_interval: Duration::from_secs(10 * 60),
 			publish_non_global_ips: true,
 			strict_record_validation: false,
+			metadata: None,
+			min_metadata_republish_interval: Duration::from_secs(10 * 60),
 		}
 	}
 }
@@ -167,4 +184,8 @@ pub(crate) enum ServicetoWorkerMsg {
 	GetAddressesByAuthorityId(AuthorityId, oneshot::Sender<Option<HashSet<Multiaddr>>>),
 	/// See [`Service::get_authority_ids_by_peer_id`].
 	GetAuthorityIdsByPeerId(PeerId, oneshot::Sender<Option<HashSet<AuthorityId>>>),
+	/// See [`Service::get_metadata_by_authority_id`].
+	GetMetadataByAuthorityId(AuthorityId, oneshot::Sender<Option<AuthorityMetadata>>),
+	/// See [`Service::set_local_metadata`].
+	SetLocalMetadata(Option<AuthorityMetadata>),
 }
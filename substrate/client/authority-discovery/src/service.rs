@@ -18,7 +18,7 @@
 
 use std::{collections::HashSet, fmt::Debug};
 
-use crate::ServicetoWorkerMsg;
+use crate::{error::Error, AuthorityMetadata, ServicetoWorkerMsg, MAX_METADATA_SIZE};
 
 use futures::{
 	channel::{mpsc, oneshot},
@@ -91,4 +91,45 @@ impl Service {
 
 		rx.await.ok().flatten()
 	}
+
+	/// Get the latest metadata published by the given [`AuthorityId`] from the local metadata
+	/// cache.
+	///
+	/// Returns `None` if no entry was present or connection to the
+	/// [`crate::Worker`] failed.
+	pub async fn get_metadata_by_authority_id(
+		&mut self,
+		authority: AuthorityId,
+	) -> Option<AuthorityMetadata> {
+		let (tx, rx) = oneshot::channel();
+
+		self.to_worker
+			.send(ServicetoWorkerMsg::GetMetadataByAuthorityId(authority, tx))
+			.await
+			.ok()?;
+
+		rx.await.ok().flatten()
+	}
+
+	/// Set the metadata published along with the local node's addresses, or stop publishing
+	/// metadata if `None`.
+	///
+	/// The change is published with the next publication of the local addresses, but not sooner
+	/// than [`crate::WorkerConfig::min_metadata_republish_interval`] after the previous
+	/// publication. Fails if the metadata exceeds [`MAX_METADATA_SIZE`]; failure to reach the
+	/// [`crate::Worker`] is ignored.
+	pub async fn set_local_metadata(
+		&mut self,
+		metadata: Option<AuthorityMetadata>,
+	) -> Result<(), Error> {
+		if let Some(size) = metadata.as_ref().map(AuthorityMetadata::encoded_size) {
+			if size > MAX_METADATA_SIZE {
+				return Err(Error::MetadataTooLarge(size))
+			}
+		}
+
+		let _ = self.to_worker.send(ServicetoWorkerMsg::SetLocalMetadata(metadata)).await;
+
+		Ok(())
+	}
 }
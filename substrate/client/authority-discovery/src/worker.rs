@@ -26,7 +26,7 @@ use std::{
 	collections::{HashMap, HashSet},
 	marker::PhantomData,
 	sync::Arc,
-	time::Duration,
+	time::{Duration, Instant},
 };
 
 use futures::{channel::mpsc, future, stream::Fuse, FutureExt, Stream, StreamExt};
@@ -35,6 +35,8 @@ use addr_cache::AddrCache;
 use codec::{Decode, Encode};
 use ip_network::IpNetwork;
 use libp2p::{core::multiaddr, identity::PublicKey, multihash::Multihash, Multiaddr, PeerId};
+use metadata::MetadataCache;
+pub use metadata::{AuthorityMetadata, MAX_METADATA_SIZE};
 use multihash_codetable::{Code, MultihashDigest};
 
 use log::{debug, error, log_enabled};
@@ -55,6 +57,7 @@ use sp_keystore::{Keystore, KeystorePtr};
 use sp_runtime::traits::Block as BlockT;
 
 mod addr_cache;
+mod metadata;
 /// Dht payload schemas generated from Protobuf definitions via Prost crate in build.rs.
 mod schema {
 	#[cfg(test)]
@@ -90,9 +93,10 @@ pub enum Role {
 ///
 ///    2. Get the list of keys owned by the local node participating in the current authority set.
 ///
-///    3. Sign the addresses with the keys.
+///    3. Sign the addresses, along with any configured [`AuthorityMetadata`], with the keys.
 ///
-///    4. Put addresses and signature as a record with the authority id as a key on a Kademlia DHT.
+///    4. Put addresses, metadata and signature as a record with the authority id as a key on a
+///    Kademlia DHT.
 ///
 /// When constructed with either [`Role::PublishAndDiscover`] or [`Role::Discover`] a [`Worker`]
 /// will
@@ -106,7 +110,7 @@ pub enum Role {
 ///    4. Add the retrieved external addresses as priority nodes to the
 ///    network peerset.
 ///
-///    5. Allow querying of the collected addresses via the [`crate::Service`].
+///    5. Allow querying of the collected addresses and metadata via the [`crate::Service`].
 pub struct Worker<Client, Network, Block, DhtEventStream> {
 	/// Channel receiver for messages send by a [`crate::Service`].
 	from_service: Fuse<mpsc::Receiver<ServicetoWorkerMsg>>,
@@ -131,6 +135,15 @@ pub struct Worker<Client, Network, Block, DhtEventStream> {
 	/// Same value as in the configuration.
 	strict_record_validation: bool,
 
+	/// Metadata to publish along with our own addresses.
+	local_metadata: Option<AuthorityMetadata>,
+	/// Whether `local_metadata` has changed since our addresses were last published.
+	local_metadata_changed: bool,
+	/// Same value as in the configuration.
+	min_metadata_republish_interval: Duration,
+	/// When our addresses were last published, if ever.
+	last_publication: Option<Instant>,
+
 	/// Interval at which to request addresses of authorities, refilling the pending lookups queue.
 	query_interval: ExpIncInterval,
 
@@ -141,6 +154,8 @@ pub struct Worker<Client, Network, Block, DhtEventStream> {
 
 	addr_cache: addr_cache::AddrCache,
 
+	metadata_cache: MetadataCache,
+
 	metrics: Option<Metrics>,
 
 	role: Role,
@@ -213,6 +228,16 @@ where
 
 		let addr_cache = AddrCache::new();
 
+		let local_metadata = config.metadata.filter(|metadata| {
+			let size = metadata.encoded_size();
+			if size > MAX_METADATA_SIZE {
+				error!(target: LOG_TARGET, "Not publishing metadata: {}", Error::MetadataTooLarge(size));
+				false
+			} else {
+				true
+			}
+		});
+
 		let metrics = match prometheus_registry {
 			Some(registry) => match Metrics::register(&registry) {
 				Ok(metrics) => Some(metrics),
@@ -234,10 +259,15 @@ where
 			latest_published_keys: HashSet::new(),
 			publish_non_global_ips: config.publish_non_global_ips,
 			strict_record_validation: config.strict_record_validation,
+			local_metadata,
+			local_metadata_changed: false,
+			min_metadata_republish_interval: config.min_metadata_republish_interval,
+			last_publication: None,
 			query_interval,
 			pending_lookups: Vec::new(),
 			in_flight_lookups: HashMap::new(),
 			addr_cache,
+			metadata_cache: MetadataCache::new(),
 			role,
 			metrics,
 			phantom: PhantomData,
@@ -289,7 +319,7 @@ where
 		}
 	}
 
-	fn process_message_from_service(&self, msg: ServicetoWorkerMsg) {
+	fn process_message_from_service(&mut self, msg: ServicetoWorkerMsg) {
 		match msg {
 			ServicetoWorkerMsg::GetAddressesByAuthorityId(authority, sender) => {
 				let _ = sender.send(
@@ -300,9 +330,27 @@ where
 				let _ = sender
 					.send(self.addr_cache.get_authority_ids_by_peer_id(&peer_id).map(Clone::clone));
 			},
+			ServicetoWorkerMsg::GetMetadataByAuthorityId(authority, sender) => {
+				let _ = sender.send(
+					self.metadata_cache.get_metadata_by_authority_id(&authority).map(Clone::clone),
+				);
+			},
+			ServicetoWorkerMsg::SetLocalMetadata(metadata) =>
+				if metadata != self.local_metadata {
+					self.local_metadata = metadata;
+					self.local_metadata_changed = true;
+				},
 		}
 	}
 
+	/// Whether a change of the local metadata should be published now. Changes are published at
+	/// most once every `min_metadata_republish_interval`.
+	fn local_metadata_republish_due(&self) -> bool {
+		self.local_metadata_changed &&
+			self.last_publication
+				.map_or(true, |last| last.elapsed() >= self.min_metadata_republish_interval)
+	}
+
 	fn addresses_to_publish(&self) -> impl Iterator<Item = Multiaddr> {
 		let peer_id: Multihash = self.network.local_peer_id().into();
 		let publish_non_global_ips = self.publish_non_global_ips;
@@ -334,7 +382,8 @@ where
 	/// Publish own public addresses.
 	///
 	/// If `only_if_changed` is true, the function has no effect if the list of keys to publish
-	/// is equal to `self.latest_published_keys`.
+	/// is equal to `self.latest_published_keys` and no change of the local metadata is due to be
+	/// published.
 	async fn publish_ext_addresses(&mut self, only_if_changed: bool) -> Result<()> {
 		let key_store = match &self.role {
 			Role::PublishAndDiscover(key_store) => key_store,
@@ -346,7 +395,10 @@ where
 			self.client.as_ref(),
 		).await?.into_iter().collect::<HashSet<_>>();
 
-		if only_if_changed && keys == self.latest_published_keys {
+		if only_if_changed &&
+			keys == self.latest_published_keys &&
+			!self.local_metadata_republish_due()
+		{
 			return Ok(())
 		}
 
@@ -359,7 +411,8 @@ where
 				.set(addresses.len().try_into().unwrap_or(std::u64::MAX));
 		}
 
-		let serialized_record = serialize_authority_record(addresses)?;
+		let serialized_record =
+			serialize_authority_record(addresses, self.local_metadata.clone().map(Into::into))?;
 		let peer_signature = sign_record_with_peer_id(&serialized_record, self.network.as_ref())?;
 
 		let keys_vec = keys.iter().cloned().collect::<Vec<_>>();
@@ -376,6 +429,8 @@ where
 		}
 
 		self.latest_published_keys = keys;
+		self.local_metadata_changed = false;
+		self.last_publication = Some(Instant::now());
 
 		Ok(())
 	}
@@ -401,6 +456,8 @@ where
 			.collect::<Vec<_>>();
 
 		self.addr_cache.retain_ids(&authorities);
+		self.metadata_cache.retain_ids(&authorities);
+		self.update_known_metadata_count();
 
 		authorities.shuffle(&mut thread_rng());
 		self.pending_lookups = authorities;
@@ -506,7 +563,7 @@ where
 
 		let local_peer_id = self.network.local_peer_id();
 
-		let remote_addresses: Vec<Multiaddr> = values
+		let records = values
 			.into_iter()
 			.map(|(_k, v)| {
 				let schema::SignedAuthorityRecord { record, auth_signature, peer_signature } =
@@ -520,9 +577,11 @@ where
 					return Err(Error::VerifyingDhtPayload)
 				}
 
-				let addresses: Vec<Multiaddr> = schema::AuthorityRecord::decode(record.as_slice())
-					.map(|a| a.addresses)
-					.map_err(Error::DecodingProto)?
+				let schema::AuthorityRecord { addresses, metadata } =
+					schema::AuthorityRecord::decode(record.as_slice())
+						.map_err(Error::DecodingProto)?;
+
+				let addresses: Vec<Multiaddr> = addresses
 					.into_iter()
 					.map(|a| a.try_into())
 					.collect::<std::result::Result<_, _>>()
@@ -564,14 +623,46 @@ where
 						"Received unsigned authority discovery record from {}", authority_id
 					);
 				}
-				Ok(addresses)
+				Ok((addresses, metadata))
 			})
-			.collect::<Result<Vec<Vec<Multiaddr>>>>()?
+			.collect::<Result<Vec<(Vec<Multiaddr>, Option<schema::AuthorityMetadata>)>>>()?;
+		let (remote_addresses, remote_metadata): (Vec<_>, Vec<_>) = records.into_iter().unzip();
+
+		let remote_addresses: Vec<Multiaddr> = remote_addresses
 			.into_iter()
 			.flatten()
 			.take(MAX_ADDRESSES_PER_AUTHORITY)
 			.collect();
 
+		// Different DHT nodes may hold different versions of the record; use the latest metadata.
+		// If none of them has any, the authority no longer publishes metadata.
+		let remote_metadata = remote_metadata
+			.into_iter()
+			.flatten()
+			.filter(|metadata| {
+				let size = metadata.encoded_len();
+				if size > MAX_METADATA_SIZE {
+					debug!(
+						target: LOG_TARGET,
+						"Ignoring metadata of authority {}: {}",
+						authority_id,
+						Error::MetadataTooLarge(size),
+					);
+					false
+				} else {
+					true
+				}
+			})
+			.max_by_key(|metadata| metadata.version);
+
+		match remote_metadata {
+			Some(metadata) => {
+				self.metadata_cache.insert(authority_id.clone(), metadata.into());
+			},
+			None => self.metadata_cache.remove(&authority_id),
+		}
+		self.update_known_metadata_count();
+
 		if !remote_addresses.is_empty() {
 			self.addr_cache.insert(authority_id, remote_addresses);
 			if let Some(metrics) = &self.metrics {
@@ -583,6 +674,14 @@ where
 		Ok(())
 	}
 
+	fn update_known_metadata_count(&self) {
+		if let Some(metrics) = &self.metrics {
+			metrics
+				.known_metadata_count
+				.set(self.metadata_cache.num_authority_ids().try_into().unwrap_or(std::u64::MAX));
+		}
+	}
+
 	/// Retrieve our public keys within the current and next authority set.
 	// A node might have multiple authority discovery keys within its keystore, e.g. an old one and
 	// one for the upcoming session. In addition it could be participating in the current and (/ or)
@@ -643,9 +742,12 @@ fn serialize_addresses(addresses: impl Iterator<Item = Multiaddr>) -> Vec<Vec<u8
 	addresses.map(|a| a.to_vec()).collect()
 }
 
-fn serialize_authority_record(addresses: Vec<Vec<u8>>) -> Result<Vec<u8>> {
+fn serialize_authority_record(
+	addresses: Vec<Vec<u8>>,
+	metadata: Option<schema::AuthorityMetadata>,
+) -> Result<Vec<u8>> {
 	let mut serialized_record = vec![];
-	schema::AuthorityRecord { addresses }
+	schema::AuthorityRecord { addresses, metadata }
 		.encode(&mut serialized_record)
 		.map_err(Error::EncodingProto)?;
 	Ok(serialized_record)
@@ -705,6 +807,7 @@ pub(crate) struct Metrics {
 	dht_event_received: CounterVec<U64>,
 	handle_value_found_event_failure: Counter<U64>,
 	known_authorities_count: Gauge<U64>,
+	known_metadata_count: Gauge<U64>,
 }
 
 impl Metrics {
@@ -764,6 +867,13 @@ impl Metrics {
 				)?,
 				registry,
 			)?,
+			known_metadata_count: register(
+				Gauge::new(
+					"substrate_authority_discovery_known_metadata_count",
+					"Number of authorities whose metadata is known by authority discovery.",
+				)?,
+				registry,
+			)?,
 		})
 	}
 }
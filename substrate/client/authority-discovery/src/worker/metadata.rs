@@ -0,0 +1,171 @@
+// This file is part of Substrate.
+
+// Copyright (C) Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use super::schema;
+use prost::Message;
+use sp_authority_discovery::AuthorityId;
+use std::collections::HashMap;
+
+/// Maximum size of the encoded metadata of a single authority. Larger metadata is neither
+/// published nor accepted from the DHT.
+pub const MAX_METADATA_SIZE: usize = 1024;
+
+/// Additional information an authority can publish on the DHT along with its addresses.
+///
+/// The metadata is signed with the authority key together with the addresses. It is purely
+/// informational; nothing in authority discovery depends on its contents.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AuthorityMetadata {
+	/// Version of the metadata. Should be increased whenever the metadata changes; metadata with
+	/// a lower version than already known for an authority is ignored.
+	pub version: u64,
+	/// Name and version of the node implementation.
+	pub node_version: Option<String>,
+	/// Names of protocols supported by the node.
+	pub protocols: Vec<String>,
+	/// Contact or identity hints as key-value pairs, e.g. `("email", "ops@example.com")`.
+	pub hints: Vec<(String, String)>,
+}
+
+impl AuthorityMetadata {
+	/// Size of the metadata when encoded on the wire. Must not exceed [`MAX_METADATA_SIZE`].
+	pub fn encoded_size(&self) -> usize {
+		schema::AuthorityMetadata::from(self.clone()).encoded_len()
+	}
+}
+
+impl From<AuthorityMetadata> for schema::AuthorityMetadata {
+	fn from(metadata: AuthorityMetadata) -> Self {
+		Self {
+			version: metadata.version,
+			node_version: metadata.node_version.unwrap_or_default(),
+			protocols: metadata.protocols,
+			hints: metadata
+				.hints
+				.into_iter()
+				.map(|(key, value)| schema::MetadataHint { key, value })
+				.collect(),
+		}
+	}
+}
+
+impl From<schema::AuthorityMetadata> for AuthorityMetadata {
+	fn from(metadata: schema::AuthorityMetadata) -> Self {
+		Self {
+			version: metadata.version,
+			node_version: (!metadata.node_version.is_empty()).then_some(metadata.node_version),
+			protocols: metadata.protocols,
+			hints: metadata.hints.into_iter().map(|hint| (hint.key, hint.value)).collect(),
+		}
+	}
+}
+
+/// Cache of the latest known [`AuthorityMetadata`] of each authority.
+pub(super) struct MetadataCache {
+	authority_id_to_metadata: HashMap<AuthorityId, AuthorityMetadata>,
+}
+
+impl MetadataCache {
+	pub fn new() -> Self {
+		MetadataCache { authority_id_to_metadata: HashMap::new() }
+	}
+
+	/// Inserts the given metadata for `authority_id`, unless metadata with a higher version is
+	/// already known. Returns `true` if the metadata was inserted.
+	pub fn insert(&mut self, authority_id: AuthorityId, metadata: AuthorityMetadata) -> bool {
+		match self.authority_id_to_metadata.get(&authority_id) {
+			Some(known) if known.version > metadata.version => false,
+			_ => {
+				self.authority_id_to_metadata.insert(authority_id, metadata);
+				true
+			},
+		}
+	}
+
+	/// Removes the metadata of `authority_id`, if any.
+	pub fn remove(&mut self, authority_id: &AuthorityId) {
+		self.authority_id_to_metadata.remove(authority_id);
+	}
+
+	/// Returns the number of authority IDs in the cache.
+	pub fn num_authority_ids(&self) -> usize {
+		self.authority_id_to_metadata.len()
+	}
+
+	/// Returns the metadata for the given [`AuthorityId`].
+	pub fn get_metadata_by_authority_id(
+		&self,
+		authority_id: &AuthorityId,
+	) -> Option<&AuthorityMetadata> {
+		self.authority_id_to_metadata.get(authority_id)
+	}
+
+	/// Removes all [`AuthorityMetadata`]s of [`AuthorityId`]s that are not in the given set.
+	pub fn retain_ids(&mut self, authority_ids: &[AuthorityId]) {
+		self.authority_id_to_metadata
+			.retain(|authority_id, _| authority_ids.contains(authority_id));
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_authority_discovery::AuthorityPair;
+	use sp_core::crypto::Pair;
+
+	fn metadata(version: u64) -> AuthorityMetadata {
+		AuthorityMetadata {
+			version,
+			node_version: Some(format!("test-node/{version}")),
+			protocols: vec!["/foo/1".into()],
+			hints: vec![("email".into(), "ops@example.com".into())],
+		}
+	}
+
+	#[test]
+	fn roundtrips_through_schema() {
+		let metadata = metadata(1);
+		let encoded = schema::AuthorityMetadata::from(metadata.clone()).encode_to_vec();
+		assert_eq!(encoded.len(), metadata.encoded_size());
+		let decoded = schema::AuthorityMetadata::decode(encoded.as_slice()).unwrap();
+		assert_eq!(AuthorityMetadata::from(decoded), metadata);
+
+		let empty = AuthorityMetadata::default();
+		let decoded = schema::AuthorityMetadata::from(empty.clone());
+		assert_eq!(AuthorityMetadata::from(decoded), empty);
+	}
+
+	#[test]
+	fn older_versions_are_ignored() {
+		let authority: AuthorityId = AuthorityPair::generate().0.public();
+		let mut cache = MetadataCache::new();
+
+		assert!(cache.insert(authority.clone(), metadata(2)));
+		assert!(!cache.insert(authority.clone(), metadata(1)));
+		assert_eq!(cache.get_metadata_by_authority_id(&authority), Some(&metadata(2)));
+		assert!(cache.insert(authority.clone(), metadata(3)));
+		assert_eq!(cache.get_metadata_by_authority_id(&authority), Some(&metadata(3)));
+
+		cache.remove(&authority);
+		assert_eq!(cache.get_metadata_by_authority_id(&authority), None);
+		assert!(cache.insert(authority.clone(), metadata(1)));
+
+		cache.retain_ids(&[]);
+		assert_eq!(cache.num_authority_ids(), 0);
+	}
+}
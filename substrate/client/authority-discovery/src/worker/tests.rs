@@ -215,7 +215,7 @@ fn build_dht_event<Signer: NetworkSigner>(
 	network: Option<&Signer>,
 ) -> Vec<(KademliaKey, Vec<u8>)> {
 	let serialized_record =
-		serialize_authority_record(serialize_addresses(addresses.into_iter())).unwrap();
+		serialize_authority_record(serialize_addresses(addresses.into_iter()), None).unwrap();
 
 	let peer_signature = network.map(|n| sign_record_with_peer_id(&serialized_record, n).unwrap());
 	let kv_pairs = sign_record_with_authority_ids(
@@ -362,6 +362,97 @@ fn publish_discover_cycle() {
 	pool.run();
 }
 
+#[test]
+fn publish_and_resolve_metadata() {
+	let metadata = |version| AuthorityMetadata {
+		version,
+		node_version: Some("test-node/1.0.0".into()),
+		protocols: vec!["/test/1".into()],
+		hints: vec![("email".into(), "ops@example.com".into())],
+	};
+
+	// Node A publishing its addresses and metadata.
+	let (_dht_event_tx, dht_event_rx) = channel(1000);
+	let network: Arc<TestNetwork> = Arc::new(Default::default());
+	let key_store = MemoryKeystore::new();
+	let node_a_public =
+		key_store.sr25519_generate_new(key_types::AUTHORITY_DISCOVERY, None).unwrap();
+	let test_api = Arc::new(TestApi { authorities: vec![node_a_public.into()] });
+
+	let (_to_worker, from_service) = mpsc::channel(0);
+	let mut node_a_worker = Worker::new(
+		from_service,
+		test_api.clone(),
+		network.clone(),
+		Box::pin(dht_event_rx),
+		Role::PublishAndDiscover(key_store.into()),
+		None,
+		WorkerConfig {
+			metadata: Some(metadata(1)),
+			min_metadata_republish_interval: Duration::ZERO,
+			..Default::default()
+		},
+	);
+
+	block_on(node_a_worker.publish_ext_addresses(false)).unwrap();
+	assert_eq!(network.put_value_call.lock().unwrap().len(), 1);
+
+	// Setting the same metadata again does not trigger a new publication.
+	node_a_worker
+		.process_message_from_service(ServicetoWorkerMsg::SetLocalMetadata(Some(metadata(1))));
+	block_on(node_a_worker.publish_ext_addresses(true)).unwrap();
+	assert_eq!(network.put_value_call.lock().unwrap().len(), 1);
+
+	// Changed metadata does.
+	node_a_worker
+		.process_message_from_service(ServicetoWorkerMsg::SetLocalMetadata(Some(metadata(2))));
+	block_on(node_a_worker.publish_ext_addresses(true)).unwrap();
+	assert_eq!(network.put_value_call.lock().unwrap().len(), 2);
+
+	// Unless the previous publication was too recent.
+	node_a_worker.min_metadata_republish_interval = Duration::from_secs(60 * 60);
+	node_a_worker
+		.process_message_from_service(ServicetoWorkerMsg::SetLocalMetadata(Some(metadata(3))));
+	block_on(node_a_worker.publish_ext_addresses(true)).unwrap();
+	assert_eq!(network.put_value_call.lock().unwrap().len(), 2);
+
+	// Node B resolving node A's metadata, from both the old and the new record.
+	let values = network.put_value_call.lock().unwrap().clone();
+	let (_dht_event_tx, dht_event_rx) = channel(1000);
+	let (_to_worker, from_service) = mpsc::channel(0);
+	let mut worker = Worker::new(
+		from_service,
+		test_api,
+		Arc::new(TestNetwork::default()),
+		Box::pin(dht_event_rx),
+		Role::Discover,
+		None,
+		Default::default(),
+	);
+
+	block_on(worker.refill_pending_lookups_queue()).unwrap();
+	worker.start_new_lookups();
+	worker.handle_dht_value_found_event(values).unwrap();
+
+	assert_eq!(
+		worker.metadata_cache.get_metadata_by_authority_id(&node_a_public.into()),
+		Some(&metadata(2)),
+	);
+
+	// Node A stops publishing metadata.
+	node_a_worker.min_metadata_republish_interval = Duration::ZERO;
+	node_a_worker.process_message_from_service(ServicetoWorkerMsg::SetLocalMetadata(None));
+	block_on(node_a_worker.publish_ext_addresses(true)).unwrap();
+	let values = network.put_value_call.lock().unwrap()[2..].to_vec();
+
+	// Node B forgets node A's metadata once the new record is found.
+	block_on(worker.refill_pending_lookups_queue()).unwrap();
+	worker.start_new_lookups();
+	worker.handle_dht_value_found_event(values).unwrap();
+
+	assert_eq!(worker.metadata_cache.get_metadata_by_authority_id(&node_a_public.into()), None);
+}
+
 /// Don't terminate when sender side of service channel is dropped. Terminate when network event
 /// stream terminates.
 #[test]
@@ -19,11 +19,14 @@
 use crate::{CliConfiguration, DatabaseParams, PruningParams, Result as CliResult, SharedParams};
 use parity_scale_codec::{Decode, Encode};
 use sc_client_api::{backend::Backend as BackendT, blockchain::HeaderBackend};
+use sc_service::sync_progress::{self, SyncProgressStore};
 use sp_blockchain::Info;
 use sp_runtime::traits::{Block as BlockT, Header as HeaderT};
 use std::{fmt::Debug, io};
 
 /// The `chain-info` subcommand used to output db meta columns information.
+///
+/// Also reports the progress of an interrupted warp or state sync.
 #[derive(Debug, Clone, clap::Parser)]
 pub struct ChainInfoCmd {
 	#[allow(missing_docs)]
@@ -52,6 +55,52 @@ struct ChainInfo<B: BlockT> {
 	finalized_hash: B::Hash,
 	/// Last finalized block number.
 	finalized_number: <<B as BlockT>::Header as HeaderT>::Number,
+	/// Progress of an interrupted warp or state sync, if any.
+	#[serde(
+		skip_serializing_if = "Option::is_none",
+		bound(serialize = "SyncProgress<B>: serde::Serialize")
+	)]
+	sync_progress: Option<SyncProgress<B>>,
+}
+
+/// Persisted progress of an interrupted warp or state sync.
+#[derive(Clone, Eq, PartialEq, Debug, Encode, Decode, serde::Serialize)]
+struct SyncProgress<B: BlockT> {
+	/// Authority set id of the last verified warp proof.
+	warp_proof_set_id: Option<u64>,
+	/// Block the next warp proof is requested from.
+	warp_proof_last_hash: Option<B::Hash>,
+	/// Total warp proof bytes downloaded.
+	warp_proof_bytes: Option<u64>,
+	/// Block whose state is downloaded.
+	state_target_hash: Option<B::Hash>,
+	/// Number of the block whose state is downloaded.
+	state_target_number: Option<<<B as BlockT>::Header as HeaderT>::Number>,
+	/// Estimated state download percentage.
+	state_percentage: Option<u32>,
+	/// Total state bytes downloaded.
+	state_bytes: Option<u64>,
+}
+
+impl<B: BlockT> SyncProgress<B> {
+	/// Load the persisted sync progress from `store`.
+	fn load(store: &dyn SyncProgressStore) -> CliResult<Option<Self>> {
+		let warp = sync_progress::load_warp_proof_progress::<B>(store)?;
+		let state = sync_progress::load_state_sync_progress::<B>(store)?;
+		if warp.is_none() && state.is_none() {
+			return Ok(None)
+		}
+
+		Ok(Some(SyncProgress {
+			warp_proof_set_id: warp.as_ref().map(|w| w.set_id),
+			warp_proof_last_hash: warp.as_ref().map(|w| w.last_hash),
+			warp_proof_bytes: warp.as_ref().map(|w| w.total_proof_bytes),
+			state_target_hash: state.as_ref().map(|s| s.target_header.hash()),
+			state_target_number: state.as_ref().map(|s| *s.target_header.number()),
+			state_percentage: state.as_ref().map(|s| s.percentage),
+			state_bytes: state.as_ref().map(|s| s.imported_bytes),
+		}))
+	}
 }
 
 impl<B: BlockT> From<Info<B>> for ChainInfo<B> {
@@ -62,6 +111,7 @@ impl<B: BlockT> From<Info<B>> for ChainInfo<B> {
 			genesis_hash: info.genesis_hash,
 			finalized_hash: info.finalized_hash,
 			finalized_number: info.finalized_number,
+			sync_progress: None,
 		}
 	}
 }
@@ -79,7 +129,8 @@ impl ChainInfoCmd {
 			blocks_pruning: config.blocks_pruning,
 		};
 		let backend = sc_service::new_db_backend::<B>(db_config)?;
-		let info: ChainInfo<B> = backend.blockchain().info().into();
+		let mut info: ChainInfo<B> = backend.blockchain().info().into();
+		info.sync_progress = SyncProgress::load(&*backend)?;
 		let mut out = io::stdout();
 		serde_json::to_writer_pretty(&mut out, &info)
 			.map_err(|e| format!("Error writing JSON: {}", e))?;
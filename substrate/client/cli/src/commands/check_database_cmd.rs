@@ -0,0 +1,144 @@
+// This file is part of Substrate.
+
+// Copyright (C) Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use crate::{
+	error,
+	params::{DatabaseParams, GenericNumber, PruningParams, SharedParams},
+	CliConfiguration,
+};
+use clap::Parser;
+use sc_client_db::{check_database, repair_database, JustificationVerifier};
+use sc_service::{BlocksPruning, DatabaseSource};
+use sp_runtime::traits::{Block as BlockT, Header as HeaderT, NumberFor, One};
+use std::{fmt::Debug, str::FromStr};
+
+/// The `check-database` command used to find and repair damaged database entries.
+#[derive(Debug, Parser)]
+pub struct CheckDatabaseCmd {
+	/// Number of the first block to check.
+	#[arg(long, value_name = "NUMBER", default_value = "0")]
+	pub from: GenericNumber,
+
+	/// Number of the last block to check. Defaults to the best block.
+	#[arg(long, value_name = "NUMBER")]
+	pub to: Option<GenericNumber>,
+
+	/// Traverse the state trie of every checked block whose state is kept.
+	///
+	/// This reads the whole state of every block and can take a long time on archive nodes.
+	#[arg(long)]
+	pub check_state: bool,
+
+	/// Repair the damaged blocks that were found.
+	///
+	/// Damaged finalized blocks are removed and downloaded from peers again once the node is
+	/// restarted. Damaged blocks above the last finalized block are reverted together with all
+	/// blocks above them.
+	#[arg(long)]
+	pub repair: bool,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub shared_params: SharedParams,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub pruning_params: PruningParams,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub database_params: DatabaseParams,
+}
+
+impl CheckDatabaseCmd {
+	/// Run the check-database command
+	///
+	/// The database is opened directly, so that a database the node refuses to start with can be
+	/// checked as well. `verifier` verifies the justifications of the checked blocks and
+	/// `aux_revert` reverts the consensus data of blocks above the last finalized block that are
+	/// removed by the repair.
+	pub fn run<B>(
+		&self,
+		database: &DatabaseSource,
+		blocks_pruning: BlocksPruning,
+		verifier: Option<JustificationVerifier<B>>,
+		aux_revert: Option<Box<dyn FnOnce(NumberFor<B>) -> error::Result<()>>>,
+	) -> error::Result<()>
+	where
+		B: BlockT,
+		<<<B as BlockT>::Header as HeaderT>::Number as FromStr>::Err: Debug,
+	{
+		let from = self.from.parse()?;
+		let to = self.to.as_ref().map(|to| to.parse()).transpose()?;
+
+		let report =
+			check_database::<B>(database, blocks_pruning, from, to, self.check_state, verifier)?;
+		println!(
+			"Checked {} blocks and {} state trie nodes, found {} problems.",
+			report.blocks,
+			report.state_nodes,
+			report.issues.len(),
+		);
+		for issue in &report.issues {
+			println!("{}", issue);
+		}
+
+		if report.issues.is_empty() {
+			return Ok(())
+		}
+		if !self.repair {
+			return Err(error::Error::Input(
+				"Database is damaged, run with `--repair` to repair the damaged blocks".into(),
+			))
+		}
+
+		if let (Some(first), Some(aux_revert)) = (report.first_damaged_unfinalized(), aux_revert) {
+			let blocks = report.best_number - first + One::one();
+			if let Err(e) = aux_revert(blocks) {
+				eprintln!("Failed to revert the consensus data of the damaged blocks: {}", e);
+			}
+		}
+
+		let summary = repair_database(database, &report)?;
+		if let Some((start, end)) = summary.gap {
+			println!(
+				"Removed finalized blocks #{}..=#{}, they are downloaded again on the next start.",
+				start, end,
+			);
+		}
+		if summary.reverted > 0 {
+			println!("Reverted {} blocks above the last finalized block.", summary.reverted);
+		}
+
+		Ok(())
+	}
+}
+
+impl CliConfiguration for CheckDatabaseCmd {
+	fn shared_params(&self) -> &SharedParams {
+		&self.shared_params
+	}
+
+	fn pruning_params(&self) -> Option<&PruningParams> {
+		Some(&self.pruning_params)
+	}
+
+	fn database_params(&self) -> Option<&DatabaseParams> {
+		Some(&self.database_params)
+	}
+}
@@ -23,9 +23,10 @@ use crate::{
 };
 use clap::Parser;
 use log::info;
-use sc_client_api::{HeaderBackend, StorageProvider, UsageProvider};
+use sc_client_api::{BlockBackend, HeaderBackend, StorageProvider, UsageProvider};
+use sc_service::WarpSyncProvider;
 use sp_runtime::traits::{Block as BlockT, Header as HeaderT};
-use std::{fmt::Debug, io::Write, str::FromStr, sync::Arc};
+use std::{fmt::Debug, fs, io::Write, path::PathBuf, str::FromStr, sync::Arc};
 
 /// The `export-state` command used to export the state of a given block into
 /// a chain spec.
@@ -35,6 +36,13 @@ pub struct ExportStateCmd {
 	#[arg(value_name = "HASH or NUMBER")]
 	pub input: Option<BlockNumberOrHash>,
 
+	/// Export a state snapshot into the given file instead of a chain spec.
+	///
+	/// The snapshot contains the state of the latest finalized block together with the block
+	/// and a proof of its finality, and can be imported with `import-state-snapshot`.
+	#[arg(long, value_name = "PATH", conflicts_with = "input")]
+	pub snapshot: Option<PathBuf>,
+
 	#[allow(missing_docs)]
 	#[clap(flatten)]
 	pub shared_params: SharedParams,
@@ -62,6 +70,10 @@ impl ExportStateCmd {
 		<B::Hash as FromStr>::Err: Debug,
 		<<B::Header as HeaderT>::Number as FromStr>::Err: Debug,
 	{
+		if self.snapshot.is_some() {
+			return Err("State snapshots are not supported by this node".into())
+		}
+
 		info!("Exporting raw state...");
 		let block_id = self.input.as_ref().map(|b| b.parse()).transpose()?;
 		let hash = match block_id {
@@ -78,6 +90,28 @@ impl ExportStateCmd {
 		}
 		Ok(())
 	}
+
+	/// Run the `export-state` command with `--snapshot`.
+	///
+	/// `warp_sync_provider` generates the proof of finality of the snapshot block.
+	pub async fn run_snapshot<B, BA, C>(
+		&self,
+		client: Arc<C>,
+		warp_sync_provider: Arc<dyn WarpSyncProvider<B>>,
+	) -> error::Result<()>
+	where
+		B: BlockT,
+		C: UsageProvider<B> + StorageProvider<B, BA> + BlockBackend<B> + HeaderBackend<B>,
+		BA: sc_client_api::backend::Backend<B>,
+	{
+		let path = self.snapshot.as_ref().ok_or("Missing `--snapshot` output file")?;
+		info!("Exporting state snapshot to {}...", path.display());
+		let file = std::io::BufWriter::new(fs::File::create(path)?);
+		let number =
+			sc_service::chain_ops::export_state_snapshot(client, &*warp_sync_provider, file)?;
+		info!("Exported state snapshot of block #{}", number);
+		Ok(())
+	}
 }
 
 impl CliConfiguration for ExportStateCmd {
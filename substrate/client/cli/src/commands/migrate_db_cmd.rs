@@ -0,0 +1,96 @@
+// This file is part of Substrate.
+
+// Copyright (C) Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use crate::{
+	arg_enums::Database,
+	error,
+	params::{DatabaseParams, SharedParams},
+	CliConfiguration,
+};
+use clap::Parser;
+use sc_service::DatabaseSource;
+use sp_runtime::traits::Block as BlockT;
+use std::{fmt::Debug, path::PathBuf};
+
+/// The `migrate-db` command used to copy a RocksDB database into a new ParityDB database, or a
+/// ParityDB database into a new RocksDB database.
+///
+/// The source database is left untouched. Once the migration is complete, the node can be
+/// restarted with the target database pointing at the target path. An interrupted migration
+/// is resumed column by column when the command is run again with the same target.
+#[derive(Debug, Clone, Parser)]
+pub struct MigrateDbCmd {
+	/// Path of the database to create.
+	#[arg(long, value_name = "PATH")]
+	pub target_path: PathBuf,
+
+	/// Backend of the database to create.
+	#[arg(long, value_name = "DB", ignore_case = true, value_enum, default_value_t = Database::ParityDb)]
+	pub target_database: Database,
+
+	/// Compare every migrated entry with the source database after the migration.
+	#[arg(long)]
+	pub verify: bool,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub shared_params: SharedParams,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub database_params: DatabaseParams,
+}
+
+impl MigrateDbCmd {
+	/// Run the migrate-db command
+	pub fn run<B: BlockT>(&self, database_config: &DatabaseSource) -> error::Result<()> {
+		let path = self.target_path.clone();
+		let target = match self.target_database {
+			#[cfg(feature = "rocksdb")]
+			Database::RocksDb => DatabaseSource::RocksDb {
+				path,
+				cache_size: self.database_params.database_cache_size().unwrap_or(1024),
+			},
+			Database::ParityDb | Database::ParityDbDeprecated => DatabaseSource::ParityDb { path },
+			Database::Auto =>
+				return Err(error::Error::Input(
+					"The target database backend must be given explicitly".into(),
+				)),
+		};
+		let summary = sc_client_db::migrate_database::<B>(database_config, &target, self.verify)?;
+
+		println!(
+			"Migrated {} entries ({} bytes) to {:?}.",
+			summary.entries, summary.bytes, self.target_path,
+		);
+		if self.verify {
+			println!("Verified {} entries.", summary.verified);
+		}
+		Ok(())
+	}
+}
+
+impl CliConfiguration for MigrateDbCmd {
+	fn shared_params(&self) -> &SharedParams {
+		&self.shared_params
+	}
+
+	fn database_params(&self) -> Option<&DatabaseParams> {
+		Some(&self.database_params)
+	}
+}
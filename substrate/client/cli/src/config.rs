@@ -28,7 +28,8 @@ use sc_service::{
 	config::{
 		BasePath, Configuration, DatabaseSource, KeystoreConfig, NetworkConfiguration,
 		NodeKeyConfig, OffchainWorkerConfig, PrometheusConfig, PruningMode, Role, RpcMethods,
-		TelemetryEndpoints, TransactionPoolOptions, WasmExecutionMethod,
+		RuntimeArtifactCacheConfig, TelemetryEndpoints, TransactionPoolOptions,
+		WasmExecutionMethod,
 	},
 	BlocksPruning, ChainSpec, TracingReceiver,
 };
@@ -428,6 +429,16 @@ pub trait CliConfiguration<DCV: DefaultConfigurationValues = ()>: Sized {
 		Ok(2)
 	}
 
+	/// Get the on-disk cache of compiled runtimes.
+	///
+	/// By default this is `None`, i.e. compiled runtimes are not stored on disk.
+	fn runtime_artifact_cache(
+		&self,
+		_config_dir: &PathBuf,
+	) -> Result<Option<RuntimeArtifactCacheConfig>> {
+		Ok(None)
+	}
+
 	/// Activate or not the automatic announcing of blocks after import
 	///
 	/// By default this is `false`.
@@ -468,6 +479,7 @@ pub trait CliConfiguration<DCV: DefaultConfigurationValues = ()>: Sized {
 		let keystore = self.keystore_config(&config_dir)?;
 		let telemetry_endpoints = self.telemetry_endpoints(&chain_spec)?;
 		let runtime_cache_size = self.runtime_cache_size()?;
+		let runtime_artifact_cache = self.runtime_artifact_cache(&config_dir)?;
 
 		Ok(Configuration {
 			impl_name: C::impl_name(),
@@ -518,6 +530,7 @@ pub trait CliConfiguration<DCV: DefaultConfigurationValues = ()>: Sized {
 			base_path,
 			informant_output_format: Default::default(),
 			runtime_cache_size,
+			runtime_artifact_cache,
 		})
 	}
 
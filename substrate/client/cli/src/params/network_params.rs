@@ -20,7 +20,8 @@ use crate::{arg_enums::SyncMode, params::node_key_params::NodeKeyParams};
 use clap::Args;
 use sc_network::{
 	config::{
-		NetworkConfiguration, NodeKeyConfig, NonReservedPeerMode, SetConfig, TransportConfig,
+		BandwidthLimits, IpConnectionLimits, NetworkConfiguration, NodeKeyConfig,
+		NonReservedPeerMode, SetConfig, TransportConfig,
 	},
 	multiaddr::Protocol,
 };
@@ -28,7 +29,11 @@ use sc_service::{
 	config::{Multiaddr, MultiaddrWithPeerId},
 	ChainSpec, ChainType,
 };
-use std::{borrow::Cow, num::NonZeroUsize, path::PathBuf};
+use std::{
+	borrow::Cow,
+	num::{NonZeroU32, NonZeroUsize},
+	path::PathBuf,
+};
 
 /// Parameters used to create the network configuration.
 #[derive(Debug, Clone, Args)]
@@ -166,6 +171,41 @@ pub struct NetworkParams {
 	/// and observe block requests timing out.
 	#[arg(long, value_name = "COUNT", default_value_t = 64)]
 	pub max_blocks_per_request: u32,
+
+	/// Maximum upload rate of the node in KiB/s, shared by all protocols.
+	///
+	/// Once the limit is reached, latency-sensitive protocols such as GRANDPA are served before
+	/// bulk transfers such as block requests.
+	#[arg(long, value_name = "KIB_PER_SEC")]
+	pub max_upload_rate: Option<NonZeroU32>,
+
+	/// Maximum download rate of the node in KiB/s, shared by all protocols.
+	///
+	/// Once the limit is reached, latency-sensitive protocols such as GRANDPA are served before
+	/// bulk transfers such as block requests.
+	#[arg(long, value_name = "KIB_PER_SEC")]
+	pub max_download_rate: Option<NonZeroU32>,
+
+	/// Maximum number of inbound peers connecting from the same IP address.
+	///
+	/// Reserved peers are not counted. Unlimited by default.
+	#[arg(long, value_name = "COUNT")]
+	pub max_inbound_peers_per_ip: Option<u32>,
+
+	/// Maximum number of inbound peers connecting from the same subnet (/24 for IPv4, /48 for
+	/// IPv6).
+	///
+	/// Reserved peers are not counted. Unlimited by default.
+	#[arg(long, value_name = "COUNT")]
+	pub max_inbound_peers_per_subnet: Option<u32>,
+
+	/// Maximum number of outbound peers in the same subnet (/24 for IPv4, /48 for IPv6).
+	///
+	/// Spreads the outbound slots over several networks, making it harder for an attacker
+	/// controlling a single subnet to eclipse the node. Reserved peers are not counted.
+	/// Unlimited by default.
+	#[arg(long, value_name = "COUNT")]
+	pub max_outbound_peers_per_subnet: Option<u32>,
 }
 
 impl NetworkParams {
@@ -261,10 +301,23 @@ impl NetworkParams {
 			yamux_window_size: None,
 			ipfs_server: self.ipfs_server,
 			sync_mode: self.sync.into(),
+			bandwidth_limits: BandwidthLimits {
+				upload: self.max_upload_rate.map(kib_to_bytes),
+				download: self.max_download_rate.map(kib_to_bytes),
+			},
+			ip_connection_limits: IpConnectionLimits {
+				max_inbound_per_ip: self.max_inbound_peers_per_ip,
+				max_inbound_per_subnet: self.max_inbound_peers_per_subnet,
+				max_outbound_per_subnet: self.max_outbound_peers_per_subnet,
+			},
 		}
 	}
 }
 
+fn kib_to_bytes(rate: NonZeroU32) -> NonZeroU32 {
+	rate.saturating_mul(NonZeroU32::new(1024).expect("1024 is not zero; qed"))
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
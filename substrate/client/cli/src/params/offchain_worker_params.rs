@@ -26,6 +26,7 @@
 use clap::{ArgAction, Args};
 use sc_network::config::Role;
 use sc_service::config::OffchainWorkerConfig;
+use std::path::PathBuf;
 
 use crate::{error, OffchainWorkerEnabled};
 
@@ -47,6 +48,21 @@ pub struct OffchainWorkerParams {
 	/// Allows the runtime to write directly to offchain workers DB during block import.
 	#[arg(long = "enable-offchain-indexing", value_name = "ENABLE_OFFCHAIN_INDEXING", default_value_t = false, action = ArgAction::Set)]
 	pub indexing_enabled: bool,
+
+	/// Record the HTTP requests of offchain workers and their responses to the given file.
+	///
+	/// The requests are still performed. The recorded file can be used with
+	/// `--offchain-http-replay`.
+	#[arg(long, value_name = "PATH", conflicts_with = "offchain_http_replay")]
+	pub offchain_http_record: Option<PathBuf>,
+
+	/// Answer the HTTP requests of offchain workers from the given fixtures file.
+	///
+	/// No request is sent over the network. Requests without a matching fixture fail. Fixtures
+	/// can also delay their response (`latencyMs`) or inject a failure (`failure`, either
+	/// `ioError` or `timeout`).
+	#[arg(long, value_name = "PATH")]
+	pub offchain_http_replay: Option<PathBuf>,
 }
 
 impl OffchainWorkerParams {
@@ -60,6 +76,11 @@ impl OffchainWorkerParams {
 		};
 
 		let indexing_enabled = self.indexing_enabled;
-		Ok(OffchainWorkerConfig { enabled, indexing_enabled })
+		Ok(OffchainWorkerConfig {
+			enabled,
+			indexing_enabled,
+			http_record: self.offchain_http_record.clone(),
+			http_replay: self.offchain_http_replay.clone(),
+		})
 	}
 }
@@ -18,7 +18,7 @@
 
 use crate::error;
 use clap::Args;
-use sc_service::{BlocksPruning, PruningMode};
+use sc_service::{BlocksPruning, PruningMode, RetentionRule};
 
 /// Parameters to define the pruning mode
 #[derive(Debug, Clone, Args)]
@@ -54,12 +54,57 @@ pub struct PruningParams {
 		default_value = "archive-canonical"
 	)]
 	pub blocks_pruning: DatabasePruningMode,
+
+	/// Keep the state history of all storage keys starting with the given hex prefix.
+	///
+	/// The history of matching keys stays available for finalized blocks after the rest of their
+	/// state is pruned, e.g. to keep the storage of a pallet for indexers. Can be passed multiple
+	/// times. Requires a pruned state, and the rules can not be changed once the database is
+	/// created.
+	#[arg(long, value_name = "HEX_PREFIX")]
+	pub state_retain_prefix: Vec<String>,
+
+	/// Keep the state history of the default child trie with the given hex storage key.
+	///
+	/// The storage key is given without the `:child_storage:default:` prefix. Can be passed
+	/// multiple times. Requires a pruned state, and the rules can not be changed once the
+	/// database is created.
+	#[arg(long, value_name = "HEX_STORAGE_KEY")]
+	pub state_retain_child_trie: Vec<String>,
 }
 
 impl PruningParams {
 	/// Get the pruning value from the parameters
 	pub fn state_pruning(&self) -> error::Result<Option<PruningMode>> {
-		Ok(self.state_pruning.map(|v| v.into()))
+		let retain = self.retention_rules()?;
+		if retain.is_empty() {
+			return Ok(self.state_pruning.map(|v| v.into()))
+		}
+
+		let mut mode = self.state_pruning.map_or_else(PruningMode::default, Into::into);
+		match &mut mode {
+			PruningMode::Constrained(constraints) => constraints.retain = retain,
+			PruningMode::ArchiveAll | PruningMode::ArchiveCanonical =>
+				return Err(error::Error::Input(
+					"State retention rules require a pruned state".into(),
+				)),
+		}
+		Ok(Some(mode))
+	}
+
+	/// Get the state retention rules from the parameters
+	fn retention_rules(&self) -> error::Result<Vec<RetentionRule>> {
+		let parse =
+			|hex: &String| array_bytes::hex2bytes(hex).map_err(error::Error::HexDataConversion);
+		self.state_retain_prefix
+			.iter()
+			.map(|prefix| parse(prefix).map(RetentionRule::Prefix))
+			.chain(
+				self.state_retain_child_trie
+					.iter()
+					.map(|storage_key| parse(storage_key).map(RetentionRule::ChildTrie)),
+			)
+			.collect()
 	}
 
 	/// Get the block pruning value from the parameters
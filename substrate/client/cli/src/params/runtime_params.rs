@@ -17,7 +17,14 @@
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
 use clap::Args;
-use std::str::FromStr;
+use sc_service::config::RuntimeArtifactCacheConfig;
+use std::{path::PathBuf, str::FromStr};
+
+/// Default sub directory to store compiled runtimes.
+const DEFAULT_RUNTIME_ARTIFACT_CACHE_PATH: &str = "runtime-artifacts";
+
+/// Default maximum size in MiB of all compiled runtimes stored on disk.
+const DEFAULT_RUNTIME_ARTIFACT_CACHE_SIZE: u64 = 512;
 
 /// Parameters used to config runtime.
 #[derive(Debug, Clone, Args)]
@@ -31,6 +38,46 @@ pub struct RuntimeParams {
 	/// Maximum number of different runtimes that can be cached.
 	#[arg(long, default_value_t = 2)]
 	pub runtime_cache_size: u8,
+
+	/// Store compiled runtimes on disk, in the given directory.
+	///
+	/// Compiled runtimes are reused across restarts of the node instead of compiling them again.
+	/// Compiled runtimes are not stored on disk unless this or `--runtime-artifact-cache-size` is
+	/// given. The directory must only be writable by the node's user, as the node executes the
+	/// compiled runtimes it finds there; on unix a directory that isn't is not used.
+	#[arg(long, value_name = "PATH")]
+	pub runtime_artifact_cache_path: Option<PathBuf>,
+
+	/// Store compiled runtimes on disk, using at most the given size in MiB.
+	///
+	/// The least recently used compiled runtimes are removed once the limit is exceeded. Unless a
+	/// directory is given with `--runtime-artifact-cache-path`, they are stored in the
+	/// `runtime-artifacts` directory of the chain's data path. Defaults to 512 MiB when only the
+	/// directory is given, `0` disables storing compiled runtimes on disk.
+	#[arg(long, value_name = "MiB")]
+	pub runtime_artifact_cache_size: Option<u64>,
+}
+
+impl RuntimeParams {
+	/// Returns the configuration of the on-disk cache of compiled runtimes, if it is enabled.
+	pub fn runtime_artifact_cache(
+		&self,
+		config_dir: &PathBuf,
+	) -> Option<RuntimeArtifactCacheConfig> {
+		let max_size = match (&self.runtime_artifact_cache_path, self.runtime_artifact_cache_size) {
+			(None, None) | (_, Some(0)) => return None,
+			(_, Some(size)) => size,
+			(Some(_), None) => DEFAULT_RUNTIME_ARTIFACT_CACHE_SIZE,
+		};
+
+		Some(RuntimeArtifactCacheConfig {
+			path: self
+				.runtime_artifact_cache_path
+				.clone()
+				.unwrap_or_else(|| config_dir.join(DEFAULT_RUNTIME_ARTIFACT_CACHE_PATH)),
+			max_size: max_size.saturating_mul(1024 * 1024),
+		})
+	}
 }
 
 fn parse_max_runtime_instances(s: &str) -> Result<usize, String> {
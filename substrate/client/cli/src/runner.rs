@@ -285,6 +285,7 @@ mod tests {
 				data_path: root,
 				informant_output_format: Default::default(),
 				runtime_cache_size: 2,
+				runtime_artifact_cache: None,
 			},
 			runtime,
 			Signals::dummy(),
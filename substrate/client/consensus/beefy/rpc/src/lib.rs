@@ -21,12 +21,29 @@
 #![warn(missing_docs)]
 
 use parking_lot::RwLock;
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
+use codec::{DecodeAll, Encode};
+use sc_client_api::BlockBackend;
 use sc_rpc::SubscriptionTaskExecutor;
-use sp_runtime::traits::Block as BlockT;
+use sc_rpc_api::DenyUnsafe;
+use serde::{Deserialize, Serialize};
+use sp_api::{ApiExt, ProvideRuntimeApi};
+use sp_blockchain::HeaderBackend;
+use sp_consensus_beefy::{MmrRootHash, VersionedFinalityProof};
+use sp_core::{
+	offchain::{storage::OffchainDb, OffchainDbExt, OffchainStorage},
+	Bytes,
+};
+use sp_mmr_primitives::MmrApi;
+use sp_runtime::traits::{Block as BlockT, NumberFor};
 
-use futures::{task::SpawnError, FutureExt, StreamExt};
+use futures::{
+	future::{self, Either},
+	task::SpawnError,
+	FutureExt, StreamExt,
+};
+use futures_timer::Delay;
 use jsonrpsee::{
 	core::{async_trait, Error as JsonRpseeError, RpcResult},
 	proc_macros::rpc,
@@ -35,8 +52,12 @@ use jsonrpsee::{
 };
 use log::warn;
 
-use sc_consensus_beefy::communication::notification::{
-	BeefyBestBlockStream, BeefyVersionedFinalityProofStream,
+use sc_consensus_beefy::{
+	communication::{
+		notification::{BeefyBestBlockStream, BeefyVersionedFinalityProofStream},
+		request_response::{find_justification, BeefyJustifsRequester},
+	},
+	justification::BeefyVersionedFinalityProof,
 };
 
 mod notification;
@@ -50,6 +71,12 @@ pub enum Error {
 	/// The BEEFY RPC background task failed to spawn.
 	#[error("BEEFY RPC background task failed to spawn")]
 	RpcTaskFailure(#[from] SpawnError),
+	/// No BEEFY justification covering the requested block could be found.
+	#[error("BEEFY justification not found")]
+	JustificationNotFound,
+	/// Generating the MMR proof for the requested block failed.
+	#[error("MMR proof generation failed: {0}")]
+	MmrProof(String),
 }
 
 /// The error codes returned by jsonrpc.
@@ -58,6 +85,10 @@ pub enum ErrorCode {
 	NotReady = 1,
 	/// Returned on BEEFY RPC background task failure.
 	TaskFailure = 2,
+	/// Returned when no BEEFY justification was found.
+	NotFound = 3,
+	/// Returned when generating an MMR proof failed.
+	ProofGeneration = 4,
 }
 
 impl From<Error> for ErrorCode {
@@ -65,6 +96,8 @@ impl From<Error> for ErrorCode {
 		match error {
 			Error::EndpointNotReady => ErrorCode::NotReady,
 			Error::RpcTaskFailure(_) => ErrorCode::TaskFailure,
+			Error::JustificationNotFound => ErrorCode::NotFound,
+			Error::MmrProof(_) => ErrorCode::ProofGeneration,
 		}
 	}
 }
@@ -162,19 +195,168 @@ where
 	}
 }
 
+/// Time to wait for the BEEFY voter to fetch a justification from the network.
+const JUSTIF_REQUEST_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// BEEFY justification together with an MMR proof of a block it covers.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JustificationWithMmrProof<Hash> {
+	/// Hash of the block the justification is for.
+	pub justified_block_hash: Hash,
+	/// SCALE-encoded `sp_consensus_beefy::VersionedFinalityProof`.
+	pub justification: Bytes,
+	/// SCALE-encoded vector of MMR leaves, holding the leaf of the requested block.
+	pub leaves: Bytes,
+	/// SCALE-encoded proof of `leaves`, see [`sp_mmr_primitives::Proof`].
+	///
+	/// The proof is against the MMR root in the payload of the justification's commitment.
+	pub proof: Bytes,
+}
+
+/// Provides RPC methods for proving blocks with BEEFY justifications.
+#[rpc(client, server)]
+pub trait BeefyProofApi<Hash, BlockNumber> {
+	/// Returns the BEEFY justification of the given block, or of the closest later block
+	/// having one, together with an MMR proof of the given block's leaf.
+	///
+	/// Justifications not known locally are requested from the network by the BEEFY voter,
+	/// which is why this method is unsafe.
+	/// The result can be submitted as is to the bridge header-chain pallets.
+	#[method(name = "beefy_getJustificationWithMmrProof")]
+	async fn justification_with_mmr_proof(
+		&self,
+		block_number: BlockNumber,
+	) -> RpcResult<JustificationWithMmrProof<Hash>>;
+}
+
+/// Implements the BeefyProofApi RPC trait.
+pub struct BeefyProof<Client, Block: BlockT, S> {
+	client: Arc<Client>,
+	offchain_db: OffchainDb<S>,
+	justif_requester: BeefyJustifsRequester<Block>,
+	deny_unsafe: DenyUnsafe,
+}
+
+impl<Client, Block, S> BeefyProof<Client, Block, S>
+where
+	Block: BlockT,
+	S: OffchainStorage,
+{
+	/// Creates a new BeefyProof Rpc handler instance.
+	///
+	/// The offchain storage holds the MMR nodes needed to generate proofs.
+	pub fn new(
+		client: Arc<Client>,
+		offchain_storage: S,
+		justif_requester: BeefyJustifsRequester<Block>,
+		deny_unsafe: DenyUnsafe,
+	) -> Self {
+		Self {
+			client,
+			offchain_db: OffchainDb::new(offchain_storage),
+			justif_requester,
+			deny_unsafe,
+		}
+	}
+}
+
+impl<Client, Block, S> BeefyProof<Client, Block, S>
+where
+	Block: BlockT,
+	Client: BlockBackend<Block> + HeaderBackend<Block> + Send + Sync,
+{
+	/// Find a justification of `block` or a later block, locally or through the BEEFY voter.
+	async fn justification_covering(
+		&self,
+		block: NumberFor<Block>,
+	) -> Result<BeefyVersionedFinalityProof<Block>, Error> {
+		let local = find_justification(&*self.client, block, true).and_then(|encoded| {
+			BeefyVersionedFinalityProof::<Block>::decode_all(&mut &encoded[..]).ok()
+		});
+		if let Some(justification) = local {
+			return Ok(justification)
+		}
+		let request = Box::pin(self.justif_requester.request(block));
+		match future::select(request, Delay::new(JUSTIF_REQUEST_TIMEOUT)).await {
+			Either::Left((Some(justification), _)) => Ok(justification),
+			_ => Err(Error::JustificationNotFound),
+		}
+	}
+}
+
+#[async_trait]
+impl<Client, Block, S> BeefyProofApiServer<Block::Hash, NumberFor<Block>>
+	for BeefyProof<Client, Block, S>
+where
+	Block: BlockT,
+	Client: BlockBackend<Block>
+		+ HeaderBackend<Block>
+		+ ProvideRuntimeApi<Block>
+		+ Send
+		+ Sync
+		+ 'static,
+	Client::Api: MmrApi<Block, MmrRootHash, NumberFor<Block>>,
+	S: OffchainStorage + 'static,
+{
+	async fn justification_with_mmr_proof(
+		&self,
+		block_number: NumberFor<Block>,
+	) -> RpcResult<JustificationWithMmrProof<Block::Hash>> {
+		self.deny_unsafe.check_if_safe()?;
+
+		let justification = self.justification_covering(block_number).await?;
+		let justified_block = match justification {
+			VersionedFinalityProof::V1(ref sc) => sc.commitment.block_number,
+		};
+		let justified_block_hash = self
+			.client
+			.hash(justified_block)
+			.map_err(|e| Error::MmrProof(e.to_string()))?
+			.ok_or_else(|| Error::MmrProof(format!("unknown block #{}", justified_block)))?;
+
+		// Prove the leaf with the MMR as of the justified block, its root is the one
+		// committed to in the justification payload.
+		let mut api = self.client.runtime_api();
+		api.register_extension(OffchainDbExt::new(self.offchain_db.clone()));
+		let (leaves, proof) = api
+			.generate_proof(self.client.info().best_hash, vec![block_number], Some(justified_block))
+			.map_err(|e| Error::MmrProof(e.to_string()))?
+			.map_err(|e| Error::MmrProof(e.to_string()))?;
+
+		Ok(JustificationWithMmrProof {
+			justified_block_hash,
+			justification: justification.encode().into(),
+			leaves: leaves.encode().into(),
+			proof: proof.encode().into(),
+		})
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
 
-	use codec::{Decode, Encode};
+	use codec::Decode;
 	use jsonrpsee::{types::EmptyServerParams as EmptyParams, RpcModule};
-	use sc_consensus_beefy::{
-		communication::notification::BeefyVersionedFinalityProofSender,
-		justification::BeefyVersionedFinalityProof,
+	use parking_lot::Mutex;
+	use sc_block_builder::BlockBuilderBuilder;
+	use sc_consensus_beefy::communication::notification::BeefyVersionedFinalityProofSender;
+	use sp_api::ApiRef;
+	use sp_consensus_beefy::{known_payloads, Payload, SignedCommitment, BEEFY_ENGINE_ID};
+	use sp_core::offchain::storage::InMemOffchainStorage;
+	use sp_mmr_primitives::{EncodableOpaqueLeaf, Error as MmrError, Proof};
+	use sp_runtime::{
+		generic::SignedBlock,
+		traits::{BlakeTwo256, Hash},
+		Justifications,
+	};
+	use substrate_test_runtime_client::{
+		prelude::*,
+		runtime::{Block, Extrinsic, Hash as BlockHash, Header},
+		sp_consensus::BlockStatus,
+		BlockOrigin,
 	};
-	use sp_consensus_beefy::{known_payloads, Payload, SignedCommitment};
-	use sp_runtime::traits::{BlakeTwo256, Hash};
-	use substrate_test_runtime_client::runtime::Block;
 
 	fn setup_io_handler() -> (RpcModule<Beefy<Block>>, BeefyVersionedFinalityProofSender<Block>) {
 		let (_, stream) = BeefyBestBlockStream::<Block>::channel();
@@ -300,4 +482,194 @@ mod tests {
 		assert_eq!(&recv_sub_id, sub.subscription_id());
 		assert_eq!(recv_finality_proof, finality_proof);
 	}
+
+	#[tokio::test]
+	async fn justification_not_found_without_voter() {
+		let client = Arc::new(substrate_test_runtime_client::new());
+		// Voter end of the channel is dropped, nothing can be fetched from the network.
+		let (requester, _) = BeefyJustifsRequester::<Block>::new();
+		let handler =
+			BeefyProof::new(client, InMemOffchainStorage::default(), requester, DenyUnsafe::No);
+
+		assert!(matches!(
+			handler.justification_covering(0).await,
+			Err(Error::JustificationNotFound)
+		));
+	}
+
+	/// MMR proof requests seen by the runtime API: requested blocks and historical block.
+	type ProofRequests = Arc<Mutex<Vec<(Vec<u64>, Option<u64>)>>>;
+
+	/// Test client answering MMR proof requests with canned leaves and proof.
+	struct TestClient {
+		inner: Arc<substrate_test_runtime_client::TestClient>,
+		proof_requests: ProofRequests,
+	}
+
+	fn mmr_leaves() -> Vec<EncodableOpaqueLeaf> {
+		vec![EncodableOpaqueLeaf(vec![1, 2, 3])]
+	}
+
+	fn mmr_proof() -> Proof<MmrRootHash> {
+		Proof { leaf_indices: vec![0], leaf_count: 2, items: vec![MmrRootHash::repeat_byte(7)] }
+	}
+
+	struct RuntimeApi {
+		proof_requests: ProofRequests,
+	}
+
+	impl ProvideRuntimeApi<Block> for TestClient {
+		type Api = RuntimeApi;
+		fn runtime_api(&self) -> ApiRef<Self::Api> {
+			RuntimeApi { proof_requests: self.proof_requests.clone() }.into()
+		}
+	}
+
+	sp_api::mock_impl_runtime_apis! {
+		impl MmrApi<Block, MmrRootHash, u64> for RuntimeApi {
+			fn generate_proof(
+				block_numbers: Vec<u64>,
+				best_known_block_number: Option<u64>,
+			) -> Result<(Vec<EncodableOpaqueLeaf>, Proof<MmrRootHash>), MmrError> {
+				self.proof_requests.lock().push((block_numbers, best_known_block_number));
+				Ok((mmr_leaves(), mmr_proof()))
+			}
+		}
+	}
+
+	impl HeaderBackend<Block> for TestClient {
+		fn header(&self, hash: BlockHash) -> sp_blockchain::Result<Option<Header>> {
+			self.inner.header(hash)
+		}
+
+		fn info(&self) -> sp_blockchain::Info<Block> {
+			self.inner.info()
+		}
+
+		fn status(&self, hash: BlockHash) -> sp_blockchain::Result<sp_blockchain::BlockStatus> {
+			self.inner.status(hash)
+		}
+
+		fn number(&self, hash: BlockHash) -> sp_blockchain::Result<Option<u64>> {
+			self.inner.number(hash)
+		}
+
+		fn hash(&self, number: u64) -> sp_blockchain::Result<Option<BlockHash>> {
+			self.inner.hash(number)
+		}
+	}
+
+	impl BlockBackend<Block> for TestClient {
+		fn block_body(&self, hash: BlockHash) -> sp_blockchain::Result<Option<Vec<Extrinsic>>> {
+			self.inner.block_body(hash)
+		}
+
+		fn block_indexed_body(
+			&self,
+			hash: BlockHash,
+		) -> sp_blockchain::Result<Option<Vec<Vec<u8>>>> {
+			self.inner.block_indexed_body(hash)
+		}
+
+		fn block(&self, hash: BlockHash) -> sp_blockchain::Result<Option<SignedBlock<Block>>> {
+			self.inner.block(hash)
+		}
+
+		fn block_status(&self, hash: BlockHash) -> sp_blockchain::Result<BlockStatus> {
+			self.inner.block_status(hash)
+		}
+
+		fn justifications(&self, hash: BlockHash) -> sp_blockchain::Result<Option<Justifications>> {
+			self.inner.justifications(hash)
+		}
+
+		fn block_hash(&self, number: u64) -> sp_blockchain::Result<Option<BlockHash>> {
+			self.inner.block_hash(number)
+		}
+
+		fn indexed_transaction(&self, hash: BlockHash) -> sp_blockchain::Result<Option<Vec<u8>>> {
+			self.inner.indexed_transaction(hash)
+		}
+
+		fn requires_full_sync(&self) -> bool {
+			self.inner.requires_full_sync()
+		}
+	}
+
+	fn justification_for(block_number: u64) -> BeefyVersionedFinalityProof<Block> {
+		let payload =
+			Payload::from_single_entry(known_payloads::MMR_ROOT_ID, MmrRootHash::zero().encode());
+		BeefyVersionedFinalityProof::<Block>::V1(SignedCommitment {
+			commitment: sp_consensus_beefy::Commitment {
+				payload,
+				block_number,
+				validator_set_id: 0,
+			},
+			signatures: vec![],
+		})
+	}
+
+	/// Import block #1 without and block #2 with a BEEFY justification.
+	async fn setup_proof_handler(
+		deny_unsafe: DenyUnsafe,
+	) -> (RpcModule<BeefyProof<TestClient, Block, InMemOffchainStorage>>, ProofRequests, BlockHash)
+	{
+		let mut inner = Arc::new(substrate_test_runtime_client::new());
+		let mut justified_hash = Default::default();
+		for number in 1..=2 {
+			let block = BlockBuilderBuilder::new(&*inner)
+				.on_parent_block(inner.info().best_hash)
+				.with_parent_block_number(inner.info().best_number)
+				.build()
+				.unwrap()
+				.build()
+				.unwrap()
+				.block;
+			if number == 1 {
+				inner.import(BlockOrigin::Own, block).await.unwrap();
+			} else {
+				justified_hash = block.hash();
+				let justifs =
+					Justifications::from((BEEFY_ENGINE_ID, justification_for(number).encode()));
+				inner.import_justified(BlockOrigin::Own, block, justifs).await.unwrap();
+			}
+		}
+
+		let proof_requests = ProofRequests::default();
+		let client = Arc::new(TestClient { inner, proof_requests: proof_requests.clone() });
+		let (requester, _) = BeefyJustifsRequester::<Block>::new();
+		let handler =
+			BeefyProof::new(client, InMemOffchainStorage::default(), requester, deny_unsafe);
+		(handler.into_rpc(), proof_requests, justified_hash)
+	}
+
+	#[tokio::test]
+	async fn justification_with_mmr_proof_of_earlier_block() {
+		let (rpc, proof_requests, justified_hash) = setup_proof_handler(DenyUnsafe::No).await;
+
+		let result: JustificationWithMmrProof<BlockHash> =
+			rpc.call("beefy_getJustificationWithMmrProof", [1u64]).await.unwrap();
+
+		// Block #1 is covered by the justification of block #2, and the MMR proof is
+		// generated for block #1 against the MMR as of block #2.
+		assert_eq!(result.justified_block_hash, justified_hash);
+		assert_eq!(
+			BeefyVersionedFinalityProof::<Block>::decode(&mut &result.justification[..]).unwrap(),
+			justification_for(2)
+		);
+		assert_eq!(result.leaves.0, mmr_leaves().encode());
+		assert_eq!(result.proof.0, mmr_proof().encode());
+		assert_eq!(*proof_requests.lock(), vec![(vec![1], Some(2))]);
+	}
+
+	#[tokio::test]
+	async fn justification_with_mmr_proof_is_unsafe() {
+		let (rpc, proof_requests, _) = setup_proof_handler(DenyUnsafe::Yes).await;
+
+		let request = r#"{"jsonrpc":"2.0","method":"beefy_getJustificationWithMmrProof","params":[1],"id":1}"#;
+		let (response, _) = rpc.raw_json_request(request).await.unwrap();
+
+		assert!(response.result.contains("RPC call is unsafe to be called externally"));
+		assert!(proof_requests.lock().is_empty());
+	}
 }
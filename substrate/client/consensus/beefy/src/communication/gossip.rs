@@ -235,16 +235,25 @@ where
 	next_rebroadcast: Mutex<Instant>,
 	known_peers: Arc<Mutex<KnownPeers<B>>>,
 	report_sender: TracingUnboundedSender<PeerReport>,
+	future_votes_sender: TracingUnboundedSender<VoteMessage<NumberFor<B>, AuthorityId, Signature>>,
 }
 
 impl<B> GossipValidator<B>
 where
 	B: Block,
 {
+	/// Create a new gossip validator, along with the streams of peer reports and of
+	/// validly signed votes from the current set for rounds beyond best GRANDPA.
 	pub(crate) fn new(
 		known_peers: Arc<Mutex<KnownPeers<B>>>,
-	) -> (GossipValidator<B>, TracingUnboundedReceiver<PeerReport>) {
+	) -> (
+		GossipValidator<B>,
+		TracingUnboundedReceiver<PeerReport>,
+		TracingUnboundedReceiver<VoteMessage<NumberFor<B>, AuthorityId, Signature>>,
+	) {
 		let (tx, rx) = tracing_unbounded("mpsc_beefy_gossip_validator", 10_000);
+		let (future_votes_tx, future_votes_rx) =
+			tracing_unbounded("mpsc_beefy_gossip_future_votes", 10_000);
 		let val = GossipValidator {
 			votes_topic: votes_topic::<B>(),
 			justifs_topic: proofs_topic::<B>(),
@@ -252,8 +261,9 @@ where
 			next_rebroadcast: Mutex::new(Instant::now() + REBROADCAST_AFTER),
 			known_peers,
 			report_sender: tx,
+			future_votes_sender: future_votes_tx,
 		};
-		(val, rx)
+		(val, rx, future_votes_rx)
 	}
 
 	/// Update gossip validator filter.
@@ -284,7 +294,24 @@ where
 
 			match filter.consider_vote(round, set_id) {
 				Consider::RejectPast => return Action::Discard(cost::OUTDATED_MESSAGE),
-				Consider::RejectFuture => return Action::Discard(cost::FUTURE_MESSAGE),
+				Consider::RejectFuture => {
+					// Votes from the current set on rounds beyond best GRANDPA might be for
+					// blocks that don't exist yet, let the worker check and report them.
+					let from_current_voter = filter
+						.validator_set()
+						.map(|set| set.id() == set_id && set.validators().contains(&vote.id))
+						.unwrap_or(false);
+					drop(filter);
+					if from_current_voter &&
+						BeefyKeystore::verify(
+							&vote.id,
+							&vote.signature,
+							&vote.commitment.encode(),
+						) {
+						let _ = self.future_votes_sender.unbounded_send(vote);
+					}
+					return Action::Discard(cost::FUTURE_MESSAGE)
+				},
 				Consider::RejectOutOfScope => return Action::Discard(cost::OUT_OF_SCOPE_MESSAGE),
 				Consider::Accept => {},
 			}
@@ -545,7 +572,7 @@ pub(crate) mod tests {
 	fn should_validate_messages() {
 		let keys = vec![Keyring::Alice.public()];
 		let validator_set = ValidatorSet::<AuthorityId>::new(keys.clone(), 0).unwrap();
-		let (gv, mut report_stream) =
+		let (gv, mut report_stream, mut future_votes_stream) =
 			GossipValidator::<Block>::new(Arc::new(Mutex::new(KnownPeers::new())));
 		let sender = PeerId::random();
 		let mut context = TestContext;
@@ -597,6 +624,8 @@ pub(crate) mod tests {
 		assert!(matches!(res, ValidationResult::Discard));
 		expected_report.cost_benefit = cost::FUTURE_MESSAGE;
 		assert_eq!(report_stream.try_recv().unwrap(), expected_report);
+		// future votes from the current set are forwarded to be checked by the worker
+		assert_eq!(future_votes_stream.try_recv().unwrap(), vote);
 
 		// reject if the round is not live anymore
 		gv.update_filter(GossipFilterCfg { start: 7, end: 10, validator_set: &validator_set });
@@ -608,6 +637,9 @@ pub(crate) mod tests {
 		expected_report.cost_benefit = cost::OUTDATED_MESSAGE;
 		assert_eq!(report_stream.try_recv().unwrap(), expected_report);
 
+		// only future votes are forwarded
+		assert!(future_votes_stream.try_recv().is_err());
+
 		// now verify proofs validation
 
 		// reject old proof
@@ -659,7 +691,7 @@ pub(crate) mod tests {
 	fn messages_allowed_and_expired() {
 		let keys = vec![Keyring::Alice.public()];
 		let validator_set = ValidatorSet::<AuthorityId>::new(keys.clone(), 0).unwrap();
-		let (gv, _) = GossipValidator::<Block>::new(Arc::new(Mutex::new(KnownPeers::new())));
+		let (gv, _, _) = GossipValidator::<Block>::new(Arc::new(Mutex::new(KnownPeers::new())));
 		gv.update_filter(GossipFilterCfg { start: 0, end: 10, validator_set: &validator_set });
 		let sender = sc_network::PeerId::random();
 		let topic = Default::default();
@@ -736,7 +768,7 @@ pub(crate) mod tests {
 	fn messages_rebroadcast() {
 		let keys = vec![Keyring::Alice.public()];
 		let validator_set = ValidatorSet::<AuthorityId>::new(keys.clone(), 0).unwrap();
-		let (gv, _) = GossipValidator::<Block>::new(Arc::new(Mutex::new(KnownPeers::new())));
+		let (gv, _, _) = GossipValidator::<Block>::new(Arc::new(Mutex::new(KnownPeers::new())));
 		gv.update_filter(GossipFilterCfg { start: 0, end: 10, validator_set: &validator_set });
 		let sender = sc_network::PeerId::random();
 		let topic = Default::default();
@@ -32,6 +32,8 @@ pub(crate) mod beefy_protocol_name {
 	const GOSSIP_NAME: &str = "/beefy/2";
 	/// BEEFY justifications protocol name suffix.
 	const JUSTIFICATIONS_NAME: &str = "/beefy/justifications/1";
+	/// BEEFY justifications protocol name suffix, for requests also accepting later blocks.
+	const COVERING_JUSTIFICATIONS_NAME: &str = "/beefy/justifications/2";
 
 	/// Name of the votes gossip protocol used by BEEFY.
 	///
@@ -60,6 +62,24 @@ pub(crate) mod beefy_protocol_name {
 			format!("/{}{}", bytes2hex("", genesis_hash), JUSTIFICATIONS_NAME).into()
 		}
 	}
+
+	/// Name of the BEEFY justifications request-response protocol accepting requests for
+	/// justifications of later blocks.
+	///
+	/// Peers not supporting it refuse to negotiate it, instead of receiving a request they
+	/// can't decode.
+	pub fn covering_justifications_protocol_name<Hash: AsRef<[u8]>>(
+		genesis_hash: Hash,
+		fork_id: Option<&str>,
+	) -> ProtocolName {
+		let genesis_hash = genesis_hash.as_ref();
+		if let Some(fork_id) = fork_id {
+			format!("/{}/{}{}", bytes2hex("", genesis_hash), fork_id, COVERING_JUSTIFICATIONS_NAME)
+				.into()
+		} else {
+			format!("/{}{}", bytes2hex("", genesis_hash), COVERING_JUSTIFICATIONS_NAME).into()
+		}
+	}
 }
 
 /// Returns the configuration value to put in
@@ -120,7 +140,10 @@ mod tests {
 
 	#[test]
 	fn beefy_protocols_names() {
-		use beefy_protocol_name::{gossip_protocol_name, justifications_protocol_name};
+		use beefy_protocol_name::{
+			covering_justifications_protocol_name, gossip_protocol_name,
+			justifications_protocol_name,
+		};
 		// Create protocol name using random genesis hash.
 		let genesis_hash = H256::random();
 		let genesis_hex = array_bytes::bytes2hex("", genesis_hash);
@@ -133,6 +156,10 @@ mod tests {
 		let justif_proto_name = justifications_protocol_name(&genesis_hash, None);
 		assert_eq!(justif_proto_name.to_string(), expected_justif_name);
 
+		let expected_covering_name = format!("/{}/beefy/justifications/2", genesis_hex);
+		let covering_proto_name = covering_justifications_protocol_name(&genesis_hash, None);
+		assert_eq!(covering_proto_name.to_string(), expected_covering_name);
+
 		// Create protocol name using hardcoded genesis hash. Verify exact representation.
 		let genesis_hash = [
 			50, 4, 60, 123, 58, 106, 216, 246, 194, 188, 139, 193, 33, 212, 202, 171, 9, 55, 123,
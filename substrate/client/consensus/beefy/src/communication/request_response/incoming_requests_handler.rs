@@ -19,12 +19,11 @@
 use codec::DecodeAll;
 use futures::{channel::oneshot, StreamExt};
 use log::{debug, trace};
-use sc_client_api::BlockBackend;
+use sc_client_api::{BlockBackend, HeaderBackend};
 use sc_network::{
 	config as netconfig, config::RequestResponseConfig, types::ProtocolName, PeerId,
 	ReputationChange,
 };
-use sp_consensus_beefy::BEEFY_ENGINE_ID;
 use sp_runtime::traits::Block;
 use std::{marker::PhantomData, sync::Arc};
 
@@ -32,8 +31,8 @@ use crate::{
 	communication::{
 		cost,
 		request_response::{
-			on_demand_justifications_protocol_config, Error, JustificationRequest,
-			BEEFY_SYNC_LOG_TARGET,
+			find_justification, on_demand_justifications_protocol_config, Error,
+			JustificationRequest, BEEFY_SYNC_LOG_TARGET,
 		},
 	},
 	metric_inc,
@@ -128,6 +127,7 @@ impl IncomingRequestReceiver {
 pub struct BeefyJustifsRequestHandler<B, Client> {
 	pub(crate) request_receiver: IncomingRequestReceiver,
 	pub(crate) justif_protocol_name: ProtocolName,
+	pub(crate) covering_justif_protocol_name: ProtocolName,
 	pub(crate) client: Arc<Client>,
 	pub(crate) metrics: Option<OnDemandIncomingRequestsMetrics>,
 	pub(crate) _block: PhantomData<B>,
@@ -136,22 +136,32 @@ pub struct BeefyJustifsRequestHandler<B, Client> {
 impl<B, Client> BeefyJustifsRequestHandler<B, Client>
 where
 	B: Block,
-	Client: BlockBackend<B> + Send + Sync,
+	Client: BlockBackend<B> + HeaderBackend<B> + Send + Sync,
 {
 	/// Create a new [`BeefyJustifsRequestHandler`].
+	///
+	/// All returned configs need to be registered towards the networking.
 	pub fn new<Hash: AsRef<[u8]>>(
 		genesis_hash: Hash,
 		fork_id: Option<&str>,
 		client: Arc<Client>,
 		prometheus_registry: Option<prometheus::Registry>,
-	) -> (Self, RequestResponseConfig) {
-		let (request_receiver, config) =
+	) -> (Self, Vec<RequestResponseConfig>) {
+		let (request_receiver, configs) =
 			on_demand_justifications_protocol_config(genesis_hash, fork_id);
-		let justif_protocol_name = config.name.clone();
+		let justif_protocol_name = configs[0].name.clone();
+		let covering_justif_protocol_name = configs[1].name.clone();
 		let metrics = register_metrics(prometheus_registry);
 		(
-			Self { request_receiver, justif_protocol_name, client, metrics, _block: PhantomData },
-			config,
+			Self {
+				request_receiver,
+				justif_protocol_name,
+				covering_justif_protocol_name,
+				client,
+				metrics,
+				_block: PhantomData,
+			},
+			configs,
 		)
 	}
 
@@ -160,16 +170,20 @@ where
 		self.justif_protocol_name.clone()
 	}
 
+	/// Network request-response protocol name used by this handler for requests also
+	/// accepting justifications of later blocks.
+	pub fn covering_protocol_name(&self) -> ProtocolName {
+		self.covering_justif_protocol_name.clone()
+	}
+
 	// Sends back justification response if justification found in client backend.
+	//
+	// Requests allowing later blocks are answered with the first justification found
+	// at or after the requested block.
 	fn handle_request(&self, request: IncomingRequest<B>) -> Result<(), Error> {
 		let mut reputation_changes = vec![];
-		let maybe_encoded_proof = self
-			.client
-			.block_hash(request.payload.begin)
-			.ok()
-			.flatten()
-			.and_then(|hash| self.client.justifications(hash).ok().flatten())
-			.and_then(|justifs| justifs.get(BEEFY_ENGINE_ID).cloned())
+		let JustificationRequest { begin, allow_later } = request.payload;
+		let maybe_encoded_proof = find_justification(&*self.client, begin, allow_later)
 			.ok_or_else(|| reputation_changes.push(cost::UNKOWN_PROOF_REQUEST));
 		request
 			.pending_response
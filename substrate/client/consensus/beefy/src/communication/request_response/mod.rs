@@ -25,53 +25,193 @@ pub use incoming_requests_handler::BeefyJustifsRequestHandler;
 
 use std::time::Duration;
 
-use codec::{Decode, Encode, Error as CodecError};
+use codec::{Decode, Encode, Error as CodecError, Input, Output};
+use futures::channel::oneshot;
+use sc_client_api::{BlockBackend, HeaderBackend};
 use sc_network::{config::RequestResponseConfig, PeerId};
-use sp_runtime::traits::{Block, NumberFor};
-
-use crate::communication::{beefy_protocol_name::justifications_protocol_name, peers::PeerReport};
+use sp_consensus_beefy::BEEFY_ENGINE_ID;
+use sp_runtime::{
+	traits::{Block, NumberFor},
+	EncodedJustification, SaturatedConversion,
+};
+
+use crate::{
+	communication::{
+		beefy_protocol_name::{
+			covering_justifications_protocol_name, justifications_protocol_name,
+		},
+		peers::PeerReport,
+	},
+	justification::BeefyVersionedFinalityProof,
+};
 use incoming_requests_handler::IncomingRequestReceiver;
 
 // 10 seems reasonable, considering justifs are explicitly requested only
 // for mandatory blocks, by nodes that are syncing/catching-up.
 const JUSTIF_CHANNEL_SIZE: usize = 10;
 
+// Justifications requested through RPC are served one at a time by the voter,
+// anything above this is rejected right away.
+pub(crate) const RPC_JUSTIF_CHANNEL_SIZE: usize = 16;
+
+// Maximum number of RPC justification requests the voter keeps waiting for a justification,
+// further requests are dropped until some of them are answered.
+pub(crate) const MAX_PENDING_RPC_JUSTIF_REQUESTS: usize = 64;
+
+/// Maximum number of blocks searched for a justification when answering a request
+/// that also accepts justifications of later blocks.
+///
+/// Every searched block costs a database read, so this is kept well below the distance
+/// between two mandatory blocks.
+const MAX_LATER_JUSTIF_SEARCH: u32 = 32;
+
 const MAX_RESPONSE_SIZE: u64 = 1024 * 1024;
 const JUSTIF_REQUEST_TIMEOUT: Duration = Duration::from_secs(3);
 
 const BEEFY_SYNC_LOG_TARGET: &str = "beefy::sync";
 
-/// Get the configuration for the BEEFY justifications Request/response protocol.
+/// Get the configurations for the BEEFY justifications Request/response protocols.
 ///
-/// Returns a receiver for messages received on this protocol and the requested
-/// `ProtocolConfig`.
+/// Returns a receiver for messages received on these protocols and the requested
+/// `ProtocolConfig`s: the plain justifications protocol, and the protocol also
+/// accepting requests for justifications of later blocks. Both protocols feed the
+/// same receiver.
 ///
 /// Consider using [`BeefyJustifsRequestHandler`] instead of this low-level function.
 pub(crate) fn on_demand_justifications_protocol_config<Hash: AsRef<[u8]>>(
 	genesis_hash: Hash,
 	fork_id: Option<&str>,
-) -> (IncomingRequestReceiver, RequestResponseConfig) {
-	let name = justifications_protocol_name(genesis_hash, fork_id);
-	let fallback_names = vec![];
+) -> (IncomingRequestReceiver, Vec<RequestResponseConfig>) {
 	let (tx, rx) = async_channel::bounded(JUSTIF_CHANNEL_SIZE);
 	let rx = IncomingRequestReceiver::new(rx);
-	let cfg = RequestResponseConfig {
+	let cfg = |name, inbound_queue| RequestResponseConfig {
 		name,
-		fallback_names,
+		fallback_names: vec![],
 		max_request_size: 32,
 		max_response_size: MAX_RESPONSE_SIZE,
 		// We are connected to all validators:
 		request_timeout: JUSTIF_REQUEST_TIMEOUT,
-		inbound_queue: Some(tx),
+		inbound_queue: Some(inbound_queue),
+		bandwidth: Default::default(),
 	};
-	(rx, cfg)
+	let cfgs = vec![
+		cfg(justifications_protocol_name(&genesis_hash, fork_id), tx.clone()),
+		cfg(covering_justifications_protocol_name(&genesis_hash, fork_id), tx),
+	];
+	(rx, cfgs)
 }
 
 /// BEEFY justification request.
-#[derive(Debug, Clone, Encode, Decode)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct JustificationRequest<B: Block> {
 	/// Start collecting proofs from this block.
 	pub begin: NumberFor<B>,
+	/// Also accept a justification for any block after `begin`.
+	///
+	/// The flag is only encoded when set, so that plain requests keep the encoding
+	/// understood by peers that don't know about it. Requests with the flag set must
+	/// only be sent over the covering justifications protocol, see
+	/// [`covering_justifications_protocol_name`].
+	pub allow_later: bool,
+}
+
+impl<B: Block> Encode for JustificationRequest<B> {
+	fn encode_to<T: Output + ?Sized>(&self, dest: &mut T) {
+		self.begin.encode_to(dest);
+		if self.allow_later {
+			true.encode_to(dest);
+		}
+	}
+}
+
+impl<B: Block> Decode for JustificationRequest<B> {
+	fn decode<I: Input>(input: &mut I) -> Result<Self, CodecError> {
+		let begin = NumberFor::<B>::decode(input)?;
+		let allow_later = match input.remaining_len()? {
+			Some(0) => false,
+			_ => bool::decode(input)?,
+		};
+		Ok(Self { begin, allow_later })
+	}
+}
+
+/// Look up the encoded BEEFY justification of block `begin` in `client`.
+///
+/// If `allow_later` is set and `begin` has no justification, the first justification found
+/// within the next finalized blocks (bounded by `MAX_LATER_JUSTIF_SEARCH`) is returned instead.
+pub fn find_justification<B, Client>(
+	client: &Client,
+	begin: NumberFor<B>,
+	allow_later: bool,
+) -> Option<EncodedJustification>
+where
+	B: Block,
+	Client: BlockBackend<B> + HeaderBackend<B>,
+{
+	let max_blocks = if allow_later {
+		// Only finalized blocks can have justifications, don't search beyond them.
+		let finalized = client.info().finalized_number;
+		if begin > finalized {
+			return None
+		}
+		let remaining: u32 = (finalized - begin).saturated_into();
+		remaining.saturating_add(1).min(MAX_LATER_JUSTIF_SEARCH)
+	} else {
+		1
+	};
+	let mut number = begin;
+	for _ in 0..max_blocks {
+		// Stop at the first unknown block, there's nothing beyond it.
+		let hash = client.block_hash(number).ok().flatten()?;
+		let justif = client
+			.justifications(hash)
+			.ok()
+			.flatten()
+			.and_then(|justifs| justifs.get(BEEFY_ENGINE_ID).cloned());
+		if justif.is_some() {
+			return justif
+		}
+		number = number.saturating_add(1u32.into());
+	}
+	None
+}
+
+/// Request for a BEEFY justification of `block` or of any later block,
+/// sent by the RPC layer to the voter.
+pub struct CoveringJustificationRequest<B: Block> {
+	/// Lowest block the justification may be for.
+	pub block: NumberFor<B>,
+	/// Sender for the found justification.
+	///
+	/// Dropped without answer if the voter gives up on the request.
+	pub pending_response: oneshot::Sender<BeefyVersionedFinalityProof<B>>,
+}
+
+/// Handle used by the RPC layer to have the voter fetch BEEFY justifications from the network.
+#[derive(Clone)]
+pub struct BeefyJustifsRequester<B: Block> {
+	sender: async_channel::Sender<CoveringJustificationRequest<B>>,
+}
+
+impl<B: Block> BeefyJustifsRequester<B> {
+	/// Create a new requester and the receiving end to be handed to the voter.
+	pub fn new() -> (Self, async_channel::Receiver<CoveringJustificationRequest<B>>) {
+		let (sender, receiver) = async_channel::bounded(RPC_JUSTIF_CHANNEL_SIZE);
+		(Self { sender }, receiver)
+	}
+
+	/// Ask the voter for a justification of `block` or of any later block.
+	///
+	/// Returns `None` if the voter is not running, has too many requests in flight,
+	/// or gave up on the request. The voter keeps looking until the returned future
+	/// is dropped, so callers should bound the wait with a timeout.
+	pub async fn request(&self, block: NumberFor<B>) -> Option<BeefyVersionedFinalityProof<B>> {
+		let (tx, rx) = oneshot::channel();
+		self.sender
+			.try_send(CoveringJustificationRequest { block, pending_response: tx })
+			.ok()?;
+		rx.await.ok()
+	}
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -18,7 +18,7 @@
 
 //! Generating request logic for request/response protocol for syncing BEEFY justifications.
 
-use codec::Encode;
+use codec::{DecodeAll, Encode};
 use futures::channel::{oneshot, oneshot::Canceled};
 use log::{debug, warn};
 use parking_lot::Mutex;
@@ -26,6 +26,7 @@ use sc_network::{
 	request_responses::{IfDisconnected, RequestFailure},
 	NetworkRequest, PeerId, ProtocolName,
 };
+use sp_consensus::Error as ConsensusError;
 use sp_consensus_beefy::{ecdsa_crypto::AuthorityId, ValidatorSet};
 use sp_runtime::traits::{Block, NumberFor};
 use std::{collections::VecDeque, result::Result, sync::Arc};
@@ -36,7 +37,10 @@ use crate::{
 		peers::PeerReport,
 		request_response::{Error, JustificationRequest, BEEFY_SYNC_LOG_TARGET},
 	},
-	justification::{decode_and_verify_finality_proof, BeefyVersionedFinalityProof},
+	justification::{
+		decode_and_verify_finality_proof, proof_block_num_and_set_id, verify_with_validator_set,
+		BeefyVersionedFinalityProof,
+	},
 	metric_inc,
 	metrics::{register_metrics, OnDemandOutgoingRequestsMetrics},
 	KnownPeers,
@@ -51,6 +55,7 @@ type ResponseReceiver = oneshot::Receiver<Response>;
 struct RequestInfo<B: Block> {
 	block: NumberFor<B>,
 	active_set: ValidatorSet<AuthorityId>,
+	allow_later: bool,
 }
 
 enum State<B: Block> {
@@ -71,6 +76,7 @@ pub(crate) enum ResponseInfo<B: Block> {
 pub struct OnDemandJustificationsEngine<B: Block> {
 	network: Arc<dyn NetworkRequest + Send + Sync>,
 	protocol_name: ProtocolName,
+	covering_protocol_name: ProtocolName,
 
 	live_peers: Arc<Mutex<KnownPeers<B>>>,
 	peers_cache: VecDeque<PeerId>,
@@ -83,6 +89,7 @@ impl<B: Block> OnDemandJustificationsEngine<B> {
 	pub fn new(
 		network: Arc<dyn NetworkRequest + Send + Sync>,
 		protocol_name: ProtocolName,
+		covering_protocol_name: ProtocolName,
 		live_peers: Arc<Mutex<KnownPeers<B>>>,
 		prometheus_registry: Option<prometheus::Registry>,
 	) -> Self {
@@ -90,6 +97,7 @@ impl<B: Block> OnDemandJustificationsEngine<B> {
 		Self {
 			network,
 			protocol_name,
+			covering_protocol_name,
 			live_peers,
 			peers_cache: VecDeque::new(),
 			state: State::Idle,
@@ -117,13 +125,22 @@ impl<B: Block> OnDemandJustificationsEngine<B> {
 			"🥩 requesting justif #{:?} from peer {:?}", req_info.block, peer,
 		);
 
-		let payload = JustificationRequest::<B> { begin: req_info.block }.encode();
+		let payload =
+			JustificationRequest::<B> { begin: req_info.block, allow_later: req_info.allow_later }
+				.encode();
 
+		// Peers that don't know about covering requests don't support the covering protocol
+		// either, so they never get to see one. They fail the request and we move on.
+		let protocol_name = if req_info.allow_later {
+			self.covering_protocol_name.clone()
+		} else {
+			self.protocol_name.clone()
+		};
 		let (tx, rx) = oneshot::channel();
 
 		self.network.start_request(
 			peer,
-			self.protocol_name.clone(),
+			protocol_name,
 			payload,
 			tx,
 			IfDisconnected::ImmediateError,
@@ -136,16 +153,32 @@ impl<B: Block> OnDemandJustificationsEngine<B> {
 	///
 	/// `active_set` will be used to verify validity of potential responses.
 	pub fn request(&mut self, block: NumberFor<B>, active_set: ValidatorSet<AuthorityId>) {
+		self.start_request(RequestInfo { block, active_set, allow_later: false })
+	}
+
+	/// Start new request for a justification of `block` or of any later block signed by
+	/// `active_set`, if no other request is in progress.
+	pub fn request_covering(&mut self, block: NumberFor<B>, active_set: ValidatorSet<AuthorityId>) {
+		self.start_request(RequestInfo { block, active_set, allow_later: true })
+	}
+
+	/// Returns `true` if there's no request in progress.
+	pub fn is_idle(&self) -> bool {
+		matches!(self.state, State::Idle)
+	}
+
+	fn start_request(&mut self, req_info: RequestInfo<B>) {
 		// ignore new requests while there's already one pending
-		if matches!(self.state, State::AwaitingResponse(_, _, _)) {
+		if !self.is_idle() {
 			return
 		}
+		let block = req_info.block;
 		self.reset_peers_cache_for_block(block);
 
 		// Start the requests engine - each unsuccessful received response will automatically
 		// trigger a new request to the next peer in the `peers_cache` until there are none left.
 		if let Some(peer) = self.try_next_peer() {
-			self.request_from_peer(peer, RequestInfo { block, active_set });
+			self.request_from_peer(peer, req_info);
 		} else {
 			metric_inc!(self, beefy_on_demand_justification_no_peer_to_request_from);
 			debug!(
@@ -205,23 +238,30 @@ impl<B: Block> OnDemandJustificationsEngine<B> {
 				}
 			})
 			.and_then(|encoded| {
-				decode_and_verify_finality_proof::<B>(
-					&encoded[..],
-					req_info.block,
-					&req_info.active_set,
+				decode_and_verify_response::<B>(&encoded[..], req_info).map_err(
+					|(err, signatures_checked)| {
+						if let ConsensusError::InvalidAuthoritiesSet = err {
+							// Valid justification of a later block, but signed by a validator
+							// set we can't check. Not the peer's fault, just try the next one.
+							debug!(
+								target: BEEFY_SYNC_LOG_TARGET,
+								"🥩 for on demand justification #{:?}, peer {:?} responded with proof of another validator set",
+								req_info.block, peer
+							);
+							return Error::ResponseError
+						}
+						metric_inc!(self, beefy_on_demand_justification_invalid_proof);
+						debug!(
+							target: BEEFY_SYNC_LOG_TARGET,
+							"🥩 for on demand justification #{:?}, peer {:?} responded with invalid proof: {:?}",
+							req_info.block, peer, err
+						);
+						let mut cost = cost::INVALID_PROOF;
+						cost.value +=
+							cost::PER_SIGNATURE_CHECKED.saturating_mul(signatures_checked as i32);
+						Error::InvalidResponse(PeerReport { who: *peer, cost_benefit: cost })
+					},
 				)
-				.map_err(|(err, signatures_checked)| {
-					metric_inc!(self, beefy_on_demand_justification_invalid_proof);
-					debug!(
-						target: BEEFY_SYNC_LOG_TARGET,
-						"🥩 for on demand justification #{:?}, peer {:?} responded with invalid proof: {:?}",
-						req_info.block, peer, err
-					);
-					let mut cost = cost::INVALID_PROOF;
-					cost.value +=
-						cost::PER_SIGNATURE_CHECKED.saturating_mul(signatures_checked as i32);
-					Error::InvalidResponse(PeerReport { who: *peer, cost_benefit: cost })
-				})
 			})
 	}
 
@@ -271,3 +311,25 @@ impl<B: Block> OnDemandJustificationsEngine<B> {
 		}
 	}
 }
+
+/// Decode and verify a response to a request described by `req_info`.
+fn decode_and_verify_response<B: Block>(
+	encoded: &[u8],
+	req_info: &RequestInfo<B>,
+) -> Result<BeefyVersionedFinalityProof<B>, (ConsensusError, u32)> {
+	if !req_info.allow_later {
+		return decode_and_verify_finality_proof::<B>(encoded, req_info.block, &req_info.active_set)
+	}
+	// Any block starting with the requested one is good, as long as it was signed by
+	// the validator set we know of.
+	let proof = <BeefyVersionedFinalityProof<B>>::decode_all(&mut &*encoded)
+		.map_err(|_| (ConsensusError::InvalidJustification, 0))?;
+	let (block, set_id) = proof_block_num_and_set_id::<B>(&proof);
+	if block < req_info.block {
+		return Err((ConsensusError::InvalidJustification, 0))
+	}
+	if set_id != req_info.active_set.id() {
+		return Err((ConsensusError::InvalidAuthoritiesSet, 0))
+	}
+	verify_with_validator_set::<B>(block, &req_info.active_set, &proof).map(|_| proof)
+}
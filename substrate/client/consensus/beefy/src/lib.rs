@@ -25,6 +25,7 @@ use crate::{
 		peers::KnownPeers,
 		request_response::{
 			outgoing_requests_engine::OnDemandJustificationsEngine, BeefyJustifsRequestHandler,
+			BeefyJustifsRequester, CoveringJustificationRequest,
 		},
 	},
 	import::BeefyBlockImport,
@@ -53,7 +54,7 @@ use sp_keystore::KeystorePtr;
 use sp_mmr_primitives::MmrApi;
 use sp_runtime::traits::{Block, Header as HeaderT, NumberFor, Zero};
 use std::{
-	collections::{BTreeMap, VecDeque},
+	collections::{BTreeMap, BTreeSet, VecDeque},
 	marker::PhantomData,
 	sync::Arc,
 };
@@ -70,7 +71,8 @@ pub mod import;
 pub mod justification;
 
 pub use communication::beefy_protocol_name::{
-	gossip_protocol_name, justifications_protocol_name as justifs_protocol_name,
+	covering_justifications_protocol_name as covering_justifs_protocol_name, gossip_protocol_name,
+	justifications_protocol_name as justifs_protocol_name,
 };
 
 #[cfg(test)]
@@ -118,6 +120,10 @@ pub struct BeefyVoterLinks<B: Block> {
 	pub to_rpc_justif_sender: BeefyVersionedFinalityProofSender<B>,
 	/// Sends BEEFY best block hashes from voter to RPC.
 	pub to_rpc_best_block_sender: BeefyBestBlockSender<B>,
+
+	// RPC -> Voter links
+	/// Stream of requests for justifications to be fetched from the network.
+	pub from_rpc_justif_requests: async_channel::Receiver<CoveringJustificationRequest<B>>,
 }
 
 /// Links used by the BEEFY RPC layer, from the BEEFY background voter.
@@ -127,6 +133,8 @@ pub struct BeefyRPCLinks<B: Block> {
 	pub from_voter_justif_stream: BeefyVersionedFinalityProofStream<B>,
 	/// Stream of BEEFY best block hashes coming from the voter.
 	pub from_voter_best_beefy_stream: BeefyBestBlockStream<B>,
+	/// Handle to have the voter fetch justifications from the network.
+	pub to_voter_justif_requester: BeefyJustifsRequester<B>,
 }
 
 /// Make block importer and link half necessary to tie the background voter to it.
@@ -149,6 +157,9 @@ where
 	let (to_rpc_best_block_sender, from_voter_best_beefy_stream) =
 		BeefyBestBlockStream::<B>::channel();
 
+	// RPC -> Voter links
+	let (to_voter_justif_requester, from_rpc_justif_requests) = BeefyJustifsRequester::<B>::new();
+
 	// BlockImport -> Voter links
 	let (to_voter_justif_sender, from_block_import_justif_stream) =
 		BeefyVersionedFinalityProofStream::<B>::channel();
@@ -166,8 +177,13 @@ where
 		from_block_import_justif_stream,
 		to_rpc_justif_sender,
 		to_rpc_best_block_sender,
+		from_rpc_justif_requests,
+	};
+	let rpc_links = BeefyRPCLinks {
+		from_voter_best_beefy_stream,
+		from_voter_justif_stream,
+		to_voter_justif_requester,
 	};
-	let rpc_links = BeefyRPCLinks { from_voter_best_beefy_stream, from_voter_justif_stream };
 
 	(import, voter_links, rpc_links)
 }
@@ -186,6 +202,10 @@ pub struct BeefyNetworkParams<B: Block, N, S> {
 	/// Chain specific BEEFY on-demand justifications protocol name. See
 	/// [`communication::beefy_protocol_name::justifications_protocol_name`].
 	pub justifications_protocol_name: ProtocolName,
+	/// Chain specific BEEFY on-demand justifications protocol name, for requests also
+	/// accepting justifications of later blocks. See
+	/// [`communication::beefy_protocol_name::covering_justifications_protocol_name`].
+	pub covering_justifications_protocol_name: ProtocolName,
 
 	pub _phantom: PhantomData<B>,
 }
@@ -248,6 +268,7 @@ pub async fn start_beefy_gadget<B, BE, C, N, P, R, S>(
 		notification_service,
 		gossip_protocol_name,
 		justifications_protocol_name,
+		covering_justifications_protocol_name,
 		..
 	} = network_params;
 
@@ -261,7 +282,7 @@ pub async fn start_beefy_gadget<B, BE, C, N, P, R, S>(
 	let known_peers = Arc::new(Mutex::new(KnownPeers::new()));
 	// Default votes filter is to discard everything.
 	// Validator is updated later with correct starting round and set id.
-	let (gossip_validator, gossip_report_stream) =
+	let (gossip_validator, gossip_report_stream, future_votes_stream) =
 		communication::gossip::GossipValidator::new(known_peers.clone());
 	let gossip_validator = Arc::new(gossip_validator);
 	let gossip_engine = GossipEngine::new(
@@ -278,6 +299,7 @@ pub async fn start_beefy_gadget<B, BE, C, N, P, R, S>(
 	let on_demand_justifications = OnDemandJustificationsEngine::new(
 		network.clone(),
 		justifications_protocol_name.clone(),
+		covering_justifications_protocol_name.clone(),
 		known_peers,
 		prometheus_registry.clone(),
 	);
@@ -285,6 +307,7 @@ pub async fn start_beefy_gadget<B, BE, C, N, P, R, S>(
 		gossip_engine,
 		gossip_validator,
 		gossip_report_stream,
+		future_votes_stream,
 		on_demand_justifications,
 	};
 
@@ -332,6 +355,9 @@ pub async fn start_beefy_gadget<B, BE, C, N, P, R, S>(
 			links: links.clone(),
 			metrics: metrics.clone(),
 			pending_justifications: BTreeMap::new(),
+			pending_rpc_justif_requests: Vec::new(),
+			reported_future_voters: BTreeSet::new(),
+			fork_check_cache: None,
 			persisted_state,
 		};
 
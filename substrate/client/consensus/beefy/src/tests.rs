@@ -55,9 +55,9 @@ use sp_consensus_beefy::{
 	ecdsa_crypto::{AuthorityId, Signature},
 	known_payloads,
 	mmr::{find_mmr_root_digest, MmrRootProvider},
-	BeefyApi, Commitment, ConsensusLog, EquivocationProof, Keyring as BeefyKeyring, MmrRootHash,
-	OpaqueKeyOwnershipProof, Payload, SignedCommitment, ValidatorSet, ValidatorSetId,
-	VersionedFinalityProof, VoteMessage, BEEFY_ENGINE_ID,
+	BeefyApi, Commitment, ConsensusLog, EquivocationProof, ForkVotingProof, FutureBlockVotingProof,
+	Keyring as BeefyKeyring, MmrRootHash, OpaqueKeyOwnershipProof, Payload, SignedCommitment,
+	ValidatorSet, ValidatorSetId, VersionedFinalityProof, VoteMessage, BEEFY_ENGINE_ID,
 };
 use sp_core::H256;
 use sp_keystore::{testing::MemoryKeystore, Keystore, KeystorePtr};
@@ -68,7 +68,7 @@ use sp_runtime::{
 	BuildStorage, DigestItem, EncodedJustification, Justifications, Storage,
 };
 use std::{marker::PhantomData, sync::Arc, task::Poll};
-use substrate_test_runtime_client::{BlockBuilderExt, ClientExt};
+use substrate_test_runtime_client::{runtime::Header, BlockBuilderExt, ClientExt};
 use tokio::time::Duration;
 
 const GENESIS_HASH: H256 = H256::zero();
@@ -121,15 +121,17 @@ impl BeefyTestNet {
 		let mut net = BeefyTestNet { peers: Vec::with_capacity(n_authority), beefy_genesis };
 
 		for i in 0..n_authority {
-			let (rx, cfg) = on_demand_justifications_protocol_config(GENESIS_HASH, None);
-			let justif_protocol_name = cfg.name.clone();
+			let (rx, cfgs) = on_demand_justifications_protocol_config(GENESIS_HASH, None);
+			let justif_protocol_name = cfgs[0].name.clone();
+			let covering_justif_protocol_name = cfgs[1].name.clone();
 
-			net.add_authority_peer(vec![cfg]);
+			net.add_authority_peer(cfgs);
 
 			let client = net.peers[i].client().as_client();
 			let justif_handler = BeefyJustifsRequestHandler {
 				request_receiver: rx,
 				justif_protocol_name,
+				covering_justif_protocol_name,
 				client,
 				_block: PhantomData,
 				metrics: None,
@@ -252,6 +254,10 @@ pub(crate) struct TestApi {
 	pub mmr_root_hash: MmrRootHash,
 	pub reported_equivocations:
 		Option<Arc<Mutex<Vec<EquivocationProof<NumberFor<Block>, AuthorityId, Signature>>>>>,
+	pub reported_fork_votes:
+		Option<Arc<Mutex<Vec<ForkVotingProof<NumberFor<Block>, AuthorityId, Signature, Header>>>>>,
+	pub reported_future_votes:
+		Option<Arc<Mutex<Vec<FutureBlockVotingProof<NumberFor<Block>, AuthorityId, Signature>>>>>,
 }
 
 impl TestApi {
@@ -265,6 +271,8 @@ impl TestApi {
 			validator_set: Some(validator_set.clone()),
 			mmr_root_hash,
 			reported_equivocations: None,
+			reported_fork_votes: None,
+			reported_future_votes: None,
 		}
 	}
 
@@ -274,11 +282,15 @@ impl TestApi {
 			validator_set: Some(validator_set.clone()),
 			mmr_root_hash: GOOD_MMR_ROOT,
 			reported_equivocations: None,
+			reported_fork_votes: None,
+			reported_future_votes: None,
 		}
 	}
 
 	pub fn allow_equivocations(&mut self) {
 		self.reported_equivocations = Some(Arc::new(Mutex::new(vec![])));
+		self.reported_fork_votes = Some(Arc::new(Mutex::new(vec![])));
+		self.reported_future_votes = Some(Arc::new(Mutex::new(vec![])));
 	}
 }
 
@@ -316,6 +328,30 @@ sp_api::mock_impl_runtime_apis! {
 			}
 		}
 
+		fn submit_report_fork_voting_unsigned_extrinsic(
+			proof: ForkVotingProof<NumberFor<Block>, AuthorityId, Signature, Header>,
+			_dummy: OpaqueKeyOwnershipProof,
+		) -> Option<()> {
+			if let Some(fork_votes_buf) = self.inner.reported_fork_votes.as_ref() {
+				fork_votes_buf.lock().push(proof);
+				None
+			} else {
+				panic!("Fork votes not expected, but following proof was reported: {:?}", proof);
+			}
+		}
+
+		fn submit_report_future_block_voting_unsigned_extrinsic(
+			proof: FutureBlockVotingProof<NumberFor<Block>, AuthorityId, Signature>,
+			_dummy: OpaqueKeyOwnershipProof,
+		) -> Option<()> {
+			if let Some(future_votes_buf) = self.inner.reported_future_votes.as_ref() {
+				future_votes_buf.lock().push(proof);
+				None
+			} else {
+				panic!("Future votes not expected, but following proof was reported: {:?}", proof);
+			}
+		}
+
 		fn generate_key_ownership_proof(
 			_dummy1: ValidatorSetId,
 			_dummy2: AuthorityId,
@@ -329,7 +365,7 @@ sp_api::mock_impl_runtime_apis! {
 	}
 }
 
-fn add_mmr_digest(builder: &mut impl BlockBuilderExt, mmr_hash: MmrRootHash) {
+pub(crate) fn add_mmr_digest(builder: &mut impl BlockBuilderExt, mmr_hash: MmrRootHash) {
 	builder
 		.push_deposit_log_digest_item(DigestItem::Consensus(
 			BEEFY_ENGINE_ID,
@@ -366,7 +402,7 @@ async fn voter_init_setup(
 ) -> sp_blockchain::Result<PersistedState<Block>> {
 	let backend = net.peer(0).client().as_backend();
 	let known_peers = Arc::new(Mutex::new(KnownPeers::new()));
-	let (gossip_validator, _) = GossipValidator::new(known_peers);
+	let (gossip_validator, _, _) = GossipValidator::new(known_peers);
 	let gossip_validator = Arc::new(gossip_validator);
 	let mut gossip_engine = sc_network_gossip::GossipEngine::new(
 		net.peer(0).network_service().clone(),
@@ -421,6 +457,8 @@ where
 			notification_service: notification_services.remove(&peer_id).unwrap(),
 			gossip_protocol_name: beefy_gossip_proto_name(),
 			justifications_protocol_name: on_demand_justif_handler.protocol_name(),
+			covering_justifications_protocol_name: on_demand_justif_handler
+				.covering_protocol_name(),
 			_phantom: PhantomData,
 		};
 		let payload_provider = MmrRootProvider::new(api.clone());
@@ -469,7 +507,7 @@ pub(crate) fn get_beefy_streams(
 	let mut versioned_finality_proof_streams = Vec::new();
 	peers.for_each(|(index, _)| {
 		let beefy_rpc_links = net.peer(index).data.beefy_rpc_links.lock().clone().unwrap();
-		let BeefyRPCLinks { from_voter_justif_stream, from_voter_best_beefy_stream } =
+		let BeefyRPCLinks { from_voter_justif_stream, from_voter_best_beefy_stream, .. } =
 			beefy_rpc_links;
 		best_block_streams.push(from_voter_best_beefy_stream.subscribe(100_000));
 		versioned_finality_proof_streams.push(from_voter_justif_stream.subscribe(100_000));
@@ -1060,7 +1098,7 @@ async fn should_initialize_voter_at_custom_genesis() {
 	// doesn't allow creating a new `GossipEngine` as the notification handle is consumed by the
 	// first `GossipEngine`
 	let known_peers = Arc::new(Mutex::new(KnownPeers::new()));
-	let (gossip_validator, _) = GossipValidator::new(known_peers);
+	let (gossip_validator, _, _) = GossipValidator::new(known_peers);
 	let gossip_validator = Arc::new(gossip_validator);
 	let mut gossip_engine = sc_network_gossip::GossipEngine::new(
 		net.peer(0).network_service().clone(),
@@ -1406,7 +1444,7 @@ async fn gossipped_finality_proofs() {
 	let charlie = &mut net.peers[2];
 	let known_peers = Arc::new(Mutex::new(KnownPeers::<Block>::new()));
 	// Charlie will run just the gossip engine and not the full voter.
-	let (gossip_validator, _) = GossipValidator::new(known_peers);
+	let (gossip_validator, _, _) = GossipValidator::new(known_peers);
 	let charlie_gossip_validator = Arc::new(gossip_validator);
 	charlie_gossip_validator.update_filter(GossipFilterCfg::<Block> {
 		start: 1,
@@ -1522,3 +1560,20 @@ async fn gossipped_finality_proofs() {
 		}
 	}
 }
+
+#[test]
+fn justification_request_encoding_is_backwards_compatible() {
+	use crate::communication::request_response::JustificationRequest;
+
+	let plain = JustificationRequest::<Block> { begin: 42, allow_later: false };
+	// Plain requests are encoded the way peers not knowing about `allow_later` expect.
+	assert_eq!(plain.encode(), 42u64.encode());
+	assert_eq!(JustificationRequest::<Block>::decode(&mut &plain.encode()[..]).unwrap(), plain);
+
+	let covering = JustificationRequest::<Block> { begin: 42, allow_later: true };
+	assert_eq!(covering.encode(), (42u64, true).encode());
+	assert_eq!(
+		JustificationRequest::<Block>::decode(&mut &covering.encode()[..]).unwrap(),
+		covering
+	);
+}
@@ -20,7 +20,10 @@ use crate::{
 	communication::{
 		gossip::{proofs_topic, votes_topic, GossipFilterCfg, GossipMessage, GossipValidator},
 		peers::PeerReport,
-		request_response::outgoing_requests_engine::{OnDemandJustificationsEngine, ResponseInfo},
+		request_response::{
+			outgoing_requests_engine::{OnDemandJustificationsEngine, ResponseInfo},
+			CoveringJustificationRequest, MAX_PENDING_RPC_JUSTIF_REQUESTS,
+		},
 	},
 	error::Error,
 	justification::BeefyVersionedFinalityProof,
@@ -36,13 +39,15 @@ use log::{debug, error, info, log_enabled, trace, warn};
 use sc_client_api::{Backend, FinalityNotification, FinalityNotifications, HeaderBackend};
 use sc_network_gossip::GossipEngine;
 use sc_utils::{mpsc::TracingUnboundedReceiver, notification::NotificationReceiver};
-use sp_api::ProvideRuntimeApi;
+use sp_api::{ApiExt, ProvideRuntimeApi};
 use sp_arithmetic::traits::{AtLeast32Bit, Saturating};
+use sp_blockchain::Backend as BlockchainBackend;
 use sp_consensus::SyncOracle;
 use sp_consensus_beefy::{
-	check_equivocation_proof,
+	check_equivocation_proof, check_fork_equivocation_proof, check_future_block_voting_proof,
 	ecdsa_crypto::{AuthorityId, Signature},
-	BeefyApi, Commitment, ConsensusLog, EquivocationProof, PayloadProvider, ValidatorSet,
+	BeefyApi, Commitment, ConsensusLog, EquivocationProof, ForkVotingProof, FutureBlockVotingProof,
+	OpaqueKeyOwnershipProof, Payload, PayloadProvider, ValidatorSet, ValidatorSetId,
 	VersionedFinalityProof, VoteMessage, BEEFY_ENGINE_ID,
 };
 use sp_runtime::{
@@ -203,6 +208,19 @@ impl<B: Block> VoterOracle<B> {
 		})
 	}
 
+	/// Return the validator set active at `block`, if `block` is GRANDPA finalized and
+	/// belongs to one of the tracked sessions.
+	pub fn validator_set_at(&self, block: NumberFor<B>) -> Option<&ValidatorSet<AuthorityId>> {
+		if block > *self.best_grandpa_block_header.number() {
+			return None
+		}
+		self.sessions
+			.iter()
+			.rev()
+			.find(|rounds| rounds.session_start() <= block)
+			.map(|rounds| rounds.validator_set())
+	}
+
 	/// Return `(A, B)` tuple representing inclusive [A, B] interval of votes to accept.
 	pub fn accepted_interval(&self) -> Result<(NumberFor<B>, NumberFor<B>), Error> {
 		let rounds = self.sessions.front().ok_or(Error::UninitSession)?;
@@ -320,6 +338,8 @@ pub(crate) struct BeefyComms<B: Block> {
 	pub gossip_engine: GossipEngine<B>,
 	pub gossip_validator: Arc<GossipValidator<B>>,
 	pub gossip_report_stream: TracingUnboundedReceiver<PeerReport>,
+	pub future_votes_stream:
+		TracingUnboundedReceiver<VoteMessage<NumberFor<B>, AuthorityId, Signature>>,
 	pub on_demand_justifications: OnDemandJustificationsEngine<B>,
 }
 
@@ -344,6 +364,12 @@ pub(crate) struct BeefyWorker<B: Block, BE, P, RuntimeApi, S> {
 	pub metrics: Option<VoterMetrics>,
 	/// Buffer holding justifications for future processing.
 	pub pending_justifications: BTreeMap<NumberFor<B>, BeefyVersionedFinalityProof<B>>,
+	/// Justification requests from the RPC layer, waiting for a justification.
+	pub pending_rpc_justif_requests: Vec<CoveringJustificationRequest<B>>,
+	/// Voters already reported for voting on future blocks, by set id.
+	pub reported_future_voters: BTreeSet<(ValidatorSetId, AuthorityId)>,
+	/// Canonical header and payload of the last block votes were checked against for forks.
+	pub fork_check_cache: Option<(B::Header, Option<Payload>)>,
 	/// Persisted voter state.
 	pub persisted_state: PersistedState<B>,
 }
@@ -424,6 +450,7 @@ where
 		}
 
 		let id = validator_set.id();
+		self.reported_future_voters.retain(|(set_id, _)| *set_id >= id);
 		self.persisted_state
 			.voting_oracle
 			.add_session(Rounds::new(new_session_start, validator_set));
@@ -505,7 +532,10 @@ where
 	) -> Result<(), Error> {
 		let block_num = vote.commitment.block_number;
 		match self.voting_oracle().triage_round(block_num)? {
-			RoundAction::Process =>
+			RoundAction::Process => {
+				if let Err(err) = self.report_fork_vote_if_any(&vote) {
+					debug!(target: LOG_TARGET, "🥩 {}", err);
+				}
 				if let Some(finality_proof) = self.handle_vote(vote)? {
 					let gossip_proof = GossipMessage::<B>::FinalityProof(finality_proof);
 					let encoded_proof = gossip_proof.encode();
@@ -514,7 +544,8 @@ where
 						encoded_proof,
 						true,
 					);
-				},
+				}
+			},
 			RoundAction::Drop => metric_inc!(self, beefy_stale_votes),
 			RoundAction::Enqueue => error!(target: LOG_TARGET, "🥩 unexpected vote: {:?}.", vote),
 		};
@@ -647,6 +678,8 @@ where
 			);
 		}
 
+		self.answer_rpc_justif_requests(&finality_proof);
+
 		self.links
 			.to_rpc_justif_sender
 			.notify(|| Ok::<_, ()>(finality_proof))
@@ -659,6 +692,62 @@ where
 		Ok(())
 	}
 
+	/// Handle justification request coming from the RPC layer.
+	///
+	/// Requests for blocks already finalized by BEEFY are answered right away with the
+	/// justification of the best BEEFY block, others wait for a justification to be
+	/// finalized or fetched from the network.
+	fn handle_rpc_justif_request(&mut self, request: CoveringJustificationRequest<B>) {
+		let best_beefy = self.persisted_state.voting_oracle.best_beefy_block;
+		if request.block > best_beefy {
+			self.pending_rpc_justif_requests
+				.retain(|request| !request.pending_response.is_canceled());
+			if self.pending_rpc_justif_requests.len() >= MAX_PENDING_RPC_JUSTIF_REQUESTS {
+				// Dropping the request closes its response channel, the caller gets an error.
+				debug!(
+					target: LOG_TARGET,
+					"🥩 Too many pending RPC justification requests, dropping #{:?}.", request.block
+				);
+				return
+			}
+			debug!(target: LOG_TARGET, "🥩 RPC request for justification #{:?}.", request.block);
+			self.pending_rpc_justif_requests.push(request);
+			return
+		}
+		let maybe_proof = self
+			.backend
+			.blockchain()
+			.expect_block_hash_from_id(&BlockId::Number(best_beefy))
+			.and_then(|hash| self.backend.blockchain().justifications(hash))
+			.ok()
+			.flatten()
+			.and_then(|justifs| justifs.get(BEEFY_ENGINE_ID).cloned())
+			.and_then(|encoded| BeefyVersionedFinalityProof::<B>::decode_all(&mut &*encoded).ok());
+		match maybe_proof {
+			Some(proof) => {
+				let _ = request.pending_response.send(proof);
+			},
+			None => debug!(
+				target: LOG_TARGET,
+				"🥩 Missing justification of best BEEFY block #{:?}.", best_beefy
+			),
+		}
+	}
+
+	/// Answer all RPC justification requests covered by `finality_proof`.
+	fn answer_rpc_justif_requests(&mut self, finality_proof: &BeefyVersionedFinalityProof<B>) {
+		let block_num = match finality_proof {
+			VersionedFinalityProof::V1(ref sc) => sc.commitment.block_number,
+		};
+		let (covered, pending) = std::mem::take(&mut self.pending_rpc_justif_requests)
+			.into_iter()
+			.partition::<Vec<_>, _>(|request| request.block <= block_num);
+		self.pending_rpc_justif_requests = pending;
+		for request in covered {
+			let _ = request.pending_response.send(finality_proof.clone());
+		}
+	}
+
 	/// Handle previously buffered justifications, that now land in the voting interval.
 	fn try_pending_justifications(&mut self) -> Result<(), Error> {
 		// Interval of blocks for which we can process justifications and votes right now.
@@ -810,6 +899,17 @@ where
 				// This only starts new request if there isn't already an active one.
 				self.comms.on_demand_justifications.request(block, active);
 			}
+			// Use the remaining capacity to fetch justifications requested over RPC.
+			if self.comms.on_demand_justifications.is_idle() {
+				self.pending_rpc_justif_requests
+					.retain(|request| !request.pending_response.is_canceled());
+				let lowest = self.pending_rpc_justif_requests.iter().map(|r| r.block).min();
+				if let Some((block, active)) = lowest.and_then(|block| {
+					self.voting_oracle().validator_set_at(block).map(|set| (block, set.clone()))
+				}) {
+					self.comms.on_demand_justifications.request_covering(block, active);
+				}
+			}
 		}
 	}
 
@@ -841,6 +941,7 @@ where
 				})
 				.fuse(),
 		);
+		let mut rpc_justif_requests = self.links.from_rpc_justif_requests.clone().fuse();
 		let mut gossip_proofs = Box::pin(
 			self.comms
 				.gossip_engine
@@ -883,6 +984,8 @@ where
 				response_info = self.comms.on_demand_justifications.next().fuse() => {
 					match response_info {
 						ResponseInfo::ValidProof(justif, peer_report) => {
+							// Proofs fetched for RPC requests might be out of the voter's range.
+							self.answer_rpc_justif_requests(&justif);
 							if let Err(err) = self.triage_incoming_justif(justif) {
 								debug!(target: LOG_TARGET, "🥩 {}", err);
 							}
@@ -929,6 +1032,20 @@ where
 						break Error::VotesGossipStreamTerminated;
 					}
 				},
+				request = rpc_justif_requests.next() => {
+					if let Some(request) = request {
+						self.handle_rpc_justif_request(request);
+					}
+				},
+				// Check votes for rounds beyond best GRANDPA, these don't change voter state.
+				vote = self.comms.future_votes_stream.next() => {
+					if let Some(vote) = vote {
+						if let Err(err) = self.report_future_vote_if_any(vote) {
+							debug!(target: LOG_TARGET, "🥩 {}", err);
+						}
+					}
+					continue;
+				},
 				// Process peer reports.
 				report = self.comms.gossip_report_stream.next() => {
 					if let Some(PeerReport { who, cost_benefit }) = report {
@@ -955,23 +1072,145 @@ where
 		&self,
 		proof: EquivocationProof<NumberFor<B>, AuthorityId, Signature>,
 	) -> Result<(), Error> {
-		let rounds = self.persisted_state.voting_oracle.active_rounds()?;
-		let (validators, validator_set_id) = (rounds.validators(), rounds.validator_set_id());
-		let offender_id = proof.offender_id().clone();
-
 		if !check_equivocation_proof::<_, _, BeefySignatureHasher>(&proof) {
 			debug!(target: LOG_TARGET, "🥩 Skip report for bad equivocation {:?}", proof);
 			return Ok(())
-		} else if let Some(local_id) = self.key_store.authority_id(validators) {
-			if offender_id == local_id {
-				debug!(target: LOG_TARGET, "🥩 Skip equivocation report for own equivocation");
-				return Ok(())
-			}
 		}
 
-		let number = *proof.round_number();
-		let hash = self
+		let hash = self.block_hash_for_report(*proof.round_number())?;
+		let key_owner_proof = match self.offender_key_owner_proof(proof.offender_id(), hash)? {
+			Some(proof) => proof,
+			None => return Ok(()),
+		};
+
+		// submit equivocation report at **best** block
+		let best_block_hash = self.backend.blockchain().info().best_hash;
+		self.runtime
+			.runtime_api()
+			.submit_report_equivocation_unsigned_extrinsic(best_block_hash, proof, key_owner_proof)
+			.map_err(Error::RuntimeApi)?;
+
+		Ok(())
+	}
+
+	/// Check whether `vote` is for a block other than the canonical one with the same number,
+	/// and report it to the BEEFY runtime module if so.
+	///
+	/// Only votes for GRANDPA finalized blocks are processed, so the header we have for the voted
+	/// block number is canonical. Votes matching our own payload for that block are honest, so
+	/// the canonical header and payload are cached to only look them up once per round.
+	fn report_fork_vote_if_any(
+		&mut self,
+		vote: &VoteMessage<NumberFor<B>, AuthorityId, Signature>,
+	) -> Result<(), Error> {
+		let number = vote.commitment.block_number;
+		let (header, payload) = match self.fork_check_cache.take() {
+			Some((header, payload)) if *header.number() == number => (header, payload),
+			_ => {
+				let hash = self.block_hash_for_report(number)?;
+				let header = self
+					.backend
+					.blockchain()
+					.expect_header(hash)
+					.map_err(|err| Error::Backend(err.to_string()))?;
+				let payload = self.payload_provider.payload(&header);
+				(header, payload)
+			},
+		};
+		let honest = payload.as_ref() == Some(&vote.commitment.payload);
+		self.fork_check_cache = Some((header.clone(), payload));
+		if honest {
+			return Ok(())
+		}
+
+		let hash = header.hash();
+		let mut proof = ForkVotingProof { vote: vote.clone(), header, ancestry_proof: None };
+		if !check_fork_equivocation_proof::<_, BeefySignatureHasher, _>(&proof, &hash) {
+			return Ok(())
+		}
+
+		debug!(target: LOG_TARGET, "🥩 Detected vote on non-canonical fork: {:?}", proof.vote);
+		metric_inc!(self, beefy_equivocation_votes);
+		// submit fork voting report at **best** block
+		let best_block_hash = self.backend.blockchain().info().best_hash;
+		if !self.runtime_supports_fork_voting_reports(best_block_hash)? {
+			return Ok(())
+		}
+		// The runtime only knows the hashes of recent blocks, prove older ones canonical
+		// in the context of our best block.
+		let best_header = self
 			.backend
+			.blockchain()
+			.expect_header(best_block_hash)
+			.map_err(|err| Error::Backend(err.to_string()))?;
+		proof.ancestry_proof = self.payload_provider.ancestry_proof(&proof.header, &best_header);
+		let key_owner_proof = match self.offender_key_owner_proof(proof.offender_id(), hash)? {
+			Some(proof) => proof,
+			None => return Ok(()),
+		};
+
+		self.runtime
+			.runtime_api()
+			.submit_report_fork_voting_unsigned_extrinsic(best_block_hash, proof, key_owner_proof)
+			.map_err(Error::RuntimeApi)?;
+
+		Ok(())
+	}
+
+	/// Check whether `vote`, rejected by the gossip validator as being beyond best GRANDPA, is
+	/// for a block that hasn't been produced yet, and report it to the BEEFY runtime module if
+	/// so.
+	///
+	/// Each voter is reported at most once per validator set. Nothing is reported while major
+	/// syncing, since our best block might be far behind the rest of the network.
+	pub(crate) fn report_future_vote_if_any(
+		&mut self,
+		vote: VoteMessage<NumberFor<B>, AuthorityId, Signature>,
+	) -> Result<(), Error> {
+		if self.sync.is_major_syncing() {
+			return Ok(())
+		}
+
+		let info = self.backend.blockchain().info();
+		let proof = FutureBlockVotingProof { vote };
+		if !check_future_block_voting_proof::<_, _, BeefySignatureHasher>(&proof, info.best_number)
+		{
+			return Ok(())
+		}
+		if !self
+			.reported_future_voters
+			.insert((proof.set_id(), proof.offender_id().clone()))
+		{
+			return Ok(())
+		}
+
+		debug!(target: LOG_TARGET, "🥩 Detected vote on future block: {:?}", proof.vote);
+		metric_inc!(self, beefy_equivocation_votes);
+		if !self.runtime_supports_fork_voting_reports(info.best_hash)? {
+			return Ok(())
+		}
+		// the voted block doesn't exist, prove key ownership at **best** block
+		let key_owner_proof =
+			match self.offender_key_owner_proof(proof.offender_id(), info.best_hash)? {
+				Some(proof) => proof,
+				None => return Ok(()),
+			};
+
+		self.runtime
+			.runtime_api()
+			.submit_report_future_block_voting_unsigned_extrinsic(
+				info.best_hash,
+				proof,
+				key_owner_proof,
+			)
+			.map_err(Error::RuntimeApi)?;
+
+		Ok(())
+	}
+
+	/// Hash of the canonical block `number` that some misbehavior report is about.
+	fn block_hash_for_report(&self, number: NumberFor<B>) -> Result<B::Hash, Error> {
+		self.backend
 			.blockchain()
 			.expect_block_hash_from_id(&BlockId::Number(number))
 			.map_err(|err| {
@@ -980,30 +1219,52 @@ where
 					number, err
 				);
 				Error::Backend(err_msg)
-			})?;
-		let runtime_api = self.runtime.runtime_api();
-		// generate key ownership proof at that block
-		let key_owner_proof = match runtime_api
-			.generate_key_ownership_proof(hash, validator_set_id, offender_id)
-			.map_err(Error::RuntimeApi)?
-		{
-			Some(proof) => proof,
-			None => {
-				debug!(
-					target: LOG_TARGET,
-					"🥩 Equivocation offender not part of the authority set."
-				);
-				return Ok(())
-			},
-		};
+			})
+	}
 
-		// submit equivocation report at **best** block
-		let best_block_hash = self.backend.blockchain().info().best_hash;
-		runtime_api
-			.submit_report_equivocation_unsigned_extrinsic(best_block_hash, proof, key_owner_proof)
+	/// Generate, at block `hash`, the proof that `offender_id` is part of the active validator
+	/// set, as needed to report its misbehavior.
+	///
+	/// Returns `None` if the offender is not part of the active set, or is ourselves.
+	fn offender_key_owner_proof(
+		&self,
+		offender_id: &AuthorityId,
+		hash: B::Hash,
+	) -> Result<Option<OpaqueKeyOwnershipProof>, Error> {
+		let rounds = self.persisted_state.voting_oracle.active_rounds()?;
+		let (validators, validator_set_id) = (rounds.validators(), rounds.validator_set_id());
+
+		if let Some(local_id) = self.key_store.authority_id(validators) {
+			if *offender_id == local_id {
+				debug!(target: LOG_TARGET, "🥩 Skip equivocation report for own equivocation");
+				return Ok(None)
+			}
+		}
+
+		// generate key ownership proof at that block
+		let key_owner_proof = self
+			.runtime
+			.runtime_api()
+			.generate_key_ownership_proof(hash, validator_set_id, offender_id.clone())
 			.map_err(Error::RuntimeApi)?;
+		if key_owner_proof.is_none() {
+			debug!(target: LOG_TARGET, "🥩 Equivocation offender not part of the authority set.");
+		}
+		Ok(key_owner_proof)
+	}
 
-		Ok(())
+	/// Fork and future block voting reports are only available from `BeefyApi` version 4.
+	fn runtime_supports_fork_voting_reports(&self, at: B::Hash) -> Result<bool, Error> {
+		let version = self
+			.runtime
+			.runtime_api()
+			.api_version::<dyn BeefyApi<B, AuthorityId>>(at)
+			.map_err(Error::RuntimeApi)?;
+		let supported = version.map_or(false, |version| version >= 4);
+		if !supported {
+			debug!(target: LOG_TARGET, "🥩 Runtime doesn't support fork voting reports.");
+		}
+		Ok(supported)
 	}
 }
 
@@ -1062,10 +1323,13 @@ where
 pub(crate) mod tests {
 	use super::*;
 	use crate::{
-		communication::notification::{BeefyBestBlockStream, BeefyVersionedFinalityProofStream},
+		communication::{
+			notification::{BeefyBestBlockStream, BeefyVersionedFinalityProofStream},
+			request_response::BeefyJustifsRequester,
+		},
 		tests::{
-			create_beefy_keystore, get_beefy_streams, make_beefy_ids, BeefyPeer, BeefyTestNet,
-			TestApi,
+			add_mmr_digest, create_beefy_keystore, get_beefy_streams, make_beefy_ids, BeefyPeer,
+			BeefyTestNet, TestApi,
 		},
 		BeefyRPCLinks, KnownPeers,
 	};
@@ -1075,9 +1339,11 @@ pub(crate) mod tests {
 	use sc_network_sync::SyncingService;
 	use sc_network_test::TestNetFactory;
 	use sp_blockchain::Backend as BlockchainBackendT;
+	use sp_consensus::BlockOrigin;
 	use sp_consensus_beefy::{
-		generate_equivocation_proof, known_payloads, known_payloads::MMR_ROOT_ID,
-		mmr::MmrRootProvider, Keyring, Payload, SignedCommitment,
+		generate_equivocation_proof, generate_fork_voting_proof,
+		generate_future_block_voting_proof, known_payloads, known_payloads::MMR_ROOT_ID,
+		mmr::MmrRootProvider, Keyring, MmrRootHash, Payload, SignedCommitment,
 	};
 	use sp_runtime::traits::{Header as HeaderT, One};
 	use substrate_test_runtime_client::{
@@ -1130,14 +1396,21 @@ pub(crate) mod tests {
 		let (_, from_block_import_justif_stream) =
 			BeefyVersionedFinalityProofStream::<Block>::channel();
 
-		let beefy_rpc_links =
-			BeefyRPCLinks { from_voter_justif_stream, from_voter_best_beefy_stream };
+		let (to_voter_justif_requester, from_rpc_justif_requests) =
+			BeefyJustifsRequester::<Block>::new();
+
+		let beefy_rpc_links = BeefyRPCLinks {
+			from_voter_justif_stream,
+			from_voter_best_beefy_stream,
+			to_voter_justif_requester,
+		};
 		*peer.data.beefy_rpc_links.lock() = Some(beefy_rpc_links);
 
 		let links = BeefyVoterLinks {
 			from_block_import_justif_stream,
 			to_rpc_justif_sender,
 			to_rpc_best_block_sender,
+			from_rpc_justif_requests,
 		};
 
 		let backend = peer.client().as_backend();
@@ -1149,7 +1422,8 @@ pub(crate) mod tests {
 			.take_notification_service(&crate::tests::beefy_gossip_proto_name())
 			.unwrap();
 		let known_peers = Arc::new(Mutex::new(KnownPeers::new()));
-		let (gossip_validator, gossip_report_stream) = GossipValidator::new(known_peers.clone());
+		let (gossip_validator, gossip_report_stream, future_votes_stream) =
+			GossipValidator::new(known_peers.clone());
 		let gossip_validator = Arc::new(gossip_validator);
 		let gossip_engine = GossipEngine::new(
 			network.clone(),
@@ -1163,6 +1437,7 @@ pub(crate) mod tests {
 		let on_demand_justifications = OnDemandJustificationsEngine::new(
 			network.clone(),
 			"/beefy/justifs/1".into(),
+			"/beefy/justifs/2".into(),
 			known_peers,
 			None,
 		);
@@ -1186,6 +1461,7 @@ pub(crate) mod tests {
 			gossip_engine,
 			gossip_validator,
 			gossip_report_stream,
+			future_votes_stream,
 			on_demand_justifications,
 		};
 		BeefyWorker {
@@ -1198,6 +1474,9 @@ pub(crate) mod tests {
 			metrics,
 			sync: Arc::new(sync),
 			pending_justifications: BTreeMap::new(),
+			pending_rpc_justif_requests: Vec::new(),
+			reported_future_voters: BTreeSet::new(),
+			fork_check_cache: None,
 			persisted_state,
 		}
 	}
@@ -1684,4 +1963,95 @@ pub(crate) mod tests {
 		// verify nothing reported to runtime
 		assert!(api_alice.reported_equivocations.as_ref().unwrap().lock().is_empty());
 	}
+
+	#[tokio::test]
+	async fn should_report_future_block_votes_once_per_set() {
+		let set_id = 1;
+		let keys = [Keyring::Alice];
+		let validator_set = ValidatorSet::new(make_beefy_ids(&keys), set_id).unwrap();
+		let mut api_alice = TestApi::with_validator_set(&validator_set);
+		api_alice.allow_equivocations();
+		let api_alice = Arc::new(api_alice);
+
+		let mut net = BeefyTestNet::new(1);
+		let mut worker = create_beefy_worker(net.peer(0), &keys[0], 1, validator_set.clone());
+		worker.runtime = api_alice.clone();
+		let best_number = worker.backend.blockchain().info().best_number;
+
+		let payload = Payload::from_single_entry(MMR_ROOT_ID, vec![42]);
+		let vote = |number: u64, keyring: &Keyring| {
+			generate_future_block_voting_proof((number, payload.clone(), set_id, keyring)).vote
+		};
+		let reported_count = || api_alice.reported_future_votes.as_ref().unwrap().lock().len();
+
+		// votes on existing blocks are not reported
+		assert_eq!(worker.report_future_vote_if_any(vote(best_number, &Keyring::Bob)), Ok(()));
+		assert_eq!(reported_count(), 0);
+
+		// Bob voting on a block that doesn't exist yet gets reported
+		let future_vote = vote(best_number + 10, &Keyring::Bob);
+		assert_eq!(worker.report_future_vote_if_any(future_vote.clone()), Ok(()));
+		assert_eq!(reported_count(), 1);
+		assert_eq!(api_alice.reported_future_votes.as_ref().unwrap().lock()[0].vote, future_vote);
+
+		// but only once for the same set
+		assert_eq!(worker.report_future_vote_if_any(vote(best_number + 20, &Keyring::Bob)), Ok(()));
+		assert_eq!(reported_count(), 1);
+
+		// and own votes are never reported
+		assert_eq!(
+			worker.report_future_vote_if_any(vote(best_number + 10, &Keyring::Alice)),
+			Ok(())
+		);
+		assert_eq!(reported_count(), 1);
+
+		// voters reported for past sets are forgotten once a new session starts
+		let next_validator_set = ValidatorSet::new(make_beefy_ids(&keys), set_id + 1).unwrap();
+		worker.init_session_at(next_validator_set, best_number);
+		assert!(worker.reported_future_voters.is_empty());
+	}
+
+	#[tokio::test]
+	async fn should_report_fork_votes() {
+		let set_id = 1;
+		let keys = [Keyring::Alice];
+		let validator_set = ValidatorSet::new(make_beefy_ids(&keys), set_id).unwrap();
+		let mut api_alice = TestApi::with_validator_set(&validator_set);
+		api_alice.allow_equivocations();
+		let api_alice = Arc::new(api_alice);
+
+		let mut net = BeefyTestNet::new(1);
+		let mut worker = create_beefy_worker(net.peer(0), &keys[0], 1, validator_set.clone());
+		worker.runtime = api_alice.clone();
+
+		let canonical_root = MmrRootHash::repeat_byte(1);
+		let hashes = net.peer(0).generate_blocks(1, BlockOrigin::File, |mut builder| {
+			add_mmr_digest(&mut builder, canonical_root);
+			builder.build().unwrap().block
+		});
+		let header = worker.backend.blockchain().expect_header(hashes[0]).unwrap();
+		let block_num = *header.number();
+
+		let vote = |mmr_root: MmrRootHash, keyring: &Keyring| {
+			let payload = Payload::from_single_entry(MMR_ROOT_ID, mmr_root.encode());
+			generate_fork_voting_proof((block_num, payload, set_id, keyring), header.clone()).vote
+		};
+		let reported_votes = || api_alice.reported_fork_votes.as_ref().unwrap().lock().clone();
+
+		// votes matching the canonical chain are not reported
+		assert_eq!(worker.report_fork_vote_if_any(&vote(canonical_root, &Keyring::Bob)), Ok(()));
+		assert!(reported_votes().is_empty());
+		// and the canonical header is kept for the other votes of the round
+		assert_eq!(worker.fork_check_cache.as_ref().map(|(h, _)| h.hash()), Some(hashes[0]));
+
+		// Bob voting on a fork gets reported
+		let fork_vote = vote(MmrRootHash::repeat_byte(2), &Keyring::Bob);
+		assert_eq!(worker.report_fork_vote_if_any(&fork_vote), Ok(()));
+		let reported = reported_votes();
+		assert_eq!(reported.len(), 1);
+		assert_eq!(reported[0].vote, fork_vote);
+		assert_eq!(reported[0].header, header);
+		// no offchain storage to generate ancestry proofs from
+		assert_eq!(reported[0].ancestry_proof, None);
+	}
 }
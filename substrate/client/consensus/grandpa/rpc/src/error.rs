@@ -36,6 +36,9 @@ pub enum Error {
 	/// GRANDPA prove finality failed.
 	#[error("GRANDPA prove finality rpc failed: {0}")]
 	ProveFinalityFailed(#[from] sc_consensus_grandpa::FinalityProofError),
+	/// GRANDPA prove warp sync failed.
+	#[error("GRANDPA prove warp sync rpc failed: {0}")]
+	ProveWarpSyncFailed(#[from] sc_consensus_grandpa::warp_proof::Error),
 }
 
 /// The error codes returned by jsonrpc.
@@ -48,6 +51,8 @@ pub enum ErrorCode {
 	VoterStateTooLarge,
 	/// Failed to prove finality.
 	ProveFinality,
+	/// Failed to prove warp sync.
+	ProveWarpSync,
 }
 
 impl From<Error> for ErrorCode {
@@ -57,6 +62,7 @@ impl From<Error> for ErrorCode {
 			Error::AuthoritySetIdReportedAsUnreasonablyLarge => ErrorCode::AuthoritySetTooLarge,
 			Error::VoterStateReportsUnreasonablyLargeNumbers => ErrorCode::VoterStateTooLarge,
 			Error::ProveFinalityFailed(_) => ErrorCode::ProveFinality,
+			Error::ProveWarpSyncFailed(_) => ErrorCode::ProveWarpSync,
 		}
 	}
 }
@@ -34,6 +34,7 @@ mod error;
 mod finality;
 mod notification;
 mod report;
+mod warp_sync;
 
 use sc_consensus_grandpa::GrandpaJustificationStream;
 use sc_rpc::SubscriptionTaskExecutor;
@@ -42,6 +43,7 @@ use sp_runtime::traits::{Block as BlockT, NumberFor};
 use finality::{EncodedFinalityProof, RpcFinalityProofProvider};
 use notification::JustificationNotification;
 use report::{ReportAuthoritySet, ReportVoterState, ReportedRoundStates};
+pub use warp_sync::{EncodedWarpSyncProofPage, RpcWarpSyncProofProvider, WarpSyncCursor};
 
 /// Provides RPC methods for interacting with GRANDPA.
 #[rpc(client, server)]
@@ -132,6 +134,49 @@ where
 	}
 }
 
+/// Provides RPC methods for fetching GRANDPA warp sync proofs.
+#[rpc(client, server)]
+pub trait GrandpaWarpSyncApi<Hash> {
+	/// Prove the GRANDPA authority set changes following `cursor`, for light clients to warp
+	/// sync with. Proofs are paged, the cursor returned along with a page is used to fetch the
+	/// next one.
+	#[method(name = "grandpa_proveWarpSync", blocking)]
+	fn prove_warp_sync(&self, cursor: WarpSyncCursor<Hash>) -> RpcResult<EncodedWarpSyncProofPage>;
+}
+
+/// Provides RPC methods for fetching GRANDPA warp sync proofs.
+pub struct GrandpaWarpSync<Block, ProofProvider> {
+	warp_sync_proof_provider: Arc<ProofProvider>,
+	_phantom: std::marker::PhantomData<Block>,
+}
+
+impl<Block, ProofProvider> GrandpaWarpSync<Block, ProofProvider> {
+	/// Prepare a new [`GrandpaWarpSync`] Rpc handler.
+	pub fn new(warp_sync_proof_provider: Arc<ProofProvider>) -> Self {
+		Self { warp_sync_proof_provider, _phantom: Default::default() }
+	}
+}
+
+impl<Block, ProofProvider> GrandpaWarpSyncApiServer<Block::Hash>
+	for GrandpaWarpSync<Block, ProofProvider>
+where
+	Block: BlockT,
+	ProofProvider: RpcWarpSyncProofProvider<Block> + Send + Sync + 'static,
+{
+	fn prove_warp_sync(
+		&self,
+		cursor: WarpSyncCursor<Block::Hash>,
+	) -> RpcResult<EncodedWarpSyncProofPage> {
+		self.warp_sync_proof_provider
+			.rpc_prove_warp_sync(cursor)
+			.map_err(|e| {
+				warn!("Error proving warp sync: {}", e);
+				error::Error::ProveWarpSyncFailed(e)
+			})
+			.map_err(Into::into)
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -163,6 +208,10 @@ mod tests {
 		finality_proof: Option<FinalityProof<Header>>,
 	}
 
+	struct TestWarpSyncProofProvider {
+		last_set_id: u64,
+	}
+
 	fn voters() -> HashSet<AuthorityId> {
 		let voter_id_1 = AuthorityId::from_slice(&[1; 32]).unwrap();
 		let voter_id_2 = AuthorityId::from_slice(&[2; 32]).unwrap();
@@ -196,6 +245,24 @@ mod tests {
 		)
 	}
 
+	impl<Block: BlockT> RpcWarpSyncProofProvider<Block> for TestWarpSyncProofProvider {
+		fn rpc_prove_warp_sync(
+			&self,
+			cursor: WarpSyncCursor<Block::Hash>,
+		) -> Result<EncodedWarpSyncProofPage, sc_consensus_grandpa::warp_proof::Error> {
+			let set_id = match cursor {
+				WarpSyncCursor::Block(_) => 0,
+				WarpSyncCursor::SetId(set_id) if set_id <= self.last_set_id => set_id,
+				WarpSyncCursor::SetId(_) =>
+					return Err(sc_consensus_grandpa::warp_proof::Error::InvalidRequest(
+						"Unknown set id".into(),
+					)),
+			};
+			let next_cursor = (set_id < self.last_set_id).then(|| set_id + 1);
+			Ok(EncodedWarpSyncProofPage { proof: set_id.encode().into(), next_cursor })
+		}
+	}
+
 	impl<Block: BlockT> RpcFinalityProofProvider<Block> for TestFinalityProofProvider {
 		fn rpc_prove_finality(
 			&self,
@@ -417,4 +484,34 @@ mod tests {
 		let finality_proof_rpc: FinalityProof<Header> = Decode::decode(&mut &bytes[..]).unwrap();
 		assert_eq!(finality_proof_rpc, finality_proof);
 	}
+
+	#[tokio::test]
+	async fn prove_warp_sync_follows_cursor() {
+		let rpc = GrandpaWarpSync::<Block, _>::new(Arc::new(TestWarpSyncProofProvider {
+			last_set_id: 1,
+		}))
+		.into_rpc();
+
+		let page: EncodedWarpSyncProofPage = rpc
+			.call("grandpa_proveWarpSync", [WarpSyncCursor::Block(header(0).hash())])
+			.await
+			.unwrap();
+		assert_eq!(u64::decode(&mut &page.proof[..]).unwrap(), 0);
+		assert_eq!(page.next_cursor, Some(1));
+
+		let page: EncodedWarpSyncProofPage = rpc
+			.call("grandpa_proveWarpSync", [WarpSyncCursor::<H256>::SetId(1)])
+			.await
+			.unwrap();
+		assert_eq!(u64::decode(&mut &page.proof[..]).unwrap(), 1);
+		assert_eq!(page.next_cursor, None);
+
+		assert!(rpc
+			.call::<_, EncodedWarpSyncProofPage>(
+				"grandpa_proveWarpSync",
+				[WarpSyncCursor::<H256>::SetId(2)],
+			)
+			.await
+			.is_err());
+	}
 }
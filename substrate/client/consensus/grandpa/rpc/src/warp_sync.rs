@@ -0,0 +1,89 @@
+// This file is part of Substrate.
+
+// Copyright (C) Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use parity_scale_codec::Encode;
+use serde::{Deserialize, Serialize};
+
+use sc_consensus_grandpa::{
+	warp_proof::{
+		Error as WarpSyncError, NetworkProvider, WarpSyncProofCursor, WarpSyncProofLimits,
+	},
+	BlockNumberOps,
+};
+use sp_runtime::traits::{Block as BlockT, NumberFor};
+
+/// The maximum size in bytes of a warp sync proof page served over RPC. Kept well below the
+/// default RPC response size limit, since the proof is hex encoded in the response.
+const MAX_RPC_WARP_SYNC_PROOF_SIZE: usize = 2 * 1024 * 1024;
+
+/// The maximum number of authority set changes proven by a page served over RPC.
+const MAX_RPC_WARP_SYNC_PROOF_FRAGMENTS: usize = 64;
+
+/// Position from which a page of a warp sync proof starts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum WarpSyncCursor<Hash> {
+	/// Start with the first authority set change after the given finalized block.
+	Block(Hash),
+	/// Continue with the cursor returned along with a previous page.
+	SetId(u64),
+}
+
+/// A page of a warp sync proof.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EncodedWarpSyncProofPage {
+	/// SCALE encoded warp sync proof, in the format served to warp syncing peers.
+	pub proof: sp_core::Bytes,
+	/// The cursor to request the next page with, `None` if this is the last page.
+	pub next_cursor: Option<u64>,
+}
+
+/// Local trait mainly to allow mocking in tests.
+pub trait RpcWarpSyncProofProvider<Block: BlockT> {
+	/// Prove the authority set changes following `cursor`, up to a page size limit.
+	fn rpc_prove_warp_sync(
+		&self,
+		cursor: WarpSyncCursor<Block::Hash>,
+	) -> Result<EncodedWarpSyncProofPage, WarpSyncError>;
+}
+
+impl<Block, B> RpcWarpSyncProofProvider<Block> for NetworkProvider<Block, B>
+where
+	Block: BlockT,
+	NumberFor<Block>: BlockNumberOps,
+	B: sc_client_api::backend::Backend<Block> + Send + Sync + 'static,
+{
+	fn rpc_prove_warp_sync(
+		&self,
+		cursor: WarpSyncCursor<Block::Hash>,
+	) -> Result<EncodedWarpSyncProofPage, WarpSyncError> {
+		let cursor = match cursor {
+			WarpSyncCursor::Block(hash) => WarpSyncProofCursor::Block(hash),
+			WarpSyncCursor::SetId(set_id) => WarpSyncProofCursor::SetId(set_id),
+		};
+		let limits = WarpSyncProofLimits {
+			max_size: MAX_RPC_WARP_SYNC_PROOF_SIZE,
+			max_fragments: MAX_RPC_WARP_SYNC_PROOF_FRAGMENTS,
+		};
+		self.generate_page(cursor, limits).map(|page| EncodedWarpSyncProofPage {
+			proof: page.proof.encode().into(),
+			next_cursor: page.next_cursor,
+		})
+	}
+}
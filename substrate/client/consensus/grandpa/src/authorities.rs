@@ -758,6 +758,21 @@ impl<N: Ord + Clone> AuthoritySetChanges<N> {
 		self.0.insert(idx, (set_id, block_number));
 	}
 
+	/// Returns the block number of the last block of the given authority set, if it is a
+	/// historical set we know about.
+	pub fn last_block_of_set(&self, set_id: SetId) -> Option<N> {
+		self.0
+			.binary_search_by_key(&set_id, |(id, _)| *id)
+			.ok()
+			.map(|idx| self.0[idx].1.clone())
+	}
+
+	/// Returns an iterator over all known historical authority set changes. The iterator yields
+	/// a tuple representing the set id and the block number of the last block in that set.
+	pub(crate) fn iter(&self) -> impl Iterator<Item = &(u64, N)> {
+		self.0.iter()
+	}
+
 	/// Returns an iterator over all historical authority set changes starting at the given block
 	/// number (excluded). The iterator yields a tuple representing the set id and the block number
 	/// of the last block in that set.
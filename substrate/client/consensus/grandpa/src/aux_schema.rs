@@ -38,6 +38,7 @@ use crate::{
 		CompletedRound, CompletedRounds, CurrentRounds, HasVoted, SharedVoterSetState,
 		VoterSetState,
 	},
+	warp_proof::WarpSyncFragment,
 	GrandpaJustification, NewAuthoritySet, LOG_TARGET,
 };
 
@@ -46,6 +47,7 @@ const SET_STATE_KEY: &[u8] = b"grandpa_completed_round";
 const CONCLUDED_ROUNDS: &[u8] = b"grandpa_concluded_rounds";
 const AUTHORITY_SET_KEY: &[u8] = b"grandpa_voters";
 const BEST_JUSTIFICATION: &[u8] = b"grandpa_best_justification";
+const WARP_SYNC_FRAGMENTS: &[u8] = b"grandpa_warp_sync_fragments";
 
 const CURRENT_VERSION: u32 = 3;
 
@@ -471,6 +473,30 @@ where
 	load_decode::<_, GrandpaJustification<Block>>(backend, BEST_JUSTIFICATION)
 }
 
+fn warp_sync_fragment_key(set_id: SetId) -> Vec<u8> {
+	let mut key = WARP_SYNC_FRAGMENTS.to_vec();
+	set_id.using_encoded(|id| key.extend(id));
+	key
+}
+
+/// Fetch the indexed warp sync fragment proving the change away from authority set `set_id`,
+/// if any.
+pub(crate) fn load_warp_sync_fragment<Block: BlockT, B: AuxStore>(
+	backend: &B,
+	set_id: SetId,
+) -> ClientResult<Option<WarpSyncFragment<Block>>> {
+	load_decode(backend, &warp_sync_fragment_key(set_id))
+}
+
+/// Index the warp sync fragment proving the change away from authority set `set_id`.
+pub(crate) fn write_warp_sync_fragment<Block: BlockT, B: AuxStore>(
+	backend: &B,
+	set_id: SetId,
+	fragment: &WarpSyncFragment<Block>,
+) -> ClientResult<()> {
+	backend.insert_aux(&[(&warp_sync_fragment_key(set_id)[..], fragment.encode().as_slice())], &[])
+}
+
 /// Write voter set state.
 pub(crate) fn write_voter_set_state<Block: BlockT, B: AuxStore>(
 	backend: &B,
@@ -25,10 +25,13 @@ use std::{
 use finality_grandpa::{voter_set::VoterSet, Error as GrandpaError};
 use parity_scale_codec::{Decode, DecodeAll, Encode};
 use sp_blockchain::{Error as ClientError, HeaderBackend};
-use sp_consensus_grandpa::AuthorityId;
-use sp_runtime::traits::{Block as BlockT, Header as HeaderT, NumberFor};
+use sp_consensus_grandpa::{AuthorityId, GRANDPA_ENGINE_ID};
+use sp_runtime::{
+	traits::{Block as BlockT, Header as HeaderT, NumberFor},
+	Justifications,
+};
 
-use crate::{AuthorityList, Commit, Error};
+use crate::{find_forced_change, find_scheduled_change, AuthorityList, Commit, Error};
 
 /// A GRANDPA justification for block finality, it includes a commit message and
 /// an ancestry proof including all headers routing all precommit target blocks
@@ -305,3 +308,75 @@ where
 		Ok(route)
 	}
 }
+
+/// Verifies the GRANDPA justifications of a chain of headers, following the authority set changes
+/// signalled in the headers.
+///
+/// Used to check the justifications stored in the database without the authority set stored by
+/// the voter, see `sc_client_db::check_database`.
+pub struct JustificationVerifier<Block: BlockT> {
+	set_id: u64,
+	voters: VoterSet<AuthorityId>,
+	// Changes signalled by the already verified headers: number of the block enacting the change,
+	// whether it is forced and the new authorities.
+	pending_changes: Vec<(NumberFor<Block>, bool, AuthorityList)>,
+}
+
+impl<Block: BlockT> JustificationVerifier<Block>
+where
+	NumberFor<Block>: finality_grandpa::BlockNumberOps,
+{
+	/// Create a verifier starting at genesis with the given genesis authorities.
+	pub fn new(genesis_authorities: AuthorityList) -> Result<Self, ClientError> {
+		let voters = VoterSet::new(genesis_authorities)
+			.ok_or(ClientError::Consensus(sp_consensus::Error::InvalidAuthoritiesSet))?;
+		Ok(Self { set_id: 0, voters, pending_changes: Vec::new() })
+	}
+
+	/// Verify the GRANDPA justification of `header`, if any.
+	///
+	/// Has to be called with every header of the chain in ascending order, starting at genesis.
+	pub fn verify(
+		&mut self,
+		header: &Block::Header,
+		justifications: Option<&Justifications>,
+	) -> Result<(), ClientError> {
+		let number = *header.number();
+
+		// A standard change is enacted once its block is finalized, so that block is still
+		// finalized by the old set. A forced change is enacted once its block is imported.
+		let mut enacted = None;
+		self.pending_changes.retain(|(enact_at, forced, authorities)| {
+			let is_enacted = *enact_at < number || *forced && *enact_at == number;
+			if is_enacted {
+				enacted = Some(authorities.clone());
+			}
+			!is_enacted
+		});
+		if let Some(authorities) = enacted {
+			self.voters = VoterSet::new(authorities)
+				.ok_or(ClientError::Consensus(sp_consensus::Error::InvalidAuthoritiesSet))?;
+			self.set_id += 1;
+		}
+
+		if let Some(justification) = justifications.and_then(|j| j.get(GRANDPA_ENGINE_ID)) {
+			GrandpaJustification::<Block>::decode_and_verify_finalizes(
+				justification,
+				(header.hash(), number),
+				self.set_id,
+				&self.voters,
+			)?;
+		}
+
+		if let Some(change) = find_scheduled_change::<Block>(header) {
+			self.pending_changes
+				.push((number + change.delay, false, change.next_authorities));
+		}
+		if let Some((_, change)) = find_forced_change::<Block>(header) {
+			self.pending_changes
+				.push((number + change.delay, true, change.next_authorities));
+		}
+
+		Ok(())
+	}
+}
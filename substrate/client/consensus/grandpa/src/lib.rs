@@ -132,7 +132,7 @@ pub use communication::grandpa_protocol_name::standard_name as protocol_standard
 pub use finality_grandpa::voter::report;
 pub use finality_proof::{FinalityProof, FinalityProofError, FinalityProofProvider};
 pub use import::{find_forced_change, find_scheduled_change, GrandpaBlockImport};
-pub use justification::GrandpaJustification;
+pub use justification::{GrandpaJustification, JustificationVerifier};
 pub use notification::{GrandpaJustificationSender, GrandpaJustificationStream};
 pub use observer::run_grandpa_observer;
 pub use voting_rule::{
@@ -711,7 +711,7 @@ pub fn grandpa_peers_set_config(
 	protocol_name: ProtocolName,
 ) -> (sc_network::config::NonDefaultSetConfig, Box<dyn NotificationService>) {
 	use communication::grandpa_protocol_name;
-	sc_network::config::NonDefaultSetConfig::new(
+	let (mut config, notification_service) = sc_network::config::NonDefaultSetConfig::new(
 		protocol_name,
 		grandpa_protocol_name::LEGACY_NAMES.iter().map(|&n| n.into()).collect(),
 		// Notifications reach ~256kiB in size at the time of writing on Kusama and Polkadot.
@@ -723,7 +723,14 @@ pub fn grandpa_peers_set_config(
 			reserved_nodes: Vec::new(),
 			non_reserved_mode: sc_network::config::NonReservedPeerMode::Deny,
 		},
-	)
+	);
+
+	// Votes must not be delayed by block sync when the bandwidth is limited.
+	config.set_bandwidth(sc_network::config::ProtocolBandwidth::with_priority(
+		sc_network::config::BandwidthPriority::High,
+	));
+
+	(config, notification_service)
 }
 
 /// Run a GRANDPA voter as a task. Provide configuration and a link to a
@@ -30,6 +30,7 @@ use sc_consensus::{
 };
 use sc_network::config::Role;
 use sc_network_test::{
+	simulation::{LinkConfig, SimulatedNetwork},
 	Block, BlockImportAdapter, FullPeerConfig, Hash, PassThroughVerifier, Peer, PeersClient,
 	PeersFullClient, TestClient, TestNetFactory,
 };
@@ -75,12 +76,31 @@ const JUSTIFICATION_IMPORT_PERIOD: u32 = 32;
 struct GrandpaTestNet {
 	peers: Vec<GrandpaPeer>,
 	test_config: TestApi,
+	simulated_network: Option<SimulatedNetwork>,
 }
 
 impl GrandpaTestNet {
 	fn new(test_config: TestApi, n_authority: usize, n_full: usize) -> Self {
-		let mut net =
-			GrandpaTestNet { peers: Vec::with_capacity(n_authority + n_full), test_config };
+		Self::with_network(test_config, n_authority, n_full, None)
+	}
+
+	/// Create a network whose peers are connected through a [`SimulatedNetwork`] whose
+	/// randomness is derived from `seed`.
+	fn simulated(test_config: TestApi, n_authority: usize, n_full: usize, seed: u64) -> Self {
+		Self::with_network(test_config, n_authority, n_full, Some(SimulatedNetwork::new(seed)))
+	}
+
+	fn with_network(
+		test_config: TestApi,
+		n_authority: usize,
+		n_full: usize,
+		simulated_network: Option<SimulatedNetwork>,
+	) -> Self {
+		let mut net = GrandpaTestNet {
+			peers: Vec::with_capacity(n_authority + n_full),
+			test_config,
+			simulated_network,
+		};
 
 		for _ in 0..n_authority {
 			net.add_authority_peer();
@@ -153,6 +173,10 @@ impl TestNetFactory for GrandpaTestNet {
 	fn mut_peers<F: FnOnce(&mut Vec<GrandpaPeer>)>(&mut self, closure: F) {
 		closure(&mut self.peers);
 	}
+
+	fn simulated_network(&self) -> Option<&SimulatedNetwork> {
+		self.simulated_network.as_ref()
+	}
 }
 
 #[derive(Default, Clone)]
@@ -1435,6 +1459,48 @@ async fn voter_catches_up_to_latest_round_when_behind() {
 type TestEnvironment<N, S, SC, VR> =
 	Environment<substrate_test_runtime_client::Backend, Block, TestClient, N, S, SC, VR>;
 
+#[tokio::test]
+async fn finalizes_on_the_majority_side_of_a_partition() {
+	sp_tracing::try_init_simple();
+	let peers = &[
+		Ed25519Keyring::Alice,
+		Ed25519Keyring::Bob,
+		Ed25519Keyring::Charlie,
+		Ed25519Keyring::Dave,
+	];
+	let voters = make_ids(peers);
+
+	// The round timers of the voters run in real time, and so does the clock of the network.
+	let mut net = GrandpaTestNet::simulated(TestApi::new(voters), 4, 0, 11);
+	let network = net.simulated_network().unwrap().clone();
+	network.set_default_link(LinkConfig {
+		latency: Duration::from_millis(50),
+		jitter: Duration::from_millis(20),
+		..Default::default()
+	});
+	tokio::spawn(initialize_grandpa(&mut net, peers));
+	net.run_until_connected().await;
+
+	// Dave is cut off, the three other voters are enough to finalize.
+	network.partition(vec![vec![0, 1, 2], vec![3]]);
+	net.peer(0).push_blocks(20, false);
+	let net = Arc::new(Mutex::new(net));
+	run_to_completion(20, net.clone(), &peers[..3]).await;
+
+	let dave = net.lock().peer(3).client().clone();
+	assert_eq!(dave.info().best_number, 0);
+	assert_eq!(dave.info().finalized_number, 0);
+
+	// Once the partition heals, Dave catches up with the blocks and their finality.
+	network.heal();
+	let finalized = dave
+		.finality_notification_stream()
+		.take_while(|n| future::ready(n.header.number() < &20))
+		.for_each(|_| future::ready(()));
+	run_until_complete(Box::pin(finalized), &net).await;
+	assert_eq!(dave.info().finalized_number, 20);
+}
+
 fn test_environment_with_select_chain<N, S, VR, SC>(
 	link: &TestLinkHalf,
 	keystore: Option<KeystorePtr>,
@@ -2241,3 +2307,69 @@ async fn revert_prunes_authority_changes() {
 		.collect();
 	assert_eq!(changes_num, [21, 27]);
 }
+
+#[tokio::test]
+async fn justification_verifier_follows_authority_set_changes() {
+	let peers_a = &[Ed25519Keyring::Alice];
+	let peers_b = &[Ed25519Keyring::Bob];
+	let api = TestApi::new(make_ids(peers_a));
+	let mut net = GrandpaTestNet::new(api, 1, 0);
+
+	// block #1 schedules a change which is enacted once block #2 is finalized
+	net.peer(0).generate_blocks(1, BlockOrigin::File, |mut builder| {
+		add_scheduled_change(
+			&mut builder,
+			ScheduledChange { next_authorities: make_ids(peers_b), delay: 1 },
+		);
+		builder.build().unwrap().block
+	});
+	net.peer(0).push_blocks(2, false);
+
+	let client = net.peer(0).client().as_client().clone();
+	let headers = (0..4)
+		.map(|number| {
+			let hash = client.expect_block_hash_from_id(&BlockId::Number(number)).unwrap();
+			client.expect_header(hash).unwrap()
+		})
+		.collect::<Vec<_>>();
+
+	let justification = |number: usize, set_id, voter: Ed25519Keyring| {
+		let header = &headers[number];
+		let precommit = finality_grandpa::Precommit {
+			target_hash: header.hash(),
+			target_number: *header.number(),
+		};
+		let msg = finality_grandpa::Message::Precommit(precommit.clone());
+		let encoded = sp_consensus_grandpa::localized_payload(1, set_id, &msg);
+		let precommit = finality_grandpa::SignedPrecommit {
+			precommit,
+			signature: voter.sign(&encoded[..]).into(),
+			id: voter.public().into(),
+		};
+		let commit = finality_grandpa::Commit {
+			target_hash: header.hash(),
+			target_number: *header.number(),
+			precommits: vec![precommit],
+		};
+		let justification = GrandpaJustification::from_commit(&client, 1, commit).unwrap();
+		Justifications::from((GRANDPA_ENGINE_ID, justification.encode()))
+	};
+
+	let verify = |justifications: Vec<(usize, Justifications)>| {
+		let mut verifier = JustificationVerifier::<Block>::new(make_ids(peers_a)).unwrap();
+		headers.iter().enumerate().try_for_each(|(number, header)| {
+			let justifications = justifications.iter().find(|(n, _)| *n == number).map(|(_, j)| j);
+			verifier.verify(header, justifications)
+		})
+	};
+
+	// the change block is finalized by the old set, its descendants by the new set
+	assert!(verify(vec![
+		(1, justification(1, 0, Ed25519Keyring::Alice)),
+		(2, justification(2, 0, Ed25519Keyring::Alice)),
+		(3, justification(3, 1, Ed25519Keyring::Bob)),
+	])
+	.is_ok());
+	assert!(verify(vec![(2, justification(2, 1, Ed25519Keyring::Bob))]).is_err());
+	assert!(verify(vec![(3, justification(3, 0, Ed25519Keyring::Alice))]).is_err());
+}
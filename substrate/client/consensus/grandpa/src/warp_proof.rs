@@ -19,16 +19,19 @@
 use parity_scale_codec::{Decode, DecodeAll, Encode};
 
 use crate::{
+	aux_schema::{load_warp_sync_fragment, write_warp_sync_fragment},
 	best_justification, find_scheduled_change, AuthoritySetChanges, AuthoritySetHardFork,
-	BlockNumberOps, GrandpaJustification, SharedAuthoritySet,
+	BlockNumberOps, GrandpaJustification, SharedAuthoritySet, LOG_TARGET,
 };
-use sc_client_api::Backend as ClientBackend;
+use futures::StreamExt;
+use log::debug;
+use sc_client_api::{Backend as ClientBackend, FinalityNotifications};
 use sc_network_sync::warp::{EncodedProof, VerificationResult, WarpSyncProvider};
 use sp_blockchain::{Backend as BlockchainBackend, HeaderBackend};
 use sp_consensus_grandpa::{AuthorityList, SetId, GRANDPA_ENGINE_ID};
 use sp_runtime::{
 	generic::BlockId,
-	traits::{Block as BlockT, Header as HeaderT, NumberFor, One},
+	traits::{Block as BlockT, Header as HeaderT, NumberFor, One, Zero},
 };
 
 use std::{collections::HashMap, sync::Arc};
@@ -56,6 +59,45 @@ pub enum Error {
 /// The maximum size in bytes of the `WarpSyncProof`.
 pub(super) const MAX_WARP_SYNC_PROOF_SIZE: usize = 8 * 1024 * 1024;
 
+/// The maximum number of authority set changes proven by a `WarpSyncProof` served to peers.
+///
+/// Peers request the remaining changes once they verified a partial proof, so this bounds the
+/// time spent serving a single request.
+pub(super) const MAX_WARP_SYNC_PROOF_FRAGMENTS: usize = 128;
+
+/// Limits on a single page of a warp sync proof.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WarpSyncProofLimits {
+	/// Maximum encoded size of the page, in bytes.
+	pub max_size: usize,
+	/// Maximum number of authority set changes proven by the page.
+	pub max_fragments: usize,
+}
+
+impl Default for WarpSyncProofLimits {
+	fn default() -> Self {
+		Self { max_size: MAX_WARP_SYNC_PROOF_SIZE, max_fragments: usize::MAX }
+	}
+}
+
+/// Position from which a page of a warp sync proof starts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WarpSyncProofCursor<Hash> {
+	/// Start with the first authority set change after the given finalized block.
+	Block(Hash),
+	/// Start with the change away from the given authority set, as returned along with a
+	/// previous page.
+	SetId(SetId),
+}
+
+/// A page of a warp sync proof.
+pub struct WarpSyncProofPage<Block: BlockT> {
+	/// The proof fragments of this page.
+	pub proof: WarpSyncProof<Block>,
+	/// The cursor to request the next page with, `None` if this is the last page.
+	pub next_cursor: Option<SetId>,
+}
+
 /// A proof of an authority set change.
 #[derive(Decode, Encode, Debug)]
 pub struct WarpSyncFragment<Block: BlockT> {
@@ -86,67 +128,89 @@ impl<Block: BlockT> WarpSyncProof<Block> {
 	where
 		Backend: ClientBackend<Block>,
 	{
-		// TODO: cache best response (i.e. the one with lowest begin_number)
+		Self::generate_page(
+			backend,
+			WarpSyncProofCursor::Block(begin),
+			set_changes,
+			WarpSyncProofLimits::default(),
+		)
+		.map(|page| page.proof)
+	}
+
+	/// Generates a page of a warp sync proof starting at the given cursor. It will generate
+	/// authority set change proofs for the changes that happened from `cursor` until the current
+	/// authority set, capped by `limits`. Set change proofs are served from the fragment index,
+	/// or built from the set change justifications if not indexed yet.
+	fn generate_page<Backend>(
+		backend: &Backend,
+		cursor: WarpSyncProofCursor<Block::Hash>,
+		set_changes: &AuthoritySetChanges<NumberFor<Block>>,
+		limits: WarpSyncProofLimits,
+	) -> Result<WarpSyncProofPage<Block>, Error>
+	where
+		Backend: ClientBackend<Block>,
+	{
 		let blockchain = backend.blockchain();
 
-		let begin_number = blockchain
-			.block_number_from_id(&BlockId::Hash(begin))?
-			.ok_or_else(|| Error::InvalidRequest("Missing start block".to_string()))?;
+		let begin_number = match cursor {
+			WarpSyncProofCursor::Block(begin) => {
+				let begin_number = blockchain
+					.block_number_from_id(&BlockId::Hash(begin))?
+					.ok_or_else(|| Error::InvalidRequest("Missing start block".to_string()))?;
 
-		if begin_number > blockchain.info().finalized_number {
-			return Err(Error::InvalidRequest("Start block is not finalized".to_string()))
-		}
+				if begin_number > blockchain.info().finalized_number {
+					return Err(Error::InvalidRequest("Start block is not finalized".to_string()))
+				}
 
-		let canon_hash = blockchain.hash(begin_number)?.expect(
-			"begin number is lower than finalized number; \
-			 all blocks below finalized number must have been imported; \
-			 qed.",
-		);
+				let canon_hash = blockchain.hash(begin_number)?.expect(
+					"begin number is lower than finalized number; \
+					 all blocks below finalized number must have been imported; \
+					 qed.",
+				);
 
-		if canon_hash != begin {
-			return Err(Error::InvalidRequest(
-				"Start block is not in the finalized chain".to_string(),
-			))
-		}
+				if canon_hash != begin {
+					return Err(Error::InvalidRequest(
+						"Start block is not in the finalized chain".to_string(),
+					))
+				}
+
+				begin_number
+			},
+			// the change away from `set_id` is the first one after the last block of the
+			// previous set
+			WarpSyncProofCursor::SetId(set_id) => match set_id.checked_sub(1) {
+				None => Zero::zero(),
+				Some(previous_set_id) => set_changes
+					.last_block_of_set(previous_set_id)
+					.ok_or_else(|| Error::InvalidRequest("Unknown cursor set id".to_string()))?,
+			},
+		};
 
 		let mut proofs = Vec::new();
 		let mut proofs_encoded_len = 0;
-		let mut proof_limit_reached = false;
+		let mut next_cursor = None;
 
 		let set_changes = set_changes.iter_from(begin_number).ok_or(Error::MissingData)?;
 
-		for (_, last_block) in set_changes {
-			let hash = blockchain.block_hash_from_id(&BlockId::Number(*last_block))?
-				.expect("header number comes from previously applied set changes; corresponding hash must exist in db; qed.");
-
-			let header = blockchain
-				.header(hash)?
-				.expect("header hash obtained from header number exists in db; corresponding header must exist in db too; qed.");
-
-			// the last block in a set is the one that triggers a change to the next set,
-			// therefore the block must have a digest that signals the authority set change
-			if find_scheduled_change::<Block>(&header).is_none() {
-				// if it doesn't contain a signal for standard change then the set must have changed
-				// through a forced changed, in which case we stop collecting proofs as the chain of
-				// trust in authority handoffs was broken.
+		for (set_id, last_block) in set_changes {
+			if proofs.len() >= limits.max_fragments.max(1) {
+				next_cursor = Some(*set_id);
 				break
 			}
 
-			let justification = blockchain
-				.justifications(header.hash())?
-				.and_then(|just| just.into_justification(GRANDPA_ENGINE_ID))
-				.ok_or_else(|| Error::MissingData)?;
-
-			let justification = GrandpaJustification::<Block>::decode_all(&mut &justification[..])?;
-
-			let proof = WarpSyncFragment { header: header.clone(), justification };
+			let proof = match Self::set_change_fragment(backend, *set_id, *last_block)? {
+				Some(proof) => proof,
+				// the set changed through a forced change, in which case we stop collecting
+				// proofs as the chain of trust in authority handoffs was broken.
+				None => break,
+			};
 			let proof_size = proof.encoded_size();
 
 			// Check for the limit. We remove some bytes from the maximum size, because we're only
 			// counting the size of the `WarpSyncFragment`s. The extra margin is here to leave
 			// room for rest of the data (the size of the `Vec` and the boolean).
-			if proofs_encoded_len + proof_size >= MAX_WARP_SYNC_PROOF_SIZE - 50 {
-				proof_limit_reached = true;
+			if proofs_encoded_len + proof_size >= limits.max_size.saturating_sub(50) {
+				next_cursor = Some(*set_id);
 				break
 			}
 
@@ -154,7 +218,7 @@ impl<Block: BlockT> WarpSyncProof<Block> {
 			proofs.push(proof);
 		}
 
-		let is_finished = if proof_limit_reached {
+		let is_finished = if next_cursor.is_some() {
 			false
 		} else {
 			let latest_justification = best_justification(backend)?.filter(|justification| {
@@ -181,8 +245,109 @@ impl<Block: BlockT> WarpSyncProof<Block> {
 		};
 
 		let final_outcome = WarpSyncProof { proofs, is_finished };
-		debug_assert!(final_outcome.encoded_size() <= MAX_WARP_SYNC_PROOF_SIZE);
-		Ok(final_outcome)
+		debug_assert!(final_outcome.encoded_size() <= limits.max_size);
+		Ok(WarpSyncProofPage { proof: final_outcome, next_cursor })
+	}
+
+	/// Returns the proof of the change away from authority set `set_id`, whose last block is
+	/// `last_block`. The proof is read from the fragment index, or built from the set change
+	/// justification if not indexed yet. Returns `None` if the set ended with a forced change.
+	fn set_change_fragment<Backend>(
+		backend: &Backend,
+		set_id: SetId,
+		last_block: NumberFor<Block>,
+	) -> Result<Option<WarpSyncFragment<Block>>, Error>
+	where
+		Backend: ClientBackend<Block>,
+	{
+		match Self::indexed_set_change_fragment(backend, set_id, last_block)? {
+			Some(fragment) => Ok(Some(fragment)),
+			None => Self::build_set_change_fragment(backend, last_block),
+		}
+	}
+
+	/// Returns the indexed proof of the change away from authority set `set_id`, if it is the
+	/// one ending at `last_block`.
+	fn indexed_set_change_fragment<Backend>(
+		backend: &Backend,
+		set_id: SetId,
+		last_block: NumberFor<Block>,
+	) -> Result<Option<WarpSyncFragment<Block>>, Error>
+	where
+		Backend: ClientBackend<Block>,
+	{
+		Ok(load_warp_sync_fragment::<Block, _>(backend, set_id)?
+			.filter(|fragment| *fragment.header.number() == last_block))
+	}
+
+	/// Builds the proof of the change away from the set whose last block is `last_block`, from
+	/// the justification of that block. Returns `None` if the set ended with a forced change.
+	fn build_set_change_fragment<Backend>(
+		backend: &Backend,
+		last_block: NumberFor<Block>,
+	) -> Result<Option<WarpSyncFragment<Block>>, Error>
+	where
+		Backend: ClientBackend<Block>,
+	{
+		let blockchain = backend.blockchain();
+		let hash = blockchain.block_hash_from_id(&BlockId::Number(last_block))?
+			.expect("header number comes from previously applied set changes; corresponding hash must exist in db; qed.");
+
+		let header = blockchain
+			.header(hash)?
+			.expect("header hash obtained from header number exists in db; corresponding header must exist in db too; qed.");
+
+		// the last block in a set is the one that triggers a change to the next set,
+		// therefore the block must have a digest that signals the authority set change
+		if find_scheduled_change::<Block>(&header).is_none() {
+			// if it doesn't contain a signal for standard change then the set must have changed
+			// through a forced changed.
+			return Ok(None)
+		}
+
+		let justification = blockchain
+			.justifications(header.hash())?
+			.and_then(|just| just.into_justification(GRANDPA_ENGINE_ID))
+			.ok_or_else(|| Error::MissingData)?;
+
+		let justification = GrandpaJustification::<Block>::decode_all(&mut &justification[..])?;
+
+		Ok(Some(WarpSyncFragment { header, justification }))
+	}
+
+	/// Indexes the proofs of the changes away from authority sets `from_set_id` and later,
+	/// skipping the ones already indexed. Returns the id of the first set not indexed yet.
+	fn index_set_change_fragments<Backend>(
+		backend: &Backend,
+		set_changes: &AuthoritySetChanges<NumberFor<Block>>,
+		from_set_id: SetId,
+	) -> SetId
+	where
+		Backend: ClientBackend<Block>,
+	{
+		let mut next_set_id = from_set_id;
+		for (set_id, last_block) in set_changes.iter().filter(|(set_id, _)| *set_id >= from_set_id)
+		{
+			let result = Self::indexed_set_change_fragment(backend, *set_id, *last_block).and_then(
+				|indexed| match indexed {
+					Some(_) => Ok(()),
+					None => match Self::build_set_change_fragment(backend, *last_block)? {
+						Some(fragment) => write_warp_sync_fragment(backend, *set_id, &fragment)
+							.map_err(Into::into),
+						None => Ok(()),
+					},
+				},
+			);
+			// justifications of old set changes might be missing, e.g. after warp sync
+			if let Err(err) = result {
+				debug!(
+					target: LOG_TARGET,
+					"Failed to index warp sync fragment of set {}: {}", set_id, err,
+				);
+			}
+			next_set_id = set_id + 1;
+		}
+		next_set_id
 	}
 
 	/// Verifies the warp sync proof starting at the given set id and with the given authorities.
@@ -236,7 +401,7 @@ impl<Block: BlockT> WarpSyncProof<Block> {
 }
 
 /// Implements network API for warp sync.
-pub struct NetworkProvider<Block: BlockT, Backend: ClientBackend<Block>>
+pub struct NetworkProvider<Block: BlockT, Backend>
 where
 	NumberFor<Block>: BlockNumberOps,
 {
@@ -249,6 +414,45 @@ impl<Block: BlockT, Backend: ClientBackend<Block>> NetworkProvider<Block, Backen
 where
 	NumberFor<Block>: BlockNumberOps,
 {
+	/// Generates a page of a warp sync proof starting at `cursor`, capped by `limits`.
+	pub fn generate_page(
+		&self,
+		cursor: WarpSyncProofCursor<Block::Hash>,
+		limits: WarpSyncProofLimits,
+	) -> Result<WarpSyncProofPage<Block>, Error> {
+		WarpSyncProof::<Block>::generate_page(
+			&*self.backend,
+			cursor,
+			&self.authority_set.authority_set_changes(),
+			limits,
+		)
+	}
+
+	/// Index the proofs of all finalized authority set changes, then keep indexing new changes
+	/// as they get finalized, so that serving warp sync proofs doesn't require reading and
+	/// decoding the justifications of every set change block.
+	///
+	/// Runs until `finality_notifications` terminates.
+	pub async fn precompute_fragments(
+		self: Arc<Self>,
+		finality_notifications: FinalityNotifications<Block>,
+	) {
+		let mut finality_notifications = finality_notifications.fuse();
+		let mut next_set_id = 0;
+
+		loop {
+			next_set_id = WarpSyncProof::<Block>::index_set_change_fragments(
+				&*self.backend,
+				&self.authority_set.authority_set_changes(),
+				next_set_id,
+			);
+
+			if finality_notifications.next().await.is_none() {
+				break
+			}
+		}
+	}
+
 	/// Create a new istance for a given backend and authority set.
 	pub fn new(
 		backend: Arc<Backend>,
@@ -275,13 +479,14 @@ where
 		&self,
 		start: Block::Hash,
 	) -> Result<EncodedProof, Box<dyn std::error::Error + Send + Sync>> {
-		let proof = WarpSyncProof::<Block>::generate(
-			&*self.backend,
-			start,
-			&self.authority_set.authority_set_changes(),
-		)
-		.map_err(Box::new)?;
-		Ok(EncodedProof(proof.encode()))
+		let limits = WarpSyncProofLimits {
+			max_size: MAX_WARP_SYNC_PROOF_SIZE,
+			max_fragments: MAX_WARP_SYNC_PROOF_FRAGMENTS,
+		};
+		let page = self
+			.generate_page(WarpSyncProofCursor::Block(start), limits)
+			.map_err(Box::new)?;
+		Ok(EncodedProof(page.proof.encode()))
 	}
 
 	fn verify(
@@ -314,27 +519,52 @@ where
 	fn current_authorities(&self) -> AuthorityList {
 		self.authority_set.inner().current_authorities.clone()
 	}
+
+	fn proof_end(
+		&self,
+		proof: &EncodedProof,
+	) -> Result<(Block::Hash, bool), Box<dyn std::error::Error + Send + Sync>> {
+		let EncodedProof(proof) = proof;
+		let proof = WarpSyncProof::<Block>::decode_all(&mut proof.as_slice())
+			.map_err(|e| format!("Proof decoding error: {:?}", e))?;
+		let last_hash = proof
+			.proofs
+			.last()
+			.map(|p| p.header.hash())
+			.ok_or_else(|| "Empty proof".to_string())?;
+		Ok((last_hash, proof.is_finished))
+	}
 }
 
 #[cfg(test)]
 mod tests {
-	use super::WarpSyncProof;
-	use crate::{AuthoritySetChanges, GrandpaJustification};
+	use super::{WarpSyncProof, WarpSyncProofCursor, WarpSyncProofLimits};
+	use crate::{aux_schema::load_warp_sync_fragment, AuthoritySetChanges, GrandpaJustification};
 	use parity_scale_codec::Encode;
 	use rand::prelude::*;
 	use sc_block_builder::BlockBuilderBuilder;
 	use sp_blockchain::HeaderBackend;
 	use sp_consensus::BlockOrigin;
-	use sp_consensus_grandpa::GRANDPA_ENGINE_ID;
+	use sp_consensus_grandpa::{AuthorityList, GRANDPA_ENGINE_ID};
 	use sp_keyring::Ed25519Keyring;
+	use sp_runtime::traits::{Block as BlockT, Header as HeaderT};
 	use std::sync::Arc;
 	use substrate_test_runtime_client::{
-		BlockBuilderExt, ClientBlockImportExt, ClientExt, DefaultTestClientBuilderExt,
-		TestClientBuilder, TestClientBuilderExt,
+		runtime::Block, Backend, BlockBuilderExt, ClientBlockImportExt, ClientExt,
+		DefaultTestClientBuilderExt, TestClientBuilder, TestClientBuilderExt,
 	};
 
-	#[test]
-	fn warp_sync_proof_generate_verify() {
+	struct TestChain {
+		backend: Arc<Backend>,
+		genesis_hash: <Block as BlockT>::Hash,
+		authority_set_changes: AuthoritySetChanges<u64>,
+		genesis_authorities: AuthorityList,
+		current_set_id: u64,
+		current_authorities: AuthorityList,
+	}
+
+	/// Builds a chain of 100 blocks, with an authority set change every 10 blocks.
+	fn test_chain() -> TestChain {
 		let mut rng = rand::rngs::StdRng::from_seed([0; 32]);
 		let builder = TestClientBuilder::new();
 		let backend = builder.backend();
@@ -426,24 +656,137 @@ mod tests {
 			}
 		}
 
-		let authority_set_changes = AuthoritySetChanges::from(authority_set_changes);
+		TestChain {
+			backend,
+			genesis_hash: client.hash(0).unwrap().unwrap(),
+			authority_set_changes: AuthoritySetChanges::from(authority_set_changes),
+			genesis_authorities,
+			current_set_id,
+			current_authorities: current_authorities
+				.iter()
+				.map(|keyring| (keyring.public().into(), 1))
+				.collect(),
+		}
+	}
+
+	#[test]
+	fn warp_sync_proof_generate_verify() {
+		let chain = test_chain();
 
 		// generate a warp sync proof
-		let genesis_hash = client.hash(0).unwrap().unwrap();
-
-		let warp_sync_proof =
-			WarpSyncProof::generate(&*backend, genesis_hash, &authority_set_changes).unwrap();
+		let warp_sync_proof = WarpSyncProof::<Block>::generate(
+			&*chain.backend,
+			chain.genesis_hash,
+			&chain.authority_set_changes,
+		)
+		.unwrap();
 
 		// verifying the proof should yield the last set id and authorities
-		let (new_set_id, new_authorities) =
-			warp_sync_proof.verify(0, genesis_authorities, &Default::default()).unwrap();
+		let (new_set_id, new_authorities) = warp_sync_proof
+			.verify(0, chain.genesis_authorities, &Default::default())
+			.unwrap();
+
+		assert_eq!(new_set_id, chain.current_set_id);
+		assert_eq!(new_authorities, chain.current_authorities);
+	}
+
+	#[test]
+	fn warp_sync_proof_served_from_index() {
+		let chain = test_chain();
+		let last_set_id = chain.authority_set_changes.iter().last().unwrap().0;
+
+		// all set changes get indexed
+		let next_set_id = WarpSyncProof::<Block>::index_set_change_fragments(
+			&*chain.backend,
+			&chain.authority_set_changes,
+			0,
+		);
+		assert_eq!(next_set_id, last_set_id + 1);
+		for (set_id, last_block) in chain.authority_set_changes.iter() {
+			let fragment =
+				load_warp_sync_fragment::<Block, _>(&*chain.backend, *set_id).unwrap().unwrap();
+			assert_eq!(fragment.header.number(), last_block);
+		}
+
+		// and indexing again starts after the last indexed set
+		assert_eq!(
+			WarpSyncProof::<Block>::index_set_change_fragments(
+				&*chain.backend,
+				&chain.authority_set_changes,
+				next_set_id,
+			),
+			next_set_id,
+		);
+
+		// proofs built from the index verify like the ones built from justifications
+		let warp_sync_proof = WarpSyncProof::<Block>::generate(
+			&*chain.backend,
+			chain.genesis_hash,
+			&chain.authority_set_changes,
+		)
+		.unwrap();
+		let (new_set_id, new_authorities) = warp_sync_proof
+			.verify(0, chain.genesis_authorities, &Default::default())
+			.unwrap();
+		assert_eq!(new_set_id, chain.current_set_id);
+		assert_eq!(new_authorities, chain.current_authorities);
+	}
+
+	#[test]
+	fn warp_sync_proof_pages_follow_cursor() {
+		let chain = test_chain();
+		let limits = WarpSyncProofLimits { max_fragments: 3, ..Default::default() };
+
+		let mut cursor = WarpSyncProofCursor::Block(chain.genesis_hash);
+		let mut set_id = 0;
+		let mut authorities = chain.genesis_authorities;
+		let mut pages = 0;
+		loop {
+			let page = WarpSyncProof::<Block>::generate_page(
+				&*chain.backend,
+				cursor,
+				&chain.authority_set_changes,
+				limits,
+			)
+			.unwrap();
+			pages += 1;
+
+			// every page is verifiable on its own, given the authorities it starts with
+			(set_id, authorities) =
+				page.proof.verify(set_id, authorities, &Default::default()).unwrap();
+
+			match page.next_cursor {
+				Some(next_set_id) => {
+					assert!(!page.proof.is_finished);
+					assert_eq!(page.proof.proofs.len(), 3);
+					assert_eq!(next_set_id, set_id);
+					cursor = WarpSyncProofCursor::SetId(next_set_id);
+				},
+				None => {
+					assert!(page.proof.is_finished);
+					break
+				},
+			}
+		}
+
+		assert_eq!(pages, 4);
+		assert_eq!(set_id, chain.current_set_id);
+		assert_eq!(authorities, chain.current_authorities);
 
-		let expected_authorities = current_authorities
-			.iter()
-			.map(|keyring| (keyring.public().into(), 1))
-			.collect::<Vec<_>>();
+		// serving proofs doesn't write to the fragment index
+		for (set_id, _) in chain.authority_set_changes.iter() {
+			assert!(load_warp_sync_fragment::<Block, _>(&*chain.backend, *set_id)
+				.unwrap()
+				.is_none());
+		}
 
-		assert_eq!(new_set_id, current_set_id);
-		assert_eq!(new_authorities, expected_authorities);
+		// and unknown cursors are rejected
+		assert!(WarpSyncProof::<Block>::generate_page(
+			&*chain.backend,
+			WarpSyncProofCursor::SetId(42),
+			&chain.authority_set_changes,
+			limits,
+		)
+		.is_err());
 	}
 }
@@ -33,6 +33,16 @@ pub trait ConsensusDataProvider<B: BlockT>: Send + Sync {
 	/// The proof type.
 	type Proof;
 
+	/// Adjust the inherent data of the next block before its digest is created, e.g. to move the
+	/// time of the block.
+	fn prepare_inherent_data(
+		&self,
+		_parent: &B::Header,
+		_inherents: &mut InherentData,
+	) -> Result<(), Error> {
+		Ok(())
+	}
+
 	/// Attempt to create a consensus digest.
 	fn create_digest(&self, parent: &B::Header, inherents: &InherentData) -> Result<Digest, Error>;
 
@@ -19,6 +19,7 @@
 //! Aura consensus data provider, This allows manual seal author blocks that are valid for
 //! runtimes that expect the aura-specific digests.
 
+use super::timestamp::TimeTravel;
 use crate::{ConsensusDataProvider, Error};
 use sc_client_api::{AuxStore, UsageProvider};
 use sc_consensus::BlockImportParams;
@@ -31,13 +32,15 @@ use sp_consensus_aura::{
 };
 use sp_inherents::InherentData;
 use sp_runtime::{traits::Block as BlockT, Digest, DigestItem};
-use sp_timestamp::TimestampInherentData;
+use sp_timestamp::{TimestampInherentData, INHERENT_IDENTIFIER as TIMESTAMP_INHERENT_IDENTIFIER};
 use std::{marker::PhantomData, sync::Arc};
 
 /// Consensus data provider for Aura.
 pub struct AuraConsensusDataProvider<B, C, P> {
 	// slot duration
 	slot_duration: SlotDuration,
+	// handle to move the time of the next blocks
+	time_travel: Option<TimeTravel>,
 	// phantom data for required generics
 	_phantom: PhantomData<(B, C, P)>,
 }
@@ -54,7 +57,14 @@ where
 		let slot_duration = sc_consensus_aura::slot_duration(&*client)
 			.expect("slot_duration is always present; qed.");
 
-		Self { slot_duration, _phantom: PhantomData }
+		Self { slot_duration, time_travel: None, _phantom: PhantomData }
+	}
+
+	/// Apply the jumps queued on `time_travel` to the timestamp, and thus the slot, of the next
+	/// blocks.
+	pub fn with_time_travel(mut self, time_travel: TimeTravel) -> Self {
+		self.time_travel = Some(time_travel);
+		self
 	}
 }
 
@@ -71,6 +81,25 @@ where
 {
 	type Proof = P;
 
+	fn prepare_inherent_data(
+		&self,
+		_parent: &B::Header,
+		inherents: &mut InherentData,
+	) -> Result<(), Error> {
+		if let Some(time_travel) = &self.time_travel {
+			let timestamp = inherents
+				.timestamp_inherent_data()?
+				.ok_or_else(|| Error::StringError("No timestamp inherent data".into()))?;
+			let timestamp = time_travel.shift(timestamp.as_millis(), self.slot_duration);
+			inherents.replace_data(
+				TIMESTAMP_INHERENT_IDENTIFIER,
+				&sp_timestamp::Timestamp::new(timestamp),
+			);
+		}
+
+		Ok(())
+	}
+
 	fn create_digest(
 		&self,
 		_parent: &B::Header,
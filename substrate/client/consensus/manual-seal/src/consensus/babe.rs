@@ -19,7 +19,7 @@
 //! BABE consensus data provider, This allows manual seal author blocks that are valid for runtimes
 //! that expect babe-specific digests.
 
-use super::ConsensusDataProvider;
+use super::{timestamp::TimeTravel, ConsensusDataProvider};
 use crate::{Error, LOG_TARGET};
 use codec::Encode;
 use sc_client_api::{AuxStore, UsageProvider};
@@ -47,7 +47,7 @@ use sp_runtime::{
 	traits::{Block as BlockT, Header},
 	DigestItem,
 };
-use sp_timestamp::TimestampInherentData;
+use sp_timestamp::{TimestampInherentData, INHERENT_IDENTIFIER as TIMESTAMP_INHERENT_IDENTIFIER};
 
 /// Provides BABE-compatible predigests and BlockImportParams.
 /// Intended for use with BABE runtimes.
@@ -69,6 +69,9 @@ pub struct BabeConsensusDataProvider<B: BlockT, C, P> {
 
 	/// Authorities to be used for this babe chain.
 	authorities: Vec<(AuthorityId, BabeAuthorityWeight)>,
+
+	/// Handle to move the time of the next blocks.
+	time_travel: Option<TimeTravel>,
 	_phantom: PhantomData<P>,
 }
 
@@ -159,10 +162,17 @@ where
 			keystore,
 			epoch_changes,
 			authorities,
+			time_travel: None,
 			_phantom: Default::default(),
 		})
 	}
 
+	/// Apply the jumps queued on `time_travel` to the timestamp and slot of the next blocks.
+	pub fn with_time_travel(mut self, time_travel: TimeTravel) -> Self {
+		self.time_travel = Some(time_travel);
+		self
+	}
+
 	fn epoch(&self, parent: &B::Header, slot: Slot) -> Result<Epoch, Error> {
 		let epoch_changes = self.epoch_changes.shared_data();
 		let epoch_descriptor = epoch_changes
@@ -199,6 +209,28 @@ where
 {
 	type Proof = P;
 
+	fn prepare_inherent_data(
+		&self,
+		_parent: &B::Header,
+		inherents: &mut InherentData,
+	) -> Result<(), Error> {
+		if let Some(time_travel) = &self.time_travel {
+			let timestamp = inherents
+				.timestamp_inherent_data()?
+				.ok_or_else(|| Error::StringError("No timestamp inherent data".into()))?;
+			let timestamp = sp_timestamp::Timestamp::new(
+				time_travel.shift(timestamp.as_millis(), self.config.slot_duration()),
+			);
+			inherents.replace_data(TIMESTAMP_INHERENT_IDENTIFIER, &timestamp);
+			inherents.babe_replace_inherent_data(Slot::from_timestamp(
+				timestamp,
+				self.config.slot_duration(),
+			));
+		}
+
+		Ok(())
+	}
+
 	fn create_digest(&self, parent: &B::Header, inherents: &InherentData) -> Result<Digest, Error> {
 		let slot = inherents
 			.babe_inherent_data()?
@@ -33,10 +33,95 @@ use sp_inherents::{InherentData, InherentDataProvider, InherentIdentifier};
 use sp_runtime::traits::{Block as BlockT, Zero};
 use sp_timestamp::{InherentType, INHERENT_IDENTIFIER};
 use std::{
-	sync::{atomic, Arc},
+	sync::{atomic, Arc, Mutex},
 	time::SystemTime,
 };
 
+/// A jump in time requested through a [`TimeTravel`] handle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeJump {
+	/// Set the timestamp of the next block, in milliseconds since the unix epoch.
+	SetTimestamp(u64),
+	/// Move the timestamp of the next block forward by the given milliseconds.
+	AdvanceTime(u64),
+	/// Set the slot of the next block.
+	SetSlot(u64),
+	/// Move the slot of the next block forward by the given number of slots.
+	AdvanceSlots(u64),
+}
+
+/// Shared handle to move the time of the blocks built by manual seal in tests.
+///
+/// Jumps are queued until the next block is built, when they are applied and consumed in order
+/// by either of:
+/// - a [`SlotTimestampProvider`] attached to the handle with
+///   [`SlotTimestampProvider::with_time_travel`]. Since the provider derives the time of every
+///   following block from the slot of the best block, a jump shifts the time of all blocks built
+///   after it.
+/// - the [`AuraConsensusDataProvider`](super::aura::AuraConsensusDataProvider) or
+///   [`BabeConsensusDataProvider`](super::babe::BabeConsensusDataProvider) the handle is passed
+///   to with their `with_time_travel` method. It rewrites the timestamp and slot inherents of the next block, whatever inherent data
+///   providers are used, and keeps the distance covered by the jumps for all following blocks.
+#[derive(Debug, Clone, Default)]
+pub struct TimeTravel {
+	state: Arc<Mutex<TimeTravelState>>,
+}
+
+#[derive(Debug, Default)]
+struct TimeTravelState {
+	// jumps that weren't applied to a block yet
+	pending: Vec<TimeJump>,
+	// milliseconds the jumps applied by `TimeTravel::shift` moved the time so far
+	offset: i64,
+}
+
+impl TimeTravel {
+	/// Create a new handle with no pending jumps.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Queue a jump to be applied to the next block.
+	pub fn jump(&self, jump: TimeJump) {
+		self.state.lock().expect("lock is never poisoned; qed").pending.push(jump);
+	}
+
+	/// Apply the pending jumps to `unix_millis`, the timestamp the next block would have had
+	/// otherwise.
+	pub fn apply(&self, unix_millis: u64, slot_duration: SlotDuration) -> u64 {
+		let jumps =
+			std::mem::take(&mut self.state.lock().expect("lock is never poisoned; qed").pending);
+		Self::fold_jumps(jumps, unix_millis, slot_duration)
+	}
+
+	/// Move `unix_millis`, the timestamp the next block would have had otherwise, by all the jumps
+	/// applied so far and by the pending ones.
+	///
+	/// Unlike [`Self::apply`], the distance covered by the pending jumps is added to the
+	/// timestamps of all following blocks, so that time doesn't go back once the jumps are
+	/// consumed.
+	pub fn shift(&self, unix_millis: u64, slot_duration: SlotDuration) -> u64 {
+		let mut state = self.state.lock().expect("lock is never poisoned; qed");
+		let shifted = unix_millis.saturating_add_signed(state.offset);
+		let jumped = Self::fold_jumps(std::mem::take(&mut state.pending), shifted, slot_duration);
+		let distance = (jumped as i128 - shifted as i128).clamp(i64::MIN as i128, i64::MAX as i128);
+		state.offset = state.offset.saturating_add(distance as i64);
+		jumped
+	}
+
+	fn fold_jumps(jumps: Vec<TimeJump>, unix_millis: u64, slot_duration: SlotDuration) -> u64 {
+		let slot_duration = slot_duration.as_millis() as u64;
+
+		jumps.into_iter().fold(unix_millis, |unix_millis, jump| match jump {
+			TimeJump::SetTimestamp(timestamp) => timestamp,
+			TimeJump::AdvanceTime(millis) => unix_millis.saturating_add(millis),
+			TimeJump::SetSlot(slot) => slot.saturating_mul(slot_duration),
+			TimeJump::AdvanceSlots(slots) =>
+				unix_millis.saturating_add(slots.saturating_mul(slot_duration)),
+		})
+	}
+}
+
 /// Provide duration since unix epoch in millisecond for timestamp inherent.
 /// Mocks the timestamp inherent to always produce a valid timestamp for the next slot.
 ///
@@ -122,6 +207,15 @@ impl SlotTimestampProvider {
 		Ok(time)
 	}
 
+	/// Apply the jumps queued on `time_travel` to the timestamp of the next block.
+	///
+	/// Must be called before reading [`Self::slot`], e.g. to build the BABE slot inherent.
+	pub fn with_time_travel(self, time_travel: &TimeTravel) -> Self {
+		let Self { unix_millis, slot_duration } = self;
+		let unix_millis = time_travel.apply(unix_millis.into_inner(), slot_duration);
+		Self { unix_millis: atomic::AtomicU64::new(unix_millis), slot_duration }
+	}
+
 	/// Get the current slot number
 	pub fn slot(&self) -> Slot {
 		Slot::from_timestamp(
@@ -38,6 +38,9 @@ mod codes {
 	pub const CONSENSUS_ERROR: i32 = 14_000;
 	pub const INHERENTS_ERROR: i32 = 15_000;
 	pub const BLOCKCHAIN_ERROR: i32 = 16_000;
+	pub const NO_BLOCKS_TO_SEAL: i32 = 17_000;
+	pub const TIME_TRAVEL_DISABLED: i32 = 18_000;
+	pub const TOO_MANY_BLOCKS: i32 = 19_000;
 	pub const UNKNOWN_ERROR: i32 = 20_000;
 }
 
@@ -61,6 +64,20 @@ pub enum Error {
 	/// error encountered during finalization
 	#[error("Finalization Error: {0}")]
 	BlockchainError(#[from] BlockchainError),
+	/// No blocks left to seal, e.g. the chain is already past the requested block number
+	#[error("No blocks to seal, the best block is already at or past the requested block")]
+	NoBlocksToSeal,
+	/// More blocks were requested than a single command may seal
+	#[error("Requested {requested} blocks, at most {max} blocks can be sealed at once")]
+	TooManyBlocks {
+		/// number of requested blocks
+		requested: u64,
+		/// maximum number of blocks sealed at once
+		max: u64,
+	},
+	/// Time travel was requested but isn't enabled for this node
+	#[error("Time travel is not enabled, the node has to be set up with a `TimeTravel` handle")]
+	TimeTravelDisabled,
 	/// Supplied parent_hash doesn't exist in chain
 	#[error("Supplied parent_hash: {0} doesn't exist in chain")]
 	BlockNotFound(String),
@@ -100,6 +117,9 @@ impl Error {
 			ConsensusError(_) => codes::CONSENSUS_ERROR,
 			InherentError(_) => codes::INHERENTS_ERROR,
 			BlockchainError(_) => codes::BLOCKCHAIN_ERROR,
+			NoBlocksToSeal => codes::NO_BLOCKS_TO_SEAL,
+			TimeTravelDisabled => codes::TIME_TRAVEL_DISABLED,
+			TooManyBlocks { .. } => codes::TOO_MANY_BLOCKS,
 			SendError(_) | Canceled(_) => codes::SERVER_SHUTTING_DOWN,
 			_ => codes::UNKNOWN_ERROR,
 		}
@@ -49,10 +49,14 @@ pub use self::{
 	error::Error,
 	finalize_block::{finalize_block, FinalizeBlockParams},
 	rpc::{CreatedBlock, EngineCommand},
-	seal_block::{seal_block, SealBlockParams, MAX_PROPOSAL_DURATION},
+	seal_block::{
+		seal_block, seal_empty_blocks, EmptyBlocksTarget, SealBlockParams, SealEmptyBlocksParams,
+		MAX_EMPTY_BLOCKS, MAX_PROPOSAL_DURATION,
+	},
 };
+use sc_block_builder::BlockBuilderApi;
 use sc_transaction_pool_api::TransactionPool;
-use sp_api::ProvideRuntimeApi;
+use sp_api::{CallApiAt, ProvideRuntimeApi};
 
 const LOG_TARGET: &str = "manual-seal";
 
@@ -165,7 +169,8 @@ pub async fn run_manual_seal<B, BI, CB, E, C, TP, SC, CS, CIDP, P>(
 ) where
 	B: BlockT + 'static,
 	BI: BlockImport<B, Error = sp_consensus::Error> + Send + Sync + 'static,
-	C: HeaderBackend<B> + Finalizer<B, CB> + ProvideRuntimeApi<B> + 'static,
+	C: HeaderBackend<B> + Finalizer<B, CB> + ProvideRuntimeApi<B> + CallApiAt<B> + 'static,
+	C::Api: BlockBuilderApi<B>,
 	CB: ClientBackend<B> + 'static,
 	E: Environment<B> + 'static,
 	E::Proposer: Proposer<B, Proof = P>,
@@ -193,6 +198,34 @@ pub async fn run_manual_seal<B, BI, CB, E, C, TP, SC, CS, CIDP, P>(
 				})
 				.await;
 			},
+			EngineCommand::SealEmptyBlocks { count, finalize, sender } => {
+				seal_empty_blocks(SealEmptyBlocksParams {
+					sender,
+					target: EmptyBlocksTarget::Count(count),
+					finalize,
+					select_chain: &select_chain,
+					block_import: &mut block_import,
+					consensus_data_provider: consensus_data_provider.as_deref(),
+					client: client.clone(),
+					create_inherent_data_providers: &create_inherent_data_providers,
+					_phantom: PhantomData::<<E::Proposer as Proposer<B>>::ProofRecording>,
+				})
+				.await;
+			},
+			EngineCommand::JumpToBlock { number, finalize, sender } => {
+				seal_empty_blocks(SealEmptyBlocksParams {
+					sender,
+					target: EmptyBlocksTarget::Number(number),
+					finalize,
+					select_chain: &select_chain,
+					block_import: &mut block_import,
+					consensus_data_provider: consensus_data_provider.as_deref(),
+					client: client.clone(),
+					create_inherent_data_providers: &create_inherent_data_providers,
+					_phantom: PhantomData::<<E::Proposer as Proposer<B>>::ProofRecording>,
+				})
+				.await;
+			},
 			EngineCommand::FinalizeBlock { hash, sender, justification } => {
 				let justification = justification.map(|j| (MANUAL_SEAL_ENGINE_ID, j));
 				finalize_block(FinalizeBlockParams {
@@ -224,7 +257,8 @@ pub async fn run_instant_seal<B, BI, CB, E, C, TP, SC, CIDP, P>(
 ) where
 	B: BlockT + 'static,
 	BI: BlockImport<B, Error = sp_consensus::Error> + Send + Sync + 'static,
-	C: HeaderBackend<B> + Finalizer<B, CB> + ProvideRuntimeApi<B> + 'static,
+	C: HeaderBackend<B> + Finalizer<B, CB> + ProvideRuntimeApi<B> + CallApiAt<B> + 'static,
+	C::Api: BlockBuilderApi<B>,
 	CB: ClientBackend<B> + 'static,
 	E: Environment<B> + 'static,
 	E::Proposer: Proposer<B, Proof = P>,
@@ -274,7 +308,8 @@ pub async fn run_instant_seal_and_finalize<B, BI, CB, E, C, TP, SC, CIDP, P>(
 ) where
 	B: BlockT + 'static,
 	BI: BlockImport<B, Error = sp_consensus::Error> + Send + Sync + 'static,
-	C: HeaderBackend<B> + Finalizer<B, CB> + ProvideRuntimeApi<B> + 'static,
+	C: HeaderBackend<B> + Finalizer<B, CB> + ProvideRuntimeApi<B> + CallApiAt<B> + 'static,
+	C::Api: BlockBuilderApi<B>,
 	CB: ClientBackend<B> + 'static,
 	E: Environment<B> + 'static,
 	E::Proposer: Proposer<B, Proof = P>,
@@ -347,13 +382,15 @@ pub async fn run_delayed_finalize<B, CB, C, S>(
 mod tests {
 	use super::*;
 	use sc_basic_authorship::ProposerFactory;
+	use sc_client_api::BlockBackend;
 	use sc_consensus::ImportedAux;
 	use sc_transaction_pool::{BasicPool, FullChainApi, Options, RevalidationType};
 	use sc_transaction_pool_api::{MaintainedTransactionPool, TransactionPool, TransactionSource};
 	use sp_inherents::InherentData;
 	use sp_runtime::generic::{Digest, DigestItem};
 	use substrate_test_runtime_client::{
-		AccountKeyring::*, DefaultTestClientBuilderExt, TestClientBuilder, TestClientBuilderExt,
+		runtime::Block, AccountKeyring::*, DefaultTestClientBuilderExt, TestClientBuilder,
+		TestClientBuilderExt,
 	};
 	use substrate_test_runtime_transaction_pool::{uxt, TestApi};
 
@@ -811,4 +848,246 @@ mod tests {
 		let header = client.header(created_block.hash).unwrap().unwrap();
 		assert_eq!(header.number, 1);
 	}
+
+	#[tokio::test]
+	async fn manual_seal_empty_blocks_and_jump() {
+		let builder = TestClientBuilder::new();
+		let (client, select_chain) = builder.build_with_longest_chain();
+		let client = Arc::new(client);
+		let spawner = sp_core::testing::TaskExecutor::new();
+		let genesis_hash = client.info().genesis_hash;
+		let pool_api = Arc::new(FullChainApi::new(client.clone(), None, &spawner.clone()));
+		let pool = Arc::new(BasicPool::with_revalidation_type(
+			Options::default(),
+			true.into(),
+			pool_api,
+			None,
+			RevalidationType::Full,
+			spawner.clone(),
+			0,
+			genesis_hash,
+			genesis_hash,
+		));
+		let env = ProposerFactory::new(spawner.clone(), client.clone(), pool.clone(), None, None);
+		let (mut sink, commands_stream) = futures::channel::mpsc::channel(1024);
+		let future = run_manual_seal(ManualSealParams {
+			block_import: client.clone(),
+			env,
+			client: client.clone(),
+			pool: pool.clone(),
+			commands_stream,
+			select_chain,
+			consensus_data_provider: None,
+			create_inherent_data_providers: |_, _| async { Ok(()) },
+		});
+		std::thread::spawn(|| {
+			let rt = tokio::runtime::Runtime::new().unwrap();
+			// spawn the background authorship task
+			rt.block_on(future);
+		});
+
+		// seal a few blocks, leaving the ready transactions in the pool.
+		pool.submit_one(genesis_hash, SOURCE, uxt(Alice, 0)).await.unwrap();
+		let (tx, rx) = futures::channel::oneshot::channel();
+		sink.send(EngineCommand::SealEmptyBlocks { count: 3, finalize: false, sender: Some(tx) })
+			.await
+			.unwrap();
+		let created_block = rx.await.unwrap().unwrap();
+		assert_eq!(client.info().best_number, 3);
+		assert_eq!(client.info().best_hash, created_block.hash);
+		for number in 1..=3 {
+			let hash = client.hash(number).unwrap().unwrap();
+			assert!(client.body(hash).unwrap().unwrap().is_empty());
+		}
+		assert_eq!(pool.status().ready, 1);
+
+		// jump ahead and finalize on the way.
+		let (tx, rx) = futures::channel::oneshot::channel();
+		sink.send(EngineCommand::JumpToBlock { number: 10, finalize: true, sender: Some(tx) })
+			.await
+			.unwrap();
+		let created_block = rx.await.unwrap().unwrap();
+		assert_eq!(client.info().best_number, 10);
+		assert_eq!(client.info().finalized_hash, created_block.hash);
+
+		// the chain is already at the requested block.
+		let (tx, rx) = futures::channel::oneshot::channel();
+		sink.send(EngineCommand::JumpToBlock { number: 5, finalize: false, sender: Some(tx) })
+			.await
+			.unwrap();
+		assert!(matches!(rx.await.unwrap(), Err(Error::NoBlocksToSeal)));
+		assert_eq!(client.info().best_number, 10);
+
+		// the number of blocks sealed at once is bounded.
+		let (tx, rx) = futures::channel::oneshot::channel();
+		sink.send(EngineCommand::JumpToBlock {
+			number: 11 + MAX_EMPTY_BLOCKS,
+			finalize: false,
+			sender: Some(tx),
+		})
+		.await
+		.unwrap();
+		assert!(matches!(
+			rx.await.unwrap(),
+			Err(Error::TooManyBlocks { requested, max: MAX_EMPTY_BLOCKS })
+				if requested == MAX_EMPTY_BLOCKS + 1
+		));
+		assert_eq!(client.info().best_number, 10);
+	}
+
+	#[tokio::test]
+	async fn manual_seal_time_travel_moves_aura_slot() {
+		use consensus::{
+			aura::AuraConsensusDataProvider,
+			timestamp::{TimeJump, TimeTravel},
+		};
+		use sp_consensus_aura::sr25519::AuthoritySignature;
+		use std::sync::atomic::{AtomicU64, Ordering};
+
+		let builder = TestClientBuilder::new();
+		let (client, select_chain) = builder.build_with_longest_chain();
+		let client = Arc::new(client);
+		let spawner = sp_core::testing::TaskExecutor::new();
+		let genesis_hash = client.info().genesis_hash;
+		let pool_api = Arc::new(FullChainApi::new(client.clone(), None, &spawner.clone()));
+		let pool = Arc::new(BasicPool::with_revalidation_type(
+			Options::default(),
+			true.into(),
+			pool_api,
+			None,
+			RevalidationType::Full,
+			spawner.clone(),
+			0,
+			genesis_hash,
+			genesis_hash,
+		));
+		let env = ProposerFactory::new(spawner.clone(), client.clone(), pool.clone(), None, None);
+		let time_travel = TimeTravel::new();
+		let consensus_data_provider = AuraConsensusDataProvider::<_, _, ()>::new(client.clone())
+			.with_time_travel(time_travel.clone());
+		// the test runtime has a slot duration of one second, every block moves one slot ahead.
+		let now = Arc::new(AtomicU64::new(10_000));
+		let (mut sink, commands_stream) = futures::channel::mpsc::channel(1024);
+		let future = run_manual_seal(ManualSealParams {
+			block_import: client.clone(),
+			env,
+			client: client.clone(),
+			pool: pool.clone(),
+			commands_stream,
+			select_chain,
+			consensus_data_provider: Some(Box::new(consensus_data_provider)),
+			create_inherent_data_providers: move |_, _| {
+				let timestamp = now.fetch_add(1_000, Ordering::SeqCst);
+				async move { Ok(sp_timestamp::InherentDataProvider::new(timestamp.into())) }
+			},
+		});
+		std::thread::spawn(|| {
+			let rt = tokio::runtime::Runtime::new().unwrap();
+			// spawn the background authorship task
+			rt.block_on(future);
+		});
+
+		let (tx, rx) = futures::channel::oneshot::channel();
+		sink.send(EngineCommand::SealNewBlock {
+			create_empty: true,
+			finalize: false,
+			parent_hash: None,
+			sender: Some(tx),
+		})
+		.await
+		.unwrap();
+		rx.await.unwrap().unwrap();
+
+		time_travel.jump(TimeJump::AdvanceSlots(5));
+		let (tx, rx) = futures::channel::oneshot::channel();
+		sink.send(EngineCommand::SealEmptyBlocks { count: 2, finalize: false, sender: Some(tx) })
+			.await
+			.unwrap();
+		rx.await.unwrap().unwrap();
+
+		let slots = (1..=3)
+			.map(|number| {
+				let header = client.header(client.hash(number).unwrap().unwrap()).unwrap().unwrap();
+				*sc_consensus_aura::find_pre_digest::<Block, AuthoritySignature>(&header).unwrap()
+			})
+			.collect::<Vec<_>>();
+		// the jump also moves the blocks built after the next one.
+		assert_eq!(slots, vec![10, 16, 17]);
+	}
+
+	#[test]
+	fn babe_time_travel_moves_timestamp_and_slot() {
+		use consensus::{
+			babe::BabeConsensusDataProvider,
+			timestamp::{TimeJump, TimeTravel},
+		};
+		use sc_consensus_epochs::SharedEpochChanges;
+		use sp_consensus_babe::{inherents::BabeInherentData, AuthorityId};
+		use sp_consensus_slots::Slot;
+		use sp_timestamp::TimestampInherentData;
+
+		let client = Arc::new(TestClientBuilder::new().build());
+		let genesis = client.header(client.info().genesis_hash).unwrap().unwrap();
+		let slot_duration = sc_consensus_babe::configuration(&*client).unwrap().slot_duration();
+		let time_travel = TimeTravel::new();
+		let provider = BabeConsensusDataProvider::<_, _, ()>::new(
+			client.clone(),
+			Arc::new(sp_keystore::testing::MemoryKeystore::new()),
+			SharedEpochChanges::<Block, _>::new(Default::default()),
+			vec![(AuthorityId::from(Alice.public()), 1)],
+		)
+		.unwrap()
+		.with_time_travel(time_travel.clone());
+
+		let inherent_data = |slot: u64| {
+			let mut inherent_data = InherentData::new();
+			inherent_data
+				.put_data(
+					sp_timestamp::INHERENT_IDENTIFIER,
+					&sp_timestamp::Timestamp::new(slot * slot_duration.as_millis()),
+				)
+				.unwrap();
+			inherent_data.babe_replace_inherent_data(Slot::from(slot));
+			inherent_data
+		};
+		let prepared = |mut inherent_data: InherentData| {
+			provider.prepare_inherent_data(&genesis, &mut inherent_data).unwrap();
+			let timestamp = inherent_data.timestamp_inherent_data().unwrap().unwrap();
+			let slot = inherent_data.babe_inherent_data().unwrap().unwrap();
+			(timestamp.as_millis() / slot_duration.as_millis(), *slot)
+		};
+
+		assert_eq!(prepared(inherent_data(10)), (10, 10));
+
+		time_travel.jump(TimeJump::AdvanceSlots(5));
+		assert_eq!(prepared(inherent_data(11)), (16, 16));
+		// the distance is kept once the jump is consumed.
+		assert_eq!(prepared(inherent_data(12)), (17, 17));
+
+		time_travel.jump(TimeJump::SetSlot(100));
+		assert_eq!(prepared(inherent_data(13)), (100, 100));
+		assert_eq!(prepared(inherent_data(14)), (101, 101));
+	}
+
+	#[test]
+	fn time_travel_applies_pending_jumps_once() {
+		use consensus::timestamp::{TimeJump, TimeTravel};
+		use sp_consensus_slots::SlotDuration;
+
+		let slot_duration = SlotDuration::from_millis(6000);
+		let time_travel = TimeTravel::new();
+		assert_eq!(time_travel.apply(1_000, slot_duration), 1_000);
+
+		time_travel.jump(TimeJump::SetSlot(10));
+		time_travel.jump(TimeJump::AdvanceSlots(2));
+		time_travel.jump(TimeJump::AdvanceTime(500));
+		assert_eq!(time_travel.apply(1_000, slot_duration), 12 * 6000 + 500);
+
+		// jumps are consumed.
+		assert_eq!(time_travel.apply(1_000, slot_duration), 1_000);
+
+		time_travel.jump(TimeJump::AdvanceTime(500));
+		time_travel.clone().jump(TimeJump::SetTimestamp(42_000));
+		assert_eq!(time_travel.apply(1_000, slot_duration), 42_000);
+	}
 }
@@ -18,7 +18,10 @@
 
 //! RPC interface for the `ManualSeal` Engine.
 
-use crate::error::Error;
+use crate::{
+	consensus::timestamp::{TimeJump, TimeTravel},
+	error::Error,
+};
 use futures::{
 	channel::{mpsc, oneshot},
 	SinkExt,
@@ -53,6 +56,25 @@ pub enum EngineCommand<Hash> {
 		/// sender to report errors/success to the rpc.
 		sender: Sender<CreatedBlock<Hash>>,
 	},
+	/// Tells the engine to seal `count` blocks without transactions on top of the best block.
+	SealEmptyBlocks {
+		/// number of blocks to seal.
+		count: u32,
+		/// instantly finalize the blocks?
+		finalize: bool,
+		/// sender to report the last created block or the first error to the rpc.
+		sender: Sender<CreatedBlock<Hash>>,
+	},
+	/// Tells the engine to seal blocks without transactions on top of the best block until it
+	/// reaches `number`.
+	JumpToBlock {
+		/// number of the last block to seal.
+		number: u64,
+		/// instantly finalize the blocks?
+		finalize: bool,
+		/// sender to report the last created block or the first error to the rpc.
+		sender: Sender<CreatedBlock<Hash>>,
+	},
 	/// Tells the engine to finalize the block with the supplied hash
 	FinalizeBlock {
 		/// hash of the block
@@ -83,11 +105,39 @@ pub trait ManualSealApi<Hash> {
 		hash: Hash,
 		justification: Option<EncodedJustification>,
 	) -> RpcResult<bool>;
+
+	/// Instructs the manual-seal authorship task to create `count` blocks without transactions,
+	/// at most [`MAX_EMPTY_BLOCKS`](crate::MAX_EMPTY_BLOCKS). Returns the last created block.
+	#[method(name = "engine_createBlocks")]
+	async fn create_blocks(&self, count: u32, finalize: bool) -> RpcResult<CreatedBlock<Hash>>;
+
+	/// Instructs the manual-seal authorship task to create blocks without transactions until the
+	/// best block has the given number, at most [`MAX_EMPTY_BLOCKS`](crate::MAX_EMPTY_BLOCKS).
+	/// Returns the last created block.
+	#[method(name = "engine_jumpToBlock")]
+	async fn jump_to_block(&self, number: u64, finalize: bool) -> RpcResult<CreatedBlock<Hash>>;
+
+	/// Sets the timestamp, in milliseconds since the unix epoch, of the next block.
+	#[method(name = "engine_setTimestamp")]
+	fn set_timestamp(&self, timestamp: u64) -> RpcResult<bool>;
+
+	/// Moves the timestamp of the next block forward by the given milliseconds.
+	#[method(name = "engine_advanceTime")]
+	fn advance_time(&self, millis: u64) -> RpcResult<bool>;
+
+	/// Sets the slot of the next block.
+	#[method(name = "engine_setSlot")]
+	fn set_slot(&self, slot: u64) -> RpcResult<bool>;
+
+	/// Moves the slot of the next block forward by the given number of slots.
+	#[method(name = "engine_advanceSlots")]
+	fn advance_slots(&self, slots: u64) -> RpcResult<bool>;
 }
 
 /// A struct that implements the [`ManualSealApiServer`].
 pub struct ManualSeal<Hash> {
 	import_block_channel: mpsc::Sender<EngineCommand<Hash>>,
+	time_travel: Option<TimeTravel>,
 }
 
 /// return type of `engine_createBlock`
@@ -104,7 +154,38 @@ pub struct CreatedBlock<Hash> {
 impl<Hash> ManualSeal<Hash> {
 	/// Create new `ManualSeal` with the given reference to the client.
 	pub fn new(import_block_channel: mpsc::Sender<EngineCommand<Hash>>) -> Self {
-		Self { import_block_channel }
+		Self { import_block_channel, time_travel: None }
+	}
+
+	/// Enable the time travel methods, which queue their jumps on `time_travel`.
+	///
+	/// The same handle has to be passed to the Aura or BABE consensus data provider, or attached
+	/// to the [`SlotTimestampProvider`](crate::consensus::timestamp::SlotTimestampProvider)
+	/// created for every block, see [`TimeTravel`].
+	pub fn with_time_travel(mut self, time_travel: TimeTravel) -> Self {
+		self.time_travel = Some(time_travel);
+		self
+	}
+
+	fn time_travel(&self, jump: TimeJump) -> RpcResult<bool> {
+		let time_travel = self.time_travel.as_ref().ok_or(Error::TimeTravelDisabled)?;
+		time_travel.jump(jump);
+		Ok(true)
+	}
+
+	async fn send_seal_command(
+		&self,
+		command: impl FnOnce(Sender<CreatedBlock<Hash>>) -> EngineCommand<Hash>,
+	) -> RpcResult<CreatedBlock<Hash>> {
+		let mut sink = self.import_block_channel.clone();
+		let (sender, receiver) = oneshot::channel();
+		sink.send(command(Some(sender))).await?;
+
+		match receiver.await {
+			Ok(Ok(rx)) => Ok(rx),
+			Ok(Err(e)) => Err(e.into()),
+			Err(e) => Err(JsonRpseeError::to_call_error(e)),
+		}
 	}
 }
 
@@ -146,6 +227,32 @@ impl<Hash: Send + 'static> ManualSealApiServer<Hash> for ManualSeal<Hash> {
 		sink.send(command).await?;
 		receiver.await.map(|_| true).map_err(|e| JsonRpseeError::to_call_error(e))
 	}
+
+	async fn create_blocks(&self, count: u32, finalize: bool) -> RpcResult<CreatedBlock<Hash>> {
+		self.send_seal_command(|sender| EngineCommand::SealEmptyBlocks { count, finalize, sender })
+			.await
+	}
+
+	async fn jump_to_block(&self, number: u64, finalize: bool) -> RpcResult<CreatedBlock<Hash>> {
+		self.send_seal_command(|sender| EngineCommand::JumpToBlock { number, finalize, sender })
+			.await
+	}
+
+	fn set_timestamp(&self, timestamp: u64) -> RpcResult<bool> {
+		self.time_travel(TimeJump::SetTimestamp(timestamp))
+	}
+
+	fn advance_time(&self, millis: u64) -> RpcResult<bool> {
+		self.time_travel(TimeJump::AdvanceTime(millis))
+	}
+
+	fn set_slot(&self, slot: u64) -> RpcResult<bool> {
+		self.time_travel(TimeJump::SetSlot(slot))
+	}
+
+	fn advance_slots(&self, slots: u64) -> RpcResult<bool> {
+		self.time_travel(TimeJump::AdvanceSlots(slots))
+	}
 }
 
 /// report any errors or successes encountered by the authorship task back
@@ -19,19 +19,26 @@
 //! Block sealing utilities
 
 use crate::{rpc, ConsensusDataProvider, CreatedBlock, Error};
-use futures::prelude::*;
+use futures::{channel::oneshot, prelude::*};
+use sc_block_builder::{BlockBuilderApi, BlockBuilderBuilder};
 use sc_consensus::{BlockImport, BlockImportParams, ForkChoiceStrategy, ImportResult, StateAction};
 use sc_transaction_pool_api::TransactionPool;
-use sp_api::ProvideRuntimeApi;
+use sp_api::{CallApiAt, ProvideRuntimeApi, StorageChanges};
 use sp_blockchain::HeaderBackend;
-use sp_consensus::{self, BlockOrigin, Environment, Proposer, SelectChain};
-use sp_inherents::{CreateInherentDataProviders, InherentDataProvider};
-use sp_runtime::traits::{Block as BlockT, Header as HeaderT};
-use std::{sync::Arc, time::Duration};
+use sp_consensus::{self, BlockOrigin, Environment, ProofRecording, Proposer, SelectChain};
+use sp_inherents::{CreateInherentDataProviders, InherentData, InherentDataProvider};
+use sp_runtime::{
+	traits::{Block as BlockT, Header as HeaderT},
+	Digest, SaturatedConversion,
+};
+use std::{marker::PhantomData, sync::Arc, time::Duration};
 
 /// max duration for creating a proposal in secs
 pub const MAX_PROPOSAL_DURATION: u64 = 10;
 
+/// max number of blocks sealed by a single [`seal_empty_blocks`] call
+pub const MAX_EMPTY_BLOCKS: u64 = 10_000;
+
 /// params for sealing a new block
 pub struct SealBlockParams<'a, B: BlockT, BI, SC, C: ProvideRuntimeApi<B>, E, TP, CIDP, P> {
 	/// if true, empty blocks(without extrinsics) will be created.
@@ -99,22 +106,12 @@ pub async fn seal_block<B, BI, SC, C, E, TP, CIDP, P>(
 			None => select_chain.best_chain().await?,
 		};
 
-		let inherent_data_providers = create_inherent_data_providers
-			.create_inherent_data_providers(parent.hash(), ())
-			.await
-			.map_err(|e| Error::Other(e))?;
-
-		let inherent_data = inherent_data_providers.create_inherent_data().await?;
+		let (inherent_data, digest) =
+			prepare_block(&parent, create_inherent_data_providers, digest_provider).await?;
 
 		let proposer = env.init(&parent).map_err(|err| Error::StringError(err.to_string())).await?;
 		let inherents_len = inherent_data.len();
 
-		let digest = if let Some(digest_provider) = digest_provider {
-			digest_provider.create_digest(&parent, &inherent_data)?
-		} else {
-			Default::default()
-		};
-
 		let proposal = proposer
 			.propose(
 				inherent_data.clone(),
@@ -129,35 +126,202 @@ pub async fn seal_block<B, BI, SC, C, E, TP, CIDP, P>(
 			return Err(Error::EmptyTransactionPool)
 		}
 
-		let (header, body) = proposal.block.deconstruct();
-		let proof = proposal.proof;
-		let proof_size = proof.encoded_size();
-		let mut params = BlockImportParams::new(BlockOrigin::Own, header.clone());
-		params.body = Some(body);
-		params.finalized = finalize;
-		params.fork_choice = Some(ForkChoiceStrategy::LongestChain);
-		params.state_action = StateAction::ApplyChanges(sc_consensus::StorageChanges::Changes(
+		import_block(
+			&parent,
+			proposal.block,
 			proposal.storage_changes,
-		));
+			proposal.proof,
+			&inherent_data,
+			finalize,
+			digest_provider,
+			block_import,
+		)
+		.await
+	};
+
+	rpc::send_result(&mut sender, future.await)
+}
+
+/// How many blocks [`seal_empty_blocks`] seals.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmptyBlocksTarget {
+	/// Seal the given number of blocks.
+	Count(u32),
+	/// Seal blocks until the best block has the given number.
+	Number(u64),
+}
+
+/// params for sealing a sequence of empty blocks on top of the best block
+pub struct SealEmptyBlocksParams<'a, B: BlockT, BI, SC, C, CIDP, PR: ProofRecording> {
+	/// how many blocks to seal
+	pub target: EmptyBlocksTarget,
+	/// instantly finalize the blocks?
+	pub finalize: bool,
+	/// sender to report the last created block or the first error to the rpc.
+	pub sender: rpc::Sender<CreatedBlock<<B as BlockT>::Hash>>,
+	/// client to build the blocks with
+	pub client: Arc<C>,
+	/// SelectChain object
+	pub select_chain: &'a SC,
+	/// Digest provider for inclusion in blocks.
+	pub consensus_data_provider: Option<&'a dyn ConsensusDataProvider<B, Proof = PR::Proof>>,
+	/// block import object
+	pub block_import: &'a mut BI,
+	/// Something that can create the inherent data providers.
+	pub create_inherent_data_providers: &'a CIDP,
+	/// whether to record a storage proof of the blocks, usually the
+	/// [`Proposer::ProofRecording`] of the proposers used for [`seal_block`].
+	pub _phantom: PhantomData<PR>,
+}
 
-		if let Some(digest_provider) = digest_provider {
-			digest_provider.append_block_import(&parent, &mut params, &inherent_data, proof)?;
+/// seals a sequence of blocks on top of the best block, e.g. to quickly move a test chain forward.
+///
+/// The blocks only contain the inherents, transactions ready in the pool are left there. At most
+/// [`MAX_EMPTY_BLOCKS`] blocks are sealed, larger targets are rejected with
+/// [`Error::TooManyBlocks`].
+pub async fn seal_empty_blocks<B, BI, SC, C, CIDP, PR>(
+	SealEmptyBlocksParams {
+		target,
+		finalize,
+		client,
+		select_chain,
+		block_import,
+		create_inherent_data_providers,
+		consensus_data_provider,
+		mut sender,
+		_phantom,
+	}: SealEmptyBlocksParams<'_, B, BI, SC, C, CIDP, PR>,
+) where
+	B: BlockT,
+	BI: BlockImport<B, Error = sp_consensus::Error> + Send + Sync + 'static,
+	C: HeaderBackend<B> + CallApiAt<B> + ProvideRuntimeApi<B>,
+	C::Api: BlockBuilderApi<B>,
+	SC: SelectChain<B>,
+	CIDP: CreateInherentDataProviders<B, ()>,
+	PR: ProofRecording,
+	PR::Proof: codec::Encode,
+{
+	let future = async {
+		let best_header = select_chain.best_chain().await?;
+		let count = match target {
+			EmptyBlocksTarget::Count(count) => count as u64,
+			EmptyBlocksTarget::Number(number) => {
+				let best_number: u64 = (*best_header.number()).saturated_into();
+				number.saturating_sub(best_number)
+			},
+		};
+		if count > MAX_EMPTY_BLOCKS {
+			return Err(Error::TooManyBlocks { requested: count, max: MAX_EMPTY_BLOCKS })
 		}
 
-		// Make sure we return the same post-hash that will be calculated when importing the block
-		// This is important in case the digest_provider added any signature, seal, ect.
-		let mut post_header = header.clone();
-		post_header.digest_mut().logs.extend(params.post_digests.iter().cloned());
-
-		match block_import.import_block(params).await? {
-			ImportResult::Imported(aux) => Ok(CreatedBlock {
-				hash: <B as BlockT>::Header::hash(&post_header),
-				aux,
-				proof_size,
-			}),
-			other => Err(other.into()),
+		let mut parent = best_header;
+		let mut created_block = None;
+		for _ in 0..count {
+			let (inherent_data, digest) =
+				prepare_block(&parent, create_inherent_data_providers, consensus_data_provider)
+					.await?;
+
+			let mut block_builder = BlockBuilderBuilder::new(&*client)
+				.on_parent_block(parent.hash())
+				.with_parent_block_number(*parent.number())
+				.with_proof_recording(PR::ENABLED)
+				.with_inherent_digests(digest)
+				.build()?;
+			for inherent in block_builder.create_inherents(inherent_data.clone())? {
+				block_builder.push(inherent)?;
+			}
+			let (block, storage_changes, proof) = block_builder.build()?.into_inner();
+			let proof = PR::into_proof(proof).map_err(|err| Error::StringError(err.to_string()))?;
+
+			let block = import_block(
+				&parent,
+				block,
+				storage_changes,
+				proof,
+				&inherent_data,
+				finalize,
+				consensus_data_provider,
+				&mut *block_import,
+			)
+			.await?;
+
+			// the imported header also holds the post digests.
+			parent = client
+				.header(block.hash)?
+				.ok_or_else(|| Error::BlockNotFound(format!("{}", block.hash)))?;
+			created_block = Some(block);
 		}
+
+		created_block.ok_or(Error::NoBlocksToSeal)
 	};
 
 	rpc::send_result(&mut sender, future.await)
 }
+
+/// Create the inherent data and the digest of a block built on `parent`.
+async fn prepare_block<B, CIDP, P>(
+	parent: &B::Header,
+	create_inherent_data_providers: &CIDP,
+	digest_provider: Option<&dyn ConsensusDataProvider<B, Proof = P>>,
+) -> Result<(InherentData, Digest), Error>
+where
+	B: BlockT,
+	CIDP: CreateInherentDataProviders<B, ()>,
+{
+	let inherent_data_providers = create_inherent_data_providers
+		.create_inherent_data_providers(parent.hash(), ())
+		.await
+		.map_err(|e| Error::Other(e))?;
+
+	let mut inherent_data = inherent_data_providers.create_inherent_data().await?;
+
+	let digest = if let Some(digest_provider) = digest_provider {
+		digest_provider.prepare_inherent_data(parent, &mut inherent_data)?;
+		digest_provider.create_digest(parent, &inherent_data)?
+	} else {
+		Default::default()
+	};
+
+	Ok((inherent_data, digest))
+}
+
+/// Import a block built on `parent` and return its post-hash.
+async fn import_block<B, BI, P>(
+	parent: &B::Header,
+	block: B,
+	storage_changes: StorageChanges<B>,
+	proof: P,
+	inherent_data: &InherentData,
+	finalize: bool,
+	digest_provider: Option<&dyn ConsensusDataProvider<B, Proof = P>>,
+	block_import: &mut BI,
+) -> Result<CreatedBlock<B::Hash>, Error>
+where
+	B: BlockT,
+	BI: BlockImport<B, Error = sp_consensus::Error> + Send + Sync + 'static,
+	P: codec::Encode,
+{
+	let (header, body) = block.deconstruct();
+	let proof_size = proof.encoded_size();
+	let mut params = BlockImportParams::new(BlockOrigin::Own, header.clone());
+	params.body = Some(body);
+	params.finalized = finalize;
+	params.fork_choice = Some(ForkChoiceStrategy::LongestChain);
+	params.state_action =
+		StateAction::ApplyChanges(sc_consensus::StorageChanges::Changes(storage_changes));
+
+	if let Some(digest_provider) = digest_provider {
+		digest_provider.append_block_import(parent, &mut params, inherent_data, proof)?;
+	}
+
+	// Make sure we return the same post-hash that will be calculated when importing the block
+	// This is important in case the digest_provider added any signature, seal, ect.
+	let mut post_header = header.clone();
+	post_header.digest_mut().logs.extend(params.post_digests.iter().cloned());
+
+	match block_import.import_block(params).await? {
+		ImportResult::Imported(aux) =>
+			Ok(CreatedBlock { hash: <B as BlockT>::Header::hash(&post_header), aux, proof_size }),
+		other => Err(other.into()),
+	}
+}
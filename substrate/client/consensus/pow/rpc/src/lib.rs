@@ -0,0 +1,254 @@
+// This file is part of Substrate.
+
+// Copyright (C) Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! RPC api for PoW mining, allowing external miners to fetch work from and submit seals to the
+//! node's [`MiningHandle`].
+
+use jsonrpsee::{
+	core::{async_trait, Error as JsonRpseeError, RpcResult},
+	proc_macros::rpc,
+	types::{error::CallError, ErrorObject},
+};
+use serde::{Deserialize, Serialize};
+
+use sc_consensus::JustificationSyncLink;
+use sc_consensus_pow::{MiningHandle, PowAlgorithm};
+use sp_core::Bytes;
+use sp_runtime::traits::Block as BlockT;
+
+/// Base code for all PoW errors.
+const BASE_ERROR: i32 = 7000;
+
+/// Work to be mined on, as returned by `pow_getWork`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Work<Hash, Difficulty> {
+	/// Best block the block being mined is built on.
+	pub best_hash: Hash,
+	/// Hash of the block being mined, before it is sealed.
+	pub pre_hash: Hash,
+	/// Pre-runtime digest of the block being mined, if any.
+	pub pre_runtime: Option<Bytes>,
+	/// Target difficulty.
+	pub difficulty: Difficulty,
+}
+
+/// PoW RPC errors.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+	/// The submitted seal isn't for the block currently being mined.
+	#[error("Submitted work is stale, the node is no longer mining on top of this pre-hash")]
+	StaleWork,
+}
+
+impl From<Error> for JsonRpseeError {
+	fn from(error: Error) -> Self {
+		let code = match error {
+			Error::StaleWork => BASE_ERROR + 1,
+		};
+		JsonRpseeError::Call(CallError::Custom(ErrorObject::owned(
+			code,
+			error.to_string(),
+			None::<()>,
+		)))
+	}
+}
+
+/// Provides RPC methods for external PoW miners.
+#[rpc(client, server)]
+pub trait PowApi<Hash, Difficulty> {
+	/// Returns the work currently mined on, `None` if there is none yet or the node is syncing.
+	#[method(name = "pow_getWork")]
+	fn get_work(&self) -> RpcResult<Option<Work<Hash, Difficulty>>>;
+
+	/// Submits the seal of the block with the given pre-hash. Returns whether the seal was valid
+	/// and the block got imported.
+	#[method(name = "pow_submitWork")]
+	async fn submit_work(&self, pre_hash: Hash, seal: Bytes) -> RpcResult<bool>;
+}
+
+/// Implements the [`PowApiServer`] RPC trait on top of a [`MiningHandle`].
+pub struct Pow<Block, Algorithm, L, Proof>
+where
+	Block: BlockT,
+	Algorithm: PowAlgorithm<Block>,
+	L: JustificationSyncLink<Block>,
+{
+	mining_handle: MiningHandle<Block, Algorithm, L, Proof>,
+}
+
+impl<Block, Algorithm, L, Proof> Pow<Block, Algorithm, L, Proof>
+where
+	Block: BlockT,
+	Algorithm: PowAlgorithm<Block>,
+	L: JustificationSyncLink<Block>,
+{
+	/// Creates a new PoW RPC handler instance.
+	pub fn new(mining_handle: MiningHandle<Block, Algorithm, L, Proof>) -> Self {
+		Self { mining_handle }
+	}
+}
+
+#[async_trait]
+impl<Block, Algorithm, L, Proof> PowApiServer<Block::Hash, Algorithm::Difficulty>
+	for Pow<Block, Algorithm, L, Proof>
+where
+	Block: BlockT,
+	Algorithm: PowAlgorithm<Block> + Send + Sync + 'static,
+	Algorithm::Difficulty: Serialize + Send + Sync + 'static,
+	L: JustificationSyncLink<Block> + Send + Sync + 'static,
+	Proof: Send + 'static,
+{
+	fn get_work(&self) -> RpcResult<Option<Work<Block::Hash, Algorithm::Difficulty>>> {
+		Ok(self.mining_handle.metadata().map(|metadata| Work {
+			best_hash: metadata.best_hash,
+			pre_hash: metadata.pre_hash,
+			pre_runtime: metadata.pre_runtime.map(Into::into),
+			difficulty: metadata.difficulty,
+		}))
+	}
+
+	async fn submit_work(&self, pre_hash: Block::Hash, seal: Bytes) -> RpcResult<bool> {
+		match self.mining_handle.metadata() {
+			Some(metadata) if metadata.pre_hash == pre_hash => (),
+			_ => return Err(Error::StaleWork.into()),
+		}
+
+		Ok(self.mining_handle.submit(seal.0).await)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use jsonrpsee::{
+		core::Error as RpcError,
+		types::{error::CallError, EmptyServerParams as EmptyParams},
+		RpcModule,
+	};
+	use sc_basic_authorship::ProposerFactory;
+	use sc_consensus_pow::{Error as PowError, PowBlockImport};
+	use sc_transaction_pool::BasicPool;
+	use sp_consensus::NoNetwork;
+	use sp_consensus_pow::Seal;
+	use sp_core::{H256, U256};
+	use sp_runtime::generic::BlockId;
+	use std::{sync::Arc, time::Duration};
+	use substrate_test_runtime_client::{
+		runtime::{Block, Hash},
+		DefaultTestClientBuilderExt, TestClientBuilder, TestClientBuilderExt,
+	};
+
+	const VALID_SEAL: &[u8] = b"valid";
+
+	/// Accepts [`VALID_SEAL`] for any block.
+	#[derive(Clone)]
+	struct TestAlgorithm;
+
+	impl PowAlgorithm<Block> for TestAlgorithm {
+		type Difficulty = U256;
+
+		fn difficulty(&self, _parent: Hash) -> Result<U256, PowError<Block>> {
+			Ok(U256::one())
+		}
+
+		fn verify(
+			&self,
+			_parent: &BlockId<Block>,
+			_pre_hash: &Hash,
+			_pre_digest: Option<&[u8]>,
+			seal: &Seal,
+			_difficulty: U256,
+		) -> Result<bool, PowError<Block>> {
+			Ok(seal == VALID_SEAL)
+		}
+	}
+
+	#[tokio::test]
+	async fn mined_work_is_submitted() {
+		let (client, select_chain) = TestClientBuilder::new().build_with_longest_chain();
+		let client = Arc::new(client);
+		let spawner = sp_core::testing::TaskExecutor::new();
+		let pool = BasicPool::new_full(
+			Default::default(),
+			true.into(),
+			None,
+			spawner.clone(),
+			client.clone(),
+		);
+		let env = ProposerFactory::new(spawner, client.clone(), pool, None, None);
+		let block_import = PowBlockImport::new(
+			client.clone(),
+			client.clone(),
+			TestAlgorithm,
+			u64::MAX,
+			select_chain.clone(),
+			|_, _| async { Ok(()) },
+		);
+		let (mining_handle, task) = sc_consensus_pow::start_mining_worker(
+			Box::new(block_import),
+			client.clone(),
+			select_chain,
+			TestAlgorithm,
+			env,
+			NoNetwork,
+			(),
+			None,
+			|_, _| async { Ok(()) },
+			Duration::from_millis(10),
+			Duration::from_secs(10),
+		);
+		tokio::spawn(task);
+		let rpc: RpcModule<_> = Pow::new(mining_handle).into_rpc();
+
+		let work = loop {
+			let work: Option<Work<Hash, U256>> =
+				rpc.call("pow_getWork", EmptyParams::new()).await.unwrap();
+			match work {
+				Some(work) => break work,
+				None => tokio::time::sleep(Duration::from_millis(10)).await,
+			}
+		};
+		assert_eq!(work.best_hash, client.chain_info().genesis_hash);
+		assert_eq!(work.difficulty, U256::one());
+
+		// seals for another pre-hash are rejected.
+		let stale = rpc
+			.call::<_, bool>("pow_submitWork", (H256::repeat_byte(1), Bytes(VALID_SEAL.to_vec())))
+			.await;
+		assert!(matches!(
+			stale,
+			Err(RpcError::Call(CallError::Custom(ref err))) if err.code() == BASE_ERROR + 1
+		));
+
+		// invalid seals don't import the block.
+		let imported: bool = rpc
+			.call("pow_submitWork", (work.pre_hash, Bytes(b"invalid".to_vec())))
+			.await
+			.unwrap();
+		assert!(!imported);
+		assert_eq!(client.chain_info().best_number, 0);
+
+		let imported: bool = rpc
+			.call("pow_submitWork", (work.pre_hash, Bytes(VALID_SEAL.to_vec())))
+			.await
+			.unwrap();
+		assert!(imported);
+		assert_eq!(client.chain_info().best_number, 1);
+	}
+}
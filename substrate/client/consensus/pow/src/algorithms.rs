@@ -0,0 +1,229 @@
+// This file is part of Substrate.
+
+// Copyright (C) Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Reference [`PowAlgorithm`] implementations, based on plain hash functions.
+//!
+//! A seal is valid if the hash of the pre-hash, the pre-runtime digest, the difficulty and a
+//! nonce, interpreted as a big endian number, can be multiplied by the difficulty without
+//! overflowing 256 bits.
+
+use codec::{Decode, Encode};
+use sc_client_api::backend::AuxStore;
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use sp_consensus_pow::{DifficultyAdjustmentApi, Seal, TimestampApi};
+use sp_core::{H256, U256};
+use sp_runtime::{generic::BlockId, traits::Block as BlockT};
+use std::{marker::PhantomData, sync::Arc};
+
+use crate::{difficulty::RetargetingDifficulty, Error, PowAlgorithm};
+
+/// Hash function used by a [`HashAlgorithm`].
+pub trait PowHasher {
+	/// Hash `data`.
+	fn hash(data: &[u8]) -> H256;
+}
+
+/// Blake2b with a 256 bit output.
+pub struct Blake2;
+
+impl PowHasher for Blake2 {
+	fn hash(data: &[u8]) -> H256 {
+		sp_core::hashing::blake2_256(data).into()
+	}
+}
+
+/// Keccak with a 256 bit output, the SHA3 variant used by Ethereum.
+pub struct Keccak;
+
+impl PowHasher for Keccak {
+	fn hash(data: &[u8]) -> H256 {
+		sp_core::hashing::keccak_256(data).into()
+	}
+}
+
+/// Seal produced by a [`HashAlgorithm`], SCALE encoded into the block's [`Seal`].
+#[derive(Clone, Debug, PartialEq, Eq, Encode, Decode)]
+pub struct HashSeal {
+	/// The nonce the work was found with.
+	pub nonce: U256,
+	/// The resulting hash.
+	pub work: H256,
+}
+
+#[derive(Encode)]
+struct Compute<'a> {
+	pre_hash: &'a [u8],
+	pre_digest: Option<&'a [u8]>,
+	difficulty: U256,
+	nonce: U256,
+}
+
+/// Whether `work` is a valid proof of work for `difficulty`.
+pub fn hash_meets_difficulty(work: &H256, difficulty: U256) -> bool {
+	let work = U256::from_big_endian(work.as_bytes());
+	!work.overflowing_mul(difficulty).1
+}
+
+/// Try to seal a block with the given `nonce`, returning the seal if the resulting work meets
+/// `difficulty`.
+///
+/// This is the single step of a mining loop, to be called with nonces until a seal is found.
+pub fn try_seal<H: PowHasher>(
+	pre_hash: &[u8],
+	pre_digest: Option<&[u8]>,
+	difficulty: U256,
+	nonce: U256,
+) -> Option<Seal> {
+	let work = H::hash(&Compute { pre_hash, pre_digest, difficulty, nonce }.encode());
+	hash_meets_difficulty(&work, difficulty).then(|| HashSeal { nonce, work }.encode())
+}
+
+/// [`PowAlgorithm`] hashing its input with `H`, and retargeting the difficulty of every block
+/// with [`RetargetingDifficulty`].
+pub struct HashAlgorithm<H, B: BlockT, C> {
+	difficulty: RetargetingDifficulty<B, C>,
+	_phantom: PhantomData<fn() -> H>,
+}
+
+/// [`HashAlgorithm`] using [`Blake2`].
+pub type Blake2Algorithm<B, C> = HashAlgorithm<Blake2, B, C>;
+
+/// [`HashAlgorithm`] using [`Keccak`].
+pub type KeccakAlgorithm<B, C> = HashAlgorithm<Keccak, B, C>;
+
+impl<H, B: BlockT, C> HashAlgorithm<H, B, C> {
+	/// Create a new algorithm, reading the difficulty parameters from `client`.
+	pub fn new(client: Arc<C>) -> Self
+	where
+		C: ProvideRuntimeApi<B> + HeaderBackend<B> + AuxStore,
+		C::Api: DifficultyAdjustmentApi<B> + TimestampApi<B, u64>,
+	{
+		Self { difficulty: RetargetingDifficulty::new(client), _phantom: PhantomData }
+	}
+}
+
+impl<H, B: BlockT, C> Clone for HashAlgorithm<H, B, C> {
+	fn clone(&self) -> Self {
+		Self { difficulty: self.difficulty.clone(), _phantom: PhantomData }
+	}
+}
+
+impl<H, B, C> PowAlgorithm<B> for HashAlgorithm<H, B, C>
+where
+	H: PowHasher,
+	B: BlockT,
+	C: ProvideRuntimeApi<B> + HeaderBackend<B> + AuxStore,
+	C::Api: DifficultyAdjustmentApi<B> + TimestampApi<B, u64>,
+{
+	type Difficulty = U256;
+
+	fn difficulty(&self, parent: B::Hash) -> Result<Self::Difficulty, Error<B>> {
+		self.difficulty.difficulty(parent)
+	}
+
+	fn verify(
+		&self,
+		_parent: &BlockId<B>,
+		pre_hash: &B::Hash,
+		pre_digest: Option<&[u8]>,
+		seal: &Seal,
+		difficulty: Self::Difficulty,
+	) -> Result<bool, Error<B>> {
+		let seal = match HashSeal::decode(&mut &seal[..]) {
+			Ok(seal) => seal,
+			Err(_) => return Ok(false),
+		};
+
+		let compute =
+			Compute { pre_hash: pre_hash.as_ref(), pre_digest, difficulty, nonce: seal.nonce };
+		Ok(H::hash(&compute.encode()) == seal.work && hash_meets_difficulty(&seal.work, difficulty))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::difficulty::tests::TestClient;
+	use substrate_test_runtime_client::runtime::{Block, Hash};
+
+	#[test]
+	fn seals_meet_difficulty() {
+		let pre_hash = [1u8; 32];
+		let difficulty = U256::from(16);
+
+		let seal = (0..1000u64)
+			.find_map(|nonce| try_seal::<Blake2>(&pre_hash, None, difficulty, nonce.into()))
+			.expect("a seal is found within 1000 nonces with overwhelming probability");
+		let seal = HashSeal::decode(&mut &seal[..]).unwrap();
+
+		assert!(hash_meets_difficulty(&seal.work, difficulty));
+		let compute =
+			Compute { pre_hash: &pre_hash, pre_digest: None, difficulty, nonce: seal.nonce };
+		assert_eq!(Blake2::hash(&compute.encode()), seal.work);
+		assert_ne!(Keccak::hash(&compute.encode()), seal.work);
+	}
+
+	#[test]
+	fn difficulty_bounds_work() {
+		assert!(hash_meets_difficulty(&H256::repeat_byte(0xff), U256::one()));
+		assert!(!hash_meets_difficulty(&H256::repeat_byte(0xff), U256::from(2)));
+		assert!(hash_meets_difficulty(&H256::from_low_u64_be(1), U256::MAX));
+		assert!(!hash_meets_difficulty(&H256::from_low_u64_be(2), U256::MAX));
+	}
+
+	#[test]
+	fn verify_checks_seal() {
+		let client = Arc::new(TestClient::new(1, 1_000, U256::one()));
+		let parent = BlockId::Hash(client.hash_of(1));
+		let algorithm = Blake2Algorithm::<Block, TestClient>::new(client.clone());
+		let pre_hash = Hash::repeat_byte(1);
+		let pre_digest = Some(&b"author"[..]);
+		let difficulty = U256::from(16);
+
+		let seal = (0..1000u64)
+			.find_map(|nonce| {
+				try_seal::<Blake2>(pre_hash.as_ref(), pre_digest, difficulty, nonce.into())
+			})
+			.expect("a seal is found within 1000 nonces with overwhelming probability");
+		let verify = |pre_hash: &Hash, pre_digest: Option<&[u8]>, seal: &Seal, difficulty: U256| {
+			algorithm.verify(&parent, pre_hash, pre_digest, seal, difficulty).unwrap()
+		};
+		assert!(verify(&pre_hash, pre_digest, &seal, difficulty));
+
+		// the seal is bound to the pre-hash, the pre-runtime digest and the difficulty.
+		assert!(!verify(&Hash::repeat_byte(2), pre_digest, &seal, difficulty));
+		assert!(!verify(&pre_hash, None, &seal, difficulty));
+		assert!(!verify(&pre_hash, pre_digest, &seal, difficulty + U256::one()));
+
+		// the work has to match the nonce and meet the difficulty.
+		let HashSeal { nonce, work } = HashSeal::decode(&mut &seal[..]).unwrap();
+		let other_nonce = HashSeal { nonce: nonce + U256::one(), work }.encode();
+		assert!(!verify(&pre_hash, pre_digest, &other_nonce, difficulty));
+		let compute =
+			Compute { pre_hash: pre_hash.as_ref(), pre_digest, difficulty: U256::MAX, nonce };
+		let too_easy = HashSeal { nonce, work: Blake2::hash(&compute.encode()) }.encode();
+		assert!(!verify(&pre_hash, pre_digest, &too_easy, U256::MAX));
+
+		// undecodable seals are invalid, not an error.
+		assert!(!verify(&pre_hash, pre_digest, &vec![1, 2, 3], difficulty));
+		// hashing with another function doesn't produce a valid seal.
+		let keccak = KeccakAlgorithm::<Block, TestClient>::new(client);
+		assert!(!keccak.verify(&parent, &pre_hash, pre_digest, &seal, difficulty).unwrap());
+	}
+}
@@ -0,0 +1,343 @@
+// This file is part of Substrate.
+
+// Copyright (C) Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Client side difficulty retargeting, configured by the runtime.
+
+use codec::{Decode, Encode};
+use parking_lot::Mutex;
+use sc_client_api::backend::AuxStore;
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use sp_consensus_pow::{difficulty::retarget, DifficultyAdjustmentApi, TimestampApi};
+use sp_core::U256;
+use sp_runtime::traits::{Block as BlockT, Header as HeaderT, One};
+use std::sync::Arc;
+
+use crate::{Error, PowAux};
+
+/// Prefix of the auxiliary keys the block timestamps are stored under.
+pub const TIMESTAMP_AUX_PREFIX: [u8; 13] = *b"PoWTimestamp:";
+
+fn timestamp_key<T: AsRef<[u8]>>(hash: &T) -> Vec<u8> {
+	TIMESTAMP_AUX_PREFIX.iter().chain(hash.as_ref()).copied().collect()
+}
+
+/// Difficulty source retargeting the difficulty of every block with
+/// [`sp_consensus_pow::difficulty::retarget`].
+///
+/// The retargeting parameters are read from the runtime through [`DifficultyAdjustmentApi`]. The
+/// timestamp of a block is read through [`TimestampApi`] once, when the difficulty of its child is
+/// computed and its state is thus available, and kept in the auxiliary storage. The timestamps of
+/// the older blocks of the window are read from there, so that retargeting doesn't depend on
+/// their state, which might be pruned.
+///
+/// The window of block intervals never includes the genesis block, whose timestamp is usually
+/// unrelated to the time the chain started, so it is only shorter than `window` right after
+/// genesis. Otherwise the difficulty must be the same on all nodes: if the timestamp of a block of
+/// the window was neither recorded nor can be read from its state, e.g. for blocks before the
+/// target of a warp sync, the difficulty can't be computed and an error is returned.
+pub struct RetargetingDifficulty<B: BlockT, C> {
+	client: Arc<C>,
+	// difficulty of the child of the last queried block, since it is queried twice per import.
+	cache: Arc<Mutex<Option<(B::Hash, U256)>>>,
+}
+
+impl<B: BlockT, C> Clone for RetargetingDifficulty<B, C> {
+	fn clone(&self) -> Self {
+		Self { client: self.client.clone(), cache: self.cache.clone() }
+	}
+}
+
+impl<B, C> RetargetingDifficulty<B, C>
+where
+	B: BlockT,
+	C: ProvideRuntimeApi<B> + HeaderBackend<B> + AuxStore,
+	C::Api: DifficultyAdjustmentApi<B> + TimestampApi<B, u64>,
+{
+	/// Create a new difficulty source.
+	pub fn new(client: Arc<C>) -> Self {
+		Self { client, cache: Arc::new(Mutex::new(None)) }
+	}
+
+	/// Get the difficulty of the child of `parent`.
+	pub fn difficulty(&self, parent: B::Hash) -> Result<U256, Error<B>> {
+		if let Some((hash, difficulty)) = *self.cache.lock() {
+			if hash == parent {
+				return Ok(difficulty)
+			}
+		}
+
+		let difficulty = self.compute(parent)?;
+		*self.cache.lock() = Some((parent, difficulty));
+		Ok(difficulty)
+	}
+
+	fn compute(&self, parent: B::Hash) -> Result<U256, Error<B>> {
+		let config =
+			self.client.runtime_api().retarget_config(parent).map_err(|e| {
+				Error::Environment(format!("Fetching retarget config failed: {}", e))
+			})?;
+		let parent_difficulty = PowAux::<U256>::read::<_, B>(&*self.client, &parent)?.difficulty;
+		let parent_timestamp = self.record_timestamp(parent)?;
+
+		let mut oldest = self
+			.client
+			.header(parent)
+			.map_err(Error::Client)?
+			.ok_or_else(|| Error::Other(format!("Header {:?} not found", parent)))?;
+		let mut oldest_timestamp = parent_timestamp;
+		let mut intervals = 0;
+		while intervals < config.window && *oldest.number() > One::one() {
+			let parent_hash = *oldest.parent_hash();
+			oldest = self
+				.client
+				.header(parent_hash)
+				.map_err(Error::Client)?
+				.ok_or_else(|| Error::Other(format!("Header {:?} not found", parent_hash)))?;
+			oldest_timestamp = self.record_timestamp(parent_hash)?;
+			intervals += 1;
+		}
+
+		let timespan = parent_timestamp.saturating_sub(oldest_timestamp);
+		Ok(retarget(&config, parent_difficulty, timespan, intervals))
+	}
+
+	/// Read the timestamp of `hash` from the auxiliary storage.
+	fn timestamp(&self, hash: B::Hash) -> Result<Option<u64>, Error<B>> {
+		match self.client.get_aux(&timestamp_key(&hash)).map_err(Error::Client)? {
+			Some(bytes) => u64::decode(&mut &bytes[..]).map(Some).map_err(Error::Codec),
+			None => Ok(None),
+		}
+	}
+
+	/// Read the timestamp of `hash`, fetching it from the runtime and storing it in the auxiliary
+	/// storage if it wasn't yet.
+	fn record_timestamp(&self, hash: B::Hash) -> Result<u64, Error<B>> {
+		if let Some(timestamp) = self.timestamp(hash)? {
+			return Ok(timestamp)
+		}
+
+		let timestamp = self.client.runtime_api().timestamp(hash).map_err(|e| {
+			Error::Environment(format!("Fetching timestamp of {:?} failed: {}", hash, e))
+		})?;
+		self.client
+			.insert_aux(&[(&timestamp_key(&hash)[..], &timestamp.encode()[..])], &[])
+			.map_err(Error::Client)?;
+		Ok(timestamp)
+	}
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+	use super::*;
+	use crate::aux_key;
+	use futures::executor::block_on;
+	use sc_block_builder::BlockBuilderBuilder;
+	use sp_api::{ApiError, ApiRef};
+	use sp_blockchain::{BlockStatus, Info};
+	use sp_consensus_pow::RetargetConfig;
+	use sp_runtime::traits::NumberFor;
+	use std::collections::HashMap;
+	use substrate_test_runtime_client::{
+		runtime::{Block, Hash, Header},
+		BlockOrigin, ClientBlockImportExt, DefaultTestClientBuilderExt, TestClientBuilder,
+		TestClientBuilderExt,
+	};
+
+	const TARGET_BLOCK_TIME: u64 = 1_000;
+	const WINDOW: u32 = 4;
+
+	/// Test client whose runtime only knows the timestamps of the blocks in `timestamps`, i.e. the
+	/// state of the other blocks is pruned.
+	pub(crate) struct TestClient {
+		inner: substrate_test_runtime_client::TestClient,
+		timestamps: Arc<Mutex<HashMap<Hash, u64>>>,
+	}
+
+	impl TestClient {
+		/// Create a chain of `blocks` blocks on top of genesis, whose timestamps are `block_time`
+		/// apart and whose difficulty is `difficulty`.
+		pub(crate) fn new(blocks: u64, block_time: u64, difficulty: U256) -> Self {
+			let inner = TestClientBuilder::new().build();
+			let mut timestamps = HashMap::new();
+			let mut parent = inner.chain_info().genesis_hash;
+			for number in 1..=blocks {
+				let block = BlockBuilderBuilder::new(&inner)
+					.on_parent_block(parent)
+					.fetch_parent_block_number(&inner)
+					.unwrap()
+					.build()
+					.unwrap()
+					.build()
+					.unwrap()
+					.block;
+				parent = block.hash();
+				block_on(inner.import(BlockOrigin::Own, block)).unwrap();
+
+				let aux = PowAux { difficulty, total_difficulty: difficulty * U256::from(number) };
+				inner.insert_aux(&[(&aux_key(&parent)[..], &aux.encode()[..])], &[]).unwrap();
+				timestamps.insert(parent, number * block_time);
+			}
+
+			Self { inner, timestamps: Arc::new(Mutex::new(timestamps)) }
+		}
+
+		pub(crate) fn hash_of(&self, number: u64) -> Hash {
+			self.inner.hash(number).unwrap().unwrap()
+		}
+
+		/// Prune the state of all blocks but `keep`.
+		fn prune_state_except(&self, keep: Hash) {
+			self.timestamps.lock().retain(|hash, _| *hash == keep);
+		}
+	}
+
+	impl HeaderBackend<Block> for TestClient {
+		fn header(&self, hash: Hash) -> sp_blockchain::Result<Option<Header>> {
+			self.inner.header(hash)
+		}
+
+		fn info(&self) -> Info<Block> {
+			self.inner.info()
+		}
+
+		fn status(&self, hash: Hash) -> sp_blockchain::Result<BlockStatus> {
+			self.inner.status(hash)
+		}
+
+		fn number(&self, hash: Hash) -> sp_blockchain::Result<Option<NumberFor<Block>>> {
+			self.inner.number(hash)
+		}
+
+		fn hash(&self, number: NumberFor<Block>) -> sp_blockchain::Result<Option<Hash>> {
+			self.inner.hash(number)
+		}
+	}
+
+	impl AuxStore for TestClient {
+		fn insert_aux<
+			'a,
+			'b: 'a,
+			'c: 'a,
+			I: IntoIterator<Item = &'a (&'c [u8], &'c [u8])>,
+			D: IntoIterator<Item = &'a &'b [u8]>,
+		>(
+			&self,
+			insert: I,
+			delete: D,
+		) -> sp_blockchain::Result<()> {
+			self.inner.insert_aux(insert, delete)
+		}
+
+		fn get_aux(&self, key: &[u8]) -> sp_blockchain::Result<Option<Vec<u8>>> {
+			self.inner.get_aux(key)
+		}
+	}
+
+	pub(crate) struct RuntimeApi {
+		timestamps: Arc<Mutex<HashMap<Hash, u64>>>,
+	}
+
+	impl ProvideRuntimeApi<Block> for TestClient {
+		type Api = RuntimeApi;
+
+		fn runtime_api(&self) -> ApiRef<'_, Self::Api> {
+			RuntimeApi { timestamps: self.timestamps.clone() }.into()
+		}
+	}
+
+	sp_api::mock_impl_runtime_apis! {
+		impl DifficultyAdjustmentApi<Block> for RuntimeApi {
+			fn retarget_config() -> RetargetConfig {
+				RetargetConfig {
+					target_block_time: TARGET_BLOCK_TIME,
+					window: WINDOW,
+					max_adjustment_factor: 4,
+					min_difficulty: U256::one(),
+				}
+			}
+		}
+
+		impl TimestampApi<Block, u64> for RuntimeApi {
+			#[advanced]
+			fn timestamp(&self, at: Hash) -> Result<u64, ApiError> {
+				self.timestamps.lock().get(&at).copied().ok_or_else(|| {
+					(Box::from("State already discarded") as Box<dyn std::error::Error + Send + Sync>)
+						.into()
+				})
+			}
+		}
+	}
+
+	#[test]
+	fn retargets_on_the_recent_block_intervals() {
+		// blocks come twice as fast as targeted.
+		let client = Arc::new(TestClient::new(10, TARGET_BLOCK_TIME / 2, U256::from(1_000)));
+		let difficulty = RetargetingDifficulty::new(client.clone());
+
+		// there is no interval to retarget on before block 2.
+		assert_eq!(difficulty.difficulty(client.hash_of(1)).unwrap(), U256::from(1_000));
+		for number in 2..=10 {
+			assert_eq!(difficulty.difficulty(client.hash_of(number)).unwrap(), U256::from(2_000));
+		}
+	}
+
+	#[test]
+	fn reads_old_timestamps_from_aux_storage() {
+		let client = Arc::new(TestClient::new(10, TARGET_BLOCK_TIME * 2, U256::from(1_000)));
+		let difficulty = RetargetingDifficulty::new(client.clone());
+		for number in 1..=9 {
+			difficulty.difficulty(client.hash_of(number)).unwrap();
+		}
+
+		// only the state of the parent is needed.
+		client.prune_state_except(client.hash_of(10));
+		assert_eq!(difficulty.difficulty(client.hash_of(10)).unwrap(), U256::from(500));
+
+		// the cache is not involved.
+		let difficulty = RetargetingDifficulty::new(client.clone());
+		assert_eq!(difficulty.difficulty(client.hash_of(10)).unwrap(), U256::from(500));
+	}
+
+	#[test]
+	fn reads_unrecorded_timestamps_from_the_runtime() {
+		let client = Arc::new(TestClient::new(10, TARGET_BLOCK_TIME * 2, U256::from(1_000)));
+		let difficulty = RetargetingDifficulty::new(client.clone());
+
+		// no timestamp was recorded yet, but the state of the whole window is available.
+		assert_eq!(difficulty.difficulty(client.hash_of(10)).unwrap(), U256::from(500));
+
+		// the timestamps are now recorded.
+		client.prune_state_except(client.hash_of(10));
+		let difficulty = RetargetingDifficulty::new(client.clone());
+		assert_eq!(difficulty.difficulty(client.hash_of(10)).unwrap(), U256::from(500));
+	}
+
+	#[test]
+	fn refuses_incomplete_window() {
+		let client = Arc::new(TestClient::new(10, TARGET_BLOCK_TIME * 2, U256::from(1_000)));
+		let difficulty = RetargetingDifficulty::new(client.clone());
+
+		// e.g. right after a warp sync to block 10, no older timestamp was recorded.
+		client.prune_state_except(client.hash_of(10));
+		assert!(difficulty.difficulty(client.hash_of(10)).is_err());
+
+		// the state of the parent is always needed.
+		assert!(difficulty.difficulty(client.hash_of(9)).is_err());
+	}
+}
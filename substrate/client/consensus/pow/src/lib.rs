@@ -31,6 +31,10 @@
 //! mining on a standalone thread. Finally, when a seal is found, call
 //! [`MiningHandle::submit`] to build the block.
 //!
+//! Reference algorithms hashing with Blake2 or Keccak are available in [`algorithms`], with
+//! their difficulty retargeted by [`RetargetingDifficulty`] according to the parameters of
+//! the runtime's [`sp_consensus_pow::DifficultyAdjustmentApi`].
+//!
 //! The auxiliary storage for PoW engine only stores the total difficulty.
 //! For other storage requirements for particular PoW algorithm (such as
 //! the actual difficulty for each particular blocks), you can take a client
@@ -39,9 +43,14 @@
 //! as the storage, but it is not recommended as it won't work well with light
 //! clients.
 
+pub mod algorithms;
+pub mod difficulty;
 mod worker;
 
-pub use crate::worker::{MiningBuild, MiningHandle, MiningMetadata};
+pub use crate::{
+	difficulty::RetargetingDifficulty,
+	worker::{MiningBuild, MiningHandle, MiningMetadata},
+};
 
 use crate::worker::UntilImportedOrTimeout;
 use codec::{Decode, Encode};
@@ -17,6 +17,7 @@
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
 use futures::{
+	lock::Mutex as AsyncMutex,
 	prelude::*,
 	task::{Context, Poll},
 };
@@ -78,7 +79,7 @@ pub struct MiningHandle<
 	algorithm: Arc<Algorithm>,
 	justification_sync_link: Arc<L>,
 	build: Arc<Mutex<Option<MiningBuild<Block, Algorithm, Proof>>>>,
-	block_import: Arc<Mutex<BoxBlockImport<Block>>>,
+	block_import: Arc<AsyncMutex<BoxBlockImport<Block>>>,
 }
 
 impl<Block, Algorithm, L, Proof> MiningHandle<Block, Algorithm, L, Proof>
@@ -102,7 +103,7 @@ where
 			algorithm: Arc::new(algorithm),
 			justification_sync_link: Arc::new(justification_sync_link),
 			build: Arc::new(Mutex::new(None)),
-			block_import: Arc::new(Mutex::new(block_import)),
+			block_import: Arc::new(AsyncMutex::new(block_import)),
 		}
 	}
 
@@ -192,7 +193,7 @@ where
 		import_block.insert_intermediate(INTERMEDIATE_KEY, intermediate);
 
 		let header = import_block.post_header();
-		let mut block_import = self.block_import.lock();
+		let mut block_import = self.block_import.lock().await;
 
 		match block_import.import_block(import_block).await {
 			Ok(res) => {
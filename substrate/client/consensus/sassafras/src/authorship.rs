@@ -0,0 +1,787 @@
+// This file is part of Substrate.
+
+// Copyright (C) Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Sassafras tickets generation, slot claiming and block authoring.
+
+use super::*;
+
+use std::{
+	future::Future,
+	pin::Pin,
+	sync::Arc,
+	task::{Context, Poll},
+	time::Duration,
+};
+
+use futures::prelude::*;
+use log::{info, warn};
+use parking_lot::Mutex;
+
+use sc_client_api::backend::AuxStore;
+use sc_consensus::block_import::{BlockImport, BlockImportParams, StateAction};
+use sc_consensus_epochs::{descendent_query, ViableEpochDescriptor};
+use sc_consensus_slots::{
+	BackoffAuthoringBlocksStrategy, InherentDataProviderExt, SlotInfo, StorageChanges,
+};
+use sc_telemetry::TelemetryHandle;
+use sc_transaction_pool_api::OffchainTransactionPoolFactory;
+use sp_api::ApiExt;
+use sp_application_crypto::AppCrypto;
+use sp_blockchain::{Error as ClientError, HeaderBackend, HeaderMetadata};
+use sp_consensus::{BlockOrigin, Environment, Proposer, SelectChain};
+use sp_consensus_sassafras::{
+	ticket_id_threshold,
+	vrf::{
+		make_revealed_key_seed, make_ticket_id, revealed_key_input, slot_claim_sign_data,
+		ticket_body_sign_data, ticket_id_input, RingContext, VrfSignData,
+	},
+	TicketClaim, TicketEnvelope,
+};
+use sp_core::{
+	blake2_256,
+	crypto::{ByteArray, Pair as _},
+	ed25519, U256,
+};
+use sp_inherents::CreateInherentDataProviders;
+use sp_keystore::KeystorePtr;
+use sp_runtime::DigestItem;
+
+/// Get the expected secondary author index for the given slot and epoch.
+///
+/// Secondary authors claim the slots which have no ticket assigned. This should always
+/// assign the slot to some authority unless the authorities list is empty.
+pub(crate) fn secondary_authority_index(slot: Slot, epoch: &Epoch) -> Option<AuthorityIndex> {
+	if epoch.authorities.is_empty() {
+		return None
+	}
+
+	let rand = U256::from((epoch.randomness, slot).using_encoded(blake2_256));
+	let authorities_len = U256::from(epoch.authorities.len());
+	Some((rand % authorities_len).as_u32())
+}
+
+/// Slot claim sign data, extended with the revealed key input when the slot is claimed
+/// with a ticket.
+pub(crate) fn claim_sign_data(
+	slot: Slot,
+	epoch: &Epoch,
+	ticket: Option<&TicketBody>,
+) -> VrfSignData {
+	let mut data = slot_claim_sign_data(&epoch.randomness, slot, epoch.index);
+	if let Some(body) = ticket {
+		let revealed_input = revealed_key_input(&epoch.randomness, body.attempt_idx, epoch.index);
+		data.push_vrf_input(revealed_input)
+			.expect("Sign data has at most one input before this push; qed");
+	}
+	data
+}
+
+/// Try to claim the given slot.
+///
+/// `ticket` is the ticket assigned to the slot by the runtime, if any. A slot with a ticket
+/// can only be claimed by the owner of the ticket, all the other slots are claimed by the
+/// secondary author.
+///
+/// Returns the slot claim to use when authoring the block together with the claiming
+/// authority, or `None` if it is not our turn to propose.
+pub(crate) fn claim_slot(
+	slot: Slot,
+	epoch: &Epoch,
+	ticket: Option<(TicketId, TicketBody)>,
+	keystore: &KeystorePtr,
+) -> Option<(SlotClaim, AuthorityId)> {
+	let mut epoch = epoch;
+	let skipped_epoch;
+	if epoch.end_slot() <= slot {
+		// Slot doesn't strictly belong to the epoch, create a clone with fixed values.
+		skipped_epoch = epoch.clone_for_slot(slot);
+		epoch = &skipped_epoch;
+	}
+
+	let (authority_idx, ticket_claim, data) = match ticket {
+		Some((ticket_id, ticket_body)) => {
+			let secret = epoch.tickets_aux.get(&ticket_id).filter(|s| s.body == ticket_body)?;
+			debug!(target: LOG_TARGET, "Claiming slot {} with ticket {:032x}", slot, ticket_id);
+
+			let data = claim_sign_data(slot, epoch, Some(&ticket_body));
+			let erased_pair = ed25519::Pair::from_seed(&secret.erased_seed);
+			let erased_signature = erased_pair.sign(&data.challenge::<32>());
+
+			(secret.authority_idx, Some(TicketClaim { erased_signature }), data)
+		},
+		None => {
+			let authority_idx = secondary_authority_index(slot, epoch)?;
+			(authority_idx, None, claim_sign_data(slot, epoch, None))
+		},
+	};
+
+	let authority_id = epoch.authorities.get(authority_idx as usize)?;
+	let vrf_signature = keystore
+		.bandersnatch_vrf_sign(AuthorityId::ID, authority_id.as_ref(), &data)
+		.ok()
+		.flatten()?;
+
+	let claim = SlotClaim { authority_idx, slot, vrf_signature, ticket_claim };
+	Some((claim, authority_id.clone()))
+}
+
+/// Generate the tickets of all our authority keys for the given epoch.
+///
+/// Only tickets whose identifier is below the epoch threshold are generated, the secrets of
+/// the generated tickets are stored in `epoch.tickets_aux`.
+pub(crate) fn generate_epoch_tickets(
+	epoch: &mut Epoch,
+	keystore: &KeystorePtr,
+	ring_ctx: &RingContext,
+) -> Vec<TicketEnvelope> {
+	let mut tickets = Vec::new();
+	let mut secrets = Vec::new();
+
+	let threshold = ticket_id_threshold(
+		epoch.config.redundancy_factor,
+		epoch.length,
+		epoch.config.attempts_number,
+		epoch.authorities.len() as u32,
+	);
+	debug!(target: LOG_TARGET, "Tickets threshold for epoch {}: {:032x}", epoch.index, threshold);
+
+	let authorities: Vec<_> = epoch.authorities.iter().map(|a| a.clone().into_inner()).collect();
+
+	for (authority_idx, authority_id) in epoch.authorities.iter().enumerate() {
+		if !keystore.has_keys(&[(authority_id.to_raw_vec(), AuthorityId::ID)]) {
+			continue
+		}
+
+		let Some(prover) = ring_ctx.prover(&authorities, authority_idx) else {
+			warn!(target: LOG_TARGET, "Unable to build ring prover for {:?}", authority_id);
+			continue
+		};
+
+		let make_ticket = |attempt_idx| {
+			let ticket_id_input = ticket_id_input(&epoch.randomness, attempt_idx, epoch.index);
+			let pre_output = keystore
+				.bandersnatch_vrf_pre_output(
+					AuthorityId::ID,
+					authority_id.as_ref(),
+					&ticket_id_input,
+				)
+				.ok()??;
+
+			let ticket_id = make_ticket_id(&ticket_id_input, &pre_output);
+			if ticket_id >= threshold {
+				return None
+			}
+
+			let (erased_pair, erased_seed) = ed25519::Pair::generate();
+
+			let revealed_input = revealed_key_input(&epoch.randomness, attempt_idx, epoch.index);
+			let revealed_pre_output = keystore
+				.bandersnatch_vrf_pre_output(
+					AuthorityId::ID,
+					authority_id.as_ref(),
+					&revealed_input,
+				)
+				.ok()??;
+			let revealed_seed = make_revealed_key_seed(&revealed_input, &revealed_pre_output);
+			let revealed_public = ed25519::Pair::from_seed(&revealed_seed).public();
+
+			let body =
+				TicketBody { attempt_idx, erased_public: erased_pair.public(), revealed_public };
+
+			debug!(target: LOG_TARGET, ">>> Creating ring proof for attempt {}", attempt_idx);
+			let sign_data = ticket_body_sign_data(&body, ticket_id_input);
+			let signature = keystore
+				.bandersnatch_ring_vrf_sign(
+					AuthorityId::ID,
+					authority_id.as_ref(),
+					&sign_data,
+					&prover,
+				)
+				.ok()??;
+			debug!(target: LOG_TARGET, "<<< Ring proof for attempt {} done", attempt_idx);
+
+			let secret =
+				TicketSecret { authority_idx: authority_idx as AuthorityIndex, body, erased_seed };
+			Some((ticket_id, secret, signature))
+		};
+
+		for attempt in 0..epoch.config.attempts_number {
+			if let Some((ticket_id, secret, signature)) = make_ticket(attempt) {
+				tickets.push(TicketEnvelope { body: secret.body.clone(), signature });
+				secrets.push((ticket_id, secret));
+			}
+		}
+	}
+
+	epoch.tickets_aux.extend(secrets);
+	tickets
+}
+
+/// Parameters for Sassafras.
+pub struct SassafrasParams<B: BlockT, C, SC, E, I, SO, L, CIDP, BS> {
+	/// The keystore that manages the keys of the node.
+	pub keystore: KeystorePtr,
+
+	/// The client to use
+	pub client: Arc<C>,
+
+	/// The SelectChain Strategy
+	pub select_chain: SC,
+
+	/// The environment we are producing blocks for.
+	pub env: E,
+
+	/// The underlying block-import object to supply our produced blocks to.
+	/// This must be a `SassafrasBlockImport` or a wrapper of it, otherwise
+	/// critical consensus logic will be omitted.
+	pub block_import: I,
+
+	/// A sync oracle
+	pub sync_oracle: SO,
+
+	/// Hook into the sync module to control the justification sync process.
+	pub justification_sync_link: L,
+
+	/// Something that can create the inherent data providers.
+	pub create_inherent_data_providers: CIDP,
+
+	/// Force authoring of blocks even if we are offline
+	pub force_authoring: bool,
+
+	/// Strategy and parameters for backing off block production.
+	pub backoff_authoring_blocks: Option<BS>,
+
+	/// State shared between the import queue and the authoring logic.
+	pub sassafras_link: SassafrasLink<B>,
+
+	/// The proportion of the slot dedicated to proposing.
+	pub block_proposal_slot_portion: SlotProportion,
+
+	/// The maximum proportion of the slot dedicated to proposing with any lenience factor applied
+	/// due to no blocks being produced.
+	pub max_block_proposal_slot_portion: Option<SlotProportion>,
+
+	/// Handle use to report telemetries.
+	pub telemetry: Option<TelemetryHandle>,
+
+	/// The offchain transaction pool factory.
+	///
+	/// Will be used when submitting the generated tickets.
+	pub offchain_tx_pool_factory: OffchainTransactionPoolFactory<B>,
+}
+
+/// Start the Sassafras worker.
+pub fn start_sassafras<B, C, SC, E, I, SO, CIDP, BS, L, Error>(
+	SassafrasParams {
+		keystore,
+		client,
+		select_chain,
+		env,
+		block_import,
+		sync_oracle,
+		justification_sync_link,
+		create_inherent_data_providers,
+		force_authoring,
+		backoff_authoring_blocks,
+		sassafras_link,
+		block_proposal_slot_portion,
+		max_block_proposal_slot_portion,
+		telemetry,
+		offchain_tx_pool_factory,
+	}: SassafrasParams<B, C, SC, E, I, SO, L, CIDP, BS>,
+) -> Result<SassafrasWorker, ConsensusError>
+where
+	B: BlockT,
+	C: ProvideRuntimeApi<B>
+		+ HeaderBackend<B>
+		+ HeaderMetadata<B, Error = ClientError>
+		+ AuxStore
+		+ Send
+		+ Sync
+		+ 'static,
+	C::Api: SassafrasApi<B>,
+	SC: SelectChain<B> + 'static,
+	E: Environment<B, Error = Error> + Send + Sync + 'static,
+	E::Proposer: Proposer<B, Error = Error>,
+	I: BlockImport<B, Error = ConsensusError> + Send + Sync + 'static,
+	SO: SyncOracle + Send + Sync + Clone + 'static,
+	L: sc_consensus::JustificationSyncLink<B> + 'static,
+	CIDP: CreateInherentDataProviders<B, ()> + Send + Sync + 'static,
+	CIDP::InherentDataProviders: InherentDataProviderExt + Send,
+	BS: BackoffAuthoringBlocksStrategy<NumberFor<B>> + Send + Sync + 'static,
+	Error: std::error::Error + Send + From<ConsensusError> + From<I::Error> + 'static,
+{
+	let worker = SassafrasSlotWorker {
+		client,
+		block_import,
+		env,
+		sync_oracle: sync_oracle.clone(),
+		justification_sync_link,
+		force_authoring,
+		backoff_authoring_blocks,
+		keystore,
+		epoch_changes: sassafras_link.epoch_changes.clone(),
+		config: sassafras_link.config.clone(),
+		last_tickets_epoch: Mutex::new(None),
+		block_proposal_slot_portion,
+		max_block_proposal_slot_portion,
+		telemetry,
+		offchain_tx_pool_factory,
+	};
+
+	info!(target: LOG_TARGET, "🍁 Starting Sassafras Authorship worker");
+
+	let slot_worker = sc_consensus_slots::start_slot_worker(
+		sassafras_link.config.slot_duration,
+		select_chain,
+		sc_consensus_slots::SimpleSlotWorkerToSlotWorker(worker),
+		sync_oracle,
+		create_inherent_data_providers,
+	);
+
+	Ok(SassafrasWorker { inner: Box::pin(slot_worker) })
+}
+
+/// Worker for Sassafras which implements `Future<Output=()>`. This must be polled.
+#[must_use]
+pub struct SassafrasWorker {
+	inner: Pin<Box<dyn Future<Output = ()> + Send + 'static>>,
+}
+
+impl Future for SassafrasWorker {
+	type Output = ();
+
+	fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+		self.inner.as_mut().poll(cx)
+	}
+}
+
+struct SassafrasSlotWorker<B: BlockT, C, E, I, SO, L, BS> {
+	client: Arc<C>,
+	block_import: I,
+	env: E,
+	sync_oracle: SO,
+	justification_sync_link: L,
+	force_authoring: bool,
+	backoff_authoring_blocks: Option<BS>,
+	keystore: KeystorePtr,
+	epoch_changes: SharedEpochChanges<B, Epoch>,
+	config: SassafrasConfiguration,
+	/// Index of the last epoch we generated tickets for.
+	last_tickets_epoch: Mutex<Option<u64>>,
+	block_proposal_slot_portion: SlotProportion,
+	max_block_proposal_slot_portion: Option<SlotProportion>,
+	telemetry: Option<TelemetryHandle>,
+	offchain_tx_pool_factory: OffchainTransactionPoolFactory<B>,
+}
+
+impl<B, C, E, I, SO, L, BS> SassafrasSlotWorker<B, C, E, I, SO, L, BS>
+where
+	B: BlockT,
+	C: ProvideRuntimeApi<B> + HeaderBackend<B> + HeaderMetadata<B, Error = ClientError> + AuxStore,
+	C::Api: SassafrasApi<B>,
+{
+	/// Generate and submit our tickets for the epoch following the one of `slot`.
+	///
+	/// Tickets are generated once per epoch, during the first half of the epoch which
+	/// is the tickets submission period.
+	fn generate_tickets(
+		&self,
+		parent_header: &B::Header,
+		slot: Slot,
+		epoch_descriptor: &ViableEpochDescriptor<B::Hash, NumberFor<B>, Epoch>,
+	) -> Result<(), Error<B>> {
+		let parent_hash = parent_header.hash();
+
+		let next_epoch_start = {
+			let epoch_changes = self.epoch_changes.shared_data();
+			let Some(epoch) = epoch_changes
+				.viable_epoch(epoch_descriptor, |slot| Epoch::genesis(&self.config, slot))
+			else {
+				return Ok(())
+			};
+			let epoch = epoch.as_ref();
+			if epoch.end_slot() <= slot || slot >= epoch.start + (epoch.length / 2) as u64 {
+				// Out of the tickets submission period.
+				return Ok(())
+			}
+			if self.last_tickets_epoch.lock().map_or(false, |last| last > epoch.index) {
+				return Ok(())
+			}
+			epoch.end_slot()
+		};
+
+		// The next epoch has been announced by the first block of the current one.
+		let (next_epoch_id, mut next_epoch) = {
+			let epoch_changes = self.epoch_changes.shared_data();
+			let descriptor = epoch_changes
+				.epoch_descriptor_for_child_of(
+					descendent_query(&*self.client),
+					&parent_hash,
+					*parent_header.number(),
+					next_epoch_start,
+				)
+				.map_err(|e| Error::ForkTree(Box::new(e)))?
+				.ok_or(Error::FetchEpoch(parent_hash))?;
+			let ViableEpochDescriptor::Signaled(id, _) = descriptor else { return Ok(()) };
+			let Some(epoch) = epoch_changes.epoch(&id) else { return Ok(()) };
+			if epoch.start != next_epoch_start {
+				// Next epoch not announced yet on this fork.
+				return Ok(())
+			}
+			(id, epoch.clone())
+		};
+
+		let Some(ring_ctx) =
+			self.client.runtime_api().ring_context(parent_hash).map_err(Error::RuntimeApi)?
+		else {
+			debug!(target: LOG_TARGET, "Ring context not available, skipping tickets generation");
+			return Ok(())
+		};
+
+		debug!(target: LOG_TARGET, "Generating tickets for epoch {}", next_epoch.index);
+		next_epoch.tickets_aux.clear();
+		let tickets = generate_epoch_tickets(&mut next_epoch, &self.keystore, &ring_ctx);
+		*self.last_tickets_epoch.lock() = Some(next_epoch.index);
+
+		if tickets.is_empty() {
+			return Ok(())
+		}
+
+		{
+			let mut epoch_changes = self.epoch_changes.shared_data();
+			let Some(epoch) = epoch_changes.epoch_mut(&next_epoch_id) else { return Ok(()) };
+			epoch.tickets_aux = next_epoch.tickets_aux;
+			aux_schema::write_epoch_changes::<B, _, _>(&*epoch_changes, |insert| {
+				self.client.insert_aux(insert, &[])
+			})
+			.map_err(Error::Client)?;
+		}
+
+		let mut runtime_api = self.client.runtime_api();
+		// Register the offchain tx pool to be able to use it from the runtime.
+		runtime_api.register_extension(
+			self.offchain_tx_pool_factory.offchain_transaction_pool(parent_hash),
+		);
+
+		let count = tickets.len();
+		if runtime_api
+			.submit_tickets_unsigned_extrinsic(parent_hash, tickets)
+			.map_err(Error::RuntimeApi)?
+		{
+			info!(target: LOG_TARGET, "🎫 Submitted {} tickets for epoch {}", count, next_epoch.index);
+		} else {
+			warn!(target: LOG_TARGET, "Failed to submit tickets for epoch {}", next_epoch.index);
+		}
+
+		Ok(())
+	}
+}
+
+#[async_trait::async_trait]
+impl<B, C, E, I, Error, SO, L, BS> sc_consensus_slots::SimpleSlotWorker<B>
+	for SassafrasSlotWorker<B, C, E, I, SO, L, BS>
+where
+	B: BlockT,
+	C: ProvideRuntimeApi<B>
+		+ HeaderBackend<B>
+		+ HeaderMetadata<B, Error = ClientError>
+		+ AuxStore
+		+ Send
+		+ Sync,
+	C::Api: SassafrasApi<B>,
+	E: Environment<B, Error = Error> + Send + Sync,
+	E::Proposer: Proposer<B, Error = Error>,
+	I: BlockImport<B> + Send + Sync + 'static,
+	SO: SyncOracle + Send + Clone + Sync,
+	L: sc_consensus::JustificationSyncLink<B>,
+	BS: BackoffAuthoringBlocksStrategy<NumberFor<B>> + Send + Sync,
+	Error: std::error::Error + Send + From<ConsensusError> + From<I::Error> + 'static,
+{
+	type Claim = (SlotClaim, AuthorityId);
+	type SyncOracle = SO;
+	type JustificationSyncLink = L;
+	type CreateProposer =
+		Pin<Box<dyn Future<Output = Result<E::Proposer, ConsensusError>> + Send + 'static>>;
+	type Proposer = E::Proposer;
+	type BlockImport = I;
+	type AuxData = ViableEpochDescriptor<B::Hash, NumberFor<B>, Epoch>;
+
+	fn logging_target(&self) -> &'static str {
+		LOG_TARGET
+	}
+
+	fn block_import(&mut self) -> &mut Self::BlockImport {
+		&mut self.block_import
+	}
+
+	fn aux_data(&self, parent: &B::Header, slot: Slot) -> Result<Self::AuxData, ConsensusError> {
+		self.epoch_changes
+			.shared_data()
+			.epoch_descriptor_for_child_of(
+				descendent_query(&*self.client),
+				&parent.hash(),
+				*parent.number(),
+				slot,
+			)
+			.map_err(|e| ConsensusError::ChainLookup(e.to_string()))?
+			.ok_or(ConsensusError::InvalidAuthoritiesSet)
+	}
+
+	fn authorities_len(&self, epoch_descriptor: &Self::AuxData) -> Option<usize> {
+		self.epoch_changes
+			.shared_data()
+			.viable_epoch(epoch_descriptor, |slot| Epoch::genesis(&self.config, slot))
+			.map(|epoch| epoch.as_ref().authorities.len())
+	}
+
+	async fn claim_slot(
+		&mut self,
+		parent_header: &B::Header,
+		slot: Slot,
+		epoch_descriptor: &ViableEpochDescriptor<B::Hash, NumberFor<B>, Epoch>,
+	) -> Option<Self::Claim> {
+		debug!(target: LOG_TARGET, "Attempting to claim slot {}", slot);
+
+		let ticket = match self.client.runtime_api().slot_ticket(parent_header.hash(), slot) {
+			Ok(ticket) => ticket,
+			Err(err) => {
+				warn!(target: LOG_TARGET, "Unable to fetch ticket for slot {}: {}", slot, err);
+				return None
+			},
+		};
+
+		let s = claim_slot(
+			slot,
+			self.epoch_changes
+				.shared_data()
+				.viable_epoch(epoch_descriptor, |slot| Epoch::genesis(&self.config, slot))?
+				.as_ref(),
+			ticket,
+			&self.keystore,
+		);
+
+		if s.is_some() {
+			debug!(target: LOG_TARGET, "Claimed slot {}", slot);
+		}
+
+		s
+	}
+
+	fn notify_slot(
+		&self,
+		parent_header: &B::Header,
+		slot: Slot,
+		epoch_descriptor: &ViableEpochDescriptor<B::Hash, NumberFor<B>, Epoch>,
+	) {
+		if let Err(err) = self.generate_tickets(parent_header, slot, epoch_descriptor) {
+			warn!(target: LOG_TARGET, "Tickets generation failed: {}", err);
+		}
+	}
+
+	fn pre_digest_data(&self, _slot: Slot, claim: &Self::Claim) -> Vec<DigestItem> {
+		vec![DigestItem::from(&claim.0)]
+	}
+
+	async fn block_import_params(
+		&self,
+		header: B::Header,
+		header_hash: &B::Hash,
+		body: Vec<B::Extrinsic>,
+		storage_changes: StorageChanges<B>,
+		(_, public): Self::Claim,
+		epoch_descriptor: Self::AuxData,
+	) -> Result<BlockImportParams<B>, ConsensusError> {
+		let signature: AuthoritySignature = self
+			.keystore
+			.bandersnatch_sign(
+				<AuthorityId as AppCrypto>::ID,
+				public.as_ref(),
+				header_hash.as_ref(),
+			)
+			.map_err(|e| ConsensusError::CannotSign(format!("{}. Key: {:?}", e, public)))?
+			.ok_or_else(|| {
+				ConsensusError::CannotSign(format!(
+					"Could not find key in keystore. Key: {:?}",
+					public
+				))
+			})?
+			.into();
+
+		let mut import_block = BlockImportParams::new(BlockOrigin::Own, header);
+		import_block.post_digests.push(DigestItem::from(&signature));
+		import_block.body = Some(body);
+		import_block.state_action =
+			StateAction::ApplyChanges(sc_consensus::StorageChanges::Changes(storage_changes));
+		import_block
+			.insert_intermediate(INTERMEDIATE_KEY, SassafrasIntermediate::<B> { epoch_descriptor });
+
+		Ok(import_block)
+	}
+
+	fn force_authoring(&self) -> bool {
+		self.force_authoring
+	}
+
+	fn should_backoff(&self, slot: Slot, chain_head: &B::Header) -> bool {
+		if let Some(ref strategy) = self.backoff_authoring_blocks {
+			if let Ok(chain_head_slot) = find_slot::<B>(chain_head) {
+				return strategy.should_backoff(
+					*chain_head.number(),
+					chain_head_slot,
+					self.client.info().finalized_number,
+					slot,
+					self.logging_target(),
+				)
+			}
+		}
+		false
+	}
+
+	fn sync_oracle(&mut self) -> &mut Self::SyncOracle {
+		&mut self.sync_oracle
+	}
+
+	fn justification_sync_link(&mut self) -> &mut Self::JustificationSyncLink {
+		&mut self.justification_sync_link
+	}
+
+	fn proposer(&mut self, block: &B::Header) -> Self::CreateProposer {
+		Box::pin(self.env.init(block).map_err(|e| ConsensusError::ClientImport(e.to_string())))
+	}
+
+	fn telemetry(&self) -> Option<TelemetryHandle> {
+		self.telemetry.clone()
+	}
+
+	fn proposing_remaining_duration(&self, slot_info: &SlotInfo<B>) -> Duration {
+		let parent_slot = find_slot::<B>(&slot_info.chain_head).ok();
+
+		sc_consensus_slots::proposing_remaining_duration(
+			parent_slot,
+			slot_info,
+			&self.block_proposal_slot_portion,
+			self.max_block_proposal_slot_portion.as_ref(),
+			sc_consensus_slots::SlotLenienceType::Exponential,
+			self.logging_target(),
+		)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::verification::check_slot_claim;
+	use sp_core::crypto::DEV_PHRASE;
+	use sp_keystore::testing::MemoryKeystore;
+	use substrate_test_runtime_client::runtime::Block;
+
+	fn test_epoch(authorities: Vec<AuthorityId>) -> Epoch {
+		sp_consensus_sassafras::Epoch {
+			index: 3,
+			start: 30.into(),
+			length: 100,
+			randomness: [7; 32],
+			authorities,
+			config: EpochConfiguration { redundancy_factor: 1, attempts_number: 4 },
+		}
+		.into()
+	}
+
+	fn random_authority() -> AuthorityId {
+		AuthorityPair::generate().0.public()
+	}
+
+	#[test]
+	fn claim_secondary_slot_works() {
+		let keystore: KeystorePtr = MemoryKeystore::new().into();
+		let public = keystore.bandersnatch_generate_new(AuthorityId::ID, Some(DEV_PHRASE)).unwrap();
+
+		let mut epoch = test_epoch(vec![random_authority(), random_authority()]);
+		let our_slot = (30u64..130)
+			.map(Slot::from)
+			.find(|slot| {
+				epoch.authorities.push(public.into());
+				let idx = secondary_authority_index(*slot, &epoch);
+				epoch.authorities.pop();
+				idx == Some(2)
+			})
+			.expect("Some slot in the epoch is assigned to the third authority");
+
+		// Not an authority yet.
+		assert!(claim_slot(our_slot, &epoch, None, &keystore).is_none());
+
+		epoch.authorities.push(public.into());
+		let (claim, author) = claim_slot(our_slot, &epoch, None, &keystore).unwrap();
+		assert_eq!(author, public.into());
+		assert_eq!(claim.authority_idx, 2);
+		assert!(claim.ticket_claim.is_none());
+		assert_eq!(check_slot_claim::<Block>(&claim, &epoch, None).unwrap(), author);
+
+		// A claim of a slot with a ticket must reveal the ticket keys.
+		let ticket = TicketBody {
+			attempt_idx: 0,
+			erased_public: ed25519::Pair::generate().0.public(),
+			revealed_public: ed25519::Pair::generate().0.public(),
+		};
+		assert!(check_slot_claim::<Block>(&claim, &epoch, Some(&ticket)).is_err());
+	}
+
+	#[test]
+	fn claim_ticket_slot_works() {
+		let keystore: KeystorePtr = MemoryKeystore::new().into();
+		let public = keystore.bandersnatch_generate_new(AuthorityId::ID, Some(DEV_PHRASE)).unwrap();
+		let authority_id: AuthorityId = public.into();
+
+		let mut epoch = test_epoch(vec![random_authority(), authority_id.clone()]);
+		let slot = Slot::from(33);
+		let attempt_idx = 2;
+
+		// Build the ticket secrets as done by `generate_epoch_tickets`, without the ring proof.
+		let revealed_input = revealed_key_input(&epoch.randomness, attempt_idx, epoch.index);
+		let revealed_pre_output = keystore
+			.bandersnatch_vrf_pre_output(AuthorityId::ID, &public, &revealed_input)
+			.unwrap()
+			.unwrap();
+		let revealed_seed = make_revealed_key_seed(&revealed_input, &revealed_pre_output);
+		let (erased_pair, erased_seed) = ed25519::Pair::generate();
+		let body = TicketBody {
+			attempt_idx,
+			erased_public: erased_pair.public(),
+			revealed_public: ed25519::Pair::from_seed(&revealed_seed).public(),
+		};
+		let ticket_id = 42;
+
+		// Not our ticket.
+		assert!(claim_slot(slot, &epoch, Some((ticket_id, body.clone())), &keystore).is_none());
+
+		epoch
+			.tickets_aux
+			.insert(ticket_id, TicketSecret { authority_idx: 1, body: body.clone(), erased_seed });
+		let (claim, author) =
+			claim_slot(slot, &epoch, Some((ticket_id, body.clone())), &keystore).unwrap();
+		assert_eq!(author, authority_id);
+		assert!(claim.ticket_claim.is_some());
+		assert_eq!(check_slot_claim::<Block>(&claim, &epoch, Some(&body)).unwrap(), author);
+
+		// The claim doesn't match a different ticket.
+		let other_body = TicketBody { attempt_idx: 3, ..body };
+		assert!(check_slot_claim::<Block>(&claim, &epoch, Some(&other_body)).is_err());
+		assert!(check_slot_claim::<Block>(&claim, &epoch, None).is_err());
+	}
+}
@@ -0,0 +1,450 @@
+// This file is part of Substrate.
+
+// Copyright (C) Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Sassafras block import, epoch changes and tickets tests.
+
+use super::*;
+use crate::{
+	authorship::{claim_slot, generate_epoch_tickets},
+	verification::check_slot_claim,
+};
+
+use std::sync::Arc;
+
+use sc_client_api::{OnFinalityAction, OnImportAction, PreCommitActions};
+use sc_consensus::{BlockImport, BlockImportParams, ImportResult, StateAction};
+use sc_consensus_epochs::descendent_query;
+use sp_api::{ApiRef, ProvideRuntimeApi};
+use sp_application_crypto::AppCrypto;
+use sp_blockchain::{BlockStatus, CachedHeaderMetadata, HeaderBackend, HeaderMetadata, Info};
+use sp_consensus::BlockOrigin;
+use sp_consensus_sassafras::{
+	ticket_id_threshold,
+	vrf::{make_ticket_id, ticket_body_sign_data, ticket_id_input, RingContext},
+	EquivocationProof, OpaqueKeyOwnershipProof, TicketClaim, TicketEnvelope,
+};
+use sp_core::{crypto::Pair as _, ed25519};
+use sp_keystore::{testing::MemoryKeystore, Keystore, KeystorePtr};
+use sp_runtime::{traits::Header as _, Digest, DigestItem};
+use substrate_test_runtime_client::runtime::{Block, Hash, Header};
+
+const EPOCH_LENGTH: u32 = 6;
+
+/// The test runtime client, exposing a mocked Sassafras runtime API.
+#[derive(Clone)]
+struct TestClient {
+	inner: Arc<substrate_test_runtime_client::TestClient>,
+	genesis_epoch: sp_consensus_sassafras::Epoch,
+}
+
+struct RuntimeApi {
+	inner: TestClient,
+}
+
+impl ProvideRuntimeApi<Block> for TestClient {
+	type Api = RuntimeApi;
+
+	fn runtime_api(&self) -> ApiRef<'_, Self::Api> {
+		RuntimeApi { inner: self.clone() }.into()
+	}
+}
+
+sp_api::mock_impl_runtime_apis! {
+	impl SassafrasApi<Block> for RuntimeApi {
+		fn ring_context() -> Option<RingContext> {
+			None
+		}
+
+		fn submit_tickets_unsigned_extrinsic(_tickets: Vec<TicketEnvelope>) -> bool {
+			false
+		}
+
+		fn slot_ticket_id(_slot: Slot) -> Option<TicketId> {
+			None
+		}
+
+		fn slot_ticket(_slot: Slot) -> Option<(TicketId, TicketBody)> {
+			None
+		}
+
+		fn current_epoch(&self) -> sp_consensus_sassafras::Epoch {
+			self.inner.genesis_epoch.clone()
+		}
+
+		fn next_epoch(&self) -> sp_consensus_sassafras::Epoch {
+			let mut epoch = self.inner.genesis_epoch.clone();
+			epoch.index += 1;
+			epoch.start = epoch.start + epoch.length as u64;
+			epoch
+		}
+
+		fn generate_key_ownership_proof(_authority_id: AuthorityId) -> Option<OpaqueKeyOwnershipProof> {
+			None
+		}
+
+		fn submit_report_equivocation_unsigned_extrinsic(
+			_equivocation_proof: EquivocationProof<Header>,
+			_key_owner_proof: OpaqueKeyOwnershipProof,
+		) -> bool {
+			false
+		}
+	}
+}
+
+impl HeaderBackend<Block> for TestClient {
+	fn header(&self, hash: Hash) -> sp_blockchain::Result<Option<Header>> {
+		self.inner.header(hash)
+	}
+
+	fn info(&self) -> Info<Block> {
+		self.inner.info()
+	}
+
+	fn status(&self, hash: Hash) -> sp_blockchain::Result<BlockStatus> {
+		self.inner.status(hash)
+	}
+
+	fn number(&self, hash: Hash) -> sp_blockchain::Result<Option<u64>> {
+		self.inner.number(hash)
+	}
+
+	fn hash(&self, number: u64) -> sp_blockchain::Result<Option<Hash>> {
+		self.inner.hash(number)
+	}
+}
+
+impl HeaderMetadata<Block> for TestClient {
+	type Error = sp_blockchain::Error;
+
+	fn header_metadata(&self, hash: Hash) -> Result<CachedHeaderMetadata<Block>, Self::Error> {
+		self.inner.header_metadata(hash)
+	}
+
+	fn insert_header_metadata(&self, hash: Hash, metadata: CachedHeaderMetadata<Block>) {
+		self.inner.insert_header_metadata(hash, metadata)
+	}
+
+	fn remove_header_metadata(&self, hash: Hash) {
+		self.inner.remove_header_metadata(hash)
+	}
+}
+
+impl AuxStore for TestClient {
+	fn insert_aux<
+		'a,
+		'b: 'a,
+		'c: 'a,
+		I: IntoIterator<Item = &'a (&'c [u8], &'c [u8])>,
+		D: IntoIterator<Item = &'a &'b [u8]>,
+	>(
+		&self,
+		insert: I,
+		delete: D,
+	) -> sp_blockchain::Result<()> {
+		self.inner.insert_aux(insert, delete)
+	}
+
+	fn get_aux(&self, key: &[u8]) -> sp_blockchain::Result<Option<Vec<u8>>> {
+		self.inner.get_aux(key)
+	}
+}
+
+impl PreCommitActions<Block> for TestClient {
+	fn register_import_action(&self, action: OnImportAction<Block>) {
+		self.inner.register_import_action(action)
+	}
+
+	fn register_finality_action(&self, action: OnFinalityAction<Block>) {
+		self.inner.register_finality_action(action)
+	}
+}
+
+type TestBlockImport =
+	SassafrasBlockImport<Block, TestClient, Arc<substrate_test_runtime_client::TestClient>>;
+
+struct TestContext {
+	client: Arc<TestClient>,
+	block_import: TestBlockImport,
+	link: SassafrasLink<Block>,
+	keystore: KeystorePtr,
+}
+
+impl TestContext {
+	fn new() -> Self {
+		let keystore: KeystorePtr = MemoryKeystore::new().into();
+		let authority =
+			keystore.bandersnatch_generate_new(AuthorityId::ID, Some("//Alice")).unwrap();
+
+		let genesis_epoch = sp_consensus_sassafras::Epoch {
+			index: 0,
+			start: 0.into(),
+			length: EPOCH_LENGTH,
+			randomness: [0; 32],
+			authorities: vec![authority.into()],
+			config: EpochConfiguration { redundancy_factor: 1, attempts_number: 4 },
+		};
+		let inner = Arc::new(substrate_test_runtime_client::new());
+		let client =
+			Arc::new(TestClient { inner: inner.clone(), genesis_epoch: genesis_epoch.clone() });
+		let config = SassafrasConfiguration {
+			slot_duration: SlotDuration::from_millis(6000),
+			genesis_epoch,
+		};
+
+		let (block_import, link) = crate::block_import(config, inner, client.clone()).unwrap();
+		TestContext { client, block_import, link, keystore }
+	}
+
+	fn next_epoch_descriptor(&self) -> NextEpochDescriptor {
+		NextEpochDescriptor {
+			randomness: [1; 32],
+			authorities: self.link.config().genesis_epoch.authorities.clone(),
+			config: None,
+		}
+	}
+
+	/// Epoch which should be used to claim a slot on top of the given parent.
+	fn epoch_for_child_of(&self, parent: &Header, slot: Slot) -> Epoch {
+		self.link
+			.epoch_changes()
+			.shared_data()
+			.epoch_data_for_child_of(
+				descendent_query(&*self.client),
+				&parent.hash(),
+				*parent.number(),
+				slot,
+				|slot| Epoch::genesis(self.link.config(), slot),
+			)
+			.unwrap()
+			.unwrap()
+	}
+
+	fn make_header(
+		&self,
+		parent: &Header,
+		slot: u64,
+		with_ticket: bool,
+		next_epoch: Option<NextEpochDescriptor>,
+	) -> Header {
+		let slot = Slot::from(slot);
+		let epoch = self.epoch_for_child_of(parent, slot);
+		let (mut claim, _) = claim_slot(slot, &epoch, None, &self.keystore).unwrap();
+		if with_ticket {
+			// Block import doesn't check the claim, only its weight.
+			let erased_signature = ed25519::Pair::generate().0.sign(b"ticket");
+			claim.ticket_claim = Some(TicketClaim { erased_signature });
+		}
+
+		let mut digest = Digest { logs: vec![DigestItem::from(&claim)] };
+		if let Some(descriptor) = next_epoch {
+			digest.push(DigestItem::Consensus(
+				SASSAFRAS_ENGINE_ID,
+				ConsensusLog::NextEpochData(descriptor).encode(),
+			));
+		}
+		Header::new(
+			*parent.number() + 1,
+			Default::default(),
+			Default::default(),
+			parent.hash(),
+			digest,
+		)
+	}
+
+	async fn import(&mut self, header: Header) -> Result<Header, ConsensusError> {
+		let parent = self.client.header(*header.parent_hash()).unwrap().unwrap();
+		let slot = find_slot::<Block>(&header).unwrap();
+		let epoch_descriptor = self
+			.link
+			.epoch_changes()
+			.shared_data()
+			.epoch_descriptor_for_child_of(
+				descendent_query(&*self.client),
+				&parent.hash(),
+				*parent.number(),
+				slot,
+			)
+			.unwrap()
+			.unwrap();
+
+		let mut params = BlockImportParams::new(BlockOrigin::Own, header.clone());
+		params.state_action = StateAction::Skip;
+		params.insert_intermediate(
+			INTERMEDIATE_KEY,
+			SassafrasIntermediate::<Block> { epoch_descriptor },
+		);
+
+		match self.block_import.import_block(params).await? {
+			ImportResult::Imported(_) => Ok(header),
+			r => panic!("Unexpected import result: {:?}", r),
+		}
+	}
+
+	fn genesis(&self) -> Header {
+		let hash = self.client.info().genesis_hash;
+		self.client.header(hash).unwrap().unwrap()
+	}
+
+	fn best_hash(&self) -> Hash {
+		self.client.info().best_hash
+	}
+}
+
+#[tokio::test]
+async fn epoch_changes_are_enforced() {
+	let mut ctx = TestContext::new();
+	let genesis = ctx.genesis();
+
+	// The first block starts the genesis epoch and must announce the next one.
+	let header = ctx.make_header(&genesis, 1, false, None);
+	assert!(ctx
+		.import(header)
+		.await
+		.unwrap_err()
+		.to_string()
+		.contains("Expected epoch change"));
+	let next = ctx.next_epoch_descriptor();
+	let b1 = ctx.import(ctx.make_header(&genesis, 1, false, Some(next))).await.unwrap();
+
+	// Announcements are only allowed in the first block of an epoch.
+	let header = ctx.make_header(&b1, 2, false, Some(ctx.next_epoch_descriptor()));
+	assert!(ctx
+		.import(header)
+		.await
+		.unwrap_err()
+		.to_string()
+		.contains("Unexpected epoch change"));
+	let b2 = ctx.import(ctx.make_header(&b1, 2, false, None)).await.unwrap();
+
+	// Slots must increase.
+	let header = ctx.make_header(&b2, 2, false, None);
+	assert!(ctx
+		.import(header)
+		.await
+		.unwrap_err()
+		.to_string()
+		.contains("Slot number must increase"));
+
+	// The genesis epoch spans slots [1, 7), the first block of epoch #1 announces epoch #2.
+	assert_eq!(ctx.epoch_for_child_of(&b2, 6.into()).index, 0);
+	assert_eq!(ctx.epoch_for_child_of(&b2, 7.into()).index, 1);
+	let header = ctx.make_header(&b2, 8, false, None);
+	assert!(ctx
+		.import(header)
+		.await
+		.unwrap_err()
+		.to_string()
+		.contains("Expected epoch change"));
+	let b3 = ctx
+		.import(ctx.make_header(&b2, 8, false, Some(ctx.next_epoch_descriptor())))
+		.await
+		.unwrap();
+
+	let epoch = ctx.epoch_for_child_of(&b3, 13.into());
+	assert_eq!((epoch.index, epoch.start), (2, 13.into()));
+	assert_eq!(epoch.randomness, [1; 32]);
+
+	// Skipped epochs reuse the data of the last announced epoch.
+	let b4 = ctx
+		.import(ctx.make_header(&b3, 30, false, Some(ctx.next_epoch_descriptor())))
+		.await
+		.unwrap();
+	let epoch = ctx.epoch_for_child_of(&b4, 31.into());
+	assert_eq!((epoch.index, epoch.start), (5, 31.into()));
+}
+
+#[tokio::test]
+async fn heaviest_chain_is_best() {
+	let mut ctx = TestContext::new();
+	let genesis = ctx.genesis();
+
+	let a1 = ctx
+		.import(ctx.make_header(&genesis, 1, false, Some(ctx.next_epoch_descriptor())))
+		.await
+		.unwrap();
+	let a2 = ctx.import(ctx.make_header(&a1, 2, false, None)).await.unwrap();
+	let a3 = ctx.import(ctx.make_header(&a2, 3, false, None)).await.unwrap();
+	assert_eq!(ctx.best_hash(), a3.hash());
+
+	// A shorter fork with a block claimed with a ticket is heavier.
+	let b2 = ctx.import(ctx.make_header(&a1, 4, true, None)).await.unwrap();
+	assert_eq!(block_weight(&*ctx.client, b2.hash()).unwrap(), Some(1));
+	assert_eq!(block_weight(&*ctx.client, a3.hash()).unwrap(), Some(0));
+	assert_eq!(ctx.best_hash(), b2.hash());
+
+	// Extending the lighter chain doesn't change the best block.
+	ctx.import(ctx.make_header(&a3, 5, false, None)).await.unwrap();
+	assert_eq!(ctx.best_hash(), b2.hash());
+
+	// With equal weight the longest chain wins.
+	let b3 = ctx.import(ctx.make_header(&b2, 6, false, None)).await.unwrap();
+	assert_eq!(ctx.best_hash(), b3.hash());
+}
+
+#[test]
+fn generated_tickets_are_valid_and_claimable() {
+	let keystore: KeystorePtr = MemoryKeystore::new().into();
+	let ours = keystore.bandersnatch_generate_new(AuthorityId::ID, Some("//Alice")).unwrap();
+	let other = AuthorityPair::from_string("//Bob", None).unwrap().public();
+
+	let mut epoch: Epoch = sp_consensus_sassafras::Epoch {
+		index: 1,
+		start: 10.into(),
+		length: 20,
+		randomness: [3; 32],
+		authorities: vec![other.clone(), ours.into()],
+		config: EpochConfiguration { redundancy_factor: 1, attempts_number: 4 },
+	}
+	.into();
+	let threshold = ticket_id_threshold(1, 20, 4, 2);
+
+	let ring_ctx = RingContext::new_testing();
+	let tickets = generate_epoch_tickets(&mut epoch, &keystore, &ring_ctx);
+
+	// The threshold is above every ticket identifier, so every attempt produces a ticket,
+	// and only our authority generates tickets.
+	assert_eq!(threshold, TicketId::MAX);
+	assert_eq!(tickets.len(), 4);
+	assert_eq!(epoch.tickets_aux.len(), 4);
+
+	let publics: Vec<_> = epoch.authorities.iter().map(|a| a.clone().into_inner()).collect();
+	let verifier = ring_ctx.verifier(&publics).unwrap();
+	for TicketEnvelope { body, signature } in &tickets {
+		let input = ticket_id_input(&epoch.randomness, body.attempt_idx, epoch.index);
+		let ticket_id = make_ticket_id(&input, &signature.pre_outputs[0]);
+		let sign_data = ticket_body_sign_data(body, input);
+		assert!(signature.ring_vrf_verify(&sign_data, &verifier));
+
+		let secret = &epoch.tickets_aux[&ticket_id];
+		assert_eq!(&secret.body, body);
+		assert_eq!(secret.authority_idx, 1);
+
+		// The slot assigned to the ticket can be claimed only by revealing the ticket keys.
+		let slot = Slot::from(15);
+		let (claim, author) =
+			claim_slot(slot, &epoch, Some((ticket_id, body.clone())), &keystore).unwrap();
+		assert_eq!(author, ours.into());
+		assert_eq!(check_slot_claim::<Block>(&claim, &epoch, Some(body)).unwrap(), author);
+	}
+
+	// Tickets are not generated for epochs we are not part of.
+	epoch.authorities = vec![other];
+	epoch.tickets_aux.clear();
+	assert!(generate_epoch_tickets(&mut epoch, &keystore, &ring_ctx).is_empty());
+	assert!(epoch.tickets_aux.is_empty());
+}
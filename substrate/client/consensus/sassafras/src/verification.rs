@@ -0,0 +1,665 @@
+// This file is part of Substrate.
+
+// Copyright (C) Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Verification for Sassafras headers.
+
+use super::*;
+use crate::authorship::{claim_sign_data, secondary_authority_index};
+
+use std::sync::Arc;
+
+use log::{info, warn};
+use prometheus_endpoint::Registry;
+
+use sc_consensus::{
+	block_import::{BlockImport, BlockImportParams},
+	import_queue::{BasicQueue, BoxJustificationImport, DefaultImportQueue, Verifier},
+};
+use sc_consensus_epochs::descendent_query;
+use sc_consensus_slots::{check_equivocation, CheckedHeader, InherentDataProviderExt};
+use sc_telemetry::{telemetry, TelemetryHandle, CONSENSUS_DEBUG, CONSENSUS_TRACE};
+use sc_transaction_pool_api::OffchainTransactionPoolFactory;
+use sp_api::ApiExt;
+use sp_block_builder::BlockBuilder as BlockBuilderApi;
+use sp_blockchain::{HeaderBackend, HeaderMetadata};
+use sp_consensus::BlockOrigin;
+use sp_consensus_sassafras::vrf::{make_revealed_key_seed, revealed_key_input};
+use sp_core::{
+	crypto::{Pair as _, VrfPublic, Wraps},
+	ed25519,
+	traits::SpawnEssentialNamed,
+};
+use sp_inherents::{CreateInherentDataProviders, InherentData, InherentDataProvider};
+use sp_runtime::DigestItem;
+
+/// Sassafras verification parameters
+struct VerificationParams<'a, B: 'a + BlockT> {
+	/// The header being verified.
+	header: B::Header,
+	/// The slot claim of the header being verified.
+	claim: SlotClaim,
+	/// The ticket assigned to the claimed slot, if any.
+	ticket: Option<TicketBody>,
+	/// The slot number of the current time.
+	slot_now: Slot,
+	/// Epoch descriptor of the epoch this block _should_ be under, if it's valid.
+	epoch: &'a Epoch,
+}
+
+/// Information from verified header
+struct VerifiedHeaderInfo {
+	/// The seal digest item.
+	seal: DigestItem,
+	/// Block author.
+	author: AuthorityId,
+}
+
+/// Check a slot claim against the given epoch and the ticket assigned to the slot.
+///
+/// Slots with a ticket must be claimed by the ticket owner, who proves the ownership by
+/// revealing the ticket ephemeral keys. All the other slots must be claimed by the
+/// secondary author.
+///
+/// Returns the slot author on success.
+pub(crate) fn check_slot_claim<B: BlockT>(
+	claim: &SlotClaim,
+	epoch: &Epoch,
+	ticket: Option<&TicketBody>,
+) -> Result<AuthorityId, Error<B>> {
+	let slot = claim.slot;
+	let mut epoch = epoch;
+	let skipped_epoch;
+	if epoch.end_slot() <= slot {
+		// Slot doesn't strictly belong to the epoch, create a clone with fixed values.
+		skipped_epoch = epoch.clone_for_slot(slot);
+		epoch = &skipped_epoch;
+	}
+
+	let author = epoch
+		.authorities
+		.get(claim.authority_idx as usize)
+		.ok_or(Error::SlotAuthorNotFound)?;
+
+	let data = claim_sign_data(slot, epoch, ticket);
+
+	match (ticket, &claim.ticket_claim) {
+		(Some(body), Some(ticket_claim)) => {
+			debug!(target: LOG_TARGET, "Checking primary (ticket) claim for slot {}", slot);
+
+			// The revealed key seed comes from the second VRF pre-output, the key must match
+			// the one committed in the ticket body.
+			let revealed_input =
+				revealed_key_input(&epoch.randomness, body.attempt_idx, epoch.index);
+			let revealed_pre_output =
+				claim.vrf_signature.pre_outputs.get(1).ok_or(Error::InvalidTicketClaim(slot))?;
+			let revealed_seed = make_revealed_key_seed(&revealed_input, revealed_pre_output);
+			if ed25519::Pair::from_seed(&revealed_seed).public() != body.revealed_public {
+				return Err(Error::InvalidTicketClaim(slot))
+			}
+
+			// The erased key is known only to the ticket owner.
+			if !ed25519::Pair::verify(
+				&ticket_claim.erased_signature,
+				data.challenge::<32>(),
+				&body.erased_public,
+			) {
+				return Err(Error::InvalidTicketClaim(slot))
+			}
+		},
+		(None, None) => {
+			debug!(target: LOG_TARGET, "Checking secondary claim for slot {}", slot);
+
+			let expected_idx =
+				secondary_authority_index(slot, epoch).ok_or(Error::SlotAuthorNotFound)?;
+			if expected_idx != claim.authority_idx {
+				let expected = epoch.authorities[expected_idx as usize].clone();
+				return Err(Error::InvalidAuthor(expected, author.clone()))
+			}
+		},
+		_ => return Err(Error::TicketMismatch(slot)),
+	}
+
+	if !author.as_inner_ref().vrf_verify(&data, &claim.vrf_signature) {
+		return Err(Error::VrfVerificationFailed)
+	}
+
+	Ok(author.clone())
+}
+
+/// Check a header has been signed by the right key. If the slot is too far in
+/// the future, an error will be returned. If successful, returns the pre-header
+/// and the digest item containing the seal.
+///
+/// The seal must be the last digest.  Otherwise, the whole header is considered
+/// unsigned.  This is required for security and must not be changed.
+fn check_header<B: BlockT + Sized>(
+	params: VerificationParams<B>,
+) -> Result<CheckedHeader<B::Header, VerifiedHeaderInfo>, Error<B>> {
+	let VerificationParams { mut header, claim, ticket, slot_now, epoch } = params;
+
+	trace!(target: LOG_TARGET, "Checking header");
+	let seal = header
+		.digest_mut()
+		.pop()
+		.ok_or_else(|| sassafras_err(Error::HeaderUnsealed(header.hash())))?;
+
+	let signature = AuthoritySignature::try_from(&seal)
+		.map_err(|_| sassafras_err(Error::HeaderBadSeal(header.hash())))?;
+
+	// the pre-hash of the header doesn't include the seal
+	// and that's what we sign
+	let pre_hash = header.hash();
+
+	if claim.slot > slot_now {
+		header.digest_mut().push(seal);
+		return Ok(CheckedHeader::Deferred(header, claim.slot))
+	}
+
+	let author = check_slot_claim::<B>(&claim, epoch, ticket.as_ref()).map_err(sassafras_err)?;
+
+	if !AuthorityPair::verify(&signature, pre_hash, &author) {
+		return Err(sassafras_err(Error::BadSignature(pre_hash)))
+	}
+
+	Ok(CheckedHeader::Checked(header, VerifiedHeaderInfo { seal, author }))
+}
+
+/// A verifier for Sassafras blocks.
+pub struct SassafrasVerifier<Block: BlockT, Client, SelectChain, CIDP> {
+	client: Arc<Client>,
+	select_chain: SelectChain,
+	create_inherent_data_providers: CIDP,
+	config: SassafrasConfiguration,
+	epoch_changes: SharedEpochChanges<Block, Epoch>,
+	telemetry: Option<TelemetryHandle>,
+	offchain_tx_pool_factory: OffchainTransactionPoolFactory<Block>,
+}
+
+impl<Block, Client, SelectChain, CIDP> SassafrasVerifier<Block, Client, SelectChain, CIDP>
+where
+	Block: BlockT,
+	Client: AuxStore + HeaderBackend<Block> + HeaderMetadata<Block> + ProvideRuntimeApi<Block>,
+	Client::Api: BlockBuilderApi<Block> + SassafrasApi<Block>,
+	SelectChain: sp_consensus::SelectChain<Block>,
+	CIDP: CreateInherentDataProviders<Block, ()>,
+{
+	async fn check_inherents(
+		&self,
+		block: Block,
+		at_hash: Block::Hash,
+		inherent_data: InherentData,
+		create_inherent_data_providers: CIDP::InherentDataProviders,
+	) -> Result<(), Error<Block>> {
+		let inherent_res = self
+			.client
+			.runtime_api()
+			.check_inherents(at_hash, block, inherent_data)
+			.map_err(Error::RuntimeApi)?;
+
+		if !inherent_res.ok() {
+			for (i, e) in inherent_res.into_errors() {
+				match create_inherent_data_providers.try_handle_error(&i, &e).await {
+					Some(res) => res.map_err(Error::CheckInherents)?,
+					None => return Err(Error::CheckInherentsUnhandled(i)),
+				}
+			}
+		}
+
+		Ok(())
+	}
+
+	async fn check_and_report_equivocation(
+		&self,
+		slot_now: Slot,
+		slot: Slot,
+		header: &Block::Header,
+		author: &AuthorityId,
+		origin: &BlockOrigin,
+	) -> Result<(), Error<Block>> {
+		// don't report any equivocations during initial sync
+		// as they are most likely stale.
+		if *origin == BlockOrigin::NetworkInitialSync {
+			return Ok(())
+		}
+
+		// check if authorship of this header is an equivocation and return a proof if so.
+		let equivocation_proof =
+			match check_equivocation(&*self.client, slot_now, slot, header, author)
+				.map_err(Error::Client)?
+			{
+				Some(proof) => proof,
+				None => return Ok(()),
+			};
+
+		info!(
+			target: LOG_TARGET,
+			"Slot author {:?} is equivocating at slot {} with headers {:?} and {:?}",
+			author,
+			slot,
+			equivocation_proof.first_header.hash(),
+			equivocation_proof.second_header.hash(),
+		);
+
+		// get the best block on which we will build and send the equivocation report.
+		let best_hash = self
+			.select_chain
+			.best_chain()
+			.await
+			.map(|h| h.hash())
+			.map_err(|e| Error::Client(e.into()))?;
+
+		// generate a key ownership proof. we start by trying to generate the
+		// key ownership proof at the parent of the equivocating header, this
+		// will make sure that proof generation is successful since it happens
+		// during the on-going session (i.e. session keys are available in the
+		// state to be able to generate the proof). this might fail if the
+		// equivocation happens on the first block of the session, in which case
+		// its parent would be on the previous session. if generation on the
+		// parent header fails we try with best block as well.
+		let generate_key_owner_proof = |at_hash: Block::Hash| {
+			self.client
+				.runtime_api()
+				.generate_key_ownership_proof(at_hash, equivocation_proof.offender.clone())
+				.map_err(Error::RuntimeApi)
+		};
+
+		let parent_hash = *header.parent_hash();
+		let key_owner_proof = match generate_key_owner_proof(parent_hash)? {
+			Some(proof) => proof,
+			None => match generate_key_owner_proof(best_hash)? {
+				Some(proof) => proof,
+				None => {
+					debug!(
+						target: LOG_TARGET,
+						"Equivocation offender is not part of the authority set."
+					);
+					return Ok(())
+				},
+			},
+		};
+
+		// submit equivocation report at best block.
+		let mut runtime_api = self.client.runtime_api();
+
+		// Register the offchain tx pool to be able to use it from the runtime.
+		runtime_api
+			.register_extension(self.offchain_tx_pool_factory.offchain_transaction_pool(best_hash));
+
+		runtime_api
+			.submit_report_equivocation_unsigned_extrinsic(
+				best_hash,
+				equivocation_proof,
+				key_owner_proof,
+			)
+			.map_err(Error::RuntimeApi)?;
+
+		info!(target: LOG_TARGET, "Submitted equivocation report for author {:?}", author);
+
+		Ok(())
+	}
+}
+
+#[async_trait::async_trait]
+impl<Block, Client, SelectChain, CIDP> Verifier<Block>
+	for SassafrasVerifier<Block, Client, SelectChain, CIDP>
+where
+	Block: BlockT,
+	Client: HeaderMetadata<Block, Error = sp_blockchain::Error>
+		+ HeaderBackend<Block>
+		+ ProvideRuntimeApi<Block>
+		+ Send
+		+ Sync
+		+ AuxStore,
+	Client::Api: BlockBuilderApi<Block> + SassafrasApi<Block>,
+	SelectChain: sp_consensus::SelectChain<Block>,
+	CIDP: CreateInherentDataProviders<Block, ()> + Send + Sync,
+	CIDP::InherentDataProviders: InherentDataProviderExt + Send + Sync,
+{
+	async fn verify(
+		&mut self,
+		mut block: BlockImportParams<Block>,
+	) -> Result<BlockImportParams<Block>, String> {
+		trace!(
+			target: LOG_TARGET,
+			"Verifying origin: {:?} header: {:?} justification(s): {:?} body: {:?}",
+			block.origin,
+			block.header,
+			block.justifications,
+			block.body,
+		);
+
+		let hash = block.header.hash();
+		let parent_hash = *block.header.parent_hash();
+
+		let info = self.client.info();
+		let number = *block.header.number();
+
+		if info.block_gap.map_or(false, |(s, e)| s <= number && number <= e) || block.with_state() {
+			// Verification for imported blocks is skipped in two cases:
+			// 1. When importing blocks below the last finalized block during network initial
+			//    synchronization.
+			// 2. When importing whole state we don't calculate epoch descriptor, but rather read it
+			//    from the state after import. We also skip all verifications because there's no
+			//    parent state and we trust the sync module to verify that the state is correct and
+			//    finalized.
+			return Ok(block)
+		}
+
+		let create_inherent_data_providers = self
+			.create_inherent_data_providers
+			.create_inherent_data_providers(parent_hash, ())
+			.await
+			.map_err(|e| Error::<Block>::Client(ConsensusError::from(e).into()))?;
+
+		let slot_now = create_inherent_data_providers.slot();
+
+		let parent_header_metadata = self
+			.client
+			.header_metadata(parent_hash)
+			.map_err(Error::<Block>::FetchParentHeader)?;
+
+		let claim = find_slot_claim::<Block>(&block.header)?;
+
+		// The ticket assigned to the slot is read from the parent state.
+		let ticket = self
+			.client
+			.runtime_api()
+			.slot_ticket(parent_hash, claim.slot)
+			.map_err(Error::<Block>::RuntimeApi)?
+			.map(|(_, body)| body);
+
+		let (check_header, epoch_descriptor) = {
+			let epoch_changes = self.epoch_changes.shared_data();
+			let epoch_descriptor = epoch_changes
+				.epoch_descriptor_for_child_of(
+					descendent_query(&*self.client),
+					&parent_hash,
+					parent_header_metadata.number,
+					claim.slot,
+				)
+				.map_err(|e| Error::<Block>::ForkTree(Box::new(e)))?
+				.ok_or(Error::<Block>::FetchEpoch(parent_hash))?;
+			let viable_epoch = epoch_changes
+				.viable_epoch(&epoch_descriptor, |slot| Epoch::genesis(&self.config, slot))
+				.ok_or(Error::<Block>::FetchEpoch(parent_hash))?;
+
+			// We add one to the current slot to allow for some small drift.
+			let v_params = VerificationParams {
+				header: block.header.clone(),
+				claim: claim.clone(),
+				ticket,
+				slot_now: slot_now + 1,
+				epoch: viable_epoch.as_ref(),
+			};
+
+			(check_header::<Block>(v_params)?, epoch_descriptor)
+		};
+
+		match check_header {
+			CheckedHeader::Checked(pre_header, verified_info) => {
+				// the header is valid but let's check if there was something else already
+				// proposed at the same slot by the given author. if there was, we will
+				// report the equivocation to the runtime.
+				if let Err(err) = self
+					.check_and_report_equivocation(
+						slot_now,
+						claim.slot,
+						&block.header,
+						&verified_info.author,
+						&block.origin,
+					)
+					.await
+				{
+					warn!(
+						target: LOG_TARGET,
+						"Error checking/reporting Sassafras equivocation: {}", err
+					);
+				}
+
+				if let Some(inner_body) = block.body {
+					let new_block = Block::new(pre_header.clone(), inner_body);
+					if !block.state_action.skip_execution_checks() {
+						let inherent_data = create_inherent_data_providers
+							.create_inherent_data()
+							.await
+							.map_err(Error::<Block>::CreateInherents)?;
+
+						self.check_inherents(
+							new_block.clone(),
+							parent_hash,
+							inherent_data,
+							create_inherent_data_providers,
+						)
+						.await?;
+					}
+
+					let (_, inner_body) = new_block.deconstruct();
+					block.body = Some(inner_body);
+				}
+
+				trace!(target: LOG_TARGET, "Checked {:?}; importing.", pre_header);
+				telemetry!(
+					self.telemetry;
+					CONSENSUS_TRACE;
+					"sassafras.checked_and_importing";
+					"pre_header" => ?pre_header,
+				);
+
+				block.header = pre_header;
+				block.post_digests.push(verified_info.seal);
+				block.insert_intermediate(
+					INTERMEDIATE_KEY,
+					SassafrasIntermediate::<Block> { epoch_descriptor },
+				);
+				block.post_hash = Some(hash);
+
+				Ok(block)
+			},
+			CheckedHeader::Deferred(a, b) => {
+				debug!(target: LOG_TARGET, "Checking {:?} failed; {:?}, {:?}.", hash, a, b);
+				telemetry!(
+					self.telemetry;
+					CONSENSUS_DEBUG;
+					"sassafras.header_too_far_in_future";
+					"hash" => ?hash, "a" => ?a, "b" => ?b
+				);
+				Err(Error::<Block>::TooFarInFuture(hash).into())
+			},
+		}
+	}
+}
+
+/// Parameters passed to [`import_queue`].
+pub struct ImportQueueParams<'a, Block: BlockT, BI, Client, CIDP, SelectChain, Spawn> {
+	/// The Sassafras link that is created by [`block_import`](crate::block_import).
+	pub link: SassafrasLink<Block>,
+	/// The block import that should be wrapped.
+	pub block_import: BI,
+	/// Optional justification import.
+	pub justification_import: Option<BoxJustificationImport<Block>>,
+	/// The client to interact with the internals of the node.
+	pub client: Arc<Client>,
+	/// A [`SelectChain`](sp_consensus::SelectChain) implementation.
+	///
+	/// Used to determine the best block that should be used as basis when sending an equivocation
+	/// report.
+	pub select_chain: SelectChain,
+	/// Used to crate the inherent data providers.
+	///
+	/// These inherent data providers are then used to create the inherent data that is
+	/// passed to the `check_inherents` runtime call.
+	pub create_inherent_data_providers: CIDP,
+	/// Spawner for spawning futures.
+	pub spawner: &'a Spawn,
+	/// Registry for prometheus metrics.
+	pub registry: Option<&'a Registry>,
+	/// Optional telemetry handle to report telemetry events.
+	pub telemetry: Option<TelemetryHandle>,
+	/// The offchain transaction pool factory.
+	///
+	/// Will be used when sending equivocation reports.
+	pub offchain_tx_pool_factory: OffchainTransactionPoolFactory<Block>,
+}
+
+/// Start an import queue for the Sassafras consensus algorithm.
+///
+/// The block import object provided must be the `SassafrasBlockImport` or a wrapper
+/// of it, otherwise crucial import logic will be omitted.
+pub fn import_queue<Block: BlockT, Client, SelectChain, BI, CIDP, Spawn>(
+	ImportQueueParams {
+		link,
+		block_import,
+		justification_import,
+		client,
+		select_chain,
+		create_inherent_data_providers,
+		spawner,
+		registry,
+		telemetry,
+		offchain_tx_pool_factory,
+	}: ImportQueueParams<'_, Block, BI, Client, CIDP, SelectChain, Spawn>,
+) -> ClientResult<DefaultImportQueue<Block>>
+where
+	BI: BlockImport<Block, Error = ConsensusError> + Send + Sync + 'static,
+	Client: ProvideRuntimeApi<Block>
+		+ HeaderBackend<Block>
+		+ HeaderMetadata<Block, Error = sp_blockchain::Error>
+		+ AuxStore
+		+ Send
+		+ Sync
+		+ 'static,
+	Client::Api: BlockBuilderApi<Block> + SassafrasApi<Block> + ApiExt<Block>,
+	SelectChain: sp_consensus::SelectChain<Block> + 'static,
+	CIDP: CreateInherentDataProviders<Block, ()> + Send + Sync + 'static,
+	CIDP::InherentDataProviders: InherentDataProviderExt + Send + Sync,
+	Spawn: SpawnEssentialNamed,
+{
+	let verifier = SassafrasVerifier {
+		select_chain,
+		create_inherent_data_providers,
+		config: link.config,
+		epoch_changes: link.epoch_changes,
+		telemetry,
+		client,
+		offchain_tx_pool_factory,
+	};
+
+	Ok(BasicQueue::new(verifier, Box::new(block_import), justification_import, spawner, registry))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::authorship::claim_slot;
+	use sp_application_crypto::AppCrypto;
+	use sp_keystore::{testing::MemoryKeystore, Keystore, KeystorePtr};
+	use sp_runtime::{traits::Header as _, Digest};
+	use substrate_test_runtime_client::runtime::{Block, Header};
+
+	fn test_epoch(authorities: Vec<AuthorityId>) -> Epoch {
+		sp_consensus_sassafras::Epoch {
+			index: 0,
+			start: 10.into(),
+			length: 10,
+			randomness: [5; 32],
+			authorities,
+			config: EpochConfiguration { redundancy_factor: 1, attempts_number: 4 },
+		}
+		.into()
+	}
+
+	fn make_header(claim: &SlotClaim, keystore: &KeystorePtr, signer: &AuthorityId) -> Header {
+		let mut header = Header::new(
+			1,
+			Default::default(),
+			Default::default(),
+			Default::default(),
+			Digest { logs: vec![DigestItem::from(claim)] },
+		);
+		let signature: AuthoritySignature = keystore
+			.bandersnatch_sign(AuthorityId::ID, signer.as_ref(), header.hash().as_ref())
+			.unwrap()
+			.unwrap()
+			.into();
+		header.digest_mut().push(DigestItem::from(&signature));
+		header
+	}
+
+	fn check(
+		header: Header,
+		claim: &SlotClaim,
+		slot_now: u64,
+		epoch: &Epoch,
+	) -> Result<CheckedHeader<Header, VerifiedHeaderInfo>, Error<Block>> {
+		check_header::<Block>(VerificationParams {
+			header,
+			claim: claim.clone(),
+			ticket: None,
+			slot_now: slot_now.into(),
+			epoch,
+		})
+	}
+
+	#[test]
+	fn check_header_works() {
+		let keystore: KeystorePtr = MemoryKeystore::new().into();
+		let alice: AuthorityId = keystore
+			.bandersnatch_generate_new(AuthorityId::ID, Some("//Alice"))
+			.unwrap()
+			.into();
+		let bob: AuthorityId = keystore
+			.bandersnatch_generate_new(AuthorityId::ID, Some("//Bob"))
+			.unwrap()
+			.into();
+
+		let epoch = test_epoch(vec![alice.clone()]);
+		let (claim, author) = claim_slot(12.into(), &epoch, None, &keystore).unwrap();
+		assert_eq!(author, alice);
+
+		// Properly sealed by the slot author.
+		let header = make_header(&claim, &keystore, &alice);
+		match check(header.clone(), &claim, 12, &epoch) {
+			Ok(CheckedHeader::Checked(pre_header, info)) => {
+				assert_eq!(info.author, alice);
+				assert_eq!(pre_header.digest().logs().len(), 1);
+				assert_eq!(Some(&info.seal), header.digest().logs().last());
+			},
+			_ => panic!("Header should be valid"),
+		}
+
+		// Slots in the future are deferred.
+		assert!(matches!(
+			check(header, &claim, 11, &epoch),
+			Ok(CheckedHeader::Deferred(_, slot)) if slot == 12.into(),
+		));
+
+		// Sealed by someone else.
+		let header = make_header(&claim, &keystore, &bob);
+		assert!(matches!(check(header, &claim, 12, &epoch), Err(Error::BadSignature(_))));
+
+		// Not sealed at all.
+		let mut header = make_header(&claim, &keystore, &alice);
+		header.digest_mut().pop();
+		assert!(matches!(check(header, &claim, 12, &epoch), Err(Error::HeaderBadSeal(_))));
+
+		// The claim doesn't belong to the epoch authorities.
+		let other_epoch = test_epoch(vec![bob.clone()]);
+		let header = make_header(&claim, &keystore, &alice);
+		assert!(check(header, &claim, 12, &other_epoch).is_err());
+	}
+}
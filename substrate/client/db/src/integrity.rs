@@ -0,0 +1,932 @@
+// This file is part of Substrate.
+
+// Copyright (C) Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Database integrity checks.
+//!
+//! The database is read directly instead of through the backend, so that a database the backend
+//! refuses to open can be checked and repaired as well. The canonical chain is walked and missing
+//! or corrupt headers, bodies, justifications and state trie nodes are reported.
+//!
+//! Damaged blocks are repaired by downloading them from peers again:
+//!
+//! - Damaged finalized blocks are removed and recorded as a block gap, which the regular sync
+//!   downloads again on the next start, like the blocks skipped by warp sync.
+//! - Damaged blocks above the last finalized block are reverted together with all other blocks at
+//!   the same or a greater height, and imported again from peers.
+//!
+//! The state of finalized blocks can't be downloaded again, a database with damaged finalized
+//! state has to be synced from scratch.
+
+use crate::{
+	apply_state_commit, children, columns,
+	trie_diff::{diff_states, empty_root},
+	utils::{self, meta_keys, DatabaseType},
+	BlockchainDb, BlocksPruning, DatabaseSource, DbExtrinsic, DbHash, StateMetaDb,
+};
+use codec::{Decode, Encode};
+use log::{info, warn};
+use sc_client_api::{blockchain::Backend as _, leaves::LeafSet};
+use sp_blockchain::{Error as ClientError, HeaderBackend, Result as ClientResult};
+use sp_database::{Database, Transaction};
+use sp_runtime::{
+	generic::BlockId,
+	traits::{
+		Block as BlockT, Hash as HashT, HashingFor, Header as HeaderT, NumberFor, One,
+		SaturatedConversion, Zero,
+	},
+	Justifications, StateVersion,
+};
+use std::{
+	collections::{HashMap, HashSet},
+	fmt,
+	sync::Arc,
+};
+
+/// Verifies the justifications of a block, see [`check_database`].
+///
+/// Called with every canonical header in ascending order, starting at genesis, so that changes of
+/// the authority set can be followed. The justifications of blocks below the checked range are not
+/// read and passed as `None`.
+pub type JustificationVerifier<'a, Block> =
+	Box<dyn FnMut(&<Block as BlockT>::Header, Option<&Justifications>) -> Result<(), String> + 'a>;
+
+/// A problem found by [`check_database`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IntegrityIssue<B: BlockT> {
+	/// No canonical block is recorded at this height.
+	MissingCanonicalHash(NumberFor<B>),
+	/// The header of a canonical block is missing.
+	MissingHeader(NumberFor<B>, B::Hash),
+	/// The header does not hash to the hash it is stored under.
+	HeaderHashMismatch(NumberFor<B>, B::Hash),
+	/// The parent hash does not match the hash of the previous canonical block.
+	ParentHashMismatch(NumberFor<B>, B::Hash),
+	/// The body is missing although blocks pruning should have kept it.
+	MissingBody(NumberFor<B>, B::Hash),
+	/// The body does not match the extrinsics root of the header.
+	ExtrinsicsRootMismatch(NumberFor<B>, B::Hash),
+	/// The justifications were rejected by the [`JustificationVerifier`].
+	InvalidJustification(NumberFor<B>, B::Hash, String),
+	/// The state is missing although state pruning should have kept it.
+	MissingState(NumberFor<B>, B::Hash),
+	/// A database entry of the block could not be read or decoded.
+	Corrupt {
+		/// Number of the block.
+		number: NumberFor<B>,
+		/// Hash of the block.
+		hash: B::Hash,
+		/// Name of the damaged entry.
+		entry: &'static str,
+		/// Error returned when reading the entry.
+		error: String,
+	},
+}
+
+impl<B: BlockT> IntegrityIssue<B> {
+	/// Number of the damaged block.
+	pub fn number(&self) -> NumberFor<B> {
+		match self {
+			Self::MissingCanonicalHash(number) |
+			Self::MissingHeader(number, _) |
+			Self::HeaderHashMismatch(number, _) |
+			Self::ParentHashMismatch(number, _) |
+			Self::MissingBody(number, _) |
+			Self::ExtrinsicsRootMismatch(number, _) |
+			Self::InvalidJustification(number, _, _) |
+			Self::MissingState(number, _) |
+			Self::Corrupt { number, .. } => *number,
+		}
+	}
+
+	/// Whether the state of the block is damaged.
+	pub fn is_state(&self) -> bool {
+		matches!(self, Self::MissingState(..) | Self::Corrupt { entry: "state", .. })
+	}
+}
+
+impl<B: BlockT> fmt::Display for IntegrityIssue<B> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::MissingCanonicalHash(number) =>
+				write!(f, "#{number}: no canonical block recorded"),
+			Self::MissingHeader(number, hash) => write!(f, "#{number} ({hash}): header is missing"),
+			Self::HeaderHashMismatch(number, hash) =>
+				write!(f, "#{number} ({hash}): header does not match its hash"),
+			Self::ParentHashMismatch(number, hash) =>
+				write!(f, "#{number} ({hash}): parent hash does not match the previous block"),
+			Self::MissingBody(number, hash) => write!(f, "#{number} ({hash}): body is missing"),
+			Self::ExtrinsicsRootMismatch(number, hash) =>
+				write!(f, "#{number} ({hash}): body does not match the extrinsics root"),
+			Self::InvalidJustification(number, hash, error) =>
+				write!(f, "#{number} ({hash}): justification is invalid: {error}"),
+			Self::MissingState(number, hash) => write!(f, "#{number} ({hash}): state is missing"),
+			Self::Corrupt { number, hash, entry, error } =>
+				write!(f, "#{number} ({hash}): {entry} is corrupt: {error}"),
+		}
+	}
+}
+
+/// Result of [`check_database`].
+#[derive(Debug)]
+pub struct IntegrityReport<B: BlockT> {
+	/// Number of checked blocks.
+	pub blocks: u64,
+	/// Number of checked state trie nodes, including the nodes of child tries.
+	pub state_nodes: u64,
+	/// Number of the best block when the database was checked.
+	pub best_number: NumberFor<B>,
+	/// Number of the last finalized block when the database was checked.
+	pub finalized_number: NumberFor<B>,
+	/// Problems found, ordered by block number.
+	pub issues: Vec<IntegrityIssue<B>>,
+}
+
+impl<B: BlockT> IntegrityReport<B> {
+	/// Number of the first damaged block, if any.
+	pub fn first_damaged(&self) -> Option<NumberFor<B>> {
+		self.issues.iter().map(IntegrityIssue::number).min()
+	}
+
+	/// Number of the first damaged block above the last finalized block, if any.
+	///
+	/// This block and all blocks above it are reverted by [`repair_database`].
+	pub fn first_damaged_unfinalized(&self) -> Option<NumberFor<B>> {
+		self.issues
+			.iter()
+			.map(IntegrityIssue::number)
+			.filter(|number| *number > self.finalized_number)
+			.min()
+	}
+}
+
+/// Result of [`repair_database`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RepairSummary<N> {
+	/// Finalized blocks that were removed to be downloaded again, recorded as block gap.
+	pub gap: Option<(N, N)>,
+	/// Number of removed blocks above the last finalized block.
+	pub reverted: u64,
+}
+
+/// Check the canonical blocks `from..=to` of the database at `source`.
+///
+/// `to` defaults to the best block. Headers, bodies and justifications of every block are read and
+/// checked against each other, and the justifications are passed to `verify_justifications`.
+/// Bodies are only required to exist where `blocks_pruning` keeps them. If `check_state` is set,
+/// the whole state trie of every block whose state is kept is traversed as well, which can take a
+/// long time on archive nodes. Blocks in the gap left by warp sync are skipped.
+pub fn check_database<Block: BlockT>(
+	source: &DatabaseSource,
+	blocks_pruning: BlocksPruning,
+	from: NumberFor<Block>,
+	to: Option<NumberFor<Block>>,
+	check_state: bool,
+	verify_justifications: Option<JustificationVerifier<Block>>,
+) -> ClientResult<IntegrityReport<Block>> {
+	let db = utils::open_database::<Block>(source, DatabaseType::Full, false)?;
+	check(db, blocks_pruning, from, to, check_state, verify_justifications)
+}
+
+/// Repair the damaged blocks of `report`, created by [`check_database`] for the database at
+/// `source`.
+///
+/// Damaged finalized blocks are removed and recorded as a block gap, so that they are downloaded
+/// from peers again on the next start. Damaged blocks above the last finalized block are reverted
+/// together with all other blocks at the same or a greater height. Consensus data stored by the
+/// node for the reverted blocks has to be reverted before, see
+/// [`IntegrityReport::first_damaged_unfinalized`]. Fails without changing the database if the
+/// last finalized block or the state of a finalized block is damaged.
+pub fn repair_database<Block: BlockT>(
+	source: &DatabaseSource,
+	report: &IntegrityReport<Block>,
+) -> ClientResult<RepairSummary<NumberFor<Block>>> {
+	let db = utils::open_database::<Block>(source, DatabaseType::Full, false)?;
+	repair(db, report)
+}
+
+fn check<Block: BlockT>(
+	db: Arc<dyn Database<DbHash>>,
+	blocks_pruning: BlocksPruning,
+	from: NumberFor<Block>,
+	to: Option<NumberFor<Block>>,
+	check_state: bool,
+	mut verify_justifications: Option<JustificationVerifier<Block>>,
+) -> ClientResult<IntegrityReport<Block>> {
+	let blockchain = BlockchainDb::<Block>::new(db.clone())?;
+	let state = StateReader::new::<Block>(db.clone())?;
+	// The headers of the best and finalized blocks may be damaged, their numbers are read from
+	// the lookup keys.
+	let best_number =
+		meta_block_number::<Block>(&*db, meta_keys::BEST_BLOCK).unwrap_or_else(Zero::zero);
+	let finalized_number =
+		meta_block_number::<Block>(&*db, meta_keys::FINALIZED_BLOCK).unwrap_or_else(Zero::zero);
+	let block_gap = blockchain.meta.read().block_gap;
+	let last_pruned = sc_state_db::fetch_last_pruned(&StateMetaDb(db.clone()))
+		.map_err(ClientError::from_state_db)?;
+
+	let to = to.map_or(best_number, |to| to.min(best_number));
+	let bodies_from = match blocks_pruning {
+		BlocksPruning::KeepAll | BlocksPruning::KeepFinalized => Zero::zero(),
+		BlocksPruning::Some(n) => finalized_number.saturating_sub(n.into()),
+	};
+
+	let mut report = IntegrityReport {
+		blocks: 0,
+		state_nodes: 0,
+		best_number,
+		finalized_number,
+		issues: Vec::new(),
+	};
+	let mut parent_hash = None;
+	// The verifier follows the headers from genesis.
+	let mut number = if verify_justifications.is_some() { Zero::zero() } else { from };
+	while number <= to {
+		if let Some((gap_start, gap_end)) = block_gap {
+			if number >= gap_start && number <= gap_end {
+				info!("Skipping blocks #{}..#{} missing after warp sync", gap_start, gap_end);
+				if verify_justifications.take().is_some() {
+					warn!("Justifications after the gap can't be verified");
+				}
+				number = (gap_end + One::one()).max(from);
+				parent_hash = None;
+				continue
+			}
+		}
+
+		if number < from {
+			let header = canonical_hash::<Block>(&*db, number)
+				.ok()
+				.flatten()
+				.and_then(|hash| blockchain.header(hash).ok().flatten());
+			match (header, verify_justifications.as_mut()) {
+				(Some(header), Some(verify)) =>
+					if let Err(e) = verify(&header, None) {
+						warn!("Failed to follow header #{}: {}", number, e);
+					},
+				_ => {
+					warn!("Header #{} can't be read, justifications can't be verified", number);
+					verify_justifications = None;
+					number = from;
+					continue
+				},
+			}
+			number += One::one();
+			continue
+		}
+
+		let issues_before = report.issues.len();
+		let header = check_block_data(
+			&blockchain,
+			number,
+			parent_hash,
+			bodies_from,
+			verify_justifications.as_mut(),
+			&mut report.issues,
+		);
+
+		if let Some(header) = &header {
+			let hash = header.hash();
+			let root = DbHash::from_slice(header.state_root().as_ref());
+			let pruned = last_pruned
+				.map_or(false, |last_pruned| number.saturated_into::<u64>() <= last_pruned);
+			if !pruned && state.has_root::<Block>(&root) {
+				if check_state {
+					match state.count_nodes::<Block>(root) {
+						Ok(nodes) => report.state_nodes += nodes,
+						Err(e) => report.issues.push(IntegrityIssue::Corrupt {
+							number,
+							hash,
+							entry: "state",
+							error: e.to_string(),
+						}),
+					}
+				}
+			} else if number >= finalized_number || !pruned && last_pruned.is_some() {
+				// The state of the finalized block, of all blocks above it and of the blocks in the
+				// pruning window is kept. Blocks imported by fast sync have no state.
+				report.issues.push(IntegrityIssue::MissingState(number, hash));
+			}
+		}
+
+		for issue in &report.issues[issues_before..] {
+			warn!("{}", issue);
+		}
+		report.blocks += 1;
+		if report.blocks % 10_000 == 0 {
+			info!("Checked {} blocks, now at #{}", report.blocks, number);
+		}
+		parent_hash = header.map(|header| header.hash());
+		number += One::one();
+	}
+
+	Ok(report)
+}
+
+/// Check header, body and justifications of the canonical block at `number`.
+///
+/// Returns the header of the block if it is intact.
+fn check_block_data<Block: BlockT>(
+	blockchain: &BlockchainDb<Block>,
+	number: NumberFor<Block>,
+	parent_hash: Option<Block::Hash>,
+	bodies_from: NumberFor<Block>,
+	verify_justifications: Option<&mut JustificationVerifier<Block>>,
+	issues: &mut Vec<IntegrityIssue<Block>>,
+) -> Option<Block::Header> {
+	let corrupt = |hash: Block::Hash, entry: &'static str, error: ClientError| {
+		IntegrityIssue::Corrupt { number, hash, entry, error: error.to_string() }
+	};
+
+	let hash = match canonical_hash::<Block>(&*blockchain.db, number) {
+		Ok(Some(hash)) => hash,
+		Ok(None) => {
+			issues.push(IntegrityIssue::MissingCanonicalHash(number));
+			return None
+		},
+		Err(e) => {
+			issues.push(corrupt(Default::default(), "canonical hash", e));
+			return None
+		},
+	};
+	let header = match blockchain.header(hash) {
+		Ok(Some(header)) => header,
+		Ok(None) => {
+			issues.push(IntegrityIssue::MissingHeader(number, hash));
+			return None
+		},
+		Err(e) => {
+			issues.push(corrupt(hash, "header", e));
+			return None
+		},
+	};
+	if header.hash() != hash || *header.number() != number {
+		issues.push(IntegrityIssue::HeaderHashMismatch(number, hash));
+		return None
+	}
+	if parent_hash.map_or(false, |parent_hash| parent_hash != *header.parent_hash()) {
+		issues.push(IntegrityIssue::ParentHashMismatch(number, hash));
+	}
+
+	match blockchain.body(hash) {
+		Ok(Some(body)) => {
+			let extrinsics_root = HashingFor::<Block>::ordered_trie_root(
+				body.iter().map(Encode::encode).collect(),
+				StateVersion::V0,
+			);
+			if extrinsics_root != *header.extrinsics_root() {
+				issues.push(IntegrityIssue::ExtrinsicsRootMismatch(number, hash));
+			}
+		},
+		Ok(None) if number >= bodies_from => issues.push(IntegrityIssue::MissingBody(number, hash)),
+		Ok(None) => {},
+		Err(e) => issues.push(corrupt(hash, "body", e)),
+	}
+	match blockchain.justifications(hash) {
+		Ok(justifications) =>
+			if let Some(verify) = verify_justifications {
+				if let Err(error) = verify(&header, justifications.as_ref()) {
+					issues.push(IntegrityIssue::InvalidJustification(number, hash, error));
+				}
+			},
+		Err(e) => issues.push(corrupt(hash, "justifications", e)),
+	}
+
+	Some(header)
+}
+
+fn repair<Block: BlockT>(
+	db: Arc<dyn Database<DbHash>>,
+	report: &IntegrityReport<Block>,
+) -> ClientResult<RepairSummary<NumberFor<Block>>> {
+	let mut summary = RepairSummary { gap: None, reverted: 0 };
+	let Some(first_damaged) = report.first_damaged() else {
+		info!("Nothing to repair.");
+		return Ok(summary)
+	};
+	if first_damaged.is_zero() {
+		return Err(ClientError::Backend(
+			"The genesis block is damaged and cannot be repaired".into(),
+		))
+	}
+	let finalized_number = report.finalized_number;
+	if let Some(issue) = report.issues.iter().find(|issue| {
+		issue.number() == finalized_number ||
+			(issue.number() < finalized_number && issue.is_state())
+	}) {
+		return Err(ClientError::Backend(format!(
+			"Can't repair {}: the last finalized block and the state of finalized blocks can't be \
+			downloaded again, the database has to be synced from scratch",
+			issue,
+		)))
+	}
+
+	let mut transaction = Transaction::new();
+	if let Some(first) = report.first_damaged_unfinalized() {
+		summary.reverted = revert_from(&mut transaction, &db, first, report.best_number)?;
+		info!("Reverting {} blocks from #{}", summary.reverted, first);
+	}
+
+	let damaged = report
+		.issues
+		.iter()
+		.map(IntegrityIssue::number)
+		.filter(|number| *number < finalized_number);
+	if let (Some(start), Some(end)) = (damaged.clone().min(), damaged.max()) {
+		// There is only a single gap, an existing one is extended.
+		let (start, end) = match utils::read_meta::<Block>(&*db, columns::HEADER)?.block_gap {
+			Some((gap_start, gap_end)) => (start.min(gap_start), end.max(gap_end)),
+			None => (start, end),
+		};
+		let mut number = start;
+		while number <= end {
+			if let Ok(Some(hash)) = canonical_hash::<Block>(&*db, number) {
+				remove_block::<Block>(&mut transaction, &*db, number, hash)?;
+			}
+			utils::remove_number_to_key_mapping(&mut transaction, columns::KEY_LOOKUP, number)?;
+			number += One::one();
+		}
+		transaction.set_from_vec(columns::META, meta_keys::BLOCK_GAP, (start, end).encode());
+		info!("Blocks #{}..#{} will be downloaded again on the next start", start, end);
+		summary.gap = Some((start, end));
+	}
+
+	db.commit(transaction)?;
+	Ok(summary)
+}
+
+/// Remove all blocks at the height `first` and above, which must not be finalized.
+///
+/// The blocks are found through the canonical chain, the children of the canonical parent of
+/// `first`, the parents of the leaves and the journals of the state database, so that blocks with
+/// a damaged header are found as well. Their state is discarded by the state database, like
+/// `Backend::revert` does. Returns the number of removed blocks.
+fn revert_from<Block: BlockT>(
+	transaction: &mut Transaction<DbHash>,
+	db: &Arc<dyn Database<DbHash>>,
+	first: NumberFor<Block>,
+	best_number: NumberFor<Block>,
+) -> ClientResult<u64> {
+	let parent_number = first - One::one();
+	let parent_hash = canonical_hash::<Block>(&**db, parent_number)?.ok_or_else(|| {
+		ClientError::Backend(format!("No canonical block recorded at #{}", parent_number))
+	})?;
+	let mut removed = HashMap::new();
+	// Blocks below `first` that have removed children.
+	let mut parents = HashSet::from([parent_hash]);
+
+	let mut number = first;
+	while number <= best_number {
+		if let Ok(Some(hash)) = canonical_hash::<Block>(&**db, number) {
+			removed.insert(hash, number);
+		}
+		utils::remove_number_to_key_mapping(transaction, columns::KEY_LOOKUP, number)?;
+		number += One::one();
+	}
+
+	let mut queue = removed.iter().map(|(hash, number)| (*hash, *number)).collect::<Vec<_>>();
+	queue.push((parent_hash, parent_number));
+	while let Some((hash, number)) = queue.pop() {
+		let children: Vec<Block::Hash> =
+			children::read_children(&**db, columns::META, meta_keys::CHILDREN_PREFIX, hash)?;
+		for child in children {
+			if removed.insert(child, number + One::one()).is_none() {
+				queue.push((child, number + One::one()));
+			}
+		}
+	}
+
+	let mut leaves = LeafSet::<Block::Hash, NumberFor<Block>>::read_from_db(
+		&**db,
+		columns::META,
+		meta_keys::LEAF_PREFIX,
+	)?;
+	for mut hash in leaves.hashes() {
+		while let Ok(Some(header)) = utils::read_header::<Block>(
+			&**db,
+			columns::KEY_LOOKUP,
+			columns::HEADER,
+			BlockId::Hash(hash),
+		) {
+			if *header.number() < first {
+				break
+			}
+			removed.insert(hash, *header.number());
+			if *header.number() == first {
+				parents.insert(*header.parent_hash());
+			}
+			hash = *header.parent_hash();
+		}
+	}
+
+	// Journals are ordered by height, the parents of a block are found before the block.
+	let journals = sc_state_db::fetch_non_canonical_journals::<Block::Hash, Vec<u8>, _>(
+		&StateMetaDb(db.clone()),
+	)
+	.map_err(ClientError::from_state_db)?;
+	let mut journaled = HashSet::new();
+	for (_, record) in journals {
+		if let Some(number) = removed.get(&record.parent_hash).copied() {
+			removed.entry(record.hash).or_insert(number + One::one());
+		}
+		journaled.insert(record.hash);
+	}
+
+	let (_, state_db) = sc_state_db::StateDb::<Block::Hash, Vec<u8>, _>::open(
+		StateMetaDb(db.clone()),
+		None,
+		!db.supports_ref_counting(),
+		false,
+	)
+	.map_err(ClientError::from_state_db)?;
+	// The state database only removes blocks without children, so the highest blocks go first.
+	let mut by_height = removed.iter().map(|(hash, number)| (*number, *hash)).collect::<Vec<_>>();
+	by_height.sort_by(|a, b| b.0.cmp(&a.0));
+	for (number, hash) in by_height {
+		if let Some(commit) = state_db.remove(&hash) {
+			apply_state_commit(transaction, commit);
+		} else if journaled.contains(&hash) {
+			return Err(ClientError::Backend(format!(
+				"Failed to discard the state of block #{} ({})",
+				number, hash,
+			)))
+		}
+	}
+
+	for (hash, number) in &removed {
+		remove_block::<Block>(transaction, &**db, *number, *hash)?;
+		children::remove_children(transaction, columns::META, meta_keys::CHILDREN_PREFIX, *hash);
+	}
+	for parent in parents {
+		children::remove_children(transaction, columns::META, meta_keys::CHILDREN_PREFIX, parent);
+	}
+	leaves.revert(parent_hash, parent_number);
+	leaves.prepare_transaction(transaction, columns::META, meta_keys::LEAF_PREFIX);
+	transaction.set_from_vec(
+		columns::META,
+		meta_keys::BEST_BLOCK,
+		utils::number_and_hash_to_lookup_key(parent_number, parent_hash)?,
+	);
+	Ok(removed.len() as u64)
+}
+
+/// Remove the header, body and justifications of a block and its hash lookup.
+fn remove_block<Block: BlockT>(
+	transaction: &mut Transaction<DbHash>,
+	db: &dyn Database<DbHash>,
+	number: NumberFor<Block>,
+	hash: Block::Hash,
+) -> ClientResult<()> {
+	let lookup_key = utils::number_and_hash_to_lookup_key(number, hash)?;
+	for column in [columns::HEADER, columns::BODY, columns::JUSTIFICATIONS] {
+		transaction.remove(column, &lookup_key);
+	}
+	if let Some(index) = db.get(columns::BODY_INDEX, &lookup_key) {
+		transaction.remove(columns::BODY_INDEX, &lookup_key);
+		// The indexed transactions of a damaged index are not known and can't be released.
+		if let Ok(index) = Vec::<DbExtrinsic<Block>>::decode(&mut &index[..]) {
+			for extrinsic in index {
+				if let DbExtrinsic::Indexed { hash, .. } = extrinsic {
+					transaction.release(columns::TRANSACTION, hash);
+				}
+			}
+		}
+	}
+	transaction.remove(columns::KEY_LOOKUP, hash.as_ref());
+	Ok(())
+}
+
+/// Hash of the canonical block at `number`, read from its lookup key.
+fn canonical_hash<Block: BlockT>(
+	db: &dyn Database<DbHash>,
+	number: NumberFor<Block>,
+) -> ClientResult<Option<Block::Hash>> {
+	let Some(lookup_key) = db.get(columns::KEY_LOOKUP, &utils::number_index_key(number)?) else {
+		return Ok(None)
+	};
+	match lookup_key.get(4..).and_then(|hash| Block::Hash::decode(&mut &hash[..]).ok()) {
+		Some(hash) => Ok(Some(hash)),
+		None => Err(ClientError::Backend(format!("Invalid lookup key of block #{}", number))),
+	}
+}
+
+/// Number of the block stored under the meta `key`, read from its lookup key.
+fn meta_block_number<Block: BlockT>(
+	db: &dyn Database<DbHash>,
+	key: &[u8],
+) -> Option<NumberFor<Block>> {
+	let lookup_key = db.get(columns::META, key)?;
+	let number = u32::from_be_bytes(lookup_key.get(..4)?.try_into().ok()?);
+	Some(number.into())
+}
+
+/// Reads trie nodes from the database, including the nodes of non-canonical blocks that are only
+/// kept in the journals of the state database.
+struct StateReader {
+	db: Arc<dyn Database<DbHash>>,
+	prefix_keys: bool,
+	journals: HashMap<Vec<u8>, Vec<u8>>,
+}
+
+impl StateReader {
+	fn new<Block: BlockT>(db: Arc<dyn Database<DbHash>>) -> ClientResult<Self> {
+		let journals = sc_state_db::fetch_non_canonical_journals::<Block::Hash, Vec<u8>, _>(
+			&StateMetaDb(db.clone()),
+		)
+		.map_err(ClientError::from_state_db)?
+		.into_iter()
+		.flat_map(|(_, record)| record.inserted)
+		// Additional references are journaled without a value.
+		.filter(|(_, value)| !value.is_empty())
+		.collect();
+		Ok(Self { prefix_keys: !db.supports_ref_counting(), db, journals })
+	}
+
+	fn get(&self, key: &[u8], hash: &DbHash) -> Option<Vec<u8>> {
+		let key = if self.prefix_keys { key } else { hash.as_ref() };
+		self.journals.get(key).cloned().or_else(|| self.db.get(columns::STATE, key))
+	}
+
+	fn has_root<Block: BlockT>(&self, root: &DbHash) -> bool {
+		*root == empty_root::<HashingFor<Block>>() || self.get(root.as_ref(), root).is_some()
+	}
+
+	/// Visit every node of the state with `root`, returns the number of nodes.
+	fn count_nodes<Block: BlockT>(&self, root: DbHash) -> ClientResult<u64> {
+		let mut nodes = 0;
+		diff_states::<HashingFor<Block>>(
+			None,
+			Some(root),
+			&|key, hash| Ok(self.get(key, hash)),
+			&mut |_| {
+				nodes += 1;
+				Ok(())
+			},
+		)?;
+		Ok(nodes)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		tests::{insert_block, Block},
+		Backend, DatabaseSettings, PruningMode,
+	};
+	use sc_client_api::backend::{Backend as _, BlockImportOperation as _, NewBlockState};
+	use sp_core::H256;
+	use sp_runtime::{
+		testing::{ExtrinsicWrapper, Header},
+		traits::BlakeTwo256,
+		ConsensusEngineId,
+	};
+
+	const ENGINE_ID: ConsensusEngineId = *b"TEST";
+
+	fn body(number: u64) -> Vec<ExtrinsicWrapper<u64>> {
+		vec![ExtrinsicWrapper::from(number)]
+	}
+
+	fn import_block(backend: &Backend<Block>, number: u64, parent_hash: H256) -> H256 {
+		let body = body(number);
+		let extrinsics_root = BlakeTwo256::ordered_trie_root(
+			body.iter().map(Encode::encode).collect(),
+			StateVersion::V0,
+		);
+		insert_block(backend, number, parent_hash, None, extrinsics_root, body, None).unwrap()
+	}
+
+	/// Import the blocks `0..=6` and finalize `1..=4`, with a justification each.
+	fn import_chain(backend: &Backend<Block>) -> Vec<H256> {
+		let mut hashes = Vec::new();
+		for number in 0..=6 {
+			let parent_hash = hashes.last().copied().unwrap_or_default();
+			hashes.push(import_block(backend, number, parent_hash));
+		}
+		for number in 1..=4u64 {
+			backend
+				.finalize_block(hashes[number as usize], Some((ENGINE_ID, number.encode())))
+				.unwrap();
+		}
+		hashes
+	}
+
+	fn reopen(db: Arc<dyn Database<DbHash>>, blocks_pruning: BlocksPruning) -> Backend<Block> {
+		let state_pruning = match blocks_pruning {
+			BlocksPruning::KeepAll => PruningMode::ArchiveAll,
+			BlocksPruning::KeepFinalized => PruningMode::ArchiveCanonical,
+			BlocksPruning::Some(n) => PruningMode::blocks_pruning(n),
+		};
+		let settings = DatabaseSettings {
+			trie_cache_maximum_size: None,
+			state_pruning: Some(state_pruning),
+			source: DatabaseSource::Custom { db, require_create_flag: false },
+			blocks_pruning,
+		};
+		Backend::new(settings, 0).unwrap()
+	}
+
+	fn lookup_key(hashes: &[H256], number: usize) -> Vec<u8> {
+		utils::number_and_hash_to_lookup_key(number as u64, hashes[number]).unwrap()
+	}
+
+	/// Accepts the justifications that encode the number of their block.
+	fn verifier(headers: &mut Vec<u64>) -> JustificationVerifier<Block> {
+		Box::new(move |header: &Header, justifications: Option<&Justifications>| {
+			headers.push(header.number);
+			match justifications.and_then(|justifications| justifications.get(ENGINE_ID)) {
+				Some(justification) if *justification != header.number.encode() =>
+					Err("wrong block".into()),
+				_ => Ok(()),
+			}
+		})
+	}
+
+	#[test]
+	fn repairs_damaged_blocks() {
+		let backend = Backend::<Block>::new_test_with_tx_storage(BlocksPruning::KeepAll, 0);
+		let hashes = import_chain(&backend);
+		let db = backend.storage.db.clone();
+		let check_db = |db: &Arc<dyn Database<DbHash>>| {
+			check::<Block>(db.clone(), BlocksPruning::KeepAll, 0, None, true, None).unwrap()
+		};
+
+		let report = check_db(&db);
+		assert_eq!(report.blocks, 7);
+		assert!(report.state_nodes > 0);
+		assert_eq!(report.issues, vec![]);
+
+		// Remove the body of #2, corrupt the header of #3 and remove the state root of #5.
+		let headers = hashes
+			.iter()
+			.map(|hash| backend.blockchain().header(*hash).unwrap().unwrap())
+			.collect::<Vec<_>>();
+		let mut transaction = Transaction::new();
+		transaction.remove(columns::BODY, &lookup_key(&hashes, 2));
+		transaction.set(columns::HEADER, &lookup_key(&hashes, 3), b"corrupt");
+		transaction.remove(columns::STATE, headers[5].state_root.as_ref());
+		db.commit(transaction).unwrap();
+		drop(backend);
+
+		let report = check_db(&db);
+		assert_eq!(report.issues.len(), 3);
+		assert_eq!(report.issues[0], IntegrityIssue::MissingBody(2, hashes[2]));
+		assert!(matches!(
+			report.issues[1],
+			IntegrityIssue::Corrupt { number: 3, entry: "header", .. }
+		));
+		assert_eq!(report.issues[2], IntegrityIssue::MissingState(5, hashes[5]));
+		assert_eq!(report.first_damaged_unfinalized(), Some(5));
+
+		let summary = repair::<Block>(db.clone(), &report).unwrap();
+		assert_eq!(summary, RepairSummary { gap: Some((2, 3)), reverted: 2 });
+
+		// The damaged finalized blocks are left to the gap sync, the blocks above are gone.
+		let backend = reopen(db.clone(), BlocksPruning::KeepAll);
+		let info = backend.blockchain().info();
+		assert_eq!(info.best_hash, hashes[4]);
+		assert_eq!(info.finalized_hash, hashes[4]);
+		assert_eq!(info.block_gap, Some((2, 3)));
+		assert_eq!(check_db(&db).issues, vec![]);
+
+		// Import the blocks again, as if downloaded from peers.
+		for number in 2..=3 {
+			let mut op = backend.begin_operation().unwrap();
+			op.set_block_data(
+				headers[number].clone(),
+				Some(body(number as u64)),
+				None,
+				None,
+				NewBlockState::Normal,
+			)
+			.unwrap();
+			backend.commit_operation(op).unwrap();
+		}
+		for number in 5..=6 {
+			assert_eq!(import_block(&backend, number as u64, hashes[number - 1]), hashes[number]);
+		}
+		let info = backend.blockchain().info();
+		assert_eq!((info.best_hash, info.block_gap), (hashes[6], None));
+		let report = check_db(&db);
+		assert_eq!((report.blocks, report.issues), (7, vec![]));
+	}
+
+	#[test]
+	fn reverts_damaged_unfinalized_blocks() {
+		let backend = Backend::<Block>::new_test(10, 10);
+		let hashes = import_chain(&backend);
+		let db = backend.storage.db.clone();
+		let check_db = |db: &Arc<dyn Database<DbHash>>| {
+			check::<Block>(db.clone(), BlocksPruning::Some(10), 0, None, true, None).unwrap()
+		};
+		let journals = |db: &Arc<dyn Database<DbHash>>| {
+			sc_state_db::fetch_non_canonical_journals::<H256, Vec<u8>, _>(&StateMetaDb(db.clone()))
+				.unwrap()
+				.len()
+		};
+
+		// The state of the unfinalized blocks is only kept in the journals.
+		assert_eq!(journals(&db), 2);
+		assert_eq!(check_db(&db).issues, vec![]);
+
+		let mut transaction = Transaction::new();
+		transaction.set(columns::HEADER, &lookup_key(&hashes, 5), b"corrupt");
+		db.commit(transaction).unwrap();
+		drop(backend);
+
+		let report = check_db(&db);
+		assert!(matches!(
+			report.issues[..],
+			[IntegrityIssue::Corrupt { number: 5, entry: "header", .. }]
+		));
+		let summary = repair::<Block>(db.clone(), &report).unwrap();
+		assert_eq!(summary, RepairSummary { gap: None, reverted: 2 });
+		assert_eq!(journals(&db), 0);
+
+		let backend = reopen(db.clone(), BlocksPruning::Some(10));
+		assert_eq!(backend.blockchain().info().best_hash, hashes[4]);
+		for number in 5..=6 {
+			assert_eq!(import_block(&backend, number as u64, hashes[number - 1]), hashes[number]);
+		}
+		assert_eq!(check_db(&db).issues, vec![]);
+	}
+
+	#[test]
+	fn verifies_justifications() {
+		let backend = Backend::<Block>::new_test_with_tx_storage(BlocksPruning::KeepAll, 0);
+		let hashes = import_chain(&backend);
+		let db = backend.storage.db.clone();
+
+		// The verifier follows the headers from genesis, even if only the last blocks are checked.
+		let mut headers = Vec::new();
+		let report = check::<Block>(
+			db.clone(),
+			BlocksPruning::KeepAll,
+			3,
+			None,
+			false,
+			Some(verifier(&mut headers)),
+		)
+		.unwrap();
+		assert_eq!((report.blocks, report.issues), (4, vec![]));
+		assert_eq!(headers, (0..=6).collect::<Vec<_>>());
+
+		let mut transaction = Transaction::new();
+		transaction.set_from_vec(
+			columns::JUSTIFICATIONS,
+			&lookup_key(&hashes, 2),
+			Justifications::from((ENGINE_ID, 3u64.encode())).encode(),
+		);
+		db.commit(transaction).unwrap();
+
+		let mut headers = Vec::new();
+		let report = check::<Block>(
+			db.clone(),
+			BlocksPruning::KeepAll,
+			0,
+			None,
+			false,
+			Some(verifier(&mut headers)),
+		)
+		.unwrap();
+		assert_eq!(
+			report.issues,
+			vec![IntegrityIssue::InvalidJustification(2, hashes[2], "wrong block".into())]
+		);
+		let summary = repair::<Block>(db, &report).unwrap();
+		assert_eq!(summary, RepairSummary { gap: Some((2, 2)), reverted: 0 });
+	}
+
+	#[test]
+	fn finalized_state_is_not_repaired() {
+		let backend = Backend::<Block>::new_test_with_tx_storage(BlocksPruning::KeepAll, 0);
+		let hashes = import_chain(&backend);
+		let db = backend.storage.db.clone();
+
+		let header = backend.blockchain().header(hashes[4]).unwrap().unwrap();
+		let mut transaction = Transaction::new();
+		transaction.remove(columns::STATE, header.state_root.as_ref());
+		db.commit(transaction).unwrap();
+
+		let report =
+			check::<Block>(db.clone(), BlocksPruning::KeepAll, 0, None, true, None).unwrap();
+		assert_eq!(report.issues, vec![IntegrityIssue::MissingState(4, hashes[4])]);
+		assert!(repair::<Block>(db.clone(), &report).is_err());
+		assert_eq!(meta_block_number::<Block>(&*db, meta_keys::BEST_BLOCK), Some(6));
+	}
+}
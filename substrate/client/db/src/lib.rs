@@ -33,10 +33,13 @@ pub mod offchain;
 pub mod bench;
 
 mod children;
+mod integrity;
+mod migration;
 mod parity_db;
 mod pinned_blocks_cache;
 mod record_stats_state;
 mod stats;
+mod trie_diff;
 #[cfg(any(feature = "rocksdb", test))]
 mod upgrade;
 mod utils;
@@ -65,13 +68,14 @@ use sc_client_api::{
 	utils::is_descendent_of,
 	IoInfo, MemoryInfo, MemorySize, UsageInfo,
 };
-use sc_state_db::{IsPruned, LastCanonicalized, StateDb};
+use sc_state_db::{IsPruned, LastCanonicalized, RetentionRule, StateDb};
 use sp_arithmetic::traits::Saturating;
 use sp_blockchain::{
 	Backend as _, CachedHeaderMetadata, Error as ClientError, HeaderBackend, HeaderMetadata,
 	HeaderMetadataCache, Result as ClientResult,
 };
 use sp_core::{
+	hexdisplay::HexDisplay,
 	offchain::OffchainOverlayedChange,
 	storage::{well_known_keys, ChildInfo},
 };
@@ -93,10 +97,15 @@ use sp_state_machine::{
 use sp_trie::{cache::SharedTrieCache, prefixed_key, MemoryDB, MerkleValue, PrefixedMemoryDB};
 
 // Re-export the Database trait so that one can pass an implementation of it.
-pub use sc_state_db::PruningMode;
+pub use sc_state_db::{PruningMode, RetentionRule};
 pub use sp_database::Database;
 
 pub use bench::BenchmarkingState;
+pub use integrity::{
+	check_database, repair_database, IntegrityIssue, IntegrityReport, JustificationVerifier,
+	RepairSummary,
+};
+pub use migration::{migrate_database, MigrationSummary};
 
 const CACHE_HEADERS: usize = 8;
 
@@ -138,11 +147,34 @@ pub struct RefTrackingState<Block: BlockT> {
 	state: DbState<Block>,
 	storage: Arc<StorageDb<Block>>,
 	parent_hash: Option<Block::Hash>,
+	/// Retention rules of a state that is pruned except for the retained storage.
+	retain: Option<Vec<RetentionRule>>,
 }
 
 impl<B: BlockT> RefTrackingState<B> {
 	fn new(state: DbState<B>, storage: Arc<StorageDb<B>>, parent_hash: Option<B::Hash>) -> Self {
-		RefTrackingState { state, parent_hash, storage }
+		RefTrackingState { state, parent_hash, storage, retain: None }
+	}
+
+	/// Restrict access to the storage retained by the given rules.
+	fn with_retention(mut self, retain: Vec<RetentionRule>) -> Self {
+		self.retain = Some(retain);
+		self
+	}
+
+	/// Check that `key` has not been pruned from the state.
+	fn check_retained(&self, child_info: Option<&ChildInfo>, key: &[u8]) -> Result<(), String> {
+		let Some(retain) = &self.retain else { return Ok(()) };
+		let child = child_info.map(|child_info| child_info.storage_key());
+		if retain.iter().any(|rule| rule.retains_key(child, key)) {
+			Ok(())
+		} else {
+			Err(format!(
+				"State of key 0x{} is pruned at block {:?}, only retained storage is available",
+				HexDisplay::from(&key),
+				self.parent_hash,
+			))
+		}
 	}
 }
 
@@ -191,10 +223,12 @@ impl<B: BlockT> StateBackend<HashingFor<B>> for RefTrackingState<B> {
 	type RawIter = RawIter<B>;
 
 	fn storage(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+		self.check_retained(None, key)?;
 		self.state.storage(key)
 	}
 
 	fn storage_hash(&self, key: &[u8]) -> Result<Option<B::Hash>, Self::Error> {
+		self.check_retained(None, key)?;
 		self.state.storage_hash(key)
 	}
 
@@ -203,6 +237,7 @@ impl<B: BlockT> StateBackend<HashingFor<B>> for RefTrackingState<B> {
 		child_info: &ChildInfo,
 		key: &[u8],
 	) -> Result<Option<Vec<u8>>, Self::Error> {
+		self.check_retained(Some(child_info), key)?;
 		self.state.child_storage(child_info, key)
 	}
 
@@ -211,6 +246,7 @@ impl<B: BlockT> StateBackend<HashingFor<B>> for RefTrackingState<B> {
 		child_info: &ChildInfo,
 		key: &[u8],
 	) -> Result<Option<B::Hash>, Self::Error> {
+		self.check_retained(Some(child_info), key)?;
 		self.state.child_storage_hash(child_info, key)
 	}
 
@@ -218,6 +254,7 @@ impl<B: BlockT> StateBackend<HashingFor<B>> for RefTrackingState<B> {
 		&self,
 		key: &[u8],
 	) -> Result<Option<MerkleValue<B::Hash>>, Self::Error> {
+		self.check_retained(None, key)?;
 		self.state.closest_merkle_value(key)
 	}
 
@@ -226,10 +263,12 @@ impl<B: BlockT> StateBackend<HashingFor<B>> for RefTrackingState<B> {
 		child_info: &ChildInfo,
 		key: &[u8],
 	) -> Result<Option<MerkleValue<B::Hash>>, Self::Error> {
+		self.check_retained(Some(child_info), key)?;
 		self.state.child_closest_merkle_value(child_info, key)
 	}
 
 	fn exists_storage(&self, key: &[u8]) -> Result<bool, Self::Error> {
+		self.check_retained(None, key)?;
 		self.state.exists_storage(key)
 	}
 
@@ -238,6 +277,7 @@ impl<B: BlockT> StateBackend<HashingFor<B>> for RefTrackingState<B> {
 		child_info: &ChildInfo,
 		key: &[u8],
 	) -> Result<bool, Self::Error> {
+		self.check_retained(Some(child_info), key)?;
 		self.state.exists_child_storage(child_info, key)
 	}
 
@@ -271,6 +311,8 @@ impl<B: BlockT> StateBackend<HashingFor<B>> for RefTrackingState<B> {
 	}
 
 	fn raw_iter(&self, args: IterArgs) -> Result<Self::RawIter, Self::Error> {
+		// Iteration must stay within the retained storage.
+		self.check_retained(args.child_info.as_ref(), args.prefix.unwrap_or_default())?;
 		self.state.raw_iter(args).map(|inner| RawIter { inner })
 	}
 
@@ -1537,7 +1579,16 @@ impl<Block: BlockT> Backend<Block> {
 				let mut bytes: u64 = 0;
 				let mut removal: u64 = 0;
 				let mut bytes_removal: u64 = 0;
+				// Databases counting references store nodes without their trie path, so retained
+				// nodes are kept by never releasing them while the path is still known.
+				let retaining_mode = Some(self.storage.state_db.pruning_mode())
+					.filter(|mode| !self.storage.prefix_keys && !mode.retention_rules().is_empty());
 				for (mut key, (val, rc)) in operation.db_updates.drain() {
+					if rc < 0 &&
+						retaining_mode.as_ref().map_or(false, |mode| mode.retains_node(&key))
+					{
+						continue
+					}
 					self.storage.db.sanitize_key(&mut key);
 					if rc > 0 {
 						ops += 1;
@@ -1701,7 +1752,8 @@ impl<Block: BlockT> Backend<Block> {
 						);
 					}
 				} else if number > best_num + One::one() &&
-					number > One::one() && self.blockchain.header(parent_hash)?.is_none()
+					number > One::one() &&
+					self.blockchain.header(parent_hash)?.is_none()
 				{
 					let gap = (best_num + One::one(), number - One::one());
 					transaction.set(columns::META, meta_keys::BLOCK_GAP, &gap.encode());
@@ -2435,16 +2487,25 @@ impl<Block: BlockT> sc_client_api::backend::Backend<Block> for Backend<Block> {
 						.is_some()
 				};
 
-				if let Ok(()) =
-					self.storage.state_db.pin(&hash, hdr.number.saturated_into::<u64>(), hint)
-				{
+				let number = hdr.number.saturated_into::<u64>();
+				if let Ok(()) = self.storage.state_db.pin(&hash, number, hint) {
 					let root = hdr.state_root;
 					let db_state = DbStateBuilder::<Block>::new(self.storage.clone(), root)
 						.with_optional_cache(
 							self.shared_trie_cache.as_ref().map(|c| c.local_cache()),
 						)
 						.build();
-					let state = RefTrackingState::new(db_state, self.storage.clone(), Some(hash));
+					let mut state =
+						RefTrackingState::new(db_state, self.storage.clone(), Some(hash));
+					let is_canonical =
+						|| self.blockchain.hash(hdr.number).ok().flatten() == Some(hash);
+					if self.storage.state_db.is_partially_pruned(&hash, number, is_canonical) ==
+						IsPruned::PartiallyPruned
+					{
+						let retain =
+							self.storage.state_db.pruning_mode().retention_rules().to_vec();
+						state = state.with_retention(retain);
+					}
 					Ok(RecordStatsState::new(state, Some(hash), self.state_usage.clone()))
 				} else {
 					Err(sp_blockchain::Error::UnknownBlock(format!(
@@ -2471,7 +2532,8 @@ impl<Block: BlockT> sc_client_api::backend::Backend<Block> for Backend<Block> {
 			}
 		} else {
 			match self.storage.state_db.is_pruned(&hash, number.saturated_into::<u64>()) {
-				IsPruned::Pruned => false,
+				// Only the retained storage is left, which is not enough to execute blocks.
+				IsPruned::Pruned | IsPruned::PartiallyPruned => false,
 				IsPruned::NotPruned => true,
 				IsPruned::MaybePruned => match self.blockchain.header_metadata(hash) {
 					Ok(header) => sp_state_machine::Storage::get(
@@ -2783,6 +2845,81 @@ pub(crate) mod tests {
 		}
 	}
 
+	fn check_retained_state_after_pruning(source: DatabaseSource) {
+		let backend = Backend::<Block>::new(
+			DatabaseSettings {
+				trie_cache_maximum_size: None,
+				state_pruning: Some(PruningMode::blocks_pruning_with_retention(
+					1,
+					vec![RetentionRule::Prefix(vec![1])],
+				)),
+				source,
+				blocks_pruning: BlocksPruning::KeepAll,
+			},
+			0,
+		)
+		.unwrap();
+
+		let import = |number: u64, parent_hash: H256, value: u8, state: NewBlockState| {
+			let mut op = backend.begin_operation().unwrap();
+			backend.begin_state_operation(&mut op, parent_hash).unwrap();
+			let storage = vec![(vec![1, 1], Some(vec![value])), (vec![2, 2], Some(vec![value]))];
+			let (root, overlay) = op.old_state.storage_root(
+				storage.iter().map(|(k, v)| (k.as_slice(), v.as_deref())),
+				StateVersion::default(),
+			);
+			op.update_db_storage(overlay).unwrap();
+			op.update_storage(storage, Vec::new()).unwrap();
+			let header = Header {
+				number,
+				parent_hash,
+				state_root: root.into(),
+				digest: Default::default(),
+				extrinsics_root: Default::default(),
+			};
+			let hash = header.hash();
+			op.set_block_data(header, Some(vec![]), None, None, state).unwrap();
+			backend.commit_operation(op).unwrap();
+			hash
+		};
+
+		let mut hashes = Vec::new();
+		let mut parent_hash = Default::default();
+		for number in 0..4 {
+			if number == 2 {
+				// Fork discarded when block 2 is canonicalized.
+				hashes.push(import(number, parent_hash, 0xff, NewBlockState::Normal));
+			}
+			parent_hash = import(number, parent_hash, number as u8, NewBlockState::Best);
+			hashes.push(parent_hash);
+		}
+
+		assert!(!backend.have_state_at(hashes[1], 1));
+		let state = backend.state_at(hashes[1]).unwrap();
+		assert_eq!(state.storage(&[1, 1]).unwrap(), Some(vec![1]));
+		assert!(state.storage(&[2, 2]).is_err());
+
+		assert!(!backend.have_state_at(hashes[2], 2));
+		assert!(backend.state_at(hashes[2]).is_err());
+
+		let state = backend.state_at(hashes[4]).unwrap();
+		assert_eq!(state.storage(&[2, 2]).unwrap(), Some(vec![3]));
+	}
+
+	#[test]
+	fn retained_state_is_available_after_pruning() {
+		check_retained_state_after_pruning(DatabaseSource::Custom {
+			db: sp_database::as_database(kvdb_memorydb::create(crate::utils::NUM_COLUMNS)),
+			require_create_flag: true,
+		});
+	}
+
+	#[test]
+	fn retained_state_is_available_after_pruning_with_paritydb() {
+		let dir = tempfile::tempdir().unwrap();
+		check_retained_state_after_pruning(DatabaseSource::ParityDb { path: dir.path().into() });
+	}
+
 	#[test]
 	fn delete_only_when_negative_rc() {
 		sp_tracing::try_init_simple();
@@ -0,0 +1,400 @@
+// This file is part of Substrate.
+
+// Copyright (C) Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Migration of a database between RocksDB and ParityDB.
+//!
+//! ParityDB stores state nodes under their hash and counts their references, while RocksDB keys
+//! them with their trie path and relies on the state database to keep track of reinserted nodes.
+//!
+//! - From RocksDB, every column is streamed into ParityDB in key order. The trie path of the state
+//!   nodes is dropped, every node key adds a reference, and the deletions of the pruning window are
+//!   resolved the way the state database applies them to RocksDB.
+//! - From ParityDB, the blocks are copied by following the chain, and the trie path of the state
+//!   nodes is recovered by comparing the state of every block with the state of its parent. The
+//!   journals of the pruning window and of the non-canonical blocks are rebuilt from the same
+//!   comparison. ParityDB only keeps the keys of the auxiliary and offchain columns in databases
+//!   created with ordered columns, older databases can't be migrated to RocksDB.
+//!
+//! The position of the migration is stored per column in the target database and committed
+//! together with every batch, so an interrupted migration continues where it stopped. Pruning
+//! retention rules are not supported, the retained nodes can't be told apart without the trie path.
+
+use crate::DatabaseSource;
+use sp_blockchain::{Error as ClientError, Result as ClientResult};
+use sp_runtime::traits::Block as BlockT;
+
+#[cfg(feature = "rocksdb")]
+mod to_parity_db;
+#[cfg(feature = "rocksdb")]
+mod to_rocksdb;
+
+/// Statistics of a completed database migration.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MigrationSummary {
+	/// Number of migrated entries.
+	pub entries: u64,
+	/// Number of migrated key and value bytes.
+	pub bytes: u64,
+	/// Number of entries compared with the source database by the verification pass.
+	pub verified: u64,
+}
+
+/// Migrate the database at `source` to `target`, from RocksDB to ParityDB or the other way around.
+///
+/// An existing `target` is only accepted if it contains an unfinished migration, which is
+/// resumed. If `verify` is set, the migrated entries are compared with the source database after
+/// the migration.
+pub fn migrate_database<Block: BlockT>(
+	source: &DatabaseSource,
+	target: &DatabaseSource,
+	verify: bool,
+) -> ClientResult<MigrationSummary> {
+	#[cfg(feature = "rocksdb")]
+	{
+		let source = match source {
+			DatabaseSource::Auto { rocksdb_path, .. } if rocksdb_path.exists() =>
+				DatabaseSource::RocksDb { path: rocksdb_path.clone(), cache_size: 0 },
+			DatabaseSource::Auto { paritydb_path, .. } =>
+				DatabaseSource::ParityDb { path: paritydb_path.clone() },
+			source => source.clone(),
+		};
+		return match (&source, target) {
+			(DatabaseSource::RocksDb { path: source, .. }, DatabaseSource::ParityDb { path }) =>
+				to_parity_db::migrate::<Block>(source, path, verify),
+			(DatabaseSource::ParityDb { path: source }, DatabaseSource::RocksDb { path, .. }) =>
+				to_rocksdb::migrate::<Block>(source, path, verify),
+			_ => Err(ClientError::Backend(
+				"Databases can only be migrated from RocksDB to ParityDB or from ParityDB to RocksDB"
+					.into(),
+			)),
+		}
+	}
+
+	#[cfg(not(feature = "rocksdb"))]
+	{
+		let _ = (source, target, verify);
+		return Err(ClientError::Backend("RocksDB support is not enabled".into()))
+	}
+}
+
+#[cfg(feature = "rocksdb")]
+mod common {
+	use crate::utils::meta_keys;
+	use codec::{Decode, Encode};
+	use sc_state_db::PruningMode;
+	use sp_blockchain::{Error as ClientError, Result as ClientResult};
+
+	/// Number of entries written per database transaction.
+	pub(super) const BATCH_SIZE: u64 = if cfg!(test) { 16 } else { 10_000 };
+
+	#[cfg(test)]
+	thread_local! {
+		/// Number of batches after which the migration is interrupted.
+		pub(super) static INTERRUPT_AFTER: std::cell::Cell<Option<u32>> = Default::default();
+	}
+
+	/// Called once a batch is committed to the target database.
+	pub(super) fn batch_committed() -> ClientResult<()> {
+		#[cfg(test)]
+		if let Some(batches) = INTERRUPT_AFTER.with(|interrupt| interrupt.get()) {
+			if batches == 0 {
+				INTERRUPT_AFTER.with(|interrupt| interrupt.set(None));
+				return Err(ClientError::Backend("Migration interrupted".into()))
+			}
+			INTERRUPT_AFTER.with(|interrupt| interrupt.set(Some(batches - 1)));
+		}
+		Ok(())
+	}
+
+	/// Overall migration progress stored in the target database.
+	#[derive(Debug, Default, Encode, Decode)]
+	pub(super) struct Progress {
+		/// Number of migrated entries.
+		pub entries: u64,
+		/// Number of migrated key and value bytes.
+		pub bytes: u64,
+	}
+
+	/// Migration position within a column, stored in the target database.
+	#[derive(Debug, Encode, Decode)]
+	pub(super) enum Cursor {
+		/// Everything up to the encoded position has been migrated.
+		After(Vec<u8>),
+		/// The column has been migrated.
+		Done,
+	}
+
+	/// Key of the cursor of `column` in the meta column of the target database.
+	pub(super) fn cursor_key(column: u32) -> Vec<u8> {
+		let mut key = meta_keys::MIGRATION_CURSOR.to_vec();
+		column.encode_to(&mut key);
+		key
+	}
+
+	pub(super) fn decode_cursor(cursor: Option<Vec<u8>>) -> ClientResult<Option<Cursor>> {
+		cursor
+			.map(|cursor| Cursor::decode(&mut &cursor[..]))
+			.transpose()
+			.map_err(db_err)
+	}
+
+	pub(super) fn db_err(e: impl std::fmt::Debug) -> ClientError {
+		ClientError::Backend(format!("Database migration failed: {:?}", e))
+	}
+
+	/// Reject pruning modes the migration can't preserve.
+	pub(super) fn check_pruning_mode(mode: Option<&PruningMode>) -> ClientResult<()> {
+		match mode {
+			Some(mode) if !mode.retention_rules().is_empty() => Err(ClientError::Backend(
+				"Databases with pruning retention rules can't be migrated".into(),
+			)),
+			_ => Ok(()),
+		}
+	}
+
+	/// Remove the trie path prefix of a state node key.
+	pub(super) fn node_hash_key(mut key: Vec<u8>) -> Vec<u8> {
+		key.drain(..key.len().saturating_sub(crate::DB_HASH_LEN));
+		key
+	}
+}
+
+#[cfg(all(test, feature = "rocksdb"))]
+mod tests {
+	use super::*;
+	use crate::{tests::Block, Backend, BlocksPruning, DatabaseSettings, PruningMode};
+	use sc_client_api::backend::{Backend as _, BlockImportOperation as _, NewBlockState};
+	use sp_blockchain::{Backend as _, HeaderBackend};
+	use sp_core::{storage::ChildInfo, H256};
+	use sp_runtime::{testing::Header, traits::Header as _, StateVersion};
+	use sp_state_machine::Backend as _;
+
+	fn settings(source: DatabaseSource, state_pruning: PruningMode) -> DatabaseSettings {
+		DatabaseSettings {
+			trie_cache_maximum_size: None,
+			state_pruning: Some(state_pruning),
+			source,
+			blocks_pruning: BlocksPruning::KeepAll,
+		}
+	}
+
+	fn child_info() -> ChildInfo {
+		ChildInfo::new_default(b"child")
+	}
+
+	/// Import a block that stores `seed` under its own number, in a value shared by all blocks and
+	/// in a child trie. The shared value alternates, so its nodes are reinserted.
+	fn import_block(
+		backend: &Backend<Block>,
+		number: u64,
+		parent_hash: H256,
+		seed: u8,
+		state: NewBlockState,
+	) -> H256 {
+		let mut op = backend.begin_operation().unwrap();
+		backend.begin_state_operation(&mut op, parent_hash).unwrap();
+		let storage = vec![
+			(vec![number as u8], Some(vec![seed; 64])),
+			(b"shared".to_vec(), Some(vec![number as u8 % 2; 64])),
+		];
+		let child_storage = vec![(vec![1], Some(vec![seed; 40]))];
+		let child_info = child_info();
+		let (root, overlay) = op.old_state.full_storage_root(
+			storage.iter().map(|(k, v)| (k.as_slice(), v.as_deref())),
+			std::iter::once((
+				&child_info,
+				child_storage.iter().map(|(k, v)| (k.as_slice(), v.as_deref())),
+			)),
+			StateVersion::V1,
+		);
+		op.update_db_storage(overlay).unwrap();
+		op.update_storage(storage, vec![(child_info.storage_key().to_vec(), child_storage)])
+			.unwrap();
+		let header = Header {
+			number,
+			parent_hash,
+			state_root: root.into(),
+			digest: Default::default(),
+			extrinsics_root: Default::default(),
+		};
+		let hash = header.hash();
+		op.set_block_data(header, Some(vec![]), None, None, state).unwrap();
+		backend.commit_operation(op).unwrap();
+		hash
+	}
+
+	fn import_chain(
+		backend: &Backend<Block>,
+		from: u64,
+		to: u64,
+		mut parent_hash: H256,
+	) -> Vec<H256> {
+		(from..to)
+			.map(|number| {
+				parent_hash =
+					import_block(backend, number, parent_hash, number as u8, NewBlockState::Final);
+				parent_hash
+			})
+			.collect()
+	}
+
+	fn check_state(backend: &Backend<Block>, hash: H256, number: u64, seed: u8) {
+		let state = backend.state_at(hash).unwrap();
+		assert_eq!(state.storage(&[number as u8]).unwrap(), Some(vec![seed; 64]));
+		assert_eq!(state.storage(b"shared").unwrap(), Some(vec![number as u8 % 2; 64]));
+		assert_eq!(state.child_storage(&child_info(), &[1]).unwrap(), Some(vec![seed; 40]));
+	}
+
+	/// Import the finalized blocks `0..5`, a fork block on top of #2 and an unfinalized block on
+	/// top of #4.
+	fn populate(backend: &Backend<Block>) {
+		let mut hashes = import_chain(backend, 0, 3, Default::default());
+		import_block(backend, 3, hashes[2], 100, NewBlockState::Normal);
+		hashes.extend(import_chain(backend, 3, 5, hashes[2]));
+		import_block(backend, 5, hashes[4], 5, NewBlockState::Best);
+	}
+
+	/// Check the migrated database and keep importing and pruning blocks on top of it.
+	fn check_migrated(target: DatabaseSource, pruning: PruningMode) {
+		let archive = pruning.is_archive();
+		let backend = Backend::<Block>::new(settings(target, pruning.clone()), 0).unwrap();
+		let info = backend.blockchain().info();
+		assert_eq!(info.best_number, 5);
+		assert_eq!(info.finalized_number, 4);
+		let hashes: Vec<_> = (0..=5)
+			.map(|number| backend.blockchain().hash(number).unwrap().unwrap())
+			.collect();
+		for (number, hash) in hashes.iter().enumerate() {
+			if archive || number >= 3 {
+				check_state(&backend, *hash, number as u64, number as u8);
+			}
+		}
+		if matches!(pruning, PruningMode::ArchiveAll) {
+			let fork = backend.blockchain().children(hashes[2]).unwrap();
+			assert_eq!(fork.len(), 2);
+			let fork = fork.into_iter().find(|hash| *hash != hashes[3]).unwrap();
+			check_state(&backend, fork, 3, 100);
+		}
+
+		backend.finalize_block(hashes[5], None).unwrap();
+		let new_hashes = import_chain(&backend, 6, 10, hashes[5]);
+		for (number, hash) in new_hashes.iter().enumerate().skip(2) {
+			check_state(&backend, *hash, number as u64 + 6, number as u8 + 6);
+		}
+	}
+
+	#[test]
+	fn migrates_database_to_paritydb() {
+		for pruning in [PruningMode::ArchiveAll, PruningMode::blocks_pruning(2)] {
+			let dir = tempfile::tempdir().unwrap();
+			let rocksdb =
+				DatabaseSource::RocksDb { path: dir.path().join("rocksdb"), cache_size: 16 };
+			let paritydb = DatabaseSource::ParityDb { path: dir.path().join("paritydb") };
+			{
+				let backend =
+					Backend::<Block>::new(settings(rocksdb.clone(), pruning.clone()), 0).unwrap();
+				populate(&backend);
+			}
+
+			let summary = migrate_database::<Block>(&rocksdb, &paritydb, true).unwrap();
+			assert!(summary.entries > 0);
+			assert_eq!(summary.verified, summary.entries);
+			check_migrated(paritydb.clone(), pruning);
+
+			// A completed migration is not repeated into the same database.
+			assert!(migrate_database::<Block>(&rocksdb, &paritydb, false).is_err());
+		}
+	}
+
+	#[test]
+	fn migrates_database_to_rocksdb() {
+		for pruning in
+			[PruningMode::ArchiveAll, PruningMode::ArchiveCanonical, PruningMode::blocks_pruning(2)]
+		{
+			let dir = tempfile::tempdir().unwrap();
+			let paritydb = DatabaseSource::ParityDb { path: dir.path().join("paritydb") };
+			let rocksdb =
+				DatabaseSource::RocksDb { path: dir.path().join("rocksdb"), cache_size: 16 };
+			{
+				let backend =
+					Backend::<Block>::new(settings(paritydb.clone(), pruning.clone()), 0).unwrap();
+				populate(&backend);
+			}
+
+			let summary = migrate_database::<Block>(&paritydb, &rocksdb, true).unwrap();
+			assert!(summary.entries > 0);
+			assert!(summary.verified > 0);
+			check_migrated(rocksdb.clone(), pruning);
+
+			assert!(migrate_database::<Block>(&paritydb, &rocksdb, false).is_err());
+		}
+	}
+
+	#[test]
+	fn resumes_interrupted_migration() {
+		let dir = tempfile::tempdir().unwrap();
+		let rocksdb = DatabaseSource::RocksDb { path: dir.path().join("rocksdb"), cache_size: 16 };
+		let paritydb = DatabaseSource::ParityDb { path: dir.path().join("paritydb") };
+		let pruning = PruningMode::blocks_pruning(2);
+		{
+			let backend =
+				Backend::<Block>::new(settings(rocksdb.clone(), pruning.clone()), 0).unwrap();
+			populate(&backend);
+		}
+
+		let migrate = |source: &DatabaseSource, target: &DatabaseSource| {
+			common::INTERRUPT_AFTER.with(|interrupt| interrupt.set(Some(4)));
+			assert!(migrate_database::<Block>(source, target, false).is_err());
+			// The unfinished database can't be used.
+			assert!(Backend::<Block>::new(settings(target.clone(), pruning.clone()), 0).is_err());
+			migrate_database::<Block>(source, target, true).unwrap()
+		};
+
+		let summary = migrate(&rocksdb, &paritydb);
+		let uninterrupted = migrate_database::<Block>(
+			&rocksdb,
+			&DatabaseSource::ParityDb { path: dir.path().join("uninterrupted") },
+			false,
+		)
+		.unwrap();
+		assert_eq!(summary.entries, uninterrupted.entries);
+
+		let back = DatabaseSource::RocksDb { path: dir.path().join("back"), cache_size: 16 };
+		migrate(&paritydb, &back);
+		check_migrated(paritydb, pruning.clone());
+		check_migrated(back, pruning);
+	}
+
+	#[test]
+	fn retention_rules_are_not_migrated() {
+		let dir = tempfile::tempdir().unwrap();
+		let rocksdb = DatabaseSource::RocksDb { path: dir.path().join("rocksdb"), cache_size: 16 };
+		let paritydb = DatabaseSource::ParityDb { path: dir.path().join("paritydb") };
+		let pruning = PruningMode::blocks_pruning_with_retention(
+			2,
+			vec![sc_state_db::RetentionRule::Prefix(b"shared".to_vec())],
+		);
+		{
+			let backend = Backend::<Block>::new(settings(rocksdb.clone(), pruning), 0).unwrap();
+			import_chain(&backend, 0, 1, Default::default());
+		}
+
+		assert!(migrate_database::<Block>(&rocksdb, &paritydb, false).is_err());
+		assert!(migrate_database::<Block>(&rocksdb, &rocksdb, false).is_err());
+	}
+}
@@ -0,0 +1,255 @@
+// This file is part of Substrate.
+
+// Copyright (C) Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Migration of a RocksDB database to ParityDB.
+
+use super::{
+	common::{
+		batch_committed, check_pruning_mode, cursor_key, db_err, decode_cursor, node_hash_key,
+		Cursor, Progress, BATCH_SIZE,
+	},
+	MigrationSummary,
+};
+use crate::{
+	columns,
+	utils::{meta_keys, DatabaseType, NUM_COLUMNS},
+	DbHash, DB_HASH_LEN,
+};
+use codec::{Decode, Encode};
+use kvdb::KeyValueDB;
+use log::info;
+use sc_state_db::MetaDb;
+use sp_blockchain::{Error as ClientError, Result as ClientResult};
+use sp_core::hexdisplay::HexDisplay;
+use sp_database::{Database, Transaction};
+use sp_runtime::traits::Block as BlockT;
+use std::{collections::HashMap, path::Path, sync::Arc};
+
+struct SourceMetaDb<'a>(&'a kvdb_rocksdb::Database);
+
+impl<'a> MetaDb for SourceMetaDb<'a> {
+	type Error = std::io::Error;
+
+	fn get_meta(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+		self.0.get(columns::STATE_META, key)
+	}
+}
+
+/// Check if `key` is the reference counter of a transaction column entry.
+fn is_counter_key(key: &[u8]) -> bool {
+	key.len() == DB_HASH_LEN + 1 && key[DB_HASH_LEN] == 0
+}
+
+/// Reference count of a transaction column entry.
+fn read_counter(source: &kvdb_rocksdb::Database, key: &[u8]) -> ClientResult<u32> {
+	let mut counter_key = key.to_vec();
+	counter_key.push(0);
+	match source.get(columns::TRANSACTION, &counter_key).map_err(db_err)? {
+		Some(counter) => counter
+			.try_into()
+			.map(u32::from_le_bytes)
+			.map_err(|_| db_err("Invalid transaction reference counter")),
+		None => Ok(1),
+	}
+}
+
+/// Converts the entries of the source database into the entries stored in ParityDB.
+struct Converter<Block: BlockT> {
+	/// Pruning journals of the source database, with the deletions the source would apply.
+	pruning_journals: HashMap<Vec<u8>, Vec<u8>>,
+	_phantom: std::marker::PhantomData<Block>,
+}
+
+impl<Block: BlockT> Converter<Block> {
+	fn new(source: &kvdb_rocksdb::Database) -> ClientResult<Self> {
+		let (keys, mut journals): (Vec<_>, Vec<_>) =
+			sc_state_db::fetch_pruning_journals::<Block::Hash, Vec<u8>, _>(&SourceMetaDb(source))
+				.map_err(db_err)?
+				.into_iter()
+				.unzip();
+		// ParityDB doesn't track reinserted nodes, it only sees the deletions that RocksDB applies.
+		sc_state_db::resolve_reinsertions(&mut journals);
+		let pruning_journals = keys
+			.into_iter()
+			.zip(journals)
+			.map(|(key, mut journal)| {
+				journal.inserted.clear();
+				journal.deleted = journal.deleted.into_iter().map(node_hash_key).collect();
+				(key, journal.encode())
+			})
+			.collect();
+		Ok(Converter { pruning_journals, _phantom: Default::default() })
+	}
+
+	/// Convert a source entry into the key and value stored in the target database.
+	fn convert(
+		&self,
+		column: u32,
+		key: Vec<u8>,
+		value: Vec<u8>,
+	) -> ClientResult<(Vec<u8>, Vec<u8>)> {
+		match column {
+			columns::STATE => Ok((node_hash_key(key), value)),
+			columns::STATE_META => {
+				if let Some(journal) = self.pruning_journals.get(&key) {
+					return Ok((key, journal.clone()))
+				}
+				match sc_state_db::map_journal_record_keys::<Block::Hash, Vec<u8>>(
+					&key,
+					&value,
+					node_hash_key,
+				) {
+					Some(record) => Ok((key, record.map_err(db_err)?)),
+					None => Ok((key, value)),
+				}
+			},
+			_ => Ok((key, value)),
+		}
+	}
+}
+
+pub(super) fn migrate<Block: BlockT>(
+	source_path: &Path,
+	target_path: &Path,
+	verify: bool,
+) -> ClientResult<MigrationSummary> {
+	crate::upgrade::upgrade_db::<Block>(source_path, DatabaseType::Full).map_err(db_err)?;
+	let mut config = kvdb_rocksdb::DatabaseConfig::with_columns(NUM_COLUMNS);
+	config.create_if_missing = false;
+	let source = kvdb_rocksdb::Database::open(&config, source_path).map_err(db_err)?;
+	let mode = sc_state_db::fetch_stored_pruning_mode(&SourceMetaDb(&source)).map_err(db_err)?;
+	check_pruning_mode(mode.as_ref())?;
+	let converter = Converter::<Block>::new(&source)?;
+
+	let target: Arc<dyn Database<DbHash>> =
+		crate::parity_db::open(target_path, DatabaseType::Full, true, false).map_err(db_err)?;
+	let mut progress = match target.get(columns::META, meta_keys::MIGRATION_PROGRESS) {
+		Some(progress) => {
+			let progress = Progress::decode(&mut &progress[..]).map_err(db_err)?;
+			info!("⏩ Resuming database migration after {} entries", progress.entries);
+			progress
+		},
+		None if target.get(columns::META, meta_keys::TYPE).is_some() =>
+			return Err(ClientError::Backend("Target database is not empty".into())),
+		None => Progress::default(),
+	};
+
+	for column in 0..NUM_COLUMNS {
+		let last_key = match decode_cursor(target.get(columns::META, &cursor_key(column)))? {
+			Some(Cursor::Done) => continue,
+			Some(Cursor::After(last_key)) => Some(last_key),
+			None => None,
+		};
+		let mut transaction = Transaction::new();
+		let mut pending = 0;
+		for entry in source.iter(column) {
+			let (key, value) = entry.map_err(db_err)?;
+			let key = key.to_vec();
+			if last_key.as_ref().map_or(false, |last_key| key <= *last_key) {
+				continue
+			}
+			if column == columns::TRANSACTION && is_counter_key(&key) {
+				continue
+			}
+			progress.entries += 1;
+			progress.bytes += (key.len() + value.len()) as u64;
+
+			if column == columns::TRANSACTION {
+				if key.len() != DB_HASH_LEN {
+					return Err(db_err("Invalid transaction column key"))
+				}
+				// Every insertion of a key adds a reference in ParityDB.
+				for _ in 0..read_counter(&source, &key)? {
+					transaction.set(column, &key, &value);
+				}
+			} else {
+				// State nodes stored under several trie paths get a reference for each of them.
+				let (target_key, value) = converter.convert(column, key.clone(), value)?;
+				transaction.set_from_vec(column, &target_key, value);
+			}
+
+			pending += 1;
+			if pending == BATCH_SIZE {
+				transaction.set_from_vec(
+					columns::META,
+					&cursor_key(column),
+					Cursor::After(key).encode(),
+				);
+				transaction.set_from_vec(
+					columns::META,
+					meta_keys::MIGRATION_PROGRESS,
+					progress.encode(),
+				);
+				target.commit(std::mem::take(&mut transaction)).map_err(db_err)?;
+				batch_committed()?;
+				pending = 0;
+				info!(
+					"⚙️  Migrated {} entries ({} MiB), column {}/{}",
+					progress.entries,
+					progress.bytes / (1024 * 1024),
+					column + 1,
+					NUM_COLUMNS,
+				);
+			}
+		}
+		transaction.set_from_vec(columns::META, &cursor_key(column), Cursor::Done.encode());
+		transaction.set_from_vec(columns::META, meta_keys::MIGRATION_PROGRESS, progress.encode());
+		target.commit(transaction).map_err(db_err)?;
+		batch_committed()?;
+	}
+
+	let verified = if verify { verify_migration(&source, &*target, &converter)? } else { 0 };
+
+	let mut transaction = Transaction::new();
+	for column in 0..NUM_COLUMNS {
+		transaction.remove(columns::META, &cursor_key(column));
+	}
+	transaction.remove(columns::META, meta_keys::MIGRATION_PROGRESS);
+	target.commit(transaction).map_err(db_err)?;
+	info!("🎉 Migrated {} entries ({} bytes) to ParityDB", progress.entries, progress.bytes);
+
+	Ok(MigrationSummary { entries: progress.entries, bytes: progress.bytes, verified })
+}
+
+/// Compare every entry of the source database with the target database.
+fn verify_migration<Block: BlockT>(
+	source: &kvdb_rocksdb::Database,
+	target: &dyn Database<DbHash>,
+	converter: &Converter<Block>,
+) -> ClientResult<u64> {
+	info!("🔍 Verifying migrated database");
+	let mut verified = 0;
+	for column in 0..NUM_COLUMNS {
+		for entry in source.iter(column) {
+			let (key, value) = entry.map_err(db_err)?;
+			if column == columns::TRANSACTION && is_counter_key(&key) {
+				continue
+			}
+			let (key, value) = converter.convert(column, key.to_vec(), value)?;
+			if target.get(column, &key).as_ref() != Some(&value) {
+				return Err(ClientError::Backend(format!(
+					"Database migration verification failed for key 0x{} of column {}",
+					HexDisplay::from(&key),
+					column,
+				)))
+			}
+			verified += 1;
+		}
+	}
+	Ok(verified)
+}
@@ -0,0 +1,589 @@
+// This file is part of Substrate.
+
+// Copyright (C) Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Migration of a ParityDB database to RocksDB.
+
+use super::{
+	common::{
+		batch_committed, check_pruning_mode, cursor_key, db_err, decode_cursor, Cursor, Progress,
+		BATCH_SIZE,
+	},
+	MigrationSummary,
+};
+use crate::{
+	columns,
+	trie_diff::{diff_states, empty_root, Change},
+	utils::{meta_keys, number_index_key, DatabaseType, NUM_COLUMNS},
+	DbHash, DB_HASH_LEN,
+};
+use codec::{Decode, Encode};
+use kvdb::{DBTransaction, KeyValueDB};
+use log::info;
+use sc_state_db::{MetaDb, NonCanonicalJournalRecord, PruningJournalRecord, PruningMode};
+use sp_blockchain::{Error as ClientError, Result as ClientResult};
+use sp_core::hexdisplay::HexDisplay;
+use sp_runtime::traits::{Block as BlockT, HashingFor, Header as HeaderT};
+use std::{
+	collections::{HashMap, HashSet},
+	path::Path,
+};
+
+/// Lookup keys start with the block number.
+const NUMBER_LEN: usize = 4;
+
+struct Source(parity_db::Db);
+
+impl Source {
+	fn get(&self, column: u32, key: &[u8]) -> ClientResult<Option<Vec<u8>>> {
+		self.0.get(column as u8, key).map_err(db_err)
+	}
+
+	/// Number of the best block.
+	fn best_number(&self) -> ClientResult<Option<u32>> {
+		Ok(self.get(columns::META, meta_keys::BEST_BLOCK)?.map(|lookup_key| {
+			let mut number = [0; NUMBER_LEN];
+			number.copy_from_slice(&lookup_key[..NUMBER_LEN]);
+			u32::from_be_bytes(number)
+		}))
+	}
+
+	/// State root of the block with `lookup_key`, if the header is known.
+	fn state_root<Block: BlockT>(&self, lookup_key: &[u8]) -> ClientResult<Option<DbHash>> {
+		let Some(header) = self.get(columns::HEADER, lookup_key)? else { return Ok(None) };
+		let header = Block::Header::decode(&mut &header[..]).map_err(db_err)?;
+		Ok(Some(DbHash::from_slice(header.state_root().as_ref())))
+	}
+
+	/// State root of the block with `hash`, if the header is known.
+	fn state_root_of<Block: BlockT>(&self, hash: &Block::Hash) -> ClientResult<Option<DbHash>> {
+		match self.get(columns::KEY_LOOKUP, hash.as_ref())? {
+			Some(lookup_key) => self.state_root::<Block>(&lookup_key),
+			None => Ok(None),
+		}
+	}
+
+	/// Check if the state with `root` has been committed.
+	fn has_state<Block: BlockT>(&self, root: &DbHash) -> ClientResult<bool> {
+		Ok(*root == empty_root::<HashingFor<Block>>() ||
+			self.get(columns::STATE, root.as_ref())?.is_some())
+	}
+}
+
+impl MetaDb for Source {
+	type Error = parity_db::Error;
+
+	fn get_meta(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+		self.0.get(columns::STATE_META as u8, key)
+	}
+}
+
+struct Target {
+	db: kvdb_rocksdb::Database,
+	transaction: DBTransaction,
+	progress: Progress,
+}
+
+impl Target {
+	fn put(&mut self, column: u32, key: &[u8], value: Vec<u8>) {
+		self.progress.entries += 1;
+		self.progress.bytes += (key.len() + value.len()) as u64;
+		self.transaction.put_vec(column, key, value);
+	}
+
+	fn is_full(&self) -> bool {
+		self.transaction.ops.len() as u64 >= BATCH_SIZE
+	}
+
+	fn cursor(&self, column: u32) -> ClientResult<Option<Cursor>> {
+		decode_cursor(self.db.get(columns::META, &cursor_key(column)).map_err(db_err)?)
+	}
+
+	/// Commit the pending entries, together with the cursor of `column` if any.
+	fn commit(&mut self, cursor: Option<(u32, Cursor)>) -> ClientResult<()> {
+		let mut transaction = std::mem::take(&mut self.transaction);
+		if let Some((column, cursor)) = cursor {
+			transaction.put_vec(columns::META, &cursor_key(column), cursor.encode());
+		}
+		transaction.put_vec(columns::META, meta_keys::MIGRATION_PROGRESS, self.progress.encode());
+		self.db.write(transaction).map_err(db_err)?;
+		batch_committed()?;
+		info!(
+			"⚙️  Migrated {} entries ({} MiB)",
+			self.progress.entries,
+			self.progress.bytes / (1024 * 1024),
+		);
+		Ok(())
+	}
+}
+
+pub(super) fn migrate<Block: BlockT>(
+	source_path: &Path,
+	target_path: &Path,
+	verify: bool,
+) -> ClientResult<MigrationSummary> {
+	let ordered_columns = parity_db::Options::load_metadata(source_path)
+		.map_err(db_err)?
+		.ok_or_else(|| db_err("Source database does not exist"))?
+		.columns
+		.iter()
+		.map(|column| column.btree_index)
+		.collect::<Vec<_>>();
+	let source =
+		Source(crate::parity_db::open_read_only(source_path, DatabaseType::Full).map_err(db_err)?);
+	let mode = sc_state_db::fetch_stored_pruning_mode(&source).map_err(db_err)?;
+	check_pruning_mode(mode.as_ref())?;
+
+	let mut config = kvdb_rocksdb::DatabaseConfig::with_columns(NUM_COLUMNS);
+	config.create_if_missing = true;
+	let db = kvdb_rocksdb::Database::open(&config, target_path).map_err(db_err)?;
+	crate::upgrade::update_version(target_path).map_err(db_err)?;
+	let progress = match db.get(columns::META, meta_keys::MIGRATION_PROGRESS).map_err(db_err)? {
+		Some(progress) => {
+			let progress = Progress::decode(&mut &progress[..]).map_err(db_err)?;
+			info!("⏩ Resuming database migration after {} entries", progress.entries);
+			progress
+		},
+		None if db.has_key(columns::META, meta_keys::TYPE).map_err(db_err)? =>
+			return Err(ClientError::Backend("Target database is not empty".into())),
+		None => Progress::default(),
+	};
+	let mut target = Target { db, transaction: DBTransaction::new(), progress };
+
+	for column in crate::parity_db::ORDERED_COLUMNS {
+		copy_ordered_column(&source, &mut target, column, ordered_columns[column as usize])?;
+	}
+	copy_transactions(&source, &mut target)?;
+	copy_blocks::<Block>(&source, &mut target)?;
+	copy_state::<Block>(&source, &mut target, mode)?;
+	copy_meta(&source, &mut target)?;
+
+	let verified = if verify { verify_migration::<Block>(&source, &target.db)? } else { 0 };
+
+	let mut transaction = DBTransaction::new();
+	for column in 0..NUM_COLUMNS {
+		transaction.delete(columns::META, &cursor_key(column));
+	}
+	transaction.delete(columns::META, meta_keys::MIGRATION_PROGRESS);
+	target.db.write(transaction).map_err(db_err)?;
+	let Progress { entries, bytes } = target.progress;
+	info!("🎉 Migrated {} entries ({} bytes) to RocksDB", entries, bytes);
+
+	Ok(MigrationSummary { entries, bytes, verified })
+}
+
+/// Copy a column that keeps its keys ordered.
+fn copy_ordered_column(
+	source: &Source,
+	target: &mut Target,
+	column: u32,
+	ordered: bool,
+) -> ClientResult<()> {
+	let last_key = match target.cursor(column)? {
+		Some(Cursor::Done) => return Ok(()),
+		Some(Cursor::After(last_key)) => Some(last_key),
+		None => None,
+	};
+	if !ordered {
+		let mut empty = true;
+		source
+			.0
+			.iter_column_while(column as u8, |_| {
+				empty = false;
+				false
+			})
+			.map_err(db_err)?;
+		if !empty {
+			return Err(ClientError::Backend(format!(
+				"Column {} of the source database does not keep its keys, databases created by \
+				 older versions can't be migrated to RocksDB",
+				column
+			)))
+		}
+		return target.commit(Some((column, Cursor::Done)))
+	}
+
+	let mut iter = source.0.iter(column as u8).map_err(db_err)?;
+	match &last_key {
+		Some(last_key) => iter.seek(last_key),
+		None => iter.seek_to_first(),
+	}
+	.map_err(db_err)?;
+	while let Some((key, value)) = iter.next().map_err(db_err)? {
+		if last_key.as_ref() == Some(&key) {
+			continue
+		}
+		target.put(column, &key, value);
+		if target.is_full() {
+			target.commit(Some((column, Cursor::After(key))))?;
+		}
+	}
+	target.commit(Some((column, Cursor::Done)))
+}
+
+/// Copy the transaction column, with the reference counts kept next to the values in RocksDB.
+fn copy_transactions(source: &Source, target: &mut Target) -> ClientResult<()> {
+	let column = columns::TRANSACTION;
+	let last_index = match target.cursor(column)? {
+		Some(Cursor::Done) => return Ok(()),
+		Some(Cursor::After(index)) => Some(u64::decode(&mut &index[..]).map_err(db_err)?),
+		None => None,
+	};
+	let mut result = Ok(());
+	source
+		.0
+		.iter_column_index_while(column as u8, |entry| {
+			if last_index.map_or(false, |last_index| entry.item_index <= last_index) {
+				return true
+			}
+			let mut counter_key = entry.key.to_vec();
+			counter_key.push(0);
+			target.put(column, &counter_key, entry.rc.to_le_bytes().to_vec());
+			target.put(column, &entry.key, entry.value);
+			if target.is_full() {
+				result = target.commit(Some((column, Cursor::After(entry.item_index.encode()))));
+			}
+			result.is_ok()
+		})
+		.map_err(db_err)?;
+	result?;
+	target.commit(Some((column, Cursor::Done)))
+}
+
+/// Copy the blocks of the canonical chain, each followed by the non-canonical blocks built on it.
+fn copy_blocks<Block: BlockT>(source: &Source, target: &mut Target) -> ClientResult<()> {
+	let column = columns::HEADER;
+	let mut number = match target.cursor(column)? {
+		Some(Cursor::Done) => return Ok(()),
+		Some(Cursor::After(number)) => u32::decode(&mut &number[..]).map_err(db_err)? + 1,
+		None => 0,
+	};
+	let best_number = source.best_number()?.unwrap_or_default();
+	while number <= best_number {
+		let number_key = number_index_key(number)?;
+		if let Some(lookup_key) = source.get(columns::KEY_LOOKUP, &number_key)? {
+			let canonical_child =
+				source.get(columns::KEY_LOOKUP, &number_index_key(number + 1)?)?;
+			target.put(columns::KEY_LOOKUP, &number_key, lookup_key.clone());
+			copy_block_tree::<Block>(source, target, lookup_key, canonical_child.as_deref())?;
+		}
+		if target.is_full() {
+			target.commit(Some((column, Cursor::After(number.encode()))))?;
+		}
+		number += 1;
+	}
+	target.commit(Some((column, Cursor::Done)))
+}
+
+/// Copy the block with `lookup_key` and its descendants, except the canonical child.
+fn copy_block_tree<Block: BlockT>(
+	source: &Source,
+	target: &mut Target,
+	lookup_key: Vec<u8>,
+	canonical_child: Option<&[u8]>,
+) -> ClientResult<()> {
+	let mut blocks = vec![lookup_key];
+	while let Some(lookup_key) = blocks.pop() {
+		let hash = &lookup_key[NUMBER_LEN..];
+		if let Some(value) = source.get(columns::KEY_LOOKUP, hash)? {
+			target.put(columns::KEY_LOOKUP, hash, value);
+		}
+		for column in [columns::HEADER, columns::BODY, columns::JUSTIFICATIONS, columns::BODY_INDEX]
+		{
+			if let Some(value) = source.get(column, &lookup_key)? {
+				target.put(column, &lookup_key, value);
+			}
+		}
+
+		let mut children_key = meta_keys::CHILDREN_PREFIX.to_vec();
+		children_key.extend_from_slice(hash);
+		let Some(children) = source.get(columns::META, &children_key)? else { continue };
+		for child in Vec::<Block::Hash>::decode(&mut &children[..]).map_err(db_err)? {
+			match source.get(columns::KEY_LOOKUP, child.as_ref())? {
+				Some(child) if Some(&child[..]) != canonical_child => blocks.push(child),
+				_ => {},
+			}
+		}
+		target.put(columns::META, &children_key, children);
+	}
+	Ok(())
+}
+
+/// Rebuild the state column and the state journals, keyed with the trie path of the nodes.
+fn copy_state<Block: BlockT>(
+	source: &Source,
+	target: &mut Target,
+	mode: Option<PruningMode>,
+) -> ClientResult<()> {
+	let column = columns::STATE;
+	let resume_at = match target.cursor(column)? {
+		Some(Cursor::Done) => return Ok(()),
+		Some(Cursor::After(position)) =>
+			Some(<(u32, Option<DbHash>)>::decode(&mut &position[..]).map_err(db_err)?),
+		None => None,
+	};
+	let last_canonical =
+		sc_state_db::fetch_last_canonicalized::<Block::Hash, _>(source).map_err(db_err)?;
+	let last_pruned = sc_state_db::fetch_last_pruned(source).map_err(db_err)?;
+	let pruning_journals = sc_state_db::fetch_pruning_journals::<Block::Hash, Vec<u8>, _>(source)
+		.map_err(db_err)?
+		.into_iter()
+		.map(|(key, record)| (record.hash, key))
+		.collect::<HashMap<_, _>>();
+
+	// Without archive, only the canonicalized blocks have their state committed. The state of the
+	// blocks before the pruning window is gone, except for the last pruned one.
+	let archive_all = matches!(mode, Some(PruningMode::ArchiveAll));
+	let last_number = if archive_all {
+		source.best_number()?
+	} else {
+		last_canonical.as_ref().map(|(_, number)| *number as u32)
+	};
+	let (mut number, mut parent_root) = match resume_at {
+		Some((number, root)) => (number + 1, root),
+		None => (last_pruned.unwrap_or_default() as u32, None),
+	};
+	while Some(number) <= last_number {
+		let lookup_key = source.get(columns::KEY_LOOKUP, &number_index_key(number)?)?;
+		if let Some(lookup_key) = lookup_key {
+			let root = match source.state_root::<Block>(&lookup_key)? {
+				Some(root) if source.has_state::<Block>(&root)? => Some(root),
+				_ => None,
+			};
+			if let Some(root) = root {
+				let hash = Block::Hash::decode(&mut &lookup_key[NUMBER_LEN..]).map_err(db_err)?;
+				let journal_key = pruning_journals.get(&hash);
+				let (inserted, deleted) =
+					write_state::<Block>(source, target, parent_root, root, journal_key.is_some())?;
+				if let Some(journal_key) = journal_key {
+					let record = PruningJournalRecord { hash, inserted, deleted };
+					target.put(columns::STATE_META, journal_key, record.encode());
+				}
+				if archive_all {
+					write_fork_states::<Block>(source, target, &lookup_key, root)?;
+				}
+				parent_root = Some(root);
+			}
+		}
+		if target.is_full() {
+			target.commit(Some((column, Cursor::After((number, parent_root).encode()))))?;
+		}
+		number += 1;
+	}
+
+	// Nodes of the non-canonical blocks are only kept in their journals.
+	let journals = sc_state_db::fetch_non_canonical_journals::<Block::Hash, Vec<u8>, _>(source)
+		.map_err(db_err)?;
+	let overlay = journals
+		.iter()
+		.flat_map(|(_, record)| record.inserted.iter())
+		.filter(|(key, value)| key.len() == DB_HASH_LEN && !value.is_empty())
+		.map(|(key, value)| (DbHash::from_slice(key), value.clone()))
+		.collect::<HashMap<_, _>>();
+	for (journal_key, record) in journals {
+		let missing_header =
+			|| ClientError::Backend(format!("Missing header of block {:?}", record.hash));
+		let parent_root = source.state_root_of::<Block>(&record.parent_hash)?;
+		let root = source.state_root_of::<Block>(&record.hash)?.ok_or_else(missing_header)?;
+		let mut inserted = Vec::new();
+		let mut deleted = Vec::new();
+		let get = |_: &[u8], hash: &DbHash| match overlay.get(hash) {
+			Some(value) => Ok(Some(value.clone())),
+			None => source.get(columns::STATE, hash.as_ref()),
+		};
+		diff_states::<HashingFor<Block>>(parent_root, Some(root), &get, &mut |change| {
+			match change {
+				Change::Inserted(key, value) => inserted.push((key, value)),
+				Change::Deleted(key) => deleted.push(key),
+			}
+			Ok(())
+		})?;
+		let record = NonCanonicalJournalRecord {
+			hash: record.hash,
+			parent_hash: record.parent_hash,
+			inserted,
+			deleted,
+		};
+		target.put(columns::STATE_META, &journal_key, record.encode());
+	}
+
+	for (key, value) in sc_state_db::fetch_stored_settings(source).map_err(db_err)? {
+		target.put(columns::STATE_META, &key, value);
+	}
+	target.commit(Some((column, Cursor::Done)))
+}
+
+/// Write the nodes of the state with `root` that are not in the state with `parent_root`.
+///
+/// Returns the inserted and deleted node keys if `journal` is set.
+fn write_state<Block: BlockT>(
+	source: &Source,
+	target: &mut Target,
+	parent_root: Option<DbHash>,
+	root: DbHash,
+	journal: bool,
+) -> ClientResult<(Vec<Vec<u8>>, Vec<Vec<u8>>)> {
+	let mut inserted = Vec::new();
+	let mut deleted = Vec::new();
+	let get = |_: &[u8], hash: &DbHash| source.get(columns::STATE, hash.as_ref());
+	diff_states::<HashingFor<Block>>(parent_root, Some(root), &get, &mut |change| {
+		match change {
+			Change::Inserted(key, value) => {
+				if journal {
+					inserted.push(key.clone());
+				}
+				target.put(columns::STATE, &key, value);
+				// The first state is written at once, it may not fit in a single transaction.
+				if target.is_full() {
+					target.commit(None)?;
+				}
+			},
+			Change::Deleted(key) =>
+				if journal {
+					deleted.push(key)
+				},
+		}
+		Ok(())
+	})?;
+	Ok((inserted, deleted))
+}
+
+/// Write the states of the non-canonical descendants of the canonical block with `lookup_key`.
+fn write_fork_states<Block: BlockT>(
+	source: &Source,
+	target: &mut Target,
+	lookup_key: &[u8],
+	root: DbHash,
+) -> ClientResult<()> {
+	let number = number_index_key(
+		u32::from_be_bytes(lookup_key[..NUMBER_LEN].try_into().expect("Lookup key is valid")) + 1,
+	)?;
+	let canonical_child = source.get(columns::KEY_LOOKUP, &number)?;
+	let mut blocks = vec![(lookup_key.to_vec(), root)];
+	while let Some((lookup_key, parent_root)) = blocks.pop() {
+		let mut children_key = meta_keys::CHILDREN_PREFIX.to_vec();
+		children_key.extend_from_slice(&lookup_key[NUMBER_LEN..]);
+		let Some(children) = source.get(columns::META, &children_key)? else { continue };
+		for child in Vec::<Block::Hash>::decode(&mut &children[..]).map_err(db_err)? {
+			let Some(child) = source.get(columns::KEY_LOOKUP, child.as_ref())? else { continue };
+			if Some(&child) == canonical_child.as_ref() {
+				continue
+			}
+			match source.state_root::<Block>(&child)? {
+				Some(root) if source.has_state::<Block>(&root)? => {
+					write_state::<Block>(source, target, Some(parent_root), root, false)?;
+					blocks.push((child, root));
+				},
+				_ => {},
+			}
+		}
+	}
+	Ok(())
+}
+
+/// Copy the remaining entries of the meta column, once everything they refer to is migrated.
+fn copy_meta(source: &Source, target: &mut Target) -> ClientResult<()> {
+	if let Some(Cursor::Done) = target.cursor(columns::META)? {
+		return Ok(())
+	}
+	for key in [
+		&meta_keys::TYPE[..],
+		meta_keys::BEST_BLOCK,
+		meta_keys::FINALIZED_BLOCK,
+		meta_keys::FINALIZED_STATE,
+		meta_keys::BLOCK_GAP,
+		meta_keys::GENESIS_HASH,
+		meta_keys::LEAF_PREFIX,
+	] {
+		if let Some(value) = source.get(columns::META, key)? {
+			target.put(columns::META, key, value);
+		}
+	}
+	target.commit(Some((columns::META, Cursor::Done)))
+}
+
+/// Compare every entry of the target database with the source database.
+fn verify_migration<Block: BlockT>(
+	source: &Source,
+	target: &kvdb_rocksdb::Database,
+) -> ClientResult<u64> {
+	info!("🔍 Verifying migrated database");
+	// State journals are compared by the hashes of their nodes.
+	let hashes = |keys: &mut dyn Iterator<Item = &Vec<u8>>| {
+		keys.map(|key| key[key.len().saturating_sub(DB_HASH_LEN)..].to_vec())
+			.collect::<HashSet<_>>()
+	};
+	let pruning_journals = sc_state_db::fetch_pruning_journals::<Block::Hash, Vec<u8>, _>(source)
+		.map_err(db_err)?
+		.into_iter()
+		.collect::<HashMap<_, _>>();
+	let non_canonical_journals =
+		sc_state_db::fetch_non_canonical_journals::<Block::Hash, Vec<u8>, _>(source)
+			.map_err(db_err)?
+			.into_iter()
+			.collect::<HashMap<_, _>>();
+
+	let mut verified = 0;
+	for column in 0..NUM_COLUMNS {
+		for entry in target.iter(column) {
+			let (key, value) = entry.map_err(db_err)?;
+			let matches = match column {
+				columns::META
+					if key.starts_with(meta_keys::MIGRATION_CURSOR) ||
+						&key[..] == meta_keys::MIGRATION_PROGRESS =>
+					continue,
+				// Reference counters are not visible through the ParityDB interface.
+				columns::TRANSACTION if key.len() == DB_HASH_LEN + 1 => continue,
+				columns::STATE =>
+					source.get(column, &key[key.len().saturating_sub(DB_HASH_LEN)..])? ==
+						Some(value),
+				columns::STATE_META =>
+					if let Some(expected) = pruning_journals.get(&key[..]) {
+						let record =
+							PruningJournalRecord::<Block::Hash, Vec<u8>>::decode(&mut &value[..])
+								.map_err(db_err)?;
+						record.hash == expected.hash &&
+							hashes(&mut record.deleted.iter()) ==
+								hashes(&mut expected.deleted.iter())
+					} else if let Some(expected) = non_canonical_journals.get(&key[..]) {
+						let record = NonCanonicalJournalRecord::<Block::Hash, Vec<u8>>::decode(
+							&mut &value[..],
+						)
+						.map_err(db_err)?;
+						record.hash == expected.hash &&
+							record.parent_hash == expected.parent_hash &&
+							hashes(&mut record.inserted.iter().map(|(key, _)| key)) ==
+								hashes(&mut expected.inserted.iter().map(|(key, _)| key)) &&
+							hashes(&mut record.deleted.iter()) ==
+								hashes(&mut expected.deleted.iter())
+					} else {
+						source.get(column, &key)? == Some(value)
+					},
+				_ => source.get(column, &key)? == Some(value),
+			};
+			if !matches {
+				return Err(ClientError::Backend(format!(
+					"Database migration verification failed for key 0x{} of column {}",
+					HexDisplay::from(&key.to_vec()),
+					column,
+				)))
+			}
+			verified += 1;
+		}
+	}
+	Ok(verified)
+}
@@ -40,6 +40,37 @@ pub fn open<H: Clone + AsRef<[u8]>>(
 	create: bool,
 	upgrade: bool,
 ) -> parity_db::Result<std::sync::Arc<dyn Database<H>>> {
+	let config = options(path, db_type)?;
+
+	if upgrade {
+		log::info!("Upgrading database metadata.");
+		if let Some(meta) = parity_db::Options::load_metadata(path)? {
+			config.write_metadata_with_version(path, &meta.salt, Some(meta.version))?;
+		}
+	}
+
+	let db = if create {
+		parity_db::Db::open_or_create(&config)?
+	} else {
+		parity_db::Db::open(&config)?
+	};
+
+	Ok(std::sync::Arc::new(DbAdapter(db)))
+}
+
+/// Open the parity-db database at `path` for reading, without the `sp_database::Database`
+/// adapter.
+pub(crate) fn open_read_only(
+	path: &std::path::Path,
+	db_type: DatabaseType,
+) -> parity_db::Result<parity_db::Db> {
+	parity_db::Db::open_read_only(&options(path, db_type)?)
+}
+
+/// Columns that keep their keys ordered, so that they can be iterated.
+pub(crate) const ORDERED_COLUMNS: [u32; 2] = [columns::AUX, columns::OFFCHAIN];
+
+fn options(path: &std::path::Path, db_type: DatabaseType) -> parity_db::Result<parity_db::Options> {
 	let mut config = parity_db::Options::with_columns(path, NUM_COLUMNS as u8);
 
 	match db_type {
@@ -67,23 +98,18 @@ pub fn open<H: Clone + AsRef<[u8]>>(
 			tx_col.ref_counted = true;
 			tx_col.preimage = true;
 			tx_col.uniform = true;
-		},
-	}
 
-	if upgrade {
-		log::info!("Upgrading database metadata.");
-		if let Some(meta) = parity_db::Options::load_metadata(path)? {
-			config.write_metadata_with_version(path, &meta.salt, Some(meta.version))?;
-		}
+			// Databases created before the ordered columns were introduced keep them hashed, the
+			// index type of a column can't be changed afterwards.
+			let existing = parity_db::Options::load_metadata(path)?;
+			for i in ORDERED_COLUMNS {
+				config.columns[i as usize].btree_index =
+					existing.as_ref().map_or(true, |meta| meta.columns[i as usize].btree_index);
+			}
+		},
 	}
 
-	let db = if create {
-		parity_db::Db::open_or_create(&config)?
-	} else {
-		parity_db::Db::open(&config)?
-	};
-
-	Ok(std::sync::Arc::new(DbAdapter(db)))
+	Ok(config)
 }
 
 fn ref_counted_column(col: u32) -> bool {
@@ -0,0 +1,194 @@
+// This file is part of Substrate.
+
+// Copyright (C) Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Differences between the trie nodes of two states.
+//!
+//! Databases that don't count references store every trie node under its path in the trie,
+//! followed by its hash. The nodes of both tries are visited in path order, and a subtree is
+//! skipped as soon as both tries have the same node at the same path.
+
+use crate::DbHash;
+use hash_db::Hasher;
+use sp_blockchain::{Error as ClientError, Result as ClientResult};
+use sp_core::storage::{ChildType, PrefixedStorageKey};
+use sp_trie::{empty_trie_root, LayoutV1, NodeCodec};
+use std::collections::BTreeMap;
+use trie_db::{
+	node::{NodeHandlePlan, NodePlan, ValuePlan},
+	NodeCodec as _,
+};
+
+/// Trie node that differs between two states.
+pub(crate) enum Change {
+	/// Node of the new state, with its value.
+	Inserted(Vec<u8>, Vec<u8>),
+	/// Node of the old state.
+	Deleted(Vec<u8>),
+}
+
+/// Index of the old and the new state in the pairs of nodes.
+const OLD: usize = 0;
+const NEW: usize = 1;
+
+/// Nodes of both states at the same trie path, keyed by the path nibbles and whether the node is a
+/// value node.
+type Frontier = BTreeMap<(Vec<u8>, bool), [Option<DbHash>; 2]>;
+
+/// Report the nodes of the state with root `new` that are not in the state with root `old` and the
+/// other way around to `on_change`, including the nodes of the child tries.
+///
+/// Node keys are prefixed with their trie path. `get` returns the node or value with the given key
+/// and hash.
+pub(crate) fn diff_states<H: Hasher>(
+	old: Option<DbHash>,
+	new: Option<DbHash>,
+	get: &dyn Fn(&[u8], &DbHash) -> ClientResult<Option<Vec<u8>>>,
+	on_change: &mut dyn FnMut(Change) -> ClientResult<()>,
+) -> ClientResult<()> {
+	let mut child_roots = BTreeMap::new();
+	diff_trie::<H>(&[], [old, new], get, on_change, Some(&mut child_roots))?;
+	for (keyspace, roots) in child_roots {
+		diff_trie::<H>(&keyspace, roots, get, on_change, None)?;
+	}
+	Ok(())
+}
+
+/// Root of the empty trie, which is not stored in the database.
+pub(crate) fn empty_root<H: Hasher>() -> DbHash {
+	DbHash::from_slice(empty_trie_root::<LayoutV1<H>>().as_ref())
+}
+
+fn diff_trie<H: Hasher>(
+	keyspace: &[u8],
+	roots: [Option<DbHash>; 2],
+	get: &dyn Fn(&[u8], &DbHash) -> ClientResult<Option<Vec<u8>>>,
+	on_change: &mut dyn FnMut(Change) -> ClientResult<()>,
+	mut child_roots: Option<&mut BTreeMap<Vec<u8>, [Option<DbHash>; 2]>>,
+) -> ClientResult<()> {
+	let empty_root = empty_root::<H>();
+	let mut frontier = Frontier::new();
+	frontier.insert((Vec::new(), false), roots.map(|root| root.filter(|root| *root != empty_root)));
+
+	// Children have longer paths than their parents, so both nodes at a path are known once it is
+	// the first one of the frontier.
+	while let Some(((path, is_value), nodes)) = frontier.pop_first() {
+		if nodes[OLD] == nodes[NEW] {
+			continue
+		}
+		for (side, hash) in nodes.into_iter().enumerate() {
+			let Some(hash) = hash else { continue };
+			let key = node_key(keyspace, &path, &hash);
+			let data = get(&key, &hash)?.ok_or_else(|| {
+				ClientError::Backend(format!("Trie node {:?} is missing from the database", hash))
+			})?;
+			if is_value {
+				if let Some(child_roots) = child_roots.as_deref_mut() {
+					note_child_root(child_roots, &path, side, &data)?;
+				}
+			} else {
+				expand::<H>(&path, &hash, &data, side, &mut frontier, child_roots.as_deref_mut())?;
+			}
+			on_change(if side == NEW {
+				Change::Inserted(key, data)
+			} else {
+				Change::Deleted(key)
+			})?;
+		}
+	}
+	Ok(())
+}
+
+/// Add the children and the value node of a trie node to the frontier.
+fn expand<H: Hasher>(
+	path: &[u8],
+	hash: &DbHash,
+	data: &[u8],
+	side: usize,
+	frontier: &mut Frontier,
+	child_roots: Option<&mut BTreeMap<Vec<u8>, [Option<DbHash>; 2]>>,
+) -> ClientResult<()> {
+	let invalid = |e: String| ClientError::Backend(format!("Invalid trie node {:?}: {}", hash, e));
+	let plan = NodeCodec::<H>::decode_plan(data).map_err(|e| invalid(format!("{:?}", e)))?;
+	let (partial, value, children) = match &plan {
+		NodePlan::Empty => return Ok(()),
+		NodePlan::Leaf { partial, value } => (Some(partial), Some(value), None),
+		NodePlan::Branch { value, children } => (None, value.as_ref(), Some(children)),
+		NodePlan::NibbledBranch { partial, value, children } =>
+			(Some(partial), value.as_ref(), Some(children)),
+		NodePlan::Extension { .. } => return Err(invalid("unexpected extension node".into())),
+	};
+
+	let mut full_path = path.to_vec();
+	if let Some(partial) = partial {
+		let partial = partial.build(data);
+		full_path.extend((0..partial.len()).map(|i| partial.at(i)));
+	}
+
+	match value {
+		Some(ValuePlan::Node(range)) => {
+			frontier.entry((full_path.clone(), true)).or_default()[side] =
+				Some(DbHash::from_slice(&data[range.clone()]));
+		},
+		Some(ValuePlan::Inline(range)) =>
+			if let Some(child_roots) = child_roots {
+				note_child_root(child_roots, &full_path, side, &data[range.clone()])?;
+			},
+		None => {},
+	}
+
+	// Inline children are too small to reference other nodes, they are not stored on their own.
+	for (nibble, child) in children.into_iter().flatten().enumerate() {
+		if let Some(NodeHandlePlan::Hash(range)) = child {
+			let mut child_path = full_path.clone();
+			child_path.push(nibble as u8);
+			frontier.entry((child_path, false)).or_default()[side] =
+				Some(DbHash::from_slice(&data[range.clone()]));
+		}
+	}
+	Ok(())
+}
+
+/// Record the root of the child trie stored at `path` of the main trie, if any.
+fn note_child_root(
+	child_roots: &mut BTreeMap<Vec<u8>, [Option<DbHash>; 2]>,
+	path: &[u8],
+	side: usize,
+	value: &[u8],
+) -> ClientResult<()> {
+	if path.len() % 2 != 0 {
+		return Ok(())
+	}
+	let key = path.chunks(2).map(|nibbles| (nibbles[0] << 4) | nibbles[1]).collect::<Vec<_>>();
+	let Some((_, keyspace)) = ChildType::from_prefixed_key(PrefixedStorageKey::new_ref(&key))
+	else {
+		return Ok(())
+	};
+	if value.len() != crate::DB_HASH_LEN {
+		return Err(ClientError::Backend(format!("Invalid child trie root at key {:?}", key)))
+	}
+	child_roots.entry(keyspace.to_vec()).or_default()[side] = Some(DbHash::from_slice(value));
+	Ok(())
+}
+
+/// Key of a trie node in a database that doesn't count references.
+fn node_key(keyspace: &[u8], path: &[u8], hash: &DbHash) -> Vec<u8> {
+	let mut key = keyspace.to_vec();
+	key.extend(path.chunks(2).map(|nibbles| (nibbles[0] << 4) | nibbles.get(1).unwrap_or(&0)));
+	key.extend_from_slice(hash.as_ref());
+	key
+}
@@ -56,6 +56,10 @@ pub mod meta_keys {
 	pub const LEAF_PREFIX: &[u8; 4] = b"leaf";
 	/// Children prefix list key.
 	pub const CHILDREN_PREFIX: &[u8; 8] = b"children";
+	/// Progress of an unfinished database migration.
+	pub const MIGRATION_PROGRESS: &[u8; 18] = b"migration_progress";
+	/// Migration position within a column, followed by the encoded column.
+	pub const MIGRATION_CURSOR: &[u8; 16] = b"migration_cursor";
 }
 
 /// Database metadata.
@@ -213,6 +217,9 @@ fn open_database_at<Block: BlockT>(
 	};
 
 	check_database_type(&*db, db_type)?;
+	if db.contains(COLUMN_META, meta_keys::MIGRATION_PROGRESS) {
+		return Err(OpenDbError::Internal("Database migration has not completed".into()))
+	}
 	Ok(db)
 }
 
@@ -0,0 +1,423 @@
+// This file is part of Substrate.
+
+// Copyright (C) Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! On-disk cache of compiled runtime artifacts.
+//!
+//! Compiling a runtime takes a considerable amount of time, which is spent again on every node
+//! start and for every runtime version the node encounters. To avoid this the artifacts produced
+//! by the compilation are stored in a directory and reused for as long as they were compiled from
+//! the same code, by the same version of wasmtime and with the same semantics.
+//!
+//! Every artifact is stored in its own file alongside a checksum of its contents. Artifacts that
+//! fail validation are removed and the runtime is compiled again, so a damaged cache directory
+//! only costs time. Once the artifacts in the directory exceed the configured size the least
+//! recently used ones are removed.
+//!
+//! The checksum only detects accidental corruption: anyone able to write to the directory can
+//! store a matching checksum alongside an artifact of their choice, which is then executed as
+//! native code by the node. The directory must therefore only be writable by the node. On unix
+//! it is created accessible to its owner only, and a directory that isn't owned by the node's
+//! user or that is writable by its group or others is not used at all.
+
+use filetime::FileTime;
+use sp_core::{blake2_256, hexdisplay::HexDisplay};
+use std::{
+	fs,
+	io::Write,
+	path::{Path, PathBuf},
+	sync::atomic::{AtomicU64, Ordering},
+	time::SystemTime,
+};
+
+/// Magic bytes at the start of every artifact file, bumped whenever the file format changes.
+const MAGIC: &[u8; 8] = b"scartf01";
+
+/// Size of the file header, i.e. the magic bytes followed by the checksum of the artifact.
+const HEADER_LEN: usize = MAGIC.len() + 32;
+
+/// Extension of the artifact files.
+const ARTIFACT_EXTENSION: &str = "artifact";
+
+/// Log target for this module.
+const LOG_TARGET: &str = "wasm-runtime";
+
+/// Used to give temporary files unique names, as multiple executors may share a directory.
+static TMP_FILE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Configuration of the on-disk cache of compiled runtime artifacts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuntimeArtifactCacheConfig {
+	/// The directory the artifacts are stored in.
+	pub path: PathBuf,
+	/// The maximum size in bytes of all artifacts stored in the directory.
+	pub max_size: u64,
+}
+
+/// The on-disk cache of compiled runtime artifacts.
+pub(crate) struct ArtifactCache {
+	config: RuntimeArtifactCacheConfig,
+}
+
+impl ArtifactCache {
+	/// Creates the cache with the given `config`.
+	pub fn new(config: RuntimeArtifactCacheConfig) -> Self {
+		Self { config }
+	}
+
+	/// Returns the key an artifact of `code` is stored under.
+	///
+	/// `fingerprint` should identify everything else that went into the artifact, see
+	/// [`sc_executor_wasmtime::runtime_artifact_fingerprint`].
+	pub fn key(code: &[u8], fingerprint: &[u8]) -> [u8; 32] {
+		blake2_256(&[&blake2_256(code)[..], fingerprint].concat())
+	}
+
+	fn artifact_path(&self, key: &[u8; 32]) -> PathBuf {
+		self.config
+			.path
+			.join(format!("{}.{}", HexDisplay::from(key), ARTIFACT_EXTENSION))
+	}
+
+	/// Loads the artifact stored under `key`.
+	///
+	/// Returns `None` if there is no such artifact or it failed validation, in which case it is
+	/// removed from the cache. Loading an artifact marks it as recently used.
+	pub fn load(&self, key: &[u8; 32]) -> Option<Vec<u8>> {
+		match check_private_dir(&self.config.path) {
+			Ok(()) => (),
+			Err(err) if err.kind() == std::io::ErrorKind::NotFound => return None,
+			Err(err) => {
+				tracing::warn!(
+					target: LOG_TARGET,
+					path = %self.config.path.display(),
+					error = %err,
+					"Not loading cached runtime artifact",
+				);
+				return None
+			},
+		}
+
+		let path = self.artifact_path(key);
+		let mut contents = match fs::read(&path) {
+			Ok(contents) => contents,
+			Err(err) => {
+				if err.kind() != std::io::ErrorKind::NotFound {
+					tracing::warn!(
+						target: LOG_TARGET,
+						path = %path.display(),
+						error = %err,
+						"Failed to read cached runtime artifact",
+					);
+				}
+				return None
+			},
+		};
+
+		let valid = contents.len() >= HEADER_LEN &&
+			contents[..MAGIC.len()] == MAGIC[..] &&
+			contents[MAGIC.len()..HEADER_LEN] == blake2_256(&contents[HEADER_LEN..])[..];
+		if !valid {
+			tracing::warn!(
+				target: LOG_TARGET,
+				path = %path.display(),
+				"Removing invalid cached runtime artifact",
+			);
+			self.remove(key);
+			return None
+		}
+
+		// The modification time tracks the last use of the artifact, see `prune`.
+		if let Err(err) = filetime::set_file_mtime(&path, FileTime::now()) {
+			tracing::debug!(
+				target: LOG_TARGET,
+				path = %path.display(),
+				error = %err,
+				"Failed to mark cached runtime artifact as used",
+			);
+		}
+
+		contents.drain(..HEADER_LEN);
+		Some(contents)
+	}
+
+	/// Removes the artifact stored under `key`, if any.
+	pub fn remove(&self, key: &[u8; 32]) {
+		let path = self.artifact_path(key);
+		if let Err(err) = fs::remove_file(&path) {
+			if err.kind() != std::io::ErrorKind::NotFound {
+				tracing::warn!(
+					target: LOG_TARGET,
+					path = %path.display(),
+					error = %err,
+					"Failed to remove cached runtime artifact",
+				);
+			}
+		}
+	}
+
+	/// Stores `artifact` under `key` and evicts the least recently used artifacts if the cache
+	/// grows too big.
+	///
+	/// Failing to store the artifact is not fatal and only logged.
+	pub fn store(&self, key: &[u8; 32], artifact: &[u8]) {
+		if let Err(err) = self.write_artifact(key, artifact) {
+			tracing::warn!(
+				target: LOG_TARGET,
+				path = %self.config.path.display(),
+				error = %err,
+				"Failed to cache runtime artifact",
+			);
+			return
+		}
+
+		if let Err(err) = self.prune() {
+			tracing::warn!(
+				target: LOG_TARGET,
+				path = %self.config.path.display(),
+				error = %err,
+				"Failed to prune runtime artifact cache",
+			);
+		}
+	}
+
+	fn write_artifact(&self, key: &[u8; 32], artifact: &[u8]) -> std::io::Result<()> {
+		create_private_dir(&self.config.path)?;
+		check_private_dir(&self.config.path)?;
+
+		// Write to a temporary file first and move it into place afterwards, so that a reader never
+		// observes a partially written artifact.
+		let path = self.artifact_path(key);
+		let tmp_path = path.with_extension(format!(
+			"tmp-{}-{}",
+			std::process::id(),
+			TMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed),
+		));
+
+		let result = (|| {
+			let mut file = fs::File::create(&tmp_path)?;
+			file.write_all(MAGIC)?;
+			file.write_all(&blake2_256(artifact))?;
+			file.write_all(artifact)?;
+			file.sync_all()?;
+			fs::rename(&tmp_path, &path)
+		})();
+
+		if result.is_err() {
+			let _ = fs::remove_file(&tmp_path);
+		}
+
+		result
+	}
+
+	/// Removes the least recently used artifacts until their total size doesn't exceed the
+	/// configured maximum.
+	///
+	/// An artifact was last used when it was stored or last loaded, whichever is the most recent,
+	/// which is recorded as the modification time of its file.
+	fn prune(&self) -> std::io::Result<()> {
+		let mut artifacts = Vec::new();
+		let mut total_size = 0;
+
+		for entry in fs::read_dir(&self.config.path)? {
+			let entry = entry?;
+			let path = entry.path();
+			if path.extension().map_or(true, |ext| ext != ARTIFACT_EXTENSION) {
+				continue
+			}
+
+			let metadata = entry.metadata()?;
+			total_size += metadata.len();
+			artifacts.push((
+				metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+				metadata.len(),
+				path,
+			));
+		}
+
+		if total_size <= self.config.max_size {
+			return Ok(())
+		}
+
+		artifacts.sort_by(|a, b| a.0.cmp(&b.0));
+		for (_, size, path) in artifacts {
+			if total_size <= self.config.max_size {
+				break
+			}
+
+			remove_evicted(&path);
+			total_size = total_size.saturating_sub(size);
+		}
+
+		Ok(())
+	}
+}
+
+/// Creates the directory at `path` and its missing parents, accessible to their owner only.
+fn create_private_dir(path: &Path) -> std::io::Result<()> {
+	let mut builder = fs::DirBuilder::new();
+	builder.recursive(true);
+
+	#[cfg(target_family = "unix")]
+	{
+		use std::os::unix::fs::DirBuilderExt;
+		builder.mode(0o700);
+	}
+
+	builder.create(path)
+}
+
+/// Checks that nobody but the node's user can modify the contents of the directory at `path`.
+///
+/// Doesn't check anything on platforms other than unix.
+fn check_private_dir(path: &Path) -> std::io::Result<()> {
+	let metadata = fs::metadata(path)?;
+	if !metadata.is_dir() {
+		return Err(std::io::Error::new(std::io::ErrorKind::Other, "not a directory"))
+	}
+
+	#[cfg(target_family = "unix")]
+	{
+		use std::os::unix::fs::MetadataExt;
+
+		// SAFETY: `geteuid` is always successful and has no side effects.
+		if metadata.uid() != unsafe { libc::geteuid() } {
+			return Err(std::io::Error::new(
+				std::io::ErrorKind::PermissionDenied,
+				"the directory is owned by another user",
+			))
+		}
+
+		if metadata.mode() & 0o022 != 0 {
+			return Err(std::io::Error::new(
+				std::io::ErrorKind::PermissionDenied,
+				"the directory is writable by its group or others",
+			))
+		}
+	}
+
+	Ok(())
+}
+
+fn remove_evicted(path: &Path) {
+	tracing::debug!(target: LOG_TARGET, path = %path.display(), "Evicting cached runtime artifact");
+
+	if let Err(err) = fs::remove_file(path) {
+		tracing::warn!(
+			target: LOG_TARGET,
+			path = %path.display(),
+			error = %err,
+			"Failed to evict cached runtime artifact",
+		);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn cache(dir: &tempfile::TempDir, max_size: u64) -> ArtifactCache {
+		ArtifactCache::new(RuntimeArtifactCacheConfig {
+			path: dir.path().join("artifacts"),
+			max_size,
+		})
+	}
+
+	#[test]
+	fn stored_artifact_can_be_loaded() {
+		let dir = tempfile::tempdir().unwrap();
+		let cache = cache(&dir, u64::MAX);
+		let key = ArtifactCache::key(b"code", b"fingerprint");
+
+		assert_eq!(cache.load(&key), None);
+		cache.store(&key, b"artifact");
+		assert_eq!(cache.load(&key), Some(b"artifact".to_vec()));
+
+		let other_key = ArtifactCache::key(b"code", b"other fingerprint");
+		assert_ne!(key, other_key);
+		assert_eq!(cache.load(&other_key), None);
+	}
+
+	#[test]
+	fn corrupted_artifact_is_removed() {
+		let dir = tempfile::tempdir().unwrap();
+		let cache = cache(&dir, u64::MAX);
+		let key = ArtifactCache::key(b"code", b"fingerprint");
+
+		cache.store(&key, b"artifact");
+		let path = cache.artifact_path(&key);
+		let mut contents = fs::read(&path).unwrap();
+		*contents.last_mut().unwrap() ^= 0xff;
+		fs::write(&path, contents).unwrap();
+
+		assert_eq!(cache.load(&key), None);
+		assert!(!path.exists());
+
+		// Truncated files are rejected as well.
+		cache.store(&key, b"artifact");
+		fs::write(&path, &MAGIC[..]).unwrap();
+		assert_eq!(cache.load(&key), None);
+		assert!(!path.exists());
+	}
+
+	#[test]
+	#[cfg(target_family = "unix")]
+	fn directory_writable_by_others_is_not_used() {
+		use std::os::unix::fs::PermissionsExt;
+
+		let dir = tempfile::tempdir().unwrap();
+		let cache = cache(&dir, u64::MAX);
+		let key = ArtifactCache::key(b"code", b"fingerprint");
+
+		cache.store(&key, b"artifact");
+		let permissions = fs::metadata(&cache.config.path).unwrap().permissions();
+		assert_eq!(permissions.mode() & 0o777, 0o700);
+		assert_eq!(cache.load(&key), Some(b"artifact".to_vec()));
+
+		fs::set_permissions(&cache.config.path, fs::Permissions::from_mode(0o777)).unwrap();
+		assert_eq!(cache.load(&key), None);
+		// The artifact isn't removed either, the directory isn't ours to manage.
+		assert!(cache.artifact_path(&key).exists());
+
+		let other_key = ArtifactCache::key(b"other code", b"fingerprint");
+		cache.store(&other_key, b"artifact");
+		assert!(!cache.artifact_path(&other_key).exists());
+	}
+
+	#[test]
+	fn least_recently_used_artifacts_are_evicted() {
+		let dir = tempfile::tempdir().unwrap();
+		let artifact = [0u8; 100];
+		let cache = cache(&dir, 2 * (HEADER_LEN + artifact.len()) as u64);
+		let keys = (0u8..3).map(|i| ArtifactCache::key(&[i], b"fingerprint")).collect::<Vec<_>>();
+		// Make sure the modification times differ.
+		let wait = || std::thread::sleep(std::time::Duration::from_millis(20));
+
+		cache.store(&keys[0], &artifact);
+		wait();
+		cache.store(&keys[1], &artifact);
+		wait();
+		// The first artifact is used again, the second one is now the least recently used.
+		assert_eq!(cache.load(&keys[0]), Some(artifact.to_vec()));
+		wait();
+		cache.store(&keys[2], &artifact);
+
+		assert_eq!(cache.load(&keys[1]), None);
+		assert_eq!(cache.load(&keys[0]), Some(artifact.to_vec()));
+		assert_eq!(cache.load(&keys[2]), Some(artifact.to_vec()));
+	}
+}
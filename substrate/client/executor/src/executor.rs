@@ -18,8 +18,8 @@
 
 use crate::{
 	error::{Error, Result},
-	wasm_runtime::{RuntimeCache, WasmExecutionMethod},
-	RuntimeVersionOf,
+	wasm_runtime::{ExecutionLimits, RuntimeCache, WasmExecutionMethod},
+	DeterministicStackLimit, RuntimeArtifactCacheConfig, RuntimeVersionOf,
 };
 
 use std::{
@@ -93,6 +93,8 @@ pub struct WasmExecutorBuilder<H> {
 	cache_path: Option<PathBuf>,
 	allow_missing_host_functions: bool,
 	runtime_cache_size: u8,
+	execution_limits: ExecutionLimits,
+	artifact_cache: Option<RuntimeArtifactCacheConfig>,
 }
 
 impl<H> WasmExecutorBuilder<H> {
@@ -110,6 +112,8 @@ impl<H> WasmExecutorBuilder<H> {
 			runtime_cache_size: 4,
 			allow_missing_host_functions: false,
 			cache_path: None,
+			execution_limits: Default::default(),
+			artifact_cache: None,
 		}
 	}
 
@@ -193,6 +197,45 @@ impl<H> WasmExecutorBuilder<H> {
 		self
 	}
 
+	/// Create the wasm executor with the given on-disk cache of compiled runtimes.
+	///
+	/// Compiled runtimes are stored in the configured directory and reused, also across restarts
+	/// of the node, instead of compiling the same runtime again. Artifacts are keyed by the code,
+	/// the wasmtime version and the semantics they were compiled with and are validated when they
+	/// are loaded.
+	///
+	/// By default compiled runtimes are not stored on disk.
+	pub fn with_artifact_cache(
+		mut self,
+		artifact_cache: Option<RuntimeArtifactCacheConfig>,
+	) -> Self {
+		self.artifact_cache = artifact_cache;
+		self
+	}
+
+	/// Create the wasm executor with the given deterministic stack limit.
+	///
+	/// Runtimes are instrumented to trap once the logical stack depth exceeds the limit, so that
+	/// a call overflows its stack at the same point on every machine. See
+	/// [`DeterministicStackLimit`] for details.
+	///
+	/// By default the stack is only limited by the native stack of the executing thread.
+	pub fn with_deterministic_stack_limit(mut self, stack_limit: DeterministicStackLimit) -> Self {
+		self.execution_limits.deterministic_stack_limit = Some(stack_limit);
+		self
+	}
+
+	/// Create the wasm executor with the given `fuel_limit`.
+	///
+	/// Every runtime call gets `fuel_limit` units of fuel, roughly one per executed wasm
+	/// instruction, and traps once they are used up.
+	///
+	/// By default the number of executed instructions isn't limited.
+	pub fn with_fuel_limit(mut self, fuel_limit: u64) -> Self {
+		self.execution_limits.fuel_limit = Some(fuel_limit);
+		self
+	}
+
 	/// Build the configured [`WasmExecutor`].
 	pub fn build(self) -> WasmExecutor<H> {
 		WasmExecutor {
@@ -208,9 +251,12 @@ impl<H> WasmExecutorBuilder<H> {
 				self.max_runtime_instances,
 				self.cache_path.clone(),
 				self.runtime_cache_size,
+				self.execution_limits,
+				self.artifact_cache,
 			)),
 			cache_path: self.cache_path,
 			allow_missing_host_functions: self.allow_missing_host_functions,
+			execution_limits: self.execution_limits,
 			phantom: PhantomData,
 		}
 	}
@@ -234,6 +280,8 @@ pub struct WasmExecutor<H> {
 	cache_path: Option<PathBuf>,
 	/// Ignore missing function imports.
 	allow_missing_host_functions: bool,
+	/// The limits applied to runtime calls.
+	execution_limits: ExecutionLimits,
 	phantom: PhantomData<H>,
 }
 
@@ -247,6 +295,7 @@ impl<H> Clone for WasmExecutor<H> {
 			cache: self.cache.clone(),
 			cache_path: self.cache_path.clone(),
 			allow_missing_host_functions: self.allow_missing_host_functions,
+			execution_limits: self.execution_limits,
 			phantom: self.phantom,
 		}
 	}
@@ -295,9 +344,12 @@ where
 				max_runtime_instances,
 				cache_path.clone(),
 				runtime_cache_size,
+				Default::default(),
+				None,
 			)),
 			cache_path,
 			allow_missing_host_functions: false,
+			execution_limits: Default::default(),
 			phantom: PhantomData,
 		}
 	}
@@ -422,6 +474,7 @@ where
 			runtime_blob,
 			allow_missing_host_functions,
 			self.cache_path.as_deref(),
+			self.execution_limits,
 		)
 		.map_err(|e| format!("Failed to create module: {}", e))?;
 
@@ -101,6 +101,21 @@ fn call_in_wasm<E: Externalities>(
 	)
 }
 
+fn call_in_wasm_with_executor(
+	executor: crate::WasmExecutor<HostFunctions>,
+	function: &str,
+	call_data: &[u8],
+	ext: &mut dyn Externalities,
+) -> Result<Vec<u8>, Error> {
+	executor.uncached_call(
+		RuntimeBlob::uncompress_if_needed(wasm_binary_unwrap()).unwrap(),
+		ext,
+		true,
+		function,
+		call_data,
+	)
+}
+
 test_wasm_execution!(returning_should_work);
 fn returning_should_work(wasm_method: WasmExecutionMethod) {
 	let mut ext = TestExternalities::default();
@@ -464,6 +479,7 @@ fn mk_test_runtime(
 		blob,
 		true,
 		None,
+		Default::default(),
 	)
 	.expect("failed to instantiate wasm runtime")
 }
@@ -690,6 +706,7 @@ fn memory_is_cleared_between_invocations(wasm_method: WasmExecutionMethod) {
 		RuntimeBlob::uncompress_if_needed(&binary[..]).unwrap(),
 		true,
 		None,
+		Default::default(),
 	)
 	.unwrap();
 
@@ -810,3 +827,114 @@ fn return_overflow(wasm_method: WasmExecutionMethod) {
 		error => panic!("unexpected error: {:?}", error),
 	}
 }
+
+test_wasm_execution!(fuel_limit_should_work);
+fn fuel_limit_should_work(wasm_method: WasmExecutionMethod) {
+	let mut ext = TestExternalities::default();
+	let mut ext = ext.ext();
+	let input = vec![7u8; 1024].encode();
+	let executor = |fuel_limit| {
+		crate::WasmExecutor::<HostFunctions>::builder()
+			.with_execution_method(wasm_method)
+			.with_fuel_limit(fuel_limit)
+			.build()
+	};
+
+	let output =
+		call_in_wasm_with_executor(executor(10_000_000), "test_blake2_256", &input, &mut ext)
+			.unwrap();
+	assert_eq!(output, blake2_256(&vec![7u8; 1024]).to_vec().encode());
+
+	match call_in_wasm_with_executor(executor(100), "test_blake2_256", &input, &mut ext)
+		.unwrap_err()
+	{
+		Error::AbortedDueToTrap(error) => {
+			assert_eq!(error.message, "wasm trap: all fuel consumed by WebAssembly");
+		},
+		error => panic!("unexpected error: {:?}", error),
+	}
+}
+
+test_wasm_execution!(deterministic_stack_limit_should_work);
+fn deterministic_stack_limit_should_work(wasm_method: WasmExecutionMethod) {
+	let mut ext = TestExternalities::default();
+	let mut ext = ext.ext();
+	let input = vec![7u8; 1024].encode();
+	let executor = |logical_max| {
+		crate::WasmExecutor::<HostFunctions>::builder()
+			.with_execution_method(wasm_method)
+			.with_deterministic_stack_limit(crate::DeterministicStackLimit {
+				logical_max,
+				native_stack_max: 256 * 1024 * 1024,
+			})
+			.build()
+	};
+
+	let output =
+		call_in_wasm_with_executor(executor(65536), "test_blake2_256", &input, &mut ext).unwrap();
+	assert_eq!(output, blake2_256(&vec![7u8; 1024]).to_vec().encode());
+
+	match call_in_wasm_with_executor(executor(8), "test_blake2_256", &input, &mut ext).unwrap_err()
+	{
+		Error::AbortedDueToTrap(error) => {
+			assert_eq!(error.message, "wasm trap: wasm `unreachable` instruction executed");
+		},
+		error => panic!("unexpected error: {:?}", error),
+	}
+}
+
+test_wasm_execution!(artifact_cache_should_work);
+fn artifact_cache_should_work(wasm_method: WasmExecutionMethod) {
+	let mut ext = TestExternalities::default();
+	let mut ext = ext.ext();
+	let dir = tempfile::tempdir().unwrap();
+	let input = vec![7u8; 32].encode();
+	let expected = blake2_256(&vec![7u8; 32]).to_vec().encode();
+
+	let code_fetcher = sp_core::traits::WrappedRuntimeCode(wasm_binary_unwrap().into());
+	let runtime_code = sp_core::traits::RuntimeCode {
+		code_fetcher: &code_fetcher,
+		heap_pages: None,
+		hash: vec![1],
+	};
+	let artifacts = || {
+		std::fs::read_dir(dir.path())
+			.unwrap()
+			.map(|entry| entry.unwrap().path())
+			.collect::<Vec<_>>()
+	};
+	let call = |ext: &mut dyn Externalities| {
+		// A fresh executor doesn't have the runtime in its in-memory cache.
+		crate::WasmExecutor::<HostFunctions>::builder()
+			.with_execution_method(wasm_method)
+			.with_artifact_cache(Some(crate::RuntimeArtifactCacheConfig {
+				path: dir.path().to_owned(),
+				max_size: u64::MAX,
+			}))
+			.build()
+			.with_instance(
+				&runtime_code,
+				ext,
+				crate::DEFAULT_HEAP_ALLOC_STRATEGY,
+				|_, mut instance, _, _| Ok(instance.call_export("test_blake2_256", &input)),
+			)
+			.unwrap()
+	};
+
+	// The first executor compiles the runtime and stores the artifact.
+	assert_eq!(call(&mut ext), expected);
+	let stored = artifacts();
+	assert_eq!(stored.len(), 1);
+	let artifact = std::fs::read(&stored[0]).unwrap();
+
+	// The second one reuses it.
+	assert_eq!(call(&mut ext), expected);
+	assert_eq!(artifacts(), stored);
+	assert_eq!(std::fs::read(&stored[0]).unwrap(), artifact);
+
+	// A damaged artifact is replaced by a freshly compiled one.
+	std::fs::write(&stored[0], b"garbage").unwrap();
+	assert_eq!(call(&mut ext), expected);
+	assert_eq!(artifacts(), stored);
+	assert_eq!(std::fs::read(&stored[0]).unwrap(), artifact);
+}
@@ -31,6 +31,7 @@
 #![warn(missing_docs)]
 #![recursion_limit = "128"]
 
+mod artifact_cache;
 #[macro_use]
 mod executor;
 #[cfg(test)]
@@ -38,6 +39,7 @@ mod integration_tests;
 mod wasm_runtime;
 
 pub use self::{
+	artifact_cache::RuntimeArtifactCacheConfig,
 	executor::{
 		with_externalities_safe, NativeElseWasmExecutor, NativeExecutionDispatch, WasmExecutor,
 	},
@@ -54,7 +56,9 @@ pub use sc_executor_common::{
 	error,
 	wasm_runtime::{HeapAllocStrategy, DEFAULT_HEAP_ALLOC_PAGES, DEFAULT_HEAP_ALLOC_STRATEGY},
 };
-pub use sc_executor_wasmtime::InstantiationStrategy as WasmtimeInstantiationStrategy;
+pub use sc_executor_wasmtime::{
+	DeterministicStackLimit, InstantiationStrategy as WasmtimeInstantiationStrategy,
+};
 
 /// Extracts the runtime version of a given runtime code.
 pub trait RuntimeVersionOf {
@@ -21,7 +21,10 @@
 //! The primary means of accessing the runtimes is through a cache which saves the reusable
 //! components of the runtime that are expensive to initialize.
 
-use crate::error::{Error, WasmError};
+use crate::{
+	artifact_cache::{ArtifactCache, RuntimeArtifactCacheConfig},
+	error::{Error, WasmError},
+};
 
 use codec::Decode;
 use parking_lot::Mutex;
@@ -58,6 +61,16 @@ impl Default for WasmExecutionMethod {
 	}
 }
 
+/// Limits making runtime calls run out of resources at the same point on every machine.
+#[derive(Debug, Default, PartialEq, Eq, Hash, Copy, Clone)]
+pub struct ExecutionLimits {
+	/// Deterministic limit of the wasm stack depth, see [`sc_executor_wasmtime::Semantics`].
+	pub deterministic_stack_limit: Option<sc_executor_wasmtime::DeterministicStackLimit>,
+	/// Limit of the instructions executed by a runtime call, see
+	/// [`sc_executor_wasmtime::Semantics`].
+	pub fuel_limit: Option<u64>,
+}
+
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
 struct VersionedRuntimeId {
 	/// Runtime code hash.
@@ -167,6 +180,10 @@ pub struct RuntimeCache {
 	/// The size of the instances cache for each runtime.
 	max_runtime_instances: usize,
 	cache_path: Option<PathBuf>,
+	/// The limits applied to all runtimes of this cache.
+	execution_limits: ExecutionLimits,
+	/// The on-disk cache of compiled runtimes.
+	artifact_cache: Option<ArtifactCache>,
 }
 
 impl RuntimeCache {
@@ -178,15 +195,28 @@ impl RuntimeCache {
 	/// `cache_path` allows to specify an optional directory where the executor can store files
 	/// for caching.
 	///
+	/// `execution_limits` are applied to every runtime created by this cache.
+	///
+	/// `artifact_cache` configures an optional on-disk cache of compiled runtimes, which are then
+	/// reused instead of compiling the same runtime again.
+	///
 	/// `runtime_cache_size` specifies the number of different runtimes versions preserved in an
 	/// in-memory cache, must always be at least 1.
 	pub fn new(
 		max_runtime_instances: usize,
 		cache_path: Option<PathBuf>,
 		runtime_cache_size: u8,
+		execution_limits: ExecutionLimits,
+		artifact_cache: Option<RuntimeArtifactCacheConfig>,
 	) -> RuntimeCache {
 		let cap = ByLength::new(runtime_cache_size.max(1) as u32);
-		RuntimeCache { runtimes: Mutex::new(LruMap::new(cap)), max_runtime_instances, cache_path }
+		RuntimeCache {
+			runtimes: Mutex::new(LruMap::new(cap)),
+			max_runtime_instances,
+			cache_path,
+			execution_limits,
+			artifact_cache: artifact_cache.map(ArtifactCache::new),
+		}
 	}
 
 	/// Prepares a WASM module instance and executes given function for it.
@@ -255,6 +285,8 @@ impl RuntimeCache {
 				allow_missing_func_imports,
 				self.max_runtime_instances,
 				self.cache_path.as_deref(),
+				self.execution_limits,
+				self.artifact_cache.as_ref(),
 			);
 
 			match result {
@@ -293,6 +325,7 @@ pub fn create_wasm_runtime_with_code<H>(
 	blob: RuntimeBlob,
 	allow_missing_func_imports: bool,
 	cache_path: Option<&Path>,
+	execution_limits: ExecutionLimits,
 ) -> Result<Box<dyn WasmModule>, WasmError>
 where
 	H: HostFunctions,
@@ -301,26 +334,100 @@ where
 		WasmExecutionMethod::Compiled { instantiation_strategy } =>
 			sc_executor_wasmtime::create_runtime::<H>(
 				blob,
-				sc_executor_wasmtime::Config {
+				wasmtime_config(
+					heap_alloc_strategy,
+					instantiation_strategy,
 					allow_missing_func_imports,
-					cache_path: cache_path.map(ToOwned::to_owned),
-					semantics: sc_executor_wasmtime::Semantics {
-						heap_alloc_strategy,
-						instantiation_strategy,
-						deterministic_stack_limit: None,
-						canonicalize_nans: false,
-						parallel_compilation: true,
-						wasm_multi_value: false,
-						wasm_bulk_memory: false,
-						wasm_reference_types: false,
-						wasm_simd: false,
-					},
-				},
+					cache_path,
+					execution_limits,
+				),
 			)
 			.map(|runtime| -> Box<dyn WasmModule> { Box::new(runtime) }),
 	}
 }
 
+/// Create a wasm runtime with the given `code`, reusing the compiled artifact stored in
+/// `artifact_cache` if there is one and storing it there otherwise.
+fn create_wasm_runtime_with_artifact_cache<H>(
+	wasm_method: WasmExecutionMethod,
+	heap_alloc_strategy: HeapAllocStrategy,
+	code: &[u8],
+	blob: RuntimeBlob,
+	allow_missing_func_imports: bool,
+	cache_path: Option<&Path>,
+	execution_limits: ExecutionLimits,
+	artifact_cache: &ArtifactCache,
+) -> Result<Box<dyn WasmModule>, WasmError>
+where
+	H: HostFunctions,
+{
+	let WasmExecutionMethod::Compiled { instantiation_strategy } = wasm_method;
+	let config = wasmtime_config(
+		heap_alloc_strategy,
+		instantiation_strategy,
+		allow_missing_func_imports,
+		cache_path,
+		execution_limits,
+	);
+
+	let fingerprint = sc_executor_wasmtime::runtime_artifact_fingerprint(&config.semantics)?;
+	let key = ArtifactCache::key(code, &fingerprint);
+
+	if let Some(artifact) = artifact_cache.load(&key) {
+		// SAFETY: The checksum verified when loading the artifact only detects corruption, it
+		//         doesn't prove the artifact was produced by `prepare_runtime_artifact`. That
+		//         relies on the cache directory being writable by this node only, which
+		//         `ArtifactCache` checks on unix before loading anything from it. Artifacts
+		//         compiled by another version of wasmtime or with other settings are rejected by
+		//         wasmtime.
+		match unsafe {
+			sc_executor_wasmtime::create_runtime_from_artifact_bytes::<H>(&artifact, config.clone())
+		} {
+			Ok(runtime) => return Ok(Box::new(runtime)),
+			Err(err) => {
+				tracing::warn!(
+					target: "wasm-runtime",
+					error = ?err,
+					"Cannot create a runtime from the cached artifact, compiling it again",
+				);
+				artifact_cache.remove(&key);
+			},
+		}
+	}
+
+	let artifact = sc_executor_wasmtime::prepare_runtime_artifact(blob, &config.semantics)?;
+	artifact_cache.store(&key, &artifact);
+
+	// SAFETY: The artifact was just produced by `prepare_runtime_artifact`.
+	unsafe { sc_executor_wasmtime::create_runtime_from_artifact_bytes::<H>(&artifact, config) }
+		.map(|runtime| -> Box<dyn WasmModule> { Box::new(runtime) })
+}
+
+fn wasmtime_config(
+	heap_alloc_strategy: HeapAllocStrategy,
+	instantiation_strategy: sc_executor_wasmtime::InstantiationStrategy,
+	allow_missing_func_imports: bool,
+	cache_path: Option<&Path>,
+	execution_limits: ExecutionLimits,
+) -> sc_executor_wasmtime::Config {
+	sc_executor_wasmtime::Config {
+		allow_missing_func_imports,
+		cache_path: cache_path.map(ToOwned::to_owned),
+		semantics: sc_executor_wasmtime::Semantics {
+			heap_alloc_strategy,
+			instantiation_strategy,
+			deterministic_stack_limit: execution_limits.deterministic_stack_limit,
+			fuel_limit: execution_limits.fuel_limit,
+			canonicalize_nans: false,
+			parallel_compilation: true,
+			wasm_multi_value: false,
+			wasm_bulk_memory: false,
+			wasm_reference_types: false,
+			wasm_simd: false,
+		},
+	}
+}
+
 fn decode_version(mut version: &[u8]) -> Result<RuntimeVersion, WasmError> {
 	Decode::decode(&mut version).map_err(|_| {
 		WasmError::Instantiation(
@@ -385,6 +492,8 @@ fn create_versioned_wasm_runtime<H>(
 	allow_missing_func_imports: bool,
 	max_instances: usize,
 	cache_path: Option<&Path>,
+	execution_limits: ExecutionLimits,
+	artifact_cache: Option<&ArtifactCache>,
 ) -> Result<VersionedRuntime, WasmError>
 where
 	H: HostFunctions,
@@ -398,13 +507,26 @@ where
 	// runtime.
 	let mut version = read_embedded_version(&blob)?;
 
-	let runtime = create_wasm_runtime_with_code::<H>(
-		wasm_method,
-		heap_alloc_strategy,
-		blob,
-		allow_missing_func_imports,
-		cache_path,
-	)?;
+	let runtime = match artifact_cache {
+		Some(artifact_cache) => create_wasm_runtime_with_artifact_cache::<H>(
+			wasm_method,
+			heap_alloc_strategy,
+			code,
+			blob,
+			allow_missing_func_imports,
+			cache_path,
+			execution_limits,
+			artifact_cache,
+		)?,
+		None => create_wasm_runtime_with_code::<H>(
+			wasm_method,
+			heap_alloc_strategy,
+			blob,
+			allow_missing_func_imports,
+			cache_path,
+			execution_limits,
+		)?,
+	};
 
 	// If the runtime blob doesn't embed the runtime version then use the legacy version query
 	// mechanism: call the runtime.
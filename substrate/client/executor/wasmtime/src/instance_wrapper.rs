@@ -167,9 +167,15 @@ impl InstanceWrapper {
 		engine: &Engine,
 		instance_pre: &InstancePre<StoreData>,
 		instance_counter: Arc<InstanceCounter>,
+		fuel_limit: Option<u64>,
 	) -> Result<Self> {
 		let _release_instance_handle = instance_counter.acquire_instance();
 		let mut store = Store::new(engine, Default::default());
+		if let Some(fuel_limit) = fuel_limit {
+			store.add_fuel(fuel_limit).map_err(|error| {
+				WasmError::Other(format!("failed to add fuel to the store: {:#}", error))
+			})?;
+		}
 		let instance = instance_pre.instantiate(&mut store).map_err(|error| {
 			WasmError::Other(format!(
 				"failed to instantiate a new WASM module instance: {:#}",
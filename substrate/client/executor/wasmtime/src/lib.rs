@@ -38,6 +38,6 @@ mod tests;
 
 pub use runtime::{
 	create_runtime, create_runtime_from_artifact, create_runtime_from_artifact_bytes,
-	prepare_runtime_artifact, Config, DeterministicStackLimit, InstantiationStrategy, Semantics,
-	WasmtimeRuntime,
+	prepare_runtime_artifact, runtime_artifact_fingerprint, Config, DeterministicStackLimit,
+	InstantiationStrategy, Semantics, WasmtimeRuntime,
 };
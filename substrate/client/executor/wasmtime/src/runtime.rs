@@ -77,11 +77,17 @@ struct InstanceCreator {
 	engine: Engine,
 	instance_pre: Arc<wasmtime::InstancePre<StoreData>>,
 	instance_counter: Arc<InstanceCounter>,
+	fuel_limit: Option<u64>,
 }
 
 impl InstanceCreator {
 	fn instantiate(&mut self) -> Result<InstanceWrapper> {
-		InstanceWrapper::new(&self.engine, &self.instance_pre, self.instance_counter.clone())
+		InstanceWrapper::new(
+			&self.engine,
+			&self.instance_pre,
+			self.instance_counter.clone(),
+			self.fuel_limit,
+		)
 	}
 }
 
@@ -139,6 +145,7 @@ pub struct WasmtimeRuntime {
 	instance_pre: Arc<wasmtime::InstancePre<StoreData>>,
 	instantiation_strategy: InternalInstantiationStrategy,
 	instance_counter: Arc<InstanceCounter>,
+	fuel_limit: Option<u64>,
 }
 
 impl WasmModule for WasmtimeRuntime {
@@ -148,6 +155,7 @@ impl WasmModule for WasmtimeRuntime {
 				engine: self.engine.clone(),
 				instance_pre: self.instance_pre.clone(),
 				instance_counter: self.instance_counter.clone(),
+				fuel_limit: self.fuel_limit,
 			}),
 		};
 
@@ -274,6 +282,7 @@ fn common_config(semantics: &Semantics) -> std::result::Result<wasmtime::Config,
 	};
 
 	config.max_wasm_stack(native_stack_max as usize);
+	config.consume_fuel(semantics.fuel_limit.is_some());
 
 	config.parallel_compilation(semantics.parallel_compilation);
 
@@ -363,7 +372,7 @@ fn common_config(semantics: &Semantics) -> std::result::Result<wasmtime::Config,
 /// See [here][stack_height] for more details of the instrumentation
 ///
 /// [stack_height]: https://github.com/paritytech/wasm-instrument/blob/master/src/stack_limiter/mod.rs
-#[derive(Clone)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct DeterministicStackLimit {
 	/// A number of logical "values" that can be pushed on the wasm stack. A trap will be triggered
 	/// if exceeded.
@@ -417,7 +426,7 @@ enum InternalInstantiationStrategy {
 	Builtin,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Hash)]
 pub struct Semantics {
 	/// The instantiation strategy to use.
 	pub instantiation_strategy: InstantiationStrategy,
@@ -434,6 +443,15 @@ pub struct Semantics {
 	// I.e. if [`CodeSupplyMode::Verbatim`] is used.
 	pub deterministic_stack_limit: Option<DeterministicStackLimit>,
 
+	/// Specifying `Some` limits the number of instructions a single runtime call can execute.
+	///
+	/// Every instance starts out with the given amount of wasmtime fuel, which is consumed by
+	/// the executed instructions. Once it is used up, the call traps. Unlike a timeout, the point
+	/// at which this happens only depends on the executed code and not on the machine running it.
+	///
+	/// Artifacts compiled with and without a fuel limit are not interchangeable.
+	pub fuel_limit: Option<u64>,
+
 	/// Controls whether wasmtime should compile floating point in a way that doesn't allow for
 	/// non-determinism.
 	///
@@ -645,6 +663,7 @@ where
 		instance_pre: Arc::new(instance_pre),
 		instantiation_strategy,
 		instance_counter: Default::default(),
+		fuel_limit: config.semantics.fuel_limit,
 	})
 }
 
@@ -685,6 +704,48 @@ pub fn prepare_runtime_artifact(
 		.map_err(|e| WasmError::Other(format!("cannot precompile module: {:#}", e)))
 }
 
+/// Returns a fingerprint of everything besides the code itself that went into an artifact produced
+/// by [`prepare_runtime_artifact`] with the given `semantics`.
+///
+/// This covers the wasmtime version, the compilation settings derived from `semantics` and the
+/// host it is running on, as well as the `semantics` used to prepare the blob. Artifacts with
+/// different fingerprints must not be used interchangeably, so the fingerprint is meant to be part
+/// of the key under which an artifact is cached.
+///
+/// The fingerprint is only stable for the same build of this crate.
+pub fn runtime_artifact_fingerprint(
+	semantics: &Semantics,
+) -> std::result::Result<Vec<u8>, WasmError> {
+	use std::hash::Hash;
+
+	let mut semantics = semantics.clone();
+	replace_strategy_if_broken(&mut semantics.instantiation_strategy);
+
+	let engine = Engine::new(&common_config(&semantics)?)
+		.map_err(|e| WasmError::Other(format!("cannot create the engine: {:#}", e)))?;
+
+	let mut fingerprint = FingerprintHasher::default();
+	engine.precompile_compatibility_hash().hash(&mut fingerprint);
+	semantics.hash(&mut fingerprint);
+
+	Ok(fingerprint.0)
+}
+
+/// A [`std::hash::Hasher`] collecting everything written to it instead of compressing it, so that
+/// the result doesn't depend on the hashing algorithm of the standard library.
+#[derive(Default)]
+struct FingerprintHasher(Vec<u8>);
+
+impl std::hash::Hasher for FingerprintHasher {
+	fn finish(&self) -> u64 {
+		unreachable!("only the collected bytes are used as the fingerprint; qed")
+	}
+
+	fn write(&mut self, bytes: &[u8]) {
+		self.0.extend_from_slice(bytes);
+	}
+}
+
 fn perform_call(
 	data: &[u8],
 	instance_wrapper: &mut InstanceWrapper,
@@ -16,13 +16,40 @@
 // You should have received a copy of the GNU General Public License
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
-use super::{config::Config, error::Error, request::Request};
+use super::{
+	config::{Config, RequestPolicy},
+	error::Error,
+	request::Request,
+	request_handler::{
+		ReadProofReply, ReadProofRequest, READ_PROOF_PROTOCOL, STATEMENT_BROADCASTS_PROTOCOL,
+	},
+};
+use codec::{Decode, DecodeAll, Encode};
 use futures::{
 	channel::{mpsc, oneshot},
-	SinkExt,
+	future::{select, Either},
+	pin_mut, SinkExt,
 };
+use futures_timer::Delay;
 use sp_core::Bytes;
-use std::future::Future;
+use sp_statement_store::Topic;
+use std::{future::Future, time::Duration};
+
+async fn with_timeout<T>(
+	fut: impl Future<Output = Result<T, Error>>,
+	timeout: Option<Duration>,
+) -> Result<T, Error> {
+	let Some(timeout) = timeout else { return fut.await };
+	pin_mut!(fut);
+	match select(fut, Delay::new(timeout)).await {
+		Either::Left((res, _)) => res,
+		Either::Right(_) => Err(Error::Timeout),
+	}
+}
+
+fn decode_reply<T: Decode>(mut data: &[u8]) -> Result<T, Error> {
+	T::decode_all(&mut data).map_err(|_| Error::BadReply)
+}
 
 /// The other end of an [`Api`]. This should be passed to [`run`](super::run::run).
 pub struct ApiBackend {
@@ -33,13 +60,15 @@ pub struct ApiBackend {
 #[derive(Clone)]
 pub struct Api {
 	request_sender: mpsc::Sender<Request>,
+	default_request_policy: RequestPolicy,
 }
 
 impl Api {
 	/// Create a new `Api`. The [`ApiBackend`] should be passed to [`run`](super::run::run).
 	pub fn new(config: &Config) -> (Self, ApiBackend) {
 		let (request_sender, request_receiver) = mpsc::channel(config.substrate.request_buffer);
-		(Self { request_sender }, ApiBackend { request_receiver })
+		let default_request_policy = config.substrate.default_request_policy;
+		(Self { request_sender, default_request_policy }, ApiBackend { request_receiver })
 	}
 
 	/// Submit an extrinsic via the mixnet.
@@ -66,4 +95,174 @@ impl Api {
 			reply_receiver.await.map_err(|_| Error::ServiceUnavailable)?
 		}
 	}
+
+	/// Send a request over the mixnet, to be handled by the
+	/// [`RequestHandler`](super::RequestHandler) registered for `protocol` on the destination
+	/// mixnode. The default request policy from the [`Config`] is used.
+	///
+	/// Returns the reply payload. See [`submit_extrinsic`](Self::submit_extrinsic) for an
+	/// explanation of the nested `Future`s.
+	pub async fn request(
+		&mut self,
+		protocol: impl Into<String>,
+		payload: Bytes,
+	) -> impl Future<Output = Result<Vec<u8>, Error>> {
+		let policy = self.default_request_policy;
+		self.request_with_policy(protocol, payload, policy).await
+	}
+
+	/// Like [`request`](Self::request), but with an explicit retry and timeout policy.
+	///
+	/// Note that each attempt is sent as a separate mixnet request, possibly to a different
+	/// mixnode, so handlers may see the same request more than once.
+	pub async fn request_with_policy(
+		&mut self,
+		protocol: impl Into<String>,
+		payload: Bytes,
+		policy: RequestPolicy,
+	) -> impl Future<Output = Result<Vec<u8>, Error>> {
+		let protocol = protocol.into();
+		let request = move |reply_sender| Request::Handled {
+			protocol: protocol.clone(),
+			payload: payload.clone(),
+			max_reply_fragments: policy.max_reply_fragments,
+			reply_sender,
+		};
+
+		let (reply_sender, mut reply_receiver) = oneshot::channel();
+		let res = self.request_sender.feed(request(reply_sender)).await;
+		let mut request_sender = self.request_sender.clone();
+		let attempts = async move {
+			res.map_err(|_| Error::ServiceUnavailable)?;
+			let mut attempt = 1;
+			loop {
+				match reply_receiver.await.map_err(|_| Error::ServiceUnavailable)? {
+					Err(err) if err.is_retryable() && attempt < policy.max_attempts => {
+						attempt += 1;
+						let (reply_sender, next_reply_receiver) = oneshot::channel();
+						request_sender
+							.send(request(reply_sender))
+							.await
+							.map_err(|_| Error::ServiceUnavailable)?;
+						reply_receiver = next_reply_receiver;
+					},
+					res => return res,
+				}
+			}
+		};
+		with_timeout(attempts, policy.timeout)
+	}
+
+	/// Request a storage read proof for `keys` at block `at` over the mixnet. If `at` is `None`,
+	/// the proof is generated at the destination mixnode's best block; the hash of this block is
+	/// returned along with the proof.
+	///
+	/// The destination mixnode is not trusted; the proof should be checked against the state root
+	/// of the returned block.
+	pub async fn read_proof<H: Encode + Decode>(
+		&mut self,
+		at: Option<H>,
+		keys: Vec<Vec<u8>>,
+	) -> impl Future<Output = Result<ReadProofReply<H>, Error>> {
+		let payload: ReadProofRequest<H> = (at, keys);
+		let reply = self.request(READ_PROOF_PROTOCOL, payload.encode().into()).await;
+		async move { decode_reply(&reply.await?) }
+	}
+
+	/// Query the statement store of a mixnode over the mixnet. Returns the data of all broadcast
+	/// statements matching all of `match_all_topics`.
+	pub async fn statement_broadcasts(
+		&mut self,
+		match_all_topics: Vec<Topic>,
+	) -> impl Future<Output = Result<Vec<Vec<u8>>, Error>> {
+		let reply = self
+			.request(STATEMENT_BROADCASTS_PROTOCOL, match_all_topics.encode().into())
+			.await;
+		async move { decode_reply(&reply.await?) }
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::error::RemoteErr;
+	use futures::{executor::block_on, future::join, StreamExt};
+	use mixnet::core::PostErr;
+
+	/// Answer the requests received by `backend` with `replies`, in order. Returns the number of
+	/// requests received.
+	async fn reply_with(mut backend: ApiBackend, replies: Vec<Result<Vec<u8>, Error>>) -> usize {
+		let mut num_requests = 0;
+		for reply in replies {
+			let Some(Request::Handled { reply_sender, .. }) = backend.request_receiver.next().await
+			else {
+				panic!("Expected a handled request")
+			};
+			num_requests += 1;
+			let _ = reply_sender.send(reply);
+		}
+		num_requests
+	}
+
+	fn request(
+		policy: RequestPolicy,
+		replies: Vec<Result<Vec<u8>, Error>>,
+	) -> (Result<Vec<u8>, Error>, usize) {
+		let (mut api, backend) = Api::new(&Config::default());
+		block_on(async {
+			let reply = api.request_with_policy("echo/1", b"hello".to_vec().into(), policy).await;
+			join(reply, reply_with(backend, replies)).await
+		})
+	}
+
+	#[test]
+	fn only_transient_errors_are_retryable() {
+		assert!(Error::NoReply.is_retryable());
+		assert!(Error::Post(PostErr::NotEnoughSpaceInQueue).is_retryable());
+		assert!(!Error::Post(PostErr::TooManyFragments).is_retryable());
+		assert!(!Error::ServiceUnavailable.is_retryable());
+		assert!(!Error::Timeout.is_retryable());
+		assert!(!Error::BadReply.is_retryable());
+		assert!(!Error::Remote(RemoteErr::ReplyTooLarge(0)).is_retryable());
+	}
+
+	#[test]
+	fn retryable_errors_are_retried() {
+		let (reply, num_requests) =
+			request(RequestPolicy::default(), vec![Err(Error::NoReply), Ok(b"world".to_vec())]);
+		assert_eq!(reply.unwrap(), b"world");
+		assert_eq!(num_requests, 2);
+	}
+
+	#[test]
+	fn retries_are_limited_by_policy() {
+		let policy = RequestPolicy { max_attempts: 2, ..Default::default() };
+		let (reply, num_requests) = request(policy, vec![Err(Error::NoReply), Err(Error::NoReply)]);
+		assert!(matches!(reply, Err(Error::NoReply)));
+		assert_eq!(num_requests, 2);
+	}
+
+	#[test]
+	fn other_errors_are_not_retried() {
+		let (reply, num_requests) = request(
+			RequestPolicy::default(),
+			vec![Err(Error::Remote(RemoteErr::UnsupportedProtocol("echo/1".into())))],
+		);
+		assert!(matches!(reply, Err(Error::Remote(RemoteErr::UnsupportedProtocol(_)))));
+		assert_eq!(num_requests, 1);
+	}
+
+	#[test]
+	fn requests_time_out() {
+		let (mut api, mut backend) = Api::new(&Config::default());
+		let policy =
+			RequestPolicy { timeout: Some(Duration::from_millis(10)), ..Default::default() };
+		let reply = block_on(async {
+			let reply = api.request_with_policy("echo/1", b"hello".to_vec().into(), policy).await;
+			// Hold on to the request, and its reply sender, without ever replying
+			let _request = backend.request_receiver.next().await;
+			reply.await
+		});
+		assert!(matches!(reply, Err(Error::Timeout)));
+	}
 }
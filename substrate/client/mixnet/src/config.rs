@@ -19,6 +19,28 @@
 pub use mixnet::core::Config as CoreConfig;
 use std::time::Duration;
 
+/// Retry and timeout policy for a request sent over the mixnet.
+#[derive(Clone, Copy, Debug)]
+pub struct RequestPolicy {
+	/// Maximum number of times the request will be submitted to the mixnet service. Each
+	/// submission is itself retried according to the request manager configuration; a new
+	/// submission is only made if the previous one fails in a way that might not be permanent (eg
+	/// no reply was received). Values less than 1 are treated as 1.
+	pub max_attempts: u32,
+	/// Overall time limit for the request, including all attempts. `None` means no limit.
+	pub timeout: Option<Duration>,
+	/// Maximum number of fragments in a reply. The number of SURBs included in each request
+	/// message is this multiplied by [`SubstrateConfig::surb_factor`]. Replies which do not fit
+	/// in this many fragments will not be delivered.
+	pub max_reply_fragments: usize,
+}
+
+impl Default for RequestPolicy {
+	fn default() -> Self {
+		Self { max_attempts: 3, timeout: Some(Duration::from_secs(60)), max_reply_fragments: 4 }
+	}
+}
+
 /// Substrate-specific mixnet configuration.
 #[derive(Clone, Debug)]
 pub struct SubstrateConfig {
@@ -45,6 +67,14 @@ pub struct SubstrateConfig {
 	/// Maximum number of extrinsics being actively submitted. If a submit extrinsic request's
 	/// delay elapses and we are already at this limit, the request will simply be dropped.
 	pub max_pending_extrinsics: usize,
+
+	/// Policy used for requests made via [`Api`](super::api::Api) when no policy is explicitly
+	/// specified.
+	pub default_request_policy: RequestPolicy,
+	/// Maximum number of incoming requests being actively handled by
+	/// [`RequestHandler`](super::request_handler::RequestHandler)s. When at the limit, any
+	/// requests that arrive will simply be dropped.
+	pub max_pending_requests: usize,
 }
 
 impl Default for SubstrateConfig {
@@ -59,6 +89,9 @@ impl Default for SubstrateConfig {
 			extrinsic_queue_capacity: 50,
 			mean_extrinsic_delay: Duration::from_secs(1),
 			max_pending_extrinsics: 20,
+
+			default_request_policy: Default::default(),
+			max_pending_requests: 20,
 		}
 	}
 }
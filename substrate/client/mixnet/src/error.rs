@@ -28,6 +28,13 @@ pub enum RemoteErr {
 	/// Failed to decode the request.
 	#[error("Failed to decode the request: {0}")]
 	Decode(String),
+	/// The destination mixnode has no handler for the requested protocol.
+	#[error("Unsupported request protocol: {0}")]
+	UnsupportedProtocol(String),
+	/// The reply would be larger than the given number of bytes, and would not fit in the SURBs
+	/// included with the request.
+	#[error("Reply larger than {0} bytes")]
+	ReplyTooLarge(u32),
 }
 
 /// Mixnet error.
@@ -42,6 +49,9 @@ pub enum Error {
 	/// Did not receive a reply after the configured number of attempts.
 	#[error("Did not receive a reply from the mixnet after the configured number of attempts")]
 	NoReply,
+	/// Did not receive a reply within the time limit of the request policy.
+	#[error("Did not receive a reply from the mixnet within the configured time limit")]
+	Timeout,
 	/// Received a malformed reply.
 	#[error("Received a malformed reply from the mixnet")]
 	BadReply,
@@ -54,3 +64,16 @@ pub enum Error {
 	#[error("Error reported by the destination mixnode: {0}")]
 	Remote(#[from] RemoteErr),
 }
+
+impl Error {
+	/// Returns `true` if a new attempt at the failed request might succeed.
+	pub fn is_retryable(&self) -> bool {
+		match self {
+			Error::NoReply => true,
+			Error::Post(PostErr::TooManyFragments) => false,
+			Error::Post(_) => true,
+			Error::ServiceUnavailable | Error::Timeout | Error::BadReply | Error::Remote(_) =>
+				false,
+		}
+	}
+}
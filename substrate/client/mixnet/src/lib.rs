@@ -27,18 +27,25 @@ mod config;
 mod error;
 mod extrinsic_queue;
 mod maybe_inf_delay;
+mod metrics;
 mod packet_dispatcher;
 mod peer_id;
 mod protocol;
 mod request;
+mod request_handler;
 mod run;
 mod sync_with_runtime;
 
 pub use self::{
 	api::{Api, ApiBackend},
-	config::{Config, CoreConfig, SubstrateConfig},
+	config::{Config, CoreConfig, RequestPolicy, SubstrateConfig},
 	error::{Error, RemoteErr},
 	protocol::{peers_set_config, protocol_name},
+	request_handler::{
+		max_reply_size, ReadProofHandler, ReadProofReply, ReadProofRequest, RequestHandler,
+		RequestHandlers, StatementBroadcastsHandler, MAX_READ_PROOF_KEYS, READ_PROOF_PROTOCOL,
+		STATEMENT_BROADCASTS_PROTOCOL,
+	},
 	run::run,
 };
 pub use mixnet::core::{KxSecret, PostErr, TopologyErr};
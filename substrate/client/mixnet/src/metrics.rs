@@ -0,0 +1,106 @@
+// This file is part of Substrate.
+
+// Copyright (C) Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Prometheus metrics for the mixnet service.
+
+use prometheus_endpoint::{
+	register, Counter, CounterVec, Gauge, Opts, PrometheusError, Registry, U64,
+};
+
+/// Mixnet service metrics.
+pub struct Metrics {
+	/// Requests submitted locally, by kind.
+	pub requests: CounterVec<U64>,
+	/// Replies received to locally submitted requests.
+	pub replies: Counter<U64>,
+	/// Requests received from the mixnet, by kind and outcome.
+	pub incoming_requests: CounterVec<U64>,
+	/// Incoming requests currently being handled.
+	pub pending_incoming_requests: Gauge<U64>,
+	/// Packets authored by the local node. This includes both packets carrying request and reply
+	/// fragments and cover packets; the rate at which packets are authored is independent of the
+	/// amount of real traffic.
+	pub authored_packets: Counter<U64>,
+	/// Packets forwarded on behalf of other nodes.
+	pub forwarded_packets: Counter<U64>,
+	/// Packets received from peers.
+	pub received_packets: Counter<U64>,
+}
+
+impl Metrics {
+	/// Register the metrics with `registry`.
+	pub fn register(registry: &Registry) -> Result<Self, PrometheusError> {
+		Ok(Self {
+			requests: register(
+				CounterVec::new(
+					Opts::new(
+						"substrate_mixnet_requests_total",
+						"Number of requests submitted to the mixnet by the local node",
+					),
+					&["kind"],
+				)?,
+				registry,
+			)?,
+			replies: register(
+				Counter::new(
+					"substrate_mixnet_replies_total",
+					"Number of replies received to requests submitted by the local node",
+				)?,
+				registry,
+			)?,
+			incoming_requests: register(
+				CounterVec::new(
+					Opts::new(
+						"substrate_mixnet_incoming_requests_total",
+						"Number of requests received from the mixnet",
+					),
+					&["kind", "outcome"],
+				)?,
+				registry,
+			)?,
+			pending_incoming_requests: register(
+				Gauge::new(
+					"substrate_mixnet_pending_incoming_requests",
+					"Number of requests received from the mixnet which are being handled",
+				)?,
+				registry,
+			)?,
+			authored_packets: register(
+				Counter::new(
+					"substrate_mixnet_authored_packets_total",
+					"Number of packets authored by the local node, including cover packets",
+				)?,
+				registry,
+			)?,
+			forwarded_packets: register(
+				Counter::new(
+					"substrate_mixnet_forwarded_packets_total",
+					"Number of packets forwarded on behalf of other nodes",
+				)?,
+				registry,
+			)?,
+			received_packets: register(
+				Counter::new(
+					"substrate_mixnet_received_packets_total",
+					"Number of packets received from peers",
+				)?,
+				registry,
+			)?,
+		})
+	}
+}
@@ -24,7 +24,7 @@ use blake2::{
 	digest::{consts::U16, Mac},
 	Blake2bMac,
 };
-use codec::{Decode, DecodeAll};
+use codec::{Decode, DecodeAll, Encode};
 use futures::channel::oneshot;
 use log::debug;
 use mixnet::core::{Delay, MessageId, PostErr, Scattered};
@@ -54,6 +54,10 @@ fn send_reply<T: Decode>(reply_sender: oneshot::Sender<Result<T, Error>>, mut da
 /// First byte of a submit extrinsic request, identifying it as such.
 pub const SUBMIT_EXTRINSIC: u8 = 1;
 
+/// First byte of a request to be handled by a [`RequestHandler`](super::RequestHandler). This is
+/// followed by the SCALE-encoded protocol name and then the raw request payload.
+pub const HANDLED_REQUEST: u8 = 2;
+
 const EXTRINSIC_DELAY_PERSONA: &[u8; 16] = b"submit-extrn-dly";
 
 /// Returns the artificial delay that should be inserted between receipt of a submit extrinsic
@@ -68,14 +72,32 @@ pub fn extrinsic_delay(message_id: &MessageId, config: &SubstrateConfig) -> Dura
 /// Request parameters and local reply channel. Stored by the
 /// [`RequestManager`](mixnet::request_manager::RequestManager).
 pub enum Request {
-	SubmitExtrinsic { extrinsic: Bytes, reply_sender: oneshot::Sender<Result<(), Error>> },
+	SubmitExtrinsic {
+		extrinsic: Bytes,
+		reply_sender: oneshot::Sender<Result<(), Error>>,
+	},
+	Handled {
+		protocol: String,
+		payload: Bytes,
+		max_reply_fragments: usize,
+		reply_sender: oneshot::Sender<Result<Vec<u8>, Error>>,
+	},
 }
 
 impl Request {
+	/// Name of the kind of request, for use in metrics.
+	pub fn kind(&self) -> &'static str {
+		match self {
+			Request::SubmitExtrinsic { .. } => "submit_extrinsic",
+			Request::Handled { .. } => "handled",
+		}
+	}
+
 	/// Forward an error to the user of the mixnet service.
 	fn send_err(self, err: Error) {
 		match self {
 			Request::SubmitExtrinsic { reply_sender, .. } => send_err(reply_sender, err),
+			Request::Handled { reply_sender, .. } => send_err(reply_sender, err),
 		}
 	}
 
@@ -83,6 +105,7 @@ impl Request {
 	pub fn send_reply(self, data: &[u8]) {
 		match self {
 			Request::SubmitExtrinsic { reply_sender, .. } => send_reply(reply_sender, data),
+			Request::Handled { reply_sender, .. } => send_reply(reply_sender, data),
 		}
 	}
 }
@@ -94,18 +117,26 @@ impl mixnet::request_manager::Request for Request {
 		match self {
 			Request::SubmitExtrinsic { extrinsic, .. } =>
 				f([&[SUBMIT_EXTRINSIC], extrinsic.as_ref()].as_slice().into()),
+			Request::Handled { protocol, payload, .. } => {
+				let protocol = protocol.encode();
+				f([&[HANDLED_REQUEST], protocol.as_slice(), payload.as_ref()].as_slice().into())
+			},
 		}
 	}
 
 	fn num_surbs(&self, context: &Self::Context) -> usize {
 		match self {
 			Request::SubmitExtrinsic { .. } => context.surb_factor,
+			Request::Handled { max_reply_fragments, .. } =>
+				max_reply_fragments.saturating_mul(context.surb_factor),
 		}
 	}
 
 	fn handling_delay(&self, message_id: &MessageId, context: &Self::Context) -> Duration {
 		match self {
 			Request::SubmitExtrinsic { .. } => extrinsic_delay(message_id, context),
+			// Handled requests are not deliberately delayed by the destination
+			Request::Handled { .. } => Duration::ZERO,
 		}
 	}
 
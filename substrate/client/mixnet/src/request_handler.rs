@@ -0,0 +1,248 @@
+// This file is part of Substrate.
+
+// Copyright (C) Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Receiver-side handling of requests which are not built in to the mixnet service. Handlers are
+//! registered by protocol name in a [`RequestHandlers`] set which is passed to
+//! [`run`](super::run::run).
+
+use super::{config::RequestPolicy, error::RemoteErr};
+use codec::{Decode, DecodeAll, Encode};
+use futures::future::{ready, BoxFuture};
+use sc_client_api::{HeaderBackend, ProofProvider};
+use sp_runtime::traits::Block;
+use sp_statement_store::{StatementStore, Topic, MAX_TOPICS};
+use std::{collections::HashMap, marker::PhantomData, sync::Arc};
+
+/// Protocol name of requests handled by [`ReadProofHandler`].
+pub const READ_PROOF_PROTOCOL: &str = "read-proof/1";
+
+/// Protocol name of requests handled by [`StatementBroadcastsHandler`].
+pub const STATEMENT_BROADCASTS_PROTOCOL: &str = "statement-broadcasts/1";
+
+/// Maximum number of keys in a single read proof request.
+pub const MAX_READ_PROOF_KEYS: usize = 64;
+
+/// Lower bound on the number of reply bytes carried by a single fragment. Fragments carry 2048
+/// bytes of payload data, part of which is taken by the fragment header.
+const FRAGMENT_REPLY_SIZE: usize = 2000;
+
+/// Returns the maximum size of a reply payload which fits in `max_reply_fragments` fragments, ie
+/// which can be sent back using the SURBs of a request made with a [`RequestPolicy`] having this
+/// `max_reply_fragments`.
+pub fn max_reply_size(max_reply_fragments: usize) -> usize {
+	// The reply payload is sent as an encoded `Result`, which takes up to 6 more bytes
+	max_reply_fragments.saturating_mul(FRAGMENT_REPLY_SIZE).saturating_sub(6)
+}
+
+fn default_max_reply_size() -> usize {
+	max_reply_size(RequestPolicy::default().max_reply_fragments)
+}
+
+fn check_reply_size(reply: Vec<u8>, max_reply_size: usize) -> Result<Vec<u8>, RemoteErr> {
+	if reply.len() > max_reply_size {
+		return Err(RemoteErr::ReplyTooLarge(max_reply_size as u32))
+	}
+	Ok(reply)
+}
+
+/// Handler for requests of a particular protocol received over the mixnet.
+pub trait RequestHandler: Send + Sync {
+	/// Handle a request with the given payload, returning the reply payload.
+	///
+	/// The reply is sent back using the SURBs included with the request. If it does not fit, it
+	/// will be dropped; handlers should bound the size of their replies (see [`max_reply_size`])
+	/// and fail with [`RemoteErr::ReplyTooLarge`] instead.
+	fn handle(&self, payload: Vec<u8>) -> BoxFuture<'static, Result<Vec<u8>, RemoteErr>>;
+}
+
+/// Set of [`RequestHandler`]s, keyed by protocol name.
+#[derive(Clone, Default)]
+pub struct RequestHandlers(HashMap<String, Arc<dyn RequestHandler>>);
+
+impl RequestHandlers {
+	/// Create an empty set of handlers. Requests other than extrinsic submissions will be
+	/// rejected.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Register `handler` for requests of the given protocol, replacing any existing handler for
+	/// the protocol.
+	pub fn with_handler(
+		mut self,
+		protocol: impl Into<String>,
+		handler: impl RequestHandler + 'static,
+	) -> Self {
+		self.0.insert(protocol.into(), Arc::new(handler));
+		self
+	}
+
+	/// Returns the handler for the given protocol, if there is one.
+	pub fn get(&self, protocol: &str) -> Option<&Arc<dyn RequestHandler>> {
+		self.0.get(protocol)
+	}
+}
+
+fn decode_payload<T: Decode>(mut payload: &[u8]) -> Result<T, RemoteErr> {
+	T::decode_all(&mut payload).map_err(|err| RemoteErr::Decode(err.to_string()))
+}
+
+/// Request payload for [`READ_PROOF_PROTOCOL`]: the block to read at (the best block if `None`)
+/// and the keys to read.
+pub type ReadProofRequest<Hash> = (Option<Hash>, Vec<Vec<u8>>);
+
+/// Reply payload for [`READ_PROOF_PROTOCOL`]: the block the proof was generated at and the proof
+/// trie nodes.
+pub type ReadProofReply<Hash> = (Hash, Vec<Vec<u8>>);
+
+/// Handles [`READ_PROOF_PROTOCOL`] requests by generating storage read proofs.
+pub struct ReadProofHandler<B, C> {
+	client: Arc<C>,
+	max_reply_size: usize,
+	_phantom: PhantomData<fn() -> B>,
+}
+
+impl<B, C> ReadProofHandler<B, C> {
+	/// Create a new handler which reads proofs from `client`. Proofs which do not fit in the
+	/// replies of requests made with the default [`RequestPolicy`] are refused.
+	pub fn new(client: Arc<C>) -> Self {
+		Self { client, max_reply_size: default_max_reply_size(), _phantom: PhantomData }
+	}
+
+	/// Refuse proofs which do not fit in `max_reply_fragments` reply fragments.
+	pub fn with_max_reply_fragments(mut self, max_reply_fragments: usize) -> Self {
+		self.max_reply_size = max_reply_size(max_reply_fragments);
+		self
+	}
+
+	fn read_proof(&self, payload: &[u8]) -> Result<Vec<u8>, RemoteErr>
+	where
+		B: Block,
+		C: HeaderBackend<B> + ProofProvider<B>,
+	{
+		let (at, keys): ReadProofRequest<B::Hash> = decode_payload(payload)?;
+		if keys.len() > MAX_READ_PROOF_KEYS {
+			return Err(RemoteErr::Other(format!(
+				"Too many keys ({}, maximum is {MAX_READ_PROOF_KEYS})",
+				keys.len()
+			)))
+		}
+		let at = at.unwrap_or_else(|| self.client.info().best_hash);
+		let proof = self
+			.client
+			.read_proof(at, &mut keys.iter().map(AsRef::as_ref))
+			.map_err(|err| RemoteErr::Other(err.to_string()))?;
+		let reply: ReadProofReply<B::Hash> = (at, proof.into_iter_nodes().collect());
+		check_reply_size(reply.encode(), self.max_reply_size)
+	}
+}
+
+impl<B, C> RequestHandler for ReadProofHandler<B, C>
+where
+	B: Block,
+	C: HeaderBackend<B> + ProofProvider<B> + Send + Sync,
+{
+	fn handle(&self, payload: Vec<u8>) -> BoxFuture<'static, Result<Vec<u8>, RemoteErr>> {
+		Box::pin(ready(self.read_proof(&payload)))
+	}
+}
+
+/// Handles [`STATEMENT_BROADCASTS_PROTOCOL`] requests by querying a statement store. The request
+/// payload is a list of topics which must all be matched; the reply is the data of all matching
+/// broadcast statements.
+pub struct StatementBroadcastsHandler {
+	store: Arc<dyn StatementStore>,
+	max_reply_size: usize,
+}
+
+impl StatementBroadcastsHandler {
+	/// Create a new handler which queries `store`. Queries whose results do not fit in the replies
+	/// of requests made with the default [`RequestPolicy`] are refused.
+	pub fn new(store: Arc<dyn StatementStore>) -> Self {
+		Self { store, max_reply_size: default_max_reply_size() }
+	}
+
+	/// Refuse queries whose results do not fit in `max_reply_fragments` reply fragments.
+	pub fn with_max_reply_fragments(mut self, max_reply_fragments: usize) -> Self {
+		self.max_reply_size = max_reply_size(max_reply_fragments);
+		self
+	}
+
+	fn broadcasts(&self, payload: &[u8]) -> Result<Vec<u8>, RemoteErr> {
+		let topics: Vec<Topic> = decode_payload(payload)?;
+		if topics.len() > MAX_TOPICS {
+			return Err(RemoteErr::Other(format!(
+				"Too many topics ({}, maximum is {MAX_TOPICS})",
+				topics.len()
+			)))
+		}
+		let broadcasts = self
+			.store
+			.broadcasts(&topics)
+			.map_err(|err| RemoteErr::Other(err.to_string()))?;
+		check_reply_size(broadcasts.encode(), self.max_reply_size)
+	}
+}
+
+impl RequestHandler for StatementBroadcastsHandler {
+	fn handle(&self, payload: Vec<u8>) -> BoxFuture<'static, Result<Vec<u8>, RemoteErr>> {
+		Box::pin(ready(self.broadcasts(&payload)))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	struct Echo;
+
+	impl RequestHandler for Echo {
+		fn handle(&self, payload: Vec<u8>) -> BoxFuture<'static, Result<Vec<u8>, RemoteErr>> {
+			Box::pin(ready(Ok(payload)))
+		}
+	}
+
+	#[test]
+	fn handlers_are_looked_up_by_protocol() {
+		let handlers = RequestHandlers::new().with_handler("echo/1", Echo);
+		assert!(handlers.get("echo/2").is_none());
+		let handler = handlers.get("echo/1").unwrap();
+		let reply = futures::executor::block_on(handler.handle(b"hello".to_vec()));
+		assert_eq!(reply.unwrap(), b"hello");
+	}
+
+	#[test]
+	fn replies_are_bounded_by_surb_capacity() {
+		let max = max_reply_size(1);
+		assert!(Ok::<_, RemoteErr>(vec![0u8; max]).encode().len() <= FRAGMENT_REPLY_SIZE);
+		assert_eq!(check_reply_size(vec![0; max], max).unwrap().len(), max);
+		assert!(matches!(
+			check_reply_size(vec![0; max + 1], max),
+			Err(RemoteErr::ReplyTooLarge(size)) if size as usize == max
+		));
+		assert_eq!(default_max_reply_size(), max_reply_size(4));
+	}
+
+	#[test]
+	fn trailing_bytes_in_payload_are_rejected() {
+		let mut payload = vec![[1u8; 32]].encode();
+		assert_eq!(decode_payload::<Vec<Topic>>(&payload).unwrap(), vec![[1u8; 32]]);
+		payload.push(0);
+		assert!(matches!(decode_payload::<Vec<Topic>>(&payload), Err(RemoteErr::Decode(_))));
+	}
+}
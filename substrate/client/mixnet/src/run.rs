@@ -24,15 +24,17 @@ use super::{
 	error::RemoteErr,
 	extrinsic_queue::ExtrinsicQueue,
 	maybe_inf_delay::MaybeInfDelay,
+	metrics::Metrics,
 	packet_dispatcher::PacketDispatcher,
 	peer_id::to_core_peer_id,
-	request::{extrinsic_delay, Request, SUBMIT_EXTRINSIC},
+	request::{extrinsic_delay, Request, HANDLED_REQUEST, SUBMIT_EXTRINSIC},
+	request_handler::RequestHandlers,
 	sync_with_runtime::sync_with_runtime,
 };
 use bytes::Bytes;
 use codec::{Decode, DecodeAll, Encode};
 use futures::{
-	future::{pending, Either},
+	future::{pending, BoxFuture, Either},
 	stream::FuturesUnordered,
 	FutureExt, StreamExt,
 };
@@ -42,6 +44,7 @@ use mixnet::{
 	reply_manager::{ReplyContext, ReplyManager},
 	request_manager::RequestManager,
 };
+use prometheus_endpoint::Registry;
 use sc_client_api::{BlockchainEvents, HeaderBackend};
 use sc_network::{
 	service::traits::{NotificationEvent, ValidationResult},
@@ -68,21 +71,69 @@ const LOG_TARGET: &str = "mixnet";
 
 const MIN_BLOCKS_BETWEEN_REGISTRATION_ATTEMPTS: u32 = 3;
 
-fn complete_submit_extrinsic<X>(
+/// Future handling an incoming request, yielding the reply and the context needed to send it.
+type PendingRequest = BoxFuture<'static, (Result<Vec<u8>, RemoteErr>, ReplyContext)>;
+
+fn complete_request<X, T: Encode>(
 	reply_manager: &mut ReplyManager,
 	reply_context: ReplyContext,
-	data: Result<(), RemoteErr>,
+	data: Result<T, RemoteErr>,
 	mixnet: &mut Mixnet<X>,
 ) {
 	reply_manager.complete(reply_context, data.encode(), mixnet);
 }
 
+fn note_incoming_request(metrics: Option<&Metrics>, kind: &str, outcome: &str) {
+	if let Some(metrics) = metrics {
+		metrics.incoming_requests.with_label_values(&[kind, outcome]).inc();
+	}
+}
+
+/// Outcome of [`dispatch_handled_request`].
+enum Dispatch {
+	/// The request is being handled by the future.
+	Accepted(BoxFuture<'static, Result<Vec<u8>, RemoteErr>>),
+	/// The request should be answered with the error.
+	Rejected(RemoteErr),
+	/// The request should be dropped without a reply.
+	Dropped,
+}
+
+/// Pass a [`HANDLED_REQUEST`] (`request` being the data following the request kind) to the
+/// handler registered for its protocol, if any. `num_pending_requests` is the number of incoming
+/// requests already being handled.
+fn dispatch_handled_request(
+	mut request: &[u8],
+	request_handlers: &RequestHandlers,
+	num_pending_requests: usize,
+	config: &SubstrateConfig,
+) -> Dispatch {
+	let handler = match String::decode(&mut request) {
+		Ok(protocol) => match request_handlers.get(&protocol) {
+			Some(handler) => handler,
+			None => return Dispatch::Rejected(RemoteErr::UnsupportedProtocol(protocol)),
+		},
+		Err(err) =>
+			return Dispatch::Rejected(RemoteErr::Decode(format!("Bad protocol name: {}", err))),
+	};
+
+	if num_pending_requests >= config.max_pending_requests {
+		debug!(target: LOG_TARGET, "Too many pending requests; dropping request");
+		return Dispatch::Dropped
+	}
+
+	Dispatch::Accepted(handler.handle(request.to_vec()))
+}
+
 fn handle_packet<X, E: Decode>(
 	packet: &Packet,
 	mixnet: &mut Mixnet<X>,
 	request_manager: &mut RequestManager<Request>,
 	reply_manager: &mut ReplyManager,
 	extrinsic_queue: &mut ExtrinsicQueue<E>,
+	request_handlers: &RequestHandlers,
+	pending_requests: &mut FuturesUnordered<PendingRequest>,
+	metrics: Option<&Metrics>,
 	config: &SubstrateConfig,
 ) {
 	match mixnet.handle_packet(packet) {
@@ -95,6 +146,7 @@ fn handle_packet<X, E: Decode>(
 						debug!(target: LOG_TARGET, "No space in extrinsic queue; dropping request");
 						// We don't send a reply in this case; we want the requester to retry
 						reply_manager.abandon(reply_context);
+						note_incoming_request(metrics, "submit_extrinsic", "dropped");
 						return
 					}
 
@@ -103,12 +155,13 @@ fn handle_packet<X, E: Decode>(
 					let extrinsic = match E::decode_all(&mut encoded_extrinsic) {
 						Ok(extrinsic) => extrinsic,
 						Err(err) => {
-							complete_submit_extrinsic(
+							complete_request::<_, ()>(
 								reply_manager,
 								reply_context,
 								Err(RemoteErr::Decode(format!("Bad extrinsic: {}", err))),
 								mixnet,
 							);
+							note_incoming_request(metrics, "submit_extrinsic", "rejected");
 							return
 						},
 					};
@@ -116,12 +169,42 @@ fn handle_packet<X, E: Decode>(
 					let deadline =
 						Instant::now() + extrinsic_delay(reply_context.message_id(), config);
 					extrinsic_queue.insert(deadline, extrinsic, reply_context);
+					note_incoming_request(metrics, "submit_extrinsic", "accepted");
+				},
+				[HANDLED_REQUEST, request @ ..] => match dispatch_handled_request(
+					request,
+					request_handlers,
+					pending_requests.len(),
+					config,
+				) {
+					Dispatch::Accepted(fut) => {
+						pending_requests.push(async move { (fut.await, reply_context) }.boxed());
+						note_incoming_request(metrics, "handled", "accepted");
+						if let Some(metrics) = metrics {
+							metrics.pending_incoming_requests.set(pending_requests.len() as u64);
+						}
+					},
+					Dispatch::Rejected(err) => {
+						complete_request::<_, Vec<u8>>(
+							reply_manager,
+							reply_context,
+							Err(err),
+							mixnet,
+						);
+						note_incoming_request(metrics, "handled", "rejected");
+					},
+					Dispatch::Dropped => {
+						// As with extrinsics, we don't send a reply; we want the requester to retry
+						reply_manager.abandon(reply_context);
+						note_incoming_request(metrics, "handled", "dropped");
+					},
 				},
 				_ => {
 					debug!(target: LOG_TARGET, "Unrecognised request; discarding");
 					// To keep things simple we don't bother sending a reply in this case. The
 					// requester will give up and try another mixnode eventually.
 					reply_manager.abandon(reply_context);
+					note_incoming_request(metrics, "unknown", "dropped");
 				},
 			}
 		},
@@ -134,6 +217,9 @@ fn handle_packet<X, E: Decode>(
 				);
 				return
 			};
+			if let Some(metrics) = metrics {
+				metrics.replies.inc();
+			}
 			request.send_reply(&message.data);
 		},
 		None => (),
@@ -146,6 +232,9 @@ fn time_until(instant: Instant) -> Duration {
 
 /// Run the mixnet service. If `keystore` is `None`, the service will not attempt to register the
 /// local node as a mixnode, even if `config.register` is `true`.
+///
+/// Requests other than extrinsic submissions are dispatched by protocol name to
+/// `request_handlers`; requests for protocols without a handler are rejected.
 pub async fn run<B, C, S, N, P>(
 	config: Config,
 	mut api_backend: ApiBackend,
@@ -156,6 +245,8 @@ pub async fn run<B, C, S, N, P>(
 	transaction_pool: Arc<P>,
 	keystore: Option<KeystorePtr>,
 	mut notification_service: Box<dyn NotificationService>,
+	request_handlers: RequestHandlers,
+	metrics_registry: Option<Registry>,
 ) where
 	B: Block,
 	C: BlockchainEvents<B> + ProvideRuntimeApi<B> + HeaderBackend<B>,
@@ -172,6 +263,14 @@ pub async fn run<B, C, S, N, P>(
 		return
 	};
 
+	let metrics = match metrics_registry.as_ref().map(Metrics::register).transpose() {
+		Ok(metrics) => metrics,
+		Err(err) => {
+			warn!(target: LOG_TARGET, "Failed to register mixnet metrics: {err}");
+			None
+		},
+	};
+
 	let offchain_transaction_pool_factory =
 		OffchainTransactionPoolFactory::new(transaction_pool.clone());
 
@@ -197,6 +296,7 @@ pub async fn run<B, C, S, N, P>(
 	let mut next_retry_delay = MaybeInfDelay::new(None);
 	let mut next_extrinsic_delay = MaybeInfDelay::new(None);
 	let mut submit_extrinsic_results = FuturesUnordered::new();
+	let mut pending_requests = FuturesUnordered::new();
 
 	loop {
 		let mut next_request = if request_manager.has_space() {
@@ -211,8 +311,12 @@ pub async fn run<B, C, S, N, P>(
 		);
 
 		futures::select! {
-			request = next_request =>
-				request_manager.insert(request, &mut mixnet, &packet_dispatcher, &config.substrate),
+			request = next_request => {
+				if let Some(metrics) = &metrics {
+					metrics.requests.with_label_values(&[request.kind()]).inc();
+				}
+				request_manager.insert(request, &mut mixnet, &packet_dispatcher, &config.substrate);
+			}
 
 			notification = finality_notifications.select_next_some() => {
 				// To avoid trying to connect to old mixnodes, ignore finality notifications while
@@ -262,11 +366,15 @@ pub async fn run<B, C, S, N, P>(
 				},
 				Some(NotificationEvent::NotificationReceived { peer, notification }) => {
 					let notification: Bytes = notification.into();
+					if let Some(metrics) = &metrics {
+						metrics.received_packets.inc();
+					}
 
 					match notification.as_ref().try_into() {
 						Ok(packet) => handle_packet(packet,
 							&mut mixnet, &mut request_manager, &mut reply_manager,
-							&mut extrinsic_queue, &config.substrate),
+							&mut extrinsic_queue, &request_handlers, &mut pending_requests,
+							metrics.as_ref(), &config.substrate),
 						Err(_) => debug!(target: LOG_TARGET,
 							"Dropped incorrectly sized packet ({} bytes) from {peer}",
 							notification.len(),
@@ -277,6 +385,9 @@ pub async fn run<B, C, S, N, P>(
 
 			_ = next_forward_packet_delay => {
 				if let Some(packet) = mixnet.pop_next_forward_packet() {
+					if let Some(metrics) = &metrics {
+						metrics.forwarded_packets.inc();
+					}
 					if let Some(ready_peer) = packet_dispatcher.dispatch(packet) {
 						if let Some(fut) = ready_peer.send_packet(&notification_service) {
 							ready_peers.push(fut);
@@ -291,6 +402,9 @@ pub async fn run<B, C, S, N, P>(
 
 			_ = next_authored_packet_delay => {
 				if let Some(packet) = mixnet.pop_next_authored_packet(&packet_dispatcher) {
+					if let Some(metrics) = &metrics {
+						metrics.authored_packets.inc();
+					}
 					if let Some(ready_peer) = packet_dispatcher.dispatch(packet) {
 						if let Some(fut) = ready_peer.send_packet(&notification_service) {
 							ready_peers.push(fut);
@@ -345,7 +459,15 @@ pub async fn run<B, C, S, N, P>(
 					Ok(_) => Ok(()),
 					Err(err) => Err(RemoteErr::Other(err.to_string())),
 				};
-				complete_submit_extrinsic(&mut reply_manager, reply_context, res, &mut mixnet);
+				complete_request(&mut reply_manager, reply_context, res, &mut mixnet);
+			}
+
+			res_reply_context = pending_requests.select_next_some() => {
+				let (res, reply_context) = res_reply_context;
+				if let Some(metrics) = &metrics {
+					metrics.pending_incoming_requests.set(pending_requests.len() as u64);
+				}
+				complete_request(&mut reply_manager, reply_context, res, &mut mixnet);
 			}
 		}
 
@@ -390,3 +512,60 @@ pub async fn run<B, C, S, N, P>(
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::request_handler::RequestHandler;
+	use futures::{executor::block_on, future::ready};
+
+	struct Echo;
+
+	impl RequestHandler for Echo {
+		fn handle(&self, payload: Vec<u8>) -> BoxFuture<'static, Result<Vec<u8>, RemoteErr>> {
+			Box::pin(ready(Ok(payload)))
+		}
+	}
+
+	fn request(protocol: &str, payload: &[u8]) -> Vec<u8> {
+		[&protocol.to_string().encode()[..], payload].concat()
+	}
+
+	#[test]
+	fn handled_requests_are_dispatched_by_protocol() {
+		let handlers = RequestHandlers::new().with_handler("echo/1", Echo);
+		let config = SubstrateConfig::default();
+
+		let Dispatch::Accepted(fut) =
+			dispatch_handled_request(&request("echo/1", b"hello"), &handlers, 0, &config)
+		else {
+			panic!("Request to a registered protocol should be accepted")
+		};
+		assert_eq!(block_on(fut).unwrap(), b"hello");
+
+		assert!(matches!(
+			dispatch_handled_request(&request("echo/2", b"hello"), &handlers, 0, &config),
+			Dispatch::Rejected(RemoteErr::UnsupportedProtocol(protocol)) if protocol == "echo/2"
+		));
+		assert!(matches!(
+			dispatch_handled_request(&[0xff], &handlers, 0, &config),
+			Dispatch::Rejected(RemoteErr::Decode(_))
+		));
+	}
+
+	#[test]
+	fn handled_requests_are_dropped_when_too_many_are_pending() {
+		let handlers = RequestHandlers::new().with_handler("echo/1", Echo);
+		let config = SubstrateConfig::default();
+		let request = request("echo/1", b"hello");
+
+		assert!(matches!(
+			dispatch_handled_request(&request, &handlers, config.max_pending_requests - 1, &config),
+			Dispatch::Accepted(_)
+		));
+		assert!(matches!(
+			dispatch_handled_request(&request, &handlers, config.max_pending_requests, &config),
+			Dispatch::Dropped
+		));
+	}
+}
@@ -0,0 +1,333 @@
+// This file is part of Substrate.
+
+// Copyright (C) Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Bitswap client, fetching content from peers.
+
+use crate::{
+	schema::bitswap::{
+		message::{
+			wantlist::{Entry as WantlistEntry, WantType},
+			Block as MessageBlock, Wantlist,
+		},
+		Message as BitswapMessage,
+	},
+	unixfs, BitswapError, Prefix, LOG_TARGET, MAX_WANTED_BLOCKS, PROTOCOL_NAME,
+};
+use cid::Cid;
+use libp2p_identity::PeerId;
+use log::{debug, trace};
+use prost::Message;
+use sc_network::{types::ProtocolName, IfDisconnected, NetworkRequest};
+use std::collections::HashMap;
+
+/// Fetches blocks and files by CID from peers serving them over bitswap.
+///
+/// Substrate nodes only serve the transactions indexed by `pallet_transaction_storage`, which are
+/// identified by their Blake2b-256 hash: they only answer for version 1 CIDs with a Blake2b-256
+/// multihash. Blocks using other hashes can only be fetched from peers serving them otherwise, and
+/// are checked against their CID all the same.
+///
+/// The bitswap protocol must be registered with the network for requests to be sent, e.g. with
+/// the configuration returned by [`BitswapRequestHandler::new`](crate::BitswapRequestHandler::new).
+pub struct BitswapClient<N> {
+	network: N,
+}
+
+impl<N: NetworkRequest> BitswapClient<N> {
+	/// Create a new [`BitswapClient`].
+	pub fn new(network: N) -> Self {
+		Self { network }
+	}
+
+	/// Fetch the block identified by `cid`, asking `peers` in turn.
+	pub async fn fetch_block(&self, peers: &[PeerId], cid: &Cid) -> Result<Vec<u8>, BitswapError> {
+		let mut blocks = self.fetch_blocks(peers, &[*cid]).await?;
+		Ok(blocks.remove(0))
+	}
+
+	/// Fetch the content identified by `cid`, asking `peers` in turn for every block.
+	///
+	/// If `cid` is the root of a UnixFS file, the whole file is fetched and reassembled. Fails with
+	/// [`BitswapError::TooLarge`] if the content is larger than `max_size` bytes, and with
+	/// [`BitswapError::TooManyBlocks`] if it spans more than `max_blocks` blocks.
+	pub async fn fetch(
+		&self,
+		peers: &[PeerId],
+		cid: &Cid,
+		max_size: usize,
+		max_blocks: usize,
+	) -> Result<Vec<u8>, BitswapError> {
+		enum Entry {
+			Cid(Cid),
+			Block(Cid, Vec<u8>),
+		}
+
+		let mut content = Vec::new();
+		let mut num_blocks = 0;
+		// Depth-first traversal, the next entry being at the top of the stack.
+		let mut stack = vec![Entry::Cid(*cid)];
+
+		while let Some(entry) = stack.pop() {
+			match entry {
+				Entry::Cid(cid) => {
+					// Fetch the following blocks along.
+					let mut cids = vec![cid];
+					while cids.len() < MAX_WANTED_BLOCKS {
+						let Some(Entry::Cid(cid)) = stack.last() else { break };
+						cids.push(*cid);
+						stack.pop();
+					}
+
+					let blocks = self.fetch_blocks(peers, &cids).await?;
+					stack.extend(
+						cids.into_iter()
+							.zip(blocks)
+							.rev()
+							.map(|(cid, block)| Entry::Block(cid, block)),
+					);
+				},
+				Entry::Block(cid, block) => {
+					let (data, links) = unixfs::decode_block(&cid, block)?;
+					content.extend(data);
+					num_blocks += 1;
+					if content.len() > max_size {
+						return Err(BitswapError::TooLarge(max_size))
+					}
+					if num_blocks > max_blocks {
+						return Err(BitswapError::TooManyBlocks(max_blocks))
+					}
+
+					stack.extend(links.into_iter().rev().map(Entry::Cid));
+				},
+			}
+		}
+
+		Ok(content)
+	}
+
+	/// Fetch the blocks identified by `cids`, returned in the same order.
+	///
+	/// `peers` are asked in turn for the blocks still missing.
+	async fn fetch_blocks(
+		&self,
+		peers: &[PeerId],
+		cids: &[Cid],
+	) -> Result<Vec<Vec<u8>>, BitswapError> {
+		let mut blocks = HashMap::new();
+
+		for peer in peers {
+			let missing = cids.iter().filter(|cid| !blocks.contains_key(*cid)).collect::<Vec<_>>();
+			if missing.is_empty() {
+				break
+			}
+
+			let request = BitswapMessage {
+				wantlist: Some(Wantlist {
+					entries: missing
+						.iter()
+						.map(|cid| WantlistEntry {
+							block: cid.to_bytes(),
+							priority: 1,
+							cancel: false,
+							want_type: WantType::Block as i32,
+							send_dont_have: true,
+						})
+						.collect(),
+					full: false,
+				}),
+				..Default::default()
+			};
+
+			let response = match self
+				.network
+				.request(
+					*peer,
+					ProtocolName::from(PROTOCOL_NAME),
+					request.encode_to_vec(),
+					IfDisconnected::TryConnect,
+				)
+				.await
+			{
+				Ok(response) => response,
+				Err(err) => {
+					debug!(target: LOG_TARGET, "Bitswap request to {peer} failed: {err}");
+					continue
+				},
+			};
+
+			let response = match BitswapMessage::decode(&response[..]) {
+				Ok(response) => response,
+				Err(err) => {
+					debug!(target: LOG_TARGET, "Invalid bitswap response from {peer}: {err}");
+					continue
+				},
+			};
+
+			for MessageBlock { prefix, data } in response.payload {
+				match Prefix::from_bytes(&prefix).and_then(|prefix| prefix.to_cid(&data)) {
+					Ok(cid) if missing.contains(&&cid) => {
+						trace!(target: LOG_TARGET, "Received block {cid} from {peer}");
+						blocks.insert(cid, data);
+					},
+					Ok(cid) => debug!(target: LOG_TARGET, "Unexpected block {cid} from {peer}"),
+					Err(err) => debug!(target: LOG_TARGET, "Invalid block from {peer}: {err}"),
+				}
+			}
+		}
+
+		cids.iter()
+			.map(|cid| blocks.get(cid).cloned().ok_or(BitswapError::NotFound(*cid)))
+			.collect()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{build_file, BitswapRequestHandler, UnixFsFile, DEFAULT_CHUNK_SIZE};
+	use futures::channel::oneshot;
+	use sc_block_builder::BlockBuilderBuilder;
+	use sc_network::{
+		request_responses::{IncomingRequest, OutgoingResponse},
+		RequestFailure,
+	};
+	use sp_consensus::BlockOrigin;
+	use std::sync::Arc;
+	use substrate_test_runtime::ExtrinsicBuilder;
+	use substrate_test_runtime_client::{self, prelude::*, TestClientBuilder};
+
+	/// Network routing requests to local request handlers.
+	#[derive(Clone, Default)]
+	struct Loopback(HashMap<PeerId, async_channel::Sender<IncomingRequest>>);
+
+	impl Loopback {
+		/// Add a peer serving the transactions indexed by `client`.
+		fn add_peer(&mut self, client: TestClient) -> PeerId {
+			let (handler, config) = BitswapRequestHandler::new(Arc::new(client));
+			tokio::spawn(handler.run());
+
+			let peer = PeerId::random();
+			self.0.insert(peer, config.inbound_queue.unwrap());
+			peer
+		}
+	}
+
+	#[async_trait::async_trait]
+	impl NetworkRequest for Loopback {
+		async fn request(
+			&self,
+			target: PeerId,
+			_protocol: ProtocolName,
+			request: Vec<u8>,
+			_connect: IfDisconnected,
+		) -> Result<Vec<u8>, RequestFailure> {
+			let queue = self.0.get(&target).ok_or(RequestFailure::NotConnected)?;
+			let (tx, rx) = oneshot::channel();
+			queue
+				.send(IncomingRequest {
+					peer: PeerId::random(),
+					payload: request,
+					pending_response: tx,
+				})
+				.await
+				.map_err(|_| RequestFailure::Refused)?;
+
+			let OutgoingResponse { result, .. } = rx.await.map_err(|_| RequestFailure::Refused)?;
+			result.map_err(|()| RequestFailure::Refused)
+		}
+
+		fn start_request(
+			&self,
+			target: PeerId,
+			protocol: ProtocolName,
+			request: Vec<u8>,
+			tx: oneshot::Sender<Result<Vec<u8>, RequestFailure>>,
+			connect: IfDisconnected,
+		) {
+			let network = self.clone();
+			tokio::spawn(async move {
+				let _ = tx.send(network.request(target, protocol, request, connect).await);
+			});
+		}
+	}
+
+	/// Client with every block of `file` stored in its own indexed transaction.
+	async fn client_storing(file: &UnixFsFile) -> TestClient {
+		let mut client = TestClientBuilder::with_tx_storage(u32::MAX).build();
+		let mut block_builder = BlockBuilderBuilder::new(&client)
+			.on_parent_block(client.chain_info().genesis_hash)
+			.with_parent_block_number(0)
+			.build()
+			.unwrap();
+		for block in &file.blocks {
+			block_builder
+				.push(ExtrinsicBuilder::new_indexed_call(block.clone()).build())
+				.unwrap();
+		}
+		let block = block_builder.build().unwrap().block;
+		client.import(BlockOrigin::File, block).await.unwrap();
+
+		client
+	}
+
+	#[tokio::test]
+	async fn fetch_multi_block_file() {
+		let data = (0..4096).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+		let file = build_file(&data, 16);
+		assert!(file.blocks.len() > 256);
+
+		let mut network = Loopback::default();
+		let unknown = PeerId::random();
+		let empty = network.add_peer(substrate_test_runtime_client::new());
+		let seeder = network.add_peer(client_storing(&file).await);
+		let client = BitswapClient::new(network);
+
+		let num_blocks = file.blocks.len();
+
+		assert_eq!(
+			client
+				.fetch(&[unknown, empty, seeder], &file.cid, data.len(), num_blocks)
+				.await
+				.unwrap(),
+			data
+		);
+		assert!(matches!(
+			client.fetch(&[seeder], &file.cid, data.len() - 1, num_blocks).await,
+			Err(BitswapError::TooLarge(_)),
+		));
+		assert!(matches!(
+			client.fetch(&[seeder], &file.cid, data.len(), num_blocks - 1).await,
+			Err(BitswapError::TooManyBlocks(_)),
+		));
+		assert!(matches!(
+			client.fetch(&[unknown, empty], &file.cid, data.len(), num_blocks).await,
+			Err(BitswapError::NotFound(cid)) if cid == file.cid,
+		));
+	}
+
+	#[tokio::test]
+	async fn fetch_single_block() {
+		let file = build_file(b"hello bitswap", DEFAULT_CHUNK_SIZE);
+		let mut network = Loopback::default();
+		let seeder = network.add_peer(client_storing(&file).await);
+		let client = BitswapClient::new(network);
+
+		assert_eq!(client.fetch_block(&[seeder], &file.cid).await.unwrap(), b"hello bitswap");
+		assert_eq!(client.fetch(&[seeder], &file.cid, 1024, 1).await.unwrap(), b"hello bitswap");
+	}
+}
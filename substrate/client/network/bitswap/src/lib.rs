@@ -14,13 +14,21 @@
 // You should have received a copy of the GNU General Public License
 // along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
 
-//! Bitswap server for Substrate.
+//! Bitswap server and client for Substrate.
 //!
 //! Allows querying transactions by hash over standard bitswap protocol
 //! Only supports bitswap 1.2.0.
 //! CID is expected to reference 256-bit Blake2b transaction hash.
-
-use cid::{self, Version};
+//!
+//! [`BitswapClient`] fetches content from peers running the server. Files too large for a single
+//! transaction can be split with [`build_file`] into UnixFS blocks, each stored in its own
+//! transaction, and fetched back as a whole by the CID of their root.
+
+use cid::{
+	self,
+	multihash::{Code, MultihashDigest},
+	Version,
+};
 use futures::StreamExt;
 use libp2p_identity::PeerId;
 use log::{debug, error, trace};
@@ -36,9 +44,15 @@ use schema::bitswap::{
 };
 use sp_runtime::traits::Block as BlockT;
 use std::{io, sync::Arc, time::Duration};
-use unsigned_varint::encode as varint_encode;
+use unsigned_varint::{decode as varint_decode, encode as varint_encode};
 
+mod client;
 mod schema;
+mod unixfs;
+
+pub use cid::Cid;
+pub use client::BitswapClient;
+pub use unixfs::{build_file, UnixFsFile, DEFAULT_CHUNK_SIZE, MAX_CHUNK_SIZE};
 
 const LOG_TARGET: &str = "bitswap";
 
@@ -88,6 +102,29 @@ impl Prefix {
 		res.extend_from_slice(mh_len);
 		res
 	}
+
+	/// Decode a prefix from bytes.
+	pub fn from_bytes(bytes: &[u8]) -> Result<Self, BitswapError> {
+		let (version, bytes) =
+			varint_decode::u64(bytes).map_err(|_| BitswapError::InvalidPrefix)?;
+		let (codec, bytes) = varint_decode::u64(bytes).map_err(|_| BitswapError::InvalidPrefix)?;
+		let (mh_type, bytes) =
+			varint_decode::u64(bytes).map_err(|_| BitswapError::InvalidPrefix)?;
+		let (mh_len, _) = varint_decode::u8(bytes).map_err(|_| BitswapError::InvalidPrefix)?;
+
+		Ok(Self { version: Version::try_from(version)?, codec, mh_type, mh_len })
+	}
+
+	/// Compute the CID of `data`, a block received with this prefix.
+	pub fn to_cid(&self, data: &[u8]) -> Result<cid::Cid, BitswapError> {
+		let code = Code::try_from(self.mh_type).map_err(|_| BitswapError::InvalidPrefix)?;
+		let hash = code.digest(data);
+		if hash.size() != self.mh_len {
+			return Err(BitswapError::InvalidPrefix)
+		}
+
+		Ok(cid::Cid::new(self.version, self.codec, hash)?)
+	}
 }
 
 /// Bitswap request handler
@@ -108,6 +145,7 @@ impl<B: BlockT> BitswapRequestHandler<B> {
 			max_response_size: MAX_PACKET_SIZE,
 			request_timeout: Duration::from_secs(15),
 			inbound_queue: Some(tx),
+			bandwidth: Default::default(),
 		};
 
 		(Self { client, request_receiver }, config)
@@ -194,6 +232,7 @@ impl<B: BlockT> BitswapRequestHandler<B> {
 				},
 			};
 
+			// Transactions are only indexed by their Blake2b-256 hash.
 			if cid.version() != cid::Version::V1 ||
 				cid.hash().code() != u64::from(cid::multihash::Code::Blake2b256) ||
 				cid.hash().size() != 32
@@ -284,6 +323,30 @@ pub enum BitswapError {
 	/// Too many blocks requested.
 	#[error("Too many block entries in the request.")]
 	TooManyEntries,
+
+	/// Block received with an invalid or unsupported CID prefix.
+	#[error("Invalid block prefix.")]
+	InvalidPrefix,
+
+	/// No peer returned the block.
+	#[error("Block {0} not found.")]
+	NotFound(cid::Cid),
+
+	/// Block encoded with a codec other than raw or DAG-PB.
+	#[error("Unsupported codec {0:#x}.")]
+	UnsupportedCodec(u64),
+
+	/// UnixFS node that isn't part of a file.
+	#[error("Unsupported UnixFS data type {0}.")]
+	UnsupportedDataType(i32),
+
+	/// Fetched content is larger than allowed.
+	#[error("Content larger than {0} bytes.")]
+	TooLarge(usize),
+
+	/// Fetched content spans more blocks than allowed.
+	#[error("Content spans more than {0} blocks.")]
+	TooManyBlocks(usize),
 }
 
 #[cfg(test)]
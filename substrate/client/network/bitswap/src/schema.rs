@@ -21,3 +21,11 @@
 pub(crate) mod bitswap {
 	include!(concat!(env!("OUT_DIR"), "/bitswap.message.rs"));
 }
+
+pub(crate) mod dag_pb {
+	include!(concat!(env!("OUT_DIR"), "/merkledag.pb.rs"));
+}
+
+pub(crate) mod unixfs {
+	include!(concat!(env!("OUT_DIR"), "/unixfs.pb.rs"));
+}
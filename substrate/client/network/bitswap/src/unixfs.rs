@@ -0,0 +1,229 @@
+// This file is part of Substrate.
+
+// Copyright (C) Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Files stored as UnixFS DAGs.
+//!
+//! A file too large for a single transaction is split into raw chunks, each stored by its own
+//! `store` extrinsic. The chunks are then linked by DAG-PB nodes, stored the same way, up to a
+//! single root whose CID identifies the whole file. As transactions are indexed by their Blake2b
+//! hash, all the blocks are addressed by Blake2b-256 CIDs.
+
+use crate::{
+	schema::{
+		dag_pb::{PbLink, PbNode},
+		unixfs::{data::DataType, Data},
+	},
+	BitswapError, MAX_PACKET_SIZE, MAX_WANTED_BLOCKS,
+};
+use cid::{
+	multihash::{Code, MultihashDigest},
+	Cid,
+};
+use prost::Message;
+
+/// Multicodec of raw binary blocks.
+pub(crate) const RAW_CODEC: u64 = 0x55;
+
+/// Multicodec of DAG-PB blocks.
+pub(crate) const DAG_PB_CODEC: u64 = 0x70;
+
+/// Default chunk size, the same as IPFS.
+pub const DEFAULT_CHUNK_SIZE: usize = 256 * 1024;
+
+/// Maximum chunk size, so that a full wantlist of chunks fits in a single response.
+pub const MAX_CHUNK_SIZE: usize = MAX_PACKET_SIZE as usize / MAX_WANTED_BLOCKS;
+
+/// Maximum number of links per DAG-PB node, the same as IPFS.
+const MAX_LINKS: usize = 174;
+
+/// Blocks making up a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnixFsFile {
+	/// CID of the root block, to fetch the whole file with.
+	pub cid: Cid,
+	/// Blocks to store, each in its own transaction. The root block is the last one.
+	pub blocks: Vec<Vec<u8>>,
+}
+
+/// Link from a DAG-PB node to a child.
+struct Link {
+	cid: Cid,
+	/// Size of the file content under the child.
+	file_size: u64,
+	/// Size of the blocks under the child, including its own.
+	total_size: u64,
+}
+
+/// Split `data` into chunks of `chunk_size` bytes, linked by a balanced UnixFS DAG.
+///
+/// A file that fits in a single chunk is stored as a single raw block.
+///
+/// # Panics
+///
+/// If `chunk_size` is zero or above [`MAX_CHUNK_SIZE`].
+pub fn build_file(data: &[u8], chunk_size: usize) -> UnixFsFile {
+	assert!((1..=MAX_CHUNK_SIZE).contains(&chunk_size), "Invalid chunk size {chunk_size}.");
+
+	let mut blocks = Vec::new();
+	let mut layer = data
+		.chunks(chunk_size)
+		.map(|chunk| {
+			blocks.push(chunk.to_vec());
+			Link {
+				cid: blake2_cid(RAW_CODEC, chunk),
+				file_size: chunk.len() as u64,
+				total_size: chunk.len() as u64,
+			}
+		})
+		.collect::<Vec<_>>();
+
+	if layer.is_empty() {
+		blocks.push(Vec::new());
+		layer.push(Link { cid: blake2_cid(RAW_CODEC, &[]), file_size: 0, total_size: 0 });
+	}
+
+	while layer.len() > 1 {
+		layer = layer
+			.chunks(MAX_LINKS)
+			.map(|links| {
+				let node = encode_file_node(links);
+				let link = Link {
+					cid: blake2_cid(DAG_PB_CODEC, &node),
+					file_size: links.iter().map(|link| link.file_size).sum(),
+					total_size: node.len() as u64 +
+						links.iter().map(|link| link.total_size).sum::<u64>(),
+				};
+				blocks.push(node);
+				link
+			})
+			.collect();
+	}
+
+	UnixFsFile { cid: layer[0].cid, blocks }
+}
+
+/// Decode a block of a file into the content it holds and the CIDs of its children, in order.
+pub(crate) fn decode_block(cid: &Cid, block: Vec<u8>) -> Result<(Vec<u8>, Vec<Cid>), BitswapError> {
+	match cid.codec() {
+		RAW_CODEC => Ok((block, Vec::new())),
+		DAG_PB_CODEC => {
+			let node = PbNode::decode(&block[..])?;
+			let content = match node.data {
+				Some(data) => {
+					let data = Data::decode(&data[..])?;
+					match DataType::from_i32(data.r#type) {
+						Some(DataType::File | DataType::Raw) => data.data.unwrap_or_default(),
+						_ => return Err(BitswapError::UnsupportedDataType(data.r#type)),
+					}
+				},
+				None => Vec::new(),
+			};
+			let links = node
+				.links
+				.into_iter()
+				.map(|link| Cid::read_bytes(link.hash.unwrap_or_default().as_slice()))
+				.collect::<Result<_, _>>()?;
+
+			Ok((content, links))
+		},
+		codec => Err(BitswapError::UnsupportedCodec(codec)),
+	}
+}
+
+fn blake2_cid(codec: u64, block: &[u8]) -> Cid {
+	Cid::new_v1(codec, Code::Blake2b256.digest(block))
+}
+
+/// Encode a UnixFS file node linking to `links`.
+fn encode_file_node(links: &[Link]) -> Vec<u8> {
+	let data = Data {
+		r#type: DataType::File as i32,
+		filesize: Some(links.iter().map(|link| link.file_size).sum()),
+		blocksizes: links.iter().map(|link| link.file_size).collect(),
+		..Default::default()
+	};
+	let node = PbNode {
+		links: links
+			.iter()
+			.map(|link| PbLink {
+				hash: Some(link.cid.to_bytes()),
+				name: Some(String::new()),
+				tsize: Some(link.total_size),
+			})
+			.collect(),
+		data: Some(data.encode_to_vec()),
+	};
+
+	// The canonical DAG-PB encoding puts the links first, unlike `prost` which follows the field
+	// numbers.
+	let mut encoded = Vec::with_capacity(node.encoded_len());
+	for link in &node.links {
+		prost::encoding::message::encode(2, link, &mut encoded);
+	}
+	if let Some(data) = &node.data {
+		prost::encoding::bytes::encode(1, data, &mut encoded);
+	}
+	encoded
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn small_file_is_a_raw_block() {
+		let file = build_file(b"hello", DEFAULT_CHUNK_SIZE);
+
+		assert_eq!(file.blocks, vec![b"hello".to_vec()]);
+		assert_eq!(file.cid.codec(), RAW_CODEC);
+		assert_eq!(
+			decode_block(&file.cid, file.blocks[0].clone()).unwrap(),
+			(b"hello".to_vec(), vec![])
+		);
+	}
+
+	#[test]
+	fn large_file_is_a_balanced_dag() {
+		let data = (0..(MAX_LINKS + 1) * 4).map(|i| i as u8).collect::<Vec<_>>();
+		let file = build_file(&data, 4);
+
+		// Leaves, two nodes linking them, and the root.
+		assert_eq!(file.blocks.len(), MAX_LINKS + 1 + 3);
+		assert_eq!(file.cid, blake2_cid(DAG_PB_CODEC, file.blocks.last().unwrap()));
+
+		let (content, links) =
+			decode_block(&file.cid, file.blocks.last().unwrap().clone()).unwrap();
+		assert!(content.is_empty());
+		assert_eq!(links.len(), 2);
+
+		let root = PbNode::decode(&file.blocks.last().unwrap()[..]).unwrap();
+		let root_data = Data::decode(&root.data.unwrap()[..]).unwrap();
+		assert_eq!(root_data.filesize, Some(data.len() as u64));
+		assert_eq!(root_data.blocksizes, vec![MAX_LINKS as u64 * 4, 4]);
+	}
+
+	#[test]
+	fn pb_node_is_canonically_encoded() {
+		let file = build_file(&[0u8; 8], 4);
+		let root = file.blocks.last().unwrap();
+
+		// Field 2 (links) comes first, as a length-delimited field.
+		assert_eq!(root[0], 2 << 3 | 2);
+		assert_eq!(PbNode::decode(&root[..]).unwrap().links.len(), 2);
+	}
+}
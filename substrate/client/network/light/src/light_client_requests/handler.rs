@@ -28,7 +28,7 @@ use futures::prelude::*;
 use libp2p_identity::PeerId;
 use log::{debug, trace};
 use prost::Message;
-use sc_client_api::{BlockBackend, ProofProvider};
+use sc_client_api::{BlockBackend, ProofProvider, StorageProof};
 use sc_network::{
 	config::ProtocolId,
 	request_responses::{IncomingRequest, OutgoingResponse, ProtocolConfig},
@@ -39,7 +39,12 @@ use sp_core::{
 	storage::{ChildInfo, ChildType, PrefixedStorageKey},
 };
 use sp_runtime::traits::Block;
-use std::{marker::PhantomData, sync::Arc};
+use std::{
+	collections::{HashMap, HashSet},
+	marker::PhantomData,
+	sync::Arc,
+	time::Instant,
+};
 
 const LOG_TARGET: &str = "light-client-request-handler";
 
@@ -47,11 +52,31 @@ const LOG_TARGET: &str = "light-client-request-handler";
 /// handling in production systems, this value is chosen to match the block request limit.
 const MAX_LIGHT_REQUEST_QUEUE: usize = 20;
 
+/// Maximum size of the proof of a range or batched child read, whatever the request asks for.
+const MAX_RANGE_PROOF_SIZE: usize = 2 * 1024 * 1024;
+
+/// Maximum number of child tries read by a batched child read request.
+const MAX_CHILD_TRIES_PER_REQUEST: usize = 64;
+
+/// Maximum number of keys read by a batched child read request, over all the child tries.
+const MAX_KEYS_PER_BATCH: usize = 1024;
+
+/// Proof bytes a peer can be sent per second in response to range and batched child reads.
+const MAX_PROOF_RATE_PER_PEER: i64 = 1024 * 1024;
+
+/// Proof bytes a peer can be sent at once in response to range and batched child reads.
+const MAX_PROOF_BURST_PER_PEER: i64 = 4 * MAX_RANGE_PROOF_SIZE as i64;
+
+/// Number of peers whose proof budget is tracked, before those with a full budget are forgotten.
+const MAX_RATE_LIMITED_PEERS: usize = 1024;
+
 /// Handler for incoming light client requests from a remote peer.
 pub struct LightClientRequestHandler<B, Client> {
 	request_receiver: async_channel::Receiver<IncomingRequest>,
 	/// Blockchain client.
 	client: Arc<Client>,
+	/// Proof budgets of the peers sending range and batched child reads.
+	rate_limiter: RateLimiter,
 	_block: PhantomData<B>,
 }
 
@@ -79,7 +104,15 @@ where
 		);
 		protocol_config.inbound_queue = Some(tx);
 
-		(Self { client, request_receiver, _block: PhantomData::default() }, protocol_config)
+		(
+			Self {
+				client,
+				request_receiver,
+				rate_limiter: RateLimiter::default(),
+				_block: PhantomData::default(),
+			},
+			protocol_config,
+		)
 	}
 
 	/// Run [`LightClientRequestHandler`].
@@ -148,6 +181,18 @@ where
 	) -> Result<Vec<u8>, HandleRequestError> {
 		let request = schema::v1::light::Request::decode(&payload[..])?;
 
+		// Range and batched child reads can ask for large proofs and are rate limited.
+		let rate_limited = matches!(
+			request.request,
+			Some(
+				schema::v1::light::request::Request::RemoteReadRangeRequest(_) |
+					schema::v1::light::request::Request::RemoteReadChildBatchRequest(_)
+			)
+		);
+		if rate_limited && !self.rate_limiter.has_budget(peer, Instant::now()) {
+			return Err(HandleRequestError::RateLimited)
+		}
+
 		let response = match &request.request {
 			Some(schema::v1::light::request::Request::RemoteCallRequest(r)) =>
 				self.on_remote_call_request(&peer, r)?,
@@ -155,6 +200,10 @@ where
 				self.on_remote_read_request(&peer, r)?,
 			Some(schema::v1::light::request::Request::RemoteReadChildRequest(r)) =>
 				self.on_remote_read_child_request(&peer, r)?,
+			Some(schema::v1::light::request::Request::RemoteReadRangeRequest(r)) =>
+				self.on_remote_read_range_request(&peer, r)?,
+			Some(schema::v1::light::request::Request::RemoteReadChildBatchRequest(r)) =>
+				self.on_remote_read_child_batch_request(&peer, r)?,
 			None =>
 				return Err(HandleRequestError::BadRequest("Remote request without request data.")),
 		};
@@ -162,6 +211,10 @@ where
 		let mut data = Vec::new();
 		response.encode(&mut data)?;
 
+		if rate_limited {
+			self.rate_limiter.consume(&peer, data.len());
+		}
+
 		Ok(data)
 	}
 
@@ -252,12 +305,7 @@ where
 
 		let block = Decode::decode(&mut request.block.as_ref())?;
 
-		let prefixed_key = PrefixedStorageKey::new_ref(&request.storage_key);
-		let child_info = match ChildType::from_prefixed_key(prefixed_key) {
-			Some((ChildType::ParentKeyId, storage_key)) => Ok(ChildInfo::new_default(storage_key)),
-			None => Err(sp_blockchain::Error::InvalidChildStorageKey),
-		};
-		let response = match child_info.and_then(|child_info| {
+		let response = match child_info(&request.storage_key).and_then(|child_info| {
 			self.client.read_child_proof(
 				block,
 				&child_info,
@@ -282,6 +330,223 @@ where
 			response: Some(schema::v1::light::response::Response::RemoteReadResponse(response)),
 		})
 	}
+
+	fn on_remote_read_range_request(
+		&mut self,
+		peer: &PeerId,
+		request: &schema::v1::light::RemoteReadRangeRequest,
+	) -> Result<schema::v1::light::Response, HandleRequestError> {
+		if let (Some(prefix), Some(start_key)) = (&request.prefix, &request.start_key) {
+			if !start_key.starts_with(prefix) {
+				debug!("Invalid remote read range request sent by {}.", peer);
+				return Err(HandleRequestError::BadRequest(
+					"Remote read range request with start key out of prefix.",
+				))
+			}
+		}
+
+		trace!(
+			"Remote read range request from {} ({} from {} at {:?}).",
+			peer,
+			fmt_keys(request.prefix.as_ref(), request.prefix.as_ref()),
+			fmt_keys(request.start_key.as_ref(), request.start_key.as_ref()),
+			request.block,
+		);
+
+		let block = Decode::decode(&mut request.block.as_ref())?;
+
+		// The start key was already read, start right after it.
+		let start_key = request.start_key.clone().map(|mut key| {
+			key.push(0);
+			key
+		});
+		let size_limit = request
+			.max_size
+			.map_or(MAX_RANGE_PROOF_SIZE, |size| (size as usize).min(MAX_RANGE_PROOF_SIZE));
+
+		let child_info = request.storage_key.as_ref().map(child_info).transpose();
+		let response = match child_info.and_then(|child_info| {
+			self.client.read_range_proof(
+				block,
+				child_info.as_ref(),
+				request.prefix.as_deref(),
+				start_key.as_deref(),
+				size_limit,
+			)
+		}) {
+			Ok((proof, count)) => {
+				trace!("Proved {} keys for remote read range request from {}.", count, peer);
+				schema::v1::light::RemoteReadRangeResponse { proof: Some(proof.encode()) }
+			},
+			Err(error) => {
+				trace!(
+					"remote read range request from {} (at {:?}) failed with: {}",
+					peer,
+					request.block,
+					error,
+				);
+				schema::v1::light::RemoteReadRangeResponse { proof: None }
+			},
+		};
+
+		Ok(schema::v1::light::Response {
+			response: Some(schema::v1::light::response::Response::RemoteReadRangeResponse(
+				response,
+			)),
+		})
+	}
+
+	fn on_remote_read_child_batch_request(
+		&mut self,
+		peer: &PeerId,
+		request: &schema::v1::light::RemoteReadChildBatchRequest,
+	) -> Result<schema::v1::light::Response, HandleRequestError> {
+		let num_keys = request.tries.iter().map(|trie| trie.keys.len()).sum::<usize>();
+		if request.tries.is_empty() || request.tries.iter().any(|trie| trie.keys.is_empty()) {
+			debug!("Invalid remote child batch read request sent by {}.", peer);
+			return Err(HandleRequestError::BadRequest(
+				"Remote read child batch request without keys.",
+			))
+		}
+		if request.tries.len() > MAX_CHILD_TRIES_PER_REQUEST || num_keys > MAX_KEYS_PER_BATCH {
+			debug!("Too large remote child batch read request sent by {}.", peer);
+			return Err(HandleRequestError::BadRequest(
+				"Remote read child batch request with too many keys.",
+			))
+		}
+
+		trace!(
+			"Remote read child batch request from {} ({} keys in {} tries at {:?}).",
+			peer,
+			num_keys,
+			request.tries.len(),
+			request.block,
+		);
+
+		let block = Decode::decode(&mut request.block.as_ref())?;
+
+		let response = match self.read_child_batch_proof(block, &request.tries) {
+			Ok((proof, count)) => {
+				trace!(
+					"Proved {} of {} keys for remote read child batch request from {}.",
+					count,
+					num_keys,
+					peer,
+				);
+				schema::v1::light::RemoteReadChildBatchResponse {
+					proof: Some(proof.encode()),
+					num_keys: Some(count),
+				}
+			},
+			Err(error) => {
+				trace!(
+					"remote read child batch request from {} (at {:?}) failed with: {}",
+					peer,
+					request.block,
+					error,
+				);
+				schema::v1::light::RemoteReadChildBatchResponse { proof: None, num_keys: None }
+			},
+		};
+
+		Ok(schema::v1::light::Response {
+			response: Some(schema::v1::light::response::Response::RemoteReadChildBatchResponse(
+				response,
+			)),
+		})
+	}
+
+	/// Prove the keys of `tries` in order, until the proof reaches [`MAX_RANGE_PROOF_SIZE`].
+	///
+	/// At least one key is proven. Returns the proof and the number of keys proven.
+	fn read_child_batch_proof(
+		&self,
+		block: B::Hash,
+		tries: &[schema::v1::light::ChildTrieKeys],
+	) -> sp_blockchain::Result<(StorageProof, u32)> {
+		let mut nodes = HashSet::new();
+		let mut size = 0;
+		let mut count = 0;
+
+		'tries: for trie in tries {
+			let child_info = child_info(&trie.storage_key)?;
+			for key in &trie.keys {
+				let proof = self.client.read_child_proof(
+					block,
+					&child_info,
+					&mut std::iter::once(key.as_ref()),
+				)?;
+				let new_nodes = proof
+					.into_iter_nodes()
+					.filter(|node| !nodes.contains(node))
+					.collect::<Vec<_>>();
+				let new_size = new_nodes.iter().map(Encode::encoded_size).sum::<usize>();
+				if count > 0 && size + new_size > MAX_RANGE_PROOF_SIZE {
+					break 'tries
+				}
+
+				nodes.extend(new_nodes);
+				size += new_size;
+				count += 1;
+			}
+		}
+
+		Ok((StorageProof::new(nodes), count))
+	}
+}
+
+/// Per-peer token buckets of proof bytes.
+#[derive(Default)]
+struct RateLimiter {
+	budgets: HashMap<PeerId, Budget>,
+}
+
+struct Budget {
+	/// Bytes left. Negative if the last response was larger than the budget.
+	bytes: i64,
+	last_refill: Instant,
+}
+
+impl Budget {
+	fn refill(&mut self, now: Instant) {
+		let elapsed = now.saturating_duration_since(self.last_refill).as_micros() as i64;
+		let refill = elapsed.saturating_mul(MAX_PROOF_RATE_PER_PEER) / 1_000_000;
+		self.bytes = self.bytes.saturating_add(refill).min(MAX_PROOF_BURST_PER_PEER);
+		self.last_refill = now;
+	}
+}
+
+impl RateLimiter {
+	/// Refill the budget of `peer` and return whether anything is left of it.
+	fn has_budget(&mut self, peer: PeerId, now: Instant) -> bool {
+		if self.budgets.len() >= MAX_RATE_LIMITED_PEERS && !self.budgets.contains_key(&peer) {
+			self.budgets.retain(|_, budget| {
+				budget.refill(now);
+				budget.bytes < MAX_PROOF_BURST_PER_PEER
+			});
+		}
+
+		let budget = self
+			.budgets
+			.entry(peer)
+			.or_insert(Budget { bytes: MAX_PROOF_BURST_PER_PEER, last_refill: now });
+		budget.refill(now);
+		budget.bytes > 0
+	}
+
+	/// Deduct `bytes` sent to `peer` from its budget.
+	fn consume(&mut self, peer: &PeerId, bytes: usize) {
+		if let Some(budget) = self.budgets.get_mut(peer) {
+			budget.bytes = budget.bytes.saturating_sub(bytes as i64);
+		}
+	}
+}
+
+fn child_info(storage_key: &Vec<u8>) -> Result<ChildInfo, sp_blockchain::Error> {
+	match ChildType::from_prefixed_key(PrefixedStorageKey::new_ref(storage_key)) {
+		Some((ChildType::ParentKeyId, storage_key)) => Ok(ChildInfo::new_default(storage_key)),
+		None => Err(sp_blockchain::Error::InvalidChildStorageKey),
+	}
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -292,6 +557,9 @@ enum HandleRequestError {
 	EncodeProto(#[from] prost::EncodeError),
 	#[error("Failed to send response.")]
 	SendResponse,
+	/// The peer exhausted its budget of proof bytes.
+	#[error("rate limited")]
+	RateLimited,
 	/// A bad request has been received.
 	#[error("bad request: {0}")]
 	BadRequest(&'static str),
@@ -311,3 +579,264 @@ fn fmt_keys(first: Option<&Vec<u8>>, last: Option<&Vec<u8>>) -> String {
 		String::from("n/a")
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sc_client_api::CompactProof;
+	use schema::v1::light::{
+		request::Request, response::Response, ChildTrieKeys, RemoteReadChildBatchRequest,
+		RemoteReadChildBatchResponse, RemoteReadRangeRequest, RemoteReadRangeResponse,
+	};
+	use sp_blockchain::HeaderBackend;
+	use sp_runtime::traits::{HashingFor, Header as _};
+	use substrate_test_runtime_client::{prelude::*, runtime::Block};
+
+	type Handler = LightClientRequestHandler<Block, TestClient>;
+
+	fn handler(client: TestClient) -> Handler {
+		LightClientRequestHandler::new(&ProtocolId::from("test"), None, Arc::new(client)).0
+	}
+
+	fn send(
+		handler: &mut Handler,
+		peer: PeerId,
+		request: Request,
+	) -> Result<Response, HandleRequestError> {
+		let payload = schema::v1::light::Request { request: Some(request) }.encode_to_vec();
+		let response = handler.handle_request(peer, payload)?;
+		Ok(schema::v1::light::Response::decode(&response[..]).unwrap().response.unwrap())
+	}
+
+	fn asset_key(index: u8) -> Vec<u8> {
+		[&b"assets:"[..], &[index]].concat()
+	}
+
+	#[test]
+	fn range_read_enumerates_prefix() {
+		let client = (0..50)
+			.fold(TestClientBuilder::new(), |builder, index| {
+				builder.add_extra_storage(asset_key(index), vec![index; 64])
+			})
+			.add_extra_storage(b"other".to_vec(), vec![0; 64])
+			.build();
+		let hash = client.chain_info().genesis_hash;
+		let root = *client.header(hash).unwrap().unwrap().state_root();
+		let mut handler = handler(client);
+		let peer = PeerId::random();
+
+		let mut entries = Vec::new();
+		let mut requests = 0;
+		let mut start_key: Option<Vec<u8>> = None;
+		loop {
+			requests += 1;
+			let request = RemoteReadRangeRequest {
+				block: hash.encode(),
+				storage_key: None,
+				prefix: Some(b"assets:".to_vec()),
+				start_key: start_key.clone(),
+				max_size: Some(512),
+			};
+			let Response::RemoteReadRangeResponse(RemoteReadRangeResponse { proof: Some(proof) }) =
+				send(&mut handler, peer, Request::RemoteReadRangeRequest(request)).unwrap()
+			else {
+				panic!("Unexpected response")
+			};
+
+			let (proof, _) = CompactProof::decode(&mut &proof[..])
+				.unwrap()
+				.to_storage_proof::<HashingFor<Block>>(Some(&root))
+				.unwrap();
+			let check_start = start_key.map(|mut key| {
+				key.push(0);
+				key
+			});
+			let (read, complete) = sp_state_machine::read_range_proof_check::<HashingFor<Block>>(
+				root,
+				proof,
+				None,
+				Some(&b"assets:"[..]),
+				None,
+				check_start.as_deref(),
+			)
+			.unwrap();
+
+			entries.extend(read);
+			if complete {
+				break
+			}
+			start_key = entries.last().map(|(key, _)| key.clone());
+		}
+
+		assert!(requests > 2);
+		assert_eq!(
+			entries,
+			(0..50).map(|index| (asset_key(index), vec![index; 64])).collect::<Vec<_>>()
+		);
+	}
+
+	#[test]
+	fn range_read_start_key_must_match_prefix() {
+		let client = substrate_test_runtime_client::new();
+		let hash = client.chain_info().genesis_hash;
+		let mut handler = handler(client);
+
+		let request = RemoteReadRangeRequest {
+			block: hash.encode(),
+			storage_key: None,
+			prefix: Some(b"assets:".to_vec()),
+			start_key: Some(b"other".to_vec()),
+			max_size: None,
+		};
+		assert!(matches!(
+			send(&mut handler, PeerId::random(), Request::RemoteReadRangeRequest(request)),
+			Err(HandleRequestError::BadRequest(_)),
+		));
+	}
+
+	#[test]
+	fn child_batch_read_proves_all_tries() {
+		let child1 = ChildInfo::new_default(b"child1");
+		let child2 = ChildInfo::new_default(b"child2");
+		let client = TestClientBuilder::new()
+			.add_child_storage(&child1, b"key1", vec![1])
+			.add_child_storage(&child2, b"key2", vec![2])
+			.build();
+		let hash = client.chain_info().genesis_hash;
+		let root = *client.header(hash).unwrap().unwrap().state_root();
+		let mut handler = handler(client);
+
+		let request = RemoteReadChildBatchRequest {
+			block: hash.encode(),
+			tries: vec![
+				ChildTrieKeys {
+					storage_key: child1.prefixed_storage_key().into_inner(),
+					keys: vec![b"key1".to_vec()],
+				},
+				ChildTrieKeys {
+					storage_key: child2.prefixed_storage_key().into_inner(),
+					keys: vec![b"key2".to_vec(), b"missing".to_vec()],
+				},
+			],
+		};
+		let Response::RemoteReadChildBatchResponse(RemoteReadChildBatchResponse {
+			proof: Some(proof),
+			num_keys: Some(3),
+		}) = send(&mut handler, PeerId::random(), Request::RemoteReadChildBatchRequest(request))
+			.unwrap()
+		else {
+			panic!("Unexpected response")
+		};
+		let proof = StorageProof::decode(&mut &proof[..]).unwrap();
+
+		let read = |child_info, keys: &[&[u8]]| {
+			sp_state_machine::read_child_proof_check::<HashingFor<Block>, _>(
+				root,
+				proof.clone(),
+				child_info,
+				keys,
+			)
+			.unwrap()
+		};
+		assert_eq!(
+			read(&child1, &[b"key1".as_slice()]),
+			HashMap::from([(b"key1".to_vec(), Some(vec![1]))])
+		);
+		assert_eq!(
+			read(&child2, &[b"key2".as_slice(), b"missing".as_slice()]),
+			HashMap::from([(b"key2".to_vec(), Some(vec![2])), (b"missing".to_vec(), None)]),
+		);
+	}
+
+	#[test]
+	fn child_batch_read_is_size_limited() {
+		let child = ChildInfo::new_default(b"child");
+		let keys = (0..3u8).map(asset_key).collect::<Vec<_>>();
+		let value = |key: &Vec<u8>| vec![key[key.len() - 1]; MAX_RANGE_PROOF_SIZE / 2];
+		let client = keys
+			.iter()
+			.fold(TestClientBuilder::new(), |builder, key| {
+				builder.add_child_storage(&child, key.clone(), value(key))
+			})
+			.build();
+		let hash = client.chain_info().genesis_hash;
+		let root = *client.header(hash).unwrap().unwrap().state_root();
+		let mut handler = handler(client);
+		let peer = PeerId::random();
+
+		// Every value fills half of the proof, so a single key is proven per response.
+		let mut remaining = keys.clone();
+		while !remaining.is_empty() {
+			let request = RemoteReadChildBatchRequest {
+				block: hash.encode(),
+				tries: vec![ChildTrieKeys {
+					storage_key: child.prefixed_storage_key().into_inner(),
+					keys: remaining.clone(),
+				}],
+			};
+			let Response::RemoteReadChildBatchResponse(RemoteReadChildBatchResponse {
+				proof: Some(proof),
+				num_keys: Some(1),
+			}) = send(&mut handler, peer, Request::RemoteReadChildBatchRequest(request)).unwrap()
+			else {
+				panic!("Unexpected response")
+			};
+
+			let key = remaining.remove(0);
+			let read = sp_state_machine::read_child_proof_check::<HashingFor<Block>, _>(
+				root,
+				StorageProof::decode(&mut &proof[..]).unwrap(),
+				&child,
+				&[&key],
+			)
+			.unwrap();
+			assert_eq!(read, HashMap::from([(key.clone(), Some(value(&key)))]));
+		}
+	}
+
+	#[test]
+	fn child_batch_read_without_keys_is_rejected() {
+		let client = substrate_test_runtime_client::new();
+		let hash = client.chain_info().genesis_hash;
+		let mut handler = handler(client);
+
+		let request = RemoteReadChildBatchRequest { block: hash.encode(), tries: Vec::new() };
+		assert!(matches!(
+			send(&mut handler, PeerId::random(), Request::RemoteReadChildBatchRequest(request)),
+			Err(HandleRequestError::BadRequest(_)),
+		));
+	}
+
+	#[test]
+	fn range_reads_are_rate_limited() {
+		let client = substrate_test_runtime_client::new();
+		let hash = client.chain_info().genesis_hash;
+		let mut handler = handler(client);
+		let (peer, other_peer) = (PeerId::random(), PeerId::random());
+		let request = || {
+			Request::RemoteReadRangeRequest(RemoteReadRangeRequest {
+				block: hash.encode(),
+				storage_key: None,
+				prefix: None,
+				start_key: None,
+				max_size: None,
+			})
+		};
+
+		assert!(send(&mut handler, peer, request()).is_ok());
+
+		// Exhaust the budget of `peer`.
+		handler.rate_limiter.consume(&peer, MAX_PROOF_BURST_PER_PEER as usize);
+		assert!(matches!(
+			send(&mut handler, peer, request()),
+			Err(HandleRequestError::RateLimited)
+		));
+		assert!(send(&mut handler, other_peer, request()).is_ok());
+
+		// The budget refills over time.
+		let now = Instant::now();
+		handler.rate_limiter.budgets.get_mut(&peer).unwrap().last_refill =
+			now - std::time::Duration::from_secs(10);
+		assert!(handler.rate_limiter.has_budget(peer, now));
+	}
+}
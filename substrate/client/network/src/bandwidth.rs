@@ -0,0 +1,378 @@
+// This file is part of Substrate.
+
+// Copyright (C) Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Bandwidth limiting of notification and request-response protocols.
+//!
+//! Every protocol gets a [`ProtocolThrottle`], shared by all the connections of the node. A
+//! throttle combines the token bucket of the protocol limits with the node-wide token bucket of
+//! [`NetworkConfiguration::bandwidth_limits`](crate::config::NetworkConfiguration), which is
+//! accessed according to the [`BandwidthPriority`] of the protocol.
+//!
+//! Buckets are allowed to go into debt: a message is transferred as soon as the buckets allow it
+//! and its full size is deducted afterwards, delaying the messages that follow. This way messages
+//! larger than the capacity of a bucket can't stall a protocol forever.
+
+use crate::{
+	config::{BandwidthLimits, BandwidthPriority, ProtocolBandwidth},
+	types::ProtocolName,
+};
+
+use futures::{future::poll_fn, FutureExt};
+use parking_lot::Mutex;
+use prometheus_endpoint::{register, Counter, CounterVec, Opts, PrometheusError, Registry, U64};
+use tokio::time::{sleep, Instant, Sleep};
+
+use std::{
+	fmt,
+	num::NonZeroU32,
+	pin::Pin,
+	sync::Arc,
+	task::{Context, Poll},
+	time::Duration,
+};
+
+/// Minimum time to wait for a bucket to refill, to avoid waking up for a handful of bytes.
+const MIN_DELAY: Duration = Duration::from_millis(5);
+
+/// Token bucket holding up to one second worth of bytes.
+#[derive(Debug)]
+struct TokenBucket {
+	/// Refill rate in bytes per second, and capacity of the bucket.
+	rate: u64,
+	/// Available bytes. Negative if the bucket is in debt.
+	tokens: i64,
+	/// Last time the bucket was refilled.
+	last_refill: Instant,
+}
+
+impl TokenBucket {
+	fn new(rate: NonZeroU32, now: Instant) -> Self {
+		let rate = u64::from(rate.get());
+		Self { rate, tokens: rate as i64, last_refill: now }
+	}
+
+	fn refill(&mut self, now: Instant) {
+		let elapsed = now.saturating_duration_since(self.last_refill).as_micros();
+		let refill = (elapsed * u128::from(self.rate) / 1_000_000).min(u128::from(self.rate));
+
+		// Don't move `last_refill` forward if not even a single byte has been refilled, otherwise
+		// frequent polling would prevent the bucket from ever refilling.
+		if refill > 0 {
+			self.tokens = self.tokens.saturating_add(refill as i64).min(self.rate as i64);
+			self.last_refill = now;
+		}
+	}
+
+	/// Time until the bucket holds at least `threshold` bytes.
+	fn time_until(&self, threshold: i64) -> Duration {
+		if self.tokens >= threshold {
+			return Duration::ZERO
+		}
+
+		let deficit = threshold.saturating_sub(self.tokens) as u64;
+		Duration::from_micros(deficit.saturating_mul(1_000_000) / self.rate).max(MIN_DELAY)
+	}
+
+	fn consume(&mut self, bytes: usize) {
+		self.tokens = self.tokens.saturating_sub(i64::try_from(bytes).unwrap_or(i64::MAX));
+	}
+}
+
+/// Throttle of one direction of a protocol.
+///
+/// The default throttle is unlimited.
+#[derive(Clone, Default)]
+pub struct Throttle {
+	/// Bucket of the protocol limit.
+	protocol: Option<Arc<Mutex<TokenBucket>>>,
+	/// Node-wide bucket.
+	shared: Option<Arc<Mutex<TokenBucket>>>,
+	/// Priority of the protocol when accessing the node-wide bucket.
+	priority: BandwidthPriority,
+	/// Number of bytes whose transfer has been delayed.
+	throttled_bytes: Option<Counter<U64>>,
+}
+
+impl fmt::Debug for Throttle {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Throttle")
+			.field("protocol", &self.protocol.as_ref().map(|bucket| bucket.lock().rate))
+			.field("shared", &self.shared.as_ref().map(|bucket| bucket.lock().rate))
+			.field("priority", &self.priority)
+			.finish()
+	}
+}
+
+impl Throttle {
+	/// Returns `true` if the throttle never delays anything.
+	pub fn is_unlimited(&self) -> bool {
+		self.protocol.is_none() && self.shared.is_none()
+	}
+
+	/// Wait until transferring a message of `bytes` is allowed and account for it.
+	pub async fn acquire(&self, bytes: usize) {
+		let mut state = ThrottleState::default();
+		poll_fn(|cx| state.poll_ready(self, cx)).await;
+		state.consume(self, bytes);
+	}
+
+	/// Time to wait before the next message can be transferred.
+	fn wait_time(&self, now: Instant) -> Duration {
+		let protocol = self.protocol.as_ref().map_or(Duration::ZERO, |bucket| {
+			let mut bucket = bucket.lock();
+			bucket.refill(now);
+			bucket.time_until(0)
+		});
+
+		let shared = match (&self.shared, self.priority) {
+			(None, _) | (_, BandwidthPriority::High) => Duration::ZERO,
+			(Some(bucket), priority) => {
+				let mut bucket = bucket.lock();
+				bucket.refill(now);
+
+				// Low priority traffic leaves half of the budget to the other protocols.
+				let threshold =
+					if priority == BandwidthPriority::Low { bucket.rate as i64 / 2 } else { 0 };
+				bucket.time_until(threshold)
+			},
+		};
+
+		protocol.max(shared)
+	}
+
+	fn consume(&self, bytes: usize, now: Instant) {
+		for bucket in self.protocol.iter().chain(self.shared.iter()) {
+			let mut bucket = bucket.lock();
+			bucket.refill(now);
+			bucket.consume(bytes);
+		}
+	}
+}
+
+/// State of a single transfer channel, such as a substream, going through a [`Throttle`].
+///
+/// Waiting for a limited throttle relies on the tokio timer, so it must happen within a tokio
+/// runtime.
+#[derive(Default)]
+pub struct ThrottleState {
+	/// Timer to wait for before checking the throttle again.
+	delay: Option<Pin<Box<Sleep>>>,
+	/// Whether the next message has been delayed.
+	throttled: bool,
+}
+
+impl ThrottleState {
+	/// Poll until `throttle` allows transferring the next message.
+	pub fn poll_ready(&mut self, throttle: &Throttle, cx: &mut Context) -> Poll<()> {
+		if throttle.is_unlimited() {
+			return Poll::Ready(())
+		}
+
+		loop {
+			if let Some(delay) = self.delay.as_mut() {
+				futures::ready!(delay.poll_unpin(cx));
+				self.delay = None;
+			}
+
+			let wait = throttle.wait_time(Instant::now());
+			if wait.is_zero() {
+				return Poll::Ready(())
+			}
+
+			self.throttled = true;
+			self.delay = Some(Box::pin(sleep(wait)));
+		}
+	}
+
+	/// Account for a message of `bytes` that has been transferred.
+	pub fn consume(&mut self, throttle: &Throttle, bytes: usize) {
+		if throttle.is_unlimited() {
+			return
+		}
+
+		throttle.consume(bytes, Instant::now());
+
+		if std::mem::take(&mut self.throttled) {
+			if let Some(throttled_bytes) = &throttle.throttled_bytes {
+				throttled_bytes.inc_by(bytes as u64);
+			}
+		}
+	}
+}
+
+/// Upload and download throttles of a protocol.
+#[derive(Debug, Clone, Default)]
+pub struct ProtocolThrottle {
+	/// Throttle of the data sent to peers.
+	pub upload: Throttle,
+	/// Throttle of the data received from peers.
+	pub download: Throttle,
+}
+
+/// Node-wide bandwidth limits, creating the [`ProtocolThrottle`] of each protocol.
+pub struct Bandwidth {
+	upload: Option<Arc<Mutex<TokenBucket>>>,
+	download: Option<Arc<Mutex<TokenBucket>>>,
+	throttled_bytes: Option<CounterVec<U64>>,
+}
+
+impl Bandwidth {
+	/// Create node-wide state for `limits`, registering the metrics in `registry` if provided.
+	pub fn new(
+		limits: &BandwidthLimits,
+		registry: Option<&Registry>,
+	) -> Result<Self, PrometheusError> {
+		let now = Instant::now();
+		let bucket = |rate: Option<NonZeroU32>| {
+			rate.map(|rate| Arc::new(Mutex::new(TokenBucket::new(rate, now))))
+		};
+
+		let throttled_bytes = registry
+			.map(|registry| {
+				register(
+					CounterVec::new(
+						Opts::new(
+							"substrate_sub_libp2p_throttled_bytes_total",
+							"Total number of bytes whose transfer has been delayed by bandwidth limits",
+						),
+						&["protocol", "direction"],
+					)?,
+					registry,
+				)
+			})
+			.transpose()?;
+
+		Ok(Self {
+			upload: bucket(limits.upload),
+			download: bucket(limits.download),
+			throttled_bytes,
+		})
+	}
+
+	/// Create the throttles of `protocol`.
+	pub fn protocol(
+		&self,
+		protocol: &ProtocolName,
+		config: &ProtocolBandwidth,
+	) -> ProtocolThrottle {
+		let now = Instant::now();
+		let throttle = |rate: Option<NonZeroU32>,
+		                shared: &Option<Arc<Mutex<TokenBucket>>>,
+		                direction: &str| Throttle {
+			protocol: rate.map(|rate| Arc::new(Mutex::new(TokenBucket::new(rate, now)))),
+			shared: shared.clone(),
+			priority: config.priority,
+			throttled_bytes: self
+				.throttled_bytes
+				.as_ref()
+				.map(|counter| counter.with_label_values(&[protocol, direction])),
+		};
+
+		ProtocolThrottle {
+			upload: throttle(config.limits.upload, &self.upload, "out"),
+			download: throttle(config.limits.download, &self.download, "in"),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn bucket(rate: u32, now: Instant) -> Arc<Mutex<TokenBucket>> {
+		Arc::new(Mutex::new(TokenBucket::new(NonZeroU32::new(rate).unwrap(), now)))
+	}
+
+	fn throttle(
+		protocol: Option<&Arc<Mutex<TokenBucket>>>,
+		shared: Option<&Arc<Mutex<TokenBucket>>>,
+		priority: BandwidthPriority,
+	) -> Throttle {
+		Throttle {
+			protocol: protocol.cloned(),
+			shared: shared.cloned(),
+			priority,
+			throttled_bytes: None,
+		}
+	}
+
+	#[test]
+	fn bucket_refills_at_rate() {
+		let now = Instant::now();
+		let bucket = bucket(1000, now);
+		let throttle = throttle(Some(&bucket), None, BandwidthPriority::Normal);
+
+		assert_eq!(throttle.wait_time(now), Duration::ZERO);
+
+		// Going into debt delays the next message until the debt is repaid.
+		throttle.consume(1500, now);
+		assert_eq!(bucket.lock().tokens, -500);
+		assert_eq!(throttle.wait_time(now), Duration::from_millis(500));
+		assert_eq!(
+			throttle.wait_time(now + Duration::from_millis(200)),
+			Duration::from_millis(300)
+		);
+		assert_eq!(throttle.wait_time(now + Duration::from_millis(500)), Duration::ZERO);
+
+		// The bucket never holds more than one second worth of bytes.
+		throttle.consume(0, now + Duration::from_secs(10));
+		assert_eq!(bucket.lock().tokens, 1000);
+	}
+
+	#[test]
+	fn priorities_share_node_wide_budget() {
+		let now = Instant::now();
+		let shared = bucket(1000, now);
+		let high = throttle(None, Some(&shared), BandwidthPriority::High);
+		let normal = throttle(None, Some(&shared), BandwidthPriority::Normal);
+		let low = throttle(None, Some(&shared), BandwidthPriority::Low);
+
+		// Half of the budget is used, low priority traffic must wait.
+		normal.consume(600, now);
+		assert_eq!(low.wait_time(now), Duration::from_millis(100));
+		assert_eq!(normal.wait_time(now), Duration::ZERO);
+
+		// The budget is exhausted, only high priority traffic goes through.
+		high.consume(600, now);
+		assert_eq!(high.wait_time(now), Duration::ZERO);
+		assert_eq!(normal.wait_time(now), Duration::from_millis(200));
+		assert_eq!(low.wait_time(now), Duration::from_millis(700));
+	}
+
+	#[test]
+	fn protocol_limit_applies_regardless_of_priority() {
+		let now = Instant::now();
+		let protocol = bucket(100, now);
+		let shared = bucket(1000, now);
+		let high = throttle(Some(&protocol), Some(&shared), BandwidthPriority::High);
+
+		high.consume(200, now);
+		assert_eq!(high.wait_time(now), Duration::from_secs(1));
+		assert_eq!(shared.lock().tokens, 800);
+	}
+
+	#[test]
+	fn unlimited_throttle_is_always_ready() {
+		let throttle = Throttle::default();
+		let mut state = ThrottleState::default();
+
+		assert!(throttle.is_unlimited());
+		futures::executor::block_on(poll_fn(|cx| state.poll_ready(&throttle, cx)));
+		state.consume(&throttle, usize::MAX);
+	}
+}
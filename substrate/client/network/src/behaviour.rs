@@ -17,6 +17,7 @@
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
 use crate::{
+	bandwidth::Bandwidth,
 	discovery::{DiscoveryBehaviour, DiscoveryConfig, DiscoveryOut},
 	event::DhtEvent,
 	peer_info,
@@ -173,6 +174,7 @@ impl<B: BlockT> Behaviour<B> {
 		local_public_key: PublicKey,
 		disco_config: DiscoveryConfig,
 		request_response_protocols: Vec<ProtocolConfig>,
+		bandwidth: &Bandwidth,
 		peer_store_handle: PeerStoreHandle,
 		external_addresses: Arc<Mutex<HashSet<Multiaddr>>>,
 	) -> Result<Self, request_responses::RegisterError> {
@@ -186,6 +188,7 @@ impl<B: BlockT> Behaviour<B> {
 			discovery: disco_config.finish(),
 			request_responses: request_responses::RequestResponsesBehaviour::new(
 				request_response_protocols.into_iter(),
+				bandwidth,
 				Box::new(peer_store_handle),
 			)?,
 		})
@@ -201,6 +204,11 @@ impl<B: BlockT> Behaviour<B> {
 		self.discovery.add_known_address(peer_id, addr)
 	}
 
+	/// Adds an address for the given peer to the DHT, which expires if the peer is unreachable.
+	pub fn add_discovered_address(&mut self, peer_id: PeerId, addr: Multiaddr) {
+		self.discovery.add_discovered_address(peer_id, addr)
+	}
+
 	/// Returns the number of nodes in each Kademlia kbucket.
 	///
 	/// Identifies kbuckets by the base 2 logarithm of their lower bound.
@@ -57,7 +57,7 @@ use std::{
 	io::{self, Write},
 	iter,
 	net::Ipv4Addr,
-	num::NonZeroUsize,
+	num::{NonZeroU32, NonZeroUsize},
 	path::{Path, PathBuf},
 	pin::Pin,
 	str::{self, FromStr},
@@ -484,6 +484,9 @@ pub struct NonDefaultSetConfig {
 	/// Base configuration.
 	set_config: SetConfig,
 
+	/// Bandwidth limits and priority of the protocol.
+	bandwidth: ProtocolBandwidth,
+
 	/// Notification handle.
 	///
 	/// Notification handle is created during `NonDefaultSetConfig` creation and its other half,
@@ -494,6 +497,71 @@ pub struct NonDefaultSetConfig {
 	protocol_handle_pair: ProtocolHandlePair,
 }
 
+/// Limits on the number of regular peers sharing an IP address or a subnet, applied to every set.
+///
+/// IPv4 addresses are grouped by /32 for [`IpConnectionLimits::max_inbound_per_ip`] and by /24
+/// for the subnet limits. IPv6 addresses are grouped by /64 and /48 respectively, as a single
+/// host usually controls a whole /64. Reserved peers are not subject to these limits.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IpConnectionLimits {
+	/// Maximum number of inbound peers per IP address. `None` if unlimited.
+	pub max_inbound_per_ip: Option<u32>,
+
+	/// Maximum number of inbound peers per subnet. `None` if unlimited.
+	pub max_inbound_per_subnet: Option<u32>,
+
+	/// Maximum number of outbound peers per subnet. `None` if unlimited.
+	///
+	/// Keeps the outbound slots spread over several networks, so that an attacker controlling a
+	/// single subnet can't take all of them.
+	pub max_outbound_per_subnet: Option<u32>,
+}
+
+/// Upload and download rate limits.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BandwidthLimits {
+	/// Maximum upload rate, in bytes per second. `None` if unlimited.
+	pub upload: Option<NonZeroU32>,
+
+	/// Maximum download rate, in bytes per second. `None` if unlimited.
+	pub download: Option<NonZeroU32>,
+}
+
+/// Priority of the traffic of a protocol.
+///
+/// The priority only matters if [`NetworkConfiguration::bandwidth_limits`] are set. Traffic of
+/// [`BandwidthPriority::High`] protocols is never delayed by the node-wide limits, but still counts
+/// towards them. [`BandwidthPriority::Normal`] traffic is delayed once the node-wide budget is
+/// exhausted, and [`BandwidthPriority::Low`] traffic as soon as half of it is used, leaving the
+/// rest for higher priority protocols.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum BandwidthPriority {
+	/// Bulk transfers, e.g. block and state requests.
+	Low,
+	/// Default priority.
+	#[default]
+	Normal,
+	/// Latency-sensitive traffic, e.g. finality votes.
+	High,
+}
+
+/// Bandwidth configuration of a notification or request-response protocol.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProtocolBandwidth {
+	/// Limits applying to this protocol only, summed over all peers.
+	pub limits: BandwidthLimits,
+
+	/// Priority of the protocol when the node-wide limits are reached.
+	pub priority: BandwidthPriority,
+}
+
+impl ProtocolBandwidth {
+	/// Unlimited protocol with the given priority.
+	pub fn with_priority(priority: BandwidthPriority) -> Self {
+		Self { limits: BandwidthLimits::default(), priority }
+	}
+}
+
 impl NonDefaultSetConfig {
 	/// Creates a new [`NonDefaultSetConfig`]. Zero slots and accepts only reserved nodes.
 	/// Also returns an object which allows the protocol to communicate with `Notifications`.
@@ -513,6 +581,7 @@ impl NonDefaultSetConfig {
 				fallback_names,
 				handshake,
 				set_config,
+				bandwidth: Default::default(),
 				protocol_handle_pair,
 			},
 			notification_service,
@@ -544,6 +613,16 @@ impl NonDefaultSetConfig {
 		&self.set_config
 	}
 
+	/// Get bandwidth limits and priority of the protocol.
+	pub fn bandwidth(&self) -> &ProtocolBandwidth {
+		&self.bandwidth
+	}
+
+	/// Set bandwidth limits and priority of the protocol.
+	pub fn set_bandwidth(&mut self, bandwidth: ProtocolBandwidth) {
+		self.bandwidth = bandwidth;
+	}
+
 	/// Take `ProtocolHandlePair` from `NonDefaultSetConfig`
 	pub fn take_protocol_handle(self) -> ProtocolHandlePair {
 		self.protocol_handle_pair
@@ -632,7 +711,8 @@ pub struct NetworkConfiguration {
 	/// `kademlia_replication_factor` peers to consider record successfully put.
 	pub kademlia_replication_factor: NonZeroUsize,
 
-	/// Enable serving block data over IPFS bitswap.
+	/// Enable serving block data over IPFS bitswap, and fetching content from peers with the
+	/// `bitswap_fetch` RPC.
 	pub ipfs_server: bool,
 
 	/// Size of Yamux receive window of all substreams. `None` for the default (256kiB).
@@ -655,6 +735,15 @@ pub struct NetworkConfiguration {
 	/// a modification of the way the implementation works. Different nodes with different
 	/// configured values remain compatible with each other.
 	pub yamux_window_size: Option<u32>,
+
+	/// Node-wide bandwidth limits, shared by all notification and request-response protocols.
+	///
+	/// Once the budget is exhausted, protocols are served in the order of their
+	/// [`BandwidthPriority`].
+	pub bandwidth_limits: BandwidthLimits,
+
+	/// Limits on the number of peers sharing an IP address or a subnet.
+	pub ip_connection_limits: IpConnectionLimits,
 }
 
 impl NetworkConfiguration {
@@ -687,6 +776,8 @@ impl NetworkConfiguration {
 				.expect("value is a constant; constant is non-zero; qed."),
 			yamux_window_size: None,
 			ipfs_server: false,
+			bandwidth_limits: BandwidthLimits::default(),
+			ip_connection_limits: IpConnectionLimits::default(),
 		}
 	}
 
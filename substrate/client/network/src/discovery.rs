@@ -345,6 +345,26 @@ impl DiscoveryBehaviour {
 		addrs_list.push(addr);
 	}
 
+	/// Add an address of a remote peer, e.g. remembered from a previous run of the node, to the
+	/// k-buckets of the DHT.
+	///
+	/// Contrary to [`DiscoveryBehaviour::add_known_address`], the address is not permanent and
+	/// is evicted from the k-buckets like any other discovered address if the peer turns out to
+	/// be unreachable.
+	pub fn add_discovered_address(&mut self, peer_id: PeerId, addr: Multiaddr) {
+		if let Some(kademlia) = self.kademlia.as_mut() {
+			if !self.allow_non_globals_in_dht && !Self::can_add_to_dht(&addr) {
+				trace!(
+					target: "sub-libp2p",
+					"Ignoring non-global address {} of {}.", addr, peer_id
+				);
+				return
+			}
+
+			kademlia.add_address(&peer_id, addr);
+		}
+	}
+
 	/// Add a self-reported address of a remote peer to the k-buckets of the DHT
 	/// if it has compatible `supported_protocols`.
 	///
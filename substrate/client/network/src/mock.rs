@@ -20,6 +20,7 @@
 
 use crate::{peer_store::PeerStoreProvider, protocol_controller::ProtocolHandle, ReputationChange};
 use libp2p::PeerId;
+use prometheus_endpoint::Registry;
 use sc_network_common::role::ObservedRole;
 use std::collections::HashSet;
 
@@ -62,3 +63,21 @@ impl PeerStoreProvider for MockPeerStore {
 		unimplemented!()
 	}
 }
+
+/// Number of bytes delayed by bandwidth limits in `direction` (`"in"` or `"out"`), as reported by
+/// the metrics in `registry`.
+pub fn throttled_bytes(registry: &Registry, direction: &str) -> u64 {
+	registry
+		.gather()
+		.iter()
+		.filter(|family| family.get_name() == "substrate_sub_libp2p_throttled_bytes_total")
+		.flat_map(|family| family.get_metric())
+		.filter(|metric| {
+			metric
+				.get_label()
+				.iter()
+				.any(|label| label.get_name() == "direction" && label.get_value() == direction)
+		})
+		.map(|metric| metric.get_counter().get_value() as u64)
+		.sum()
+}
@@ -18,18 +18,24 @@
 
 //! [`PeerStore`] manages peer reputations and provides connection candidates to
 //! [`crate::protocol_controller::ProtocolController`].
+//!
+//! The peer store can optionally be persisted to a file with [`PeerStore::with_persistence`], so
+//! that reputations and recently good addresses of peers survive restarts of the node.
 
-use libp2p::PeerId;
+use libp2p::{Multiaddr, PeerId};
 use log::trace;
 use parking_lot::Mutex;
 use partial_sort::PartialSort;
 use sc_network_common::{role::ObservedRole, types::ReputationChange};
+use serde::{Deserialize, Serialize};
 use std::{
 	cmp::{Ord, Ordering, PartialOrd},
 	collections::{hash_map::Entry, HashMap, HashSet},
 	fmt::Debug,
+	fs,
+	path::{Path, PathBuf},
 	sync::Arc,
-	time::{Duration, Instant},
+	time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 use wasm_timer::Delay;
 
@@ -51,6 +57,14 @@ const INVERSE_DECREMENT: i32 = 50;
 /// Amount of time between the moment we last updated the [`PeerStore`] entry and the moment we
 /// remove it, once the reputation value reaches 0.
 const FORGET_AFTER: Duration = Duration::from_secs(3600);
+/// Interval at which the [`PeerStore`] is written to its file, if it is persisted.
+const PERSIST_INTERVAL: Duration = Duration::from_secs(60);
+/// Maximum number of peers written to the persisted [`PeerStore`].
+const MAX_PERSISTED_PEERS: usize = 4096;
+/// Maximum number of good addresses remembered for a peer.
+const MAX_ADDRESSES_PER_PEER: usize = 4;
+/// Amount of time after which persisted entries and good addresses are dropped.
+const MAX_PERSISTED_AGE: Duration = Duration::from_secs(7 * 24 * 3600);
 
 /// Trait providing peer reputation management and connection candidates.
 pub trait PeerStoreProvider: Debug + Send {
@@ -132,6 +146,25 @@ impl PeerStoreHandle {
 	pub fn add_known_peer(&mut self, peer_id: PeerId) {
 		self.inner.lock().add_known_peer(peer_id);
 	}
+
+	/// Add an address we successfully connected to the peer on.
+	pub fn add_good_address(&mut self, peer_id: PeerId, address: Multiaddr) {
+		self.inner.lock().add_good_address(peer_id, address);
+	}
+
+	/// Get the recently good addresses of peers that are not banned, including the ones loaded
+	/// from the persisted peer store.
+	pub fn good_addresses(&self) -> Vec<(PeerId, Multiaddr)> {
+		let inner = self.inner.lock();
+		inner
+			.addresses
+			.iter()
+			.filter(|(peer_id, _)| !inner.peers.get(peer_id).map_or(false, PeerInfo::is_banned))
+			.flat_map(|(peer_id, addresses)| {
+				addresses.addresses.iter().map(move |address| (*peer_id, address.clone()))
+			})
+			.collect()
+	}
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -207,9 +240,19 @@ impl PeerInfo {
 	}
 }
 
+/// Addresses we recently connected to a peer on.
+#[derive(Debug, Clone)]
+struct GoodAddresses {
+	/// The addresses, most recently used first.
+	addresses: Vec<Multiaddr>,
+	/// When we last connected to the peer on one of the addresses.
+	last_good: SystemTime,
+}
+
 #[derive(Debug)]
 struct PeerStoreInner {
 	peers: HashMap<PeerId, PeerInfo>,
+	addresses: HashMap<PeerId, GoodAddresses>,
 	protocols: Vec<ProtocolHandle>,
 }
 
@@ -312,6 +355,115 @@ impl PeerStoreInner {
 		let now = Instant::now();
 		self.peers
 			.retain(|_, info| info.reputation != 0 || info.last_updated + FORGET_AFTER > now);
+
+		// Forget addresses that haven't been good for a long time.
+		let now = SystemTime::now();
+		self.addresses.retain(|_, addresses| {
+			now.duration_since(addresses.last_good).unwrap_or_default() < MAX_PERSISTED_AGE
+		});
+	}
+
+	fn add_good_address(&mut self, peer_id: PeerId, address: Multiaddr) {
+		let entry = self
+			.addresses
+			.entry(peer_id)
+			.or_insert_with(|| GoodAddresses { addresses: Vec::new(), last_good: UNIX_EPOCH });
+		entry.addresses.retain(|a| *a != address);
+		entry.addresses.insert(0, address);
+		entry.addresses.truncate(MAX_ADDRESSES_PER_PEER);
+		entry.last_good = SystemTime::now();
+	}
+
+	/// Create a snapshot of the peer store to persist, limited to [`MAX_PERSISTED_PEERS`] peers.
+	///
+	/// Banned peers are preferred, followed by the most recently updated ones.
+	fn snapshot(&self) -> PersistedPeerStore {
+		let now = Instant::now();
+		let system_now = SystemTime::now();
+		let unix_time =
+			|time: SystemTime| time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+
+		let mut peers = self
+			.peers
+			.keys()
+			.chain(self.addresses.keys())
+			.collect::<HashSet<_>>()
+			.into_iter()
+			.filter_map(|peer_id| {
+				let info = self.peers.get(peer_id).copied().unwrap_or_default();
+				let addresses = self.addresses.get(peer_id);
+				if info.reputation == 0 && addresses.is_none() {
+					return None
+				}
+				let updated = system_now - now.saturating_duration_since(info.last_updated);
+				Some(PersistedPeer {
+					peer_id: peer_id.to_base58(),
+					reputation: info.reputation,
+					updated_at: unix_time(updated),
+					addresses: addresses
+						.map(|a| a.addresses.iter().map(ToString::to_string).collect())
+						.unwrap_or_default(),
+					addresses_good_at: addresses.map_or(0, |a| unix_time(a.last_good)),
+				})
+			})
+			.collect::<Vec<_>>();
+		peers.sort_by_key(|peer| {
+			(peer.reputation >= BANNED_THRESHOLD, std::cmp::Reverse(peer.updated_at))
+		});
+		peers.truncate(MAX_PERSISTED_PEERS);
+
+		PersistedPeerStore { saved_at: unix_time(system_now), peers }
+	}
+
+	/// Restore entries from a persisted snapshot, decaying reputations by the time passed since
+	/// the snapshot was saved and dropping entries older than [`MAX_PERSISTED_AGE`].
+	fn restore(&mut self, snapshot: PersistedPeerStore) {
+		let now = Instant::now();
+		let system_now = SystemTime::now();
+		let age = |unix_time: u64| {
+			system_now
+				.duration_since(UNIX_EPOCH + Duration::from_secs(unix_time))
+				.unwrap_or_default()
+		};
+		let seconds_passed = age(snapshot.saved_at).as_secs();
+
+		for peer in snapshot.peers.into_iter().take(MAX_PERSISTED_PEERS) {
+			let Ok(peer_id) = peer.peer_id.parse::<PeerId>() else { continue };
+
+			let updated_age = age(peer.updated_at);
+			let mut banned = false;
+			if updated_age < MAX_PERSISTED_AGE {
+				let mut info = PeerInfo {
+					reputation: peer.reputation,
+					last_updated: now.checked_sub(updated_age).unwrap_or(now),
+					role: None,
+				};
+				info.decay_reputation(seconds_passed);
+				banned = info.is_banned();
+				if info.reputation != 0 || updated_age < FORGET_AFTER {
+					self.peers.entry(peer_id).or_insert(info);
+				}
+			}
+
+			// Don't restore the addresses of banned peers, so that they aren't dialed.
+			if banned {
+				continue
+			}
+
+			let addresses_age = age(peer.addresses_good_at);
+			let addresses = peer
+				.addresses
+				.iter()
+				.filter_map(|address| address.parse().ok())
+				.take(MAX_ADDRESSES_PER_PEER)
+				.collect::<Vec<Multiaddr>>();
+			if !addresses.is_empty() && addresses_age < MAX_PERSISTED_AGE {
+				self.addresses
+					.entry(peer_id)
+					.or_insert(GoodAddresses { addresses, last_good: system_now - addresses_age });
+				self.peers.entry(peer_id).or_default();
+			}
+		}
 	}
 
 	fn add_known_peer(&mut self, peer_id: PeerId) {
@@ -331,10 +483,38 @@ impl PeerStoreInner {
 	}
 }
 
+/// Persisted state of the [`PeerStore`].
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PersistedPeerStore {
+	/// UNIX timestamp in seconds of when the snapshot was saved.
+	saved_at: u64,
+	/// Persisted peers.
+	peers: Vec<PersistedPeer>,
+}
+
+/// Persisted entry of a peer.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PersistedPeer {
+	/// Base58 encoded peer id.
+	peer_id: String,
+	/// Reputation of the peer when the snapshot was saved.
+	reputation: i32,
+	/// UNIX timestamp in seconds of when the entry was last updated.
+	updated_at: u64,
+	/// Recently good addresses of the peer.
+	addresses: Vec<String>,
+	/// UNIX timestamp in seconds of when the peer was last connected to on one of `addresses`.
+	addresses_good_at: u64,
+}
+
 /// Worker part of [`PeerStoreHandle`]
 #[derive(Debug)]
 pub struct PeerStore {
 	inner: Arc<Mutex<PeerStoreInner>>,
+	/// File the peer store is persisted to, if any.
+	path: Option<PathBuf>,
 }
 
 impl PeerStore {
@@ -346,8 +526,60 @@ impl PeerStore {
 					.into_iter()
 					.map(|peer_id| (peer_id, PeerInfo::default()))
 					.collect(),
+				addresses: HashMap::new(),
 				protocols: Vec::new(),
 			})),
+			path: None,
+		}
+	}
+
+	/// Persist the peer store to the file at `path`.
+	///
+	/// Reputations and good addresses are loaded from the file if it exists, and the file is
+	/// periodically updated while the peer store is running and once more when it is dropped.
+	pub fn with_persistence(mut self, path: PathBuf) -> Self {
+		match Self::load(&path) {
+			Ok(Some(snapshot)) => {
+				self.inner.lock().restore(snapshot);
+				log::debug!(
+					target: LOG_TARGET,
+					"Loaded {} peers from {}",
+					self.inner.lock().peers.len(),
+					path.display(),
+				);
+			},
+			Ok(None) => {},
+			Err(e) => log::warn!(
+				target: LOG_TARGET,
+				"Failed to load the peer store from {}: {e}",
+				path.display(),
+			),
+		}
+		self.path = Some(path);
+		self
+	}
+
+	fn load(path: &Path) -> Result<Option<PersistedPeerStore>, String> {
+		match fs::read(path) {
+			Ok(data) => serde_json::from_slice(&data).map(Some).map_err(|e| e.to_string()),
+			Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+			Err(e) => Err(e.to_string()),
+		}
+	}
+
+	fn save(&self, path: &Path) {
+		let snapshot = self.inner.lock().snapshot();
+		let tmp_path = path.with_extension("tmp");
+		let result = serde_json::to_vec(&snapshot)
+			.map_err(|e| e.to_string())
+			.and_then(|data| fs::write(&tmp_path, data).map_err(|e| e.to_string()))
+			.and_then(|()| fs::rename(&tmp_path, path).map_err(|e| e.to_string()));
+		if let Err(e) = result {
+			log::debug!(
+				target: LOG_TARGET,
+				"Failed to persist the peer store to {}: {e}",
+				path.display(),
+			);
 		}
 	}
 
@@ -357,9 +589,13 @@ impl PeerStore {
 	}
 
 	/// Drive the `PeerStore`, decaying reputation values over time and removing expired entries.
+	///
+	/// If the peer store is persisted, it is also written to its file every
+	/// [`PERSIST_INTERVAL`] and when the returned future is dropped on shutdown.
 	pub async fn run(self) {
 		let started = Instant::now();
 		let mut latest_time_update = started;
+		let mut latest_save = started;
 
 		loop {
 			let now = Instant::now();
@@ -373,14 +609,31 @@ impl PeerStore {
 			};
 
 			self.inner.lock().progress_time(seconds_passed);
+
+			if let Some(path) = &self.path {
+				if now - latest_save >= PERSIST_INTERVAL {
+					latest_save = now;
+					self.save(path);
+				}
+			}
+
 			let _ = Delay::new(Duration::from_secs(1)).await;
 		}
 	}
 }
 
+impl Drop for PeerStore {
+	fn drop(&mut self) {
+		if let Some(path) = &self.path {
+			self.save(path);
+		}
+	}
+}
+
 #[cfg(test)]
 mod tests {
-	use super::PeerInfo;
+	use super::{PeerInfo, PeerStore, BANNED_THRESHOLD};
+	use libp2p::{Multiaddr, PeerId};
 
 	#[test]
 	fn decaying_zero_reputation_yields_zero() {
@@ -447,4 +700,66 @@ mod tests {
 		peer_info.decay_reputation(SECONDS / 2);
 		assert_eq!(peer_info.reputation, 0);
 	}
+
+	#[test]
+	fn persisted_peer_store_is_restored() {
+		use super::PeerStoreProvider;
+		use sc_network_common::types::ReputationChange;
+
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("peer_store.json");
+		let banned = PeerId::random();
+		let good = PeerId::random();
+		let address: Multiaddr = "/ip4/127.0.0.1/tcp/30333".parse().unwrap();
+
+		let peer_store = PeerStore::new(Vec::new()).with_persistence(path.clone());
+		let mut handle = peer_store.handle();
+		handle.add_good_address(banned, address.clone());
+		handle.report_peer(banned, ReputationChange::new_fatal("test"));
+		handle.add_good_address(good, address.clone());
+		// Banned peers are not dialed.
+		assert_eq!(handle.good_addresses(), vec![(good, address.clone())]);
+
+		// The peer store is saved when it is dropped.
+		drop(peer_store);
+		assert!(path.exists());
+
+		let restored = PeerStore::new(Vec::new()).with_persistence(path);
+		let handle = restored.handle();
+		assert!(handle.is_banned(&banned));
+		assert!(!handle.is_banned(&good));
+		assert_eq!(handle.good_addresses(), vec![(good, address)]);
+		assert_eq!(handle.num_known_peers(), 2);
+		assert!(!restored.inner.lock().addresses.contains_key(&banned));
+	}
+
+	#[test]
+	fn restored_reputations_decay_and_old_entries_are_dropped() {
+		use super::{PersistedPeer, PersistedPeerStore, MAX_PERSISTED_AGE};
+		use std::time::{SystemTime, UNIX_EPOCH};
+
+		let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+		let banned = PeerId::random();
+		let old = PeerId::random();
+		let peer = |peer_id: PeerId, updated_at| PersistedPeer {
+			peer_id: peer_id.to_base58(),
+			reputation: BANNED_THRESHOLD - 1,
+			updated_at,
+			addresses: vec!["/ip4/127.0.0.1/tcp/30333".into()],
+			addresses_good_at: updated_at,
+		};
+
+		// The node was down long enough for the reputation to decay.
+		let peer_store = PeerStore::new(Vec::new());
+		peer_store.inner.lock().restore(PersistedPeerStore {
+			saved_at: now - 3600,
+			peers: vec![peer(banned, now - 3600), peer(old, now - MAX_PERSISTED_AGE.as_secs() - 1)],
+		});
+		let inner = peer_store.inner.lock();
+		assert_eq!(inner.peers.get(&banned).map(|info| info.reputation), Some(0));
+		// The peer isn't banned anymore, so its addresses are restored.
+		assert!(inner.addresses.contains_key(&banned));
+		assert!(!inner.peers.contains_key(&old));
+		assert!(!inner.addresses.contains_key(&old));
+	}
 }
@@ -17,6 +17,7 @@
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
 use crate::{
+	bandwidth::Bandwidth,
 	config, error,
 	peer_store::{PeerStoreHandle, PeerStoreProvider},
 	protocol_controller::{self, SetId},
@@ -81,6 +82,7 @@ impl<B: BlockT> Protocol<B> {
 	pub(crate) fn new(
 		roles: Roles,
 		registry: &Option<Registry>,
+		bandwidth: &Bandwidth,
 		notification_protocols: Vec<config::NonDefaultSetConfig>,
 		block_announces_protocol: config::NonDefaultSetConfig,
 		peer_store_handle: PeerStoreHandle,
@@ -101,6 +103,10 @@ impl<B: BlockT> Protocol<B> {
 					fallback_names: block_announces_protocol.fallback_names().cloned().collect(),
 					handshake: block_announces_protocol.handshake().as_ref().unwrap().to_vec(),
 					max_notification_size: block_announces_protocol.max_notification_size(),
+					bandwidth: bandwidth.protocol(
+						block_announces_protocol.protocol_name(),
+						block_announces_protocol.bandwidth(),
+					),
 				};
 
 				let (handle, command_stream) =
@@ -114,6 +120,7 @@ impl<B: BlockT> Protocol<B> {
 					fallback_names: s.fallback_names().cloned().collect(),
 					handshake: s.handshake().as_ref().map_or(roles.encode(), |h| (*h).to_vec()),
 					max_notification_size: s.max_notification_size(),
+					bandwidth: bandwidth.protocol(s.protocol_name(), s.bandwidth()),
 				};
 
 				let (handle, command_stream) = s.take_protocol_handle().split();
@@ -17,6 +17,7 @@
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
 use crate::{
+	bandwidth::ProtocolThrottle,
 	protocol::notifications::{
 		handler::{self, NotificationsSink, NotifsHandler, NotifsHandlerIn, NotifsHandlerOut},
 		service::{metrics, NotificationCommand, ProtocolHandle, ValidationCallResult},
@@ -31,6 +32,7 @@ use fnv::FnvHashMap;
 use futures::{future::BoxFuture, prelude::*, stream::FuturesUnordered};
 use libp2p::{
 	core::{ConnectedPoint, Endpoint, Multiaddr},
+	multiaddr,
 	swarm::{
 		behaviour::{ConnectionClosed, ConnectionEstablished, DialFailure, FromSwarm},
 		ConnectionDenied, ConnectionId, DialError, NetworkBehaviour, NotifyHandler, PollParameters,
@@ -50,6 +52,7 @@ use std::{
 	cmp,
 	collections::{hash_map::Entry, VecDeque},
 	mem,
+	net::IpAddr,
 	pin::Pin,
 	sync::Arc,
 	task::{Context, Poll},
@@ -181,6 +184,17 @@ pub struct ProtocolConfig {
 	pub handshake: Vec<u8>,
 	/// Maximum allowed size for a notification.
 	pub max_notification_size: u64,
+	/// Bandwidth limits of the protocol.
+	pub bandwidth: ProtocolThrottle,
+}
+
+/// Returns the IP address of `address`, if any.
+fn ip_address(address: &Multiaddr) -> Option<IpAddr> {
+	address.iter().find_map(|protocol| match protocol {
+		multiaddr::Protocol::Ip4(ip) => Some(IpAddr::V4(ip)),
+		multiaddr::Protocol::Ip6(ip) => Some(IpAddr::V6(ip)),
+		_ => None,
+	})
 }
 
 /// Identifier for a delay firing.
@@ -421,6 +435,7 @@ impl Notifications {
 						fallback_names: cfg.fallback_names,
 						handshake: Arc::new(RwLock::new(cfg.handshake)),
 						max_notification_size: cfg.max_notification_size,
+						bandwidth: cfg.bandwidth,
 					},
 					(protocol_handle, command_stream),
 				)
@@ -1257,6 +1272,13 @@ impl NetworkBehaviour for Notifications {
 				connection_id,
 				..
 			}) => {
+				// Let `ProtocolController`s apply their IP connection limits to the peer.
+				if let Some(address) = ip_address(endpoint.get_remote_address()) {
+					for handle in &self.protocol_controller_handles {
+						handle.peer_address(peer_id, address);
+					}
+				}
+
 				for set_id in (0..self.notif_protocols.len()).map(SetId::from) {
 					match self.peers.entry((peer_id, set_id)).or_insert(PeerState::Poisoned) {
 						// Requested | PendingRequest => Enabled
@@ -2440,6 +2462,7 @@ mod tests {
 				out_peers: 25,
 				reserved_nodes: HashSet::new(),
 				reserved_only: false,
+				ip_limits: Default::default(),
 			},
 			to_notifications,
 			Box::new(MockPeerStore {}),
@@ -2457,6 +2480,7 @@ mod tests {
 						fallback_names: Vec::new(),
 						handshake: vec![1, 2, 3, 4],
 						max_notification_size: u64::MAX,
+						bandwidth: Default::default(),
 					},
 					notif_handle,
 					command_stream,
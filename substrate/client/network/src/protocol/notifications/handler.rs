@@ -58,6 +58,7 @@
 //! [`NotifsHandlerIn::Open`] has gotten an answer.
 
 use crate::{
+	bandwidth::{ProtocolThrottle, ThrottleState},
 	protocol::notifications::{
 		service::metrics,
 		upgrade::{
@@ -152,7 +153,13 @@ impl NotifsHandler {
 						config.max_notification_size,
 					);
 
-					Protocol { config, in_upgrade, state: State::Closed { pending_opening: false } }
+					Protocol {
+						config,
+						in_upgrade,
+						state: State::Closed { pending_opening: false },
+						upload: ThrottleState::default(),
+						download: ThrottleState::default(),
+					}
 				})
 				.collect(),
 			peer_id,
@@ -175,6 +182,8 @@ pub struct ProtocolConfig {
 	pub handshake: Arc<RwLock<Vec<u8>>>,
 	/// Maximum allowed size for a notification.
 	pub max_notification_size: u64,
+	/// Bandwidth limits of the protocol, shared by all connections.
+	pub bandwidth: ProtocolThrottle,
 }
 
 /// Fields specific for each individual protocol.
@@ -187,6 +196,12 @@ struct Protocol {
 
 	/// Current state of the substreams for this protocol.
 	state: State,
+
+	/// Throttling state of the outbound substream.
+	upload: ThrottleState,
+
+	/// Throttling state of the inbound substream.
+	download: ThrottleState,
 }
 
 /// See the module-level documentation to learn about the meaning of these variants.
@@ -749,9 +764,10 @@ impl ConnectionHandler for NotifsHandler {
 		// For each open substream, try send messages from `notifications_sink_rx` to the
 		// substream.
 		for protocol_index in 0..self.protocols.len() {
+			let Protocol { config, state, upload, .. } = &mut self.protocols[protocol_index];
 			if let State::Open {
 				notifications_sink_rx, out_substream: Some(out_substream), ..
-			} = &mut self.protocols[protocol_index].state
+			} = state
 			{
 				loop {
 					// Only proceed with `out_substream.poll_ready_unpin` if there is an element
@@ -766,6 +782,11 @@ impl ConnectionHandler for NotifsHandler {
 						Poll::Ready(None) | Poll::Pending => break,
 					}
 
+					// Wait for the bandwidth limits of the protocol to allow sending more.
+					if upload.poll_ready(&config.bandwidth.upload, cx).is_pending() {
+						break
+					}
+
 					// Before we extract the element from `notifications_sink_rx`, check that the
 					// substream is ready to accept a message.
 					match out_substream.poll_ready_unpin(cx) {
@@ -786,6 +807,7 @@ impl ConnectionHandler for NotifsHandler {
 						},
 					};
 
+					upload.consume(&config.bandwidth.upload, message.len());
 					let _ = out_substream.start_send_unpin(message);
 					// Note that flushing is performed later down this function.
 				}
@@ -821,17 +843,25 @@ impl ConnectionHandler for NotifsHandler {
 
 		// Poll inbound substreams.
 		for protocol_index in 0..self.protocols.len() {
+			let Protocol { config, state, download, .. } = &mut self.protocols[protocol_index];
+
 			// Inbound substreams being closed is always tolerated, except for the
 			// `OpenDesiredByRemote` state which might need to be switched back to `Closed`.
-			match &mut self.protocols[protocol_index].state {
+			match state {
 				State::Closed { .. } |
 				State::Open { in_substream: None, .. } |
 				State::Opening { in_substream: None, .. } => {},
 
+				// Stop reading from the substream while the bandwidth limits of the protocol are
+				// exceeded, applying backpressure to the remote.
+				State::Open { in_substream: Some(_), .. }
+					if download.poll_ready(&config.bandwidth.download, cx).is_pending() => {},
+
 				State::Open { in_substream: in_substream @ Some(_), .. } =>
 					match Stream::poll_next(Pin::new(in_substream.as_mut().unwrap()), cx) {
 						Poll::Pending => {},
 						Poll::Ready(Some(Ok(message))) => {
+							download.consume(&config.bandwidth.download, message.len());
 							let event = NotifsHandlerOut::Notification { protocol_index, message };
 							return Poll::Ready(ConnectionHandlerEvent::Custom(event))
 						},
@@ -843,8 +873,7 @@ impl ConnectionHandler for NotifsHandler {
 						Poll::Pending => {},
 						Poll::Ready(Ok(void)) => match void {},
 						Poll::Ready(Err(_)) => {
-							self.protocols[protocol_index].state =
-								State::Closed { pending_opening: *pending_opening };
+							*state = State::Closed { pending_opening: *pending_opening };
 							return Poll::Ready(ConnectionHandlerEvent::Custom(
 								NotifsHandlerOut::CloseDesired { protocol_index },
 							))
@@ -873,8 +902,13 @@ impl ConnectionHandler for NotifsHandler {
 #[cfg(test)]
 pub mod tests {
 	use super::*;
-	use crate::protocol::notifications::upgrade::{
-		NotificationsInOpen, NotificationsInSubstreamHandshake, NotificationsOutOpen,
+	use crate::{
+		bandwidth::Bandwidth,
+		config::{BandwidthLimits, ProtocolBandwidth},
+		mock::throttled_bytes,
+		protocol::notifications::upgrade::{
+			NotificationsInOpen, NotificationsInSubstreamHandshake, NotificationsOutOpen,
+		},
 	};
 	use asynchronous_codec::Framed;
 	use libp2p::{
@@ -883,9 +917,11 @@ pub mod tests {
 		Multiaddr,
 	};
 	use multistream_select::{dialer_select_proto, listener_select_proto, Negotiated, Version};
+	use prometheus_endpoint::Registry;
 	use std::{
 		collections::HashMap,
 		io::{Error, IoSlice, IoSliceMut},
+		num::NonZeroU32,
 	};
 	use tokio::sync::mpsc;
 	use unsigned_varint::codec::UviBytes;
@@ -1075,9 +1111,12 @@ pub mod tests {
 				fallback_names: vec![],
 				handshake: Arc::new(RwLock::new(b"hello, world".to_vec())),
 				max_notification_size: u64::MAX,
+				bandwidth: Default::default(),
 			},
 			in_upgrade: NotificationsIn::new("/foo", Vec::new(), u64::MAX),
 			state: State::Closed { pending_opening: false },
+			upload: ThrottleState::default(),
+			download: ThrottleState::default(),
 		};
 
 		NotifsHandler {
@@ -1621,6 +1660,69 @@ pub mod tests {
 		.await;
 	}
 
+	#[tokio::test(start_paused = true)]
+	async fn outbound_notifications_are_throttled() {
+		let registry = Registry::new();
+		let bandwidth = Bandwidth::new(&Default::default(), Some(&registry)).unwrap();
+		let mut handler = notifs_handler();
+		handler.protocols[0].config.bandwidth = bandwidth.protocol(
+			&"/foo".into(),
+			&ProtocolBandwidth {
+				limits: BandwidthLimits { upload: NonZeroU32::new(100), download: None },
+				..Default::default()
+			},
+		);
+		let (io, io2) = MockSubstream::negotiated().await;
+		let mut remote = Framed::new(io2, UviBytes::<BytesMut>::default());
+
+		let (async_tx, async_rx) = futures::channel::mpsc::channel(ASYNC_NOTIFICATIONS_BUFFER_SIZE);
+		let (sync_tx, sync_rx) = futures::channel::mpsc::channel(SYNC_NOTIFICATIONS_BUFFER_SIZE);
+		let notifications_sink = NotificationsSink {
+			inner: Arc::new(NotificationsSinkInner {
+				peer_id: PeerId::random(),
+				async_channel: FuturesMutex::new(async_tx),
+				sync_channel: Mutex::new(Some(sync_tx)),
+			}),
+			metrics: None,
+		};
+
+		handler.protocols[0].state = State::Open {
+			notifications_sink_rx: stream::select(async_rx.fuse(), sync_rx.fuse()).peekable(),
+			out_substream: Some(NotificationsOutSubstream::new(Framed::new(
+				io,
+				UviBytes::default(),
+			))),
+			in_substream: None,
+		};
+
+		for byte in 0..3 {
+			notifications_sink.send_sync_notification(vec![byte; 100]);
+		}
+
+		// The first second worth of bytes goes through right away, and the bucket is allowed to go
+		// into debt once, so the first two notifications are sent immediately.
+		let started = tokio::time::Instant::now();
+		futures::future::poll_fn(|cx| {
+			assert!(handler.poll(cx).is_pending());
+			Poll::Ready(())
+		})
+		.await;
+		assert_eq!(remote.next().await.unwrap().unwrap(), vec![0; 100]);
+		assert_eq!(remote.next().await.unwrap().unwrap(), vec![1; 100]);
+		assert!(remote.next().now_or_never().is_none());
+		assert_eq!(throttled_bytes(&registry, "out"), 0);
+
+		// The third one has to wait for the bucket to refill.
+		let third = futures::future::poll_fn(|cx| {
+			assert!(handler.poll(cx).is_pending());
+			remote.poll_next_unpin(cx)
+		})
+		.await;
+		assert_eq!(third.unwrap().unwrap(), vec![2; 100]);
+		assert!(started.elapsed() >= Duration::from_millis(900));
+		assert_eq!(throttled_bytes(&registry, "out"), 100);
+	}
+
 	#[tokio::test]
 	async fn close_desired_by_remote() {
 		let mut handler = notifs_handler();
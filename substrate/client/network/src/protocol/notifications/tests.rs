@@ -89,6 +89,7 @@ fn build_nodes() -> (Swarm<CustomProtoWithAddr>, Swarm<CustomProtoWithAddr>) {
 				out_peers: 25,
 				reserved_nodes: Default::default(),
 				reserved_only: false,
+				ip_limits: Default::default(),
 			},
 			to_notifications,
 			Box::new(peer_store.handle()),
@@ -106,6 +107,7 @@ fn build_nodes() -> (Swarm<CustomProtoWithAddr>, Swarm<CustomProtoWithAddr>) {
 						fallback_names: Vec::new(),
 						handshake: Vec::new(),
 						max_notification_size: 1024 * 1024,
+						bandwidth: Default::default(),
 					},
 					notif_handle,
 					command_stream,
@@ -44,19 +44,24 @@
 use futures::{channel::oneshot, future::Either, FutureExt, StreamExt};
 use libp2p::PeerId;
 use log::{debug, error, trace, warn};
+use prometheus_endpoint::{register, CounterVec, Opts, PrometheusError, Registry, U64};
 use sc_utils::mpsc::{tracing_unbounded, TracingUnboundedReceiver, TracingUnboundedSender};
 use sp_arithmetic::traits::SaturatedConversion;
 use std::{
 	collections::{HashMap, HashSet},
+	net::IpAddr,
 	time::{Duration, Instant},
 };
 use wasm_timer::Delay;
 
-use crate::peer_store::PeerStoreProvider;
+use crate::{config::IpConnectionLimits, peer_store::PeerStoreProvider, types::ProtocolName};
 
 /// Log target for this file.
 pub const LOG_TARGET: &str = "peerset";
 
+/// Maximum number of peer IP addresses remembered by a [`ProtocolController`].
+const MAX_PEER_ADDRESSES: usize = 4096;
+
 /// `Notifications` protocol index. For historical reasons it's called `SetId`, because it
 /// used to refer to a set of peers in a peerset for this protocol.
 ///
@@ -101,6 +106,9 @@ pub struct ProtoSetConfig {
 
 	/// If true, we only accept nodes in [`ProtoSetConfig::reserved_nodes`].
 	pub reserved_only: bool,
+
+	/// Limits on the number of regular peers sharing an IP address or a subnet.
+	pub ip_limits: IpConnectionLimits,
 }
 
 /// Message that is sent by [`ProtocolController`] to `Notifications`.
@@ -164,6 +172,8 @@ enum Event {
 	IncomingConnection(PeerId, IncomingIndex),
 	/// Connection with the peer dropped.
 	Dropped(PeerId),
+	/// IP address of a connected peer.
+	Address(PeerId, IpAddr),
 }
 
 /// Shared handle to [`ProtocolController`]. Distributed around the code outside of the
@@ -228,10 +238,58 @@ impl ProtocolHandle {
 	pub fn dropped(&self, peer_id: PeerId) {
 		let _ = self.events_tx.unbounded_send(Event::Dropped(peer_id));
 	}
+
+	/// Notify about the IP address of a peer a connection was established with. Must be sent
+	/// before [`ProtocolHandle::incoming_connection`] for the peer to be subject to
+	/// [`ProtoSetConfig::ip_limits`].
+	pub fn peer_address(&self, peer_id: PeerId, address: IpAddr) {
+		let _ = self.events_tx.unbounded_send(Event::Address(peer_id, address));
+	}
+}
+
+/// Metrics of the [`ProtocolController`]s.
+#[derive(Debug, Clone)]
+pub struct Metrics {
+	/// Connections refused or dropped because of the IP connection limits.
+	ip_limited_total: CounterVec<U64>,
+}
+
+impl Metrics {
+	/// Register the metrics in `registry`.
+	pub fn register(registry: &Registry) -> Result<Self, PrometheusError> {
+		Ok(Self {
+			ip_limited_total: register(
+				CounterVec::new(
+					Opts::new(
+						"substrate_sub_libp2p_peerset_ip_limited_total",
+						"Total number of peers refused or dropped because of the per IP address \
+						 and per subnet connection limits",
+					),
+					&["protocol", "reason"],
+				)?,
+				registry,
+			)?,
+		})
+	}
+}
+
+/// Mask `address` to the host (IPv4 /32, IPv6 /64) or the subnet (IPv4 /24, IPv6 /48) it belongs
+/// to.
+fn ip_prefix(address: IpAddr, subnet: bool) -> IpAddr {
+	match address {
+		IpAddr::V4(ip) => {
+			let mask = if subnet { u32::MAX << 8 } else { u32::MAX };
+			IpAddr::V4((u32::from(ip) & mask).into())
+		},
+		IpAddr::V6(ip) => {
+			let mask = if subnet { u128::MAX << 80 } else { u128::MAX << 64 };
+			IpAddr::V6((u128::from(ip) & mask).into())
+		},
+	}
 }
 
 /// Direction of a connection
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 enum Direction {
 	Inbound,
 	Outbound,
@@ -290,6 +348,12 @@ pub struct ProtocolController {
 	/// `PeerStore` handle for checking peer reputation values and getting connection candidates
 	/// with highest reputation.
 	peer_store: Box<dyn PeerStoreProvider>,
+	/// Limits on the number of regular peers sharing an IP address or a subnet.
+	ip_limits: IpConnectionLimits,
+	/// Last known IP addresses of the peers.
+	peer_addresses: HashMap<PeerId, IpAddr>,
+	/// Metrics, and the name of the protocol to report them for.
+	metrics: Option<(Metrics, ProtocolName)>,
 }
 
 impl ProtocolController {
@@ -320,10 +384,18 @@ impl ProtocolController {
 			next_periodic_alloc_slots: Instant::now(),
 			to_notifications,
 			peer_store,
+			ip_limits: config.ip_limits,
+			peer_addresses: HashMap::new(),
+			metrics: None,
 		};
 		(handle, controller)
 	}
 
+	/// Report metrics for `protocol`.
+	pub fn set_metrics(&mut self, protocol: ProtocolName, metrics: Metrics) {
+		self.metrics = Some((metrics, protocol));
+	}
+
 	/// Drive [`ProtocolController`]. This function returns when all instances of
 	/// [`ProtocolHandle`] are dropped.
 	pub async fn run(mut self) {
@@ -368,6 +440,7 @@ impl ProtocolController {
 			Event::IncomingConnection(peer_id, index) =>
 				self.on_incoming_connection(peer_id, index),
 			Event::Dropped(peer_id) => self.on_peer_dropped(peer_id),
+			Event::Address(peer_id, address) => self.on_peer_address(peer_id, address),
 		}
 	}
 
@@ -448,6 +521,64 @@ impl ProtocolController {
 		self.peer_store.report_disconnect(peer_id);
 	}
 
+	/// Count a peer refused or dropped because of the IP connection limits.
+	fn report_ip_limited(&self, reason: &str) {
+		if let Some((metrics, protocol)) = &self.metrics {
+			metrics.ip_limited_total.with_label_values(&[protocol, reason]).inc();
+		}
+	}
+
+	/// Returns the limit reached if `peer_id` were connected in `direction`, or `None` if the peer
+	/// is within the IP connection limits.
+	fn ip_limit_reached(&self, peer_id: &PeerId, direction: Direction) -> Option<&'static str> {
+		let address = *self.peer_addresses.get(peer_id)?;
+		let reached = |limit: Option<u32>, subnet: bool| {
+			limit.is_some_and(|limit| {
+				let prefix = ip_prefix(address, subnet);
+				let peers = self
+					.nodes
+					.iter()
+					.filter(|(other, other_direction)| {
+						*other != peer_id &&
+							**other_direction == direction &&
+							self.peer_addresses
+								.get(*other)
+								.is_some_and(|other| ip_prefix(*other, subnet) == prefix)
+					})
+					.count();
+				peers >= limit as usize
+			})
+		};
+
+		match direction {
+			Direction::Inbound if reached(self.ip_limits.max_inbound_per_ip, false) =>
+				Some("inbound_ip"),
+			Direction::Inbound if reached(self.ip_limits.max_inbound_per_subnet, true) =>
+				Some("inbound_subnet"),
+			Direction::Outbound if reached(self.ip_limits.max_outbound_per_subnet, true) =>
+				Some("outbound_subnet"),
+			Direction::Inbound | Direction::Outbound => None,
+		}
+	}
+
+	/// Number of outbound regular peers per subnet, if outbound peers are limited per subnet.
+	fn outbound_peers_per_subnet(&self) -> HashMap<IpAddr, u32> {
+		let mut subnets = HashMap::new();
+		if self.ip_limits.max_outbound_per_subnet.is_none() {
+			return subnets
+		}
+
+		for (peer_id, direction) in &self.nodes {
+			if let (Direction::Outbound, Some(address)) =
+				(direction, self.peer_addresses.get(peer_id))
+			{
+				*subnets.entry(ip_prefix(*address, true)).or_default() += 1;
+			}
+		}
+
+		subnets
+	}
+
 	/// Ask `Peerset` if the peer has a reputation value not sufficent for connection with it.
 	fn is_banned(&self, peer_id: &PeerId) -> bool {
 		self.peer_store.is_banned(peer_id)
@@ -696,6 +827,17 @@ impl ProtocolController {
 			return
 		}
 
+		if let Some(reason) = self.ip_limit_reached(&peer_id, Direction::Inbound) {
+			debug!(
+				target: LOG_TARGET,
+				"Rejecting {peer_id} on {:?}: {reason} connection limit reached.",
+				self.set_id,
+			);
+			self.report_ip_limited(reason);
+			self.reject_connection(peer_id, incoming_index);
+			return
+		}
+
 		if self.is_banned(&peer_id) {
 			self.reject_connection(peer_id, incoming_index);
 			return
@@ -733,6 +875,37 @@ impl ProtocolController {
 		}
 	}
 
+	/// Record the IP address of a peer. If we dialed the peer and too many outbound peers are in
+	/// its subnet already, drop it.
+	fn on_peer_address(&mut self, peer_id: PeerId, address: IpAddr) {
+		if self.peer_addresses.len() >= MAX_PEER_ADDRESSES &&
+			!self.peer_addresses.contains_key(&peer_id)
+		{
+			// Forget about the peers we are not connected to.
+			let (nodes, reserved_nodes) = (&self.nodes, &self.reserved_nodes);
+			self.peer_addresses.retain(|peer_id, _| {
+				nodes.contains_key(peer_id) || reserved_nodes.contains_key(peer_id)
+			});
+		}
+		self.peer_addresses.insert(peer_id, address);
+
+		if self.nodes.get(&peer_id) != Some(&Direction::Outbound) {
+			return
+		}
+
+		if let Some(reason) = self.ip_limit_reached(&peer_id, Direction::Outbound) {
+			debug!(
+				target: LOG_TARGET,
+				"Dropping {peer_id} on {:?}: {reason} connection limit reached.",
+				self.set_id,
+			);
+			self.report_ip_limited(reason);
+			self.nodes.remove(&peer_id);
+			self.num_out -= 1;
+			self.drop_connection(peer_id);
+		}
+	}
+
 	/// Try dropping the peer as a reserved peer. Return `Ok(true)` if the peer was found and
 	/// disconnected, `Ok(false)` if it wasn't found, `Err(PeerId)`, if the peer found, but not in
 	/// connected state.
@@ -798,15 +971,20 @@ impl ProtocolController {
 		// Fill available slots.
 		let available_slots = (self.max_out - self.num_out).saturated_into();
 
-		// Ignore reserved nodes (connected above), already connected nodes, and nodes with
-		// outstanding events/actions.
+		let mut outbound_per_subnet = self.outbound_peers_per_subnet();
+		let max_outbound_per_subnet = self.ip_limits.max_outbound_per_subnet.unwrap_or(u32::MAX);
+
+		// Ignore reserved nodes (connected above), already connected nodes, nodes with
+		// outstanding events/actions, and nodes in subnets with enough outbound peers.
 		let ignored = self
 			.reserved_nodes
 			.keys()
-			.collect::<HashSet<&PeerId>>()
-			.union(&self.nodes.keys().collect::<HashSet<&PeerId>>())
-			.cloned()
-			.collect();
+			.chain(self.nodes.keys())
+			.chain(self.peer_addresses.iter().filter_map(|(peer_id, address)| {
+				let peers = outbound_per_subnet.get(&ip_prefix(*address, true)).copied();
+				(peers.unwrap_or_default() >= max_outbound_per_subnet).then_some(peer_id)
+			}))
+			.collect::<HashSet<&PeerId>>();
 
 		let candidates = self
 			.peer_store
@@ -835,6 +1013,15 @@ impl ProtocolController {
 		}
 
 		candidates.into_iter().take(available_slots).for_each(|peer_id| {
+			// Keep the slot free if a previous candidate filled the subnet of this one.
+			if let Some(address) = self.peer_addresses.get(&peer_id) {
+				let peers = outbound_per_subnet.entry(ip_prefix(*address, true)).or_default();
+				if *peers >= max_outbound_per_subnet {
+					return
+				}
+				*peers += 1;
+			}
+
 			self.num_out += 1;
 			self.nodes.insert(peer_id, Direction::Outbound);
 			self.start_connection(peer_id);
@@ -878,6 +1065,7 @@ mod tests {
 			out_peers: 0,
 			reserved_nodes: std::iter::once(reserved1).collect(),
 			reserved_only: true,
+			ip_limits: Default::default(),
 		};
 		let (tx, mut rx) = tracing_unbounded("mpsc_test_to_notifications", 100);
 
@@ -940,6 +1128,7 @@ mod tests {
 			out_peers: 0,
 			reserved_nodes: std::iter::once(reserved1).collect(),
 			reserved_only: true,
+			ip_limits: Default::default(),
 		};
 		let (tx, mut rx) = tracing_unbounded("mpsc_test_to_notifications", 100);
 
@@ -991,6 +1180,7 @@ mod tests {
 			out_peers: 0,
 			reserved_nodes: std::iter::once(reserved1).collect(),
 			reserved_only: true,
+			ip_limits: Default::default(),
 		};
 		let (tx, mut rx) = tracing_unbounded("mpsc_test_to_notifications", 100);
 
@@ -1050,6 +1240,7 @@ mod tests {
 			out_peers: 2,
 			reserved_nodes: HashSet::new(),
 			reserved_only: false,
+			ip_limits: Default::default(),
 		};
 		let (tx, mut rx) = tracing_unbounded("mpsc_test_to_notifications", 100);
 
@@ -1137,6 +1328,7 @@ mod tests {
 			out_peers: 2,
 			reserved_nodes: HashSet::new(),
 			reserved_only: false,
+			ip_limits: Default::default(),
 		};
 		let (tx, mut rx) = tracing_unbounded("mpsc_test_to_notifications", 100);
 
@@ -1207,6 +1399,7 @@ mod tests {
 			out_peers: 2,
 			reserved_nodes: HashSet::new(),
 			reserved_only: true,
+			ip_limits: Default::default(),
 		};
 		let (tx, mut rx) = tracing_unbounded("mpsc_test_to_notifications", 100);
 
@@ -1233,6 +1426,7 @@ mod tests {
 			out_peers: 0,
 			reserved_nodes: HashSet::new(),
 			reserved_only: true,
+			ip_limits: Default::default(),
 		};
 		let (tx, mut rx) = tracing_unbounded("mpsc_test_to_notifications", 100);
 
@@ -1270,6 +1464,7 @@ mod tests {
 			out_peers: 10,
 			reserved_nodes: HashSet::new(),
 			reserved_only: true,
+			ip_limits: Default::default(),
 		};
 		let (tx, mut rx) = tracing_unbounded("mpsc_test_to_notifications", 100);
 
@@ -1316,6 +1511,7 @@ mod tests {
 			out_peers: 10,
 			reserved_nodes: [reserved1, reserved2].iter().cloned().collect(),
 			reserved_only: false,
+			ip_limits: Default::default(),
 		};
 		let (tx, mut rx) = tracing_unbounded("mpsc_test_to_notifications", 100);
 
@@ -1376,6 +1572,7 @@ mod tests {
 			out_peers: 10,
 			reserved_nodes: [reserved1, reserved2].iter().cloned().collect(),
 			reserved_only: false,
+			ip_limits: Default::default(),
 		};
 		let (tx, mut rx) = tracing_unbounded("mpsc_test_to_notifications", 100);
 
@@ -1408,6 +1605,7 @@ mod tests {
 			out_peers: 10,
 			reserved_nodes: [reserved1, reserved2].iter().cloned().collect(),
 			reserved_only: true,
+			ip_limits: Default::default(),
 		};
 		let (tx, mut rx) = tracing_unbounded("mpsc_test_to_notifications", 100);
 
@@ -1454,6 +1652,7 @@ mod tests {
 			out_peers: 10,
 			reserved_nodes: [peer1, peer2].iter().cloned().collect(),
 			reserved_only: false,
+			ip_limits: Default::default(),
 		};
 		let (tx, mut rx) = tracing_unbounded("mpsc_test_to_notifications", 100);
 
@@ -1500,6 +1699,7 @@ mod tests {
 			out_peers: 10,
 			reserved_nodes: HashSet::new(),
 			reserved_only: false,
+			ip_limits: Default::default(),
 		};
 		let (tx, mut rx) = tracing_unbounded("mpsc_test_to_notifications", 100);
 
@@ -1542,6 +1742,7 @@ mod tests {
 			out_peers: 10,
 			reserved_nodes: HashSet::new(),
 			reserved_only: false,
+			ip_limits: Default::default(),
 		};
 		let (tx, mut rx) = tracing_unbounded("mpsc_test_to_notifications", 100);
 
@@ -1601,6 +1802,7 @@ mod tests {
 			out_peers: 10,
 			reserved_nodes: [reserved1, reserved2].iter().cloned().collect(),
 			reserved_only: false,
+			ip_limits: Default::default(),
 		};
 		let (tx, mut rx) = tracing_unbounded("mpsc_test_to_notifications", 100);
 
@@ -1657,6 +1859,7 @@ mod tests {
 			out_peers: 10,
 			reserved_nodes: HashSet::new(),
 			reserved_only: false,
+			ip_limits: Default::default(),
 		};
 		let (tx, mut rx) = tracing_unbounded("mpsc_test_to_notifications", 100);
 
@@ -1709,6 +1912,7 @@ mod tests {
 			out_peers: 10,
 			reserved_nodes: [reserved1, reserved2].iter().cloned().collect(),
 			reserved_only: false,
+			ip_limits: Default::default(),
 		};
 		let (tx, mut rx) = tracing_unbounded("mpsc_test_to_notifications", 100);
 
@@ -1769,6 +1973,7 @@ mod tests {
 			out_peers: 10,
 			reserved_nodes: HashSet::new(),
 			reserved_only: false,
+			ip_limits: Default::default(),
 		};
 		let (tx, mut rx) = tracing_unbounded("mpsc_test_to_notifications", 100);
 
@@ -1821,6 +2026,7 @@ mod tests {
 			out_peers: 10,
 			reserved_nodes: HashSet::new(),
 			reserved_only: false,
+			ip_limits: Default::default(),
 		};
 		let (tx, mut rx) = tracing_unbounded("mpsc_test_to_notifications", 100);
 
@@ -1874,6 +2080,7 @@ mod tests {
 			out_peers: 1,
 			reserved_nodes: HashSet::new(),
 			reserved_only: false,
+			ip_limits: Default::default(),
 		};
 		let (tx, mut rx) = tracing_unbounded("mpsc_test_to_notifications", 100);
 
@@ -1927,6 +2134,7 @@ mod tests {
 			out_peers: 10,
 			reserved_nodes: HashSet::new(),
 			reserved_only: false,
+			ip_limits: Default::default(),
 		};
 		let (tx, mut rx) = tracing_unbounded("mpsc_test_to_notifications", 100);
 
@@ -1957,6 +2165,7 @@ mod tests {
 			out_peers: 10,
 			reserved_nodes: HashSet::new(),
 			reserved_only: false,
+			ip_limits: Default::default(),
 		};
 		let (tx, mut rx) = tracing_unbounded("mpsc_test_to_notifications", 100);
 
@@ -1982,6 +2191,7 @@ mod tests {
 			out_peers: 10,
 			reserved_nodes: std::iter::once(reserved1).collect(),
 			reserved_only: false,
+			ip_limits: Default::default(),
 		};
 		let (tx, mut rx) = tracing_unbounded("mpsc_test_to_notifications", 100);
 
@@ -2008,6 +2218,7 @@ mod tests {
 			out_peers: 10,
 			reserved_nodes: std::iter::once(reserved1).collect(),
 			reserved_only: false,
+			ip_limits: Default::default(),
 		};
 		let (tx, mut rx) = tracing_unbounded("mpsc_test_to_notifications", 100);
 
@@ -2025,4 +2236,122 @@ mod tests {
 		assert!(matches!(controller.reserved_nodes.get(&reserved1), Some(PeerState::NotConnected)));
 		assert_eq!(rx.try_recv().unwrap_err(), TryRecvError::Empty);
 	}
+
+	#[test]
+	fn incoming_peers_exceeding_ip_limits_are_rejected() {
+		let peers = (0..5).map(|_| PeerId::random()).collect::<Vec<_>>();
+		let addresses: [IpAddr; 5] = [
+			[10, 0, 0, 1].into(),
+			[10, 0, 0, 1].into(),
+			[10, 0, 0, 2].into(),
+			[10, 0, 0, 3].into(),
+			[10, 0, 1, 1].into(),
+		];
+
+		let config = ProtoSetConfig {
+			in_peers: 10,
+			out_peers: 10,
+			reserved_nodes: HashSet::new(),
+			reserved_only: false,
+			ip_limits: IpConnectionLimits {
+				max_inbound_per_ip: Some(1),
+				max_inbound_per_subnet: Some(2),
+				max_outbound_per_subnet: None,
+			},
+		};
+		let (tx, mut rx) = tracing_unbounded("mpsc_test_to_notifications", 100);
+
+		let mut peer_store = MockPeerStoreHandle::new();
+		peer_store.expect_register_protocol().once().return_const(());
+		peer_store.expect_is_banned().times(3).return_const(false);
+
+		let (_handle, mut controller) =
+			ProtocolController::new(SetId::from(0), config, tx, Box::new(peer_store));
+
+		for (index, (peer, address)) in peers.iter().zip(addresses).enumerate() {
+			controller.on_peer_address(*peer, address);
+			controller.on_incoming_connection(*peer, IncomingIndex(index as u64));
+		}
+
+		let messages = std::iter::from_fn(|| rx.try_recv().ok()).collect::<Vec<_>>();
+		assert_eq!(
+			messages,
+			vec![
+				Message::Accept(IncomingIndex(0)),
+				// Same IP address as `peers[0]`.
+				Message::Reject(IncomingIndex(1)),
+				Message::Accept(IncomingIndex(2)),
+				// Same subnet as `peers[0]` and `peers[2]`.
+				Message::Reject(IncomingIndex(3)),
+				Message::Accept(IncomingIndex(4)),
+			]
+		);
+		assert_eq!(controller.num_in, 3);
+	}
+
+	#[test]
+	fn outbound_peers_are_spread_over_subnets() {
+		let peer1 = PeerId::random();
+		let peer2 = PeerId::random();
+		let peer3 = PeerId::random();
+
+		let config = ProtoSetConfig {
+			in_peers: 0,
+			out_peers: 10,
+			reserved_nodes: HashSet::new(),
+			reserved_only: false,
+			ip_limits: IpConnectionLimits {
+				max_outbound_per_subnet: Some(1),
+				..Default::default()
+			},
+		};
+		let (tx, mut rx) = tracing_unbounded("mpsc_test_to_notifications", 100);
+
+		let mut peer_store = MockPeerStoreHandle::new();
+		peer_store.expect_register_protocol().once().return_const(());
+		peer_store
+			.expect_outgoing_candidates()
+			.once()
+			.return_const(vec![peer1, peer2, peer3]);
+
+		let (_handle, mut controller) =
+			ProtocolController::new(SetId::from(0), config, tx, Box::new(peer_store));
+
+		// Addresses of `peer1` and `peer2`, in the same subnet, are known from earlier connections.
+		controller.on_peer_address(peer1, [10, 0, 0, 1].into());
+		controller.on_peer_address(peer2, [10, 0, 0, 2].into());
+
+		// Only one of them is dialed, along with `peer3`, whose address is unknown.
+		controller.alloc_slots();
+		let messages = std::iter::from_fn(|| rx.try_recv().ok()).collect::<Vec<_>>();
+		assert_eq!(
+			messages,
+			vec![
+				Message::Connect { set_id: SetId::from(0), peer_id: peer1 },
+				Message::Connect { set_id: SetId::from(0), peer_id: peer3 },
+			]
+		);
+		assert_eq!(controller.num_out, 2);
+
+		// `peer3` turns out to be in the same subnet, and is dropped.
+		controller.on_peer_address(peer3, [10, 0, 0, 3].into());
+		assert_eq!(
+			rx.try_recv().unwrap(),
+			Message::Drop { set_id: SetId::from(0), peer_id: peer3 }
+		);
+		assert_eq!(rx.try_recv().unwrap_err(), TryRecvError::Empty);
+		assert_eq!(controller.num_out, 1);
+		assert!(!controller.nodes.contains_key(&peer3));
+	}
+
+	#[test]
+	fn ip_prefixes() {
+		let v4: IpAddr = [192, 168, 7, 42].into();
+		assert_eq!(ip_prefix(v4, false), v4);
+		assert_eq!(ip_prefix(v4, true), IpAddr::from([192, 168, 7, 0]));
+
+		let v6: IpAddr = "2001:db8:1:2:3:4:5:6".parse().unwrap();
+		assert_eq!(ip_prefix(v6, false), "2001:db8:1:2::".parse::<IpAddr>().unwrap());
+		assert_eq!(ip_prefix(v6, true), "2001:db8:1::".parse::<IpAddr>().unwrap());
+	}
 }
@@ -35,6 +35,8 @@
 //! is used to handle incoming requests.
 
 use crate::{
+	bandwidth::{Bandwidth, ProtocolThrottle},
+	config::ProtocolBandwidth,
 	peer_store::{PeerStoreProvider, BANNED_THRESHOLD},
 	types::ProtocolName,
 	ReputationChange,
@@ -126,6 +128,12 @@ pub struct ProtocolConfig {
 	/// advertise support for this protocol, but any incoming request will lead to an error being
 	/// sent back.
 	pub inbound_queue: Option<async_channel::Sender<IncomingRequest>>,
+
+	/// Bandwidth limits and priority of the protocol.
+	///
+	/// Requests and responses are delayed while the limits are exceeded, so large responses on a
+	/// limited protocol should come with a generous `request_timeout`.
+	pub bandwidth: ProtocolBandwidth,
 }
 
 /// A single request received by a peer on a request-response protocol.
@@ -298,6 +306,7 @@ impl RequestResponsesBehaviour {
 	/// the same protocol is passed twice.
 	pub fn new(
 		list: impl Iterator<Item = ProtocolConfig>,
+		bandwidth: &Bandwidth,
 		peer_store: Box<dyn PeerStoreProvider>,
 	) -> Result<Self, RegisterError> {
 		let mut protocols = HashMap::new();
@@ -316,6 +325,7 @@ impl RequestResponsesBehaviour {
 				GenericCodec {
 					max_request_size: protocol.max_request_size,
 					max_response_size: protocol.max_response_size,
+					bandwidth: bandwidth.protocol(&protocol.name, &protocol.bandwidth),
 				},
 				iter::once(protocol.name.as_bytes().to_vec())
 					.chain(protocol.fallback_names.iter().map(|name| name.as_bytes().to_vec()))
@@ -853,6 +863,7 @@ pub enum ResponseFailure {
 pub struct GenericCodec {
 	max_request_size: u64,
 	max_response_size: u64,
+	bandwidth: ProtocolThrottle,
 }
 
 #[async_trait::async_trait]
@@ -880,7 +891,8 @@ impl Codec for GenericCodec {
 			))
 		}
 
-		// Read the payload.
+		// Read the payload once the bandwidth limits allow it.
+		self.bandwidth.download.acquire(length).await;
 		let mut buffer = vec![0; length];
 		io.read_exact(&mut buffer).await?;
 		Ok(buffer)
@@ -915,7 +927,8 @@ impl Codec for GenericCodec {
 			))
 		}
 
-		// Read the payload.
+		// Read the payload once the bandwidth limits allow it.
+		self.bandwidth.download.acquire(length).await;
 		let mut buffer = vec![0; length];
 		io.read_exact(&mut buffer).await?;
 		Ok(Ok(buffer))
@@ -930,6 +943,8 @@ impl Codec for GenericCodec {
 	where
 		T: AsyncWrite + Unpin + Send,
 	{
+		self.bandwidth.upload.acquire(req.len()).await;
+
 		// TODO: check the length?
 		// Write the length.
 		{
@@ -955,6 +970,8 @@ impl Codec for GenericCodec {
 	{
 		// If `res` is an `Err`, we jump to closing the substream without writing anything on it.
 		if let Ok(res) = res {
+			self.bandwidth.upload.acquire(res.len()).await;
+
 			// TODO: check the length?
 			// Write the length.
 			{
@@ -975,8 +992,11 @@ impl Codec for GenericCodec {
 mod tests {
 	use super::*;
 
-	use crate::mock::MockPeerStore;
-	use futures::{channel::oneshot, executor::LocalPool, task::Spawn};
+	use crate::{
+		config::{BandwidthLimits, ProtocolBandwidth},
+		mock::{throttled_bytes, MockPeerStore},
+	};
+	use futures::{channel::oneshot, executor::LocalPool, io::Cursor, task::Spawn};
 	use libp2p::{
 		core::{
 			transport::{MemoryTransport, Transport},
@@ -987,7 +1007,8 @@ mod tests {
 		swarm::{Executor, Swarm, SwarmBuilder, SwarmEvent},
 		Multiaddr,
 	};
-	use std::{iter, time::Duration};
+	use prometheus_endpoint::Registry;
+	use std::{iter, num::NonZeroU32, time::Duration};
 
 	struct TokioExecutor(tokio::runtime::Runtime);
 	impl Executor for TokioExecutor {
@@ -1007,7 +1028,9 @@ mod tests {
 			.multiplex(libp2p::yamux::Config::default())
 			.boxed();
 
-		let behaviour = RequestResponsesBehaviour::new(list, Box::new(MockPeerStore {})).unwrap();
+		let bandwidth = Bandwidth::new(&Default::default(), None).unwrap();
+		let behaviour =
+			RequestResponsesBehaviour::new(list, &bandwidth, Box::new(MockPeerStore {})).unwrap();
 
 		let runtime = tokio::runtime::Runtime::new().unwrap();
 		let mut swarm = SwarmBuilder::with_executor(
@@ -1023,6 +1046,44 @@ mod tests {
 		(swarm, listen_addr)
 	}
 
+	#[tokio::test(start_paused = true)]
+	async fn codec_is_throttled() {
+		let registry = Registry::new();
+		let limits =
+			BandwidthLimits { upload: NonZeroU32::new(100), download: NonZeroU32::new(100) };
+		let bandwidth = Bandwidth::new(&Default::default(), Some(&registry)).unwrap();
+		let mut codec = GenericCodec {
+			max_request_size: 1024,
+			max_response_size: 1024,
+			bandwidth: bandwidth.protocol(
+				&ProtocolName::from("/test/req-resp/1"),
+				&ProtocolBandwidth { limits, ..Default::default() },
+			),
+		};
+		let protocol = b"/test/req-resp/1".to_vec();
+
+		// The first second worth of bytes goes through right away, and the bucket is allowed to go
+		// into debt once, so only the third request waits for it to refill.
+		let started = tokio::time::Instant::now();
+		let mut requests = Vec::new();
+		for _ in 0..3 {
+			let mut io = Cursor::new(Vec::new());
+			codec.write_request(&protocol, &mut io, vec![0; 100]).await.unwrap();
+			requests.push(io.into_inner());
+		}
+		assert!(started.elapsed() >= Duration::from_millis(900));
+		assert_eq!(throttled_bytes(&registry, "out"), 100);
+		assert_eq!(throttled_bytes(&registry, "in"), 0);
+
+		let started = tokio::time::Instant::now();
+		for request in requests {
+			let request = codec.read_request(&protocol, &mut Cursor::new(request)).await;
+			assert_eq!(request.unwrap(), vec![0; 100]);
+		}
+		assert!(started.elapsed() >= Duration::from_millis(900));
+		assert_eq!(throttled_bytes(&registry, "in"), 100);
+	}
+
 	#[test]
 	fn basic_request_response_works() {
 		let protocol_name = "/test/req-resp/1";
@@ -1059,6 +1120,7 @@ mod tests {
 					max_response_size: 1024 * 1024,
 					request_timeout: Duration::from_secs(30),
 					inbound_queue: Some(tx),
+					bandwidth: Default::default(),
 				};
 
 				build_swarm(iter::once(protocol_config))
@@ -1156,6 +1218,7 @@ mod tests {
 					max_response_size: 8, // <-- important for the test
 					request_timeout: Duration::from_secs(30),
 					inbound_queue: Some(tx),
+					bandwidth: Default::default(),
 				};
 
 				build_swarm(iter::once(protocol_config))
@@ -1249,6 +1312,7 @@ mod tests {
 					max_response_size: 1024 * 1024,
 					request_timeout: Duration::from_secs(30),
 					inbound_queue: None,
+					bandwidth: Default::default(),
 				},
 				ProtocolConfig {
 					name: From::from(protocol_name_2),
@@ -1257,6 +1321,7 @@ mod tests {
 					max_response_size: 1024 * 1024,
 					request_timeout: Duration::from_secs(30),
 					inbound_queue: None,
+					bandwidth: Default::default(),
 				},
 			];
 
@@ -1275,6 +1340,7 @@ mod tests {
 					max_response_size: 1024 * 1024,
 					request_timeout: Duration::from_secs(30),
 					inbound_queue: Some(tx_1),
+					bandwidth: Default::default(),
 				},
 				ProtocolConfig {
 					name: From::from(protocol_name_2),
@@ -1283,6 +1349,7 @@ mod tests {
 					max_response_size: 1024 * 1024,
 					request_timeout: Duration::from_secs(30),
 					inbound_queue: Some(tx_2),
+					bandwidth: Default::default(),
 				},
 			];
 
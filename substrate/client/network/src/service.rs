@@ -28,6 +28,7 @@
 //! which is then processed by [`NetworkWorker::next_action`].
 
 use crate::{
+	bandwidth::Bandwidth,
 	behaviour::{self, Behaviour, BehaviourOut},
 	config::{parse_addr, FullNetworkConfiguration, MultiaddrWithPeerId, Params, TransportConfig},
 	discovery::DiscoveryConfig,
@@ -274,10 +275,19 @@ where
 		// We must prepend a hardcoded default peer set to notification protocols.
 		let all_peer_sets_iter = iter::once(&network_config.default_peers_set)
 			.chain(notification_protocols.iter().map(|protocol| protocol.set_config()));
+		let all_peer_set_names_iter = iter::once(params.block_announce_config.protocol_name())
+			.chain(notification_protocols.iter().map(|protocol| protocol.protocol_name()));
+
+		let protocol_controller_metrics = params
+			.metrics_registry
+			.as_ref()
+			.map(protocol_controller::Metrics::register)
+			.transpose()?;
 
 		let (protocol_handles, protocol_controllers): (Vec<_>, Vec<_>) = all_peer_sets_iter
+			.zip(all_peer_set_names_iter)
 			.enumerate()
-			.map(|(set_id, set_config)| {
+			.map(|(set_id, (set_config, protocol_name))| {
 				let proto_set_config = ProtoSetConfig {
 					in_peers: set_config.in_peers,
 					out_peers: set_config.out_peers,
@@ -287,14 +297,20 @@ where
 						.map(|node| node.peer_id)
 						.collect(),
 					reserved_only: set_config.non_reserved_mode.is_reserved_only(),
+					ip_limits: network_config.ip_connection_limits,
 				};
 
-				ProtocolController::new(
+				let (handle, mut controller) = ProtocolController::new(
 					SetId::from(set_id),
 					proto_set_config,
 					to_notifications.clone(),
 					Box::new(params.peer_store.clone()),
-				)
+				);
+				if let Some(metrics) = &protocol_controller_metrics {
+					controller.set_metrics(protocol_name.clone(), metrics.clone());
+				}
+
+				(handle, controller)
 			})
 			.unzip();
 
@@ -377,9 +393,13 @@ where
 		let num_connected = Arc::new(AtomicUsize::new(0));
 		let external_addresses = Arc::new(Mutex::new(HashSet::new()));
 
+		let bandwidth_limits =
+			Bandwidth::new(&network_config.bandwidth_limits, params.metrics_registry.as_ref())?;
+
 		let (protocol, notif_protocol_handles) = Protocol::new(
 			From::from(&params.role),
 			&params.metrics_registry,
+			&bandwidth_limits,
 			notification_protocols,
 			params.block_announce_config,
 			params.peer_store.clone(),
@@ -433,6 +453,7 @@ where
 					local_public,
 					discovery_config,
 					request_response_protocols,
+					&bandwidth_limits,
 					params.peer_store.clone(),
 					external_addresses.clone(),
 				);
@@ -505,6 +526,12 @@ where
 			);
 		}
 
+		// Add the addresses that were good recently, possibly in a previous run of the node. They
+		// are forgotten like any other discovered address if they turn out to be unreachable.
+		for (peer_id, addr) in params.peer_store.good_addresses() {
+			swarm.behaviour_mut().add_discovered_address(peer_id, addr);
+		}
+
 		let listen_addresses = Arc::new(Mutex::new(HashSet::new()));
 
 		let service = Arc::new(NetworkService {
@@ -1510,6 +1537,10 @@ where
 					debug!(target: "sub-libp2p", "Libp2p => Connected({:?})", peer_id);
 				}
 
+				if let ConnectedPoint::Dialer { address, .. } = &endpoint {
+					self.peer_store_handle.add_good_address(peer_id, address.clone());
+				}
+
 				if let Some(metrics) = self.metrics.as_ref() {
 					let direction = match endpoint {
 						ConnectedPoint::Dialer { .. } => "out",
@@ -0,0 +1,326 @@
+// This file is part of Substrate.
+
+// Copyright (C) Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Schema for warp and state sync progress persisted in the aux-db.
+//!
+//! The last verified warp proof checkpoint and the key-value pairs of every verified state range
+//! are written to the database, so that a sync interrupted by a node restart can continue from
+//! the last completed key range instead of starting from scratch. Proofs are not stored, state
+//! ranges are verified once when downloaded. Stored state ranges are not held in memory while
+//! the download is in progress, they are only read back once it is complete.
+
+use codec::{Decode, Encode};
+use log::trace;
+use sc_client_api::backend::AuxStore;
+use sp_blockchain::{Error as ClientError, Result as ClientResult};
+use sp_consensus_grandpa::{AuthorityList, SetId};
+use sp_runtime::{traits::Block as BlockT, Justifications};
+
+const LOG_TARGET: &str = "sync";
+
+const VERSION_KEY: &[u8] = b"sync_progress_version";
+const WARP_PROOF_PROGRESS_KEY: &[u8] = b"sync_warp_proof_progress";
+const STATE_PROGRESS_KEY: &[u8] = b"sync_state_progress";
+const STATE_RANGE_PREFIX: &[u8] = b"sync_state_range";
+
+const CURRENT_VERSION: u32 = 1;
+
+/// Database the sync progress is persisted to.
+///
+/// Implemented for every [`AuxStore`], so a client can be passed as the store.
+pub trait SyncProgressStore: Send + Sync {
+	/// Read the value stored under `key`.
+	fn get(&self, key: &[u8]) -> ClientResult<Option<Vec<u8>>>;
+
+	/// Insert and delete values in one atomic write.
+	fn write(&self, insert: &[(&[u8], &[u8])], delete: &[&[u8]]) -> ClientResult<()>;
+}
+
+impl<T: AuxStore + Send + Sync> SyncProgressStore for T {
+	fn get(&self, key: &[u8]) -> ClientResult<Option<Vec<u8>>> {
+		self.get_aux(key)
+	}
+
+	fn write(&self, insert: &[(&[u8], &[u8])], delete: &[&[u8]]) -> ClientResult<()> {
+		self.insert_aux(insert, delete)
+	}
+}
+
+/// Last verified warp proof checkpoint.
+#[derive(Encode, Decode, Clone, Debug, PartialEq, Eq)]
+pub struct WarpProofProgress<B: BlockT> {
+	/// Authority set id the next proof is verified against.
+	pub set_id: SetId,
+	/// Authorities of `set_id`.
+	pub authorities: AuthorityList,
+	/// Block the next warp proof request starts from.
+	pub last_hash: B::Hash,
+	/// Total warp proof bytes downloaded so far.
+	pub total_proof_bytes: u64,
+	/// Target block proved by the last warp proof, once warp proofs are complete.
+	pub target_header: Option<B::Header>,
+}
+
+/// State download progress.
+///
+/// The downloaded state itself is stored as one [`StateRange`] per verified state response.
+#[derive(Encode, Decode, Clone, Debug, PartialEq, Eq)]
+pub struct StateSyncProgress<B: BlockT> {
+	/// Header of the block whose state is downloaded.
+	pub target_header: B::Header,
+	/// Body of the target block, if known.
+	pub target_body: Option<Vec<B::Extrinsic>>,
+	/// Justifications of the target block, if known.
+	pub target_justifications: Option<Justifications>,
+	/// Whether state responses are requested without proofs.
+	pub skip_proof: bool,
+	/// Number of state ranges stored so far.
+	pub ranges: u32,
+	/// Key the next state request starts from.
+	pub last_key: Vec<Vec<u8>>,
+	/// Estimated download percentage.
+	pub percentage: u32,
+	/// Total state bytes downloaded so far.
+	pub imported_bytes: u64,
+}
+
+/// Verified key-value pairs of one state response, grouped by the root of the trie they belong
+/// to. The top trie has an empty root.
+pub(crate) type StateRange = Vec<(Vec<u8>, Vec<(Vec<u8>, Vec<u8>)>)>;
+
+fn state_range_key(index: u32) -> Vec<u8> {
+	(STATE_RANGE_PREFIX, index).encode()
+}
+
+fn load_decode<T: Decode>(store: &dyn SyncProgressStore, key: &[u8]) -> ClientResult<Option<T>> {
+	match store.get(key)? {
+		None => Ok(None),
+		Some(t) => T::decode(&mut &t[..])
+			.map_err(|e| ClientError::Backend(format!("Sync progress DB is corrupted: {}", e)))
+			.map(Some),
+	}
+}
+
+fn check_version(store: &dyn SyncProgressStore) -> ClientResult<bool> {
+	match load_decode::<u32>(store, VERSION_KEY)? {
+		None => Ok(false),
+		Some(CURRENT_VERSION) => Ok(true),
+		other =>
+			Err(ClientError::Backend(format!("Unsupported sync progress DB version: {:?}", other))),
+	}
+}
+
+/// Write the last verified warp proof checkpoint.
+pub(crate) fn write_warp_proof_progress<B: BlockT>(
+	store: &dyn SyncProgressStore,
+	progress: &WarpProofProgress<B>,
+) -> ClientResult<()> {
+	trace!(target: LOG_TARGET, "Persisting warp proof progress, set_id={}", progress.set_id);
+	store.write(
+		&[
+			(VERSION_KEY, CURRENT_VERSION.encode().as_slice()),
+			(WARP_PROOF_PROGRESS_KEY, progress.encode().as_slice()),
+		],
+		&[],
+	)
+}
+
+/// Append a verified state range and update the state download progress accordingly.
+///
+/// `progress.ranges` must already account for `range`.
+pub(crate) fn write_state_range<B: BlockT>(
+	store: &dyn SyncProgressStore,
+	progress: &StateSyncProgress<B>,
+	range: &StateRange,
+) -> ClientResult<()> {
+	let index = progress.ranges.checked_sub(1).ok_or_else(|| {
+		ClientError::Backend("State sync progress must account for the stored range".into())
+	})?;
+	trace!(target: LOG_TARGET, "Persisting state range #{}", index);
+	let key = state_range_key(index);
+	store.write(
+		&[
+			(VERSION_KEY, CURRENT_VERSION.encode().as_slice()),
+			(key.as_slice(), range.encode().as_slice()),
+			(STATE_PROGRESS_KEY, progress.encode().as_slice()),
+		],
+		&[],
+	)
+}
+
+/// Load the last verified warp proof checkpoint, if any.
+pub fn load_warp_proof_progress<B: BlockT>(
+	store: &dyn SyncProgressStore,
+) -> ClientResult<Option<WarpProofProgress<B>>> {
+	if !check_version(store)? {
+		return Ok(None)
+	}
+	load_decode(store, WARP_PROOF_PROGRESS_KEY)
+}
+
+/// Load the state download progress, if any.
+pub fn load_state_sync_progress<B: BlockT>(
+	store: &dyn SyncProgressStore,
+) -> ClientResult<Option<StateSyncProgress<B>>> {
+	if !check_version(store)? {
+		return Ok(None)
+	}
+	load_decode(store, STATE_PROGRESS_KEY)
+}
+
+/// Load the state download progress together with all stored state ranges.
+pub(crate) fn load_state_ranges<B: BlockT>(
+	store: &dyn SyncProgressStore,
+) -> ClientResult<Option<(StateSyncProgress<B>, Vec<StateRange>)>> {
+	let Some(progress) = load_state_sync_progress::<B>(store)? else { return Ok(None) };
+	let ranges = (0..progress.ranges)
+		.map(|index| {
+			load_decode(store, &state_range_key(index))?.ok_or_else(|| {
+				ClientError::Backend(format!("Sync progress DB is missing state range #{index}"))
+			})
+		})
+		.collect::<ClientResult<Vec<_>>>()?;
+	Ok(Some((progress, ranges)))
+}
+
+/// Remove the state download progress and all stored state ranges.
+///
+/// Ranges are stored under consecutive indices, so they are looked up until the first missing
+/// one rather than relying on the progress, which might be corrupted.
+pub(crate) fn clear_state_sync_progress(store: &dyn SyncProgressStore) -> ClientResult<()> {
+	let mut keys = Vec::new();
+	for index in 0.. {
+		let key = state_range_key(index);
+		if store.get(&key)?.is_none() {
+			break
+		}
+		keys.push(key);
+	}
+	let delete = keys
+		.iter()
+		.map(|key| key.as_slice())
+		.chain(std::iter::once(STATE_PROGRESS_KEY))
+		.collect::<Vec<_>>();
+	store.write(&[], &delete)
+}
+
+/// Remove all persisted sync progress.
+pub(crate) fn clear_sync_progress(store: &dyn SyncProgressStore) -> ClientResult<()> {
+	clear_state_sync_progress(store)?;
+	store.write(&[], &[WARP_PROOF_PROGRESS_KEY, VERSION_KEY])
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_runtime::traits::Header as _;
+	use substrate_test_runtime_client::runtime::{Block, Header};
+
+	fn state_progress(ranges: u32) -> StateSyncProgress<Block> {
+		StateSyncProgress {
+			target_header: Header::new(
+				5,
+				Default::default(),
+				Default::default(),
+				Default::default(),
+				Default::default(),
+			),
+			target_body: None,
+			target_justifications: None,
+			skip_proof: false,
+			ranges,
+			last_key: vec![vec![1, 2, 3]],
+			percentage: 1,
+			imported_bytes: 42,
+		}
+	}
+
+	fn range(key: u8) -> StateRange {
+		vec![(Vec::new(), vec![(vec![key], vec![key, key])])]
+	}
+
+	#[test]
+	fn state_ranges_roundtrip_and_clear() {
+		let client = substrate_test_runtime_client::new();
+
+		assert_eq!(load_state_ranges::<Block>(&client).unwrap(), None);
+
+		write_state_range(&client, &state_progress(1), &range(1)).unwrap();
+		write_state_range(&client, &state_progress(2), &range(2)).unwrap();
+
+		let (progress, ranges) = load_state_ranges::<Block>(&client).unwrap().unwrap();
+		assert_eq!(progress, state_progress(2));
+		assert_eq!(ranges, vec![range(1), range(2)]);
+
+		clear_state_sync_progress(&client).unwrap();
+		assert_eq!(load_state_ranges::<Block>(&client).unwrap(), None);
+		assert_eq!(client.get_aux(&state_range_key(0)).unwrap(), None);
+		assert_eq!(client.get_aux(&state_range_key(1)).unwrap(), None);
+	}
+
+	#[test]
+	fn clear_removes_ranges_of_corrupted_progress() {
+		let client = substrate_test_runtime_client::new();
+
+		write_state_range(&client, &state_progress(1), &range(1)).unwrap();
+		write_state_range(&client, &state_progress(2), &range(2)).unwrap();
+		client.insert_aux(&[(STATE_PROGRESS_KEY, &[0xff][..])], &[]).unwrap();
+
+		clear_state_sync_progress(&client).unwrap();
+		assert_eq!(load_state_sync_progress::<Block>(&client).unwrap(), None);
+		assert_eq!(client.get_aux(&state_range_key(0)).unwrap(), None);
+		assert_eq!(client.get_aux(&state_range_key(1)).unwrap(), None);
+	}
+
+	#[test]
+	fn missing_state_range_is_an_error() {
+		let client = substrate_test_runtime_client::new();
+
+		write_state_range(&client, &state_progress(1), &range(1)).unwrap();
+		client.insert_aux(&[], &[state_range_key(0).as_slice()]).unwrap();
+
+		assert!(load_state_ranges::<Block>(&client).is_err());
+	}
+
+	#[test]
+	fn warp_proof_progress_roundtrip_and_clear() {
+		let client = substrate_test_runtime_client::new();
+		let progress = WarpProofProgress::<Block> {
+			set_id: 3,
+			authorities: Vec::new(),
+			last_hash: state_progress(0).target_header.hash(),
+			total_proof_bytes: 1024,
+			target_header: None,
+		};
+
+		assert_eq!(load_warp_proof_progress::<Block>(&client).unwrap(), None);
+		write_warp_proof_progress(&client, &progress).unwrap();
+		assert_eq!(load_warp_proof_progress::<Block>(&client).unwrap(), Some(progress));
+
+		clear_sync_progress(&client).unwrap();
+		assert_eq!(load_warp_proof_progress::<Block>(&client).unwrap(), None);
+	}
+
+	#[test]
+	fn unsupported_version_is_an_error() {
+		let client = substrate_test_runtime_client::new();
+		client.insert_aux(&[(VERSION_KEY, 99u32.encode().as_slice())], &[]).unwrap();
+
+		assert!(load_state_sync_progress::<Block>(&client).is_err());
+	}
+}
@@ -33,7 +33,7 @@ use log::debug;
 use prost::Message;
 use sc_client_api::BlockBackend;
 use sc_network::{
-	config::ProtocolId,
+	config::{BandwidthPriority, ProtocolBandwidth, ProtocolId},
 	request_responses::{
 		IfDisconnected, IncomingRequest, OutgoingResponse, ProtocolConfig, RequestFailure,
 	},
@@ -85,6 +85,7 @@ pub fn generate_protocol_config<Hash: AsRef<[u8]>>(
 		max_response_size: 16 * 1024 * 1024,
 		request_timeout: Duration::from_secs(20),
 		inbound_queue: None,
+		bandwidth: ProtocolBandwidth::with_priority(BandwidthPriority::Low),
 	}
 }
 
@@ -29,6 +29,7 @@
 //! order to update it.
 
 use crate::{
+	aux_schema::{self, SyncProgressStore},
 	blocks::BlockCollection,
 	extra_requests::ExtraRequests,
 	schema::v1::StateResponse,
@@ -98,6 +99,10 @@ const MAJOR_SYNC_BLOCKS: u8 = 5;
 /// Number of peers that need to be connected before warp sync is started.
 const MIN_PEERS_TO_START_WARP_SYNC: usize = 3;
 
+/// A persisted state sync is only resumed if its target block is at most this many blocks behind
+/// the new target. Older state is likely to be pruned by peers.
+const MAX_STATE_SYNC_RESUME_DISTANCE: u32 = 256;
+
 mod rep {
 	use sc_network::ReputationChange as Rep;
 	/// Reputation change when a peer sent us a message that led to a
@@ -255,6 +260,8 @@ pub struct ChainSync<B: BlockT, Client> {
 	gap_sync: Option<GapSync<B>>,
 	/// Pending actions.
 	actions: Vec<ChainSyncAction<B>>,
+	/// Database the warp and state sync progress is persisted to.
+	progress_store: Option<Arc<dyn SyncProgressStore>>,
 }
 
 /// All the data we have about a Peer that we are trying to sync with
@@ -370,12 +377,26 @@ where
 			warp_sync_config,
 			warp_sync_target_block_header: None,
 			actions: Vec::new(),
+			progress_store: None,
 		};
 
 		sync.reset_sync_start_point()?;
 		Ok(sync)
 	}
 
+	/// Persist the warp and state sync progress to `store`, and resume the progress of a previous
+	/// instance.
+	pub fn with_progress_store(mut self, store: Arc<dyn SyncProgressStore>) -> Self {
+		if let SyncMode::Full = self.mode {
+			// Progress of a warp or state sync can't be resumed in full sync mode.
+			if let Err(e) = aux_schema::clear_sync_progress(&*store) {
+				warn!(target: LOG_TARGET, "Failed to clear persisted sync progress: {e}");
+			}
+		}
+		self.progress_store = Some(store);
+		self
+	}
+
 	/// Get peer's best hash & number.
 	pub fn peer_info(&self, peer_id: &PeerId) -> Option<PeerInfo<B>> {
 		self.peers
@@ -560,10 +581,27 @@ where
 						log::debug!(target: LOG_TARGET, "Starting warp state sync.");
 
 						if let Some(config) = self.warp_sync_config.take() {
-							let mut warp_sync = WarpSync::new(self.client.clone(), config);
+							let mut warp_sync = WarpSync::new(
+								self.client.clone(),
+								config,
+								self.progress_store.clone(),
+							);
 							if let Some(header) = self.warp_sync_target_block_header.take() {
 								warp_sync.set_target_block(header);
 							}
+							if let (Some(target), Some(median)) =
+								(warp_sync.target_block_number(), self.median_seen())
+							{
+								if target.saturating_add(MAX_STATE_SYNC_RESUME_DISTANCE.into()) <
+									median
+								{
+									debug!(
+										target: LOG_TARGET,
+										"Resumed warp sync target #{target} is obsolete, discarding.",
+									);
+									warp_sync.discard_resumed_target();
+								}
+							}
 							self.warp_sync = Some(warp_sync);
 						}
 					}
@@ -1044,18 +1082,27 @@ where
 				heads.sort();
 				let median = heads[heads.len() / 2];
 				if number + STATE_SYNC_FINALITY_THRESHOLD.saturated_into() >= median {
-					if let Ok(Some(header)) = self.client.header(*hash) {
+					if let Some(sync) = self.resume_state_sync(number, *skip_proofs) {
+						log::debug!(
+							target: LOG_TARGET,
+							"Resuming state sync for #{} ({})",
+							sync.target_block_num(),
+							sync.target(),
+						);
+						self.state_sync = Some(sync);
+						self.allowed_requests.set_all();
+					} else if let Ok(Some(header)) = self.client.header(*hash) {
 						log::debug!(
 							target: LOG_TARGET,
 							"Starting state sync for #{number} ({hash})",
 						);
-						self.state_sync = Some(StateSync::new(
-							self.client.clone(),
-							header,
-							None,
-							None,
-							*skip_proofs,
-						));
+						let mut sync =
+							StateSync::new(self.client.clone(), header, None, None, *skip_proofs);
+						if let Some(store) = &self.progress_store {
+							StateSync::<B, Client>::clear_persisted(&**store);
+							sync = sync.with_progress_store(store.clone());
+						}
+						self.state_sync = Some(sync);
 						self.allowed_requests.set_all();
 					}
 				}
@@ -1186,6 +1233,27 @@ where
 		}
 	}
 
+	/// Remove persisted warp and state sync progress.
+	fn clear_sync_progress(&self) {
+		let Some(store) = &self.progress_store else { return };
+		if let Err(e) = aux_schema::clear_sync_progress(&**store) {
+			warn!(target: LOG_TARGET, "Failed to clear persisted sync progress: {e}");
+		}
+	}
+
+	/// Resume a persisted state sync if it is compatible with a new sync targeting `number`.
+	fn resume_state_sync(
+		&self,
+		number: NumberFor<B>,
+		skip_proofs: bool,
+	) -> Option<StateSync<B, Client>> {
+		let sync = StateSync::resume(self.client.clone(), self.progress_store.clone()?)?;
+		let target_known = self.client.header(sync.target()).map_or(false, |h| h.is_some());
+		let recent =
+			sync.target_block_num().saturating_add(MAX_STATE_SYNC_RESUME_DISTANCE.into()) >= number;
+		(target_known && recent && sync.skip_proof() == skip_proofs).then_some(sync)
+	}
+
 	/// Returns the median seen block number.
 	fn median_seen(&self) -> Option<NumberFor<B>> {
 		let mut best_seens = self.peers.values().map(|p| p.best_number).collect::<Vec<_>>();
@@ -1942,6 +2010,7 @@ where
 							self.state_sync.as_ref().map_or(0, |s| s.progress().size / (1024 * 1024)),
 						);
 						self.state_sync = None;
+						self.clear_sync_progress();
 						self.mode = SyncMode::Full;
 						self.restart();
 					}
@@ -1956,6 +2025,7 @@ where
 							self.warp_sync.as_ref().map_or(0, |s| s.progress().total_bytes / (1024 * 1024)),
 						);
 						self.warp_sync = None;
+						self.clear_sync_progress();
 						self.mode = SyncMode::Full;
 						self.restart();
 					}
@@ -2013,6 +2083,7 @@ where
 					warn!(target: LOG_TARGET, "💔 Error importing block {hash:?}: {}", e.unwrap_err());
 					self.state_sync = None;
 					self.warp_sync = None;
+					self.clear_sync_progress();
 					self.restart();
 				},
 				Err(BlockImportError::Cancelled) => {},
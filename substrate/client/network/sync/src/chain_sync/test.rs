@@ -965,3 +965,63 @@ fn request_across_forks() {
 		assert!(sync.is_known(&block.header.parent_hash()));
 	}
 }
+
+/// Download a part of the genesis state, persisting it to `store`.
+fn download_genesis_state(client: &Arc<TestClient>, store: &Arc<dyn SyncProgressStore>) -> u64 {
+	let header = client.header(client.info().genesis_hash).unwrap().unwrap();
+	let mut state_sync = StateSync::new(client.clone(), header, None, None, false)
+		.with_progress_store(store.clone());
+	let request = state_sync.next_request();
+	let (proof, _) = client
+		.read_proof_collection(client.info().genesis_hash, request.start.as_slice(), 1)
+		.unwrap();
+	let response = StateResponse { entries: Vec::new(), proof: proof.encode() };
+	assert!(matches!(state_sync.import(response), ImportResult::Continue));
+	state_sync.progress().size
+}
+
+#[test]
+fn light_state_sync_resumes_persisted_download() {
+	let client = Arc::new(TestClientBuilder::new().build());
+	let store: Arc<dyn SyncProgressStore> = client.clone();
+	let downloaded = download_genesis_state(&client, &store);
+
+	let mode = SyncMode::LightState { skip_proofs: false, storage_chain_mode: false };
+	let mut sync = ChainSync::new(mode, client.clone(), 1, 64, None)
+		.unwrap()
+		.with_progress_store(store);
+	sync.new_peer(PeerId::random(), client.info().genesis_hash, 0);
+	sync.on_block_finalized(&client.info().genesis_hash, 0);
+
+	assert_eq!(sync.status().state_sync.unwrap().size, downloaded);
+}
+
+#[test]
+fn light_state_sync_with_other_proof_mode_restarts_download() {
+	let client = Arc::new(TestClientBuilder::new().build());
+	let store: Arc<dyn SyncProgressStore> = client.clone();
+	download_genesis_state(&client, &store);
+
+	let mode = SyncMode::LightState { skip_proofs: true, storage_chain_mode: false };
+	let mut sync = ChainSync::new(mode, client.clone(), 1, 64, None)
+		.unwrap()
+		.with_progress_store(store.clone());
+	sync.new_peer(PeerId::random(), client.info().genesis_hash, 0);
+	sync.on_block_finalized(&client.info().genesis_hash, 0);
+
+	assert_eq!(sync.status().state_sync.unwrap().size, 0);
+	assert_eq!(aux_schema::load_state_sync_progress::<Block>(&*store).unwrap(), None);
+}
+
+#[test]
+fn full_sync_discards_persisted_progress() {
+	let client = Arc::new(TestClientBuilder::new().build());
+	let store: Arc<dyn SyncProgressStore> = client.clone();
+	download_genesis_state(&client, &store);
+
+	let _sync = ChainSync::new(SyncMode::Full, client.clone(), 1, 64, None)
+		.unwrap()
+		.with_progress_store(store.clone());
+
+	assert_eq!(aux_schema::load_state_sync_progress::<Block>(&*store).unwrap(), None);
+}
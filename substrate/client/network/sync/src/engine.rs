@@ -20,6 +20,7 @@
 //! to tip and keep the blockchain up to date with network updates.
 
 use crate::{
+	aux_schema::SyncProgressStore,
 	block_announce_validator::{
 		BlockAnnounceValidationResult, BlockAnnounceValidator as BlockAnnounceValidatorStream,
 	},
@@ -367,6 +368,7 @@ where
 		state_request_protocol_name: ProtocolName,
 		warp_sync_protocol_name: Option<ProtocolName>,
 		peer_store_handle: PeerStoreHandle,
+		progress_store: Option<Arc<dyn SyncProgressStore>>,
 	) -> Result<(Self, SyncingService<B>, NonDefaultSetConfig), ClientError> {
 		let mode = net_config.network_config.sync_mode;
 		let max_parallel_downloads = net_config.network_config.max_parallel_downloads;
@@ -455,13 +457,16 @@ where
 				.expect("Genesis block exists; qed"),
 		);
 
-		let chain_sync = ChainSync::new(
+		let mut chain_sync = ChainSync::new(
 			mode,
 			client.clone(),
 			max_parallel_downloads,
 			max_blocks_per_request,
 			warp_sync_config,
 		)?;
+		if let Some(store) = progress_store {
+			chain_sync = chain_sync.with_progress_store(store);
+		}
 
 		let block_announce_protocol_name = block_announce_config.protocol_name().clone();
 		let (tx, service_rx) = tracing_unbounded("mpsc_chain_sync", 100_000);
@@ -989,7 +994,8 @@ where
 		}
 
 		if !self.default_peers_set_no_slot_connected_peers.remove(&peer_id) &&
-			info.inbound && info.info.roles.is_full()
+			info.inbound &&
+			info.info.roles.is_full()
 		{
 			match self.num_in_peers.checked_sub(1) {
 				Some(value) => {
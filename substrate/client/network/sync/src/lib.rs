@@ -30,6 +30,7 @@ mod request_metrics;
 mod schema;
 mod types;
 
+pub mod aux_schema;
 pub mod block_relay_protocol;
 pub mod block_request_handler;
 pub mod blocks;
@@ -19,11 +19,12 @@
 //! State sync support.
 
 use crate::{
+	aux_schema::{self, StateRange, StateSyncProgress, SyncProgressStore},
 	schema::v1::{StateEntry, StateRequest, StateResponse},
 	types::StateDownloadProgress,
 };
 use codec::{Decode, Encode};
-use log::debug;
+use log::{debug, warn};
 use sc_client_api::{CompactProof, ProofProvider};
 use sc_consensus::ImportedState;
 use smallvec::SmallVec;
@@ -48,6 +49,11 @@ pub struct StateSync<B: BlockT, Client> {
 	client: Arc<Client>,
 	imported_bytes: u64,
 	skip_proof: bool,
+	/// Database the verified state ranges are persisted to.
+	progress_store: Option<Arc<dyn SyncProgressStore>>,
+	/// Number of ranges persisted in the database and not held in `state`, `None` if persisting
+	/// has failed and all ranges are held in `state`.
+	persisted_ranges: Option<u32>,
 }
 
 /// Import state chunk result.
@@ -85,6 +91,176 @@ where
 			complete: false,
 			imported_bytes: 0,
 			skip_proof,
+			progress_store: None,
+			persisted_ranges: Some(0),
+		}
+	}
+
+	/// Persist verified state ranges to `store`, so that the download can be resumed after
+	/// restart.
+	///
+	/// The ranges are then only kept in the database until the download is complete.
+	pub fn with_progress_store(mut self, store: Arc<dyn SyncProgressStore>) -> Self {
+		self.progress_store = Some(store);
+		self
+	}
+
+	/// Resume the state download persisted in `store`, if any.
+	///
+	/// Stored state ranges were verified when downloaded and are not verified again. They are
+	/// only read once the download is complete. Returns `None` and discards the persisted
+	/// progress if it is unusable.
+	pub fn resume(client: Arc<Client>, store: Arc<dyn SyncProgressStore>) -> Option<Self> {
+		let progress = match aux_schema::load_state_sync_progress::<B>(&*store) {
+			Ok(Some(progress)) => progress,
+			Ok(None) => return None,
+			Err(e) => {
+				warn!(target: "sync", "Failed to load state sync progress: {}", e);
+				Self::clear_persisted(&*store);
+				return None
+			},
+		};
+
+		let StateSyncProgress {
+			target_header,
+			target_body,
+			target_justifications,
+			skip_proof,
+			ranges: persisted_ranges,
+			last_key,
+			imported_bytes,
+			..
+		} = progress;
+		let mut sync =
+			Self::new(client, target_header, target_body, target_justifications, skip_proof)
+				.with_progress_store(store);
+		sync.last_key = last_key.into();
+		sync.imported_bytes = imported_bytes;
+		sync.persisted_ranges = Some(persisted_ranges);
+
+		debug!(
+			target: "sync",
+			"Resumed state sync for #{} ({} bytes).",
+			sync.target_block_num(),
+			sync.imported_bytes,
+		);
+		Some(sync)
+	}
+
+	/// Discard the state download progress persisted in `store`.
+	pub fn clear_persisted(store: &dyn SyncProgressStore) {
+		if let Err(e) = aux_schema::clear_state_sync_progress(store) {
+			warn!(target: "sync", "Failed to clear state sync progress: {}", e);
+		}
+	}
+
+	/// Persist a verified state range, so that the download can be resumed after restart, or
+	/// keep it in memory if there is no database to persist it to.
+	fn persist(&mut self, range: StateRange) {
+		let (Some(store), Some(persisted_ranges)) =
+			(self.progress_store.clone(), self.persisted_ranges)
+		else {
+			self.insert_range(range);
+			return
+		};
+		if persisted_ranges == 0 {
+			// Drop leftovers of a previous download before storing the first range.
+			Self::clear_persisted(&*store);
+		}
+		let progress = StateSyncProgress::<B> {
+			target_header: self.target_header.clone(),
+			target_body: self.target_body.clone(),
+			target_justifications: self.target_justifications.clone(),
+			skip_proof: self.skip_proof,
+			ranges: persisted_ranges + 1,
+			last_key: self.last_key.clone().into_vec(),
+			percentage: self.progress().percentage,
+			imported_bytes: self.imported_bytes,
+		};
+		match aux_schema::write_state_range(&*store, &progress, &range) {
+			Ok(()) => self.persisted_ranges = Some(progress.ranges),
+			Err(e) => {
+				// Ranges are only consistent with the key cursor if none is missing, so stop
+				// persisting after the first failure.
+				warn!(target: "sync", "Failed to persist state sync progress: {}", e);
+				if self.load_persisted() {
+					self.insert_range(range);
+				}
+				Self::clear_persisted(&*store);
+			},
+		}
+	}
+
+	/// Move the persisted state ranges into memory and stop persisting.
+	///
+	/// Returns `false` if the ranges can't be read back, in which case the persisted progress is
+	/// discarded and the download starts over.
+	fn load_persisted(&mut self) -> bool {
+		let Some(persisted_ranges) = self.persisted_ranges.take() else { return true };
+		let Some(store) = self.progress_store.clone() else { return true };
+		if persisted_ranges == 0 {
+			return true
+		}
+
+		let ranges = match aux_schema::load_state_ranges::<B>(&*store) {
+			Ok(Some((progress, ranges))) if progress.ranges == persisted_ranges => Ok(ranges),
+			Ok(_) => Err("stored ranges don't match the download".to_string()),
+			Err(e) => Err(e.to_string()),
+		};
+		match ranges {
+			Ok(ranges) => {
+				for range in ranges {
+					self.insert_range(range);
+				}
+				true
+			},
+			Err(e) => {
+				warn!(target: "sync", "Failed to load state sync progress, restarting: {}", e);
+				Self::clear_persisted(&*store);
+				self.last_key.clear();
+				self.state.clear();
+				self.imported_bytes = 0;
+				false
+			},
+		}
+	}
+
+	/// Number of bytes of the keys of `range` counted in the download progress.
+	fn range_size(range: &StateRange) -> u64 {
+		range
+			.iter()
+			.flat_map(|(state_root, key_values)| {
+				// Child trie roots are recalculated on import.
+				key_values.iter().filter(move |(key, _)| {
+					!state_root.is_empty() || !well_known_keys::is_child_storage_key(key)
+				})
+			})
+			.map(|(key, _)| key.len() as u64)
+			.sum()
+	}
+
+	/// Insert verified key-value pairs into the accumulated state.
+	fn insert_range(&mut self, range: StateRange) {
+		for (state_root, key_values) in range {
+			let is_top = state_root.is_empty();
+			let entry = self.state.entry(state_root).or_default();
+			if entry.0.len() > 0 && entry.1.len() > 1 {
+				// Already imported child trie with same root.
+				// Warning this will not work with parallel download.
+				continue
+			}
+			let mut child_roots = Vec::new();
+			for (key, value) in key_values {
+				// Skip all child key root (will be recalculated on import).
+				if is_top && well_known_keys::is_child_storage_key(key.as_slice()) {
+					child_roots.push((value, key));
+				} else {
+					entry.0.push((key, value))
+				}
+			}
+			for (root, storage_key) in child_roots {
+				self.state.entry(root).or_default().1.push(storage_key);
+			}
 		}
 	}
 
@@ -98,7 +274,7 @@ where
 			debug!(target: "sync", "Missing proof");
 			return ImportResult::BadResponse
 		}
-		let complete = if !self.skip_proof {
+		let (range, complete) = if !self.skip_proof {
 			debug!(target: "sync", "Importing state from {} trie nodes", response.proof.len());
 			let proof_size = response.proof.len() as u64;
 			let proof = match CompactProof::decode(&mut response.proof.as_ref()) {
@@ -130,49 +306,16 @@ where
 				debug!(target: "sync", "Error updating key cursor, depth: {}", completed);
 			};
 
-			for values in values.0 {
-				let key_values = if values.state_root.is_empty() {
-					// Read child trie roots.
-					values
-						.key_values
-						.into_iter()
-						.filter(|key_value| {
-							if well_known_keys::is_child_storage_key(key_value.0.as_slice()) {
-								self.state
-									.entry(key_value.1.clone())
-									.or_default()
-									.1
-									.push(key_value.0.clone());
-								false
-							} else {
-								true
-							}
-						})
-						.collect()
-				} else {
-					values.key_values
-				};
-				let entry = self.state.entry(values.state_root).or_default();
-				if entry.0.len() > 0 && entry.1.len() > 1 {
-					// Already imported child_trie with same root.
-					// Warning this will not work with parallel download.
-				} else if entry.0.is_empty() {
-					for (key, _value) in key_values.iter() {
-						self.imported_bytes += key.len() as u64;
-					}
-
-					entry.0 = key_values;
-				} else {
-					for (key, value) in key_values {
-						self.imported_bytes += key.len() as u64;
-						entry.0.push((key, value))
-					}
-				}
-			}
+			let range = values
+				.0
+				.into_iter()
+				.map(|values| (values.state_root, values.key_values))
+				.collect::<StateRange>();
 			self.imported_bytes += proof_size;
-			complete
+			(range, complete)
 		} else {
 			let mut complete = true;
+			let mut range = StateRange::new();
 			// if the trie is a child trie and one of its parent trie is empty,
 			// the parent cursor stays valid.
 			// Empty parent trie content only happens when all the response content
@@ -197,43 +340,33 @@ where
 					}
 					complete = false;
 				}
-				let is_top = state.state_root.is_empty();
-				let entry = self.state.entry(state.state_root).or_default();
-				if entry.0.len() > 0 && entry.1.len() > 1 {
-					// Already imported child trie with same root.
-				} else {
-					let mut child_roots = Vec::new();
-					for StateEntry { key, value } in state.entries {
-						// Skip all child key root (will be recalculated on import).
-						if is_top && well_known_keys::is_child_storage_key(key.as_slice()) {
-							child_roots.push((value, key));
-						} else {
-							self.imported_bytes += key.len() as u64;
-							entry.0.push((key, value))
-						}
-					}
-					for (root, storage_key) in child_roots {
-						self.state.entry(root).or_default().1.push(storage_key);
-					}
-				}
+				let key_values =
+					state.entries.into_iter().map(|StateEntry { key, value }| (key, value));
+				range.push((state.state_root, key_values.collect()));
 			}
-			complete
+			(range, complete)
 		};
-		if complete {
-			self.complete = true;
-			ImportResult::Import(
-				self.target_block,
-				self.target_header.clone(),
-				ImportedState {
-					block: self.target_block,
-					state: std::mem::take(&mut self.state).into(),
-				},
-				self.target_body.clone(),
-				self.target_justifications.clone(),
-			)
-		} else {
-			ImportResult::Continue
+		self.imported_bytes += Self::range_size(&range);
+		if !complete {
+			self.persist(range);
+			return ImportResult::Continue
+		}
+
+		if !self.load_persisted() {
+			return ImportResult::Continue
 		}
+		self.insert_range(range);
+		self.complete = true;
+		ImportResult::Import(
+			self.target_block,
+			self.target_header.clone(),
+			ImportedState {
+				block: self.target_block,
+				state: std::mem::take(&mut self.state).into(),
+			},
+			self.target_body.clone(),
+			self.target_justifications.clone(),
+		)
 	}
 
 	/// Produce next state request.
@@ -250,6 +383,11 @@ where
 		self.complete
 	}
 
+	/// Returns whether responses are requested without proofs.
+	pub fn skip_proof(&self) -> bool {
+		self.skip_proof
+	}
+
 	/// Returns target block number.
 	pub fn target_block_num(&self) -> NumberFor<B> {
 		*self.target_header.number()
@@ -267,3 +405,143 @@ where
 		StateDownloadProgress { percentage: percent_done, size: self.imported_bytes }
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sc_client_api::HeaderBackend;
+	use substrate_test_runtime_client::{runtime::Block, TestClient};
+
+	type TestStateSync = StateSync<Block, TestClient>;
+
+	fn respond(client: &TestClient, request: &StateRequest, max_bytes: usize) -> StateResponse {
+		let block = Decode::decode(&mut request.block.as_slice()).unwrap();
+		let (proof, _) = client
+			.read_proof_collection(block, request.start.as_slice(), max_bytes)
+			.unwrap();
+		StateResponse { entries: Vec::new(), proof: proof.encode() }
+	}
+
+	fn new_sync(client: &Arc<TestClient>) -> TestStateSync {
+		let header = client.header(client.info().genesis_hash).unwrap().unwrap();
+		StateSync::new(client.clone(), header, None, None, false)
+	}
+
+	fn sorted(state: ImportedState<Block>) -> Vec<(Vec<u8>, Vec<(Vec<u8>, Vec<u8>)>)> {
+		let mut levels = state
+			.state
+			.0
+			.into_iter()
+			.map(|mut level| {
+				level.key_values.sort();
+				(level.state_root, level.key_values)
+			})
+			.collect::<Vec<_>>();
+		levels.sort();
+		levels
+	}
+
+	fn imported_state(result: ImportResult<Block>) -> ImportedState<Block> {
+		match result {
+			ImportResult::Import(_, _, state, _, _) => state,
+			_ => panic!("state download is not complete"),
+		}
+	}
+
+	#[test]
+	fn resume_continues_from_persisted_ranges() {
+		let client = Arc::new(substrate_test_runtime_client::new());
+		let store: Arc<dyn SyncProgressStore> = client.clone();
+
+		let mut full = new_sync(&client);
+		let expected =
+			imported_state(full.import(respond(&client, &full.next_request(), usize::MAX)));
+
+		let mut sync = new_sync(&client).with_progress_store(store.clone());
+		for _ in 0..2 {
+			let response = respond(&client, &sync.next_request(), 1);
+			assert!(matches!(sync.import(response), ImportResult::Continue));
+		}
+		// persisted ranges are not held in memory.
+		assert!(sync.state.is_empty());
+
+		let mut resumed = TestStateSync::resume(client.clone(), store.clone()).unwrap();
+		assert_eq!(resumed.next_request(), sync.next_request());
+		assert_eq!(resumed.progress().size, sync.progress().size);
+		assert_eq!(resumed.target(), sync.target());
+
+		let response = respond(&client, &resumed.next_request(), usize::MAX);
+		assert_eq!(sorted(imported_state(resumed.import(response))), sorted(expected));
+	}
+
+	#[test]
+	fn resume_without_progress_store_is_not_persisted() {
+		let client = Arc::new(substrate_test_runtime_client::new());
+		let store: Arc<dyn SyncProgressStore> = client.clone();
+
+		let mut sync = new_sync(&client);
+		let response = respond(&client, &sync.next_request(), 1);
+		assert!(matches!(sync.import(response), ImportResult::Continue));
+
+		assert!(TestStateSync::resume(client.clone(), store).is_none());
+	}
+
+	#[test]
+	fn new_download_discards_previous_ranges() {
+		let client = Arc::new(substrate_test_runtime_client::new());
+		let store: Arc<dyn SyncProgressStore> = client.clone();
+
+		let mut sync = new_sync(&client).with_progress_store(store.clone());
+		for _ in 0..2 {
+			let response = respond(&client, &sync.next_request(), 1);
+			assert!(matches!(sync.import(response), ImportResult::Continue));
+		}
+
+		let mut restarted = new_sync(&client).with_progress_store(store.clone());
+		let response = respond(&client, &restarted.next_request(), 1);
+		assert!(matches!(restarted.import(response), ImportResult::Continue));
+
+		let progress = aux_schema::load_state_sync_progress::<Block>(&*store).unwrap().unwrap();
+		assert_eq!(progress.ranges, 1);
+		let resumed = TestStateSync::resume(client.clone(), store).unwrap();
+		assert_eq!(resumed.next_request(), restarted.next_request());
+	}
+
+	#[test]
+	fn lost_ranges_restart_the_download() {
+		let client = Arc::new(substrate_test_runtime_client::new());
+		let store: Arc<dyn SyncProgressStore> = client.clone();
+
+		let mut sync = new_sync(&client).with_progress_store(store.clone());
+		for _ in 0..2 {
+			let response = respond(&client, &sync.next_request(), 1);
+			assert!(matches!(sync.import(response), ImportResult::Continue));
+		}
+		let first_range = (&b"sync_state_range"[..], 0u32).encode();
+		store.write(&[], &[first_range.as_slice()]).unwrap();
+
+		let mut resumed = TestStateSync::resume(client.clone(), store.clone()).unwrap();
+		let response = respond(&client, &resumed.next_request(), usize::MAX);
+		assert!(matches!(resumed.import(response), ImportResult::Continue));
+		assert_eq!(resumed.next_request(), new_sync(&client).next_request());
+		assert_eq!(aux_schema::load_state_sync_progress::<Block>(&*store).unwrap(), None);
+
+		let response = respond(&client, &resumed.next_request(), usize::MAX);
+		assert!(matches!(resumed.import(response), ImportResult::Import(..)));
+	}
+
+	#[test]
+	fn corrupted_progress_is_discarded() {
+		let client = Arc::new(substrate_test_runtime_client::new());
+		let store: Arc<dyn SyncProgressStore> = client.clone();
+
+		let mut sync = new_sync(&client).with_progress_store(store.clone());
+		let response = respond(&client, &sync.next_request(), 1);
+		assert!(matches!(sync.import(response), ImportResult::Continue));
+		store.write(&[(&b"sync_state_progress"[..], &[0xff][..])], &[]).unwrap();
+
+		assert!(TestStateSync::resume(client.clone(), store.clone()).is_none());
+		assert_eq!(aux_schema::load_state_sync_progress::<Block>(&*store).unwrap(), None);
+		assert_eq!(store.get(&(&b"sync_state_range"[..], 0u32).encode()).unwrap(), None);
+	}
+}
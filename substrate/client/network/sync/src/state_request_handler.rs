@@ -28,7 +28,7 @@ use schnellru::{ByLength, LruMap};
 
 use sc_client_api::{BlockBackend, ProofProvider};
 use sc_network::{
-	config::ProtocolId,
+	config::{BandwidthPriority, ProtocolBandwidth, ProtocolId},
 	request_responses::{IncomingRequest, OutgoingResponse, ProtocolConfig},
 };
 use sp_runtime::traits::Block as BlockT;
@@ -64,6 +64,7 @@ pub fn generate_protocol_config<Hash: AsRef<[u8]>>(
 		max_response_size: 16 * 1024 * 1024,
 		request_timeout: Duration::from_secs(40),
 		inbound_queue: None,
+		bandwidth: ProtocolBandwidth::with_priority(BandwidthPriority::Low),
 	}
 }
 
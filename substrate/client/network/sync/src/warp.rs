@@ -21,12 +21,13 @@
 pub use sp_consensus_grandpa::{AuthorityList, SetId};
 
 use crate::{
+	aux_schema::{self, SyncProgressStore, WarpProofProgress},
 	schema::v1::{StateRequest, StateResponse},
 	state::{ImportResult, StateSync},
 };
 use codec::{Decode, Encode};
 use futures::channel::oneshot;
-use log::error;
+use log::{error, warn};
 use sc_client_api::ProofProvider;
 use sc_network_common::sync::message::{
 	BlockAttributes, BlockData, BlockRequest, Direction, FromBlock,
@@ -74,6 +75,17 @@ pub trait WarpSyncProvider<Block: BlockT>: Send + Sync {
 	/// Get current list of authorities. This is supposed to be genesis authorities when starting
 	/// sync.
 	fn current_authorities(&self) -> AuthorityList;
+	/// Returns the hash of the last block covered by a proof generated by this provider and
+	/// whether the proof reaches the latest finalized block. The proof is not verified.
+	///
+	/// Allows assembling a complete proof out of several [`Self::generate`] calls. Not supported
+	/// by default.
+	fn proof_end(
+		&self,
+		_proof: &EncodedProof,
+	) -> Result<(Block::Hash, bool), Box<dyn std::error::Error + Send + Sync>> {
+		Err("Warp sync provider does not support inspecting proofs".into())
+	}
 }
 
 /// Reported warp sync phase.
@@ -187,37 +199,147 @@ pub enum TargetBlockImportResult {
 }
 
 /// Warp sync state machine. Accumulates warp proofs and state.
+///
+/// If a progress store is given, verified warp proofs and state ranges are persisted to it, and a
+/// new instance resumes from the last persisted checkpoint.
 pub struct WarpSync<B: BlockT, Client> {
 	phase: Phase<B, Client>,
 	client: Arc<Client>,
 	total_proof_bytes: u64,
+	/// Database the progress is persisted to.
+	progress_store: Option<Arc<dyn SyncProgressStore>>,
+	/// Warp proof checkpoint to fall back to if the resumed target block becomes obsolete.
+	checkpoint: Option<WarpProofProgress<B>>,
+	/// Warp sync provider, if warp proofs are downloaded.
+	warp_sync_provider: Option<Arc<dyn WarpSyncProvider<B>>>,
+	/// State download resumed before the target block is set externally.
+	resumed_state: Option<StateSync<B, Client>>,
 }
 
 impl<B, Client> WarpSync<B, Client>
 where
 	B: BlockT,
-	Client: HeaderBackend<B> + ProofProvider<B> + 'static,
+	Client: HeaderBackend<B> + ProofProvider<B> + Send + Sync + 'static,
 {
 	/// Create a new instance. When passing a warp sync provider we will be checking for proof and
 	/// authorities. Alternatively we can pass a target block when we want to skip downloading
 	/// proofs, in this case we will continue polling until the target block is known.
-	pub fn new(client: Arc<Client>, warp_sync_config: WarpSyncConfig<B>) -> Self {
-		let last_hash = client.hash(Zero::zero()).unwrap().expect("Genesis header always exists");
+	///
+	/// Progress persisted in `progress_store` by a previous instance is resumed.
+	pub fn new(
+		client: Arc<Client>,
+		warp_sync_config: WarpSyncConfig<B>,
+		progress_store: Option<Arc<dyn SyncProgressStore>>,
+	) -> Self {
+		let checkpoint = progress_store.as_ref().and_then(|store| {
+			aux_schema::load_warp_proof_progress::<B>(&**store).unwrap_or_else(|e| {
+				warn!(target: LOG_TARGET, "Failed to load warp sync progress: {}", e);
+				None
+			})
+		});
+		let resumed_state = progress_store
+			.clone()
+			.and_then(|store| StateSync::resume(client.clone(), store))
+			.filter(|sync| !sync.skip_proof());
 		match warp_sync_config {
 			WarpSyncConfig::WithProvider(warp_sync_provider) => {
-				let phase = Phase::WarpProof {
-					set_id: 0,
-					authorities: warp_sync_provider.current_authorities(),
-					last_hash,
-					warp_sync_provider: warp_sync_provider.clone(),
+				let target = checkpoint.as_ref().and_then(|c| c.target_header.clone());
+				let phase = match (target, resumed_state) {
+					(Some(target), Some(sync)) if sync.target() == target.hash() => {
+						log::info!(
+							target: LOG_TARGET,
+							"Resuming warp sync state download at #{}.",
+							sync.target_block_num(),
+						);
+						Phase::State(sync)
+					},
+					(target, _) => {
+						if let Some(store) = &progress_store {
+							StateSync::<B, Client>::clear_persisted(&**store);
+						}
+						match target {
+							Some(target) => Phase::TargetBlock(target),
+							None => Self::warp_proof_phase(
+								&*client,
+								&warp_sync_provider,
+								checkpoint.as_ref(),
+							),
+						}
+					},
 				};
-				Self { client, phase, total_proof_bytes: 0 }
+				Self {
+					client,
+					phase,
+					total_proof_bytes: checkpoint.as_ref().map_or(0, |c| c.total_proof_bytes),
+					progress_store,
+					checkpoint,
+					warp_sync_provider: Some(warp_sync_provider),
+					resumed_state: None,
+				}
+			},
+			WarpSyncConfig::WaitForTarget => Self {
+				client,
+				phase: Phase::PendingTargetBlock,
+				total_proof_bytes: 0,
+				progress_store,
+				checkpoint: None,
+				warp_sync_provider: None,
+				resumed_state,
+			},
+		}
+	}
+
+	/// Warp proof phase starting at `checkpoint`, or at genesis if there is none.
+	fn warp_proof_phase(
+		client: &Client,
+		warp_sync_provider: &Arc<dyn WarpSyncProvider<B>>,
+		checkpoint: Option<&WarpProofProgress<B>>,
+	) -> Phase<B, Client> {
+		match checkpoint {
+			Some(checkpoint) => {
+				log::info!(
+					target: LOG_TARGET,
+					"Resuming warp proof download at set_id={}.",
+					checkpoint.set_id,
+				);
+				Phase::WarpProof {
+					set_id: checkpoint.set_id,
+					authorities: checkpoint.authorities.clone(),
+					last_hash: checkpoint.last_hash,
+					warp_sync_provider: warp_sync_provider.clone(),
+				}
+			},
+			None => Phase::WarpProof {
+				set_id: 0,
+				authorities: warp_sync_provider.current_authorities(),
+				last_hash: client
+					.hash(Zero::zero())
+					.unwrap()
+					.expect("Genesis header always exists"),
+				warp_sync_provider: warp_sync_provider.clone(),
 			},
-			WarpSyncConfig::WaitForTarget =>
-				Self { client, phase: Phase::PendingTargetBlock, total_proof_bytes: 0 },
 		}
 	}
 
+	/// Discard a resumed target block and its state, and continue downloading warp proofs from the
+	/// last checkpoint.
+	///
+	/// Used when the resumed target block is too old to be served by peers.
+	pub fn discard_resumed_target(&mut self) {
+		let Some(warp_sync_provider) = self.warp_sync_provider.clone() else { return };
+		if let Phase::WarpProof { .. } = self.phase {
+			return
+		}
+
+		self.clear_persisted_state();
+		if let Some(mut checkpoint) = self.checkpoint.take() {
+			checkpoint.target_header = None;
+			self.persist_checkpoint(checkpoint);
+		}
+		self.phase =
+			Self::warp_proof_phase(&*self.client, &warp_sync_provider, self.checkpoint.as_ref());
+	}
+
 	/// Set target block externally in case we skip warp proof downloading.
 	pub fn set_target_block(&mut self, header: B::Header) {
 		let Phase::PendingTargetBlock = self.phase else {
@@ -229,7 +351,37 @@ where
 			return
 		};
 
-		self.phase = Phase::TargetBlock(header);
+		self.phase = match self.resumed_state.take() {
+			Some(sync) if sync.target() == header.hash() => {
+				log::info!(
+					target: LOG_TARGET,
+					"Resuming warp sync state download at #{}.",
+					sync.target_block_num(),
+				);
+				Phase::State(sync)
+			},
+			_ => {
+				self.clear_persisted_state();
+				Phase::TargetBlock(header)
+			},
+		};
+	}
+
+	/// Persist the warp proof checkpoint.
+	fn persist_checkpoint(&mut self, checkpoint: WarpProofProgress<B>) {
+		if let Some(store) = &self.progress_store {
+			if let Err(e) = aux_schema::write_warp_proof_progress(&**store, &checkpoint) {
+				warn!(target: LOG_TARGET, "Failed to persist warp sync progress: {}", e);
+			}
+		}
+		self.checkpoint = Some(checkpoint);
+	}
+
+	/// Discard the persisted state download progress.
+	fn clear_persisted_state(&self) {
+		if let Some(store) = &self.progress_store {
+			StateSync::<B, Client>::clear_persisted(&**store);
+		}
 	}
 
 	///  Validate and import a state response.
@@ -262,12 +414,28 @@ where
 						*authorities = new_authorities;
 						*last_hash = new_last_hash;
 						self.total_proof_bytes += response.0.len() as u64;
+						let checkpoint = WarpProofProgress {
+							set_id: new_set_id,
+							authorities: authorities.clone(),
+							last_hash: new_last_hash,
+							total_proof_bytes: self.total_proof_bytes,
+							target_header: None,
+						};
+						self.persist_checkpoint(checkpoint);
 						WarpProofImportResult::Success
 					},
 					Ok(VerificationResult::Complete(new_set_id, _, header)) => {
 						log::debug!(target: "sync", "Verified complete proof, set_id={:?}", new_set_id);
 						self.total_proof_bytes += response.0.len() as u64;
+						let checkpoint = WarpProofProgress {
+							set_id: *set_id,
+							authorities: authorities.clone(),
+							last_hash: *last_hash,
+							total_proof_bytes: self.total_proof_bytes,
+							target_header: Some(header.clone()),
+						};
 						self.phase = Phase::TargetBlock(header);
+						self.persist_checkpoint(checkpoint);
 						WarpProofImportResult::Success
 					},
 				},
@@ -285,13 +453,16 @@ where
 				if let Some(block_header) = &block.header {
 					if block_header == header {
 						if block.body.is_some() {
-							let state_sync = StateSync::new(
+							let mut state_sync = StateSync::new(
 								self.client.clone(),
 								header.clone(),
 								block.body,
 								block.justifications,
 								false,
 							);
+							if let Some(store) = &self.progress_store {
+								state_sync = state_sync.with_progress_store(store.clone());
+							}
 							self.phase = Phase::State(state_sync);
 							TargetBlockImportResult::Success
 						} else {
@@ -340,7 +511,8 @@ where
 				let request = BlockRequest::<B> {
 					id: 0,
 					fields: BlockAttributes::HEADER |
-						BlockAttributes::BODY | BlockAttributes::JUSTIFICATION,
+						BlockAttributes::BODY |
+						BlockAttributes::JUSTIFICATION,
 					from: FromBlock::Hash(header.hash()),
 					direction: Direction::Ascending,
 					max: Some(1),
@@ -403,3 +575,168 @@ where
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_runtime::traits::Header as _;
+	use substrate_test_runtime_client::{
+		runtime::{Block, Hash, Header},
+		TestClient,
+	};
+
+	type TestWarpSync = WarpSync<Block, TestClient>;
+
+	/// Warp sync provider accepting proofs encoded as `(set_id, last_hash, target)`.
+	struct TestProvider;
+
+	impl WarpSyncProvider<Block> for TestProvider {
+		fn generate(
+			&self,
+			_start: Hash,
+		) -> Result<EncodedProof, Box<dyn std::error::Error + Send + Sync>> {
+			Err("Proofs are encoded by the test".into())
+		}
+
+		fn verify(
+			&self,
+			proof: &EncodedProof,
+			_set_id: SetId,
+			_authorities: AuthorityList,
+		) -> Result<VerificationResult<Block>, Box<dyn std::error::Error + Send + Sync>> {
+			let (set_id, last_hash, target) =
+				<(SetId, Hash, Option<Header>)>::decode(&mut proof.0.as_slice())?;
+			Ok(match target {
+				Some(header) => VerificationResult::Complete(set_id, Vec::new(), header),
+				None => VerificationResult::Partial(set_id, Vec::new(), last_hash),
+			})
+		}
+
+		fn current_authorities(&self) -> AuthorityList {
+			Vec::new()
+		}
+	}
+
+	fn proof(set_id: SetId, last_hash: Hash, target: Option<Header>) -> EncodedProof {
+		EncodedProof((set_id, last_hash, target).encode())
+	}
+
+	fn state_response(client: &TestClient, request: &StateRequest) -> StateResponse {
+		let block = Decode::decode(&mut request.block.as_slice()).unwrap();
+		let (proof, _) = client.read_proof_collection(block, request.start.as_slice(), 1).unwrap();
+		StateResponse { entries: Vec::new(), proof: proof.encode() }
+	}
+
+	fn genesis_header(client: &TestClient) -> Header {
+		client.header(client.info().genesis_hash).unwrap().unwrap()
+	}
+
+	fn target_block(header: Header) -> BlockData<Block> {
+		BlockData::<Block> {
+			hash: header.hash(),
+			header: Some(header),
+			body: Some(Vec::new()),
+			indexed_body: None,
+			receipt: None,
+			message_queue: None,
+			justification: None,
+			justifications: None,
+		}
+	}
+
+	fn new_warp_sync(client: &Arc<TestClient>, store: &Arc<dyn SyncProgressStore>) -> TestWarpSync {
+		WarpSync::new(
+			client.clone(),
+			WarpSyncConfig::WithProvider(Arc::new(TestProvider)),
+			Some(store.clone()),
+		)
+	}
+
+	/// Download the warp proofs up to the genesis block and a part of its state.
+	fn download_state(client: &Arc<TestClient>, sync: &mut TestWarpSync) {
+		let header = genesis_header(client);
+		let complete = proof(2, header.hash(), Some(header.clone()));
+		assert!(matches!(sync.import_warp_proof(complete), WarpProofImportResult::Success));
+		assert!(matches!(
+			sync.import_target_block(target_block(header)),
+			TargetBlockImportResult::Success
+		));
+		let response = state_response(client, &sync.next_state_request().unwrap());
+		assert!(matches!(sync.import_state(response), ImportResult::Continue));
+	}
+
+	#[test]
+	fn warp_proof_download_is_resumed() {
+		let client = Arc::new(substrate_test_runtime_client::new());
+		let store: Arc<dyn SyncProgressStore> = client.clone();
+		let last_hash = Hash::repeat_byte(1);
+
+		let mut sync = new_warp_sync(&client, &store);
+		let partial = proof(1, last_hash, None);
+		let proof_bytes = partial.0.len() as u64;
+		assert!(matches!(sync.import_warp_proof(partial), WarpProofImportResult::Success));
+
+		let resumed = new_warp_sync(&client, &store);
+		assert_eq!(resumed.next_warp_proof_request().unwrap().begin, last_hash);
+		assert_eq!(resumed.progress().total_bytes, proof_bytes);
+	}
+
+	#[test]
+	fn state_download_is_resumed() {
+		let client = Arc::new(substrate_test_runtime_client::new());
+		let store: Arc<dyn SyncProgressStore> = client.clone();
+
+		let mut sync = new_warp_sync(&client, &store);
+		download_state(&client, &mut sync);
+
+		let resumed = new_warp_sync(&client, &store);
+		assert_eq!(resumed.target_block_hash(), Some(client.info().genesis_hash));
+		assert_eq!(resumed.next_state_request(), sync.next_state_request());
+		assert_eq!(resumed.progress(), sync.progress());
+	}
+
+	#[test]
+	fn discarded_target_resumes_warp_proofs() {
+		let client = Arc::new(substrate_test_runtime_client::new());
+		let store: Arc<dyn SyncProgressStore> = client.clone();
+		let genesis_hash = client.info().genesis_hash;
+
+		let mut sync = new_warp_sync(&client, &store);
+		download_state(&client, &mut sync);
+
+		let mut resumed = new_warp_sync(&client, &store);
+		resumed.discard_resumed_target();
+		assert_eq!(resumed.target_block_number(), None);
+		assert_eq!(resumed.next_warp_proof_request().unwrap().begin, genesis_hash);
+		assert_eq!(aux_schema::load_state_sync_progress::<Block>(&*store).unwrap(), None);
+
+		// The discarded target is not resumed again.
+		let restarted = new_warp_sync(&client, &store);
+		assert_eq!(restarted.target_block_number(), None);
+		assert_eq!(restarted.next_warp_proof_request().unwrap().begin, genesis_hash);
+	}
+
+	#[test]
+	fn state_download_is_resumed_when_target_is_set() {
+		let client = Arc::new(substrate_test_runtime_client::new());
+		let store: Arc<dyn SyncProgressStore> = client.clone();
+		let header = genesis_header(&client);
+
+		let mut sync = new_warp_sync(&client, &store);
+		download_state(&client, &mut sync);
+
+		let mut resumed =
+			WarpSync::new(client.clone(), WarpSyncConfig::WaitForTarget, Some(store.clone()));
+		assert_eq!(resumed.target_block_number(), None);
+		resumed.set_target_block(header.clone());
+		assert_eq!(resumed.next_state_request(), sync.next_state_request());
+
+		// A different target discards the resumed state.
+		let mut other = WarpSync::new(client.clone(), WarpSyncConfig::WaitForTarget, Some(store));
+		let mut other_header = header;
+		other_header.number = 1;
+		other.set_target_block(other_header.clone());
+		assert_eq!(other.next_target_block_request().unwrap().0, 1);
+		assert_eq!(other.next_state_request(), None);
+	}
+}
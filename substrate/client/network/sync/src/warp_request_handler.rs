@@ -22,7 +22,7 @@ use log::debug;
 
 use crate::warp::{EncodedProof, WarpProofRequest, WarpSyncProvider};
 use sc_network::{
-	config::ProtocolId,
+	config::{BandwidthPriority, ProtocolBandwidth, ProtocolId},
 	request_responses::{
 		IncomingRequest, OutgoingResponse, ProtocolConfig as RequestResponseConfig,
 	},
@@ -51,6 +51,7 @@ pub fn generate_request_response_config<Hash: AsRef<[u8]>>(
 		max_response_size: MAX_RESPONSE_SIZE,
 		request_timeout: Duration::from_secs(10),
 		inbound_queue: None,
+		bandwidth: ProtocolBandwidth::with_priority(BandwidthPriority::Low),
 	}
 }
 
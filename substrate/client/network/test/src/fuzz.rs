@@ -161,6 +161,7 @@ async fn test_once() {
 			in_peers: Uniform::new_inclusive(0, 25).sample(&mut rng),
 			out_peers: Uniform::new_inclusive(0, 25).sample(&mut rng),
 			reserved_only: Uniform::new_inclusive(0, 10).sample(&mut rng) == 0,
+			ip_limits: Default::default(),
 		},
 		to_notifications,
 		Box::new(peer_store_handle.clone()),
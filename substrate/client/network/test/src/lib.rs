@@ -26,6 +26,8 @@ mod service;
 #[cfg(test)]
 mod sync;
 
+pub mod simulation;
+
 use std::{
 	collections::HashMap,
 	pin::Pin,
@@ -72,6 +74,7 @@ use sc_network_sync::{
 	warp_request_handler,
 };
 use sc_service::client::Client;
+use simulation::SimulatedNetwork;
 use sp_blockchain::{
 	Backend as BlockchainBackend, HeaderBackend, Info as BlockchainInfo, Result as ClientResult,
 };
@@ -93,6 +96,10 @@ pub use substrate_test_runtime_client::{
 };
 use tokio::time::timeout;
 
+/// Time the network is polled for between two checks of its state, when the peers are connected
+/// through a [`SimulatedNetwork`].
+const SIMULATION_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
 /// A Verifier that accepts all blocks and passes them on with the configured
 /// finality to be imported.
 #[derive(Clone)]
@@ -670,6 +677,14 @@ impl<B: BlockT> WarpSyncProvider<B> for TestWarpSyncProvider<B> {
 	fn current_authorities(&self) -> AuthorityList {
 		Default::default()
 	}
+	fn proof_end(
+		&self,
+		proof: &EncodedProof,
+	) -> Result<(B::Hash, bool), Box<dyn std::error::Error + Send + Sync>> {
+		let EncodedProof(encoded) = proof;
+		let header = B::Header::decode(&mut encoded.as_slice())?;
+		Ok((header.hash(), true))
+	}
 }
 
 /// Configuration for a full peer.
@@ -729,6 +744,11 @@ pub trait TestNetFactory: Default + Sized + Send {
 		Self::PeerData,
 	);
 
+	/// Links between the peers, if they are connected through a simulated network.
+	fn simulated_network(&self) -> Option<&SimulatedNetwork> {
+		None
+	}
+
 	/// Create new test network with this many peers.
 	fn new(n: usize) -> Self {
 		trace!(target: "test_network", "Creating test network");
@@ -780,14 +800,36 @@ pub trait TestNetFactory: Default + Sized + Send {
 			None,
 		));
 
-		let listen_addr = build_multiaddr![Memory(rand::random::<u64>())];
+		let simulated_network = self.simulated_network().cloned();
+		let listen_addr = simulated_network.as_ref().map_or_else(
+			|| build_multiaddr![Memory(rand::random::<u64>())],
+			|network| network.new_listen_address(),
+		);
+		let index = self.peers().len();
+		if let Some(simulated_network) = &simulated_network {
+			assert_eq!(simulated_network.add_peer(listen_addr.clone()), index);
+		}
+
+		// Address through which the peer at `from` reaches the one at `to`.
+		let address = |from: usize, to: usize, listen_addr: &Multiaddr| {
+			simulated_network
+				.as_ref()
+				.map_or_else(|| listen_addr.clone(), |network| network.address(from, to))
+		};
 
+		// Peers of a simulated network derive their identity from its seed.
+		let node_key = simulated_network
+			.as_ref()
+			.map_or_else(Default::default, |network| network.new_node_key());
 		let mut network_config =
-			NetworkConfiguration::new("test-node", "test-client", Default::default(), None);
+			NetworkConfiguration::new("test-node", "test-client", node_key, None);
 		network_config.sync_mode = config.sync_mode;
 		network_config.transport = TransportConfig::MemoryOnly;
 		network_config.listen_addresses = vec![listen_addr.clone()];
-		network_config.allow_non_globals_in_dht = true;
+		// Peers of a simulated network must only learn about each other through the relays, and
+		// not from the listen addresses reported by the DHT.
+		network_config.allow_non_globals_in_dht = simulated_network.is_none();
+		network_config.enable_dht_random_walk = simulated_network.is_none();
 
 		let (notif_configs, notif_handles): (Vec<_>, Vec<_>) = config
 			.notifications_protocols
@@ -810,7 +852,7 @@ pub trait TestNetFactory: Default + Sized + Send {
 				.iter()
 				.map(|v| {
 					let peer_id = self.peer(*v).network_service().local_peer_id();
-					let multiaddr = self.peer(*v).listen_addr.clone();
+					let multiaddr = address(index, *v, &self.peer(*v).listen_addr);
 					MultiaddrWithPeerId { peer_id, multiaddr }
 				})
 				.collect();
@@ -902,6 +944,7 @@ pub trait TestNetFactory: Default + Sized + Send {
 				state_request_protocol_config.name.clone(),
 				Some(warp_protocol_config.name.clone()),
 				peer_store_handle.clone(),
+				None,
 			)
 			.unwrap();
 		let sync_service_import_queue = Box::new(sync_service.clone());
@@ -955,10 +998,11 @@ pub trait TestNetFactory: Default + Sized + Send {
 			engine.run().await;
 		});
 
+		let known_addresses =
+			(0..index).map(|peer| address(peer, index, &listen_addr)).collect::<Vec<_>>();
 		self.mut_peers(move |peers| {
-			for peer in peers.iter_mut() {
-				peer.network
-					.add_known_address(network.service().local_peer_id(), listen_addr.clone());
+			for (peer, address) in peers.iter_mut().zip(known_addresses) {
+				peer.network.add_known_address(network.service().local_peer_id(), address);
 			}
 
 			let imported_blocks_stream = Box::pin(client.import_notification_stream().fuse());
@@ -1031,11 +1075,7 @@ pub trait TestNetFactory: Default + Sized + Send {
 	async fn run_until_sync(&mut self) {
 		timeout(Duration::from_secs(10 * 60), async {
 			loop {
-				futures::future::poll_fn::<(), _>(|cx| {
-					self.poll(cx);
-					Poll::Ready(())
-				})
-				.await;
+				self.poll_once().await;
 
 				if self.is_in_sync().await {
 					break
@@ -1051,11 +1091,7 @@ pub trait TestNetFactory: Default + Sized + Send {
 	/// Calls `poll_until_idle` repeatedly with the runtime passed as parameter.
 	async fn run_until_idle(&mut self) {
 		loop {
-			futures::future::poll_fn::<(), _>(|cx| {
-				self.poll(cx);
-				Poll::Ready(())
-			})
-			.await;
+			self.poll_once().await;
 
 			if self.is_idle().await {
 				break
@@ -1074,11 +1110,7 @@ pub trait TestNetFactory: Default + Sized + Send {
 				if sync_service.status().await.unwrap().num_connected_peers as usize !=
 					num_peers - 1
 				{
-					futures::future::poll_fn::<(), _>(|cx| {
-						self.poll(cx);
-						Poll::Ready(())
-					})
-					.await;
+					self.poll_once().await;
 					continue 'outer
 				}
 			}
@@ -1087,6 +1119,35 @@ pub trait TestNetFactory: Default + Sized + Send {
 		}
 	}
 
+	/// Run the network for `duration`, measured with the tokio clock.
+	async fn run_for(&mut self, duration: Duration) {
+		let _ = timeout(
+			duration,
+			futures::future::poll_fn::<(), _>(|cx| {
+				self.poll(cx);
+				Poll::Pending
+			}),
+		)
+		.await;
+	}
+
+	/// Polls the testnet once, processing all the pending actions.
+	///
+	/// If the peers are connected through a [`SimulatedNetwork`], the testnet is polled for
+	/// [`SIMULATION_POLL_INTERVAL`] instead, so that the tokio clock, which may be virtual,
+	/// moves forward between two checks of the state of the network.
+	async fn poll_once(&mut self) {
+		if self.simulated_network().is_some() {
+			self.run_for(SIMULATION_POLL_INTERVAL).await;
+		} else {
+			futures::future::poll_fn::<(), _>(|cx| {
+				self.poll(cx);
+				Poll::Ready(())
+			})
+			.await;
+		}
+	}
+
 	/// Polls the testnet. Processes all the pending actions.
 	fn poll(&mut self, cx: &mut FutureContext) {
 		self.mut_peers(|peers| {
@@ -1127,6 +1188,20 @@ pub trait TestNetFactory: Default + Sized + Send {
 #[derive(Default)]
 pub struct TestNet {
 	peers: Vec<Peer<(), PeersClient>>,
+	simulated_network: Option<SimulatedNetwork>,
+}
+
+impl TestNet {
+	/// Create new test network with this many peers, connected through a [`SimulatedNetwork`]
+	/// whose randomness is derived from `seed`.
+	pub fn simulated(n: usize, seed: u64) -> Self {
+		let mut net =
+			Self { peers: Vec::new(), simulated_network: Some(SimulatedNetwork::new(seed)) };
+		for _ in 0..n {
+			net.add_full_peer();
+		}
+		net
+	}
 }
 
 impl TestNetFactory for TestNet {
@@ -1164,6 +1239,10 @@ impl TestNetFactory for TestNet {
 	fn mut_peers<F: FnOnce(&mut Vec<Peer<(), Self::BlockImport>>)>(&mut self, closure: F) {
 		closure(&mut self.peers);
 	}
+
+	fn simulated_network(&self) -> Option<&SimulatedNetwork> {
+		self.simulated_network.as_ref()
+	}
 }
 
 pub struct ForceFinalized(PeersClient);
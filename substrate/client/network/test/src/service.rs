@@ -205,6 +205,7 @@ impl TestNetworkBuilder {
 			state_request_protocol_config.name.clone(),
 			None,
 			peer_store_handle.clone(),
+			None,
 		)
 		.unwrap();
 		let mut link = self.link.unwrap_or(Box::new(chain_sync_service.clone()));
@@ -495,6 +496,99 @@ async fn lots_of_incoming_peers_works() {
 	future::join_all(background_tasks_to_wait).await;
 }
 
+/// Starts a node listening on the loopback address with `ip_connection_limits`, connects `dialers`
+/// nodes to it and returns the number of them it opened a substream with.
+async fn connect_from_loopback(
+	ip_connection_limits: config::IpConnectionLimits,
+	dialers: usize,
+) -> usize {
+	let (main_node, handle) = TestNetworkBuilder::new()
+		.with_config(config::NetworkConfiguration {
+			ip_connection_limits,
+			..config::NetworkConfiguration::new_local()
+		})
+		.with_set_config(config::SetConfig { in_peers: u32::MAX, ..Default::default() })
+		.build();
+	let mut handle = handle.unwrap();
+	let (main_node, _) = main_node.start_network();
+	let main_node_peer_id = main_node.local_peer_id();
+
+	let listen_addr = loop {
+		if let Some(addr) = main_node.listen_addresses().into_iter().next() {
+			break addr
+		}
+		tokio::time::sleep(Duration::from_millis(50)).await;
+	};
+
+	// Every dialer connects from 127.0.0.1, so they all share the same IP address and subnet.
+	let mut dialing_nodes = Vec::new();
+	for _ in 0..dialers {
+		let (dialing_node, dialing_handle) = TestNetworkBuilder::new()
+			.with_config(config::NetworkConfiguration {
+				listen_addresses: Vec::new(),
+				..config::NetworkConfiguration::new_local()
+			})
+			.with_set_config(config::SetConfig {
+				reserved_nodes: vec![MultiaddrWithPeerId {
+					multiaddr: listen_addr.clone(),
+					peer_id: main_node_peer_id,
+				}],
+				..Default::default()
+			})
+			.build();
+		let mut dialing_handle = dialing_handle.unwrap();
+		dialing_nodes.push(dialing_node.start_network().0);
+
+		tokio::spawn(async move {
+			while let Some(event) = dialing_handle.next_event().await {
+				if let NotificationEvent::ValidateInboundSubstream { result_tx, .. } = event {
+					let _ = result_tx.send(ValidationResult::Accept);
+				}
+			}
+		});
+	}
+
+	// Count the substreams opened until no new one shows up for a while.
+	let mut opened = 0;
+	while let Ok(Some(event)) =
+		tokio::time::timeout(Duration::from_secs(5), handle.next_event()).await
+	{
+		match event {
+			NotificationEvent::ValidateInboundSubstream { result_tx, .. } => {
+				let _ = result_tx.send(ValidationResult::Accept);
+			},
+			NotificationEvent::NotificationStreamOpened { .. } => opened += 1,
+			_ => {},
+		}
+	}
+
+	opened
+}
+
+#[tokio::test]
+async fn inbound_peers_limited_per_ip() {
+	sp_tracing::try_init_simple();
+
+	let limits = config::IpConnectionLimits { max_inbound_per_ip: Some(2), ..Default::default() };
+	assert_eq!(connect_from_loopback(limits, 6).await, 2);
+}
+
+#[tokio::test]
+async fn inbound_peers_limited_per_subnet() {
+	sp_tracing::try_init_simple();
+
+	let limits =
+		config::IpConnectionLimits { max_inbound_per_subnet: Some(3), ..Default::default() };
+	assert_eq!(connect_from_loopback(limits, 6).await, 3);
+}
+
+#[tokio::test]
+async fn inbound_peers_not_limited_by_default() {
+	sp_tracing::try_init_simple();
+
+	assert_eq!(connect_from_loopback(Default::default(), 6).await, 6);
+}
+
 #[tokio::test]
 async fn notifications_back_pressure() {
 	// Node 1 floods node 2 with notifications. Random sleeps are done on node 2 to simulate the
@@ -0,0 +1,669 @@
+// This file is part of Substrate.
+
+// Copyright (C) Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Simulated network conditions between the peers of a test network.
+//!
+//! The peers of a [`TestNetFactory`](crate::TestNetFactory) returning a [`SimulatedNetwork`] don't
+//! dial each other directly. Every peer reaches every other peer through a relay listening on a
+//! memory address of its own, which forwards the bytes of the real connections and applies the
+//! network conditions set by the test:
+//!
+//! - links between peers have a configurable latency, jitter, loss and bandwidth, see
+//!   [`LinkConfig`];
+//! - the network can be partitioned and healed, and peers can go offline and come back. Relays
+//!   close the connections that can't be established anymore, losing the data in flight, and refuse
+//!   new ones until the peers can reach each other again.
+//!
+//! Delays are measured with the tokio clock, so tests running with
+//! `#[tokio::test(start_paused = true)]` run in virtual time: the clock only moves forward when
+//! all the tasks are idle, jumping straight to the next timer. A scenario spanning minutes of
+//! network time, including the ticks of the syncing engine, runs in a fraction of it. Changes to
+//! the network can be planned in virtual time with [`SimulatedNetwork::schedule`]:
+//!
+//! ```ignore
+//! let mut net = TestNet::simulated(3, seed);
+//! let network = net.simulated_network().unwrap().clone();
+//! network.set_default_link(LinkConfig { latency: Duration::from_millis(100), ..Default::default() });
+//! network.partition(vec![vec![0, 1], vec![2]]);
+//! net.peer(2).push_blocks(10, false);
+//! network.schedule(Duration::from_secs(30), Action::Heal);
+//! net.run_until_sync().await;
+//! ```
+//!
+//! Everything the simulation decides is derived from the seed of the network: the listen
+//! addresses and node keys of the peers, hence their identities, the addresses of the relays, and
+//! the jitter and losses of every link. Each link draws from its own RNG, so the conditions of a
+//! link only depend on the traffic it carries and not on the order in which the tasks of the
+//! other links happen to run. Running a scenario on the single-threaded runtime of
+//! `#[tokio::test(start_paused = true)]` with the same seed therefore replays it under the same
+//! network conditions, which is how a failing seed is reproduced.
+
+use futures::{
+	channel::mpsc,
+	future::{self, Either},
+	io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+	stream::{self, BoxStream, StreamExt},
+};
+use libp2p::{
+	build_multiaddr,
+	core::transport::{memory::Channel, MemoryTransport, Transport, TransportEvent},
+	Multiaddr,
+};
+use log::trace;
+use parking_lot::Mutex;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use sc_network::config::{ed25519, NodeKeyConfig, Secret};
+use std::{collections::HashMap, pin::Pin, sync::Arc, task::Poll, time::Duration};
+use tokio::{sync::watch, time::Instant};
+
+const LOG_TARGET: &str = "test_network::simulation";
+
+/// Size of the chunks read from the connections.
+const READ_BUFFER_SIZE: usize = 16 * 1024;
+
+/// Minimum time before lost data is retransmitted, as for TCP on Linux.
+const MIN_RETRANSMISSION_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Index of a peer in a [`SimulatedNetwork`], in the order the peers were added.
+pub type PeerIndex = usize;
+
+/// Properties of a directed link between two peers.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LinkConfig {
+	/// Time it takes for data to travel the link once sent.
+	pub latency: Duration,
+	/// Maximum random delay added to the latency.
+	///
+	/// Data is still delivered in the order it was sent, as it would be on a stream.
+	pub jitter: Duration,
+	/// Probability, in `[0, 1)`, that a chunk of data sent over the link is lost.
+	///
+	/// Connections are reliable streams, so lost data is retransmitted after a timeout of a round
+	/// trip, but at least [`MIN_RETRANSMISSION_TIMEOUT`], delaying the data sent after it too.
+	pub loss: f64,
+	/// Bandwidth of the link in bytes per second, or `None` if unlimited.
+	pub bandwidth: Option<u64>,
+}
+
+/// Change to a [`SimulatedNetwork`], see [`SimulatedNetwork::schedule`].
+#[derive(Debug, Clone)]
+pub enum Action {
+	/// Split the network into groups of peers that can only reach peers of the same group.
+	///
+	/// Peers not listed in any group form a group of their own.
+	Partition(Vec<Vec<PeerIndex>>),
+	/// Reconnect all the groups of a partition.
+	Heal,
+	/// Disconnect a peer from all the others.
+	Disconnect(PeerIndex),
+	/// Reconnect a peer previously disconnected.
+	Reconnect(PeerIndex),
+	/// Change the properties of the links between two peers, in both directions.
+	SetLink(PeerIndex, PeerIndex, LinkConfig),
+}
+
+/// Counters of the traffic that went through a [`SimulatedNetwork`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stats {
+	/// Connections forwarded between two peers.
+	pub connections: u64,
+	/// Connections refused or closed because the peers couldn't reach each other.
+	pub cut_connections: u64,
+	/// Bytes delivered to their recipient.
+	pub delivered_bytes: u64,
+	/// Chunks of data lost and retransmitted.
+	pub retransmissions: u64,
+}
+
+/// State of a directed link.
+struct LinkState {
+	/// Source of the jitter and losses of the link.
+	rng: StdRng,
+	/// Time at which the link is done transmitting the data already sent.
+	busy_until: Option<Instant>,
+	/// Arrival time of the data sent last, to keep it in order.
+	last_arrival: Option<Instant>,
+}
+
+struct State {
+	/// Source of the memory addresses, which may have to be drawn again if taken, so it is kept
+	/// apart from the other sources.
+	address_rng: StdRng,
+	/// Source of the node keys.
+	key_rng: StdRng,
+	/// Source of the seeds of the link RNGs.
+	link_rng: StdRng,
+	default_link: LinkConfig,
+	links: HashMap<(PeerIndex, PeerIndex), LinkConfig>,
+	link_states: HashMap<(PeerIndex, PeerIndex), LinkState>,
+	/// Real listen address of every peer.
+	listen_addresses: Vec<Multiaddr>,
+	/// Address of the relay through which the first peer reaches the second one.
+	relays: HashMap<(PeerIndex, PeerIndex), Multiaddr>,
+	/// Partition group of every peer.
+	groups: Vec<usize>,
+	online: Vec<bool>,
+	stats: Stats,
+}
+
+/// Links between the peers of a test network, see the [module documentation](self).
+#[derive(Clone)]
+pub struct SimulatedNetwork {
+	state: Arc<Mutex<State>>,
+	/// Bumped whenever peers may have lost the ability to reach each other.
+	connectivity: Arc<watch::Sender<u64>>,
+}
+
+impl SimulatedNetwork {
+	/// Create a network without peers, whose randomness is derived from `seed`.
+	pub fn new(seed: u64) -> Self {
+		let mut rng = StdRng::seed_from_u64(seed);
+		let state = State {
+			address_rng: StdRng::seed_from_u64(rng.gen()),
+			key_rng: StdRng::seed_from_u64(rng.gen()),
+			link_rng: StdRng::seed_from_u64(rng.gen()),
+			default_link: LinkConfig::default(),
+			links: HashMap::new(),
+			link_states: HashMap::new(),
+			listen_addresses: Vec::new(),
+			relays: HashMap::new(),
+			groups: Vec::new(),
+			online: Vec::new(),
+			stats: Stats::default(),
+		};
+
+		Self { state: Arc::new(Mutex::new(state)), connectivity: Arc::new(watch::channel(0).0) }
+	}
+
+	/// Add a peer listening on `listen_address`, and the relays between it and the peers added
+	/// before. Must be called from within a tokio runtime.
+	pub fn add_peer(&self, listen_address: Multiaddr) -> PeerIndex {
+		let (index, listen_addresses) = {
+			let mut state = self.state.lock();
+			state.listen_addresses.push(listen_address);
+			state.groups.push(0);
+			state.online.push(true);
+			let index = state.listen_addresses.len() - 1;
+			for peer in 0..index {
+				for link in [(index, peer), (peer, index)] {
+					let rng = StdRng::seed_from_u64(state.link_rng.gen());
+					state
+						.link_states
+						.insert(link, LinkState { rng, busy_until: None, last_arrival: None });
+				}
+			}
+			(index, state.listen_addresses.clone())
+		};
+
+		for (peer, peer_address) in listen_addresses[..index].iter().enumerate() {
+			let to_peer = self.spawn_relay(index, peer, peer_address.clone());
+			let from_peer = self.spawn_relay(peer, index, listen_addresses[index].clone());
+
+			let mut state = self.state.lock();
+			state.relays.insert((index, peer), to_peer);
+			state.relays.insert((peer, index), from_peer);
+		}
+
+		index
+	}
+
+	/// Draw a memory address no one listens on yet, for a new peer to listen on.
+	pub fn new_listen_address(&self) -> Multiaddr {
+		loop {
+			let address = self.new_address();
+			// The listener is dropped right away, freeing the address again.
+			if MemoryTransport::default().listen_on(address.clone()).is_ok() {
+				return address
+			}
+		}
+	}
+
+	/// Draw the node key of a new peer.
+	pub fn new_node_key(&self) -> NodeKeyConfig {
+		let mut bytes = [0; 32];
+		self.state.lock().key_rng.fill(&mut bytes);
+		let secret = ed25519::SecretKey::try_from_bytes(&mut bytes)
+			.expect("any 32 bytes are a valid ed25519 secret key; qed");
+		NodeKeyConfig::Ed25519(Secret::Input(secret))
+	}
+
+	/// Address through which `from` reaches `to`.
+	pub fn address(&self, from: PeerIndex, to: PeerIndex) -> Multiaddr {
+		self.state
+			.lock()
+			.relays
+			.get(&(from, to))
+			.cloned()
+			.unwrap_or_else(|| panic!("no relay from peer {from} to peer {to}"))
+	}
+
+	/// Use `config` for all the links not configured with [`SimulatedNetwork::set_link`].
+	pub fn set_default_link(&self, config: LinkConfig) {
+		assert_valid_link(&config);
+		self.state.lock().default_link = config;
+	}
+
+	/// Change the properties of the links between `a` and `b`, in both directions.
+	pub fn set_link(&self, a: PeerIndex, b: PeerIndex, config: LinkConfig) {
+		assert_valid_link(&config);
+		let mut state = self.state.lock();
+		state.links.insert((a, b), config.clone());
+		state.links.insert((b, a), config);
+	}
+
+	/// Split the network into `groups`, see [`Action::Partition`].
+	pub fn partition(&self, groups: Vec<Vec<PeerIndex>>) {
+		self.update_connectivity(|state| {
+			state.groups.iter_mut().for_each(|group| *group = 0);
+			for (group, peers) in groups.iter().enumerate() {
+				for peer in peers {
+					state.groups[*peer] = group + 1;
+				}
+			}
+		});
+	}
+
+	/// Reconnect all the groups of a partition.
+	pub fn heal(&self) {
+		self.update_connectivity(|state| state.groups.iter_mut().for_each(|group| *group = 0));
+	}
+
+	/// Disconnect `peer` from all the others.
+	pub fn disconnect(&self, peer: PeerIndex) {
+		self.update_connectivity(|state| state.online[peer] = false);
+	}
+
+	/// Reconnect `peer`, previously disconnected.
+	pub fn reconnect(&self, peer: PeerIndex) {
+		self.update_connectivity(|state| state.online[peer] = true);
+	}
+
+	/// Apply `action` right away.
+	pub fn apply(&self, action: Action) {
+		trace!(target: LOG_TARGET, "Applying {action:?}.");
+
+		match action {
+			Action::Partition(groups) => self.partition(groups),
+			Action::Heal => self.heal(),
+			Action::Disconnect(peer) => self.disconnect(peer),
+			Action::Reconnect(peer) => self.reconnect(peer),
+			Action::SetLink(a, b, config) => self.set_link(a, b, config),
+		}
+	}
+
+	/// Apply `action` once `after` elapsed on the tokio clock. Must be called from within a tokio
+	/// runtime.
+	pub fn schedule(&self, after: Duration, action: Action) {
+		let network = self.clone();
+		tokio::spawn(async move {
+			tokio::time::sleep(after).await;
+			network.apply(action);
+		});
+	}
+
+	/// Returns `true` if `a` can currently reach `b`.
+	pub fn is_connected(&self, a: PeerIndex, b: PeerIndex) -> bool {
+		let state = self.state.lock();
+		a != b && state.online[a] && state.online[b] && state.groups[a] == state.groups[b]
+	}
+
+	/// Counters of the traffic so far.
+	pub fn stats(&self) -> Stats {
+		self.state.lock().stats.clone()
+	}
+
+	/// Draw a memory address. It may be taken, e.g. by a concurrent test using the same seed.
+	fn new_address(&self) -> Multiaddr {
+		build_multiaddr![Memory(self.state.lock().address_rng.gen::<u64>())]
+	}
+
+	fn update_connectivity(&self, f: impl FnOnce(&mut State)) {
+		f(&mut self.state.lock());
+		self.connectivity.send_modify(|generation| *generation += 1);
+	}
+
+	/// Listen on a new memory address and forward the connections made to it to `target`, as
+	/// connections from `from` to `to`.
+	fn spawn_relay(&self, from: PeerIndex, to: PeerIndex, target: Multiaddr) -> Multiaddr {
+		let (address, mut incoming) = loop {
+			let address = self.new_address();
+			if let Some(incoming) = listen(address.clone()) {
+				break (address, incoming)
+			}
+		};
+		let network = self.clone();
+
+		tokio::spawn(async move {
+			while let Some(inbound) = incoming.next().await {
+				if !network.is_connected(from, to) {
+					trace!(target: LOG_TARGET, "{from} -> {to}: connection refused.");
+					network.state.lock().stats.cut_connections += 1;
+					continue
+				}
+
+				let Ok(dial) = MemoryTransport::default().dial(target.clone()) else { continue };
+				let network = network.clone();
+				tokio::spawn(async move {
+					if let Ok(outbound) = dial.await {
+						network.forward(from, to, inbound, outbound).await;
+					}
+				});
+			}
+		});
+
+		address
+	}
+
+	/// Forward the data of a connection from `from` to `to` until either end closes it or the
+	/// peers can't reach each other anymore.
+	async fn forward(
+		&self,
+		from: PeerIndex,
+		to: PeerIndex,
+		inbound: Channel<Vec<u8>>,
+		outbound: Channel<Vec<u8>>,
+	) {
+		trace!(target: LOG_TARGET, "{from} -> {to}: connection established.");
+		self.state.lock().stats.connections += 1;
+
+		let mut connectivity = self.connectivity.subscribe();
+		let cut = async {
+			while connectivity.changed().await.is_ok() {
+				if !self.is_connected(from, to) {
+					return
+				}
+			}
+			future::pending::<()>().await
+		};
+
+		let (inbound_read, inbound_write) = inbound.split();
+		let (outbound_read, outbound_write) = outbound.split();
+		let transfer = future::select(
+			Box::pin(self.transfer(from, to, inbound_read, outbound_write)),
+			Box::pin(self.transfer(to, from, outbound_read, inbound_write)),
+		);
+
+		match future::select(Box::pin(cut), transfer).await {
+			Either::Left(_) => {
+				trace!(target: LOG_TARGET, "{from} -> {to}: connection cut.");
+				self.state.lock().stats.cut_connections += 1;
+			},
+			Either::Right(_) => trace!(target: LOG_TARGET, "{from} -> {to}: connection closed."),
+		}
+	}
+
+	/// Transfer the data read from `reader` to `writer` over the link from `from` to `to`.
+	async fn transfer(
+		&self,
+		from: PeerIndex,
+		to: PeerIndex,
+		mut reader: impl AsyncRead + Unpin,
+		mut writer: impl AsyncWrite + Unpin,
+	) {
+		let (tx, mut rx) = mpsc::unbounded();
+
+		// Keep reading while earlier data is in flight, so that latency doesn't limit throughput.
+		let read = async move {
+			let mut buffer = vec![0; READ_BUFFER_SIZE];
+			while let Ok(read @ 1..) = reader.read(&mut buffer).await {
+				let arrival = self.arrival(from, to, read);
+				if tx.unbounded_send((arrival, buffer[..read].to_vec())).is_err() {
+					return
+				}
+			}
+		};
+
+		let write = async move {
+			while let Some((arrival, data)) = rx.next().await {
+				tokio::time::sleep_until(arrival).await;
+				if !self.is_connected(from, to) ||
+					writer.write_all(&data).await.is_err() ||
+					writer.flush().await.is_err()
+				{
+					return
+				}
+				self.state.lock().stats.delivered_bytes += data.len() as u64;
+			}
+			let _ = writer.close().await;
+		};
+
+		future::join(read, write).await;
+	}
+
+	/// Time at which `bytes` sent now from `from` arrive at `to`.
+	fn arrival(&self, from: PeerIndex, to: PeerIndex, bytes: usize) -> Instant {
+		let now = Instant::now();
+		let mut state = self.state.lock();
+		let State { default_link, links, link_states, stats, .. } = &mut *state;
+		let config = links.get(&(from, to)).unwrap_or(default_link);
+		let link = link_states.get_mut(&(from, to)).expect("links are added with peers; qed");
+
+		let transmission = config.bandwidth.map_or(Duration::ZERO, |bandwidth| {
+			Duration::from_secs_f64(bytes as f64 / bandwidth as f64)
+		});
+		let departure =
+			link.busy_until.map_or(now, |busy_until| busy_until.max(now)) + transmission;
+		let jitter = config.jitter.mul_f64(link.rng.gen::<f64>());
+		let mut arrival = departure + config.latency + jitter;
+		while link.rng.gen_bool(config.loss) {
+			arrival += (config.latency * 2).max(MIN_RETRANSMISSION_TIMEOUT);
+			stats.retransmissions += 1;
+		}
+		let arrival = arrival.max(link.last_arrival.unwrap_or(now));
+
+		link.busy_until = Some(departure);
+		link.last_arrival = Some(arrival);
+		arrival
+	}
+}
+
+fn assert_valid_link(config: &LinkConfig) {
+	assert!(config.bandwidth != Some(0), "the bandwidth of a link can't be zero");
+	assert!((0.0..1.0).contains(&config.loss), "the loss of a link must be in [0, 1)");
+}
+
+/// Listen on `address`, returning the stream of incoming connections, or `None` if the address
+/// is taken.
+fn listen(address: Multiaddr) -> Option<BoxStream<'static, Channel<Vec<u8>>>> {
+	let mut transport = MemoryTransport::default();
+	transport.listen_on(address).ok()?;
+
+	let incoming = stream::poll_fn(move |cx| loop {
+		match Pin::new(&mut transport).poll(cx) {
+			Poll::Ready(TransportEvent::Incoming { upgrade, .. }) =>
+				return Poll::Ready(Some(upgrade)),
+			Poll::Ready(TransportEvent::ListenerClosed { .. }) => return Poll::Ready(None),
+			Poll::Ready(_) => {},
+			Poll::Pending => return Poll::Pending,
+		}
+	})
+	.filter_map(|upgrade| async move { upgrade.await.ok() })
+	.boxed();
+	Some(incoming)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Network of two peers listening on memory addresses, returned with their incoming
+	/// connections.
+	fn two_peers(seed: u64) -> (SimulatedNetwork, [BoxStream<'static, Channel<Vec<u8>>>; 2]) {
+		let network = SimulatedNetwork::new(seed);
+		let listeners = [(); 2].map(|_| {
+			let address = network.new_listen_address();
+			let incoming = listen(address.clone()).unwrap();
+			network.add_peer(address);
+			incoming
+		});
+
+		(network, listeners)
+	}
+
+	async fn dial(network: &SimulatedNetwork, from: PeerIndex, to: PeerIndex) -> Channel<Vec<u8>> {
+		MemoryTransport::default()
+			.dial(network.address(from, to))
+			.unwrap()
+			.await
+			.unwrap()
+	}
+
+	#[tokio::test(start_paused = true)]
+	async fn links_delay_data() {
+		let (network, [_, mut incoming]) = two_peers(0);
+		network.set_link(
+			0,
+			1,
+			LinkConfig {
+				latency: Duration::from_millis(100),
+				bandwidth: Some(1000),
+				..Default::default()
+			},
+		);
+
+		let mut outbound = dial(&network, 0, 1).await;
+		let mut inbound = incoming.next().await.unwrap();
+
+		let started = Instant::now();
+		outbound.write_all(&[1; 500]).await.unwrap();
+		outbound.flush().await.unwrap();
+
+		let mut received = [0; 500];
+		inbound.read_exact(&mut received).await.unwrap();
+		assert_eq!(received, [1; 500]);
+
+		// Half a second to transmit the data at 1000 bytes per second, and the latency.
+		assert!(started.elapsed() >= Duration::from_millis(600));
+		assert_eq!(network.stats().delivered_bytes, 500);
+	}
+
+	#[tokio::test(start_paused = true)]
+	async fn jitter_keeps_data_in_order() {
+		let (network, [_, mut incoming]) = two_peers(0);
+		network.set_default_link(LinkConfig {
+			latency: Duration::from_millis(50),
+			jitter: Duration::from_millis(200),
+			..Default::default()
+		});
+
+		let mut outbound = dial(&network, 0, 1).await;
+		let mut inbound = incoming.next().await.unwrap();
+
+		for byte in 0..100u8 {
+			outbound.write_all(&[byte]).await.unwrap();
+			outbound.flush().await.unwrap();
+		}
+
+		let mut received = [0; 100];
+		inbound.read_exact(&mut received).await.unwrap();
+		assert!(received.iter().enumerate().all(|(index, byte)| *byte as usize == index));
+	}
+
+	#[tokio::test(start_paused = true)]
+	async fn lost_data_is_retransmitted() {
+		let (network, [_, mut incoming]) = two_peers(0);
+		network.set_default_link(LinkConfig {
+			latency: Duration::from_millis(10),
+			loss: 0.5,
+			..Default::default()
+		});
+
+		let mut outbound = dial(&network, 0, 1).await;
+		let mut inbound = incoming.next().await.unwrap();
+
+		let started = Instant::now();
+		for byte in 0..20u8 {
+			outbound.write_all(&[byte]).await.unwrap();
+			outbound.flush().await.unwrap();
+		}
+
+		let mut received = [0; 20];
+		inbound.read_exact(&mut received).await.unwrap();
+		assert!(received.iter().enumerate().all(|(index, byte)| *byte as usize == index));
+
+		// Lost chunks are delayed by the retransmission timeout rather than the latency.
+		assert!(network.stats().retransmissions > 0);
+		assert!(started.elapsed() >= MIN_RETRANSMISSION_TIMEOUT);
+	}
+
+	#[tokio::test(start_paused = true)]
+	async fn conditions_are_derived_from_the_seed() {
+		let conditions = |seed| {
+			let (network, _) = two_peers(seed);
+			network.set_default_link(LinkConfig {
+				latency: Duration::from_millis(50),
+				jitter: Duration::from_millis(100),
+				loss: 0.3,
+				bandwidth: None,
+			});
+			let node_key = network.new_node_key().into_keypair().unwrap().public();
+
+			// The clock is paused, so the delays only depend on the link.
+			let now = Instant::now();
+			let delays = (0..20).map(|_| network.arrival(0, 1, 100) - now).collect::<Vec<_>>();
+			(node_key, delays)
+		};
+
+		assert_eq!(conditions(1), conditions(1));
+		assert_ne!(conditions(1), conditions(2));
+	}
+
+	#[tokio::test(start_paused = true)]
+	async fn partitions_cut_connections() {
+		let (network, [_, mut incoming]) = two_peers(0);
+
+		let mut outbound = dial(&network, 0, 1).await;
+		let mut inbound = incoming.next().await.unwrap();
+
+		network.partition(vec![vec![0], vec![1]]);
+		assert!(!network.is_connected(0, 1));
+
+		// The established connection is closed, and new ones are refused.
+		let mut buffer = [0; 1];
+		assert!(matches!(inbound.read(&mut buffer).await, Ok(0) | Err(_)));
+		assert!(matches!(outbound.read(&mut buffer).await, Ok(0) | Err(_)));
+
+		let mut refused = dial(&network, 0, 1).await;
+		assert!(matches!(refused.read(&mut buffer).await, Ok(0) | Err(_)));
+		assert_eq!(network.stats().cut_connections, 2);
+
+		// Once healed, the peers can connect again.
+		network.schedule(Duration::from_secs(10), Action::Heal);
+		tokio::time::sleep(Duration::from_secs(11)).await;
+		assert!(network.is_connected(0, 1));
+
+		let mut outbound = dial(&network, 0, 1).await;
+		let mut inbound = incoming.next().await.unwrap();
+		outbound.write_all(b"ping").await.unwrap();
+		outbound.flush().await.unwrap();
+		inbound.read_exact(&mut buffer).await.unwrap();
+		assert_eq!(&buffer, b"p");
+	}
+
+	#[tokio::test(start_paused = true)]
+	async fn offline_peers_are_unreachable() {
+		let (network, _) = two_peers(0);
+
+		network.disconnect(1);
+		assert!(!network.is_connected(0, 1));
+		assert!(!network.is_connected(1, 0));
+
+		network.reconnect(1);
+		assert!(network.is_connected(0, 1));
+	}
+}
@@ -17,6 +17,7 @@
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
 use super::*;
+use crate::simulation::LinkConfig;
 use futures::Future;
 use sp_consensus::{block_validation::Validation, BlockOrigin};
 use sp_runtime::Justifications;
@@ -56,10 +57,11 @@ async fn sync_peers_works() {
 	}
 }
 
-#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+#[tokio::test(start_paused = true)]
 async fn sync_cycle_from_offline_to_syncing_to_offline() {
 	sp_tracing::try_init_simple();
-	let mut net = TestNet::new(3);
+	let mut net = TestNet::simulated(3, 1);
+	let network = net.simulated_network().unwrap().clone();
 	for peer in 0..3 {
 		// Offline, and not major syncing.
 		assert!(net.peer(peer).is_offline());
@@ -100,9 +102,9 @@ async fn sync_cycle_from_offline_to_syncing_to_offline() {
 	})
 	.await;
 
-	// Now drop nodes 1 and 2, and check that node 0 is offline.
-	net.peers.remove(2);
-	net.peers.remove(1);
+	// Now disconnect nodes 1 and 2, and check that node 0 is offline.
+	network.disconnect(1);
+	network.disconnect(2);
 	futures::future::poll_fn::<(), _>(|cx| {
 		net.poll(cx);
 		if !net.peer(0).is_offline() {
@@ -114,10 +116,11 @@ async fn sync_cycle_from_offline_to_syncing_to_offline() {
 	.await;
 }
 
-#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+#[tokio::test(start_paused = true)]
 async fn syncing_node_not_major_syncing_when_disconnected() {
 	sp_tracing::try_init_simple();
-	let mut net = TestNet::new(3);
+	let mut net = TestNet::simulated(3, 2);
+	let network = net.simulated_network().unwrap().clone();
 
 	// Generate blocks.
 	net.peer(2).push_blocks(100, false);
@@ -136,12 +139,12 @@ async fn syncing_node_not_major_syncing_when_disconnected() {
 	})
 	.await;
 
-	// Destroy two nodes, and check that we switch to non-major syncing.
-	net.peers.remove(2);
-	net.peers.remove(0);
+	// Disconnect the two other nodes, and check that we switch to non-major syncing.
+	network.disconnect(2);
+	network.disconnect(0);
 	futures::future::poll_fn::<(), _>(|cx| {
 		net.poll(cx);
-		if net.peer(0).is_major_syncing() {
+		if net.peer(1).is_major_syncing() {
 			Poll::Pending
 		} else {
 			Poll::Ready(())
@@ -333,10 +336,16 @@ async fn sync_justifications_across_forks() {
 	.await;
 }
 
-#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+#[tokio::test(start_paused = true)]
 async fn sync_after_fork_works() {
 	sp_tracing::try_init_simple();
-	let mut net = TestNet::new(3);
+	let mut net = TestNet::simulated(3, 42);
+	net.simulated_network().unwrap().set_default_link(LinkConfig {
+		latency: Duration::from_millis(150),
+		jitter: Duration::from_millis(50),
+		loss: 0.01,
+		bandwidth: Some(256 * 1024),
+	});
 	net.peer(0).push_blocks(30, false);
 	net.peer(1).push_blocks(30, false);
 	net.peer(2).push_blocks(30, false);
@@ -356,6 +365,28 @@ async fn sync_after_fork_works() {
 	(net.peers()[2].blockchain_canon_equals(peer1));
 }
 
+#[tokio::test(start_paused = true)]
+async fn sync_resumes_after_partition_heals() {
+	sp_tracing::try_init_simple();
+	let mut net = TestNet::simulated(3, 7);
+	let network = net.simulated_network().unwrap().clone();
+	net.run_until_connected().await;
+
+	network.partition(vec![vec![0, 1], vec![2]]);
+	let best = net.peer(2).push_blocks(10, false).pop().unwrap();
+
+	// blocks don't make it across the partition
+	net.run_for(Duration::from_secs(60)).await;
+	assert!(!net.peer(0).has_block(best));
+	assert!(!net.peer(1).has_block(best));
+	assert!(network.stats().cut_connections > 0);
+
+	network.heal();
+	net.run_until_sync().await;
+	assert!(net.peer(0).has_block(best));
+	assert!(net.peer(1).has_block(best));
+}
+
 #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
 async fn syncs_all_forks() {
 	sp_tracing::try_init_simple();
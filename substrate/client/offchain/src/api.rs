@@ -20,6 +20,7 @@ use std::{collections::HashSet, str::FromStr, sync::Arc, thread::sleep};
 
 use crate::NetworkProvider;
 use codec::{Decode, Encode};
+pub use fixtures::{FixturesRecorder, HttpMode, ReplayFixtures};
 use futures::Future;
 pub use http::SharedClient;
 use libp2p::{Multiaddr, PeerId};
@@ -32,6 +33,7 @@ use sp_core::{
 };
 pub use sp_offchain::STORAGE_PREFIX;
 
+mod fixtures;
 mod http;
 
 mod timestamp;
@@ -0,0 +1,386 @@
+// This file is part of Substrate.
+
+// Copyright (C) Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Recording and replaying the HTTP requests of offchain workers.
+//!
+//! In [`HttpMode::Record`] every request is performed and then stored along with its response in a
+//! fixtures file. In [`HttpMode::Replay`] requests are answered from such a file without any
+//! network access, which allows to run offchain workers deterministically in integration tests.
+//!
+//! Both modes are created by [`HttpMode::new`], which fails if the fixtures file can't be loaded
+//! or created, so that a node doesn't start with a broken configuration. The recorded fixtures
+//! are written to the file in the background, without blocking the offchain workers.
+//!
+//! The fixtures file is a JSON array of fixtures:
+//!
+//! ```json
+//! [
+//!   {
+//!     "request": { "method": "GET", "uri": "https://example.com/price" },
+//!     "response": {
+//!       "status": 200,
+//!       "headers": [["content-type", "application/json"]],
+//!       "body": { "text": "{\"price\": 42}" }
+//!     },
+//!     "latencyMs": 500
+//!   },
+//!   {
+//!     "request": { "method": "POST", "uri": "https://example.com/submit", "body": { "hex": "0x0102" } },
+//!     "failure": "ioError"
+//!   }
+//! ]
+//! ```
+//!
+//! A request matches a fixture if its method and URI are equal and, if the fixture specifies a
+//! request body, its body is equal as well. Matching fixtures are used in the order they appear in
+//! the file, once all of them are used the last one is used for any further request. Requests
+//! without a matching fixture fail.
+//!
+//! Besides the response, a fixture may specify `latencyMs` to delay the response and `failure` to
+//! make the request fail, either with an `ioError` or by never answering it (`timeout`), in which
+//! case the deadline of the offchain worker is reached.
+//!
+//! In both modes the body of a request is fully received before the request is matched or
+//! performed.
+
+use super::http::RequestFailure;
+use futures::{channel::mpsc, future, StreamExt};
+use hyper::{client, Body, Client as HyperClient};
+use hyper_rustls::HttpsConnector;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::{
+	io,
+	path::{Path, PathBuf},
+	sync::Arc,
+	time::Duration,
+};
+
+const LOG_TARGET: &str = "offchain-worker::http";
+
+/// How offchain workers perform HTTP requests.
+#[derive(Debug, Clone, Default)]
+pub enum HttpMode {
+	/// Perform the requests.
+	#[default]
+	Live,
+	/// Perform the requests and record them along with their responses.
+	Record(FixturesRecorder),
+	/// Answer the requests from the loaded fixtures instead of performing them.
+	Replay(ReplayFixtures),
+}
+
+impl HttpMode {
+	/// Returns the mode recording the requests to the fixtures file at `record` or replaying them
+	/// from the one at `replay`, [`HttpMode::Live`] if neither is given.
+	///
+	/// Fails if both are given, if the fixtures to replay can't be loaded or if the file to record
+	/// to can't be created.
+	pub fn new(record: Option<&Path>, replay: Option<&Path>) -> io::Result<Self> {
+		match (record, replay) {
+			(None, None) => Ok(HttpMode::Live),
+			(Some(path), None) => FixturesRecorder::create(path).map(HttpMode::Record),
+			(None, Some(path)) => ReplayFixtures::load(path).map(HttpMode::Replay),
+			(Some(_), Some(_)) => Err(io::Error::new(
+				io::ErrorKind::InvalidInput,
+				"HTTP requests can't be both recorded and replayed",
+			)),
+		}
+	}
+}
+
+/// Writes recorded fixtures to a fixtures file.
+///
+/// The file is written by a background thread, which exits once all clones of the recorder are
+/// dropped.
+#[derive(Debug, Clone)]
+pub struct FixturesRecorder {
+	sender: mpsc::UnboundedSender<HttpFixture>,
+}
+
+impl FixturesRecorder {
+	/// Creates the fixtures file at `path` and starts the thread writing to it.
+	pub fn create(path: &Path) -> io::Result<Self> {
+		std::fs::write(path, "[]")?;
+
+		let (sender, receiver) = mpsc::unbounded();
+		let path = path.to_owned();
+		std::thread::Builder::new()
+			.name("offchain-http-fixtures".into())
+			.spawn(move || futures::executor::block_on(write_fixtures(path, receiver)))?;
+		Ok(Self { sender })
+	}
+
+	fn record(&self, fixture: HttpFixture) {
+		if self.sender.unbounded_send(fixture).is_err() {
+			tracing::error!(target: LOG_TARGET, "HTTP fixtures writer is gone, request not recorded");
+		}
+	}
+}
+
+/// Writes the fixtures received from `receiver` to the file at `path`.
+///
+/// The file is rewritten once for all fixtures received in the meantime.
+async fn write_fixtures(path: PathBuf, mut receiver: mpsc::UnboundedReceiver<HttpFixture>) {
+	let mut fixtures = Vec::new();
+	while let Some(fixture) = receiver.next().await {
+		fixtures.push(fixture);
+		while let Ok(Some(fixture)) = receiver.try_next() {
+			fixtures.push(fixture);
+		}
+
+		let result = serde_json::to_vec_pretty(&fixtures)
+			.map_err(io::Error::from)
+			.and_then(|data| std::fs::write(&path, data));
+		if let Err(error) = result {
+			tracing::error!(
+				target: LOG_TARGET,
+				path = %path.display(),
+				%error,
+				"Failed to write HTTP fixtures",
+			);
+		}
+	}
+}
+
+/// Fixtures loaded from a fixtures file to answer requests from.
+#[derive(Debug, Clone)]
+pub struct ReplayFixtures(Arc<Vec<HttpFixture>>);
+
+impl ReplayFixtures {
+	/// Loads the fixtures from the file at `path`.
+	pub fn load(path: &Path) -> io::Result<Self> {
+		let data = std::fs::read(path)?;
+		let fixtures = serde_json::from_slice(&data).map_err(|e| {
+			io::Error::new(
+				io::ErrorKind::InvalidData,
+				format!("invalid HTTP fixtures in {}: {}", path.display(), e),
+			)
+		})?;
+		Ok(Self(Arc::new(fixtures)))
+	}
+}
+
+/// A recorded request along with its response.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct HttpFixture {
+	request: FixtureRequest,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	response: Option<FixtureResponse>,
+	#[serde(default, skip_serializing_if = "is_zero")]
+	latency_ms: u64,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	failure: Option<InjectedFailure>,
+}
+
+fn is_zero(value: &u64) -> bool {
+	*value == 0
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct FixtureRequest {
+	method: String,
+	uri: String,
+	/// The body of the request, any body matches if `None`.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	body: Option<FixtureBody>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct FixtureResponse {
+	status: u16,
+	#[serde(default)]
+	headers: Vec<(String, String)>,
+	#[serde(default)]
+	body: FixtureBody,
+}
+
+/// A request or response body, stored as text if it is valid UTF-8.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+enum FixtureBody {
+	Text(String),
+	Hex(String),
+}
+
+impl Default for FixtureBody {
+	fn default() -> Self {
+		FixtureBody::Text(String::new())
+	}
+}
+
+impl FixtureBody {
+	fn from_bytes(bytes: &[u8]) -> Self {
+		match std::str::from_utf8(bytes) {
+			Ok(text) => FixtureBody::Text(text.to_owned()),
+			Err(_) => FixtureBody::Hex(array_bytes::bytes2hex("0x", bytes)),
+		}
+	}
+
+	fn to_bytes(&self) -> Result<Vec<u8>, RequestFailure> {
+		match self {
+			FixtureBody::Text(text) => Ok(text.as_bytes().to_vec()),
+			FixtureBody::Hex(hex) => array_bytes::hex2bytes(hex).map_err(|_| {
+				RequestFailure::Fixture(format!("invalid hex body in fixture: {}", hex))
+			}),
+		}
+	}
+
+	fn matches(&self, bytes: &[u8]) -> bool {
+		self.to_bytes().is_ok_and(|body| body == bytes)
+	}
+}
+
+/// A failure injected instead of answering a request.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+enum InjectedFailure {
+	/// The request fails with an I/O error.
+	IoError,
+	/// The request is never answered.
+	Timeout,
+}
+
+/// Records requests to or replays them from fixtures.
+pub(super) enum HttpFixtures {
+	Record(FixturesRecorder),
+	Replay {
+		fixtures: ReplayFixtures,
+		/// Which fixtures were already used.
+		used: Mutex<Vec<bool>>,
+	},
+}
+
+impl HttpFixtures {
+	/// Creates the fixtures for the given `mode`, returns `None` for [`HttpMode::Live`].
+	pub fn new(mode: &HttpMode) -> Option<Arc<Self>> {
+		let fixtures = match mode {
+			HttpMode::Live => return None,
+			HttpMode::Record(recorder) => HttpFixtures::Record(recorder.clone()),
+			HttpMode::Replay(fixtures) => HttpFixtures::Replay {
+				fixtures: fixtures.clone(),
+				used: Mutex::new(vec![false; fixtures.0.len()]),
+			},
+		};
+		Some(Arc::new(fixtures))
+	}
+
+	/// Records or replays the given `request`.
+	pub async fn dispatch(
+		self: Arc<Self>,
+		client: Arc<Lazy<HyperClient<HttpsConnector<client::HttpConnector>, Body>>>,
+		request: hyper::Request<Body>,
+	) -> Result<hyper::Response<Body>, RequestFailure> {
+		let (parts, body) = request.into_parts();
+		let body = hyper::body::to_bytes(body).await.map_err(RequestFailure::Http)?;
+		let method = parts.method.to_string();
+		let uri = parts.uri.to_string();
+
+		let recorder = match &*self {
+			HttpFixtures::Record(recorder) => recorder,
+			HttpFixtures::Replay { .. } => return self.replay(&method, &uri, &body).await,
+		};
+
+		let response = client
+			.request(hyper::Request::from_parts(parts, Body::from(body.clone())))
+			.await
+			.map_err(RequestFailure::Http)?;
+		let (response_parts, response_body) = response.into_parts();
+		let response_body =
+			hyper::body::to_bytes(response_body).await.map_err(RequestFailure::Http)?;
+
+		recorder.record(HttpFixture {
+			request: FixtureRequest { method, uri, body: Some(FixtureBody::from_bytes(&body)) },
+			response: Some(FixtureResponse {
+				status: response_parts.status.as_u16(),
+				headers: response_parts
+					.headers
+					.iter()
+					.map(|(name, value)| {
+						(name.as_str().to_owned(), String::from_utf8_lossy(value.as_bytes()).into())
+					})
+					.collect(),
+				body: FixtureBody::from_bytes(&response_body),
+			}),
+			latency_ms: 0,
+			failure: None,
+		});
+
+		Ok(hyper::Response::from_parts(response_parts, Body::from(response_body)))
+	}
+
+	async fn replay(
+		&self,
+		method: &str,
+		uri: &str,
+		body: &[u8],
+	) -> Result<hyper::Response<Body>, RequestFailure> {
+		let fixture = self.find(method, uri, body).ok_or_else(|| {
+			tracing::warn!(target: LOG_TARGET, %method, %uri, "No HTTP fixture matches request");
+			RequestFailure::Fixture(format!("no fixture matches {} {}", method, uri))
+		})?;
+
+		if fixture.latency_ms > 0 {
+			futures_timer::Delay::new(Duration::from_millis(fixture.latency_ms)).await;
+		}
+
+		match fixture.failure {
+			Some(InjectedFailure::IoError) =>
+				return Err(RequestFailure::Fixture("injected failure".into())),
+			Some(InjectedFailure::Timeout) => return future::pending().await,
+			None => {},
+		}
+
+		let response = fixture.response.ok_or_else(|| {
+			RequestFailure::Fixture(format!("fixture for {} {} has no response", method, uri))
+		})?;
+		let mut builder = hyper::Response::builder().status(response.status);
+		for (name, value) in &response.headers {
+			builder = builder.header(name, value);
+		}
+		builder
+			.body(Body::from(response.body.to_bytes()?))
+			.map_err(|e| RequestFailure::Fixture(format!("invalid response in fixture: {}", e)))
+	}
+
+	/// Returns the first unused fixture matching the request, or the last used one if all of them
+	/// were used already.
+	fn find(&self, method: &str, uri: &str, body: &[u8]) -> Option<HttpFixture> {
+		let HttpFixtures::Replay { fixtures, used } = self else { return None };
+		let mut used = used.lock();
+		let matching = fixtures.0.iter().enumerate().filter(|(_, fixture)| {
+			fixture.request.method.eq_ignore_ascii_case(method) &&
+				fixture.request.uri == uri &&
+				fixture.request.body.as_ref().map_or(true, |expected| expected.matches(body))
+		});
+
+		let mut last = None;
+		for (index, fixture) in matching {
+			if !used[index] {
+				used[index] = true;
+				return Some(fixture.clone())
+			}
+			last = Some(fixture);
+		}
+		last.cloned()
+	}
+}
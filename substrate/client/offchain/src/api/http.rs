@@ -27,10 +27,15 @@
 //! (i.e.: the socket should continue being processed) in the background even if the runtime isn't
 //! actively calling any function.
 
+use super::fixtures::{HttpFixtures, HttpMode};
 use crate::api::timestamp;
 use bytes::buf::{Buf, Reader};
 use fnv::FnvHashMap;
-use futures::{channel::mpsc, future, prelude::*};
+use futures::{
+	channel::mpsc,
+	future::{self, BoxFuture},
+	prelude::*,
+};
 use hyper::{client, Body, Client as HyperClient};
 use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
 use once_cell::sync::Lazy;
@@ -48,19 +53,31 @@ const LOG_TARGET: &str = "offchain-worker::http";
 
 /// Wrapper struct used for keeping the hyper_rustls client running.
 #[derive(Clone)]
-pub struct SharedClient(Arc<Lazy<HyperClient<HttpsConnector<client::HttpConnector>, Body>>>);
+pub struct SharedClient {
+	client: Arc<Lazy<HyperClient<HttpsConnector<client::HttpConnector>, Body>>>,
+	/// Fixtures the requests are recorded to or replayed from, if any.
+	fixtures: Option<Arc<HttpFixtures>>,
+}
 
 impl SharedClient {
 	pub fn new() -> Self {
-		Self(Arc::new(Lazy::new(|| {
-			let connector = HttpsConnectorBuilder::new()
-				.with_native_roots()
-				.https_or_http()
-				.enable_http1()
-				.enable_http2()
-				.build();
-			HyperClient::builder().build(connector)
-		})))
+		Self::with_mode(&HttpMode::Live)
+	}
+
+	/// Creates a client performing requests according to the given `mode`.
+	pub fn with_mode(mode: &HttpMode) -> Self {
+		Self {
+			client: Arc::new(Lazy::new(|| {
+				let connector = HttpsConnectorBuilder::new()
+					.with_native_roots()
+					.https_or_http()
+					.enable_http1()
+					.enable_http2()
+					.build();
+				HyperClient::builder().build(connector)
+			})),
+			fixtures: HttpFixtures::new(mode),
+		}
 	}
 }
 
@@ -78,8 +95,13 @@ pub fn http(shared_client: SharedClient) -> (HttpApi, HttpWorker) {
 		requests: FnvHashMap::default(),
 	};
 
-	let engine =
-		HttpWorker { to_api, from_api, http_client: shared_client.0, requests: Vec::new() };
+	let engine = HttpWorker {
+		to_api,
+		from_api,
+		http_client: shared_client.client,
+		fixtures: shared_client.fixtures,
+		requests: Vec::new(),
+	};
 
 	(api, engine)
 }
@@ -112,7 +134,16 @@ enum HttpApiRequest {
 	/// A request has been dispatched but the worker notified us of an error. We report this
 	/// failure to the user as an `IoError` and remove the request from the list as soon as
 	/// possible.
-	Fail(hyper::Error),
+	Fail(RequestFailure),
+}
+
+/// Reason why a dispatched request failed.
+#[derive(Debug)]
+pub(super) enum RequestFailure {
+	/// The HTTP client returned an error.
+	Http(hyper::Error),
+	/// The request couldn't be answered from the fixtures or a failure was injected.
+	Fixture(String),
 }
 
 /// A request within `HttpApi` that has received a response.
@@ -613,7 +644,7 @@ enum WorkerToApi {
 		/// The ID that was passed to the worker.
 		id: HttpRequestId,
 		/// Error that happened.
-		error: hyper::Error,
+		error: RequestFailure,
 	},
 }
 
@@ -625,6 +656,8 @@ pub struct HttpWorker {
 	from_api: TracingUnboundedReceiver<ApiToWorker>,
 	/// The engine that runs HTTP requests.
 	http_client: Arc<Lazy<HyperClient<HttpsConnector<client::HttpConnector>, Body>>>,
+	/// Fixtures requests are recorded to or replayed from instead of only using `http_client`.
+	fixtures: Option<Arc<HttpFixtures>>,
 	/// HTTP requests that are being worked on by the engine.
 	requests: Vec<(HttpRequestId, HttpWorkerRequest)>,
 }
@@ -632,7 +665,7 @@ pub struct HttpWorker {
 /// HTTP request being processed by the worker.
 enum HttpWorkerRequest {
 	/// Request has been dispatched and is waiting for a response from the Internet.
-	Dispatched(hyper::client::ResponseFuture),
+	Dispatched(BoxFuture<'static, Result<hyper::Response<Body>, RequestFailure>>),
 	/// Progressively reading the body of the response and sending it to the channel.
 	ReadBody {
 		/// Body to read `Chunk`s from. Only used if the channel is ready to accept data.
@@ -658,7 +691,7 @@ impl Future for HttpWorker {
 			match request {
 				HttpWorkerRequest::Dispatched(mut future) => {
 					// Check for an HTTP response from the Internet.
-					let response = match Future::poll(Pin::new(&mut future), cx) {
+					let response = match future.poll_unpin(cx) {
 						Poll::Pending => {
 							me.requests.push((id, HttpWorkerRequest::Dispatched(future)));
 							continue
@@ -724,7 +757,11 @@ impl Future for HttpWorker {
 			Poll::Pending => {},
 			Poll::Ready(None) => return Poll::Ready(()), // stops the worker
 			Poll::Ready(Some(ApiToWorker::Dispatch { id, request })) => {
-				let future = me.http_client.request(request);
+				let future = match &me.fixtures {
+					Some(fixtures) =>
+						fixtures.clone().dispatch(me.http_client.clone(), request).boxed(),
+					None => me.http_client.request(request).map_err(RequestFailure::Http).boxed(),
+				};
 				debug_assert!(me.requests.iter().all(|(i, _)| *i != id));
 				me.requests.push((id, HttpWorkerRequest::Dispatched(future)));
 				cx.waker().wake_by_ref(); // reschedule the task to poll the request
@@ -762,7 +799,9 @@ mod tests {
 	use core::convert::Infallible;
 	use futures::{future, StreamExt};
 	use lazy_static::lazy_static;
-	use sp_core::offchain::{Duration, Externalities, HttpError, HttpRequestId, HttpRequestStatus};
+	use sp_core::offchain::{
+		Duration, Externalities, HttpError, HttpRequestId, HttpRequestStatus, Timestamp,
+	};
 
 	// Using lazy_static to avoid spawning lots of different SharedClients,
 	// as spawning a SharedClient is CPU-intensive and opens lots of fds.
@@ -776,9 +815,11 @@ mod tests {
 		() => {
 			build_api_server!(hyper::Response::new(hyper::Body::from("Hello World!")))
 		};
-		( $response:expr ) => {{
-			let hyper_client = SHARED_CLIENT.clone();
-			let (api, worker) = http(hyper_client.clone());
+		( $response:expr ) => {
+			build_api_server!(SHARED_CLIENT.clone(), $response)
+		};
+		( $client:expr, $response:expr ) => {{
+			let (api, worker) = http($client);
 
 			let (addr_tx, addr_rx) = std::sync::mpsc::channel();
 			std::thread::spawn(move || {
@@ -1110,7 +1151,7 @@ mod tests {
 		}
 
 		// Check that the http client wasn't initialized, because it wasn't used.
-		assert!(Lazy::into_value(Arc::try_unwrap(shared_client.0).unwrap()).is_err());
+		assert!(Lazy::into_value(Arc::try_unwrap(shared_client.client).unwrap()).is_err());
 
 		let shared_client = SharedClient::new();
 
@@ -1125,6 +1166,202 @@ mod tests {
 		}
 
 		// Check that the http client initialized, because it was used.
-		assert!(Lazy::into_value(Arc::try_unwrap(shared_client.0).unwrap()).is_ok());
+		assert!(Lazy::into_value(Arc::try_unwrap(shared_client.client).unwrap()).is_ok());
+	}
+
+	// Returns an `HttpApi` answering requests from the given fixtures, whose worker is ran in the
+	// background.
+	fn build_replay_api(fixtures: &str) -> HttpApi {
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("fixtures.json");
+		std::fs::write(&path, fixtures).unwrap();
+
+		let mode = HttpMode::new(None, Some(&path)).unwrap();
+		let (api, worker) = http(SharedClient::with_mode(&mode));
+		std::thread::spawn(move || futures::executor::block_on(worker));
+		api
+	}
+
+	fn read_body(api: &mut HttpApi, id: HttpRequestId, deadline: Timestamp) -> Vec<u8> {
+		let mut body = Vec::new();
+		let mut buf = vec![0; 2048];
+		loop {
+			match api.response_read_body(id, &mut buf, Some(deadline)).unwrap() {
+				0 => return body,
+				n => body.extend_from_slice(&buf[..n]),
+			}
+		}
+	}
+
+	#[test]
+	fn replays_fixtures_in_order() {
+		let deadline = timestamp::now().add(Duration::from_millis(10_000));
+		let mut api = build_replay_api(
+			r#"[
+				{
+					"request": { "method": "GET", "uri": "http://example.com/price" },
+					"response": {
+						"status": 200,
+						"headers": [["content-type", "text/plain"]],
+						"body": { "text": "first" }
+					}
+				},
+				{
+					"request": { "method": "POST", "uri": "http://example.com/price", "body": { "hex": "0x0102" } },
+					"response": { "status": 201, "body": { "hex": "0xff00" } }
+				},
+				{
+					"request": { "method": "GET", "uri": "http://example.com/price" },
+					"response": { "status": 404, "body": { "text": "second" } }
+				}
+			]"#,
+		);
+
+		let get = |api: &mut HttpApi| {
+			let id = api.request_start("GET", "http://example.com/price").unwrap();
+			api.request_write_body(id, &[], Some(deadline)).unwrap();
+			let status = api.response_wait(&[id], Some(deadline))[0];
+			(status, api.response_headers(id), read_body(api, id, deadline))
+		};
+
+		let (status, headers, body) = get(&mut api);
+		assert_eq!(status, HttpRequestStatus::Finished(200));
+		assert_eq!(headers, vec![(b"content-type".to_vec(), b"text/plain".to_vec())]);
+		assert_eq!(body, b"first");
+
+		// Once all matching fixtures are used, the last one keeps being replayed.
+		for _ in 0..2 {
+			let (status, _, body) = get(&mut api);
+			assert_eq!(status, HttpRequestStatus::Finished(404));
+			assert_eq!(body, b"second");
+		}
+
+		let id = api.request_start("POST", "http://example.com/price").unwrap();
+		api.request_write_body(id, &[1, 2], Some(deadline)).unwrap();
+		api.request_write_body(id, &[], Some(deadline)).unwrap();
+		assert_eq!(api.response_wait(&[id], Some(deadline))[0], HttpRequestStatus::Finished(201));
+		assert_eq!(read_body(&mut api, id, deadline), vec![0xff, 0x00]);
+
+		// The body of the request doesn't match the fixture.
+		let id = api.request_start("POST", "http://example.com/price").unwrap();
+		api.request_write_body(id, &[3], Some(deadline)).unwrap();
+		api.request_write_body(id, &[], Some(deadline)).unwrap();
+		assert_eq!(api.response_wait(&[id], Some(deadline))[0], HttpRequestStatus::IoError);
+	}
+
+	#[test]
+	fn replay_injects_failures_and_latency() {
+		let mut api = build_replay_api(
+			r#"[
+				{ "request": { "method": "GET", "uri": "http://example.com/fail" }, "failure": "ioError" },
+				{ "request": { "method": "GET", "uri": "http://example.com/hang" }, "failure": "timeout" },
+				{
+					"request": { "method": "GET", "uri": "http://example.com/slow" },
+					"response": { "status": 200 },
+					"latencyMs": 300
+				}
+			]"#,
+		);
+
+		let wait = |api: &mut HttpApi, uri: &str, timeout: u64| {
+			let deadline = timestamp::now().add(Duration::from_millis(timeout));
+			let id = api.request_start("GET", uri).unwrap();
+			api.request_write_body(id, &[], Some(deadline)).unwrap();
+			api.response_wait(&[id], Some(deadline))[0]
+		};
+
+		assert_eq!(wait(&mut api, "http://example.com/fail", 10_000), HttpRequestStatus::IoError);
+		assert_eq!(
+			wait(&mut api, "http://example.com/hang", 200),
+			HttpRequestStatus::DeadlineReached
+		);
+		assert_eq!(
+			wait(&mut api, "http://example.com/slow", 100),
+			HttpRequestStatus::DeadlineReached
+		);
+
+		let started = std::time::Instant::now();
+		assert_eq!(
+			wait(&mut api, "http://example.com/slow", 10_000),
+			HttpRequestStatus::Finished(200)
+		);
+		assert!(started.elapsed() >= std::time::Duration::from_millis(300));
+
+		assert_eq!(
+			wait(&mut api, "http://example.com/unknown", 10_000),
+			HttpRequestStatus::IoError
+		);
+	}
+
+	#[test]
+	fn records_fixtures() {
+		let deadline = timestamp::now().add(Duration::from_millis(10_000));
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("fixtures.json");
+
+		let (mut api, addr) = build_api_server!(
+			SharedClient::with_mode(&HttpMode::new(Some(&path), None).unwrap()),
+			hyper::Response::new(hyper::Body::from("Hello World!"))
+		);
+		let uri = format!("http://{}/", addr);
+
+		let id = api.request_start("POST", &uri).unwrap();
+		api.request_write_body(id, b"ping", Some(deadline)).unwrap();
+		api.request_write_body(id, &[], Some(deadline)).unwrap();
+		assert_eq!(api.response_wait(&[id], Some(deadline))[0], HttpRequestStatus::Finished(200));
+		assert_eq!(read_body(&mut api, id, deadline), b"Hello World!");
+
+		// The fixtures are written in the background.
+		let fixtures = loop {
+			let fixtures = std::fs::read_to_string(&path).unwrap();
+			if serde_json::from_str::<Vec<serde_json::Value>>(&fixtures)
+				.is_ok_and(|fixtures| !fixtures.is_empty())
+			{
+				break fixtures
+			}
+			assert!(timestamp::now() < deadline, "fixtures weren't written");
+			std::thread::sleep(std::time::Duration::from_millis(10));
+		};
+
+		// Replaying the recorded fixtures doesn't need the server.
+		let mut api = build_replay_api(&fixtures);
+		let id = api.request_start("POST", &uri).unwrap();
+		api.request_write_body(id, b"ping", Some(deadline)).unwrap();
+		api.request_write_body(id, &[], Some(deadline)).unwrap();
+		assert_eq!(api.response_wait(&[id], Some(deadline))[0], HttpRequestStatus::Finished(200));
+		assert_eq!(read_body(&mut api, id, deadline), b"Hello World!");
+	}
+
+	#[test]
+	fn invalid_fixtures_are_rejected() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("fixtures.json");
+
+		// Missing file.
+		assert_eq!(
+			HttpMode::new(None, Some(&path)).unwrap_err().kind(),
+			std::io::ErrorKind::NotFound
+		);
+
+		// Invalid fixtures.
+		std::fs::write(&path, r#"[{ "request": { "method": "GET" } }]"#).unwrap();
+		assert_eq!(
+			HttpMode::new(None, Some(&path)).unwrap_err().kind(),
+			std::io::ErrorKind::InvalidData
+		);
+
+		// Recording and replaying at the same time.
+		std::fs::write(&path, "[]").unwrap();
+		assert_eq!(
+			HttpMode::new(Some(&dir.path().join("recorded.json")), Some(&path))
+				.unwrap_err()
+				.kind(),
+			std::io::ErrorKind::InvalidInput
+		);
+
+		// The directory to record to doesn't exist.
+		assert!(
+			HttpMode::new(Some(&dir.path().join("missing").join("recorded.json")), None).is_err()
+		);
 	}
 }
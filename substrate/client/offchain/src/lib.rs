@@ -54,6 +54,7 @@ use threadpool::ThreadPool;
 
 mod api;
 
+pub use api::{FixturesRecorder, HttpMode, ReplayFixtures};
 pub use sp_core::offchain::storage::OffchainDb;
 pub use sp_offchain::{OffchainWorkerApi, STORAGE_PREFIX};
 
@@ -110,6 +111,10 @@ pub struct OffchainWorkerOptions<RA, Block: traits::Block, Storage, CE> {
 	///
 	/// If not enabled, any http request will panic.
 	pub enable_http_requests: bool,
+	/// Whether http requests are performed, recorded or replayed from recorded fixtures.
+	///
+	/// See [`HttpMode`] for details.
+	pub http_mode: HttpMode,
 	/// Callback to create custom [`Extension`]s that should be registered for the
 	/// `offchain_worker` runtime call.
 	///
@@ -151,6 +156,7 @@ impl<RA, Block: traits::Block, Storage> OffchainWorkers<RA, Block, Storage> {
 			network_provider,
 			is_validator,
 			enable_http_requests,
+			http_mode,
 			custom_extensions,
 		}: OffchainWorkerOptions<RA, Block, Storage, CE>,
 	) -> Self {
@@ -160,7 +166,7 @@ impl<RA, Block: traits::Block, Storage> OffchainWorkers<RA, Block, Storage> {
 				"offchain-worker".into(),
 				num_cpus::get(),
 			)),
-			shared_http_client: api::SharedClient::new(),
+			shared_http_client: api::SharedClient::with_mode(&http_mode),
 			enable_http_requests,
 			keystore,
 			offchain_db: offchain_db.map(OffchainDb::new),
@@ -455,6 +461,7 @@ mod tests {
 			network_provider: network,
 			is_validator: false,
 			enable_http_requests: false,
+			http_mode: HttpMode::Live,
 			custom_extensions: |_| Vec::new(),
 		});
 		futures::executor::block_on(offchain.on_block_imported(&header));
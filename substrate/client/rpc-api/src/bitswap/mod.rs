@@ -0,0 +1,37 @@
+// This file is part of Substrate.
+
+// Copyright (C) Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Substrate bitswap API.
+
+pub mod error;
+
+use jsonrpsee::{core::RpcResult, proc_macros::rpc};
+use sp_core::Bytes;
+
+/// Substrate bitswap RPC API
+#[rpc(client, server)]
+pub trait BitswapApi {
+	/// Fetch the content identified by `cid` over bitswap, asking the peers with the given IDs in
+	/// turn. UnixFS files are reassembled from their blocks.
+	///
+	/// Substrate nodes only serve transactions indexed by their Blake2b-256 hash, so only version 1
+	/// CIDs with a Blake2b-256 multihash, for the root and every block of a file, can be fetched
+	/// from them.
+	#[method(name = "bitswap_fetch")]
+	async fn fetch(&self, cid: String, peers: Vec<String>) -> RpcResult<Bytes>;
+}
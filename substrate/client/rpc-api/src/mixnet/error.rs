@@ -33,6 +33,7 @@ impl From<Error> for jsonrpsee::core::Error {
 			sc_mixnet::Error::ServiceUnavailable => BASE_ERROR + 1,
 			sc_mixnet::Error::NoReply => BASE_ERROR + 2,
 			sc_mixnet::Error::BadReply => BASE_ERROR + 3,
+			sc_mixnet::Error::Timeout => BASE_ERROR + 4,
 			sc_mixnet::Error::Post(PostErr::TooManyFragments) => BASE_ERROR + 101,
 			sc_mixnet::Error::Post(PostErr::SessionMixnodesNotKnown(_)) => BASE_ERROR + 102,
 			sc_mixnet::Error::Post(PostErr::SessionDisabled(_)) => BASE_ERROR + 103,
@@ -42,6 +43,8 @@ impl From<Error> for jsonrpsee::core::Error {
 			sc_mixnet::Error::Post(_) => BASE_ERROR + 100,
 			sc_mixnet::Error::Remote(RemoteErr::Other(_)) => BASE_ERROR + 200,
 			sc_mixnet::Error::Remote(RemoteErr::Decode(_)) => BASE_ERROR + 201,
+			sc_mixnet::Error::Remote(RemoteErr::UnsupportedProtocol(_)) => BASE_ERROR + 202,
+			sc_mixnet::Error::Remote(RemoteErr::ReplyTooLarge(_)) => BASE_ERROR + 203,
 		};
 		CallError::Custom(ErrorObject::owned(code, err.0.to_string(), None::<()>)).into()
 	}
@@ -20,12 +20,32 @@
 
 pub mod error;
 
+use crate::state::ReadProof;
 use jsonrpsee::{core::RpcResult, proc_macros::rpc};
-use sp_core::Bytes;
+use sp_core::{storage::StorageKey, Bytes};
 
 #[rpc(client, server)]
-pub trait MixnetApi {
+pub trait MixnetApi<Hash> {
 	/// Submit encoded extrinsic over the mixnet for inclusion in block.
 	#[method(name = "mixnet_submitExtrinsic")]
 	async fn submit_extrinsic(&self, extrinsic: Bytes) -> RpcResult<()>;
+
+	/// Send a request over the mixnet to be handled by the handler registered for `protocol` on
+	/// a mixnode. Returns the reply payload.
+	#[method(name = "mixnet_request")]
+	async fn request(&self, protocol: String, payload: Bytes) -> RpcResult<Bytes>;
+
+	/// Anonymously fetch a storage read proof for the given keys over the mixnet. If `at` is not
+	/// given, the best block of the answering mixnode is used. The proof is not checked.
+	#[method(name = "mixnet_readProof")]
+	async fn read_proof(
+		&self,
+		keys: Vec<StorageKey>,
+		at: Option<Hash>,
+	) -> RpcResult<ReadProof<Hash>>;
+
+	/// Anonymously fetch the data of broadcast statements matching all of the given topics from
+	/// the statement store of a mixnode.
+	#[method(name = "mixnet_statementBroadcasts")]
+	async fn statement_broadcasts(&self, match_all_topics: Vec<[u8; 32]>) -> RpcResult<Vec<Bytes>>;
 }
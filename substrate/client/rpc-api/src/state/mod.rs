@@ -289,4 +289,40 @@ pub trait StateApi<Hash> {
 		storage_keys: Option<String>,
 		methods: Option<String>,
 	) -> RpcResult<sp_rpc::tracing::TraceBlockResponse>;
+
+	/// The `profileBlock` RPC re-executes a single block like [`traceBlock`](#method.trace_block)
+	/// and measures the time spent in and the storage proof size added by every span.
+	///
+	/// The measurements are aggregated per stack of spans. Storage reads and writes are attributed
+	/// to the host function performing them. Besides the aggregated frames, the profile is returned
+	/// in the folded stacks format, which can be rendered as a flamegraph, e.g.:
+	///
+	/// ```text
+	/// curl \
+	/// 	-H "Content-Type: application/json" \
+	/// 	-d '{"id":1, "jsonrpc":"2.0", "method": "state_profileBlock", \
+	/// 		"params": ["0xb246acf1adea1f801ce15c77a5fa7d8f2eb8fed466978bcee172cc02cf64e264", "pallet,frame,sp_io", "time"]}' \
+	/// 	http://localhost:9933/ | jq -r .result.folded | inferno-flamegraph > block.svg
+	/// ```
+	///
+	/// The same node requirements as for [`traceBlock`](#method.trace_block) apply, in particular
+	/// the runtime must be compiled with tracing enabled.
+	///
+	/// ### Params
+	///
+	/// - `block` (param index 0): Hash of the block to profile.
+	/// - `targets` (param index 1): String of comma separated (no spaces) targets of the spans to
+	/// include, defaults to `pallet,frame,sp_io`. The measurements of spans not matching any of the
+	/// targets are attributed to their closest ancestor that does. If an empty string is specified
+	/// all spans are included. Storage accesses are only broken down if host function spans
+	/// (`sp_io`) are included.
+	/// - `metric` (param index 2): Either `time` (nanoseconds, default) or `proofSize` (bytes),
+	/// the metric used to weigh the folded stacks.
+	#[method(name = "state_profileBlock", blocking)]
+	fn profile_block(
+		&self,
+		block: Hash,
+		targets: Option<String>,
+		metric: Option<sp_rpc::tracing::ProfileMetric>,
+	) -> RpcResult<sp_rpc::tracing::BlockProfile>;
 }
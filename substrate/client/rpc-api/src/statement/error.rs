@@ -35,6 +35,9 @@ pub enum Error {
 	/// Call to an unsafe RPC was denied.
 	#[error(transparent)]
 	UnsafeRpcCalled(#[from] crate::policy::UnsafeRpcError),
+	/// The topic filter has too many topics.
+	#[error("Topic filter has too many topics")]
+	TooManyTopics,
 }
 
 /// Base error code for all statement errors.
@@ -49,6 +52,12 @@ impl From<Error> for JsonRpseeError {
 				None::<()>,
 			))
 			.into(),
+			Error::TooManyTopics => CallError::Custom(ErrorObject::owned(
+				BASE_ERROR + 2,
+				format!("Topic filter has more than {} topics", super::MAX_FILTER_TOPICS),
+				None::<()>,
+			))
+			.into(),
 			Error::UnsafeRpcCalled(e) => e.into(),
 		}
 	}
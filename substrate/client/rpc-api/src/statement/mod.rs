@@ -19,10 +19,26 @@
 //! Substrate Statement Store RPC API.
 
 use jsonrpsee::{core::RpcResult, proc_macros::rpc};
+use serde::{Deserialize, Serialize};
 use sp_core::Bytes;
 
 pub mod error;
 
+/// Maximum number of topics in a [`TopicFilter`].
+pub const MAX_FILTER_TOPICS: usize = 128;
+
+/// Filter on the topics of statements.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TopicFilter {
+	/// Matches all statements.
+	Any,
+	/// Matches statements which include all the given topics.
+	MatchAll(Vec<[u8; 32]>),
+	/// Matches statements which include at least one of the given topics.
+	MatchAny(Vec<[u8; 32]>),
+}
+
 /// Substrate statement RPC API
 #[rpc(client, server)]
 pub trait StatementApi {
@@ -57,4 +73,17 @@ pub trait StatementApi {
 	/// Remove a statement from the store.
 	#[method(name = "statement_remove")]
 	fn remove(&self, statement_hash: [u8; 32]) -> RpcResult<()>;
+
+	/// Subscribe to new statements which match `filter` and whose decryption key is identified as
+	/// `dest`, SCALE-encoded.
+	///
+	/// If `dest` is not given, only statements without a `DecryptionKey` field are returned. The
+	/// filter may contain at most [`MAX_FILTER_TOPICS`] topics. The subscription is closed if the
+	/// client doesn't keep up with the new statements.
+	#[subscription(
+		name = "statement_subscribeStatement" => "statement_statement",
+		unsubscribe = "statement_unsubscribeStatement",
+		item = Bytes,
+	)]
+	fn subscribe_statement(&self, filter: TopicFilter, dest: Option<[u8; 32]>);
 }
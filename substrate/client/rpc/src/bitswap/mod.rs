@@ -0,0 +1,74 @@
+// This file is part of Substrate.
+
+// Copyright (C) Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Substrate bitswap API.
+
+#[cfg(test)]
+mod tests;
+
+use self::error::Error;
+use jsonrpsee::core::{async_trait, RpcResult};
+use sc_network::{NetworkRequest, PeerId};
+use sc_network_bitswap::{BitswapClient, Cid};
+/// Re-export the API for backward compatibility.
+pub use sc_rpc_api::bitswap::*;
+use sc_rpc_api::DenyUnsafe;
+use sp_core::Bytes;
+use std::str::FromStr;
+
+/// Maximum size of the content fetched by `bitswap_fetch`.
+const MAX_CONTENT_SIZE: usize = 16 * 1024 * 1024;
+
+/// Maximum number of blocks the content fetched by `bitswap_fetch` may span.
+const MAX_CONTENT_BLOCKS: usize = 4096;
+
+/// Bitswap API
+pub struct Bitswap<N> {
+	client: BitswapClient<N>,
+	deny_unsafe: DenyUnsafe,
+}
+
+impl<N> Bitswap<N> {
+	/// Create new instance of Bitswap API.
+	pub fn new(network: N, deny_unsafe: DenyUnsafe) -> Self {
+		Bitswap { client: BitswapClient::new(network), deny_unsafe }
+	}
+}
+
+#[async_trait]
+impl<N> BitswapApiServer for Bitswap<N>
+where
+	N: NetworkRequest + Send + Sync + 'static,
+{
+	async fn fetch(&self, cid: String, peers: Vec<String>) -> RpcResult<Bytes> {
+		self.deny_unsafe.check_if_safe()?;
+
+		let cid = Cid::try_from(cid.as_str()).map_err(|_| Error::InvalidCid(cid))?;
+		let peers = peers
+			.into_iter()
+			.map(|peer| PeerId::from_str(&peer).map_err(|_| Error::InvalidPeerId(peer)))
+			.collect::<Result<Vec<_>, _>>()?;
+
+		let content = self
+			.client
+			.fetch(&peers, &cid, MAX_CONTENT_SIZE, MAX_CONTENT_BLOCKS)
+			.await
+			.map_err(Error::from)?;
+		Ok(content.into())
+	}
+}
@@ -0,0 +1,82 @@
+// This file is part of Substrate.
+
+// Copyright (C) Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use super::*;
+use assert_matches::assert_matches;
+use futures::channel::oneshot;
+use jsonrpsee::{core::Error as JsonRpseeError, types::error::CallError};
+use sc_network::{IfDisconnected, ProtocolName, RequestFailure};
+use sc_network_bitswap::{build_file, DEFAULT_CHUNK_SIZE};
+
+/// Network without any reachable peer.
+struct Unreachable;
+
+#[async_trait]
+impl NetworkRequest for Unreachable {
+	async fn request(
+		&self,
+		_target: PeerId,
+		_protocol: ProtocolName,
+		_request: Vec<u8>,
+		_connect: IfDisconnected,
+	) -> Result<Vec<u8>, RequestFailure> {
+		Err(RequestFailure::NotConnected)
+	}
+
+	fn start_request(
+		&self,
+		_target: PeerId,
+		_protocol: ProtocolName,
+		_request: Vec<u8>,
+		tx: oneshot::Sender<Result<Vec<u8>, RequestFailure>>,
+		_connect: IfDisconnected,
+	) {
+		let _ = tx.send(Err(RequestFailure::NotConnected));
+	}
+}
+
+fn error_code(result: RpcResult<Bytes>) -> i32 {
+	match result {
+		Err(JsonRpseeError::Call(CallError::Custom(err))) => err.code(),
+		result => panic!("Unexpected result: {result:?}"),
+	}
+}
+
+#[tokio::test]
+async fn fetch_reports_errors() {
+	let bitswap = Bitswap::new(Unreachable, DenyUnsafe::No);
+	let cid = build_file(b"hello bitswap", DEFAULT_CHUNK_SIZE).cid.to_string();
+	let peer = PeerId::random().to_string();
+
+	assert_eq!(error_code(bitswap.fetch("not a cid".into(), vec![peer.clone()]).await), 9001);
+	assert_eq!(error_code(bitswap.fetch(cid.clone(), vec!["not a peer".into()]).await), 9002);
+	assert_eq!(error_code(bitswap.fetch(cid, vec![peer]).await), 9003);
+}
+
+#[tokio::test]
+async fn fetch_considered_unsafe() {
+	let bitswap = Bitswap::new(Unreachable, DenyUnsafe::Yes);
+	let cid = build_file(b"hello bitswap", DEFAULT_CHUNK_SIZE).cid.to_string();
+
+	assert_matches!(
+		bitswap.fetch(cid, vec![PeerId::random().to_string()]).await,
+		Err(JsonRpseeError::Call(CallError::Custom(err))) => {
+			assert_eq!(err.message(), "RPC call is unsafe to be called externally")
+		}
+	);
+}
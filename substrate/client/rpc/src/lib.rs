@@ -32,6 +32,7 @@ pub use jsonrpsee::core::{
 pub use sc_rpc_api::DenyUnsafe;
 
 pub mod author;
+pub mod bitswap;
 pub mod chain;
 pub mod dev;
 pub mod mixnet;
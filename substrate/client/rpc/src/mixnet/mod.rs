@@ -18,11 +18,12 @@
 
 //! Substrate mixnet API.
 
-use jsonrpsee::core::{async_trait, RpcResult};
+use codec::{Decode, Encode};
+use jsonrpsee::core::{async_trait, DeserializeOwned, RpcResult, Serialize};
 use sc_mixnet::Api;
-use sc_rpc_api::mixnet::error::Error;
 pub use sc_rpc_api::mixnet::MixnetApiServer;
-use sp_core::Bytes;
+use sc_rpc_api::{mixnet::error::Error, state::ReadProof};
+use sp_core::{storage::StorageKey, Bytes};
 
 /// Mixnet API.
 pub struct Mixnet(futures::lock::Mutex<Api>);
@@ -35,7 +36,10 @@ impl Mixnet {
 }
 
 #[async_trait]
-impl MixnetApiServer for Mixnet {
+impl<Hash> MixnetApiServer<Hash> for Mixnet
+where
+	Hash: Encode + Decode + Serialize + DeserializeOwned + Send + Sync + 'static,
+{
 	async fn submit_extrinsic(&self, extrinsic: Bytes) -> RpcResult<()> {
 		// We only hold the lock while pushing the request into the requests channel
 		let fut = {
@@ -44,4 +48,33 @@ impl MixnetApiServer for Mixnet {
 		};
 		Ok(fut.await.map_err(Error)?)
 	}
+
+	async fn request(&self, protocol: String, payload: Bytes) -> RpcResult<Bytes> {
+		let fut = {
+			let mut api = self.0.lock().await;
+			api.request(protocol, payload).await
+		};
+		Ok(fut.await.map_err(Error)?.into())
+	}
+
+	async fn read_proof(
+		&self,
+		keys: Vec<StorageKey>,
+		at: Option<Hash>,
+	) -> RpcResult<ReadProof<Hash>> {
+		let fut = {
+			let mut api = self.0.lock().await;
+			api.read_proof(at, keys.into_iter().map(|key| key.0).collect()).await
+		};
+		let (at, proof) = fut.await.map_err(Error)?;
+		Ok(ReadProof { at, proof: proof.into_iter().map(Into::into).collect() })
+	}
+
+	async fn statement_broadcasts(&self, match_all_topics: Vec<[u8; 32]>) -> RpcResult<Vec<Bytes>> {
+		let fut = {
+			let mut api = self.0.lock().await;
+			api.statement_broadcasts(match_all_topics).await
+		};
+		Ok(fut.await.map_err(Error)?.into_iter().map(Into::into).collect())
+	}
 }
@@ -157,6 +157,14 @@ where
 		methods: Option<String>,
 	) -> Result<sp_rpc::tracing::TraceBlockResponse, Error>;
 
+	/// Profile the execution of block
+	fn profile_block(
+		&self,
+		block: Block::Hash,
+		targets: Option<String>,
+		metric: sp_rpc::tracing::ProfileMetric,
+	) -> Result<sp_rpc::tracing::BlockProfile, Error>;
+
 	/// New runtime version subscription
 	fn subscribe_runtime_version(&self, sink: SubscriptionSink);
 
@@ -325,6 +333,23 @@ where
 			.map_err(Into::into)
 	}
 
+	/// Re-execute the given block with the tracing targets given in `targets`
+	/// and measure the time and proof size of every span.
+	///
+	/// Note: requires the node to run with `--rpc-methods=Unsafe`.
+	/// Note: requires runtimes compiled with wasm tracing support, `--features with-tracing`.
+	fn profile_block(
+		&self,
+		block: Block::Hash,
+		targets: Option<String>,
+		metric: Option<sp_rpc::tracing::ProfileMetric>,
+	) -> RpcResult<sp_rpc::tracing::BlockProfile> {
+		self.deny_unsafe.check_if_safe()?;
+		self.backend
+			.profile_block(block, targets, metric.unwrap_or_default())
+			.map_err(Into::into)
+	}
+
 	fn subscribe_runtime_version(&self, sink: SubscriptionSink) -> SubscriptionResult {
 		self.backend.subscribe_runtime_version(sink);
 		Ok(())
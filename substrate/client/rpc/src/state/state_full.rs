@@ -474,6 +474,17 @@ where
 		.trace_block()
 		.map_err(|e| invalid_block::<Block>(block, None, e.to_string()))
 	}
+
+	fn profile_block(
+		&self,
+		block: Block::Hash,
+		targets: Option<String>,
+		metric: sp_rpc::tracing::ProfileMetric,
+	) -> std::result::Result<sp_rpc::tracing::BlockProfile, Error> {
+		sc_tracing::block::BlockExecutor::new(self.client.clone(), block, targets, None, None)
+			.profile_block(metric)
+			.map_err(|e| invalid_block::<Block>(block, None, e.to_string()))
+	}
 }
 
 impl<BE, Block, Client> ChildStateBackend<Block, Client> for FullState<BE, Block, Client>
@@ -18,11 +18,17 @@
 
 //! Substrate statement store API.
 
+use crate::SubscriptionTaskExecutor;
 use codec::{Decode, Encode};
-use jsonrpsee::core::{async_trait, RpcResult};
+use futures::{FutureExt, StreamExt};
+use jsonrpsee::{
+	core::{async_trait, Error as JsonRpseeError, RpcResult},
+	types::SubscriptionResult,
+	SubscriptionSink,
+};
 /// Re-export the API for backward compatibility.
-pub use sc_rpc_api::statement::{error::Error, StatementApiServer};
-use sc_rpc_api::DenyUnsafe;
+pub use sc_rpc_api::statement::{error::Error, StatementApiServer, TopicFilter};
+use sc_rpc_api::{statement::MAX_FILTER_TOPICS, DenyUnsafe};
 use sp_core::Bytes;
 use sp_statement_store::{StatementSource, SubmitResult};
 use std::sync::Arc;
@@ -31,6 +37,7 @@ use std::sync::Arc;
 pub struct StatementStore {
 	store: Arc<dyn sp_statement_store::StatementStore>,
 	deny_unsafe: DenyUnsafe,
+	executor: SubscriptionTaskExecutor,
 }
 
 impl StatementStore {
@@ -38,8 +45,9 @@ impl StatementStore {
 	pub fn new(
 		store: Arc<dyn sp_statement_store::StatementStore>,
 		deny_unsafe: DenyUnsafe,
+		executor: SubscriptionTaskExecutor,
 	) -> Self {
-		StatementStore { store, deny_unsafe }
+		StatementStore { store, deny_unsafe, executor }
 	}
 }
 
@@ -105,4 +113,37 @@ impl StatementApiServer for StatementStore {
 	fn remove(&self, hash: [u8; 32]) -> RpcResult<()> {
 		Ok(self.store.remove(&hash).map_err(|e| Error::StatementStore(e.to_string()))?)
 	}
+
+	fn subscribe_statement(
+		&self,
+		mut sink: SubscriptionSink,
+		filter: TopicFilter,
+		dest: Option<[u8; 32]>,
+	) -> SubscriptionResult {
+		let filter = match filter {
+			TopicFilter::MatchAll(topics) | TopicFilter::MatchAny(topics)
+				if topics.len() > MAX_FILTER_TOPICS =>
+			{
+				let _ = sink.reject(JsonRpseeError::from(Error::TooManyTopics));
+				return Ok(())
+			},
+			TopicFilter::Any => sp_statement_store::TopicFilter::Any,
+			TopicFilter::MatchAll(topics) => sp_statement_store::TopicFilter::MatchAll(topics),
+			TopicFilter::MatchAny(topics) => sp_statement_store::TopicFilter::MatchAny(topics),
+		};
+
+		let stream = match self.store.subscribe(filter, dest) {
+			Ok(stream) => stream.map(|statement| Bytes::from(statement.encode())),
+			Err(e) => {
+				let _ = sink.reject(JsonRpseeError::from(Error::StatementStore(e.to_string())));
+				return Ok(())
+			},
+		};
+
+		let fut = async move {
+			sink.pipe_from_stream(stream).await;
+		};
+		self.executor.spawn("substrate-rpc-subscription", Some("rpc"), fut.boxed());
+		Ok(())
+	}
 }
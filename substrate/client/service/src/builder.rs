@@ -44,19 +44,21 @@ use sc_keystore::LocalKeystore;
 use sc_network::{
 	config::{FullNetworkConfiguration, SyncMode},
 	peer_store::PeerStore,
-	NetworkService, NetworkStateInfo, NetworkStatusProvider,
+	NetworkRequest, NetworkService, NetworkStateInfo, NetworkStatusProvider,
 };
 use sc_network_bitswap::BitswapRequestHandler;
 use sc_network_common::role::Roles;
 use sc_network_light::light_client_requests::handler::LightClientRequestHandler;
 use sc_network_sync::{
-	block_relay_protocol::BlockRelayParams, block_request_handler::BlockRequestHandler,
-	engine::SyncingEngine, service::network::NetworkServiceProvider,
-	state_request_handler::StateRequestHandler, warp::WarpSyncParams,
-	warp_request_handler::RequestHandler as WarpSyncRequestHandler, SyncingService,
+	aux_schema::SyncProgressStore, block_relay_protocol::BlockRelayParams,
+	block_request_handler::BlockRequestHandler, engine::SyncingEngine,
+	service::network::NetworkServiceProvider, state_request_handler::StateRequestHandler,
+	warp::WarpSyncParams, warp_request_handler::RequestHandler as WarpSyncRequestHandler,
+	SyncingService,
 };
 use sc_rpc::{
 	author::AuthorApiServer,
+	bitswap::BitswapApiServer,
 	chain::ChainApiServer,
 	offchain::OffchainApiServer,
 	state::{ChildStateApiServer, StateApiServer},
@@ -274,6 +276,7 @@ pub fn new_wasm_executor<H: HostFunctions>(config: &Configuration) -> WasmExecut
 		.with_offchain_heap_alloc_strategy(strategy)
 		.with_max_runtime_instances(config.max_runtime_instances)
 		.with_runtime_cache_size(config.runtime_cache_size)
+		.with_artifact_cache(config.runtime_artifact_cache.clone())
 		.build()
 }
 
@@ -340,14 +343,14 @@ where
 
 /// Shared network instance implementing a set of mandatory traits.
 pub trait SpawnTaskNetwork<Block: BlockT>:
-	NetworkStateInfo + NetworkStatusProvider + Send + Sync + 'static
+	NetworkStateInfo + NetworkStatusProvider + NetworkRequest + Send + Sync + 'static
 {
 }
 
 impl<T, Block> SpawnTaskNetwork<Block> for T
 where
 	Block: BlockT,
-	T: NetworkStateInfo + NetworkStatusProvider + Send + Sync + 'static,
+	T: NetworkStateInfo + NetworkStatusProvider + NetworkRequest + Send + Sync + 'static,
 {
 }
 
@@ -504,6 +507,7 @@ where
 			transaction_pool.clone(),
 			keystore.clone(),
 			system_rpc_tx.clone(),
+			network.clone(),
 			&config,
 			backend.clone(),
 			&*rpc_builder,
@@ -603,6 +607,7 @@ fn gen_rpc_module<TBl, TBackend, TCl, TRpc, TExPool>(
 	transaction_pool: Arc<TExPool>,
 	keystore: KeystorePtr,
 	system_rpc_tx: TracingUnboundedSender<sc_rpc::system::Request<TBl>>,
+	network: Arc<dyn SpawnTaskNetwork<TBl>>,
 	config: &Configuration,
 	backend: Arc<TBackend>,
 	rpc_builder: &(dyn Fn(DenyUnsafe, SubscriptionTaskExecutor) -> Result<RpcModule<TRpc>, Error>),
@@ -681,6 +686,12 @@ where
 		rpc_api.merge(offchain).map_err(|e| Error::Application(e.into()))?;
 	}
 
+	if config.network.ipfs_server {
+		let bitswap = sc_rpc::bitswap::Bitswap::new(network, deny_unsafe).into_rpc();
+
+		rpc_api.merge(bitswap).map_err(|e| Error::Application(e.into()))?;
+	}
+
 	// Part of the RPC v2 spec.
 	rpc_api.merge(transaction_v2).map_err(|e| Error::Application(e.into()))?;
 	rpc_api.merge(chain_head_v2).map_err(|e| Error::Application(e.into()))?;
@@ -720,6 +731,9 @@ pub struct BuildNetworkParams<'a, TBl: BlockT, TExPool, TImpQu, TCl> {
 	/// User specified block relay params. If not specified, the default
 	/// block request handler will be used.
 	pub block_relay: Option<BlockRelayParams<TBl>>,
+	/// Database the warp and state sync progress is persisted to, usually the client. If not
+	/// specified, an interrupted warp or state sync starts from scratch.
+	pub sync_progress_store: Option<Arc<dyn SyncProgressStore>>,
 }
 
 /// Build the network service, the network status sinks and an RPC sender.
@@ -759,6 +773,7 @@ where
 		block_announce_validator_builder,
 		warp_sync_params,
 		block_relay,
+		sync_progress_store,
 	} = params;
 
 	if warp_sync_params.is_none() && config.network.sync_mode.is_warp() {
@@ -876,8 +891,9 @@ where
 		);
 	net_config.add_notification_protocol(transactions_config);
 
-	// Create `PeerStore` and initialize it with bootnode peer ids.
-	let peer_store = PeerStore::new(
+	// Create `PeerStore` and initialize it with bootnode peer ids. It is persisted in the database
+	// directory, if there is one.
+	let mut peer_store = PeerStore::new(
 		net_config
 			.network_config
 			.boot_nodes
@@ -885,6 +901,9 @@ where
 			.map(|bootnode| bootnode.peer_id)
 			.collect(),
 	);
+	if let Some(path) = config.database.path() {
+		peer_store = peer_store.with_persistence(path.join("peer_store.json"));
+	}
 	let peer_store_handle = peer_store.handle();
 	spawn_handle.spawn("peer-store", Some("networking"), peer_store.run());
 
@@ -903,6 +922,7 @@ where
 		state_request_protocol_name,
 		warp_request_protocol_name,
 		peer_store_handle.clone(),
+		sync_progress_store,
 	)?;
 	let sync_service_import_queue = sync_service.clone();
 	let sync_service = Arc::new(sync_service);
@@ -0,0 +1,371 @@
+// This file is part of Substrate.
+
+// Copyright (C) Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Export and import of state snapshots.
+//!
+//! A state snapshot contains the state of a finalized block together with the block itself and
+//! a warp sync proof of its finality starting at genesis. A fresh node can be bootstrapped from
+//! a snapshot without downloading the state over the network, in the same way as after warp sync.
+//!
+//! The state is written and read in chunks of bounded size, so that the snapshot is never held
+//! in memory as a single encoded blob.
+
+use crate::error::Error;
+use codec::{Decode, Encode};
+use futures::future;
+use log::info;
+use sc_client_api::{BlockBackend, HeaderBackend, StorageProvider, UsageProvider};
+use sc_consensus::{
+	import_queue::{BlockImportError, BlockImportStatus, ImportQueue, IncomingBlock, Link},
+	ImportedState,
+};
+use sc_network_sync::warp::{EncodedProof, VerificationResult, WarpSyncProvider};
+use sp_consensus::BlockOrigin;
+use sp_core::storage::{well_known_keys, ChildInfo};
+use sp_runtime::{
+	traits::{Block as BlockT, Header as HeaderT, NumberFor, Zero},
+	Justifications,
+};
+use sp_state_machine::{KeyValueStates, KeyValueStorageLevel};
+use std::{
+	collections::BTreeMap,
+	io::{Read, Write},
+	sync::Arc,
+	task::Poll,
+};
+
+/// Magic bytes at the start of every state snapshot file.
+const SNAPSHOT_MAGIC: &[u8; 8] = b"sub-snap";
+
+/// Current version of the state snapshot format.
+const SNAPSHOT_VERSION: u32 = 1;
+
+/// Size of the key value pairs after which a state chunk is written.
+const MAX_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Everything but the state of a state snapshot. Written before the state chunks.
+#[derive(Encode, Decode)]
+struct SnapshotHeader<B: BlockT> {
+	/// Genesis hash of the chain.
+	genesis_hash: B::Hash,
+	/// Warp sync proofs from genesis to `header`.
+	warp_proofs: Vec<Vec<u8>>,
+	/// Header of the snapshot block.
+	header: B::Header,
+	/// Body of the snapshot block.
+	body: Option<Vec<B::Extrinsic>>,
+	/// Justifications of the snapshot block.
+	justifications: Option<Justifications>,
+}
+
+/// A chunk of the state of a state snapshot.
+#[derive(Encode, Decode)]
+enum SnapshotChunk {
+	/// Top trie key value pairs, without child trie roots.
+	Top(Vec<(Vec<u8>, Vec<u8>)>),
+	/// Default child trie key value pairs.
+	Child {
+		/// Unprefixed child trie storage key.
+		storage_key: Vec<u8>,
+		/// Child trie root as stored in the top trie.
+		root: Vec<u8>,
+		/// Child trie key value pairs.
+		key_values: Vec<(Vec<u8>, Vec<u8>)>,
+	},
+	/// End of the state.
+	End,
+}
+
+/// Child trie content of a state snapshot.
+struct SnapshotChildTrie {
+	/// Child trie root as stored in the top trie.
+	root: Vec<u8>,
+	/// Child trie key value pairs.
+	key_values: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+/// Buffers key value pairs and writes them in chunks of at most [`MAX_CHUNK_SIZE`].
+struct ChunkWriter<W> {
+	output: W,
+	key_values: Vec<(Vec<u8>, Vec<u8>)>,
+	size: usize,
+}
+
+impl<W: Write> ChunkWriter<W> {
+	fn new(output: W) -> Self {
+		Self { output, key_values: Vec::new(), size: 0 }
+	}
+
+	/// Buffer a key value pair, writing the buffered pairs with `chunk` once they are too large.
+	fn push(
+		&mut self,
+		key: Vec<u8>,
+		value: Vec<u8>,
+		chunk: impl FnOnce(Vec<(Vec<u8>, Vec<u8>)>) -> SnapshotChunk,
+	) -> Result<(), Error> {
+		self.size += key.len() + value.len();
+		self.key_values.push((key, value));
+		if self.size >= MAX_CHUNK_SIZE {
+			self.flush(chunk)?;
+		}
+		Ok(())
+	}
+
+	/// Write the buffered pairs with `chunk`, if any.
+	fn flush(
+		&mut self,
+		chunk: impl FnOnce(Vec<(Vec<u8>, Vec<u8>)>) -> SnapshotChunk,
+	) -> Result<(), Error> {
+		if !self.key_values.is_empty() {
+			self.size = 0;
+			let chunk = chunk(std::mem::take(&mut self.key_values));
+			self.output.write_all(&chunk.encode())?;
+		}
+		Ok(())
+	}
+}
+
+/// Convert the snapshot state into the format accepted by block import.
+fn imported_state(
+	top: Vec<(Vec<u8>, Vec<u8>)>,
+	children: BTreeMap<Vec<u8>, SnapshotChildTrie>,
+) -> KeyValueStates {
+	let mut levels = vec![KeyValueStorageLevel {
+		state_root: Vec::new(),
+		parent_storage_keys: Vec::new(),
+		key_values: top,
+	}];
+	levels.extend(children.into_iter().map(|(storage_key, child)| KeyValueStorageLevel {
+		state_root: child.root,
+		parent_storage_keys: vec![
+			ChildInfo::new_default(&storage_key).prefixed_storage_key().into_inner(),
+		],
+		key_values: child.key_values,
+	}));
+	KeyValueStates(levels)
+}
+
+/// Export a state snapshot of the latest finalized block covered by `warp_sync_provider`.
+///
+/// Returns the number of the snapshot block.
+pub fn export_state_snapshot<B, BA, C>(
+	client: Arc<C>,
+	warp_sync_provider: &dyn WarpSyncProvider<B>,
+	mut output: impl Write,
+) -> Result<NumberFor<B>, Error>
+where
+	C: UsageProvider<B> + StorageProvider<B, BA> + BlockBackend<B> + HeaderBackend<B>,
+	B: BlockT,
+	BA: sc_client_api::backend::Backend<B>,
+{
+	let genesis_hash = client.info().genesis_hash;
+
+	let mut warp_proofs = Vec::new();
+	let mut begin = genesis_hash;
+	let target = loop {
+		let proof = warp_sync_provider.generate(begin)?;
+		let (last_hash, is_finished) = warp_sync_provider.proof_end(&proof)?;
+		warp_proofs.push(proof.0);
+		if is_finished {
+			break last_hash
+		}
+		if last_hash == begin {
+			return Err(Error::Other("Warp sync proof generation does not advance".into()))
+		}
+		begin = last_hash;
+	};
+
+	let header = client
+		.header(target)?
+		.ok_or_else(|| Error::Other(format!("Missing header of snapshot block {target}")))?;
+	let body = client.block_body(target)?;
+	let justifications = client.justifications(target)?;
+	let number = *header.number();
+	info!("Exporting state of block #{} ({})", number, target);
+
+	let snapshot_header =
+		SnapshotHeader::<B> { genesis_hash, warp_proofs, header, body, justifications };
+	output.write_all(SNAPSHOT_MAGIC)?;
+	output.write_all(&SNAPSHOT_VERSION.encode())?;
+	output.write_all(&snapshot_header.encode())?;
+
+	let mut top = ChunkWriter::new(&mut output);
+	let mut child_tries = Vec::new();
+	for (key, value) in client.storage_pairs(target, None, None)? {
+		// Default child tries are written separately, their roots are recalculated on import.
+		match key.0.strip_prefix(well_known_keys::DEFAULT_CHILD_STORAGE_KEY_PREFIX) {
+			Some(storage_key) => child_tries.push((storage_key.to_vec(), value.0)),
+			None => top.push(key.0, value.0, SnapshotChunk::Top)?,
+		}
+	}
+	top.flush(SnapshotChunk::Top)?;
+
+	for (storage_key, root) in child_tries {
+		let child_info = ChildInfo::new_default(&storage_key);
+		let chunk = |key_values| SnapshotChunk::Child {
+			storage_key: storage_key.clone(),
+			root: root.clone(),
+			key_values,
+		};
+		let mut child = ChunkWriter::new(&mut output);
+		for key in client.child_storage_keys(target, child_info.clone(), None, None)? {
+			if let Some(value) = client.child_storage(target, &child_info, &key)? {
+				child.push(key.0, value.0, chunk)?;
+			}
+		}
+		child.flush(chunk)?;
+	}
+
+	output.write_all(&SnapshotChunk::End.encode())?;
+	output.flush()?;
+	Ok(number)
+}
+
+/// Import a state snapshot into a fresh database.
+///
+/// The finality of the snapshot block is verified with `warp_sync_provider` starting at the
+/// genesis authorities before any state is read, and the state root is verified when the block
+/// is imported. The state is read chunk by chunk, but handed to block import at once.
+pub async fn import_state_snapshot<B, IQ, C>(
+	client: Arc<C>,
+	mut import_queue: IQ,
+	warp_sync_provider: &dyn WarpSyncProvider<B>,
+	mut input: impl Read,
+) -> Result<(), Error>
+where
+	C: HeaderBackend<B> + Send + Sync + 'static,
+	B: BlockT,
+	IQ: ImportQueue<B> + 'static,
+{
+	let mut magic = [0u8; SNAPSHOT_MAGIC.len()];
+	input.read_exact(&mut magic)?;
+	if &magic != SNAPSHOT_MAGIC {
+		return Err(Error::Other("Input is not a state snapshot".into()))
+	}
+	let mut reader = codec::IoReader(input);
+	let version = u32::decode(&mut reader)
+		.map_err(|e| Error::Other(format!("Failed to decode snapshot version: {e}")))?;
+	if version != SNAPSHOT_VERSION {
+		return Err(Error::Other(format!("Unsupported state snapshot version {version}")))
+	}
+	let SnapshotHeader::<B> { genesis_hash, warp_proofs, header, body, justifications } =
+		Decode::decode(&mut reader)
+			.map_err(|e| Error::Other(format!("Failed to decode state snapshot: {e}")))?;
+
+	let info = client.info();
+	if genesis_hash != info.genesis_hash {
+		return Err(Error::Other("State snapshot belongs to a different chain".into()))
+	}
+	if !info.best_number.is_zero() {
+		return Err(Error::Other(
+			"State snapshots can only be imported into a fresh database".into(),
+		))
+	}
+
+	if warp_proofs.is_empty() {
+		return Err(Error::Other("State snapshot contains no warp sync proof".into()))
+	}
+	let hash = header.hash();
+	let mut set_id = 0;
+	let mut authorities = warp_sync_provider.current_authorities();
+	let mut proofs = warp_proofs.into_iter().peekable();
+	while let Some(proof) = proofs.next() {
+		let is_last = proofs.peek().is_none();
+		match warp_sync_provider.verify(&EncodedProof(proof), set_id, authorities.clone())? {
+			VerificationResult::Partial(new_set_id, new_authorities, _) if !is_last => {
+				set_id = new_set_id;
+				authorities = new_authorities;
+			},
+			VerificationResult::Complete(_, _, proved) if is_last && proved == header => {},
+			_ =>
+				return Err(Error::Other(format!(
+					"Warp sync proof does not prove finality of snapshot block {hash}"
+				))),
+		}
+	}
+	info!("Verified finality of snapshot block #{} ({})", header.number(), hash);
+
+	let mut top = Vec::new();
+	let mut children = BTreeMap::<_, SnapshotChildTrie>::new();
+	loop {
+		let chunk = SnapshotChunk::decode(&mut reader)
+			.map_err(|e| Error::Other(format!("Failed to decode state snapshot chunk: {e}")))?;
+		match chunk {
+			SnapshotChunk::Top(key_values) => top.extend(key_values),
+			SnapshotChunk::Child { storage_key, root, key_values } => children
+				.entry(storage_key)
+				.or_insert_with(|| SnapshotChildTrie { root, key_values: Vec::new() })
+				.key_values
+				.extend(key_values),
+			SnapshotChunk::End => break,
+		}
+	}
+
+	let state = ImportedState { block: hash, state: imported_state(top, children) };
+	let block = IncomingBlock {
+		hash,
+		header: Some(header),
+		body,
+		indexed_body: None,
+		justifications,
+		origin: None,
+		allow_missing_state: true,
+		skip_execution: true,
+		import_existing: true,
+		state: Some(state),
+	};
+	import_queue
+		.service_ref()
+		.import_blocks(BlockOrigin::NetworkInitialSync, vec![block]);
+
+	struct WaitLink {
+		result: Option<Result<(), String>>,
+	}
+
+	impl<B: BlockT> Link<B> for WaitLink {
+		fn blocks_processed(
+			&mut self,
+			_imported: usize,
+			_count: usize,
+			results: Vec<(Result<BlockImportStatus<NumberFor<B>>, BlockImportError>, B::Hash)>,
+		) {
+			self.result = results
+				.into_iter()
+				.next()
+				.map(|(result, _)| result.map(|_| ()).map_err(|e| e.to_string()));
+		}
+	}
+
+	let mut link = WaitLink { result: None };
+	let result = future::poll_fn(|cx| {
+		import_queue.poll_actions(cx, &mut link);
+		match link.result.take() {
+			Some(result) => Poll::Ready(result),
+			None => Poll::Pending,
+		}
+	})
+	.await;
+
+	match result {
+		Ok(_) => {
+			info!("🎉 Imported state snapshot. Best: #{}", client.info().best_number);
+			Ok(())
+		},
+		Err(e) => Err(Error::Other(format!("Failed to import state snapshot: {e}"))),
+	}
+}
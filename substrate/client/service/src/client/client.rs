@@ -72,8 +72,8 @@ use sp_runtime::{
 	Justification, Justifications, StateVersion,
 };
 use sp_state_machine::{
-	prove_child_read, prove_range_read_with_child_with_size, prove_read,
-	read_range_proof_check_with_child_on_proving_backend, Backend as StateBackend,
+	prove_child_read, prove_range_read_with_child_with_size, prove_range_read_with_size,
+	prove_read, read_range_proof_check_with_child_on_proving_backend, Backend as StateBackend,
 	ChildStorageCollection, KeyValueStates, KeyValueStorageLevel, StorageCollection,
 	MAX_NESTED_TRIE_DEPTH,
 };
@@ -1276,6 +1276,27 @@ where
 		Ok((proof, count))
 	}
 
+	fn read_range_proof(
+		&self,
+		hash: Block::Hash,
+		child_info: Option<&ChildInfo>,
+		prefix: Option<&[u8]>,
+		start_key: Option<&[u8]>,
+		size_limit: usize,
+	) -> sp_blockchain::Result<(CompactProof, u32)> {
+		let state = self.state_at(hash)?;
+		// this is a read proof, using version V0 or V1 is equivalent.
+		let root = state.storage_root(std::iter::empty(), StateVersion::V0).0;
+
+		let (proof, count) = prove_range_read_with_size::<_, HashingFor<Block>>(
+			state, child_info, prefix, size_limit, start_key,
+		)?;
+		let proof = proof
+			.into_compact_proof::<HashingFor<Block>>(root)
+			.map_err(|e| sp_blockchain::Error::from_state(Box::new(e)))?;
+		Ok((proof, count))
+	}
+
 	fn storage_collection(
 		&self,
 		hash: Block::Hash,
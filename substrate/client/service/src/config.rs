@@ -18,8 +18,10 @@
 
 //! Service configuration.
 
-pub use sc_client_db::{BlocksPruning, Database, DatabaseSource, PruningMode};
-pub use sc_executor::{WasmExecutionMethod, WasmtimeInstantiationStrategy};
+pub use sc_client_db::{BlocksPruning, Database, DatabaseSource, PruningMode, RetentionRule};
+pub use sc_executor::{
+	RuntimeArtifactCacheConfig, WasmExecutionMethod, WasmtimeInstantiationStrategy,
+};
 pub use sc_network::{
 	config::{
 		MultiaddrWithPeerId, NetworkConfiguration, NodeKeyConfig, NonDefaultSetConfig, ProtocolId,
@@ -137,6 +139,8 @@ pub struct Configuration {
 	pub informant_output_format: sc_informant::OutputFormat,
 	/// Maximum number of different runtime versions that can be cached.
 	pub runtime_cache_size: u8,
+	/// The on-disk cache of compiled runtimes, if enabled.
+	pub runtime_artifact_cache: Option<RuntimeArtifactCacheConfig>,
 }
 
 /// Type for tasks spawned by the executor.
@@ -178,6 +182,10 @@ pub struct OffchainWorkerConfig {
 	pub enabled: bool,
 	/// allow writes from the runtime to the offchain worker database.
 	pub indexing_enabled: bool,
+	/// Record the HTTP requests of offchain workers and their responses to this file.
+	pub http_record: Option<PathBuf>,
+	/// Answer the HTTP requests of offchain workers from this fixtures file.
+	pub http_replay: Option<PathBuf>,
 }
 
 /// Configuration of the Prometheus endpoint.
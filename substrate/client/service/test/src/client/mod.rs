@@ -54,6 +54,7 @@ use substrate_test_runtime_client::{
 };
 
 mod db;
+mod state_snapshot;
 
 const TEST_ENGINE_ID: ConsensusEngineId = *b"TEST";
 
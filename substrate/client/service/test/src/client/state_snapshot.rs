@@ -0,0 +1,181 @@
+// This file is part of Substrate.
+
+// Copyright (C) Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use futures::executor::block_on;
+use parity_scale_codec::{Decode, Encode};
+use sc_client_api::{HeaderBackend, StorageProvider};
+use sc_consensus::{BasicQueue, BlockImportParams, ForkChoiceStrategy, Verifier};
+use sc_network_sync::warp::{
+	AuthorityList, EncodedProof, SetId, VerificationResult, WarpSyncProvider,
+};
+use sc_service::chain_ops::{export_state_snapshot, import_state_snapshot};
+use sp_consensus::BlockOrigin;
+use sp_core::{storage::well_known_keys, testing::TaskExecutor};
+use sp_runtime::traits::Header as HeaderT;
+use sp_storage::StorageKey;
+use std::sync::Arc;
+use substrate_test_runtime_client::{
+	prelude::*,
+	runtime::{Block, Hash, Header},
+	ClientBlockImportExt, TestClient,
+};
+
+/// Warp sync provider proving the finality of the finalized block of `client` with a single
+/// proof, which is the encoded header of that block.
+struct TestProvider {
+	client: Arc<TestClient>,
+	/// Whether proofs are verified as complete.
+	complete: bool,
+}
+
+impl WarpSyncProvider<Block> for TestProvider {
+	fn generate(
+		&self,
+		_start: Hash,
+	) -> Result<EncodedProof, Box<dyn std::error::Error + Send + Sync>> {
+		let header = self.client.header(self.client.info().finalized_hash)?.ok_or("No header")?;
+		Ok(EncodedProof(header.encode()))
+	}
+
+	fn verify(
+		&self,
+		proof: &EncodedProof,
+		set_id: SetId,
+		authorities: AuthorityList,
+	) -> Result<VerificationResult<Block>, Box<dyn std::error::Error + Send + Sync>> {
+		let header = Header::decode(&mut proof.0.as_slice())?;
+		Ok(if self.complete {
+			VerificationResult::Complete(set_id, authorities, header)
+		} else {
+			VerificationResult::Partial(set_id, authorities, header.hash())
+		})
+	}
+
+	fn current_authorities(&self) -> AuthorityList {
+		Vec::new()
+	}
+
+	fn proof_end(
+		&self,
+		proof: &EncodedProof,
+	) -> Result<(Hash, bool), Box<dyn std::error::Error + Send + Sync>> {
+		Ok((Header::decode(&mut proof.0.as_slice())?.hash(), true))
+	}
+}
+
+struct PassThroughVerifier;
+
+#[async_trait::async_trait]
+impl Verifier<Block> for PassThroughVerifier {
+	async fn verify(
+		&mut self,
+		mut block: BlockImportParams<Block>,
+	) -> Result<BlockImportParams<Block>, String> {
+		block.fork_choice = Some(ForkChoiceStrategy::LongestChain);
+		Ok(block)
+	}
+}
+
+/// Client with three blocks, the second one finalized.
+fn source_client() -> Arc<TestClient> {
+	let mut client = Arc::new(substrate_test_runtime_client::new());
+	for _ in 0..3 {
+		let block = client.new_block(Default::default()).unwrap().build().unwrap().block;
+		block_on(client.import(BlockOrigin::Own, block)).unwrap();
+	}
+	let hash = client.hash(2).unwrap().unwrap();
+	client.finalize_block(hash, None).unwrap();
+	client
+}
+
+fn export(client: &Arc<TestClient>) -> Vec<u8> {
+	let provider = TestProvider { client: client.clone(), complete: true };
+	let mut snapshot = Vec::new();
+	let number = export_state_snapshot(client.clone(), &provider, &mut snapshot).unwrap();
+	assert_eq!(number, 2);
+	snapshot
+}
+
+fn import(
+	source: &Arc<TestClient>,
+	client: &Arc<TestClient>,
+	snapshot: &[u8],
+	complete: bool,
+) -> Result<(), sc_service::Error> {
+	let import_queue = BasicQueue::new(
+		PassThroughVerifier,
+		Box::new(client.clone()),
+		None,
+		&TaskExecutor::new(),
+		None,
+	);
+	let provider = TestProvider { client: source.clone(), complete };
+	block_on(import_state_snapshot(client.clone(), import_queue, &provider, snapshot))
+}
+
+#[test]
+fn exported_snapshot_is_imported() {
+	let source = source_client();
+	let snapshot = export(&source);
+
+	let client = Arc::new(substrate_test_runtime_client::new());
+	import(&source, &client, &snapshot, true).unwrap();
+
+	let hash = source.info().finalized_hash;
+	assert_eq!(client.info().best_hash, hash);
+	let code = StorageKey(well_known_keys::CODE.to_vec());
+	assert_eq!(client.storage(hash, &code).unwrap(), source.storage(hash, &code).unwrap());
+	assert_eq!(
+		client.storage_pairs(hash, None, None).unwrap().collect::<Vec<_>>(),
+		source.storage_pairs(hash, None, None).unwrap().collect::<Vec<_>>(),
+	);
+}
+
+#[test]
+fn snapshot_without_finality_proof_is_rejected() {
+	let source = source_client();
+	let snapshot = export(&source);
+
+	let client = Arc::new(substrate_test_runtime_client::new());
+	assert!(import(&source, &client, &snapshot, false).is_err());
+	assert_eq!(client.info().best_number, 0);
+}
+
+#[test]
+fn snapshot_is_only_imported_into_fresh_database() {
+	let source = source_client();
+	let snapshot = export(&source);
+
+	let mut client = Arc::new(substrate_test_runtime_client::new());
+	let block = client.new_block(Default::default()).unwrap().build().unwrap().block;
+	block_on(client.import(BlockOrigin::Own, block)).unwrap();
+
+	assert!(import(&source, &client, &snapshot, true).is_err());
+	assert_eq!(client.info().best_number, 1);
+}
+
+#[test]
+fn truncated_snapshot_is_rejected() {
+	let source = source_client();
+	let snapshot = export(&source);
+
+	let client = Arc::new(substrate_test_runtime_client::new());
+	assert!(import(&source, &client, &snapshot[..snapshot.len() - 1], true).is_err());
+	assert!(import(&source, &client, b"not a snapshot", true).is_err());
+	assert_eq!(client.info().best_number, 0);
+}
@@ -268,6 +268,7 @@ fn node_config<
 		data_path: root,
 		informant_output_format: Default::default(),
 		runtime_cache_size: 2,
+		runtime_artifact_cache: None,
 	}
 }
 
@@ -40,17 +40,34 @@
 //! # Pruning.
 //! See `RefWindow` for pruning algorithm details. `StateDb` prunes on each canonicalization until
 //! pruning constraints are satisfied.
+//!
+//! # Retention.
+//! Pruning constraints may contain [`RetentionRule`]s. Trie nodes on the path to the retained
+//! storage are never pruned, so the retained part of the state of a canonical block stays
+//! accessible after the block leaves the pruning window. The rules are stored in the database and
+//! can not be changed afterwards. Node keys of databases that count references themselves carry no
+//! trie path, so deletions of retained nodes must be left out of the inserted [`ChangeSet`]s with
+//! [`PruningMode::retains_node`] instead.
 
 mod noncanonical;
 mod pruning;
 #[cfg(test)]
 mod test;
 
-use codec::Codec;
+use codec::{Codec, Decode, Encode};
 use log::trace;
 use noncanonical::NonCanonicalOverlay;
+pub use noncanonical::{
+	fetch_journals as fetch_non_canonical_journals, fetch_last_canonicalized,
+	map_journal_record_keys, JournalRecord as NonCanonicalJournalRecord,
+};
 use parking_lot::RwLock;
+pub use pruning::{
+	fetch_journals as fetch_pruning_journals, fetch_last_pruned, resolve_reinsertions,
+	JournalRecord as PruningJournalRecord,
+};
 use pruning::{HaveBlock, RefWindow};
+use sp_core::storage::well_known_keys::DEFAULT_CHILD_STORAGE_KEY_PREFIX;
 use std::{
 	collections::{hash_map::Entry, HashMap},
 	fmt,
@@ -62,7 +79,10 @@ const PRUNING_MODE: &[u8] = b"mode";
 const PRUNING_MODE_ARCHIVE: &[u8] = b"archive";
 const PRUNING_MODE_ARCHIVE_CANON: &[u8] = b"archive_canonical";
 const PRUNING_MODE_CONSTRAINED: &[u8] = b"constrained";
+const PRUNING_RETENTION: &[u8] = b"retention";
 pub(crate) const DEFAULT_MAX_BLOCK_CONSTRAINT: u32 = 256;
+/// Length of the node hash at the end of a node key.
+const NODE_HASH_LEN: usize = 32;
 
 /// Database value type.
 pub type DBValue = Vec<u8>;
@@ -224,6 +244,64 @@ pub struct Constraints {
 	/// Maximum blocks. Defaults to 0 when unspecified, effectively keeping only non-canonical
 	/// states.
 	pub max_blocks: Option<u32>,
+	/// Storage whose history is kept for canonical blocks outside of the pruning window.
+	pub retain: Vec<RetentionRule>,
+}
+
+/// Storage whose history is kept beyond the pruning window.
+#[derive(Debug, Clone, Eq, PartialEq, Encode, Decode)]
+pub enum RetentionRule {
+	/// Keep all top trie keys starting with the given prefix.
+	Prefix(Vec<u8>),
+	/// Keep the default child trie with the given unprefixed storage key.
+	ChildTrie(Vec<u8>),
+}
+
+impl RetentionRule {
+	/// Check if the rule retains the given key of the top trie, or of the child trie with the
+	/// unprefixed storage key `child`.
+	pub fn retains_key(&self, child: Option<&[u8]>, key: &[u8]) -> bool {
+		match (self, child) {
+			(RetentionRule::Prefix(prefix), None) => key.starts_with(prefix),
+			(RetentionRule::Prefix(_), Some(_)) => false,
+			(RetentionRule::ChildTrie(storage_key), None) => key
+				.strip_prefix(DEFAULT_CHILD_STORAGE_KEY_PREFIX)
+				.map_or(false, |key| key == &storage_key[..]),
+			(RetentionRule::ChildTrie(storage_key), Some(child)) => child == &storage_key[..],
+		}
+	}
+
+	/// Check if the rule retains the trie node with the given database key.
+	///
+	/// Node keys are made of the trie path of the node, prefixed with the storage key of the
+	/// child trie for child trie nodes, followed by the node hash. Nodes leading to the retained
+	/// storage are retained as well, so that it can be reached from the state root.
+	fn retains_node(&self, key: &[u8]) -> bool {
+		let path = &key[..key.len().saturating_sub(NODE_HASH_LEN)];
+		match self {
+			RetentionRule::Prefix(prefix) => is_path_related(path, prefix),
+			RetentionRule::ChildTrie(storage_key) =>
+				is_path_related(path, storage_key) ||
+					is_path_related(
+						path,
+						&[DEFAULT_CHILD_STORAGE_KEY_PREFIX, &storage_key[..]].concat(),
+					),
+		}
+	}
+}
+
+/// Check if a node path leads to `prefix` or lies below it.
+///
+/// Paths with an odd number of nibbles are padded with a zero nibble, so a path ending with a
+/// zero nibble is checked in both forms.
+fn is_path_related(path: &[u8], prefix: &[u8]) -> bool {
+	let nibble =
+		|bytes: &[u8], i: usize| if i % 2 == 0 { bytes[i / 2] >> 4 } else { bytes[i / 2] & 0x0f };
+	let related = |path_nibbles: usize| {
+		(0..path_nibbles.min(prefix.len() * 2)).all(|i| nibble(path, i) == nibble(prefix, i))
+	};
+	let nibbles = path.len() * 2;
+	related(nibbles) || (path.last().map_or(false, |last| last & 0x0f == 0) && related(nibbles - 1))
 }
 
 /// Pruning mode.
@@ -240,7 +318,26 @@ pub enum PruningMode {
 impl PruningMode {
 	/// Create a mode that keeps given number of blocks.
 	pub fn blocks_pruning(n: u32) -> PruningMode {
-		PruningMode::Constrained(Constraints { max_blocks: Some(n) })
+		PruningMode::Constrained(Constraints { max_blocks: Some(n), retain: Vec::new() })
+	}
+
+	/// Create a mode that keeps given number of blocks and the history of the retained storage.
+	pub fn blocks_pruning_with_retention(n: u32, retain: Vec<RetentionRule>) -> PruningMode {
+		PruningMode::Constrained(Constraints { max_blocks: Some(n), retain })
+	}
+
+	/// Returns the retention rules of the pruning mode.
+	pub fn retention_rules(&self) -> &[RetentionRule] {
+		match self {
+			PruningMode::Constrained(constraints) => &constraints.retain,
+			PruningMode::ArchiveAll | PruningMode::ArchiveCanonical => &[],
+		}
+	}
+
+	/// Check if the trie node with the given database key, prefixed with the trie path of the
+	/// node, is never pruned.
+	pub fn retains_node(&self, key: &[u8]) -> bool {
+		self.retention_rules().iter().any(|rule| rule.retains_node(key))
 	}
 
 	/// Is this an archive (either ArchiveAll or ArchiveCanonical) pruning mode?
@@ -278,7 +375,7 @@ impl Default for PruningMode {
 
 impl Default for Constraints {
 	fn default() -> Self {
-		Self { max_blocks: Some(DEFAULT_MAX_BLOCK_CONSTRAINT) }
+		Self { max_blocks: Some(DEFAULT_MAX_BLOCK_CONSTRAINT), retain: Vec::new() }
 	}
 }
 
@@ -317,7 +414,7 @@ impl<BlockHash: Hash, Key: Hash, D: MetaDb> StateDbSync<BlockHash, Key, D> {
 
 		let non_canonical: NonCanonicalOverlay<BlockHash, Key> = NonCanonicalOverlay::new(&db)?;
 		let pruning: Option<RefWindow<BlockHash, Key, D>> = match mode {
-			PruningMode::Constrained(Constraints { max_blocks }) =>
+			PruningMode::Constrained(Constraints { max_blocks, .. }) =>
 				Some(RefWindow::new(db, max_blocks.unwrap_or(0), ref_counting)?),
 			PruningMode::ArchiveAll | PruningMode::ArchiveCanonical => None,
 		};
@@ -345,7 +442,10 @@ impl<BlockHash: Hash, Key: Hash, D: MetaDb> StateDbSync<BlockHash, Key, D> {
 		}
 	}
 
-	fn canonicalize_block(&mut self, hash: &BlockHash) -> Result<CommitSet<Key>, Error<D::Error>> {
+	fn canonicalize_block(&mut self, hash: &BlockHash) -> Result<CommitSet<Key>, Error<D::Error>>
+	where
+		Key: AsRef<[u8]>,
+	{
 		// NOTE: it is important that the change to `LAST_CANONICAL` (emit from
 		// `non_canonical.canonicalize`) and the insert of the new pruning journal (emit from
 		// `pruning.note_canonical`) are collected into the same `CommitSet` and are committed to
@@ -358,6 +458,11 @@ impl<BlockHash: Hash, Key: Hash, D: MetaDb> StateDbSync<BlockHash, Key, D> {
 		if self.mode == PruningMode::ArchiveCanonical {
 			commit.data.deleted.clear();
 		}
+		// Node keys are only prefixed with the trie path when the database does not count
+		// references itself, otherwise retained nodes are never deleted in the first place.
+		if self.ref_counting && !self.mode.retention_rules().is_empty() {
+			commit.data.deleted.retain(|key| !self.mode.retains_node(key.as_ref()));
+		}
 		if let Some(ref mut pruning) = self.pruning {
 			pruning.note_canonical(hash, number, &mut commit)?;
 		}
@@ -377,7 +482,10 @@ impl<BlockHash: Hash, Key: Hash, D: MetaDb> StateDbSync<BlockHash, Key, D> {
 		}
 	}
 
-	fn is_pruned(&self, hash: &BlockHash, number: u64) -> IsPruned {
+	fn is_pruned<F>(&self, hash: &BlockHash, number: u64, is_canonical: F) -> IsPruned
+	where
+		F: Fn() -> bool,
+	{
 		match self.mode {
 			PruningMode::ArchiveAll => IsPruned::NotPruned,
 			PruningMode::ArchiveCanonical | PruningMode::Constrained(_) => {
@@ -397,6 +505,9 @@ impl<BlockHash: Hash, Key: Hash, D: MetaDb> StateDbSync<BlockHash, Key, D> {
 						// We don't know for sure.
 						None => IsPruned::MaybePruned,
 						Some(pruning) => match pruning.have_block(hash, number) {
+							HaveBlock::No
+								if self.is_retained(pruning, number) && is_canonical() =>
+								IsPruned::PartiallyPruned,
 							HaveBlock::No => IsPruned::Pruned,
 							HaveBlock::Yes => IsPruned::NotPruned,
 							HaveBlock::Maybe => IsPruned::MaybePruned,
@@ -407,6 +518,12 @@ impl<BlockHash: Hash, Key: Hash, D: MetaDb> StateDbSync<BlockHash, Key, D> {
 		}
 	}
 
+	/// Check if retained storage may still be available for a block older than the pruning
+	/// window.
+	fn is_retained(&self, pruning: &RefWindow<BlockHash, Key, D>, number: u64) -> bool {
+		!self.mode.retention_rules().is_empty() && number < pruning.pending()
+	}
+
 	fn prune(&mut self, commit: &mut CommitSet<Key>) -> Result<(), Error<D::Error>> {
 		if let (&mut Some(ref mut pruning), PruningMode::Constrained(constraints)) =
 			(&mut self.pruning, &self.mode)
@@ -466,6 +583,7 @@ impl<BlockHash: Hash, Key: Hash, D: MetaDb> StateDbSync<BlockHash, Key, D> {
 					self.pruning.as_ref().map_or_else(
 						|| hint(),
 						|pruning| match pruning.have_block(hash, number) {
+							HaveBlock::No if self.is_retained(pruning, number) => hint(),
 							HaveBlock::No => false,
 							HaveBlock::Yes => true,
 							HaveBlock::Maybe => hint(),
@@ -564,6 +682,11 @@ impl<BlockHash: Hash, Key: Hash, D: MetaDb> StateDb<BlockHash, Key, D> {
 
 			cs.meta.inserted.push((key, value));
 
+			let retain = selected_mode.retention_rules();
+			if !retain.is_empty() {
+				cs.meta.inserted.push((to_meta_key(PRUNING_RETENTION, &()), retain.encode()));
+			}
+
 			cs
 		} else {
 			Default::default()
@@ -591,7 +714,10 @@ impl<BlockHash: Hash, Key: Hash, D: MetaDb> StateDb<BlockHash, Key, D> {
 	}
 
 	/// Finalize a previously inserted block.
-	pub fn canonicalize_block(&self, hash: &BlockHash) -> Result<CommitSet<Key>, Error<D::Error>> {
+	pub fn canonicalize_block(&self, hash: &BlockHash) -> Result<CommitSet<Key>, Error<D::Error>>
+	where
+		Key: AsRef<[u8]>,
+	{
 		self.db.write().canonicalize_block(hash)
 	}
 
@@ -649,7 +775,18 @@ impl<BlockHash: Hash, Key: Hash, D: MetaDb> StateDb<BlockHash, Key, D> {
 
 	/// Check if block is pruned away.
 	pub fn is_pruned(&self, hash: &BlockHash, number: u64) -> IsPruned {
-		self.db.read().is_pruned(hash, number)
+		self.db.read().is_pruned(hash, number, || false)
+	}
+
+	/// Check if block is pruned away, detecting partially pruned blocks.
+	///
+	/// Only the retained storage of canonical blocks survives pruning, so `is_canonical` is
+	/// asked whether a block older than the pruning window is canonical.
+	pub fn is_partially_pruned<F>(&self, hash: &BlockHash, number: u64, is_canonical: F) -> IsPruned
+	where
+		F: Fn() -> bool,
+	{
+		self.db.read().is_pruned(hash, number, is_canonical)
 	}
 
 	/// Reset in-memory changes to the last disk-backed state.
@@ -669,12 +806,20 @@ pub enum IsPruned {
 	NotPruned,
 	/// May or may not pruned, need further checking
 	MaybePruned,
+	/// Pruned except for the retained storage, which may be available if the block is canonical
+	PartiallyPruned,
 }
 
-fn fetch_stored_pruning_mode<D: MetaDb>(db: &D) -> Result<Option<PruningMode>, Error<D::Error>> {
+/// Returns the pruning mode stored in the database, if any.
+pub fn fetch_stored_pruning_mode<D: MetaDb>(
+	db: &D,
+) -> Result<Option<PruningMode>, Error<D::Error>> {
 	let meta_key_mode = to_meta_key(PRUNING_MODE, &());
 	if let Some(stored_mode) = db.get_meta(&meta_key_mode).map_err(Error::Db)? {
-		if let Some(mode) = PruningMode::from_id(&stored_mode) {
+		if let Some(mut mode) = PruningMode::from_id(&stored_mode) {
+			if let PruningMode::Constrained(constraints) = &mut mode {
+				constraints.retain = fetch_stored_retention_rules(db)?;
+			}
 			Ok(Some(mode))
 		} else {
 			Err(StateDbError::Metadata(format!(
@@ -688,6 +833,39 @@ fn fetch_stored_pruning_mode<D: MetaDb>(db: &D) -> Result<Option<PruningMode>, E
 	}
 }
 
+/// Returns the entries of the pruning mode and of the canonicalization and pruning progress stored
+/// in `db`.
+///
+/// Together with the journal records, these are all the entries the state database keeps in the
+/// meta column.
+pub fn fetch_stored_settings<D: MetaDb>(
+	db: &D,
+) -> Result<Vec<(Vec<u8>, DBValue)>, Error<D::Error>> {
+	let mut settings = Vec::new();
+	for key in [PRUNING_MODE, PRUNING_RETENTION, noncanonical::LAST_CANONICAL, pruning::LAST_PRUNED]
+	{
+		let key = to_meta_key(key, &());
+		if let Some(value) = db.get_meta(&key).map_err(Error::Db)? {
+			settings.push((key, value));
+		}
+	}
+	Ok(settings)
+}
+
+fn fetch_stored_retention_rules<D: MetaDb>(db: &D) -> Result<Vec<RetentionRule>, Error<D::Error>> {
+	let meta_key_retention = to_meta_key(PRUNING_RETENTION, &());
+	match db.get_meta(&meta_key_retention).map_err(Error::Db)? {
+		Some(stored) => Vec::<RetentionRule>::decode(&mut stored.as_slice()).map_err(|_| {
+			StateDbError::Metadata(format!(
+				"Invalid value stored for PRUNING_RETENTION: {:02x?}",
+				stored
+			))
+			.into()
+		}),
+		None => Ok(Vec::new()),
+	}
+}
+
 fn choose_pruning_mode(
 	stored: PruningMode,
 	requested: PruningMode,
@@ -696,7 +874,10 @@ fn choose_pruning_mode(
 		(PruningMode::ArchiveAll, PruningMode::ArchiveAll) => Ok(PruningMode::ArchiveAll),
 		(PruningMode::ArchiveCanonical, PruningMode::ArchiveCanonical) =>
 			Ok(PruningMode::ArchiveCanonical),
-		(PruningMode::Constrained(_), PruningMode::Constrained(requested)) =>
+		// Storage pruned before the rules were configured can not be restored, while dropping a
+		// rule would leave its nodes in the database forever.
+		(PruningMode::Constrained(stored), PruningMode::Constrained(requested))
+			if stored.retain == requested.retain =>
 			Ok(PruningMode::Constrained(requested)),
 		(stored, requested) => Err(StateDbError::IncompatiblePruningModes { requested, stored }),
 	}
@@ -706,14 +887,21 @@ fn choose_pruning_mode(
 mod tests {
 	use crate::{
 		test::{make_changeset, make_db, TestDb},
-		Constraints, Error, IsPruned, PruningMode, StateDb, StateDbError,
+		Error, IsPruned, PruningMode, RetentionRule, StateDb, StateDbError,
 	};
 	use sp_core::H256;
 
 	fn make_test_db(settings: PruningMode) -> (TestDb, StateDb<H256, H256, TestDb>) {
+		make_test_db_with_ref_counting(settings, false)
+	}
+
+	fn make_test_db_with_ref_counting(
+		settings: PruningMode,
+		ref_counting: bool,
+	) -> (TestDb, StateDb<H256, H256, TestDb>) {
 		let mut db = make_db(&[91, 921, 922, 93, 94]);
 		let (state_db_init, state_db) =
-			StateDb::open(db.clone(), Some(settings), false, true).unwrap();
+			StateDb::open(db.clone(), Some(settings), ref_counting, true).unwrap();
 		db.commit(&state_db_init);
 
 		db.commit(
@@ -788,8 +976,7 @@ mod tests {
 
 	#[test]
 	fn block_record_unavailable() {
-		let (mut db, state_db) =
-			make_test_db(PruningMode::Constrained(Constraints { max_blocks: Some(1) }));
+		let (mut db, state_db) = make_test_db(PruningMode::blocks_pruning(1));
 		// import 2 blocks
 		for i in &[5, 6] {
 			db.commit(
@@ -823,13 +1010,13 @@ mod tests {
 
 	#[test]
 	fn prune_window_0() {
-		let (db, _) = make_test_db(PruningMode::Constrained(Constraints { max_blocks: Some(0) }));
+		let (db, _) = make_test_db(PruningMode::blocks_pruning(0));
 		assert!(db.data_eq(&make_db(&[21, 3, 922, 94])));
 	}
 
 	#[test]
 	fn prune_window_1() {
-		let (db, sdb) = make_test_db(PruningMode::Constrained(Constraints { max_blocks: Some(1) }));
+		let (db, sdb) = make_test_db(PruningMode::blocks_pruning(1));
 		assert_eq!(sdb.is_pruned(&H256::from_low_u64_be(0), 0), IsPruned::Pruned);
 		assert_eq!(sdb.is_pruned(&H256::from_low_u64_be(1), 1), IsPruned::Pruned);
 		assert_eq!(sdb.is_pruned(&H256::from_low_u64_be(21), 2), IsPruned::Pruned);
@@ -839,7 +1026,7 @@ mod tests {
 
 	#[test]
 	fn prune_window_2() {
-		let (db, sdb) = make_test_db(PruningMode::Constrained(Constraints { max_blocks: Some(2) }));
+		let (db, sdb) = make_test_db(PruningMode::blocks_pruning(2));
 		assert_eq!(sdb.is_pruned(&H256::from_low_u64_be(0), 0), IsPruned::Pruned);
 		assert_eq!(sdb.is_pruned(&H256::from_low_u64_be(1), 1), IsPruned::Pruned);
 		assert_eq!(sdb.is_pruned(&H256::from_low_u64_be(21), 2), IsPruned::NotPruned);
@@ -847,6 +1034,106 @@ mod tests {
 		assert!(db.data_eq(&make_db(&[1, 21, 3, 921, 922, 93, 94])));
 	}
 
+	#[test]
+	fn retention_keeps_nodes_of_retained_storage() {
+		let mode =
+			PruningMode::blocks_pruning_with_retention(1, vec![RetentionRule::Prefix(vec![0x12])]);
+		let (db, sdb) = make_test_db_with_ref_counting(mode, true);
+		// Keys of the test database have no trie path, so every node leads to retained storage.
+		assert!(db.data_eq(&make_db(&[1, 21, 3, 91, 921, 922, 93, 94])));
+		let is_canonical = |hash: u64| move || hash != 22;
+		for (hash, number) in [(1, 1), (21, 2)] {
+			assert_eq!(
+				sdb.is_partially_pruned(&H256::from_low_u64_be(hash), number, is_canonical(hash)),
+				IsPruned::PartiallyPruned,
+			);
+			assert_eq!(sdb.is_pruned(&H256::from_low_u64_be(hash), number), IsPruned::Pruned);
+		}
+		// Discarded forks are pruned completely.
+		assert_eq!(
+			sdb.is_partially_pruned(&H256::from_low_u64_be(22), 2, is_canonical(22)),
+			IsPruned::Pruned,
+		);
+		assert!(sdb.pin(&H256::from_low_u64_be(1), 1, || true).is_ok());
+		assert!(sdb.pin(&H256::from_low_u64_be(22), 2, || false).is_err());
+	}
+
+	#[test]
+	fn retention_leaves_unprefixed_node_keys_to_caller() {
+		let mode =
+			PruningMode::blocks_pruning_with_retention(1, vec![RetentionRule::Prefix(vec![0x12])]);
+		let (db, _) = make_test_db_with_ref_counting(mode, false);
+		assert!(db.data_eq(&make_db(&[21, 3, 922, 93, 94])));
+	}
+
+	#[test]
+	fn retention_rules_are_persisted() {
+		let rules = vec![RetentionRule::Prefix(vec![0x12]), RetentionRule::ChildTrie(vec![1])];
+		let mode = PruningMode::blocks_pruning_with_retention(1, rules.clone());
+		let mut db = make_db(&[]);
+		let (state_db_init, _) =
+			StateDb::<H256, H256, TestDb>::open(db.clone(), Some(mode.clone()), true, true)
+				.unwrap();
+		db.commit(&state_db_init);
+
+		let (_, state_db) =
+			StateDb::<H256, H256, TestDb>::open(db.clone(), None, true, false).unwrap();
+		assert_eq!(state_db.pruning_mode(), mode);
+		let (_, state_db) =
+			StateDb::<H256, H256, TestDb>::open(db.clone(), Some(mode.clone()), true, false)
+				.unwrap();
+		assert_eq!(state_db.pruning_mode(), mode);
+
+		for requested in [
+			PruningMode::blocks_pruning(1),
+			PruningMode::blocks_pruning_with_retention(1, rules[..1].to_vec()),
+		] {
+			let result =
+				StateDb::<H256, H256, TestDb>::open(db.clone(), Some(requested), true, false);
+			assert!(matches!(
+				result,
+				Err(Error::StateDb(StateDbError::IncompatiblePruningModes { .. }))
+			));
+		}
+	}
+
+	#[test]
+	fn retention_rules_match_node_paths() {
+		let node = |path: &[u8]| [path, &[0u8; 32][..]].concat();
+		let rule = RetentionRule::Prefix(vec![0x12, 0x34]);
+		assert!(rule.retains_node(&node(&[])));
+		assert!(rule.retains_node(&node(&[0x10])));
+		assert!(rule.retains_node(&node(&[0x12])));
+		assert!(rule.retains_node(&node(&[0x12, 0x30])));
+		assert!(rule.retains_node(&node(&[0x12, 0x34, 0x56])));
+		assert!(!rule.retains_node(&node(&[0x13])));
+		assert!(!rule.retains_node(&node(&[0x20])));
+		assert!(!rule.retains_node(&node(&[0x12, 0x35, 0x56])));
+
+		let rule = RetentionRule::ChildTrie(b"child".to_vec());
+		assert!(rule.retains_node(&node(b"child")));
+		assert!(rule.retains_node(&node(&[&b"child"[..], &[0x42][..]].concat())));
+		assert!(rule.retains_node(&node(b":child_storage:default:child")));
+		assert!(rule.retains_node(&node(b":child_storage:")));
+		assert!(!rule.retains_node(&node(b"other")));
+		assert!(!rule.retains_node(&node(b":child_storage:default:other")));
+	}
+
+	#[test]
+	fn retention_rules_match_storage_keys() {
+		let rule = RetentionRule::Prefix(vec![0x12, 0x34]);
+		assert!(rule.retains_key(None, &[0x12, 0x34]));
+		assert!(rule.retains_key(None, &[0x12, 0x34, 0x56]));
+		assert!(!rule.retains_key(None, &[0x12]));
+		assert!(!rule.retains_key(Some(b"child"), &[0x12, 0x34]));
+
+		let rule = RetentionRule::ChildTrie(b"child".to_vec());
+		assert!(rule.retains_key(None, b":child_storage:default:child"));
+		assert!(!rule.retains_key(None, b":child_storage:default:child2"));
+		assert!(rule.retains_key(Some(b"child"), b"any"));
+		assert!(!rule.retains_key(Some(b"other"), b"any"));
+	}
+
 	#[test]
 	fn detects_incompatible_mode() {
 		let mut db = make_db(&[]);
@@ -863,7 +1150,7 @@ mod tests {
 				)
 				.unwrap(),
 		);
-		let new_mode = PruningMode::Constrained(Constraints { max_blocks: Some(2) });
+		let new_mode = PruningMode::blocks_pruning(2);
 		let state_db_open_result: Result<(_, StateDb<H256, H256, TestDb>), _> =
 			StateDb::open(db.clone(), Some(new_mode), false, false);
 		assert!(state_db_open_result.is_err());
@@ -69,18 +69,76 @@ impl<BlockHash: Hash, Key: Hash> OverlayLevel<BlockHash, Key> {
 	}
 }
 
+/// Journal record of a non-canonical block.
 #[derive(Encode, Decode)]
-struct JournalRecord<BlockHash: Hash, Key: Hash> {
-	hash: BlockHash,
-	parent_hash: BlockHash,
-	inserted: Vec<(Key, DBValue)>,
-	deleted: Vec<Key>,
+pub struct JournalRecord<BlockHash: Hash, Key: Hash> {
+	/// Hash of the block.
+	pub hash: BlockHash,
+	/// Hash of the parent block.
+	pub parent_hash: BlockHash,
+	/// Nodes inserted by the block, with their values.
+	pub inserted: Vec<(Key, DBValue)>,
+	/// Nodes deleted by the block.
+	pub deleted: Vec<Key>,
 }
 
 fn to_journal_key(block: u64, index: u64) -> Vec<u8> {
 	to_meta_key(NON_CANONICAL_JOURNAL, &(block, index))
 }
 
+/// Rewrite the node keys of a journal record of a non-canonical block with `f`.
+///
+/// Returns `None` if `meta_key` is not the key of such a journal record.
+pub fn map_journal_record_keys<BlockHash: Hash, Key: Hash>(
+	meta_key: &[u8],
+	record: &[u8],
+	mut f: impl FnMut(Key) -> Key,
+) -> Option<Result<DBValue, codec::Error>> {
+	let index = meta_key.strip_suffix(NON_CANONICAL_JOURNAL)?;
+	if index.len() != (0u64, 0u64).encoded_size() {
+		return None
+	}
+	let result = JournalRecord::<BlockHash, Key>::decode(&mut &record[..]).map(|mut record| {
+		record.inserted = record.inserted.into_iter().map(|(key, value)| (f(key), value)).collect();
+		record.deleted = record.deleted.into_iter().map(&mut f).collect();
+		record.encode()
+	});
+	Some(result)
+}
+
+/// Returns the hash and number of the last canonicalized block stored in `db`.
+pub fn fetch_last_canonicalized<BlockHash: Hash, D: MetaDb>(
+	db: &D,
+) -> Result<Option<(BlockHash, u64)>, Error<D::Error>> {
+	match db.get_meta(&to_meta_key(LAST_CANONICAL, &())).map_err(Error::Db)? {
+		Some(buffer) => Ok(Some(Decode::decode(&mut buffer.as_slice())?)),
+		None => Ok(None),
+	}
+}
+
+/// Returns the journal records of the non-canonical blocks stored in `db`, with their meta keys.
+pub fn fetch_journals<BlockHash: Hash, Key: Hash, D: MetaDb>(
+	db: &D,
+) -> Result<Vec<(Vec<u8>, JournalRecord<BlockHash, Key>)>, Error<D::Error>> {
+	let mut journals = Vec::new();
+	let Some((_, mut block)) = fetch_last_canonicalized::<BlockHash, D>(db)? else {
+		return Ok(journals)
+	};
+	loop {
+		block += 1;
+		let level_start = journals.len();
+		for index in 0..MAX_BLOCKS_PER_LEVEL {
+			let journal_key = to_journal_key(block, index);
+			if let Some(record) = db.get_meta(&journal_key).map_err(Error::Db)? {
+				journals.push((journal_key, Decode::decode(&mut record.as_slice())?));
+			}
+		}
+		if journals.len() == level_start {
+			return Ok(journals)
+		}
+	}
+}
+
 #[cfg_attr(test, derive(PartialEq, Debug))]
 struct BlockOverlay<BlockHash: Hash, Key: Hash> {
 	hash: BlockHash,
@@ -170,11 +228,7 @@ fn discard_descendants<BlockHash: Hash, Key: Hash>(
 impl<BlockHash: Hash, Key: Hash> NonCanonicalOverlay<BlockHash, Key> {
 	/// Creates a new instance. Does not expect any metadata to be present in the DB.
 	pub fn new<D: MetaDb>(db: &D) -> Result<NonCanonicalOverlay<BlockHash, Key>, Error<D::Error>> {
-		let last_canonicalized =
-			db.get_meta(&to_meta_key(LAST_CANONICAL, &())).map_err(Error::Db)?;
-		let last_canonicalized = last_canonicalized
-			.map(|buffer| <(BlockHash, u64)>::decode(&mut buffer.as_slice()))
-			.transpose()?;
+		let last_canonicalized = fetch_last_canonicalized(db)?;
 		let mut levels = VecDeque::new();
 		let mut parents = HashMap::new();
 		let mut values = HashMap::new();
@@ -570,7 +624,7 @@ impl<BlockHash: Hash, Key: Hash> NonCanonicalOverlay<BlockHash, Key> {
 
 #[cfg(test)]
 mod tests {
-	use super::{to_journal_key, NonCanonicalOverlay};
+	use super::{map_journal_record_keys, to_journal_key, NonCanonicalOverlay};
 	use crate::{
 		test::{make_changeset, make_db},
 		ChangeSet, CommitSet, MetaDb, StateDbError,
@@ -679,6 +733,30 @@ mod tests {
 		assert!(db.data_eq(&make_db(&[1, 3, 4])));
 	}
 
+	#[test]
+	fn map_journal_record_keys_rewrites_node_keys() {
+		let h1 = H256::random();
+		let mut db = make_db(&[1, 2]);
+		let mut overlay = NonCanonicalOverlay::<H256, H256>::new(&db).unwrap();
+		let mut insertion =
+			overlay.insert(&h1, 10, &H256::default(), make_changeset(&[3], &[2])).unwrap();
+		for (key, value) in insertion.meta.inserted.iter_mut() {
+			let shifted = |key: H256| H256::from_low_u64_be(key.to_low_u64_be() + 10);
+			if let Some(record) = map_journal_record_keys::<H256, H256>(key, value, shifted) {
+				*value = record.unwrap();
+			}
+		}
+		assert!(map_journal_record_keys::<H256, H256>(b"last_canonical", &[], |k| k).is_none());
+		db.commit(&insertion);
+
+		let overlay = NonCanonicalOverlay::<H256, H256>::new(&db).unwrap();
+		assert_eq!(
+			overlay.get(&H256::from_low_u64_be(13)),
+			Some(H256::from_low_u64_be(3).as_bytes().to_vec())
+		);
+		assert_eq!(overlay.get(&H256::from_low_u64_be(3)), None);
+	}
+
 	#[test]
 	fn restore_from_journal() {
 		let h1 = H256::random();
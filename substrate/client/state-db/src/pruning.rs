@@ -268,17 +268,73 @@ struct DeathRow<BlockHash: Hash, Key: Hash> {
 	deleted: HashSet<Key>,
 }
 
+/// Journal record of a canonical block in the pruning window.
 #[derive(Encode, Decode, Default)]
-struct JournalRecord<BlockHash: Hash, Key: Hash> {
-	hash: BlockHash,
-	inserted: Vec<Key>,
-	deleted: Vec<Key>,
+pub struct JournalRecord<BlockHash: Hash, Key: Hash> {
+	/// Hash of the block.
+	pub hash: BlockHash,
+	/// Nodes inserted by the block. Only recorded if the database does not count references.
+	pub inserted: Vec<Key>,
+	/// Nodes deleted once the block is pruned.
+	pub deleted: Vec<Key>,
 }
 
 fn to_journal_key(block: u64) -> Vec<u8> {
 	to_meta_key(PRUNING_JOURNAL, &block)
 }
 
+/// Returns the number of the last pruned block stored in `db`.
+pub fn fetch_last_pruned<D: MetaDb>(db: &D) -> Result<Option<u64>, Error<D::Error>> {
+	match db.get_meta(&to_meta_key(LAST_PRUNED, &())).map_err(Error::Db)? {
+		Some(buffer) => Ok(Some(u64::decode(&mut buffer.as_slice())?)),
+		None => Ok(None),
+	}
+}
+
+/// Returns the journal records of the blocks in the pruning window stored in `db`, with their meta
+/// keys.
+pub fn fetch_journals<BlockHash: Hash, Key: Hash, D: MetaDb>(
+	db: &D,
+) -> Result<Vec<(Vec<u8>, JournalRecord<BlockHash, Key>)>, Error<D::Error>> {
+	let mut block = fetch_last_pruned(db)?.map_or(0, |last_pruned| last_pruned + 1);
+	let mut journals = Vec::new();
+	loop {
+		let journal_key = to_journal_key(block);
+		match db.get_meta(&journal_key).map_err(Error::Db)? {
+			Some(record) => journals.push((journal_key, Decode::decode(&mut record.as_slice())?)),
+			None => return Ok(journals),
+		}
+		block += 1;
+	}
+}
+
+/// Remove the deletions of the pruning window `journals` that are never applied to a database that
+/// does not count references.
+///
+/// Such a database keeps the whole window in memory. A key deleted by a block and inserted again by
+/// a later block is not deleted, and a key deleted by several blocks is removed by the first one.
+pub fn resolve_reinsertions<BlockHash: Hash, Key: Hash>(
+	journals: &mut [JournalRecord<BlockHash, Key>],
+) {
+	let mut death_rows = Vec::<HashSet<Key>>::with_capacity(journals.len());
+	let mut death_index = HashMap::new();
+	for (block, record) in journals.iter().enumerate() {
+		for key in record.inserted.iter() {
+			if let Some(row) = death_index.remove(key) {
+				death_rows[row].remove(key);
+			}
+		}
+		for key in record.deleted.iter() {
+			death_index.insert(key.clone(), block);
+		}
+		death_rows.push(record.deleted.iter().cloned().collect());
+	}
+	let mut deleted = HashSet::new();
+	for (record, row) in journals.iter_mut().zip(death_rows) {
+		record.deleted.retain(|key| row.contains(key) && deleted.insert(key.clone()));
+	}
+}
+
 /// The result return by `RefWindow::have_block`
 #[derive(Debug, PartialEq, Eq)]
 pub enum HaveBlock {
@@ -308,10 +364,7 @@ impl<BlockHash: Hash, Key: Hash, D: MetaDb> RefWindow<BlockHash, Key, D> {
 	) -> Result<RefWindow<BlockHash, Key, D>, Error<D::Error>> {
 		// the block number of the first block in the queue or the next block number if the queue is
 		// empty
-		let base = match db.get_meta(&to_meta_key(LAST_PRUNED, &())).map_err(Error::Db)? {
-			Some(buffer) => u64::decode(&mut buffer.as_slice())? + 1,
-			None => 0,
-		};
+		let base = fetch_last_pruned(&db)?.map_or(0, |last_pruned| last_pruned + 1);
 		// the block number of the last block in the queue
 		let last_canonicalized_number =
 			match db.get_meta(&to_meta_key(LAST_CANONICAL, &())).map_err(Error::Db)? {
@@ -350,6 +403,11 @@ impl<BlockHash: Hash, Key: Hash, D: MetaDb> RefWindow<BlockHash, Key, D> {
 		Ok(RefWindow { queue, base })
 	}
 
+	/// Block number that is next to be pruned.
+	pub fn pending(&self) -> u64 {
+		self.base
+	}
+
 	pub fn window_size(&self) -> u64 {
 		self.queue.len(self.base) as u64
 	}
@@ -439,7 +497,10 @@ impl<BlockHash: Hash, Key: Hash, D: MetaDb> RefWindow<BlockHash, Key, D> {
 
 #[cfg(test)]
 mod tests {
-	use super::{to_journal_key, DeathRowQueue, HaveBlock, JournalRecord, RefWindow, LAST_PRUNED};
+	use super::{
+		fetch_journals, resolve_reinsertions, to_journal_key, DeathRowQueue, HaveBlock,
+		JournalRecord, RefWindow, LAST_PRUNED,
+	};
 	use crate::{
 		noncanonical::LAST_CANONICAL,
 		test::{make_commit, make_db, TestDb},
@@ -594,6 +655,38 @@ mod tests {
 		assert_eq!(pruning.base, 3);
 	}
 
+	#[test]
+	fn resolved_journals_skip_reinserted() {
+		let mut db = make_db(&[1, 2, 3]);
+		let mut pruning: RefWindow<H256, H256, TestDb> =
+			RefWindow::new(db.clone(), DEFAULT_MAX_BLOCK_CONSTRAINT, true).unwrap();
+		for (number, (inserted, deleted)) in
+			[(&[][..], &[2, 3][..]), (&[2], &[]), (&[], &[2, 3])].into_iter().enumerate()
+		{
+			let mut commit = make_commit(inserted, deleted);
+			pruning.note_canonical(&H256::random(), number as u64, &mut commit).unwrap();
+			db.commit(&commit);
+		}
+
+		let mut journals: Vec<JournalRecord<H256, H256>> =
+			fetch_journals(&db).unwrap().into_iter().map(|(_, record)| record).collect();
+		assert_eq!(journals.len(), 3);
+		resolve_reinsertions(&mut journals);
+		let deleted: Vec<_> = journals.iter().map(|record| record.deleted.clone()).collect();
+		assert_eq!(
+			deleted,
+			vec![vec![H256::from_low_u64_be(3)], vec![], vec![H256::from_low_u64_be(2)]]
+		);
+
+		// The resolved deletions are the ones the memory queue applies.
+		let mut commit = CommitSet::default();
+		for _ in 0..3 {
+			pruning.prune_one(&mut commit).unwrap();
+		}
+		db.commit(&commit);
+		assert!(db.data_eq(&make_db(&[1])));
+	}
+
 	#[test]
 	fn reinserted_survive_pending() {
 		let mut db = make_db(&[1, 2, 3]);
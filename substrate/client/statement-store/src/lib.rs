@@ -43,16 +43,25 @@
 //! explicitly with the `remove` function) the statement is marked as expired. Expired statements
 //! can't be added to the store for `Options::purge_after_sec` seconds. This is to prevent old
 //! statements from being propagated on the network.
+//!
+//! Subscriptions.
+//!
+//! Statements that are added to the store are sent to all subscriptions whose filter they match.
+//! Each subscription buffers at most `Options::subscription_buffer` statements. A subscription
+//! that doesn't keep up with the new statements is dropped, which ends its stream. The number of
+//! subscriptions is not limited by the store, but by whoever creates them, e.g. per connection by
+//! the RPC server.
 
 #![warn(missing_docs)]
 #![warn(unused_extern_crates)]
 
 mod metrics;
 
-pub use sp_statement_store::{Error, StatementStore, MAX_TOPICS};
+pub use sp_statement_store::{Error, StatementStore, StatementStream, TopicFilter, MAX_TOPICS};
 
+use futures::{channel::mpsc, StreamExt};
 use metrics::MetricsLink as PrometheusMetrics;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 use prometheus_endpoint::Registry as PrometheusRegistry;
 use sc_keystore::LocalKeystore;
 use sp_api::ProvideRuntimeApi;
@@ -79,6 +88,7 @@ const LOG_TARGET: &str = "statement-store";
 const DEFAULT_PURGE_AFTER_SEC: u64 = 2 * 24 * 60 * 60; //48h
 const DEFAULT_MAX_TOTAL_STATEMENTS: usize = 8192;
 const DEFAULT_MAX_TOTAL_SIZE: usize = 64 * 1024 * 1024;
+const DEFAULT_SUBSCRIPTION_BUFFER: usize = 256;
 
 const MAINTENANCE_PERIOD: std::time::Duration = std::time::Duration::from_secs(30);
 
@@ -137,6 +147,8 @@ pub struct Options {
 	max_total_size: usize,
 	/// Number of seconds for which removed statements won't be allowed to be added back in.
 	purge_after_sec: u64,
+	/// Maximum number of statements buffered for a subscription before it is dropped.
+	subscription_buffer: usize,
 }
 
 impl Default for Options {
@@ -145,6 +157,7 @@ impl Default for Options {
 			max_total_statements: DEFAULT_MAX_TOTAL_STATEMENTS,
 			max_total_size: DEFAULT_MAX_TOTAL_SIZE,
 			purge_after_sec: DEFAULT_PURGE_AFTER_SEC,
+			subscription_buffer: DEFAULT_SUBSCRIPTION_BUFFER,
 		}
 	}
 }
@@ -189,10 +202,18 @@ where
 	}
 }
 
+/// A subscription to new statements.
+struct Subscriber {
+	filter: TopicFilter,
+	dest: Option<DecryptionKey>,
+	sender: mpsc::Sender<Statement>,
+}
+
 /// Statement store.
 pub struct Store {
 	db: parity_db::Db,
 	index: RwLock<Index>,
+	subscribers: Mutex<Vec<Subscriber>>,
 	validate_fn: Box<
 		dyn Fn(
 				Option<BlockHash>,
@@ -569,6 +590,7 @@ impl Store {
 		let store = Store {
 			db,
 			index: RwLock::new(Index::new(options)),
+			subscribers: Mutex::new(Vec::new()),
 			validate_fn,
 			keystore,
 			time_override: None,
@@ -689,6 +711,30 @@ impl Store {
 		);
 	}
 
+	/// Send a newly added statement to the matching subscriptions.
+	fn notify_subscribers(&self, statement: &Statement) {
+		let mut subscribers = self.subscribers.lock();
+		subscribers.retain_mut(|subscriber| {
+			if subscriber.dest != statement.decryption_key() ||
+				!subscriber.filter.matches(statement)
+			{
+				return !subscriber.sender.is_closed()
+			}
+			match subscriber.sender.try_send(statement.clone()) {
+				Ok(()) => true,
+				Err(e) => {
+					if e.is_full() {
+						log::debug!(
+							target: LOG_TARGET,
+							"Dropping subscription that doesn't keep up with new statements",
+						);
+					}
+					false
+				},
+			}
+		});
+	}
+
 	fn timestamp(&self) -> u64 {
 		self.time_override.unwrap_or_else(|| {
 			std::time::SystemTime::now()
@@ -897,6 +943,7 @@ impl StatementStore for Store {
 			}
 		} // Release index lock
 		self.metrics.report(|metrics| metrics.submitted_statements.inc());
+		self.notify_subscribers(&statement);
 		let network_priority = NetworkPriority::High;
 		log::trace!(target: LOG_TARGET, "Statement submitted: {:?}", HexDisplay::from(&hash));
 		SubmitResult::New(network_priority)
@@ -925,11 +972,21 @@ impl StatementStore for Store {
 		}
 		Ok(())
 	}
+
+	/// Subscribe to new statements which match `filter` and whose decryption key is `dest`.
+	fn subscribe(&self, filter: TopicFilter, dest: Option<[u8; 32]>) -> Result<StatementStream> {
+		let buffer = self.index.read().options.subscription_buffer;
+		let mut subscribers = self.subscribers.lock();
+		subscribers.retain(|subscriber| !subscriber.sender.is_closed());
+		let (sender, receiver) = mpsc::channel(buffer);
+		subscribers.push(Subscriber { filter, dest, sender });
+		Ok(receiver.boxed())
+	}
 }
 
 #[cfg(test)]
 mod tests {
-	use crate::Store;
+	use crate::{Options, StatementStream, Store};
 	use sc_keystore::Keystore;
 	use sp_core::Pair;
 	use sp_statement_store::{
@@ -1293,4 +1350,73 @@ mod tests {
 		let posted_clear = store.posted_clear(&[], public.into()).unwrap();
 		assert_eq!(posted_clear, vec![plain]);
 	}
+
+	#[test]
+	fn subscriptions_receive_matching_statements() {
+		use futures::{FutureExt, StreamExt};
+		use sp_statement_store::TopicFilter;
+
+		let (store, _temp) = test_store();
+		let mut all = store.subscribe(TopicFilter::Any, None).unwrap();
+		let mut any =
+			store.subscribe(TopicFilter::MatchAny(vec![topic(1), topic(2)]), None).unwrap();
+		let mut posted = store
+			.subscribe(TopicFilter::MatchAll(vec![topic(0)]), Some(dec_key(2)))
+			.unwrap();
+
+		let statements = vec![
+			signed_statement_with_topics(0, &[topic(0)], None),
+			signed_statement_with_topics(1, &[topic(0), topic(1)], None),
+			signed_statement_with_topics(2, &[topic(0), topic(2)], Some(dec_key(2))),
+			signed_statement_with_topics(3, &[topic(2)], None),
+		];
+		for s in &statements {
+			store.submit(s.clone(), StatementSource::Network);
+		}
+		// Known statements are not sent again.
+		store.submit(statements[0].clone(), StatementSource::Network);
+
+		let received = |stream: &mut StatementStream| {
+			let mut data = Vec::new();
+			while let Some(Some(statement)) = stream.next().now_or_never() {
+				data.push(statement.data().unwrap()[0]);
+			}
+			data
+		};
+		assert_eq!(received(&mut all), vec![0, 1, 3]);
+		assert_eq!(received(&mut any), vec![1, 3]);
+		assert_eq!(received(&mut posted), vec![2]);
+	}
+
+	#[test]
+	fn subscriptions_are_bounded() {
+		use futures::{FutureExt, StreamExt};
+		use sp_statement_store::TopicFilter;
+
+		let temp_dir = tempfile::Builder::new().tempdir().expect("Error creating test dir");
+		let mut path: std::path::PathBuf = temp_dir.path().into();
+		path.push("db");
+		let keystore = std::sync::Arc::new(sc_keystore::LocalKeystore::in_memory());
+		let options = Options { subscription_buffer: 0, ..Default::default() };
+		let store =
+			Store::new(&path, options, std::sync::Arc::new(TestClient), keystore, None).unwrap();
+
+		let mut slow = store.subscribe(TopicFilter::Any, None).unwrap();
+		let mut fast = store.subscribe(TopicFilter::Any, None).unwrap();
+
+		// Each subscription buffers one statement and is dropped on the next one, without
+		// affecting the other subscriptions.
+		for data in 0..3 {
+			store.submit(signed_statement(data), StatementSource::Network);
+			assert_eq!(fast.next().now_or_never().unwrap().unwrap().data(), Some(&vec![data]));
+		}
+		assert_eq!(store.subscribers.lock().len(), 1);
+		assert_eq!(slow.next().now_or_never().unwrap().unwrap().data(), Some(&vec![0]));
+		assert_eq!(slow.next().now_or_never(), Some(None));
+
+		// Closed subscriptions are removed.
+		drop(fast);
+		store.subscribe(TopicFilter::Any, None).unwrap();
+		assert_eq!(store.subscribers.lock().len(), 1);
+	}
 }
@@ -33,6 +33,10 @@ use tracing::{
 	Dispatch, Level, Subscriber,
 };
 
+mod profile;
+
+pub use profile::folded_stacks;
+
 use crate::{SpanDatum, TraceEvent, Values};
 use sc_client_api::BlockBackend;
 use sp_api::{Core, Metadata, ProvideRuntimeApi};
@@ -195,23 +199,7 @@ where
 	/// prefixes in `Self::storage_keys`.
 	pub fn trace_block(&self) -> TraceBlockResult<TraceBlockResponse> {
 		tracing::debug!(target: "state_tracing", "Tracing block: {}", self.block);
-		// Prepare the block
-		let mut header = self
-			.client
-			.header(self.block)
-			.map_err(Error::InvalidBlockId)?
-			.ok_or_else(|| Error::MissingBlockComponent("Header not found".to_string()))?;
-		let extrinsics = self
-			.client
-			.block_body(self.block)
-			.map_err(Error::InvalidBlockId)?
-			.ok_or_else(|| Error::MissingBlockComponent("Extrinsics not found".to_string()))?;
-		tracing::debug!(target: "state_tracing", "Found {} extrinsics", extrinsics.len());
-		let parent_hash = *header.parent_hash();
-		// Remove all `Seal`s as they are added by the consensus engines after building the block.
-		// On import they are normally removed by the consensus engine.
-		header.digest_mut().logs.retain(|d| d.as_seal().is_none());
-		let block = Block::new(header, extrinsics);
+		let (parent_hash, block) = self.prepare_block()?;
 
 		let targets = if let Some(t) = &self.targets { t } else { DEFAULT_TARGETS };
 		let block_subscriber = BlockSubscriber::new(targets);
@@ -278,6 +266,27 @@ where
 			events,
 		}))
 	}
+
+	/// Fetch the block to re-execute, returning it along with the hash of its parent.
+	fn prepare_block(&self) -> TraceBlockResult<(Block::Hash, Block)> {
+		let mut header = self
+			.client
+			.header(self.block)
+			.map_err(Error::InvalidBlockId)?
+			.ok_or_else(|| Error::MissingBlockComponent("Header not found".to_string()))?;
+		let extrinsics = self
+			.client
+			.block_body(self.block)
+			.map_err(Error::InvalidBlockId)?
+			.ok_or_else(|| Error::MissingBlockComponent("Extrinsics not found".to_string()))?;
+		tracing::debug!(target: "state_tracing", "Found {} extrinsics", extrinsics.len());
+		let parent_hash = *header.parent_hash();
+		// Remove all `Seal`s as they are added by the consensus engines after building the block.
+		// On import they are normally removed by the consensus engine.
+		header.digest_mut().logs.retain(|d| d.as_seal().is_none());
+
+		Ok((parent_hash, Block::new(header, extrinsics)))
+	}
 }
 
 fn event_values_filter(event: &TraceEvent, filter_kind: &str, values: &str) -> bool {
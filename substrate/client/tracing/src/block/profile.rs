@@ -0,0 +1,537 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Profiling of the re-execution of a block.
+//!
+//! The [`ProfileSubscriber`] measures the time spent in and the storage proof size added by every
+//! span entered while executing the block. Storage accesses are attributed to the host function
+//! performing them. The measurements are aggregated per stack, which allows to render them as a
+//! flamegraph.
+
+use std::{
+	collections::{BTreeMap, HashMap},
+	sync::atomic::{AtomicU64, Ordering},
+	time::{Duration, Instant},
+};
+
+use parking_lot::Mutex;
+use sc_client_api::BlockBackend;
+use sp_api::{ApiExt, Core, Metadata, ProvideRuntimeApi};
+use sp_blockchain::HeaderBackend;
+use sp_rpc::tracing::{BlockProfile, ProfileFrame, ProfileFrameKind, ProfileMetric};
+use sp_runtime::{generic::BlockId, traits::Block as BlockT};
+use sp_tracing::{WASM_NAME_KEY, WASM_TARGET_KEY, WASM_TRACE_IDENTIFIER};
+use tracing::{
+	dispatcher,
+	span::{Attributes, Id, Record},
+	Dispatch, Level, Subscriber,
+};
+
+use super::{
+	block_id_as_string, check_target, BlockExecutor, Error, TraceBlockResult, TRACE_TARGET,
+};
+use crate::Values;
+
+// Default to pallet, frame support and host function related spans
+const DEFAULT_PROFILE_TARGETS: &str = "pallet,frame,sp_io";
+// The target of the storage access events.
+const STORAGE_EVENT_TARGET: &str = "state";
+// Prefix of the targets of host function spans.
+const HOST_FUNCTION_TARGET: &str = "sp_io";
+// Number of hex characters of storage keys used to label storage accesses. This is enough to
+// cover the pallet and storage item prefix of keys generated by FRAME.
+const STORAGE_KEY_PREFIX_LEN: usize = 64;
+
+/// Storage access methods reading from storage, see `sp_state_machine::Ext`.
+const STORAGE_READ_METHODS: &[&str] =
+	&["Get", "Hash", "ChildGet", "ChildHash", "Exists", "ChildExists"];
+/// Storage access methods writing to storage, see `sp_state_machine::Ext`.
+const STORAGE_WRITE_METHODS: &[&str] =
+	&["Put", "ChildPut", "ChildKill", "ClearPrefix", "ChildClearPrefix", "Append"];
+
+/// A storage access performed while a span was entered.
+struct StorageAccess {
+	kind: ProfileFrameKind,
+	key: String,
+}
+
+/// A span along with its measurements.
+struct ProfiledSpan {
+	parent_id: Option<Id>,
+	name: String,
+	target: String,
+	level: Level,
+	wasm: bool,
+	/// Time and proof size when the span was last entered.
+	entered: Option<(Instant, u64)>,
+	time: Duration,
+	proof_size: u64,
+	storage_accesses: Vec<StorageAccess>,
+}
+
+impl ProfiledSpan {
+	fn kind(&self) -> ProfileFrameKind {
+		// Host functions are traced on the client side, so they need to be classified first.
+		if self.target.starts_with(HOST_FUNCTION_TARGET) {
+			ProfileFrameKind::HostFunction
+		} else if self.wasm {
+			ProfileFrameKind::Runtime
+		} else {
+			ProfileFrameKind::Client
+		}
+	}
+}
+
+/// Subscriber measuring the spans entered while executing a block.
+///
+/// Spans coming from the runtime don't know their contextual parent, so every span without an
+/// explicit parent is considered to be a child of the span entered last.
+struct ProfileSubscriber {
+	targets: Vec<(String, Level)>,
+	next_id: AtomicU64,
+	spans: Mutex<HashMap<Id, ProfiledSpan>>,
+	/// The currently entered spans, the innermost last.
+	entered: Mutex<Vec<Id>>,
+	/// Returns the current size of the storage proof.
+	proof_size: Box<dyn Fn() -> u64 + Send + Sync>,
+}
+
+impl ProfileSubscriber {
+	fn new(targets: &str, proof_size: Box<dyn Fn() -> u64 + Send + Sync>) -> Self {
+		let mut targets: Vec<_> = targets.split(',').map(crate::parse_target).collect();
+		// Ensure that WASM traces are always enabled
+		// Filtering happens when building the profile
+		targets.push((WASM_TRACE_IDENTIFIER.to_owned(), Level::TRACE));
+		targets.push((TRACE_TARGET.to_owned(), Level::TRACE));
+		ProfileSubscriber {
+			targets,
+			next_id: AtomicU64::new(1),
+			spans: Mutex::new(HashMap::new()),
+			entered: Mutex::new(Vec::new()),
+			proof_size,
+		}
+	}
+}
+
+impl Subscriber for ProfileSubscriber {
+	fn enabled(&self, metadata: &tracing::Metadata<'_>) -> bool {
+		if !metadata.is_span() {
+			return metadata.target() == STORAGE_EVENT_TARGET &&
+				metadata.fields().field("method").is_some()
+		}
+		self.targets.iter().any(|(target, level)| {
+			metadata.level() <= level && metadata.target().starts_with(target)
+		})
+	}
+
+	fn new_span(&self, attrs: &Attributes<'_>) -> Id {
+		let id = Id::from_u64(self.next_id.fetch_add(1, Ordering::Relaxed));
+		let mut values = Values::default();
+		attrs.record(&mut values);
+
+		let mut name = attrs.metadata().name().to_owned();
+		let mut target = attrs.metadata().target().to_owned();
+		let wasm = name == WASM_TRACE_IDENTIFIER;
+		if wasm {
+			if let Some(n) = values.string_values.remove(WASM_NAME_KEY) {
+				name = n;
+			}
+			if let Some(t) = values.string_values.remove(WASM_TARGET_KEY) {
+				target = t;
+			}
+		}
+
+		let span = ProfiledSpan {
+			parent_id: attrs.parent().cloned().or_else(|| self.entered.lock().last().cloned()),
+			name,
+			target,
+			level: *attrs.metadata().level(),
+			wasm,
+			entered: None,
+			time: Duration::ZERO,
+			proof_size: 0,
+			storage_accesses: Vec::new(),
+		};
+
+		self.spans.lock().insert(id.clone(), span);
+		id
+	}
+
+	fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+	fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+	fn event(&self, event: &tracing::Event<'_>) {
+		let mut values = Values::default();
+		event.record(&mut values);
+
+		let Some(method) = values.string_values.get("method") else { return };
+		let kind = if STORAGE_READ_METHODS.contains(&method.as_str()) {
+			ProfileFrameKind::StorageRead
+		} else if STORAGE_WRITE_METHODS.contains(&method.as_str()) {
+			ProfileFrameKind::StorageWrite
+		} else {
+			return
+		};
+		let key = values
+			.string_values
+			.get("key")
+			.map(|key| key.chars().take(STORAGE_KEY_PREFIX_LEN).collect())
+			.unwrap_or_default();
+
+		let Some(parent) = event.parent().cloned().or_else(|| self.entered.lock().last().cloned())
+		else {
+			return
+		};
+		if let Some(span) = self.spans.lock().get_mut(&parent) {
+			span.storage_accesses.push(StorageAccess { kind, key });
+		}
+	}
+
+	fn enter(&self, id: &Id) {
+		let proof_size = (self.proof_size)();
+		if let Some(span) = self.spans.lock().get_mut(id) {
+			span.entered = Some((Instant::now(), proof_size));
+		}
+		self.entered.lock().push(id.clone());
+	}
+
+	fn exit(&self, id: &Id) {
+		let proof_size = (self.proof_size)();
+		if let Some(span) = self.spans.lock().get_mut(id) {
+			if let Some((entered_at, entered_proof_size)) = span.entered.take() {
+				span.time += entered_at.elapsed();
+				span.proof_size += proof_size.saturating_sub(entered_proof_size);
+			}
+		}
+
+		let mut entered = self.entered.lock();
+		if let Some(pos) = entered.iter().rposition(|entered| entered == id) {
+			entered.remove(pos);
+		}
+	}
+}
+
+/// Aggregate the measured `spans` matching `targets` into frames, one per distinct stack.
+///
+/// The measurements of spans not matching `targets` are attributed to their closest ancestor that
+/// does.
+fn build_frames(spans: HashMap<Id, ProfiledSpan>, targets: &str) -> Vec<ProfileFrame> {
+	let kept = |span: &ProfiledSpan| {
+		span.target == TRACE_TARGET || check_target(targets, &span.target, &span.level)
+	};
+	let kept_ancestor = |mut id: Option<&Id>| {
+		while let Some(span) = id.and_then(|id| spans.get(id)) {
+			if kept(span) {
+				return id.cloned()
+			}
+			id = span.parent_id.as_ref();
+		}
+		None
+	};
+
+	// The closest kept ancestor of every kept span and the totals of its kept children.
+	let mut parents = HashMap::new();
+	let mut children_totals = HashMap::<Id, (Duration, u64)>::new();
+	for (id, span) in spans.iter().filter(|(_, span)| kept(span)) {
+		let parent = kept_ancestor(span.parent_id.as_ref());
+		if let Some(parent) = &parent {
+			let totals = children_totals.entry(parent.clone()).or_default();
+			totals.0 += span.time;
+			totals.1 += span.proof_size;
+		}
+		parents.insert(id.clone(), parent);
+	}
+
+	let mut stacks = HashMap::<Id, Vec<String>>::new();
+	fn stack_of(
+		id: &Id,
+		spans: &HashMap<Id, ProfiledSpan>,
+		parents: &HashMap<Id, Option<Id>>,
+		stacks: &mut HashMap<Id, Vec<String>>,
+	) -> Vec<String> {
+		if let Some(stack) = stacks.get(id) {
+			return stack.clone()
+		}
+		let mut stack = match &parents[id] {
+			Some(parent) => stack_of(parent, spans, parents, stacks),
+			None => Vec::new(),
+		};
+		let span = &spans[id];
+		stack.push(format!("{}::{}", span.target, span.name).replace(';', ":"));
+		stacks.insert(id.clone(), stack.clone());
+		stack
+	}
+
+	// Storage accesses are attributed to the closest kept span they happened in.
+	let mut storage_accesses = HashMap::<Id, Vec<&StorageAccess>>::new();
+	for (id, span) in &spans {
+		if let Some(kept) = kept_ancestor(Some(id)) {
+			storage_accesses.entry(kept).or_default().extend(&span.storage_accesses);
+		}
+	}
+
+	let mut frames = BTreeMap::<Vec<String>, ProfileFrame>::new();
+	let mut add = |stack: Vec<String>,
+	               kind: ProfileFrameKind,
+	               time: Duration,
+	               self_time: Duration,
+	               proof_size: u64,
+	               self_proof_size: u64| {
+		let frame = frames.entry(stack.clone()).or_insert_with(|| ProfileFrame {
+			stack,
+			kind,
+			calls: 0,
+			self_time: 0,
+			total_time: 0,
+			self_proof_size: 0,
+			total_proof_size: 0,
+		});
+		frame.calls += 1;
+		frame.self_time += self_time.as_nanos() as u64;
+		frame.total_time += time.as_nanos() as u64;
+		frame.self_proof_size += self_proof_size;
+		frame.total_proof_size += proof_size;
+	};
+
+	for id in parents.keys() {
+		let span = &spans[id];
+		let kind = span.kind();
+		let stack = stack_of(id, &spans, &parents, &mut stacks);
+		let (children_time, children_proof_size) =
+			children_totals.get(id).copied().unwrap_or_default();
+		let mut self_time = span.time.saturating_sub(children_time);
+		let mut self_proof_size = span.proof_size.saturating_sub(children_proof_size);
+
+		let accesses = storage_accesses.remove(id).unwrap_or_default();
+		// The time and proof size of a host function are those of the storage accesses it
+		// performs, other spans only count their storage accesses.
+		let (access_time, access_proof_size) =
+			if kind == ProfileFrameKind::HostFunction && !accesses.is_empty() {
+				let (time, proof_size) =
+					(self_time / accesses.len() as u32, self_proof_size / accesses.len() as u64);
+				self_time = Duration::ZERO;
+				self_proof_size = 0;
+				(time, proof_size)
+			} else {
+				(Duration::ZERO, 0)
+			};
+		for access in accesses {
+			let label = match access.kind {
+				ProfileFrameKind::StorageRead => "storage_read",
+				_ => "storage_write",
+			};
+			let mut access_stack = stack.clone();
+			access_stack.push(format!("{}({})", label, access.key));
+			add(
+				access_stack,
+				access.kind,
+				access_time,
+				access_time,
+				access_proof_size,
+				access_proof_size,
+			);
+		}
+
+		add(stack, kind, span.time, self_time, span.proof_size, self_proof_size);
+	}
+
+	frames.into_values().collect()
+}
+
+/// Render `frames` in the folded stacks format, weighed by `metric`.
+///
+/// Stacks without any self value are omitted.
+pub fn folded_stacks(frames: &[ProfileFrame], metric: ProfileMetric) -> String {
+	frames
+		.iter()
+		.filter_map(|frame| {
+			let value = match metric {
+				ProfileMetric::Time => frame.self_time,
+				ProfileMetric::ProofSize => frame.self_proof_size,
+			};
+			(value > 0).then(|| format!("{} {}\n", frame.stack.join(";"), value))
+		})
+		.collect()
+}
+
+impl<Block, Client> BlockExecutor<Block, Client>
+where
+	Block: BlockT + 'static,
+	Client: HeaderBackend<Block>
+		+ BlockBackend<Block>
+		+ ProvideRuntimeApi<Block>
+		+ Send
+		+ Sync
+		+ 'static,
+	Client::Api: Metadata<Block>,
+{
+	/// Execute block and measure the time spent in and the storage proof size added by all spans
+	/// belonging to `Self::targets`.
+	///
+	/// The resulting profile is rendered as folded stacks weighed by `metric`.
+	pub fn profile_block(&self, metric: ProfileMetric) -> TraceBlockResult<BlockProfile> {
+		tracing::debug!(target: "state_tracing", "Profiling block: {}", self.block);
+		let (parent_hash, block) = self.prepare_block()?;
+
+		let mut runtime_api = self.client.runtime_api();
+		runtime_api.record_proof();
+		let recorder = runtime_api
+			.proof_recorder()
+			.ok_or_else(|| Error::Dispatch("Proof recording is not enabled".to_string()))?;
+
+		let targets = self.targets.as_deref().unwrap_or(DEFAULT_PROFILE_TARGETS);
+		let dispatch = Dispatch::new(ProfileSubscriber::new(
+			targets,
+			Box::new(move || recorder.estimate_encoded_size() as u64),
+		));
+
+		if let Err(e) = dispatcher::with_default(&dispatch, || {
+			let span = tracing::info_span!(target: TRACE_TARGET, "profile_block");
+			let _enter = span.enter();
+			runtime_api.execute_block(parent_hash, block)
+		}) {
+			return Err(Error::Dispatch(format!(
+				"Failed to collect traces and execute block: {}",
+				e
+			)))
+		}
+
+		let subscriber = dispatch.downcast_ref::<ProfileSubscriber>().ok_or_else(|| {
+			Error::Dispatch(
+				"Cannot downcast Dispatch to ProfileSubscriber after profiling block".to_string(),
+			)
+		})?;
+		let frames = build_frames(std::mem::take(&mut *subscriber.spans.lock()), targets);
+		tracing::debug!(target: "state_tracing", "Captured {} distinct stacks", frames.len());
+
+		Ok(BlockProfile {
+			block_hash: block_id_as_string(BlockId::<Block>::Hash(self.block)),
+			parent_hash: block_id_as_string(BlockId::<Block>::Hash(parent_hash)),
+			tracing_targets: targets.to_string(),
+			metric,
+			folded: folded_stacks(&frames, metric),
+			frames,
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::sync::Arc;
+
+	#[test]
+	fn spans_are_aggregated_per_stack() {
+		let proof_size = Arc::new(AtomicU64::new(0));
+		let dispatch = Dispatch::new(ProfileSubscriber::new("pallet", {
+			let proof_size = proof_size.clone();
+			Box::new(move || proof_size.load(Ordering::Relaxed))
+		}));
+
+		dispatcher::with_default(&dispatch, || {
+			let outer = tracing::info_span!(target: "pallet_test", "outer");
+			let _outer = outer.enter();
+			for _ in 0..2 {
+				let inner = tracing::info_span!(target: "pallet_test", "inner");
+				let _inner = inner.enter();
+				proof_size.fetch_add(10, Ordering::Relaxed);
+				{
+					// Not matching the targets, so it is attributed to `inner`.
+					let hidden = tracing::info_span!(target: "other", "hidden");
+					let _hidden = hidden.enter();
+					proof_size.fetch_add(5, Ordering::Relaxed);
+					tracing::trace!(target: "state", method = "Put", key = "aabb");
+				}
+			}
+			proof_size.fetch_add(1, Ordering::Relaxed);
+		});
+
+		let subscriber = dispatch.downcast_ref::<ProfileSubscriber>().unwrap();
+		let frames = build_frames(std::mem::take(&mut *subscriber.spans.lock()), "pallet");
+		let summary = frames
+			.iter()
+			.map(|f| (f.stack.join(";"), f.kind, f.calls, f.self_proof_size, f.total_proof_size))
+			.collect::<Vec<_>>();
+		assert_eq!(
+			summary,
+			vec![
+				("pallet_test::outer".to_string(), ProfileFrameKind::Client, 1, 1, 31),
+				(
+					"pallet_test::outer;pallet_test::inner".to_string(),
+					ProfileFrameKind::Client,
+					2,
+					30,
+					30
+				),
+				(
+					"pallet_test::outer;pallet_test::inner;storage_write(aabb)".to_string(),
+					ProfileFrameKind::StorageWrite,
+					2,
+					0,
+					0
+				),
+			]
+		);
+		assert!(frames[0].total_time >= frames[1].total_time);
+
+		assert_eq!(
+			folded_stacks(&frames, ProfileMetric::ProofSize),
+			"pallet_test::outer 1\npallet_test::outer;pallet_test::inner 30\n",
+		);
+	}
+
+	#[test]
+	fn host_functions_are_classified_by_target() {
+		let dispatch = Dispatch::new(ProfileSubscriber::new("sp_io", Box::new(|| 0)));
+
+		dispatcher::with_default(&dispatch, || {
+			sp_io::TestExternalities::default().execute_with(|| {
+				sp_io::storage::set(b"key", b"value");
+				assert_eq!(sp_io::storage::get(b"key").as_deref(), Some(&b"value"[..]));
+			});
+		});
+
+		let subscriber = dispatch.downcast_ref::<ProfileSubscriber>().unwrap();
+		let frames = build_frames(std::mem::take(&mut *subscriber.spans.lock()), "sp_io");
+		let summary =
+			frames.iter().map(|f| (f.stack.join(";"), f.kind, f.calls)).collect::<Vec<_>>();
+		assert_eq!(
+			summary,
+			vec![
+				("sp_io::storage::get_version_1".to_string(), ProfileFrameKind::HostFunction, 1),
+				(
+					"sp_io::storage::get_version_1;storage_read(6b6579)".to_string(),
+					ProfileFrameKind::StorageRead,
+					1
+				),
+				("sp_io::storage::set_version_1".to_string(), ProfileFrameKind::HostFunction, 1),
+				(
+					"sp_io::storage::set_version_1;storage_write(6b6579)".to_string(),
+					ProfileFrameKind::StorageWrite,
+					1
+				),
+			]
+		);
+		// The storage accesses carry the measurements of the host functions performing them.
+		for frame in &frames {
+			match frame.kind {
+				ProfileFrameKind::HostFunction => assert_eq!(frame.self_time, 0),
+				_ => assert_eq!(frame.self_time, frame.total_time),
+			}
+		}
+	}
+}
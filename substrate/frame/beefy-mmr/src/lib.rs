@@ -33,18 +33,24 @@
 //!
 //! and thanks to versioning can be easily updated in the future.
 
-use sp_runtime::traits::{Convert, Member};
+use sp_runtime::traits::{Convert, Header, Member};
 use sp_std::prelude::*;
 
 use codec::Decode;
-use pallet_mmr::{LeafDataProvider, ParentNumberAndHash};
+use pallet_mmr::{
+	primitives::{DataOrHash, OpaqueLeaf},
+	LeafDataProvider, ParentNumberAndHash,
+};
 use sp_consensus_beefy::{
-	mmr::{BeefyAuthoritySet, BeefyDataProvider, BeefyNextAuthoritySet, MmrLeaf, MmrLeafVersion},
-	ValidatorSet as BeefyValidatorSet,
+	mmr::{
+		find_header_mmr_root_digest, AncestryProof, BeefyAuthoritySet, BeefyDataProvider,
+		BeefyNextAuthoritySet, MmrLeaf, MmrLeafVersion,
+	},
+	AncestryHelper, MmrHashing, ValidatorSet as BeefyValidatorSet,
 };
 
 use frame_support::{crypto::ecdsa::ECDSAExt, traits::Get};
-use frame_system::pallet_prelude::BlockNumberFor;
+use frame_system::pallet_prelude::{BlockNumberFor, HeaderFor};
 
 pub use pallet::*;
 
@@ -157,6 +163,26 @@ impl<T: Config> LeafDataProvider for Pallet<T> {
 	}
 }
 
+/// Proves blocks canonical using the MMR leaf of their child block, which records their number
+/// and hash.
+impl<T> AncestryHelper<HeaderFor<T>> for Pallet<T>
+where
+	T: Config + pallet_mmr::Config<Hashing = MmrHashing>,
+{
+	fn is_canonical(header: &HeaderFor<T>, proof: &AncestryProof<HeaderFor<T>>) -> bool {
+		let Some(root) = find_header_mmr_root_digest(&proof.context_header) else { return false };
+		let Ok(leaf) = <Self as LeafDataProvider>::LeafData::decode(&mut &proof.leaf[..]) else {
+			return false
+		};
+		if leaf.parent_number_and_hash != (*header.number(), header.hash()) {
+			return false
+		}
+		let leaves = vec![DataOrHash::Data(OpaqueLeaf::from_encoded_leaf(proof.leaf.clone()))];
+		pallet_mmr::verify_leaves_proof::<MmrHashing, _>(root, leaves, proof.leaf_proof.clone())
+			.is_ok()
+	}
+}
+
 impl<T> sp_consensus_beefy::OnNewValidatorSet<<T as pallet_beefy::Config>::BeefyId> for Pallet<T>
 where
 	T: pallet::Config,
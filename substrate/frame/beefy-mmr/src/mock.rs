@@ -102,6 +102,7 @@ impl pallet_beefy::Config for Test {
 	type WeightInfo = ();
 	type KeyOwnerProof = sp_core::Void;
 	type EquivocationReportSystem = ();
+	type AncestryHelper = BeefyMmr;
 }
 
 parameter_types! {
@@ -19,13 +19,17 @@ use std::vec;
 
 use codec::{Decode, Encode};
 use sp_consensus_beefy::{
-	mmr::{BeefyNextAuthoritySet, MmrLeafVersion},
-	ValidatorSet,
+	mmr::{AncestryProof, BeefyNextAuthoritySet, MmrLeafVersion},
+	AncestryHelper, ValidatorSet,
 };
 
+use frame_system::pallet_prelude::HeaderFor;
 use sp_core::H256;
 use sp_io::TestExternalities;
-use sp_runtime::{traits::Keccak256, DigestItem};
+use sp_runtime::{
+	traits::{Hash, Header, Keccak256},
+	DigestItem,
+};
 
 use frame_support::traits::OnInitialize;
 
@@ -207,3 +211,70 @@ fn should_update_authorities() {
 		assert_eq!(want, next_auth_set.keyset_commitment);
 	});
 }
+
+#[test]
+fn should_check_ancestry_proofs() {
+	let header = |number: u64, digest: Option<DigestItem>| {
+		let mut header = HeaderFor::<Test>::new(
+			number,
+			Default::default(),
+			Default::default(),
+			Default::default(),
+			Default::default(),
+		);
+		header.digest_mut().logs.extend(digest);
+		header
+	};
+	let proven_header = header(1, None);
+	let leaf = |parent_number_and_hash| {
+		MmrLeaf {
+			version: MmrLeafVersion::new(1, 5),
+			parent_number_and_hash,
+			beefy_next_authority_set: BeefyNextAuthoritySet {
+				id: 1,
+				len: 0,
+				keyset_commitment: Default::default(),
+			},
+			leaf_extra: vec![],
+		}
+		.encode()
+	};
+	// an MMR with a single leaf has the hash of that leaf as root
+	let ancestry_proof = |leaf: Vec<u8>, mmr_root| AncestryProof {
+		context_header: header(2, mmr_root.map(|root| beefy_log(ConsensusLog::MmrRoot(root)))),
+		leaf_proof: pallet_mmr::primitives::Proof {
+			leaf_indices: vec![0],
+			leaf_count: 1,
+			items: vec![],
+		},
+		leaf,
+	};
+
+	new_test_ext(vec![1, 2, 3, 4]).execute_with(|| {
+		let valid_leaf = leaf((1, proven_header.hash()));
+		let root = Keccak256::hash(&valid_leaf);
+		assert!(BeefyMmr::is_canonical(
+			&proven_header,
+			&ancestry_proof(valid_leaf.clone(), Some(root))
+		));
+
+		// context header without MMR root
+		assert!(!BeefyMmr::is_canonical(&proven_header, &ancestry_proof(valid_leaf.clone(), None)));
+
+		// leaf not part of the MMR
+		assert!(!BeefyMmr::is_canonical(
+			&proven_header,
+			&ancestry_proof(valid_leaf, Some(H256::repeat_byte(1)))
+		));
+
+		// leaf committing to another block
+		let other_leaf = leaf((1, H256::repeat_byte(2)));
+		let root = Keccak256::hash(&other_leaf);
+		assert!(!BeefyMmr::is_canonical(&proven_header, &ancestry_proof(other_leaf, Some(root))));
+
+		// leaf which isn't an MMR leaf at all
+		let garbage = vec![1, 2, 3];
+		let root = Keccak256::hash(&garbage);
+		assert!(!BeefyMmr::is_canonical(&proven_header, &ancestry_proof(garbage, Some(root))));
+	});
+}
@@ -50,6 +50,23 @@ impl crate::WeightInfo for () {
 			.saturating_add(DbWeight::get().reads(2))
 	}
 
+	fn report_fork_voting(validator_count: u32, max_nominators_per_validator: u32) -> Weight {
+		// same as an equivocation report, with the canonical block hash lookup
+		// replacing the second vote check
+		Self::report_equivocation(validator_count, max_nominators_per_validator)
+			.saturating_add(DbWeight::get().reads(1))
+	}
+
+	fn report_future_block_voting(
+		validator_count: u32,
+		max_nominators_per_validator: u32,
+	) -> Weight {
+		// same as an equivocation report, with the current block number lookup
+		// replacing the second vote check
+		Self::report_equivocation(validator_count, max_nominators_per_validator)
+			.saturating_add(DbWeight::get().reads(1))
+	}
+
 	fn set_new_genesis() -> Weight {
 		DbWeight::get().writes(1)
 	}
@@ -17,8 +17,9 @@
 
 //! An opt-in utility module for reporting equivocations.
 //!
-//! This module defines an offence type for BEEFY equivocations
-//! and some utility traits to wire together:
+//! This module defines offence types for BEEFY equivocations, votes on
+//! non-canonical forks and votes on future blocks, and some utility traits
+//! to wire together:
 //! - a key ownership proof system (e.g. to prove that a given authority was part of a session);
 //! - a system for reporting offences;
 //! - a system for signing and submitting transactions;
@@ -36,10 +37,14 @@
 
 use codec::{self as codec, Decode, Encode};
 use frame_support::traits::{Get, KeyOwnerProofSystem};
-use frame_system::pallet_prelude::BlockNumberFor;
+use frame_system::pallet_prelude::{BlockNumberFor, HeaderFor};
 use log::{error, info};
-use sp_consensus_beefy::{EquivocationProof, ValidatorSetId, KEY_TYPE as BEEFY_KEY_TYPE};
+use sp_consensus_beefy::{
+	AncestryHelper, EquivocationProof, ForkVotingProof, FutureBlockVotingProof, ValidatorSetId,
+	KEY_TYPE as BEEFY_KEY_TYPE,
+};
 use sp_runtime::{
+	traits::Header as HeaderT,
 	transaction_validity::{
 		InvalidTransaction, TransactionPriority, TransactionSource, TransactionValidity,
 		TransactionValidityError, ValidTransaction,
@@ -111,6 +116,94 @@ where
 	}
 }
 
+/// BEEFY fork voting offence report.
+pub struct ForkVotingOffence<Offender, N>
+where
+	N: Copy + Clone + PartialOrd + Ord + Eq + PartialEq + Encode + Decode,
+{
+	/// Time slot at which this incident happened.
+	pub time_slot: TimeSlot<N>,
+	/// The session index in which the incident happened.
+	pub session_index: SessionIndex,
+	/// The size of the validator set at the time of the offence.
+	pub validator_set_count: u32,
+	/// The authority which produced the invalid vote.
+	pub offender: Offender,
+}
+
+impl<Offender: Clone, N> Offence<Offender> for ForkVotingOffence<Offender, N>
+where
+	N: Copy + Clone + PartialOrd + Ord + Eq + PartialEq + Encode + Decode,
+{
+	const ID: Kind = *b"beefy:forkvoting";
+	type TimeSlot = TimeSlot<N>;
+
+	fn offenders(&self) -> Vec<Offender> {
+		vec![self.offender.clone()]
+	}
+
+	fn session_index(&self) -> SessionIndex {
+		self.session_index
+	}
+
+	fn validator_set_count(&self) -> u32 {
+		self.validator_set_count
+	}
+
+	fn time_slot(&self) -> Self::TimeSlot {
+		self.time_slot
+	}
+
+	// Same formula as for `EquivocationOffence`: min((3k / n)^2, 1)
+	fn slash_fraction(&self, offenders_count: u32) -> Perbill {
+		Perbill::from_rational(3 * offenders_count, self.validator_set_count).square()
+	}
+}
+
+/// BEEFY future block voting offence report.
+pub struct FutureBlockVotingOffence<Offender, N>
+where
+	N: Copy + Clone + PartialOrd + Ord + Eq + PartialEq + Encode + Decode,
+{
+	/// Time slot at which this incident happened.
+	pub time_slot: TimeSlot<N>,
+	/// The session index in which the incident happened.
+	pub session_index: SessionIndex,
+	/// The size of the validator set at the time of the offence.
+	pub validator_set_count: u32,
+	/// The authority which produced the invalid vote.
+	pub offender: Offender,
+}
+
+impl<Offender: Clone, N> Offence<Offender> for FutureBlockVotingOffence<Offender, N>
+where
+	N: Copy + Clone + PartialOrd + Ord + Eq + PartialEq + Encode + Decode,
+{
+	const ID: Kind = *b"beefy:futurevote";
+	type TimeSlot = TimeSlot<N>;
+
+	fn offenders(&self) -> Vec<Offender> {
+		vec![self.offender.clone()]
+	}
+
+	fn session_index(&self) -> SessionIndex {
+		self.session_index
+	}
+
+	fn validator_set_count(&self) -> u32 {
+		self.validator_set_count
+	}
+
+	fn time_slot(&self) -> Self::TimeSlot {
+		self.time_slot
+	}
+
+	// Same formula as for `EquivocationOffence`: min((3k / n)^2, 1)
+	fn slash_fraction(&self, offenders_count: u32) -> Perbill {
+		Perbill::from_rational(3 * offenders_count, self.validator_set_count).square()
+	}
+}
+
 /// BEEFY equivocation offence report system.
 ///
 /// This type implements `OffenceReportSystem` such that:
@@ -122,24 +215,153 @@ where
 pub struct EquivocationReportSystem<T, R, P, L>(sp_std::marker::PhantomData<(T, R, P, L)>);
 
 /// Equivocation evidence convenience alias.
-pub type EquivocationEvidenceFor<T> = (
-	EquivocationProof<
-		BlockNumberFor<T>,
-		<T as Config>::BeefyId,
-		<<T as Config>::BeefyId as RuntimeAppPublic>::Signature,
-	>,
-	<T as Config>::KeyOwnerProof,
-);
+pub enum EquivocationEvidenceFor<T: Config> {
+	/// Two conflicting votes in the same round.
+	DoubleVoting(
+		EquivocationProof<
+			BlockNumberFor<T>,
+			<T as Config>::BeefyId,
+			<<T as Config>::BeefyId as RuntimeAppPublic>::Signature,
+		>,
+		<T as Config>::KeyOwnerProof,
+	),
+	/// A vote on a block which is not part of the canonical chain.
+	ForkVoting(
+		ForkVotingProof<
+			BlockNumberFor<T>,
+			<T as Config>::BeefyId,
+			<<T as Config>::BeefyId as RuntimeAppPublic>::Signature,
+			HeaderFor<T>,
+		>,
+		<T as Config>::KeyOwnerProof,
+	),
+	/// A vote on a block which has not been produced yet.
+	FutureBlockVoting(
+		FutureBlockVotingProof<
+			BlockNumberFor<T>,
+			<T as Config>::BeefyId,
+			<<T as Config>::BeefyId as RuntimeAppPublic>::Signature,
+		>,
+		<T as Config>::KeyOwnerProof,
+	),
+}
+
+impl<T: Config> EquivocationEvidenceFor<T> {
+	/// Returns the authority id of the misbehaving voter.
+	fn offender_id(&self) -> &T::BeefyId {
+		match self {
+			EquivocationEvidenceFor::DoubleVoting(proof, _) => proof.offender_id(),
+			EquivocationEvidenceFor::ForkVoting(proof, _) => proof.offender_id(),
+			EquivocationEvidenceFor::FutureBlockVoting(proof, _) => proof.offender_id(),
+		}
+	}
+
+	/// Returns the round number at which the misbehavior occurred.
+	fn round_number(&self) -> &BlockNumberFor<T> {
+		match self {
+			EquivocationEvidenceFor::DoubleVoting(proof, _) => proof.round_number(),
+			EquivocationEvidenceFor::ForkVoting(proof, _) => proof.round_number(),
+			EquivocationEvidenceFor::FutureBlockVoting(proof, _) => proof.round_number(),
+		}
+	}
+
+	/// Returns the set id at which the misbehavior occurred.
+	fn set_id(&self) -> ValidatorSetId {
+		match self {
+			EquivocationEvidenceFor::DoubleVoting(proof, _) => proof.set_id(),
+			EquivocationEvidenceFor::ForkVoting(proof, _) => proof.set_id(),
+			EquivocationEvidenceFor::FutureBlockVoting(proof, _) => proof.set_id(),
+		}
+	}
+
+	/// Returns the key ownership proof of the misbehaving voter.
+	fn key_owner_proof(&self) -> &T::KeyOwnerProof {
+		match self {
+			EquivocationEvidenceFor::DoubleVoting(_, key_owner_proof) |
+			EquivocationEvidenceFor::ForkVoting(_, key_owner_proof) |
+			EquivocationEvidenceFor::FutureBlockVoting(_, key_owner_proof) => key_owner_proof,
+		}
+	}
+
+	/// Prefix of the tag under which unsigned reports of this kind are provided to the pool.
+	fn tag_prefix(&self) -> &'static str {
+		match self {
+			EquivocationEvidenceFor::DoubleVoting(..) => "BeefyEquivocation",
+			EquivocationEvidenceFor::ForkVoting(..) => "BeefyForkVoting",
+			EquivocationEvidenceFor::FutureBlockVoting(..) => "BeefyFutureBlockVoting",
+		}
+	}
+
+	/// Checks the misbehavior proof against the current chain state.
+	fn check_equivocation_proof(&self) -> Result<(), Error<T>> {
+		match self {
+			EquivocationEvidenceFor::DoubleVoting(equivocation_proof, _) => {
+				// Check votes are different and signatures are valid.
+				if !sp_consensus_beefy::check_equivocation_proof(equivocation_proof) {
+					return Err(Error::<T>::InvalidEquivocationProof)
+				}
+			},
+			EquivocationEvidenceFor::ForkVoting(fork_voting_proof, _) => {
+				// The canonical hash is only available for the last `BlockHashCount` blocks,
+				// older blocks have to be proven canonical through an ancestry proof.
+				let block_number = *fork_voting_proof.round_number();
+				let mut expected_block_hash = <frame_system::Pallet<T>>::block_hash(block_number);
+				if expected_block_hash == Default::default() {
+					let Some(ancestry_proof) = &fork_voting_proof.ancestry_proof else {
+						return Err(Error::<T>::InvalidForkVotingProof)
+					};
+					let context_header = &ancestry_proof.context_header;
+					let context_hash =
+						<frame_system::Pallet<T>>::block_hash(*context_header.number());
+					if context_hash == Default::default() ||
+						context_hash != context_header.hash() ||
+						!T::AncestryHelper::is_canonical(
+							&fork_voting_proof.header,
+							ancestry_proof,
+						) {
+						return Err(Error::<T>::InvalidForkVotingProof)
+					}
+					expected_block_hash = fork_voting_proof.header.hash();
+				}
+				if !sp_consensus_beefy::check_fork_equivocation_proof(
+					fork_voting_proof,
+					&expected_block_hash,
+				) {
+					return Err(Error::<T>::InvalidForkVotingProof)
+				}
+			},
+			EquivocationEvidenceFor::FutureBlockVoting(future_block_voting_proof, _) => {
+				let best_block_number = <frame_system::Pallet<T>>::block_number();
+				if !sp_consensus_beefy::check_future_block_voting_proof(
+					future_block_voting_proof,
+					best_block_number,
+				) {
+					return Err(Error::<T>::InvalidFutureBlockVotingProof)
+				}
+			},
+		}
+
+		Ok(())
+	}
+}
 
 impl<T, R, P, L> OffenceReportSystem<Option<T::AccountId>, EquivocationEvidenceFor<T>>
 	for EquivocationReportSystem<T, R, P, L>
 where
 	T: Config + pallet_authorship::Config + frame_system::offchain::SendTransactionTypes<Call<T>>,
 	R: ReportOffence<
-		T::AccountId,
-		P::IdentificationTuple,
-		EquivocationOffence<P::IdentificationTuple, BlockNumberFor<T>>,
-	>,
+			T::AccountId,
+			P::IdentificationTuple,
+			EquivocationOffence<P::IdentificationTuple, BlockNumberFor<T>>,
+		> + ReportOffence<
+			T::AccountId,
+			P::IdentificationTuple,
+			ForkVotingOffence<P::IdentificationTuple, BlockNumberFor<T>>,
+		> + ReportOffence<
+			T::AccountId,
+			P::IdentificationTuple,
+			FutureBlockVotingOffence<P::IdentificationTuple, BlockNumberFor<T>>,
+		>,
 	P: KeyOwnerProofSystem<(KeyTypeId, T::BeefyId), Proof = T::KeyOwnerProof>,
 	P::IdentificationTuple: Clone,
 	L: Get<u64>,
@@ -148,11 +370,25 @@ where
 
 	fn publish_evidence(evidence: EquivocationEvidenceFor<T>) -> Result<(), ()> {
 		use frame_system::offchain::SubmitTransaction;
-		let (equivocation_proof, key_owner_proof) = evidence;
 
-		let call = Call::report_equivocation_unsigned {
-			equivocation_proof: Box::new(equivocation_proof),
-			key_owner_proof,
+		let call = match evidence {
+			EquivocationEvidenceFor::DoubleVoting(equivocation_proof, key_owner_proof) =>
+				Call::report_equivocation_unsigned {
+					equivocation_proof: Box::new(equivocation_proof),
+					key_owner_proof,
+				},
+			EquivocationEvidenceFor::ForkVoting(fork_voting_proof, key_owner_proof) =>
+				Call::report_fork_voting_unsigned {
+					fork_voting_proof: Box::new(fork_voting_proof),
+					key_owner_proof,
+				},
+			EquivocationEvidenceFor::FutureBlockVoting(
+				future_block_voting_proof,
+				key_owner_proof,
+			) => Call::report_future_block_voting_unsigned {
+				future_block_voting_proof: Box::new(future_block_voting_proof),
+				key_owner_proof,
+			},
 		};
 
 		let res = SubmitTransaction::<T, Call<T>>::submit_unsigned_transaction(call.into());
@@ -166,19 +402,36 @@ where
 	fn check_evidence(
 		evidence: EquivocationEvidenceFor<T>,
 	) -> Result<(), TransactionValidityError> {
-		let (equivocation_proof, key_owner_proof) = evidence;
-
 		// Check the membership proof to extract the offender's id
-		let key = (BEEFY_KEY_TYPE, equivocation_proof.offender_id().clone());
-		let offender = P::check_proof(key, key_owner_proof).ok_or(InvalidTransaction::BadProof)?;
+		let key = (BEEFY_KEY_TYPE, evidence.offender_id().clone());
+		let offender = P::check_proof(key, evidence.key_owner_proof().clone())
+			.ok_or(InvalidTransaction::BadProof)?;
 
 		// Check if the offence has already been reported, and if so then we can discard the report.
-		let time_slot = TimeSlot {
-			set_id: equivocation_proof.set_id(),
-			round: *equivocation_proof.round_number(),
+		let time_slot = TimeSlot { set_id: evidence.set_id(), round: *evidence.round_number() };
+
+		let is_known_offence = match evidence {
+			EquivocationEvidenceFor::DoubleVoting(..) =>
+				<R as ReportOffence<
+					_,
+					_,
+					EquivocationOffence<P::IdentificationTuple, BlockNumberFor<T>>,
+				>>::is_known_offence(&[offender], &time_slot),
+			EquivocationEvidenceFor::ForkVoting(..) =>
+				<R as ReportOffence<
+					_,
+					_,
+					ForkVotingOffence<P::IdentificationTuple, BlockNumberFor<T>>,
+				>>::is_known_offence(&[offender], &time_slot),
+			EquivocationEvidenceFor::FutureBlockVoting(..) =>
+				<R as ReportOffence<
+					_,
+					_,
+					FutureBlockVotingOffence<P::IdentificationTuple, BlockNumberFor<T>>,
+				>>::is_known_offence(&[offender], &time_slot),
 		};
 
-		if R::is_known_offence(&[offender], &time_slot) {
+		if is_known_offence {
 			Err(InvalidTransaction::Stale.into())
 		} else {
 			Ok(())
@@ -189,16 +442,16 @@ where
 		reporter: Option<T::AccountId>,
 		evidence: EquivocationEvidenceFor<T>,
 	) -> Result<(), DispatchError> {
-		let (equivocation_proof, key_owner_proof) = evidence;
 		let reporter = reporter.or_else(|| <pallet_authorship::Pallet<T>>::author());
-		let offender = equivocation_proof.offender_id().clone();
+		let offender = evidence.offender_id().clone();
+		let key_owner_proof = evidence.key_owner_proof().clone();
 
 		// We check the equivocation within the context of its set id (and
 		// associated session) and round. We also need to know the validator
 		// set count at the time of the offence since it is required to calculate
 		// the slash amount.
-		let set_id = equivocation_proof.set_id();
-		let round = *equivocation_proof.round_number();
+		let set_id = evidence.set_id();
+		let round = *evidence.round_number();
 		let session_index = key_owner_proof.session();
 		let validator_set_count = key_owner_proof.validator_count();
 
@@ -206,10 +459,8 @@ where
 		let offender = P::check_proof((BEEFY_KEY_TYPE, offender), key_owner_proof)
 			.ok_or(Error::<T>::InvalidKeyOwnershipProof)?;
 
-		// Validate equivocation proof (check votes are different and signatures are valid).
-		if !sp_consensus_beefy::check_equivocation_proof(&equivocation_proof) {
-			return Err(Error::<T>::InvalidEquivocationProof.into())
-		}
+		// Validate the misbehavior proof itself.
+		evidence.check_equivocation_proof()?;
 
 		// Check that the session id for the membership proof is within the
 		// bounds of the set id reported in the equivocation.
@@ -219,69 +470,103 @@ where
 			return Err(Error::<T>::InvalidEquivocationProof.into())
 		}
 
-		let offence = EquivocationOffence {
-			time_slot: TimeSlot { set_id, round },
-			session_index,
-			validator_set_count,
-			offender,
-		};
-
-		R::report_offence(reporter.into_iter().collect(), offence)
-			.map_err(|_| Error::<T>::DuplicateOffenceReport)?;
+		let time_slot = TimeSlot { set_id, round };
+		let reporters = reporter.into_iter().collect();
+		match evidence {
+			EquivocationEvidenceFor::DoubleVoting(..) => {
+				let offence =
+					EquivocationOffence { time_slot, session_index, validator_set_count, offender };
+				R::report_offence(reporters, offence)
+			},
+			EquivocationEvidenceFor::ForkVoting(..) => {
+				let offence =
+					ForkVotingOffence { time_slot, session_index, validator_set_count, offender };
+				R::report_offence(reporters, offence)
+			},
+			EquivocationEvidenceFor::FutureBlockVoting(..) => {
+				let offence = FutureBlockVotingOffence {
+					time_slot,
+					session_index,
+					validator_set_count,
+					offender,
+				};
+				R::report_offence(reporters, offence)
+			},
+		}
+		.map_err(|_| Error::<T>::DuplicateOffenceReport)?;
 
 		Ok(())
 	}
 }
 
 /// Methods for the `ValidateUnsigned` implementation:
-/// It restricts calls to `report_equivocation_unsigned` to local calls (i.e. extrinsics generated
-/// on this node) or that already in a block. This guarantees that only block authors can include
-/// unsigned equivocation reports.
+/// It restricts calls to `report_equivocation_unsigned`, `report_fork_voting_unsigned` and
+/// `report_future_block_voting_unsigned` to local calls (i.e. extrinsics generated on this node)
+/// or that already in a block. This guarantees that only block authors can include unsigned
+/// equivocation reports.
 impl<T: Config> Pallet<T> {
+	fn evidence_from_call(call: &Call<T>) -> Option<EquivocationEvidenceFor<T>> {
+		match call {
+			Call::report_equivocation_unsigned { equivocation_proof, key_owner_proof } =>
+				Some(EquivocationEvidenceFor::DoubleVoting(
+					*equivocation_proof.clone(),
+					key_owner_proof.clone(),
+				)),
+			Call::report_fork_voting_unsigned { fork_voting_proof, key_owner_proof } =>
+				Some(EquivocationEvidenceFor::ForkVoting(
+					*fork_voting_proof.clone(),
+					key_owner_proof.clone(),
+				)),
+			Call::report_future_block_voting_unsigned {
+				future_block_voting_proof,
+				key_owner_proof,
+			} => Some(EquivocationEvidenceFor::FutureBlockVoting(
+				*future_block_voting_proof.clone(),
+				key_owner_proof.clone(),
+			)),
+			_ => None,
+		}
+	}
+
 	pub fn validate_unsigned(source: TransactionSource, call: &Call<T>) -> TransactionValidity {
-		if let Call::report_equivocation_unsigned { equivocation_proof, key_owner_proof } = call {
-			// discard equivocation report not coming from the local node
-			match source {
-				TransactionSource::Local | TransactionSource::InBlock => { /* allowed */ },
-				_ => {
-					log::warn!(
-						target: LOG_TARGET,
-						"rejecting unsigned report equivocation transaction because it is not local/in-block."
-					);
-					return InvalidTransaction::Call.into()
-				},
-			}
-
-			let evidence = (*equivocation_proof.clone(), key_owner_proof.clone());
-			T::EquivocationReportSystem::check_evidence(evidence)?;
-
-			let longevity =
-				<T::EquivocationReportSystem as OffenceReportSystem<_, _>>::Longevity::get();
-
-			ValidTransaction::with_tag_prefix("BeefyEquivocation")
-				// We assign the maximum priority for any equivocation report.
-				.priority(TransactionPriority::MAX)
-				// Only one equivocation report for the same offender at the same slot.
-				.and_provides((
-					equivocation_proof.offender_id().clone(),
-					equivocation_proof.set_id(),
-					*equivocation_proof.round_number(),
-				))
-				.longevity(longevity)
-				// We don't propagate this. This can never be included on a remote node.
-				.propagate(false)
-				.build()
-		} else {
-			InvalidTransaction::Call.into()
+		let Some(evidence) = Self::evidence_from_call(call) else {
+			return InvalidTransaction::Call.into()
+		};
+
+		// discard equivocation report not coming from the local node
+		match source {
+			TransactionSource::Local | TransactionSource::InBlock => { /* allowed */ },
+			_ => {
+				log::warn!(
+					target: LOG_TARGET,
+					"rejecting unsigned report equivocation transaction because it is not local/in-block."
+				);
+				return InvalidTransaction::Call.into()
+			},
 		}
+
+		let tag_prefix = evidence.tag_prefix();
+		let tag = (evidence.offender_id().clone(), evidence.set_id(), *evidence.round_number());
+		T::EquivocationReportSystem::check_evidence(evidence)?;
+
+		let longevity =
+			<T::EquivocationReportSystem as OffenceReportSystem<_, _>>::Longevity::get();
+
+		ValidTransaction::with_tag_prefix(tag_prefix)
+			// We assign the maximum priority for any equivocation report.
+			.priority(TransactionPriority::MAX)
+			// Only one equivocation report for the same offender at the same slot.
+			.and_provides(tag)
+			.longevity(longevity)
+			// We don't propagate this. This can never be included on a remote node.
+			.propagate(false)
+			.build()
 	}
 
 	pub fn pre_dispatch(call: &Call<T>) -> Result<(), TransactionValidityError> {
-		if let Call::report_equivocation_unsigned { equivocation_proof, key_owner_proof } = call {
-			let evidence = (*equivocation_proof.clone(), key_owner_proof.clone());
-			T::EquivocationReportSystem::check_evidence(evidence)
-		} else {
-			Err(InvalidTransaction::Call.into())
+		match Self::evidence_from_call(call) {
+			Some(evidence) => T::EquivocationReportSystem::check_evidence(evidence),
+			None => Err(InvalidTransaction::Call.into()),
 		}
 	}
 }
@@ -41,8 +41,9 @@ use sp_staking::{offence::OffenceReportSystem, SessionIndex};
 use sp_std::prelude::*;
 
 use sp_consensus_beefy::{
-	AuthorityIndex, BeefyAuthorityId, ConsensusLog, EquivocationProof, OnNewValidatorSet,
-	ValidatorSet, BEEFY_ENGINE_ID, GENESIS_AUTHORITY_SET_ID,
+	AncestryHelper, AuthorityIndex, BeefyAuthorityId, ConsensusLog, EquivocationProof,
+	ForkVotingProof, FutureBlockVotingProof, OnNewValidatorSet, ValidatorSet, BEEFY_ENGINE_ID,
+	GENESIS_AUTHORITY_SET_ID,
 };
 
 mod default_weights;
@@ -52,17 +53,21 @@ mod mock;
 #[cfg(test)]
 mod tests;
 
-pub use crate::equivocation::{EquivocationOffence, EquivocationReportSystem, TimeSlot};
+pub use crate::equivocation::{
+	EquivocationEvidenceFor, EquivocationOffence, EquivocationReportSystem, ForkVotingOffence,
+	FutureBlockVotingOffence, TimeSlot,
+};
 pub use pallet::*;
 
-use crate::equivocation::EquivocationEvidenceFor;
-
 const LOG_TARGET: &str = "runtime::beefy";
 
 #[frame_support::pallet]
 pub mod pallet {
 	use super::*;
-	use frame_system::{ensure_root, pallet_prelude::BlockNumberFor};
+	use frame_system::{
+		ensure_root,
+		pallet_prelude::{BlockNumberFor, HeaderFor},
+	};
 
 	#[pallet::config]
 	pub trait Config: frame_system::Config {
@@ -113,6 +118,12 @@ pub mod pallet {
 			Option<Self::AccountId>,
 			EquivocationEvidenceFor<Self>,
 		>;
+
+		/// Proves blocks canonical once their hash is no longer kept by `frame_system`.
+		///
+		/// Used to check fork voting reports for blocks older than `BlockHashCount`. Use `()`
+		/// if such reports should be rejected.
+		type AncestryHelper: AncestryHelper<HeaderFor<Self>>;
 	}
 
 	#[pallet::pallet]
@@ -200,6 +211,11 @@ pub mod pallet {
 		DuplicateOffenceReport,
 		/// Submitted configuration is invalid.
 		InvalidConfiguration,
+		/// A fork voting proof provided as part of a fork voting report is invalid.
+		InvalidForkVotingProof,
+		/// A future block voting proof provided as part of a future block voting report is
+		/// invalid.
+		InvalidFutureBlockVotingProof,
 	}
 
 	#[pallet::call]
@@ -228,7 +244,7 @@ pub mod pallet {
 
 			T::EquivocationReportSystem::process_evidence(
 				Some(reporter),
-				(*equivocation_proof, key_owner_proof),
+				EquivocationEvidenceFor::DoubleVoting(*equivocation_proof, key_owner_proof),
 			)?;
 			// Waive the fee since the report is valid and beneficial
 			Ok(Pays::No.into())
@@ -263,7 +279,7 @@ pub mod pallet {
 
 			T::EquivocationReportSystem::process_evidence(
 				None,
-				(*equivocation_proof, key_owner_proof),
+				EquivocationEvidenceFor::DoubleVoting(*equivocation_proof, key_owner_proof),
 			)?;
 			Ok(Pays::No.into())
 		}
@@ -284,6 +300,146 @@ pub mod pallet {
 			GenesisBlock::<T>::put(Some(genesis_block));
 			Ok(())
 		}
+
+		/// Report a vote on a block that is not part of the canonical chain. This method
+		/// will verify the fork voting proof against the canonical block hash stored by
+		/// `frame_system`, and validate the given key ownership proof against the extracted
+		/// offender. If both are valid, the offence will be reported.
+		///
+		/// Since `frame_system` only keeps the last `BlockHashCount` block hashes, older
+		/// blocks must be proven canonical by the ancestry proof, checked through
+		/// `Config::AncestryHelper`.
+		#[pallet::call_index(3)]
+		#[pallet::weight(T::WeightInfo::report_fork_voting(
+			key_owner_proof.validator_count(),
+			T::MaxNominators::get(),
+		))]
+		pub fn report_fork_voting(
+			origin: OriginFor<T>,
+			fork_voting_proof: Box<
+				ForkVotingProof<
+					BlockNumberFor<T>,
+					T::BeefyId,
+					<T::BeefyId as RuntimeAppPublic>::Signature,
+					HeaderFor<T>,
+				>,
+			>,
+			key_owner_proof: T::KeyOwnerProof,
+		) -> DispatchResultWithPostInfo {
+			let reporter = ensure_signed(origin)?;
+
+			T::EquivocationReportSystem::process_evidence(
+				Some(reporter),
+				EquivocationEvidenceFor::ForkVoting(*fork_voting_proof, key_owner_proof),
+			)?;
+			// Waive the fee since the report is valid and beneficial
+			Ok(Pays::No.into())
+		}
+
+		/// Report a vote on a block that is not part of the canonical chain. This method
+		/// will verify the fork voting proof against the canonical block hash stored by
+		/// `frame_system`, and validate the given key ownership proof against the extracted
+		/// offender. If both are valid, the offence will be reported.
+		///
+		/// This extrinsic must be called unsigned and it is expected that only
+		/// block authors will call it (validated in `ValidateUnsigned`), as such
+		/// if the block author is defined it will be defined as the equivocation
+		/// reporter.
+		#[pallet::call_index(4)]
+		#[pallet::weight(T::WeightInfo::report_fork_voting(
+			key_owner_proof.validator_count(),
+			T::MaxNominators::get(),
+		))]
+		pub fn report_fork_voting_unsigned(
+			origin: OriginFor<T>,
+			fork_voting_proof: Box<
+				ForkVotingProof<
+					BlockNumberFor<T>,
+					T::BeefyId,
+					<T::BeefyId as RuntimeAppPublic>::Signature,
+					HeaderFor<T>,
+				>,
+			>,
+			key_owner_proof: T::KeyOwnerProof,
+		) -> DispatchResultWithPostInfo {
+			ensure_none(origin)?;
+
+			T::EquivocationReportSystem::process_evidence(
+				None,
+				EquivocationEvidenceFor::ForkVoting(*fork_voting_proof, key_owner_proof),
+			)?;
+			Ok(Pays::No.into())
+		}
+
+		/// Report a vote on a block that has not been produced yet. This method will verify
+		/// that the voted block number is above the current block number, and validate the
+		/// given key ownership proof against the extracted offender. If both are valid, the
+		/// offence will be reported.
+		#[pallet::call_index(5)]
+		#[pallet::weight(T::WeightInfo::report_future_block_voting(
+			key_owner_proof.validator_count(),
+			T::MaxNominators::get(),
+		))]
+		pub fn report_future_block_voting(
+			origin: OriginFor<T>,
+			future_block_voting_proof: Box<
+				FutureBlockVotingProof<
+					BlockNumberFor<T>,
+					T::BeefyId,
+					<T::BeefyId as RuntimeAppPublic>::Signature,
+				>,
+			>,
+			key_owner_proof: T::KeyOwnerProof,
+		) -> DispatchResultWithPostInfo {
+			let reporter = ensure_signed(origin)?;
+
+			T::EquivocationReportSystem::process_evidence(
+				Some(reporter),
+				EquivocationEvidenceFor::FutureBlockVoting(
+					*future_block_voting_proof,
+					key_owner_proof,
+				),
+			)?;
+			// Waive the fee since the report is valid and beneficial
+			Ok(Pays::No.into())
+		}
+
+		/// Report a vote on a block that has not been produced yet. This method will verify
+		/// that the voted block number is above the current block number, and validate the
+		/// given key ownership proof against the extracted offender. If both are valid, the
+		/// offence will be reported.
+		///
+		/// This extrinsic must be called unsigned and it is expected that only
+		/// block authors will call it (validated in `ValidateUnsigned`), as such
+		/// if the block author is defined it will be defined as the equivocation
+		/// reporter.
+		#[pallet::call_index(6)]
+		#[pallet::weight(T::WeightInfo::report_future_block_voting(
+			key_owner_proof.validator_count(),
+			T::MaxNominators::get(),
+		))]
+		pub fn report_future_block_voting_unsigned(
+			origin: OriginFor<T>,
+			future_block_voting_proof: Box<
+				FutureBlockVotingProof<
+					BlockNumberFor<T>,
+					T::BeefyId,
+					<T::BeefyId as RuntimeAppPublic>::Signature,
+				>,
+			>,
+			key_owner_proof: T::KeyOwnerProof,
+		) -> DispatchResultWithPostInfo {
+			ensure_none(origin)?;
+
+			T::EquivocationReportSystem::process_evidence(
+				None,
+				EquivocationEvidenceFor::FutureBlockVoting(
+					*future_block_voting_proof,
+					key_owner_proof,
+				),
+			)?;
+			Ok(Pays::No.into())
+		}
 	}
 
 	#[pallet::validate_unsigned]
@@ -319,7 +475,48 @@ impl<T: Config> Pallet<T> {
 		>,
 		key_owner_proof: T::KeyOwnerProof,
 	) -> Option<()> {
-		T::EquivocationReportSystem::publish_evidence((equivocation_proof, key_owner_proof)).ok()
+		T::EquivocationReportSystem::publish_evidence(EquivocationEvidenceFor::DoubleVoting(
+			equivocation_proof,
+			key_owner_proof,
+		))
+		.ok()
+	}
+
+	/// Submits an extrinsic to report a vote on a non-canonical fork. This method will create
+	/// an unsigned extrinsic with a call to `report_fork_voting_unsigned` and will push the
+	/// transaction to the pool. Only useful in an offchain context.
+	pub fn submit_unsigned_fork_voting_report(
+		fork_voting_proof: ForkVotingProof<
+			BlockNumberFor<T>,
+			T::BeefyId,
+			<T::BeefyId as RuntimeAppPublic>::Signature,
+			HeaderFor<T>,
+		>,
+		key_owner_proof: T::KeyOwnerProof,
+	) -> Option<()> {
+		T::EquivocationReportSystem::publish_evidence(EquivocationEvidenceFor::ForkVoting(
+			fork_voting_proof,
+			key_owner_proof,
+		))
+		.ok()
+	}
+
+	/// Submits an extrinsic to report a vote on a future block. This method will create
+	/// an unsigned extrinsic with a call to `report_future_block_voting_unsigned` and will push
+	/// the transaction to the pool. Only useful in an offchain context.
+	pub fn submit_unsigned_future_block_voting_report(
+		future_block_voting_proof: FutureBlockVotingProof<
+			BlockNumberFor<T>,
+			T::BeefyId,
+			<T::BeefyId as RuntimeAppPublic>::Signature,
+		>,
+		key_owner_proof: T::KeyOwnerProof,
+	) -> Option<()> {
+		T::EquivocationReportSystem::publish_evidence(EquivocationEvidenceFor::FutureBlockVoting(
+			future_block_voting_proof,
+			key_owner_proof,
+		))
+		.ok()
 	}
 
 	fn change_authorities(
@@ -471,5 +668,10 @@ impl<T: Config> IsMember<T::BeefyId> for Pallet<T> {
 
 pub trait WeightInfo {
 	fn report_equivocation(validator_count: u32, max_nominators_per_validator: u32) -> Weight;
+	fn report_fork_voting(validator_count: u32, max_nominators_per_validator: u32) -> Weight;
+	fn report_future_block_voting(
+		validator_count: u32,
+		max_nominators_per_validator: u32,
+	) -> Weight;
 	fn set_new_genesis() -> Weight;
 }
@@ -17,6 +17,7 @@
 
 use std::vec;
 
+use codec::Encode;
 use frame_election_provider_support::{
 	bounds::{ElectionBounds, ElectionBoundsBuilder},
 	onchain, SequentialPhragmen,
@@ -25,12 +26,17 @@ use frame_support::{
 	construct_runtime, derive_impl, parameter_types,
 	traits::{ConstU32, ConstU64, KeyOwnerProofSystem, OnFinalize, OnInitialize},
 };
+use frame_system::pallet_prelude::HeaderFor;
 use pallet_session::historical as pallet_session_historical;
 use sp_core::{crypto::KeyTypeId, ConstU128};
 use sp_io::TestExternalities;
 use sp_runtime::{
-	app_crypto::ecdsa::Public, curve::PiecewiseLinear, impl_opaque_keys, testing::TestXt,
-	traits::OpaqueKeys, BuildStorage, Perbill,
+	app_crypto::ecdsa::Public,
+	curve::PiecewiseLinear,
+	impl_opaque_keys,
+	testing::TestXt,
+	traits::{Header as HeaderT, OpaqueKeys},
+	BuildStorage, Perbill,
 };
 use sp_staking::{EraIndex, SessionIndex};
 use sp_state_machine::BasicExternalities;
@@ -39,7 +45,8 @@ use crate as pallet_beefy;
 
 pub use sp_consensus_beefy::{
 	ecdsa_crypto::{AuthorityId as BeefyId, AuthoritySignature as BeefySignature},
-	ConsensusLog, EquivocationProof, BEEFY_ENGINE_ID,
+	mmr::AncestryProof,
+	AncestryHelper, ConsensusLog, EquivocationProof, BEEFY_ENGINE_ID,
 };
 
 impl_opaque_keys! {
@@ -86,6 +93,15 @@ parameter_types! {
 	pub const MaxSetIdSessionEntries: u32 = BondingDuration::get() * SessionsPerEra::get();
 }
 
+/// Accepts ancestry proofs whose leaf is the encoded hash of the proven header.
+pub struct MockAncestryHelper;
+
+impl AncestryHelper<HeaderFor<Test>> for MockAncestryHelper {
+	fn is_canonical(header: &HeaderFor<Test>, proof: &AncestryProof<HeaderFor<Test>>) -> bool {
+		proof.leaf == header.hash().encode()
+	}
+}
+
 impl pallet_beefy::Config for Test {
 	type BeefyId = BeefyId;
 	type MaxAuthorities = ConstU32<100>;
@@ -96,6 +112,7 @@ impl pallet_beefy::Config for Test {
 	type KeyOwnerProof = <Historical as KeyOwnerProofSystem<(KeyTypeId, BeefyId)>>::Proof;
 	type EquivocationReportSystem =
 		super::EquivocationReportSystem<Self, Offences, Historical, ReportLongevity>;
+	type AncestryHelper = MockAncestryHelper;
 }
 
 parameter_types! {
@@ -19,11 +19,13 @@ use std::vec;
 
 use codec::Encode;
 use sp_consensus_beefy::{
-	check_equivocation_proof, generate_equivocation_proof, known_payloads::MMR_ROOT_ID,
-	Keyring as BeefyKeyring, Payload, ValidatorSet, KEY_TYPE as BEEFY_KEY_TYPE,
+	check_equivocation_proof, generate_equivocation_proof, generate_fork_voting_proof,
+	generate_future_block_voting_proof, known_payloads::MMR_ROOT_ID, mmr::AncestryProof,
+	Keyring as BeefyKeyring, MmrRootHash, Payload, ValidatorSet, KEY_TYPE as BEEFY_KEY_TYPE,
 };
 
-use sp_runtime::DigestItem;
+use frame_system::pallet_prelude::HeaderFor;
+use sp_runtime::{traits::Header as HeaderT, DigestItem};
 
 use frame_support::{
 	assert_err, assert_ok,
@@ -615,6 +617,403 @@ fn report_equivocation_invalid_equivocation_proof() {
 	});
 }
 
+/// Builds a header for `block_num` carrying `mmr_root` in its digest and records it as the
+/// canonical block at that height.
+fn canonical_header(block_num: u64, mmr_root: MmrRootHash) -> HeaderFor<Test> {
+	let mut header = HeaderFor::<Test>::new(
+		block_num,
+		Default::default(),
+		Default::default(),
+		Default::default(),
+		Default::default(),
+	);
+	header.digest_mut().push(beefy_log(ConsensusLog::MmrRoot(mmr_root)));
+	frame_system::BlockHash::<Test>::insert(block_num, header.hash());
+	header
+}
+
+#[test]
+fn report_fork_voting_works() {
+	let authorities = test_authorities();
+
+	new_test_ext_raw_authorities(authorities).execute_with(|| {
+		start_era(1);
+
+		let block_num = System::block_number();
+		let validator_set = Beefy::validator_set().unwrap();
+		let authorities = validator_set.validators();
+		let set_id = validator_set.id();
+		let validators = Session::validators();
+
+		let equivocation_authority_index = 1;
+		let equivocation_key = &authorities[equivocation_authority_index];
+		let equivocation_keyring = BeefyKeyring::from_public(equivocation_key).unwrap();
+
+		// vote for an MMR root which is not the one of the canonical block
+		let header = canonical_header(block_num, MmrRootHash::repeat_byte(1));
+		let payload = Payload::from_single_entry(MMR_ROOT_ID, MmrRootHash::repeat_byte(2).encode());
+		let fork_voting_proof =
+			generate_fork_voting_proof((block_num, payload, set_id, &equivocation_keyring), header);
+
+		let key_owner_proof = Historical::prove((BEEFY_KEY_TYPE, &equivocation_key)).unwrap();
+
+		assert_ok!(Beefy::report_fork_voting_unsigned(
+			RuntimeOrigin::none(),
+			Box::new(fork_voting_proof),
+			key_owner_proof,
+		));
+
+		start_era(2);
+
+		// the offender is slashed while the other validators are left intact
+		let equivocation_validator_id = validators[equivocation_authority_index];
+		assert_eq!(Balances::total_balance(&equivocation_validator_id), 10_000_000 - 10_000);
+		assert_eq!(Staking::slashable_balance_of(&equivocation_validator_id), 0);
+		for validator in validators.iter().filter(|v| **v != equivocation_validator_id) {
+			assert_eq!(Balances::total_balance(validator), 10_000_000);
+			assert_eq!(Staking::slashable_balance_of(validator), 10_000);
+		}
+	});
+}
+
+#[test]
+fn report_fork_voting_invalid_proof() {
+	let authorities = test_authorities();
+
+	new_test_ext_raw_authorities(authorities).execute_with(|| {
+		start_era(1);
+
+		let block_num = System::block_number();
+		let validator_set = Beefy::validator_set().unwrap();
+		let authorities = validator_set.validators();
+		let set_id = validator_set.id();
+
+		let equivocation_key = &authorities[0];
+		let equivocation_keyring = BeefyKeyring::from_public(equivocation_key).unwrap();
+		let key_owner_proof = Historical::prove((BEEFY_KEY_TYPE, &equivocation_key)).unwrap();
+
+		let assert_invalid_fork_voting_proof = |fork_voting_proof| {
+			assert_err!(
+				Beefy::report_fork_voting_unsigned(
+					RuntimeOrigin::none(),
+					Box::new(fork_voting_proof),
+					key_owner_proof.clone(),
+				),
+				Error::<Test>::InvalidForkVotingProof,
+			);
+		};
+
+		let canonical_root = MmrRootHash::repeat_byte(1);
+		let header = canonical_header(block_num, canonical_root);
+		let canonical_payload = Payload::from_single_entry(MMR_ROOT_ID, canonical_root.encode());
+		let fork_payload =
+			Payload::from_single_entry(MMR_ROOT_ID, MmrRootHash::repeat_byte(2).encode());
+
+		// vote matching the canonical chain, there is no misbehavior.
+		assert_invalid_fork_voting_proof(generate_fork_voting_proof(
+			(block_num, canonical_payload, set_id, &equivocation_keyring),
+			header.clone(),
+		));
+
+		// vote targeting a different block than the provided header.
+		assert_invalid_fork_voting_proof(generate_fork_voting_proof(
+			(block_num + 1, fork_payload.clone(), set_id, &equivocation_keyring),
+			header.clone(),
+		));
+
+		// header which is not the canonical one.
+		frame_system::BlockHash::<Test>::insert(block_num, MmrRootHash::repeat_byte(3));
+		assert_invalid_fork_voting_proof(generate_fork_voting_proof(
+			(block_num, fork_payload.clone(), set_id, &equivocation_keyring),
+			header.clone(),
+		));
+
+		// canonical block hash no longer known and no ancestry proof.
+		frame_system::BlockHash::<Test>::remove(block_num);
+		assert_invalid_fork_voting_proof(generate_fork_voting_proof(
+			(block_num, fork_payload.clone(), set_id, &equivocation_keyring),
+			header.clone(),
+		));
+
+		// ancestry proof which doesn't prove the header.
+		let context_header = canonical_header(block_num + 1, canonical_root);
+		let mut fork_voting_proof = generate_fork_voting_proof(
+			(block_num, fork_payload.clone(), set_id, &equivocation_keyring),
+			header.clone(),
+		);
+		fork_voting_proof.ancestry_proof = Some(ancestry_proof(context_header.clone(), vec![42]));
+		assert_invalid_fork_voting_proof(fork_voting_proof);
+
+		// ancestry proof in the context of a header which isn't canonical.
+		frame_system::BlockHash::<Test>::insert(block_num + 1, MmrRootHash::repeat_byte(3));
+		let mut fork_voting_proof = generate_fork_voting_proof(
+			(block_num, fork_payload, set_id, &equivocation_keyring),
+			header.clone(),
+		);
+		fork_voting_proof.ancestry_proof =
+			Some(ancestry_proof(context_header, header.hash().encode()));
+		assert_invalid_fork_voting_proof(fork_voting_proof);
+	});
+}
+
+/// Builds an ancestry proof in the context of `context_header`, accepted by the mock
+/// `AncestryHelper` if `leaf` is the encoded hash of the proven header.
+fn ancestry_proof(
+	context_header: HeaderFor<Test>,
+	leaf: Vec<u8>,
+) -> AncestryProof<HeaderFor<Test>> {
+	let leaf_proof =
+		sp_mmr_primitives::Proof { leaf_indices: vec![], leaf_count: 0, items: vec![] };
+	AncestryProof { context_header, leaf, leaf_proof }
+}
+
+#[test]
+fn report_fork_voting_with_ancestry_proof_works() {
+	let authorities = test_authorities();
+
+	new_test_ext_raw_authorities(authorities).execute_with(|| {
+		start_era(1);
+
+		let block_num = System::block_number();
+		let validator_set = Beefy::validator_set().unwrap();
+		let authorities = validator_set.validators();
+		let set_id = validator_set.id();
+		let validators = Session::validators();
+
+		let equivocation_authority_index = 1;
+		let equivocation_key = &authorities[equivocation_authority_index];
+		let equivocation_keyring = BeefyKeyring::from_public(equivocation_key).unwrap();
+
+		// the hash of the voted block is no longer known on-chain
+		let header = canonical_header(block_num, MmrRootHash::repeat_byte(1));
+		frame_system::BlockHash::<Test>::remove(block_num);
+		let context_header = canonical_header(block_num + 1, MmrRootHash::repeat_byte(1));
+
+		let payload = Payload::from_single_entry(MMR_ROOT_ID, MmrRootHash::repeat_byte(2).encode());
+		let mut fork_voting_proof = generate_fork_voting_proof(
+			(block_num, payload, set_id, &equivocation_keyring),
+			header.clone(),
+		);
+		fork_voting_proof.ancestry_proof =
+			Some(ancestry_proof(context_header, header.hash().encode()));
+
+		let key_owner_proof = Historical::prove((BEEFY_KEY_TYPE, &equivocation_key)).unwrap();
+
+		assert_ok!(Beefy::report_fork_voting_unsigned(
+			RuntimeOrigin::none(),
+			Box::new(fork_voting_proof),
+			key_owner_proof,
+		));
+
+		start_era(2);
+
+		let equivocation_validator_id = validators[equivocation_authority_index];
+		assert_eq!(Balances::total_balance(&equivocation_validator_id), 10_000_000 - 10_000);
+		assert_eq!(Staking::slashable_balance_of(&equivocation_validator_id), 0);
+	});
+}
+
+#[test]
+fn fork_and_future_block_votes_are_separate_offences() {
+	let authorities = test_authorities();
+
+	new_test_ext_raw_authorities(authorities).execute_with(|| {
+		start_era(1);
+
+		let block_num = System::block_number();
+		let validator_set = Beefy::validator_set().unwrap();
+		let authorities = validator_set.validators();
+		let set_id = validator_set.id();
+
+		let equivocation_key = &authorities[1];
+		let equivocation_keyring = BeefyKeyring::from_public(equivocation_key).unwrap();
+		let key_owner_proof = Historical::prove((BEEFY_KEY_TYPE, &equivocation_key)).unwrap();
+
+		// vote on the next block before it's produced
+		let payload = Payload::from_single_entry(MMR_ROOT_ID, MmrRootHash::repeat_byte(2).encode());
+		let future_block_voting_proof = generate_future_block_voting_proof((
+			block_num + 1,
+			payload.clone(),
+			set_id,
+			&equivocation_keyring,
+		));
+		assert_ok!(Beefy::report_future_block_voting_unsigned(
+			RuntimeOrigin::none(),
+			Box::new(future_block_voting_proof),
+			key_owner_proof.clone(),
+		));
+
+		// once produced, the same vote is also a fork vote in the same round
+		System::set_block_number(block_num + 1);
+		let header = canonical_header(block_num + 1, MmrRootHash::repeat_byte(1));
+		let fork_voting_proof = generate_fork_voting_proof(
+			(block_num + 1, payload, set_id, &equivocation_keyring),
+			header,
+		);
+		assert_ok!(Beefy::report_fork_voting_unsigned(
+			RuntimeOrigin::none(),
+			Box::new(fork_voting_proof),
+			key_owner_proof,
+		));
+	});
+}
+
+#[test]
+fn report_future_block_voting_works() {
+	let authorities = test_authorities();
+
+	new_test_ext_raw_authorities(authorities).execute_with(|| {
+		start_era(1);
+
+		let block_num = System::block_number();
+		let validator_set = Beefy::validator_set().unwrap();
+		let authorities = validator_set.validators();
+		let set_id = validator_set.id();
+		let validators = Session::validators();
+
+		let equivocation_authority_index = 1;
+		let equivocation_key = &authorities[equivocation_authority_index];
+		let equivocation_keyring = BeefyKeyring::from_public(equivocation_key).unwrap();
+
+		// vote for a block which hasn't been produced yet
+		let payload = Payload::from_single_entry(MMR_ROOT_ID, vec![42]);
+		let future_block_voting_proof = generate_future_block_voting_proof((
+			block_num + 10,
+			payload,
+			set_id,
+			&equivocation_keyring,
+		));
+
+		let key_owner_proof = Historical::prove((BEEFY_KEY_TYPE, &equivocation_key)).unwrap();
+
+		assert_ok!(Beefy::report_future_block_voting_unsigned(
+			RuntimeOrigin::none(),
+			Box::new(future_block_voting_proof),
+			key_owner_proof,
+		));
+
+		start_era(2);
+
+		let equivocation_validator_id = validators[equivocation_authority_index];
+		assert_eq!(Balances::total_balance(&equivocation_validator_id), 10_000_000 - 10_000);
+		assert_eq!(Staking::slashable_balance_of(&equivocation_validator_id), 0);
+	});
+}
+
+#[test]
+fn report_future_block_voting_invalid_proof() {
+	let authorities = test_authorities();
+
+	new_test_ext_raw_authorities(authorities).execute_with(|| {
+		start_era(1);
+
+		let block_num = System::block_number();
+		let validator_set = Beefy::validator_set().unwrap();
+		let authorities = validator_set.validators();
+		let set_id = validator_set.id();
+
+		let equivocation_key = &authorities[0];
+		let equivocation_keyring = BeefyKeyring::from_public(equivocation_key).unwrap();
+		let key_owner_proof = Historical::prove((BEEFY_KEY_TYPE, &equivocation_key)).unwrap();
+		let payload = Payload::from_single_entry(MMR_ROOT_ID, vec![42]);
+
+		// votes on the current or past blocks are not future block votes.
+		for vote_block_num in [block_num - 1, block_num] {
+			assert_err!(
+				Beefy::report_future_block_voting_unsigned(
+					RuntimeOrigin::none(),
+					Box::new(generate_future_block_voting_proof((
+						vote_block_num,
+						payload.clone(),
+						set_id,
+						&equivocation_keyring,
+					))),
+					key_owner_proof.clone(),
+				),
+				Error::<Test>::InvalidFutureBlockVotingProof,
+			);
+		}
+	});
+}
+
+#[test]
+fn report_fork_voting_validate_unsigned_prevents_duplicates() {
+	use sp_runtime::transaction_validity::{
+		InvalidTransaction, TransactionPriority, TransactionSource, TransactionValidity,
+		ValidTransaction,
+	};
+
+	let authorities = test_authorities();
+
+	new_test_ext_raw_authorities(authorities).execute_with(|| {
+		start_era(1);
+
+		let block_num = System::block_number();
+		let validator_set = Beefy::validator_set().unwrap();
+		let authorities = validator_set.validators();
+		let set_id = validator_set.id();
+
+		let equivocation_key = &authorities[0];
+		let equivocation_keyring = BeefyKeyring::from_public(equivocation_key).unwrap();
+
+		let header = canonical_header(block_num, MmrRootHash::repeat_byte(1));
+		let payload = Payload::from_single_entry(MMR_ROOT_ID, MmrRootHash::repeat_byte(2).encode());
+		let fork_voting_proof =
+			generate_fork_voting_proof((block_num, payload, set_id, &equivocation_keyring), header);
+		let key_owner_proof = Historical::prove((BEEFY_KEY_TYPE, &equivocation_key)).unwrap();
+
+		let call = Call::report_fork_voting_unsigned {
+			fork_voting_proof: Box::new(fork_voting_proof.clone()),
+			key_owner_proof: key_owner_proof.clone(),
+		};
+
+		// only local/inblock reports are allowed
+		assert_eq!(
+			<Beefy as sp_runtime::traits::ValidateUnsigned>::validate_unsigned(
+				TransactionSource::External,
+				&call,
+			),
+			InvalidTransaction::Call.into(),
+		);
+
+		// fork voting reports don't share tags with double voting reports
+		let tx_tag = (equivocation_key, set_id, block_num);
+		assert_eq!(
+			<Beefy as sp_runtime::traits::ValidateUnsigned>::validate_unsigned(
+				TransactionSource::Local,
+				&call,
+			),
+			TransactionValidity::Ok(ValidTransaction {
+				priority: TransactionPriority::max_value(),
+				requires: vec![],
+				provides: vec![("BeefyForkVoting", tx_tag).encode()],
+				longevity: ReportLongevity::get(),
+				propagate: false,
+			})
+		);
+
+		Beefy::report_fork_voting_unsigned(
+			RuntimeOrigin::none(),
+			Box::new(fork_voting_proof),
+			key_owner_proof,
+		)
+		.unwrap();
+
+		// the report should now be considered stale
+		assert_err!(
+			<Beefy as sp_runtime::traits::ValidateUnsigned>::validate_unsigned(
+				TransactionSource::Local,
+				&call,
+			),
+			InvalidTransaction::Stale,
+		);
+		assert_err!(
+			<Beefy as sp_runtime::traits::ValidateUnsigned>::pre_dispatch(&call),
+			InvalidTransaction::Stale,
+		);
+	});
+}
+
 #[test]
 fn report_equivocation_validate_unsigned_prevents_duplicates() {
 	use sp_runtime::transaction_validity::{
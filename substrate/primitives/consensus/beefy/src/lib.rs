@@ -47,7 +47,7 @@ use codec::{Codec, Decode, Encode};
 use scale_info::TypeInfo;
 use sp_application_crypto::RuntimeAppPublic;
 use sp_core::H256;
-use sp_runtime::traits::{Hash, Keccak256, NumberFor};
+use sp_runtime::traits::{Hash, Header as HeaderT, Keccak256, NumberFor};
 use sp_std::prelude::*;
 
 /// Key type for BEEFY module.
@@ -301,6 +301,62 @@ impl<Number, Id, Signature> EquivocationProof<Number, Id, Signature> {
 	}
 }
 
+/// Proof of voter misbehavior on a given set id. A vote on a non-canonical fork happens when
+/// a voter signs a commitment for a block number whose payload doesn't match the payload
+/// of the canonical block with that number.
+/// Proving is achieved by providing the vote along with the canonical header, whose MMR root
+/// digest differs from the MMR root the vote commits to.
+#[derive(Clone, Debug, Decode, Encode, PartialEq, TypeInfo)]
+pub struct ForkVotingProof<Number, Id, Signature, Header> {
+	/// The vote on the non-canonical fork.
+	pub vote: VoteMessage<Number, Id, Signature>,
+	/// Canonical header of the block the vote is for.
+	pub header: Header,
+	/// Proof that `header` is canonical.
+	///
+	/// Only needed once the hash of `header` is too old to be known on-chain.
+	pub ancestry_proof: Option<mmr::AncestryProof<Header>>,
+}
+
+impl<Number, Id, Signature, Header> ForkVotingProof<Number, Id, Signature, Header> {
+	/// Returns the authority id of the misbehaving voter.
+	pub fn offender_id(&self) -> &Id {
+		&self.vote.id
+	}
+	/// Returns the round number at which the misbehavior occurred.
+	pub fn round_number(&self) -> &Number {
+		&self.vote.commitment.block_number
+	}
+	/// Returns the set id at which the misbehavior occurred.
+	pub fn set_id(&self) -> ValidatorSetId {
+		self.vote.commitment.validator_set_id
+	}
+}
+
+/// Proof of voter misbehavior on a given set id. A future block vote happens when
+/// a voter signs a commitment for a block number that hasn't been produced yet,
+/// so it can't possibly have been finalized.
+#[derive(Clone, Debug, Decode, Encode, PartialEq, TypeInfo)]
+pub struct FutureBlockVotingProof<Number, Id, Signature> {
+	/// The vote on the future block.
+	pub vote: VoteMessage<Number, Id, Signature>,
+}
+
+impl<Number, Id, Signature> FutureBlockVotingProof<Number, Id, Signature> {
+	/// Returns the authority id of the misbehaving voter.
+	pub fn offender_id(&self) -> &Id {
+		&self.vote.id
+	}
+	/// Returns the round number at which the misbehavior occurred.
+	pub fn round_number(&self) -> &Number {
+		&self.vote.commitment.block_number
+	}
+	/// Returns the set id at which the misbehavior occurred.
+	pub fn set_id(&self) -> ValidatorSetId {
+		self.vote.commitment.validator_set_id
+	}
+}
+
 /// Check a commitment signature by encoding the commitment and
 /// verifying the provided signature using the expected authority id.
 pub fn check_commitment_signature<Number, Id, MsgHash>(
@@ -352,6 +408,77 @@ where
 	return valid_first && valid_second
 }
 
+/// Verifies the fork voting proof by making sure that the header is the one expected
+/// by the caller for the vote's block number, that the MMR root committed to by the vote
+/// differs from the one in the header digest, and that the vote signature is valid.
+///
+/// It's up to the caller to make sure `expected_header_hash` is the hash of the canonical
+/// block at the voted block number.
+pub fn check_fork_equivocation_proof<Id, MsgHash, Header>(
+	proof: &ForkVotingProof<Header::Number, Id, <Id as RuntimeAppPublic>::Signature, Header>,
+	expected_header_hash: &Header::Hash,
+) -> bool
+where
+	Id: BeefyAuthorityId<MsgHash> + PartialEq,
+	MsgHash: Hash,
+	Header: HeaderT,
+{
+	let ForkVotingProof { vote, header, .. } = proof;
+
+	if header.hash() != *expected_header_hash || *header.number() != vote.commitment.block_number {
+		return false
+	}
+
+	// the canonical MMR root must be known, and differ from the one voted on
+	let canonical_root = match mmr::find_header_mmr_root_digest(header) {
+		Some(root) => root,
+		None => return false,
+	};
+	// votes without an MMR root can't be told apart from votes of honest voters
+	// using another payload
+	match vote.commitment.payload.get_decoded::<MmrRootHash>(&known_payloads::MMR_ROOT_ID) {
+		Some(voted_root) if voted_root != canonical_root => (),
+		_ => return false,
+	}
+
+	check_commitment_signature(&vote.commitment, &vote.id, &vote.signature)
+}
+
+/// Verifies the future block voting proof by making sure that the vote is for a block
+/// after `best_block_number` and that its signature is valid.
+pub fn check_future_block_voting_proof<Number, Id, MsgHash>(
+	proof: &FutureBlockVotingProof<Number, Id, <Id as RuntimeAppPublic>::Signature>,
+	best_block_number: Number,
+) -> bool
+where
+	Id: BeefyAuthorityId<MsgHash> + PartialEq,
+	Number: Clone + Encode + PartialEq + PartialOrd,
+	MsgHash: Hash,
+{
+	let vote = &proof.vote;
+	if vote.commitment.block_number <= best_block_number {
+		return false
+	}
+
+	check_commitment_signature(&vote.commitment, &vote.id, &vote.signature)
+}
+
+/// Checks whether blocks are part of the canonical chain, for blocks whose hash is too old
+/// to be known on-chain.
+pub trait AncestryHelper<Header: HeaderT> {
+	/// Returns `true` if `proof` shows `header` to be canonical.
+	///
+	/// It's up to the caller to make sure the context header of `proof` is canonical.
+	fn is_canonical(header: &Header, proof: &mmr::AncestryProof<Header>) -> bool;
+}
+
+/// No ancestry proofs are supported, so old enough blocks can't be proven canonical.
+impl<Header: HeaderT> AncestryHelper<Header> for () {
+	fn is_canonical(_header: &Header, _proof: &mmr::AncestryProof<Header>) -> bool {
+		false
+	}
+}
+
 /// New BEEFY validator set notification hook.
 pub trait OnNewValidatorSet<AuthorityId> {
 	/// Function called by the pallet when BEEFY validator set changes.
@@ -390,7 +517,7 @@ impl OpaqueKeyOwnershipProof {
 
 sp_api::decl_runtime_apis! {
 	/// API necessary for BEEFY voters.
-	#[api_version(3)]
+	#[api_version(4)]
 	pub trait BeefyApi<AuthorityId> where
 		AuthorityId : Codec + RuntimeAppPublic,
 	{
@@ -414,6 +541,26 @@ sp_api::decl_runtime_apis! {
 			key_owner_proof: OpaqueKeyOwnershipProof,
 		) -> Option<()>;
 
+		/// Submits an unsigned extrinsic to report a vote on a non-canonical fork.
+		/// Like `submit_report_equivocation_unsigned_extrinsic`, the extrinsic is
+		/// only accepted for local authorship. Only useful in an offchain context.
+		#[api_version(4)]
+		fn submit_report_fork_voting_unsigned_extrinsic(
+			fork_voting_proof:
+				ForkVotingProof<NumberFor<Block>, AuthorityId, <AuthorityId as RuntimeAppPublic>::Signature, Block::Header>,
+			key_owner_proof: OpaqueKeyOwnershipProof,
+		) -> Option<()>;
+
+		/// Submits an unsigned extrinsic to report a vote on a future block.
+		/// Like `submit_report_equivocation_unsigned_extrinsic`, the extrinsic is
+		/// only accepted for local authorship. Only useful in an offchain context.
+		#[api_version(4)]
+		fn submit_report_future_block_voting_unsigned_extrinsic(
+			future_block_voting_proof:
+				FutureBlockVotingProof<NumberFor<Block>, AuthorityId, <AuthorityId as RuntimeAppPublic>::Signature>,
+			key_owner_proof: OpaqueKeyOwnershipProof,
+		) -> Option<()>;
+
 		/// Generates a proof of key ownership for the given authority in the
 		/// given set. An example usage of this module is coupled with the
 		/// session historical module to prove that a given authority key is
@@ -528,4 +675,68 @@ mod tests {
 		let (other_pair, _) = ecdsa_bls_crypto::Pair::generate();
 		assert!(!BeefyAuthorityId::<Keccak256>::verify(&other_pair.public(), &signature, msg,));
 	}
+
+	#[test]
+	fn fork_voting_proof_works() {
+		let canonical_root = H256::repeat_byte(1);
+		let mut header = sp_runtime::generic::Header::<u64, BlakeTwo256>::new(
+			5,
+			Default::default(),
+			Default::default(),
+			Default::default(),
+			Default::default(),
+		);
+		header.digest_mut().push(sp_runtime::DigestItem::Consensus(
+			BEEFY_ENGINE_ID,
+			ConsensusLog::<ecdsa_crypto::AuthorityId>::MmrRoot(canonical_root).encode(),
+		));
+		let payload_with_root =
+			|root: H256| Payload::from_single_entry(known_payloads::MMR_ROOT_ID, root.encode());
+
+		// Vote on a different MMR root than the canonical one is reportable.
+		let proof = generate_fork_voting_proof(
+			(5, payload_with_root(H256::repeat_byte(2)), 0, &Keyring::Alice),
+			header.clone(),
+		);
+		assert!(check_fork_equivocation_proof::<_, Keccak256, _>(&proof, &header.hash()));
+
+		// Only against the expected canonical header.
+		assert!(!check_fork_equivocation_proof::<_, Keccak256, _>(&proof, &H256::zero()));
+
+		// Vote matching the canonical chain is fine.
+		let proof = generate_fork_voting_proof(
+			(5, payload_with_root(canonical_root), 0, &Keyring::Alice),
+			header.clone(),
+		);
+		assert!(!check_fork_equivocation_proof::<_, Keccak256, _>(&proof, &header.hash()));
+
+		// Vote must be for the block of the header.
+		let proof = generate_fork_voting_proof(
+			(6, payload_with_root(H256::repeat_byte(2)), 0, &Keyring::Alice),
+			header.clone(),
+		);
+		assert!(!check_fork_equivocation_proof::<_, Keccak256, _>(&proof, &header.hash()));
+
+		// Vote without MMR root may come from an honest voter using another payload.
+		let proof = generate_fork_voting_proof(
+			(5, Payload::from_single_entry(*b"xx", vec![42]), 0, &Keyring::Alice),
+			header.clone(),
+		);
+		assert!(!check_fork_equivocation_proof::<_, Keccak256, _>(&proof, &header.hash()));
+	}
+
+	#[test]
+	fn future_block_voting_proof_works() {
+		let payload = Payload::from_single_entry(known_payloads::MMR_ROOT_ID, vec![]);
+		let proof = generate_future_block_voting_proof((10, payload, 0, &Keyring::Alice));
+
+		assert!(check_future_block_voting_proof::<_, _, Keccak256>(&proof, 9));
+		assert!(!check_future_block_voting_proof::<_, _, Keccak256>(&proof, 10));
+		assert!(!check_future_block_voting_proof::<_, _, Keccak256>(&proof, 11));
+
+		// Signature must be valid.
+		let mut bad_proof = proof;
+		bad_proof.vote.commitment.block_number = 11;
+		assert!(!check_future_block_voting_proof::<_, _, Keccak256>(&bad_proof, 9));
+	}
 }
@@ -133,8 +133,30 @@ pub struct BeefyAuthoritySet<AuthoritySetCommitment> {
 /// Details of the next BEEFY authority set.
 pub type BeefyNextAuthoritySet<MerkleRoot> = BeefyAuthoritySet<MerkleRoot>;
 
+/// Proof that a block is part of the canonical chain.
+///
+/// The MMR leaf of the block following it commits to its number and hash. That leaf is proven
+/// against the MMR root in the digest of `context_header`, which is expected to be recent enough
+/// for its own hash to be known on-chain.
+#[derive(Clone, Debug, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct AncestryProof<Header> {
+	/// Header whose MMR root digest `leaf_proof` is checked against.
+	pub context_header: Header,
+	/// Encoded MMR leaf committing to the parent block number and hash.
+	pub leaf: Vec<u8>,
+	/// Proof of `leaf` in the MMR as of `context_header`.
+	pub leaf_proof: sp_mmr_primitives::Proof<MmrRootHash>,
+}
+
 /// Extract the MMR root hash from a digest in the given header, if it exists.
 pub fn find_mmr_root_digest<B: Block>(header: &B::Header) -> Option<MmrRootHash> {
+	find_header_mmr_root_digest(header)
+}
+
+/// Extract the MMR root hash from a digest in the given header, if it exists.
+///
+/// Same as [`find_mmr_root_digest`], for when only the header type is known.
+pub fn find_header_mmr_root_digest<H: Header>(header: &H) -> Option<MmrRootHash> {
 	let id = OpaqueDigestItemId::Consensus(&BEEFY_ENGINE_ID);
 
 	let filter = |log: ConsensusLog<AuthorityId>| match log {
@@ -150,22 +172,31 @@ pub use mmr_root_provider::MmrRootProvider;
 mod mmr_root_provider {
 	use super::*;
 	use crate::{known_payloads, payload::PayloadProvider, Payload};
-	use sp_api::ProvideRuntimeApi;
+	use sp_api::{ApiExt, ProvideRuntimeApi};
+	use sp_core::offchain::{storage::OffchainDb, OffchainDbExt, OffchainStorage};
 	use sp_mmr_primitives::MmrApi;
-	use sp_runtime::traits::NumberFor;
+	use sp_runtime::traits::{NumberFor, One, Saturating};
 	use sp_std::{marker::PhantomData, sync::Arc};
 
+	/// Creates the offchain database extension needed to generate MMR proofs.
+	type OffchainDbFactory = Arc<dyn Fn() -> OffchainDbExt + Send + Sync>;
+
 	/// A [`crate::Payload`] provider where payload is Merkle Mountain Range root hash.
 	///
 	/// Encoded payload contains a [`crate::MmrRootHash`] type (i.e. 32-bytes hash).
 	pub struct MmrRootProvider<B, R> {
 		runtime: Arc<R>,
+		offchain_db: Option<OffchainDbFactory>,
 		_phantom: PhantomData<B>,
 	}
 
 	impl<B, R> Clone for MmrRootProvider<B, R> {
 		fn clone(&self) -> Self {
-			Self { runtime: self.runtime.clone(), _phantom: PhantomData }
+			Self {
+				runtime: self.runtime.clone(),
+				offchain_db: self.offchain_db.clone(),
+				_phantom: PhantomData,
+			}
 		}
 	}
 
@@ -177,7 +208,19 @@ mod mmr_root_provider {
 	{
 		/// Create new BEEFY Payload provider with MMR Root as payload.
 		pub fn new(runtime: Arc<R>) -> Self {
-			Self { runtime, _phantom: PhantomData }
+			Self { runtime, offchain_db: None, _phantom: PhantomData }
+		}
+
+		/// Use `offchain_storage` to generate the MMR proofs backing ancestry proofs.
+		///
+		/// Without it, no ancestry proofs are provided.
+		pub fn with_offchain_storage<S: OffchainStorage + 'static>(
+			mut self,
+			offchain_storage: S,
+		) -> Self {
+			let offchain_db = OffchainDb::new(offchain_storage);
+			self.offchain_db = Some(Arc::new(move || OffchainDbExt::new(offchain_db.clone())));
+			self
 		}
 
 		/// Simple wrapper that gets MMR root from header digests or from client state.
@@ -199,6 +242,28 @@ mod mmr_root_provider {
 				Payload::from_single_entry(known_payloads::MMR_ROOT_ID, mmr_root.encode())
 			})
 		}
+
+		fn ancestry_proof(
+			&self,
+			header: &B::Header,
+			context_header: &B::Header,
+		) -> Option<AncestryProof<B::Header>> {
+			let offchain_db = self.offchain_db.as_ref()?;
+			// The MMR leaf of the next block commits to the hash of `header`.
+			let leaf_block = header.number().saturating_add(One::one());
+			let mut api = self.runtime.runtime_api();
+			api.register_extension(offchain_db());
+			let (leaves, leaf_proof) = api
+				.generate_proof(
+					context_header.hash(),
+					vec![leaf_block],
+					Some(*context_header.number()),
+				)
+				.ok()?
+				.ok()?;
+			let leaf = leaves.into_iter().next()?.0;
+			Some(AncestryProof { context_header: context_header.clone(), leaf, leaf_proof })
+		}
 	}
 }
 
@@ -18,6 +18,8 @@
 use codec::{Decode, Encode};
 use scale_info::TypeInfo;
 use sp_runtime::traits::Block;
+
+use crate::mmr::AncestryProof;
 use sp_std::prelude::*;
 
 /// Id of different payloads in the [`crate::Commitment`] data.
@@ -78,6 +80,18 @@ impl Payload {
 pub trait PayloadProvider<B: Block> {
 	/// Provide BEEFY payload if available for `header`.
 	fn payload(&self, header: &B::Header) -> Option<Payload>;
+
+	/// Provide a proof that `header` is canonical, as of the later `context_header`.
+	///
+	/// Used to report votes on forks once `header` is too old for its hash to be known
+	/// on-chain. Providers not able to prove ancestry return `None`.
+	fn ancestry_proof(
+		&self,
+		_header: &B::Header,
+		_context_header: &B::Header,
+	) -> Option<AncestryProof<B::Header>> {
+		None
+	}
 }
 
 #[cfg(test)]
@@ -0,0 +1,106 @@
+// This file is part of Substrate.
+
+// Copyright (C) Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Timestamp-based difficulty retargeting.
+//!
+//! The difficulty of the next block is derived from the difficulty of its parent, scaled by the
+//! ratio between the expected and the actual time it took to produce the blocks of a window of
+//! recent ancestors.
+
+use codec::{Decode, Encode};
+use scale_info::TypeInfo;
+use sp_core::{U256, U512};
+use sp_runtime::RuntimeDebug;
+
+/// Parameters of the difficulty retargeting algorithm, see [`retarget`].
+#[derive(Clone, Copy, PartialEq, Eq, Encode, Decode, RuntimeDebug, TypeInfo)]
+pub struct RetargetConfig {
+	/// The targeted average time between two blocks, in milliseconds.
+	pub target_block_time: u64,
+	/// The number of most recent block intervals the adjustment is based on.
+	pub window: u32,
+	/// The maximum factor by which the difficulty changes from one block to the next.
+	///
+	/// Values lower than `1` are treated as `1`, i.e. the difficulty never changes.
+	pub max_adjustment_factor: u32,
+	/// The difficulty never drops below this value.
+	pub min_difficulty: U256,
+}
+
+/// Compute the difficulty of the next block.
+///
+/// `timespan` is the time in milliseconds it took to produce the last `intervals` blocks, the
+/// last one of them having `parent_difficulty`. If no block interval is known yet, e.g. when
+/// building on genesis, the parent difficulty is kept.
+pub fn retarget(
+	config: &RetargetConfig,
+	parent_difficulty: U256,
+	timespan: u64,
+	intervals: u32,
+) -> U256 {
+	let min_difficulty = config.min_difficulty.max(U256::one());
+	if intervals == 0 {
+		return parent_difficulty.max(min_difficulty)
+	}
+
+	let factor = u64::from(config.max_adjustment_factor.max(1));
+	let expected = config.target_block_time.saturating_mul(u64::from(intervals)).max(1);
+	let actual = timespan.clamp((expected / factor).max(1), expected.saturating_mul(factor));
+
+	let next = parent_difficulty.full_mul(U256::from(expected)) / U512::from(actual);
+	U256::try_from(next).unwrap_or(U256::MAX).max(min_difficulty)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn config() -> RetargetConfig {
+		RetargetConfig {
+			target_block_time: 6_000,
+			window: 10,
+			max_adjustment_factor: 4,
+			min_difficulty: U256::from(100),
+		}
+	}
+
+	#[test]
+	fn keeps_difficulty_on_target() {
+		assert_eq!(retarget(&config(), U256::from(1_000), 60_000, 10), U256::from(1_000));
+	}
+
+	#[test]
+	fn follows_block_time() {
+		// blocks came twice as fast as targeted.
+		assert_eq!(retarget(&config(), U256::from(1_000), 30_000, 10), U256::from(2_000));
+		// blocks came twice as slow as targeted.
+		assert_eq!(retarget(&config(), U256::from(1_000), 120_000, 10), U256::from(500));
+	}
+
+	#[test]
+	fn respects_bounds() {
+		// adjustment is capped by `max_adjustment_factor`.
+		assert_eq!(retarget(&config(), U256::from(1_000), 0, 10), U256::from(4_000));
+		assert_eq!(retarget(&config(), U256::from(1_000), u64::MAX, 10), U256::from(250));
+		// never below `min_difficulty`.
+		assert_eq!(retarget(&config(), U256::from(200), u64::MAX, 10), U256::from(100));
+		// never overflows.
+		assert_eq!(retarget(&config(), U256::MAX, 0, 10), U256::MAX);
+		// no known interval keeps the parent difficulty.
+		assert_eq!(retarget(&config(), U256::zero(), 0, 0), U256::from(100));
+	}
+}
@@ -23,6 +23,10 @@ use codec::Decode;
 use sp_runtime::ConsensusEngineId;
 use sp_std::vec::Vec;
 
+pub mod difficulty;
+
+pub use difficulty::RetargetConfig;
+
 /// The `ConsensusEngineId` of PoW.
 pub const POW_ENGINE_ID: ConsensusEngineId = [b'p', b'o', b'w', b'_'];
 
@@ -62,4 +66,11 @@ sp_api::decl_runtime_apis! {
 		/// Return the target difficulty of the next block.
 		fn difficulty() -> Difficulty;
 	}
+
+	/// API for those chains that retarget their difficulty on the client side, see
+	/// [`difficulty::retarget`], while keeping the retargeting parameters in the runtime.
+	pub trait DifficultyAdjustmentApi {
+		/// Return the parameters used to retarget the difficulty of the next block.
+		fn retarget_config() -> RetargetConfig;
+	}
 }
@@ -99,3 +99,70 @@ pub enum TraceBlockResponse {
 	/// Successful block tracing response
 	BlockTrace(BlockTrace),
 }
+
+/// Profile of the re-execution of a single block, as returned by the `state_profileBlock` RPC.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockProfile {
+	/// Hash of the block being profiled
+	pub block_hash: String,
+	/// Parent hash
+	pub parent_hash: String,
+	/// Module targets that were recorded by the tracing subscriber.
+	pub tracing_targets: String,
+	/// Metric used to weigh the stacks of `folded`.
+	pub metric: ProfileMetric,
+	/// All distinct stacks recorded while executing the block.
+	pub frames: Vec<ProfileFrame>,
+	/// The profile in the folded stacks format, i.e. one `root;child;leaf value` line per stack
+	/// with the self value of its leaf. This can be passed to `flamegraph.pl` or `inferno` to
+	/// render a flamegraph.
+	pub folded: String,
+}
+
+/// The metric a [`BlockProfile`] is weighed by.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ProfileMetric {
+	/// Time spent in nanoseconds.
+	#[default]
+	Time,
+	/// Bytes added to the storage proof.
+	ProofSize,
+}
+
+/// What a [`ProfileFrame`] represents.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ProfileFrameKind {
+	/// A span of the client executing the block.
+	Client,
+	/// A span of a runtime function.
+	Runtime,
+	/// A call of a host function by the runtime.
+	HostFunction,
+	/// A read from storage.
+	StorageRead,
+	/// A write to storage.
+	StorageWrite,
+}
+
+/// Aggregated measurements of all occurrences of a single stack in a [`BlockProfile`].
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfileFrame {
+	/// Names of the frames from the root to this frame.
+	pub stack: Vec<String>,
+	/// What this frame represents.
+	pub kind: ProfileFrameKind,
+	/// Number of occurrences of this stack.
+	pub calls: u64,
+	/// Nanoseconds spent in this frame, excluding its children.
+	pub self_time: u64,
+	/// Nanoseconds spent in this frame, including its children.
+	pub total_time: u64,
+	/// Bytes added to the storage proof by this frame, excluding its children.
+	pub self_proof_size: u64,
+	/// Bytes added to the storage proof by this frame, including its children.
+	pub total_proof_size: u64,
+}
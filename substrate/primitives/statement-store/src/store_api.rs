@@ -16,7 +16,8 @@
 // limitations under the License.
 
 pub use crate::runtime_api::StatementSource;
-use crate::{Hash, Statement, Topic};
+use crate::{Hash, Statement, Topic, MAX_TOPICS};
+use futures::stream::BoxStream;
 
 /// Statement store error.
 #[derive(Debug, Eq, PartialEq, thiserror::Error)]
@@ -30,6 +31,9 @@ pub enum Error {
 	/// Error making runtime call.
 	#[error("Error calling into the runtime")]
 	Runtime,
+	/// The store doesn't support subscriptions.
+	#[error("Subscriptions are not supported")]
+	SubscriptionsNotSupported,
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -61,6 +65,33 @@ pub enum SubmitResult {
 /// Result type for `Error`
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// Filter on the topics of statements.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TopicFilter {
+	/// Matches all statements.
+	Any,
+	/// Matches statements which include all the given topics.
+	MatchAll(Vec<Topic>),
+	/// Matches statements which include at least one of the given topics.
+	MatchAny(Vec<Topic>),
+}
+
+impl TopicFilter {
+	/// Returns whether the topics of `statement` match the filter.
+	pub fn matches(&self, statement: &Statement) -> bool {
+		let has_topic =
+			|topic: &Topic| (0..MAX_TOPICS).any(|i| statement.topic(i).as_ref() == Some(topic));
+		match self {
+			TopicFilter::Any => true,
+			TopicFilter::MatchAll(topics) => topics.iter().all(has_topic),
+			TopicFilter::MatchAny(topics) => topics.iter().any(has_topic),
+		}
+	}
+}
+
+/// Stream of statements added to the store.
+pub type StatementStream = BoxStream<'static, Statement>;
+
 /// Statement store API.
 pub trait StatementStore: Send + Sync {
 	/// Return all statements.
@@ -87,4 +118,15 @@ pub trait StatementStore: Send + Sync {
 
 	/// Remove a statement from the store.
 	fn remove(&self, hash: &Hash) -> Result<()>;
+
+	/// Subscribe to new statements which match `filter` and whose decryption key is `dest`.
+	///
+	/// If `dest` is `None` only statements without a `DecryptionKey` field are returned. Only
+	/// statements added to the store after subscribing are returned. The stream ends if the
+	/// subscriber doesn't keep up with the new statements.
+	///
+	/// Stores not supporting subscriptions return [`Error::SubscriptionsNotSupported`].
+	fn subscribe(&self, _filter: TopicFilter, _dest: Option<[u8; 32]>) -> Result<StatementStream> {
+		Err(Error::SubscriptionsNotSupported)
+	}
 }